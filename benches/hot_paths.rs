@@ -0,0 +1,241 @@
+//! Benchmarks of the analysis and drawing work done for every frame.
+//!
+//! Run with `cargo bench`; `cargo bench --no-run` only checks that the benchmarks build.
+//!
+//! Timings depend on the machine, so compare against a baseline recorded on the same one:
+//! `cargo bench -- --save-baseline main` on the base branch, then
+//! `cargo bench -- --baseline main` on the change. Criterion prints the change against the
+//! baseline for every benchmark and flags significant regressions.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use gtk4::cairo::{Context, Format, ImageSurface};
+use rustfft::num_complex::Complex32;
+use sonic_spectra::settings::{PaletteKind, Settings};
+use sonic_spectra::visualizer::VisualizerRegistry;
+use sonic_spectra::{
+    band_bins, band_magnitudes, octave_bands, BarBatch, FrameRenderer, FrequencyMapper, Palette,
+    SmoothingState,
+};
+use std::f32::consts::TAU;
+use std::hint::black_box;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Size of the offscreen surface frames are drawn to, in pixels.
+const FRAME_SIZE: (i32, i32) = (1280, 720);
+/// Number of palette lookups per iteration, one per bar of a 8192-point FFT.
+const PALETTE_LOOKUPS: usize = 4096;
+/// Number of bars filled per iteration, about one per pixel of a full HD window.
+const FILLED_BARS: usize = 2048;
+
+/// Returns `len` samples of a chord of three sines at 44.1 kHz.
+fn chord(len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|i| {
+            let t = i as f32 / 44_100.0;
+            [220.0, 554.4, 3520.0]
+                .iter()
+                .map(|frequency| 0.3 * (TAU * frequency * t).sin())
+                .sum()
+        })
+        .collect()
+}
+
+/// Creates a renderer analyzing `fft_size` samples per frame without reading a config file.
+fn frame_renderer(fft_size: usize) -> (Arc<Settings>, FrameRenderer) {
+    let mut settings = Settings::default();
+    settings.fft.size = fft_size;
+    // Grid markers, as generated when the config file does not list them
+    settings.fft.frequencies = Some(settings.fft.generate_frequencies(15));
+    let settings = Arc::new(settings);
+    let renderer = FrameRenderer::new(
+        settings.clone(),
+        VisualizerRegistry::new(),
+        Vec::new(),
+        Duration::from_secs_f32(1.0 / 60.0),
+    );
+    (settings, renderer)
+}
+
+/// FFT, smoothing and noise subtraction of one frame of both channels.
+fn analyze(c: &mut Criterion) {
+    let mut group = c.benchmark_group("analyze");
+    for fft_size in [2048, 8192] {
+        let (_, mut renderer) = frame_renderer(fft_size);
+        let samples = chord(fft_size);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(fft_size),
+            &samples,
+            |b, samples| {
+                b.iter(|| renderer.analyze(black_box(samples), black_box(samples), Duration::ZERO))
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Grouping the bins of an 8192-point FFT into the bars of a window and into third-octave bands.
+fn aggregate(c: &mut Criterion) {
+    let (settings, mut renderer) = frame_renderer(8192);
+    let samples = chord(8192);
+    let spectrum = renderer.analyze(&samples, &samples, Duration::ZERO).left;
+    let mut group = c.benchmark_group("aggregate");
+
+    let mapper = FrequencyMapper::from_settings(&settings, 8192);
+    group.bench_function("bar_slots", |b| {
+        b.iter(|| {
+            let slots = mapper.bar_slots(black_box(0..4096), FRAME_SIZE.0 as f64 / 2.0);
+            slots
+                .iter()
+                .map(|slot| {
+                    spectrum[slot.bins.clone()]
+                        .iter()
+                        .map(|value| value.norm())
+                        .fold(0.0, f32::max)
+                })
+                .collect::<Vec<_>>()
+        })
+    });
+
+    let bands = octave_bands(3, 20.0, 20_000.0);
+    let bins = band_bins(&bands, settings.fft.sample_rate, 8192);
+    group.bench_function("third_octave_bands", |b| {
+        b.iter(|| band_magnitudes(black_box(&spectrum), &bins))
+    });
+    group.finish();
+}
+
+/// Color lookups of every bar of an 8192-point FFT, with the HSL conversion of the lightness.
+fn palette(c: &mut Criterion) {
+    let mut group = c.benchmark_group("palette");
+    for kind in [PaletteKind::Rainbow, PaletteKind::Viridis] {
+        let mut settings = Settings::default();
+        settings.visualizer.palette = Some(kind);
+        let palette = Palette::from_settings(&settings.visualizer_settings("frequency"));
+        group.bench_function(format!("{:?}", kind), |b| {
+            b.iter(|| {
+                (0..PALETTE_LOOKUPS)
+                    .map(|i| {
+                        let position = i as f32 / PALETTE_LOOKUPS as f32;
+                        palette.color_at_lightness(black_box(position), 0.3)
+                    })
+                    .fold(0.0, |sum, (r, g, b)| sum + r + g + b)
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Filling the bars of one frame with one fill per bar, and with the fills batched by color.
+fn fill(c: &mut Criterion) {
+    let (width, height) = (1920, 1080);
+    let surface = ImageSurface::create(Format::ARgb32, width, height).unwrap();
+    let cr = Context::new(&surface).unwrap();
+    let palette = Palette::from_settings(&Settings::default().visualizer_settings("frequency"));
+    let bar_width = width as f64 / FILLED_BARS as f64;
+    let bars: Vec<_> = (0..FILLED_BARS)
+        .map(|i| {
+            let level = (i as f32 * 0.37).sin().abs();
+            let (r, g, b) = palette.color_at_lightness(i as f32 / FILLED_BARS as f32, level);
+            let bar_height = level as f64 * height as f64;
+            (
+                (r, g, b, 0.8),
+                i as f64 * bar_width,
+                height as f64 - bar_height,
+                bar_height,
+            )
+        })
+        .collect();
+    let mut group = c.benchmark_group("fill");
+
+    group.bench_function("per_bar", |b| {
+        b.iter(|| {
+            for &((red, green, blue, alpha), x, y, bar_height) in black_box(&bars) {
+                cr.set_source_rgba(red as f64, green as f64, blue as f64, alpha as f64);
+                cr.rectangle(x, y, bar_width, bar_height);
+                let _ = cr.fill();
+            }
+        })
+    });
+
+    let mut batch = BarBatch::new(0.0);
+    group.bench_function("batched", |b| {
+        b.iter(|| {
+            for &(color, x, y, bar_height) in black_box(&bars) {
+                batch.add(color, x, y, bar_width, bar_height);
+            }
+            batch.fill(&cr);
+        })
+    });
+    group.finish();
+}
+
+/// Drawing one frame to an offscreen surface, without a window.
+fn render(c: &mut Criterion) {
+    let (width, height) = FRAME_SIZE;
+    let surface = ImageSurface::create(Format::ARgb32, width, height).unwrap();
+    let cr = Context::new(&surface).unwrap();
+    let mut group = c.benchmark_group("render");
+
+    // The visualizer alone, as drawn by every renderer
+    let (settings, mut renderer) = frame_renderer(2048);
+    let samples = chord(2048);
+    let spectrum = renderer.analyze(&samples, &samples, Duration::ZERO);
+    let visualizer = VisualizerRegistry::new()
+        .create("frequency", settings)
+        .unwrap();
+    let mut heights_left = SmoothingState::default();
+    let mut heights_right = SmoothingState::default();
+    group.bench_function("frequency_visualizer", |b| {
+        b.iter(|| {
+            visualizer.draw(
+                width,
+                height,
+                black_box(&spectrum.left),
+                black_box(&spectrum.right),
+                &cr,
+                &mut heights_left,
+                &mut heights_right,
+                1.0 / 60.0,
+            )
+        })
+    });
+
+    // Silence, where the holographic glow skips the bars lower than `visualizer.min_bar_height`
+    let silence = vec![Complex32::new(0.0, 0.0); 8192];
+    for (name, min_bar_height) in [("every_bar", 0.0), ("invisible_skipped", 0.5)] {
+        let mut settings = Settings::default();
+        settings.fft.size = 8192;
+        settings.fft.min_frequency = 0.0;
+        settings.fft.max_frequency = 20000.0;
+        settings.visualizer.min_bar_height = min_bar_height;
+        let visualizer = VisualizerRegistry::new()
+            .create("holographic_glow", Arc::new(settings))
+            .unwrap();
+        let mut heights_left = SmoothingState::default();
+        let mut heights_right = SmoothingState::default();
+        group.bench_function(format!("holographic_glow_silence/{}", name), |b| {
+            b.iter(|| {
+                visualizer.draw(
+                    width,
+                    height,
+                    black_box(&silence),
+                    black_box(&silence),
+                    &cr,
+                    &mut heights_left,
+                    &mut heights_right,
+                    1.0 / 60.0,
+                )
+            })
+        });
+    }
+
+    // A whole frame with the background, grid and overlays
+    group.bench_function("frame", |b| {
+        b.iter(|| renderer.render_frame(&cr, width as f64, height as f64, black_box(&spectrum)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, analyze, aggregate, palette, fill, render);
+criterion_main!(benches);
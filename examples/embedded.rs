@@ -0,0 +1,83 @@
+//! Embeds the visualizer in a window of a host application, fed by the host's own audio.
+//!
+//! Run with `cargo run --example embedded`.
+
+use gtk::prelude::*;
+use gtk4 as gtk;
+use sonic_spectra::settings::Settings;
+use sonic_spectra::{AudioSink, AudioSource, SpectrumWidget};
+use std::f32::consts::TAU;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Frames delivered per push, 10 ms at 44.1 kHz.
+const CHUNK_FRAMES: usize = 441;
+
+/// Stands in for the playback pipeline of a music player: a sine sweep on the left channel and
+/// its octave on the right.
+///
+/// # Fields
+/// - `sample_rate`: Sample rate of the generated audio, in Hz.
+struct SweepSource {
+    sample_rate: f32,
+}
+
+impl AudioSource for SweepSource {
+    fn start(
+        self: Box<Self>,
+        mut sink: AudioSink,
+        shutdown: Arc<AtomicBool>,
+    ) -> Result<(), String> {
+        // Audio is produced on a thread of its own; the GTK main thread must not block
+        thread::spawn(move || {
+            let mut phase = 0.0_f32;
+            let mut time = 0.0_f32;
+            let mut frames = Vec::with_capacity(CHUNK_FRAMES);
+            while !shutdown.load(Ordering::Relaxed) {
+                frames.clear();
+                for _ in 0..CHUNK_FRAMES {
+                    let frequency = 220.0 * 2.0_f32.powf((time / 4.0).sin() + 1.0);
+                    phase = (phase + frequency / self.sample_rate).fract();
+                    time += 1.0 / self.sample_rate;
+                    frames.push((0.5 * (TAU * phase).sin(), 0.5 * (2.0 * TAU * phase).sin()));
+                }
+                sink.push_frames(&frames);
+                thread::sleep(Duration::from_secs_f32(
+                    CHUNK_FRAMES as f32 / self.sample_rate,
+                ));
+            }
+        });
+        Ok(())
+    }
+}
+
+fn main() -> gtk::glib::ExitCode {
+    let application = gtk::Application::builder()
+        .application_id("com.sonic_spectra.embedded")
+        .build();
+
+    application.connect_activate(|application| {
+        let settings = Arc::new(Settings::new());
+        let source = SweepSource {
+            sample_rate: settings.fft.sample_rate,
+        };
+        let spectrum = SpectrumWidget::with_source(settings, source);
+
+        let content = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        content.append(&gtk::Label::new(Some("Now playing: a sine sweep")));
+        content.append(&spectrum);
+
+        let window = gtk::ApplicationWindow::builder()
+            .application(application)
+            .title("Embedded sonic_spectra")
+            .default_width(900)
+            .default_height(400)
+            .child(&content)
+            .build();
+        window.present();
+    });
+
+    application.run()
+}
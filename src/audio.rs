@@ -1,105 +1,1155 @@
-use crate::settings::Settings;
+use crate::audio_status::AudioStatus;
+use crate::fft_utils::db_to_linear;
+use crate::fifo_source::start_fifo_stream;
+#[cfg(feature = "jack")]
+use crate::jack_source::JackSource;
+use crate::recorder::Recorder;
+use crate::settings::{self, AudioSettings, FFTSettings, Settings};
+use crate::triple_buffer::{triple_buffer, TripleBufferReader, TripleBufferWriter};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use std::sync::{Arc, Mutex};
+use std::f32::consts::TAU;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex, Weak};
 use std::thread;
+use std::time::{Duration, Instant};
+
+/// Number of analysis windows of history kept in the capture buffers, so the windows completed
+/// between two frames can still be analyzed when a frame is late.
+const CAPTURE_WINDOWS: usize = 2;
+
+/// How often threads keeping a source alive check whether it was shut down.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Frames generated per push by `SineTestSource`, 10 ms at 44.1 kHz.
+const TEST_CHUNK_FRAMES: usize = 441;
+
+/// Returns the length of the capture buffers for the given FFT settings.
+pub fn capture_len(fft: &FFTSettings) -> usize {
+    CAPTURE_WINDOWS * fft.size
+}
 
 /// Structure to hold the audio data buffers for left and right channels.
+///
+/// # Fields
+/// - `left_buffer`: The latest captured samples of the left channel.
+/// - `right_buffer`: The latest captured samples of the right channel.
+/// - `received_frames`: Frames captured since the stream started, wrapping around on overflow;
+///   the difference between two reads is the number of frames captured in between.
+/// - `newest_frame_at`: When the newest frame was pushed to the `AudioSink`, for estimating the
+///   latency of the display; `None` before the first push.
+#[derive(Clone)]
 pub struct AudioData {
     pub left_buffer: Vec<f32>,
     pub right_buffer: Vec<f32>,
+    pub received_frames: usize,
+    pub newest_frame_at: Option<Instant>,
 }
 
 impl AudioData {
     /// Creates a new `AudioData` instance with buffers initialized to zero.
     ///
     /// # Arguments
-    /// - `fft_size`: Size of the FFT, which determines the buffer length for each channel.
+    /// - `len`: The buffer length for each channel, usually `capture_len`.
     ///
     /// # Returns
     /// - `AudioData` instance with zero-initialized buffers.
-    pub fn new(fft_size: usize) -> Self {
+    pub fn new(len: usize) -> Self {
         AudioData {
-            left_buffer: vec![0.0; fft_size],
-            right_buffer: vec![0.0; fft_size],
+            left_buffer: vec![0.0; len],
+            right_buffer: vec![0.0; len],
+            received_frames: 0,
+            newest_frame_at: None,
+        }
+    }
+
+    /// Appends stereo frames to the buffers, discarding the oldest samples to keep their length.
+    ///
+    /// # Arguments
+    /// - `frames`: `(left, right)` sample pairs, oldest first.
+    pub fn push_frames(&mut self, frames: &[(f32, f32)]) {
+        self.received_frames = self.received_frames.wrapping_add(frames.len());
+        let len = self.left_buffer.len();
+        let frames = &frames[frames.len().saturating_sub(len)..];
+        let keep = len - frames.len();
+
+        self.left_buffer.copy_within(len - keep.., 0);
+        self.right_buffer.copy_within(len - keep.., 0);
+        for (i, &(left, right)) in frames.iter().enumerate() {
+            self.left_buffer[keep + i] = left;
+            self.right_buffer[keep + i] = right;
+        }
+    }
+
+    /// Resets both buffers to silence.
+    pub fn clear(&mut self) {
+        self.left_buffer.fill(0.0);
+        self.right_buffer.fill(0.0);
+    }
+
+    /// Copies the buffers, frame count and timestamp of `other`, which must have the same buffer
+    /// length, without allocating.
+    pub fn copy_from(&mut self, other: &AudioData) {
+        self.left_buffer.copy_from_slice(&other.left_buffer);
+        self.right_buffer.copy_from_slice(&other.right_buffer);
+        self.received_frames = other.received_frames;
+        self.newest_frame_at = other.newest_frame_at;
+    }
+}
+
+/// Receiving end of the visualizer for audio delivered by an `AudioSource`.
+///
+/// Pushing never waits for the visualizer, so a sink may be used from real-time audio threads.
+///
+/// # Fields
+/// - `published`: Writing side of the triple buffer the visualizer reads the audio from.
+/// - `audio`: The latest samples, updated in place and copied to `published` after every push.
+/// - `status`: State of the input shown in the status line, shared with the `AudioReader`.
+/// - `mirror`: Sink receiving a copy of the audio, e.g. for `--emit-spectrum` next to the window.
+/// - `_alive`: Dropped with the sink, which `SinkWatch` observes.
+pub struct AudioSink {
+    published: TripleBufferWriter<AudioData>,
+    audio: AudioData,
+    status: Arc<Mutex<AudioStatus>>,
+    mirror: Option<Box<AudioSink>>,
+    _alive: Arc<()>,
+}
+
+impl AudioSink {
+    /// Appends stereo frames and hands the latest samples to the visualizer.
+    ///
+    /// # Arguments
+    /// - `frames`: `(left, right)` sample pairs in [-1.0, 1.0], oldest first, at
+    ///   `fft.sample_rate`.
+    pub fn push_frames(&mut self, frames: &[(f32, f32)]) {
+        let now = Instant::now();
+        self.audio.push_frames(frames);
+        self.audio.newest_frame_at = Some(now);
+        self.publish();
+        if let Some(mirror) = &mut self.mirror {
+            mirror.push_frames(frames);
+        }
+        // Skipped while the visualizer reads the status; the next push catches up
+        if let Ok(mut status) = self.status.try_lock() {
+            status.update(frames, now);
+        }
+    }
+
+    /// Returns the shared input status, for reporting the device, sample rate and errors.
+    pub fn status(&self) -> Arc<Mutex<AudioStatus>> {
+        self.status.clone()
+    }
+
+    /// Replaces the buffered samples with silence, e.g. when playback stops.
+    pub fn clear(&mut self) {
+        self.audio.clear();
+        self.publish();
+        if let Some(mirror) = &mut self.mirror {
+            mirror.clear();
+        }
+    }
+
+    /// Also delivers all audio pushed to this sink to `mirror`.
+    ///
+    /// # Arguments
+    /// - `mirror`: Sink of a second `audio_channel`, whose reader then sees the same audio.
+    pub fn with_mirror(mut self, mirror: AudioSink) -> Self {
+        self.mirror = Some(Box::new(mirror));
+        self
+    }
+
+    fn publish(&mut self) {
+        let audio = &self.audio;
+        self.published
+            .publish(|published| published.copy_from(audio));
+    }
+}
+
+/// Reading end of the visualizer for the audio pushed to an `AudioSink`.
+///
+/// # Fields
+/// - `buffers`: Reading side of the triple buffer the sink publishes the audio to.
+/// - `status`: State of the input shown in the status line, shared with the sink.
+/// - `sink`: Tells whether the sink was dropped.
+pub struct AudioReader {
+    buffers: TripleBufferReader<AudioData>,
+    status: Arc<Mutex<AudioStatus>>,
+    sink: SinkWatch,
+}
+
+impl AudioReader {
+    /// Returns the latest published audio, without waiting for the sink.
+    pub fn read(&mut self) -> &AudioData {
+        self.buffers.read()
+    }
+
+    /// Returns a copy of the current input status.
+    pub fn status(&self) -> AudioStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// Returns a watch telling whether the sink of this reader was dropped.
+    pub fn sink_watch(&self) -> SinkWatch {
+        self.sink.clone()
+    }
+}
+
+/// Tells whether the `AudioSink` of an `audio_channel` was dropped, which a source does once it
+/// stopped and released its input.
+#[derive(Clone)]
+pub struct SinkWatch(Weak<()>);
+
+impl SinkWatch {
+    /// Returns whether the sink was dropped.
+    pub fn is_dropped(&self) -> bool {
+        self.0.strong_count() == 0
+    }
+}
+
+/// A source of stereo audio for the visualizer.
+///
+/// The built-in sources capture the configured input device or FIFO, or generate a test tone;
+/// embedding applications can feed samples from their own playback pipeline instead.
+pub trait AudioSource: Send + 'static {
+    /// Starts delivering audio to `sink`.
+    ///
+    /// Called once on the GTK main thread, which must not be blocked: deliver the audio from a
+    /// thread of your own or from the callbacks of your pipeline, moving `sink` there.
+    ///
+    /// # Arguments
+    /// - `sink`: Receives the audio.
+    /// - `shutdown`: Set once the visualizer goes away; stop delivering audio and release the
+    ///   input soon after.
+    ///
+    /// # Returns
+    /// - `Ok(())` once the source is running, or a description of why it cannot start. The
+    ///   visualizer shows silence if the source fails.
+    fn start(self: Box<Self>, sink: AudioSink, shutdown: Arc<AtomicBool>) -> Result<(), String>;
+}
+
+/// Creates a sink for an `AudioSource` and the reader the visualizer takes the audio from.
+///
+/// # Arguments
+/// - `fft`: FFT settings determining the length of the buffers.
+pub fn audio_channel(fft: &FFTSettings) -> (AudioSink, AudioReader) {
+    let audio = AudioData::new(capture_len(fft));
+    let (published, buffers) = triple_buffer(audio.clone());
+    let status = Arc::new(Mutex::new(AudioStatus::default()));
+    let alive = Arc::new(());
+    let sink = SinkWatch(Arc::downgrade(&alive));
+    (
+        AudioSink {
+            published,
+            audio,
+            status: status.clone(),
+            mirror: None,
+            _alive: alive,
+        },
+        AudioReader {
+            buffers,
+            status,
+            sink,
+        },
+    )
+}
+
+/// The configured input device, FIFO, test tone or JACK client, selected through `audio.source`.
+///
+/// # Fields
+/// - `settings`: Shared application settings with the `[audio]` input configuration.
+/// - `recorder`: Receives the captured samples while a recording is in progress.
+pub struct CaptureSource {
+    settings: Arc<Settings>,
+    recorder: Arc<Recorder>,
+}
+
+impl CaptureSource {
+    /// Creates a new `CaptureSource` instance.
+    pub fn new(settings: Arc<Settings>, recorder: Arc<Recorder>) -> Self {
+        CaptureSource { settings, recorder }
+    }
+}
+
+impl AudioSource for CaptureSource {
+    fn start(self: Box<Self>, sink: AudioSink, shutdown: Arc<AtomicBool>) -> Result<(), String> {
+        match self.settings.audio.source {
+            settings::AudioSource::Device => {
+                start_audio_stream(sink, self.settings, self.recorder, shutdown)
+            }
+            settings::AudioSource::Fifo => {
+                start_fifo_stream(sink, self.settings, self.recorder, shutdown);
+                Ok(())
+            }
+            settings::AudioSource::Test => {
+                Box::new(SineTestSource::from_settings(&self.settings)).start(sink, shutdown)
+            }
+            #[cfg(feature = "jack")]
+            settings::AudioSource::Jack => {
+                Box::new(JackSource::new(self.settings, self.recorder)).start(sink, shutdown)
+            }
+            #[cfg(not(feature = "jack"))]
+            settings::AudioSource::Jack => {
+                eprintln!(
+                    "source is \"jack\", but sonic_spectra was built without the jack feature; capturing from the default input device."
+                );
+                start_audio_stream(sink, self.settings, self.recorder, shutdown)
+            }
+        }
+    }
+}
+
+/// Generates a sine tone on the left channel and its octave on the right, in real time.
+///
+/// Lets the whole pipeline run without audio hardware, e.g. during development or in tests.
+///
+/// # Fields
+/// - `frequency`: Frequency of the left channel's tone, in Hz.
+/// - `amplitude`: Peak amplitude of both tones.
+/// - `sample_rate`: Sample rate of the generated audio, in Hz.
+pub struct SineTestSource {
+    frequency: f32,
+    amplitude: f32,
+    sample_rate: f32,
+}
+
+impl SineTestSource {
+    /// Creates a new `SineTestSource` instance.
+    ///
+    /// # Arguments
+    /// - `frequency`: Frequency of the left channel's tone, in Hz; the right channel plays twice
+    ///   this frequency.
+    /// - `amplitude`: Peak amplitude of both tones, in [0.0, 1.0].
+    /// - `sample_rate`: Sample rate of the generated audio, in Hz.
+    pub fn new(frequency: f32, amplitude: f32, sample_rate: f32) -> Self {
+        SineTestSource {
+            frequency,
+            amplitude,
+            sample_rate,
+        }
+    }
+
+    /// Creates the tone configured through `audio.test_frequency` and `audio.test_amplitude`, at
+    /// `fft.sample_rate`.
+    pub fn from_settings(settings: &Settings) -> Self {
+        SineTestSource::new(
+            settings.audio.test_frequency,
+            settings.audio.test_amplitude,
+            settings.fft.sample_rate,
+        )
+    }
+}
+
+impl AudioSource for SineTestSource {
+    fn start(
+        self: Box<Self>,
+        mut sink: AudioSink,
+        shutdown: Arc<AtomicBool>,
+    ) -> Result<(), String> {
+        if self.sample_rate <= 0.0 {
+            return Err(format!("Invalid sample rate {} Hz", self.sample_rate));
+        }
+        {
+            let status = sink.status();
+            let mut status = status.lock().unwrap();
+            status.device = format!("test tone {} Hz", self.frequency);
+            status.sample_rate = self.sample_rate as u32;
+        }
+
+        thread::spawn(move || {
+            let chunk_duration =
+                Duration::from_secs_f32(TEST_CHUNK_FRAMES as f32 / self.sample_rate);
+            let mut frames = Vec::with_capacity(TEST_CHUNK_FRAMES);
+            let mut phase = 0.0_f32;
+            // Deadlines are advanced by whole chunks, so sleeping late does not slow the tone
+            let mut next_chunk = Instant::now();
+
+            while !shutdown.load(Ordering::Relaxed) {
+                frames.clear();
+                for _ in 0..TEST_CHUNK_FRAMES {
+                    frames.push((
+                        self.amplitude * (TAU * phase).sin(),
+                        self.amplitude * (2.0 * TAU * phase).sin(),
+                    ));
+                    phase = (phase + self.frequency / self.sample_rate).fract();
+                }
+                sink.push_frames(&frames);
+
+                next_chunk += chunk_duration;
+                thread::sleep(next_chunk.saturating_duration_since(Instant::now()));
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Per-sample processing applied to captured audio, with gains converted to linear factors once.
+///
+/// # Fields
+/// - `gain_left`: Linear gain of the captured left channel.
+/// - `gain_right`: Linear gain of the captured right channel.
+/// - `swap_channels`: Whether left and right are exchanged after the gains are applied.
+/// - `mono`: Whether both sides receive the average of left and right.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AudioProcessing {
+    pub gain_left: f32,
+    pub gain_right: f32,
+    pub swap_channels: bool,
+    pub mono: bool,
+}
+
+impl AudioProcessing {
+    /// Creates the processing parameters from the `[audio]` settings.
+    ///
+    /// # Arguments
+    /// - `settings`: Audio settings with gains in dB.
+    pub fn from_settings(settings: &AudioSettings) -> Self {
+        AudioProcessing {
+            gain_left: db_to_linear(settings.gain_left),
+            gain_right: db_to_linear(settings.gain_right),
+            swap_channels: settings.swap_channels,
+            mono: settings.mono,
+        }
+    }
+}
+
+/// Which captured channels are shown on the two sides of the display.
+///
+/// # Fields
+/// - `left`: Index of the channel shown on the left side.
+/// - `right`: Index of the channel shown on the right side.
+/// - `downmix`: Whether both sides show the average of all channels instead.
+/// - `average_pairs`: Whether inputs with more than two channels show the average of the
+///   even-numbered channels on the left and of the odd-numbered ones on the right instead.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChannelMapping {
+    pub left: usize,
+    pub right: usize,
+    pub downmix: bool,
+    pub average_pairs: bool,
+}
+
+impl Default for ChannelMapping {
+    fn default() -> Self {
+        ChannelMapping {
+            left: 0,
+            right: 1,
+            downmix: false,
+            average_pairs: false,
+        }
+    }
+}
+
+impl ChannelMapping {
+    /// Creates the mapping from the `[audio]` settings.
+    ///
+    /// # Arguments
+    /// - `settings`: Audio settings with the channel indices.
+    pub fn from_settings(settings: &AudioSettings) -> Self {
+        ChannelMapping {
+            left: settings.channel_left,
+            right: settings.channel_right,
+            downmix: settings.downmix,
+            average_pairs: settings.average_pairs,
+        }
+    }
+
+    /// Returns whether the pairs of an input are averaged, which needs more than two channels.
+    fn averages_pairs(&self, channels: usize) -> bool {
+        self.average_pairs && channels > 2
+    }
+
+    /// Describes which channels of an input are shown, such as `channels 0 and 1`.
+    ///
+    /// # Arguments
+    /// - `channels`: Number of channels of the input.
+    pub fn describe(&self, channels: usize) -> String {
+        let last = channels.saturating_sub(1);
+        let (left, right) = (self.left.min(last), self.right.min(last));
+        if self.downmix {
+            format!("the average of all {} channels on both sides", channels)
+        } else if self.averages_pairs(channels) {
+            let list = |first: usize| {
+                (first..channels)
+                    .step_by(2)
+                    .map(|channel| channel.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            format!(
+                "the average of channels {} on the left and {} on the right",
+                list(0),
+                list(1)
+            )
+        } else if left == right {
+            format!("channel {} on both sides", left)
+        } else {
+            format!("channels {} and {}", left, right)
+        }
+    }
+
+    /// Checks that the mapped channels exist in an input.
+    ///
+    /// # Arguments
+    /// - `channels`: Number of channels of the input.
+    ///
+    /// # Returns
+    /// - `Ok(())`, or a description of the missing channel. Missing channels are replaced by the
+    ///   last channel, so a mono input shows its only channel on both sides.
+    pub fn check(&self, channels: usize) -> Result<(), String> {
+        if self.downmix || self.averages_pairs(channels) {
+            return Ok(());
+        }
+        for (key, index) in [("channel_left", self.left), ("channel_right", self.right)] {
+            if index >= channels.max(2) {
+                return Err(format!(
+                    "audio.{} = {}, but the input only has {} channels (0 to {})",
+                    key,
+                    index,
+                    channels,
+                    channels.saturating_sub(1)
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits interleaved samples into the frames of the two display sides.
+///
+/// # Arguments
+/// - `data`: Interleaved samples, starting at a frame boundary; a trailing partial frame is
+///   ignored.
+/// - `channels`: Number of interleaved channels.
+/// - `mapping`: The channels shown on each side.
+/// - `frames`: Cleared and filled with one `(left, right)` pair per frame.
+pub fn deinterleave(
+    data: &[f32],
+    channels: usize,
+    mapping: &ChannelMapping,
+    frames: &mut Vec<(f32, f32)>,
+) {
+    frames.clear();
+    if channels == 0 {
+        return;
+    }
+    let last = channels - 1;
+    let (left, right) = (mapping.left.min(last), mapping.right.min(last));
+    let average_pairs = mapping.averages_pairs(channels);
+    // Even channels go left and odd ones right, so an odd count has one more on the left
+    let (left_count, right_count) = (channels.div_ceil(2) as f32, (channels / 2) as f32);
+    frames.extend(data.chunks_exact(channels).map(|frame| {
+        if mapping.downmix {
+            let average = frame.iter().sum::<f32>() / channels as f32;
+            (average, average)
+        } else if average_pairs {
+            let left_sum: f32 = frame.iter().step_by(2).sum();
+            let right_sum: f32 = frame.iter().skip(1).step_by(2).sum();
+            (left_sum / left_count, right_sum / right_count)
+        } else {
+            (frame[left], frame[right])
+        }
+    }));
+}
+
+/// Applies gains, channel swapping, and mono downmixing to one stereo frame.
+///
+/// # Arguments
+/// - `left`: The captured left sample.
+/// - `right`: The captured right sample.
+/// - `processing`: The processing parameters.
+///
+/// # Returns
+/// - The processed `(left, right)` samples, clipped to [-1.0, 1.0].
+///
+/// Gains are applied to the captured channels before swapping, so they compensate for the
+/// input hardware regardless of where the channel ends up on screen.
+pub fn process_frame(left: f32, right: f32, processing: &AudioProcessing) -> (f32, f32) {
+    let left = left * processing.gain_left;
+    let right = right * processing.gain_right;
+
+    let (left, right) = if processing.swap_channels {
+        (right, left)
+    } else {
+        (left, right)
+    };
+
+    let (left, right) = if processing.mono {
+        let mid = (left + right) / 2.0;
+        (mid, mid)
+    } else {
+        (left, right)
+    };
+
+    (left.clamp(-1.0, 1.0), right.clamp(-1.0, 1.0))
+}
+
+/// One-pole high-pass filter removing DC offset from a signal.
+///
+/// Implements `y[n] = x[n] - x[n-1] + R * y[n-1]`, keeping the previous input and output between
+/// calls so the filter runs continuously across capture callbacks.
+pub struct DcBlocker {
+    r: f32,
+    previous_input: f32,
+    previous_output: f32,
+}
+
+impl DcBlocker {
+    /// Creates a new `DcBlocker` instance.
+    ///
+    /// # Arguments
+    /// - `cutoff_hz`: The -3 dB cutoff frequency in Hz; `0.0` or less disables the filter.
+    /// - `sample_rate`: The sample rate of the filtered signal, in Hz.
+    pub fn new(cutoff_hz: f32, sample_rate: f32) -> Self {
+        let r = if cutoff_hz > 0.0 && sample_rate > 0.0 {
+            (-2.0 * std::f32::consts::PI * cutoff_hz / sample_rate).exp()
+        } else {
+            1.0
+        };
+
+        DcBlocker {
+            r,
+            previous_input: 0.0,
+            previous_output: 0.0,
+        }
+    }
+
+    /// Filters one sample.
+    ///
+    /// # Arguments
+    /// - `input`: The next input sample.
+    ///
+    /// # Returns
+    /// - The filtered sample, or `input` unchanged if the filter is disabled.
+    pub fn process(&mut self, input: f32) -> f32 {
+        if self.r >= 1.0 {
+            return input;
         }
+
+        let output = input - self.previous_input + self.r * self.previous_output;
+        self.previous_input = input;
+        self.previous_output = output;
+        output
+    }
+}
+
+/// A range of input configurations supported by a device, as listed by cpal.
+///
+/// # Fields
+/// - `channels`: Number of channels.
+/// - `min_sample_rate`: Lowest supported sample rate, in Hz.
+/// - `max_sample_rate`: Highest supported sample rate, in Hz.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InputConfigRange {
+    pub channels: u16,
+    pub min_sample_rate: u32,
+    pub max_sample_rate: u32,
+}
+
+/// The channel count and sample rate an input stream is opened with.
+///
+/// # Fields
+/// - `channels`: Number of channels.
+/// - `sample_rate`: Sample rate, in Hz.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InputConfig {
+    pub channels: u16,
+    pub sample_rate: u32,
+}
+
+/// Chooses the configuration an input device is opened with.
+///
+/// Stereo at the analyzed sample rate is preferred, so both sides of the display show a channel
+/// of their own without resampling; devices without it are opened with their default
+/// configuration, whatever its channel count.
+///
+/// # Arguments
+/// - `supported`: The configurations the device supports.
+/// - `default`: The default configuration of the device.
+/// - `sample_rate`: The configured `fft.sample_rate`, in Hz.
+///
+/// # Returns
+/// - The configuration to open the device with.
+pub fn choose_input_config(
+    supported: &[InputConfigRange],
+    default: InputConfig,
+    sample_rate: u32,
+) -> InputConfig {
+    let stereo = InputConfig {
+        channels: 2,
+        sample_rate,
+    };
+    if default == stereo {
+        return default;
+    }
+    let supports_stereo = supported.iter().any(|range| {
+        range.channels == 2
+            && (range.min_sample_rate..=range.max_sample_rate).contains(&sample_rate)
+    });
+    if supports_stereo {
+        stereo
+    } else {
+        default
     }
 }
 
 /// Starts an audio input stream to capture audio data for FFT processing.
 ///
 /// # Arguments
-/// - `audio_data`: A thread-safe, shared reference to `AudioData` where captured audio samples will be stored.
+/// - `sink`: Receives the captured audio after every callback.
 /// - `settings`: A shared reference to `Settings` containing FFT and audio configuration details.
-pub fn start_audio_stream(audio_data: Arc<Mutex<AudioData>>, settings: Arc<Settings>) {
-    let fft_size = settings.fft.size;
+/// - `recorder`: Receives the captured samples while a recording is in progress.
+/// - `shutdown`: Set to stop the stream and release the device.
+///
+/// # Returns
+/// - `Ok(())` once the stream is playing, or the error that prevented opening the device.
+///
+/// The capture callback never waits for the draw loop: the sink publishes a copy of its buffers,
+/// which the draw loop reads whenever it is ready.
+pub fn start_audio_stream(
+    mut sink: AudioSink,
+    settings: Arc<Settings>,
+    recorder: Arc<Recorder>,
+    shutdown: Arc<AtomicBool>,
+) -> Result<(), String> {
+    let processing = AudioProcessing::from_settings(&settings.audio);
+    let mapping = ChannelMapping::from_settings(&settings.audio);
+    let highpass_hz = settings.audio.highpass_hz;
+    let fft_sample_rate = settings.fft.sample_rate as u32;
+    // The stream cannot leave the thread it was created on, so the thread reports how opening
+    // the device went
+    let (started_tx, started_rx) = mpsc::channel();
+
+    let status = sink.status();
 
     thread::spawn(move || {
+        let fail = |error: String| {
+            status.lock().unwrap().set_error(error.clone());
+            let _ = started_tx.send(Err(error));
+        };
+
         // Initialize the CPAL host to interface with audio input devices
         let host = cpal::default_host();
         let device = match host.default_input_device() {
             Some(d) => d,
             None => {
-                eprintln!("No available input devices.");
+                fail("No available input devices.".to_string());
                 return;
             }
         };
 
         // Retrieve the device’s default input configuration
-        let config = match device.default_input_config() {
+        let default_config = match device.default_input_config() {
             Ok(c) => c,
             Err(e) => {
-                eprintln!("Failed to retrieve input configuration: {}", e);
+                fail(format!("Failed to retrieve input configuration: {}", e));
                 return;
             }
         };
+        let supported: Vec<InputConfigRange> = match device.supported_input_configs() {
+            Ok(configs) => configs
+                .map(|range| InputConfigRange {
+                    channels: range.channels(),
+                    min_sample_rate: range.min_sample_rate().0,
+                    max_sample_rate: range.max_sample_rate().0,
+                })
+                .collect(),
+            Err(e) => {
+                eprintln!(
+                    "Failed to list the input configurations: {}; using the default.",
+                    e
+                );
+                Vec::new()
+            }
+        };
+        let chosen = choose_input_config(
+            &supported,
+            InputConfig {
+                channels: default_config.channels(),
+                sample_rate: default_config.sample_rate().0,
+            },
+            fft_sample_rate,
+        );
+        let config = cpal::StreamConfig {
+            channels: chosen.channels,
+            sample_rate: cpal::SampleRate(chosen.sample_rate),
+            buffer_size: cpal::BufferSize::Default,
+        };
+
+        let channels = chosen.channels as usize; // Number of audio channels (e.g., 1 for mono, 2 for stereo)
+        if let Err(e) = mapping.check(channels) {
+            eprintln!("{}; using the last channel instead.", e);
+        }
+        let device_name = device
+            .name()
+            .unwrap_or_else(|_| "default input".to_string());
+        status!(
+            "Capturing {} channels at {} Hz from {}, showing {}.",
+            channels,
+            chosen.sample_rate,
+            device_name,
+            mapping.describe(channels)
+        );
 
-        let channels = config.channels(); // Number of audio channels (e.g., 1 for mono, 2 for stereo)
-        let config: cpal::StreamConfig = config.into(); // Convert configuration to `StreamConfig` format
+        // DC blockers keep their state across callbacks
+        let sample_rate = config.sample_rate.0 as f32;
+        {
+            let mut status = status.lock().unwrap();
+            status.device = device_name;
+            status.sample_rate = config.sample_rate.0;
+            status.channels = chosen.channels;
+        }
+        let error_status = status.clone();
+        let mut dc_blocker_left = DcBlocker::new(highpass_hz, sample_rate);
+        let mut dc_blocker_right = DcBlocker::new(highpass_hz, sample_rate);
+        recorder.set_sample_rate(config.sample_rate.0);
+        // Reused by every callback, so capturing does not allocate once it has grown
+        let mut frames = Vec::new();
 
         // Attempt to build an audio input stream with the specified settings
         let stream = match device.build_input_stream(
             &config,
             move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                let mut audio = audio_data.lock().unwrap(); // Lock the audio data for safe access
-                for i in 0..fft_size {
-                    let idx = i * channels as usize;
-                    if idx < data.len() {
-                        // Handle mono or stereo channel data appropriately
-                        if channels == 1 {
-                            audio.left_buffer[i] = data[idx];
-                            audio.right_buffer[i] = data[idx];
-                        } else if channels >= 2 {
-                            audio.left_buffer[i] = data[idx];
-                            audio.right_buffer[i] = data[idx + 1];
-                        }
-                    } else {
-                        // Fill with zeroes if data is insufficient
-                        audio.left_buffer[i] = 0.0;
-                        audio.right_buffer[i] = 0.0;
-                    }
+                deinterleave(data, channels, &mapping, &mut frames);
+                if recorder.is_recording() {
+                    // Record the whole callback buffer as captured, before any processing
+                    recorder.push(frames.iter().flat_map(|&(l, r)| [l, r]).collect());
+                }
+
+                for frame in frames.iter_mut() {
+                    *frame = process_frame(
+                        dc_blocker_left.process(frame.0),
+                        dc_blocker_right.process(frame.1),
+                        &processing,
+                    );
                 }
+                sink.push_frames(&frames);
             },
             move |err| {
                 eprintln!("Stream error: {}", err); // Error handling callback
+                error_status.lock().unwrap().set_error(err.to_string());
             },
             None,
         ) {
             Ok(s) => s,
             Err(e) => {
-                eprintln!("Failed to create stream: {}", e);
+                fail(format!("Failed to create stream: {}", e));
                 return;
             }
         };
 
         // Start the stream
         if let Err(e) = stream.play() {
-            eprintln!("Failed to start the stream: {}", e);
+            fail(format!("Failed to start the stream: {}", e));
             return;
         }
+        let _ = started_tx.send(Ok(()));
 
-        // Keep the thread alive as long as the program is running
-        loop {
-            thread::sleep(std::time::Duration::from_secs(1));
+        // Keep the stream alive until the visualizer goes away
+        while !shutdown.load(Ordering::Relaxed) {
+            thread::sleep(SHUTDOWN_POLL_INTERVAL);
         }
     });
+
+    started_rx
+        .recv()
+        .unwrap_or_else(|_| Err("The capture thread exited unexpectedly.".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustfft::num_complex::Complex32;
+
+    fn processing(
+        gain_left: f32,
+        gain_right: f32,
+        swap_channels: bool,
+        mono: bool,
+    ) -> AudioProcessing {
+        AudioProcessing {
+            gain_left,
+            gain_right,
+            swap_channels,
+            mono,
+        }
+    }
+
+    #[test]
+    fn db_gains_are_converted_to_linear_factors() {
+        let processing = AudioProcessing::from_settings(&AudioSettings {
+            gain_right: -6.0,
+            ..AudioSettings::default()
+        });
+
+        assert!((processing.gain_left - 1.0).abs() < 1e-6);
+        assert!((processing.gain_right - 0.501).abs() < 1e-3);
+    }
+
+    #[test]
+    fn gains_apply_per_channel() {
+        let (left, right) = process_frame(0.5, 0.5, &processing(1.0, 0.5, false, false));
+        assert_eq!((left, right), (0.5, 0.25));
+    }
+
+    #[test]
+    fn swap_exchanges_channels_after_gain() {
+        let (left, right) = process_frame(0.2, 0.8, &processing(2.0, 1.0, true, false));
+        assert_eq!((left, right), (0.8, 0.4));
+    }
+
+    #[test]
+    fn mono_downmixes_to_both_sides() {
+        let (left, right) = process_frame(0.2, 0.6, &processing(1.0, 1.0, false, true));
+        assert!((left - 0.4).abs() < 1e-6);
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn swap_and_mono_combine() {
+        let (left, right) = process_frame(0.2, 0.6, &processing(1.0, 0.5, true, true));
+        // Gains first (0.2, 0.3), swap (0.3, 0.2), then average
+        assert!((left - 0.25).abs() < 1e-6);
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn gain_above_unity_clips_to_full_scale() {
+        let (left, right) = process_frame(0.8, -0.9, &processing(2.0, 4.0, false, false));
+        assert_eq!((left, right), (1.0, -1.0));
+    }
+
+    #[test]
+    fn deinterleave_picks_the_mapped_channels() {
+        let data = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9];
+        let mut frames = Vec::new();
+        let mapping = ChannelMapping {
+            left: 2,
+            right: 0,
+            ..ChannelMapping::default()
+        };
+        deinterleave(&data, 4, &mapping, &mut frames);
+        // The trailing partial frame is left out
+        assert_eq!(frames, [(0.3, 0.1), (0.7, 0.5)]);
+
+        // Mono inputs show their only channel on both sides
+        deinterleave(&data[..2], 1, &ChannelMapping::default(), &mut frames);
+        assert_eq!(frames, [(0.1, 0.1), (0.2, 0.2)]);
+    }
+
+    #[test]
+    fn downmix_averages_all_channels() {
+        let mapping = ChannelMapping {
+            downmix: true,
+            ..ChannelMapping::default()
+        };
+        let mut frames = Vec::new();
+        deinterleave(
+            &[0.1, 0.2, 0.3, 0.6, 0.0, 0.0, 0.0, 0.0],
+            4,
+            &mapping,
+            &mut frames,
+        );
+        assert!((frames[0].0 - 0.3).abs() < 1e-6);
+        assert_eq!(frames[0].0, frames[0].1);
+        assert_eq!(frames[1], (0.0, 0.0));
+    }
+
+    #[test]
+    fn pairs_average_into_left_and_right() {
+        let mapping = ChannelMapping {
+            average_pairs: true,
+            ..ChannelMapping::default()
+        };
+        let mut frames = Vec::new();
+        deinterleave(&[0.1, 0.2, 0.3, 0.6], 4, &mapping, &mut frames);
+        assert!((frames[0].0 - 0.2).abs() < 1e-6);
+        assert!((frames[0].1 - 0.4).abs() < 1e-6);
+
+        // An odd channel count leaves one more channel on the left
+        deinterleave(&[0.3, 0.2, 0.6], 3, &mapping, &mut frames);
+        assert!((frames[0].0 - 0.45).abs() < 1e-6);
+        assert!((frames[0].1 - 0.2).abs() < 1e-6);
+
+        // Stereo and mono inputs keep the channel mapping
+        deinterleave(&[0.1, 0.2], 2, &mapping, &mut frames);
+        assert_eq!(frames, [(0.1, 0.2)]);
+        assert_eq!(
+            mapping.describe(5),
+            "the average of channels 0, 2, 4 on the left and 1, 3 on the right"
+        );
+        assert_eq!(mapping.describe(1), "channel 0 on both sides");
+        assert_eq!(ChannelMapping::default().describe(4), "channels 0 and 1");
+    }
+
+    #[test]
+    fn stereo_at_the_analyzed_rate_is_preferred() {
+        let range = |channels, min_sample_rate, max_sample_rate| InputConfigRange {
+            channels,
+            min_sample_rate,
+            max_sample_rate,
+        };
+        let config = |channels, sample_rate| InputConfig {
+            channels,
+            sample_rate,
+        };
+
+        // A 4-channel array mic that also offers stereo
+        let array_mic = [range(4, 16000, 48000), range(2, 16000, 48000)];
+        assert_eq!(
+            choose_input_config(&array_mic, config(4, 48000), 44100),
+            config(2, 44100)
+        );
+        // Stereo, but not at the analyzed rate
+        assert_eq!(
+            choose_input_config(&[range(2, 48000, 48000)], config(4, 48000), 44100),
+            config(4, 48000)
+        );
+        // Mono-only devices keep their default
+        assert_eq!(
+            choose_input_config(&[range(1, 8000, 96000)], config(1, 44100), 44100),
+            config(1, 44100)
+        );
+        // Listing the configurations may fail
+        assert_eq!(
+            choose_input_config(&[], config(2, 48000), 44100),
+            config(2, 48000)
+        );
+    }
+
+    #[test]
+    fn mapped_channels_must_exist() {
+        let mapping = ChannelMapping {
+            left: 6,
+            right: 7,
+            ..ChannelMapping::default()
+        };
+        assert!(mapping.check(8).is_ok());
+        assert!(mapping.check(2).is_err());
+        // The default mapping suits mono inputs
+        assert!(ChannelMapping::default().check(1).is_ok());
+    }
+
+    #[test]
+    fn pushed_frames_shift_out_the_oldest_samples() {
+        let mut audio = AudioData::new(4);
+        audio.push_frames(&[(1.0, -1.0), (2.0, -2.0)]);
+        assert_eq!(audio.left_buffer, [0.0, 0.0, 1.0, 2.0]);
+
+        audio.push_frames(&[(3.0, -3.0), (4.0, -4.0), (5.0, -5.0)]);
+        assert_eq!(audio.left_buffer, [2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(audio.right_buffer, [-2.0, -3.0, -4.0, -5.0]);
+
+        // Only the newest frames of an oversized chunk are kept
+        let frames: Vec<(f32, f32)> = (6..12).map(|n| (n as f32, 0.0)).collect();
+        audio.push_frames(&frames);
+        assert_eq!(audio.left_buffer, [8.0, 9.0, 10.0, 11.0]);
+        // Dropped frames still count as received
+        assert_eq!(audio.received_frames, 11);
+    }
+
+    #[test]
+    fn mirrors_receive_the_same_audio() {
+        let fft = FFTSettings::default();
+        let (sink, mut reader) = audio_channel(&fft);
+        let (mirror, mut mirror_reader) = audio_channel(&fft);
+        let mut sink = sink.with_mirror(mirror);
+
+        sink.push_frames(&[(0.25, -0.5), (0.75, 1.0)]);
+        for audio in [reader.read().clone(), mirror_reader.read().clone()] {
+            assert_eq!(audio.received_frames, 2);
+            assert_eq!(
+                audio.left_buffer[audio.left_buffer.len() - 2..],
+                [0.25, 0.75]
+            );
+            assert_eq!(audio.right_buffer[audio.right_buffer.len() - 1], 1.0);
+        }
+
+        sink.clear();
+        assert!(mirror_reader.read().left_buffer.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_tone_reaches_the_analysis() {
+        use crate::renderer::FrameRenderer;
+        use crate::visualizer::VisualizerRegistry;
+
+        let settings = Arc::new(Settings::default());
+        let (sink, mut reader) = audio_channel(&settings.fft);
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let source = SineTestSource::new(1000.0, 0.5, settings.fft.sample_rate);
+        Box::new(source).start(sink, shutdown.clone()).unwrap();
+
+        // The tone is generated in real time; wait for a full analysis window
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while reader.read().received_frames < settings.fft.size {
+            assert!(Instant::now() < deadline, "the test source stalled");
+            thread::sleep(Duration::from_millis(10));
+        }
+        shutdown.store(true, Ordering::Relaxed);
+
+        let audio = reader.read();
+        let mut renderer = FrameRenderer::new(
+            settings.clone(),
+            VisualizerRegistry::new(),
+            Vec::new(),
+            Duration::from_millis(30),
+        );
+        let spectrum = renderer.analyze_capture(
+            &audio.left_buffer,
+            &audio.right_buffer,
+            audio.received_frames,
+            Duration::ZERO,
+        );
+
+        let peak_frequency = |fft: &[Complex32]| {
+            let peak = (1..settings.fft.size / 2)
+                .max_by(|&a, &b| fft[a].norm().total_cmp(&fft[b].norm()))
+                .unwrap();
+            peak as f32 * settings.fft.sample_rate / settings.fft.size as f32
+        };
+        let bin_width = settings.fft.sample_rate / settings.fft.size as f32;
+        assert!((peak_frequency(&spectrum.left) - 1000.0).abs() <= bin_width);
+        // The right channel plays the octave above
+        assert!((peak_frequency(&spectrum.right) - 2000.0).abs() <= bin_width);
+    }
+
+    #[test]
+    fn dc_blocker_removes_constant_offset() {
+        let mut blocker = DcBlocker::new(5.0, 44100.0);
+        let mut output = 1.0;
+        for _ in 0..44100 {
+            output = blocker.process(0.5);
+        }
+        assert!(output.abs() < 1e-3, "output was {}", output);
+    }
+
+    #[test]
+    fn dc_blocker_passes_audible_frequencies() {
+        let sample_rate = 44100.0;
+        let mut blocker = DcBlocker::new(5.0, sample_rate);
+        let sine = |n: usize| (2.0 * std::f32::consts::PI * 1000.0 * n as f32 / sample_rate).sin();
+
+        // Let the filter settle before measuring
+        for n in 0..44100 {
+            blocker.process(sine(n));
+        }
+
+        let (mut input_power, mut output_power) = (0.0, 0.0);
+        for n in 44100..88200 {
+            let input = sine(n);
+            let output = blocker.process(input);
+            input_power += input * input;
+            output_power += output * output;
+        }
+
+        let ratio = (output_power / input_power).sqrt();
+        assert!((ratio - 1.0).abs() < 0.01, "gain was {}", ratio);
+    }
+
+    #[test]
+    fn dc_blocker_with_zero_cutoff_is_disabled() {
+        let mut blocker = DcBlocker::new(0.0, 44100.0);
+        for input in [0.5, 0.5, -0.25, 1.0] {
+            assert_eq!(blocker.process(input), input);
+        }
+    }
 }
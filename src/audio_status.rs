@@ -0,0 +1,193 @@
+use crate::settings::UiSettings;
+use crate::text::{draw_text, measure, TextStyle};
+use gtk::cairo::Context;
+use gtk4 as gtk;
+use std::time::{Duration, Instant};
+
+/// Peak level above which a sample counts as signal, -90 dBFS.
+const SIGNAL_THRESHOLD: f32 = 3.162_277_7e-5;
+/// Time without signal after which the input counts as silent.
+const SILENCE_AFTER: Duration = Duration::from_secs(3);
+/// Font size of the status line, in pixels.
+const FONT_SIZE: f64 = 12.0;
+/// Radius of the activity dot, in pixels.
+const DOT_RADIUS: f64 = 4.0;
+/// Distance of the status line from the left edge, in pixels.
+const MARGIN: f64 = 12.0;
+/// Distance of the bottom of the status line from the bottom edge, above the status messages.
+const BOTTOM_OFFSET: f64 = 32.0;
+
+/// Whether samples are flowing, derived from an `AudioStatus`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Activity {
+    /// Samples above -90 dBFS arrived within the last few seconds.
+    Signal,
+    /// The input has been silent, or nothing has been captured yet.
+    Silent,
+    /// The stream reported an error since the last signal.
+    Error,
+}
+
+/// State of the audio input, published by the audio thread and shown in the status line.
+///
+/// # Fields
+/// - `device`: Name of the captured device or stream; empty for sources that do not report one.
+/// - `sample_rate`: Sample rate of the input in Hz, or `0` if unknown.
+/// - `channels`: Number of channels the input was opened with, or `0` if unknown.
+/// - `error`: The last error reported by the stream, cleared once samples flow again.
+/// - `last_signal`: Time the last block with a sample above -90 dBFS was pushed.
+#[derive(Clone, Debug, Default)]
+pub struct AudioStatus {
+    pub device: String,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub error: Option<String>,
+    pub last_signal: Option<Instant>,
+}
+
+impl AudioStatus {
+    /// Records a block of pushed frames.
+    ///
+    /// # Arguments
+    /// - `frames`: The pushed `(left, right)` samples.
+    /// - `now`: Time the block was pushed.
+    pub fn update(&mut self, frames: &[(f32, f32)], now: Instant) {
+        let signal = frames
+            .iter()
+            .any(|&(left, right)| left.abs().max(right.abs()) > SIGNAL_THRESHOLD);
+        if signal {
+            self.last_signal = Some(now);
+            self.error = None;
+        }
+    }
+
+    /// Records an error reported by the stream.
+    pub fn set_error(&mut self, error: impl Into<String>) {
+        self.error = Some(error.into());
+    }
+
+    /// Returns the activity of the input at `now`.
+    pub fn activity(&self, now: Instant) -> Activity {
+        match self.last_signal {
+            _ if self.error.is_some() => Activity::Error,
+            Some(last_signal) if now.saturating_duration_since(last_signal) < SILENCE_AFTER => {
+                Activity::Signal
+            }
+            _ => Activity::Silent,
+        }
+    }
+
+    /// Returns the text of the status line, such as `default · 48 kHz · no signal`; inputs
+    /// other than stereo add their channel count, such as `4 ch`.
+    pub fn text(&self, now: Instant) -> String {
+        let mut parts = vec![if self.device.is_empty() {
+            "audio input".to_string()
+        } else {
+            self.device.clone()
+        }];
+        if self.sample_rate > 0 {
+            parts.push(format!("{} kHz", self.sample_rate as f32 / 1000.0));
+        }
+        match self.channels {
+            0 | 2 => {}
+            1 => parts.push("mono".to_string()),
+            channels => parts.push(format!("{} ch", channels)),
+        }
+        match (&self.error, self.activity(now)) {
+            (Some(error), _) => parts.push(error.clone()),
+            (None, Activity::Silent) => parts.push("no signal".to_string()),
+            _ => {}
+        }
+        parts.join(" · ")
+    }
+
+    /// Draws the activity dot and the status text in the bottom-left corner.
+    ///
+    /// # Arguments
+    /// - `cr`: The Cairo `Context` used for drawing.
+    /// - `height`: The height of the drawing area.
+    /// - `now`: The time of the frame.
+    /// - `ui`: The `[ui]` settings providing the font.
+    pub fn draw(&self, cr: &Context, height: f64, now: Instant, ui: &UiSettings) {
+        let (r, g, b) = match self.activity(now) {
+            Activity::Signal => (0.2, 0.8, 0.3),
+            Activity::Silent => (0.6, 0.6, 0.6),
+            Activity::Error => (0.9, 0.1, 0.1),
+        };
+        let text = self.text(now);
+        let style = TextStyle {
+            color: (1.0, 1.0, 1.0, 0.6),
+            ..TextStyle::ui(ui, FONT_SIZE)
+        };
+        let (_, text_height) = measure(&text, &style);
+        let top = height - BOTTOM_OFFSET - text_height;
+        cr.set_source_rgba(r, g, b, 0.9);
+        cr.arc(
+            MARGIN + DOT_RADIUS,
+            top + text_height / 2.0,
+            DOT_RADIUS,
+            0.0,
+            2.0 * std::f64::consts::PI,
+        );
+        let _ = cr.fill();
+
+        draw_text(cr, &text, MARGIN + 3.0 * DOT_RADIUS, top, &style);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn activity_follows_the_pushed_samples() {
+        let start = Instant::now();
+        let mut status = AudioStatus::default();
+        assert_eq!(status.activity(start), Activity::Silent);
+
+        status.update(&[(0.0, 0.0), (0.1, 0.0)], start);
+        assert_eq!(
+            status.activity(start + Duration::from_secs(1)),
+            Activity::Signal
+        );
+
+        // Samples below -90 dBFS count as silence
+        status.update(&[(1e-5, -1e-5)], start + Duration::from_secs(1));
+        assert_eq!(status.last_signal, Some(start));
+        assert_eq!(status.activity(start + SILENCE_AFTER), Activity::Silent);
+    }
+
+    #[test]
+    fn errors_last_until_samples_flow_again() {
+        let start = Instant::now();
+        let mut status = AudioStatus {
+            device: "default".to_string(),
+            sample_rate: 48000,
+            ..AudioStatus::default()
+        };
+        status.update(&[(0.5, 0.5)], start);
+        status.set_error("device disconnected");
+        assert_eq!(status.activity(start), Activity::Error);
+        assert_eq!(status.text(start), "default · 48 kHz · device disconnected");
+
+        status.update(&[(0.0, 0.0)], start);
+        assert_eq!(status.activity(start), Activity::Error);
+        status.update(&[(0.0, 0.2)], start);
+        assert_eq!(status.activity(start), Activity::Signal);
+        assert_eq!(status.text(start), "default · 48 kHz");
+    }
+
+    #[test]
+    fn channel_counts_other_than_stereo_are_shown() {
+        let start = Instant::now();
+        let status = |channels| AudioStatus {
+            device: "mic".to_string(),
+            channels,
+            last_signal: Some(start),
+            ..AudioStatus::default()
+        };
+        assert_eq!(status(2).text(start), "mic");
+        assert_eq!(status(1).text(start), "mic · mono");
+        assert_eq!(status(4).text(start), "mic · 4 ch");
+    }
+}
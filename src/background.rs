@@ -0,0 +1,222 @@
+use crate::settings::{BackgroundKind, BackgroundSettings, ImageMode};
+use gtk::cairo::{Context, Extend, Format, ImageSurface, LinearGradient, SurfacePattern};
+use gtk::gdk::prelude::GdkCairoContextExt;
+use gtk::gdk_pixbuf::Pixbuf;
+use gtk4 as gtk;
+
+/// The background drawn behind the grid and the visualizer, configured by `[background]`.
+///
+/// A background image is decoded once. When scaled, the scaled copy is kept until the drawing
+/// area is resized, so frames only copy it.
+///
+/// # Fields
+/// - `settings`: The background configuration.
+/// - `image`: The decoded image, when `kind` is `"image"` and it could be loaded.
+/// - `scale`: Device pixels per logical pixel of the drawing area.
+/// - `scaled`: The image scaled to the last drawn size at device resolution, for
+///   `image_mode = "scale"`.
+pub struct Background {
+    settings: BackgroundSettings,
+    image: Option<ImageSurface>,
+    scale: f64,
+    scaled: Option<ImageSurface>,
+}
+
+impl Background {
+    /// Creates a new `Background` instance, loading the image if one is configured.
+    ///
+    /// # Arguments
+    /// - `settings`: The `[background]` settings.
+    ///
+    /// An image that cannot be loaded is replaced by a solid black background.
+    pub fn new(settings: &BackgroundSettings) -> Self {
+        let mut settings = settings.clone();
+        let mut image = None;
+        if settings.kind == BackgroundKind::Image {
+            match load_image(&settings.image) {
+                Ok(surface) => image = Some(surface),
+                Err(e) => {
+                    eprintln!(
+                        "Failed to load the background image {}, using black instead: {}",
+                        settings.image, e
+                    );
+                    settings.kind = BackgroundKind::Solid;
+                    settings.color = BackgroundSettings::default().color;
+                }
+            }
+        }
+
+        Background {
+            settings,
+            image,
+            scale: 1.0,
+            scaled: None,
+        }
+    }
+
+    /// Sets the device pixels per logical pixel, scaling the image again when it changes.
+    pub fn set_scale(&mut self, scale: f64) {
+        if scale != self.scale {
+            self.scale = scale;
+            self.scaled = None;
+        }
+    }
+
+    /// Releases the scaled image, which is scaled again by the next frame.
+    pub fn clear_scaled(&mut self) {
+        self.scaled = None;
+    }
+
+    /// Draws the background over the whole drawing area.
+    ///
+    /// # Arguments
+    /// - `cr`: The Cairo `Context` to draw to.
+    /// - `width`: The width of the drawing area.
+    /// - `height`: The height of the drawing area.
+    ///
+    /// Colors with an alpha below 1.0 let the stylesheet background show through.
+    pub fn draw(&mut self, cr: &Context, width: f64, height: f64) {
+        let _ = cr.save();
+        match self.settings.kind {
+            BackgroundKind::Css => {}
+            BackgroundKind::Solid => {
+                let (r, g, b, a) = self.settings.color.to_rgba(1.0);
+                cr.set_source_rgba(r, g, b, a);
+                let _ = cr.paint();
+            }
+            BackgroundKind::Gradient => {
+                let gradient = LinearGradient::new(0.0, 0.0, 0.0, height);
+                for (offset, color) in [0.0, 1.0].into_iter().zip(&self.settings.gradient) {
+                    let (r, g, b, a) = color.to_rgba(1.0);
+                    gradient.add_color_stop_rgba(offset, r, g, b, a);
+                }
+                let _ = cr.set_source(&gradient);
+                let _ = cr.paint();
+            }
+            BackgroundKind::Image => self.draw_image(cr, width, height),
+        }
+        let _ = cr.restore();
+    }
+
+    /// Draws the image scaled to cover the drawing area, or tiled.
+    fn draw_image(&mut self, cr: &Context, width: f64, height: f64) {
+        let Some(image) = &self.image else {
+            return;
+        };
+        match self.settings.image_mode {
+            ImageMode::Tile => {
+                let pattern = SurfacePattern::create(image);
+                pattern.set_extend(Extend::Repeat);
+                let _ = cr.set_source(&pattern);
+                let _ = cr.paint();
+            }
+            ImageMode::Scale => {
+                let scale = self.scale;
+                let (width, height) = (
+                    (width * scale).ceil().max(1.0) as i32,
+                    (height * scale).ceil().max(1.0) as i32,
+                );
+                let current = matches!(
+                    &self.scaled,
+                    Some(scaled) if scaled.width() == width && scaled.height() == height
+                );
+                if !current {
+                    self.scaled = scale_image(image, width, height, scale)
+                        .map_err(|e| eprintln!("Failed to scale the background image: {}", e))
+                        .ok();
+                }
+                if let Some(scaled) = &self.scaled {
+                    let _ = cr.set_source_surface(scaled, 0.0, 0.0);
+                    let _ = cr.paint();
+                }
+            }
+        }
+    }
+}
+
+/// Decodes a PNG or JPEG file into an image surface.
+fn load_image(path: &str) -> Result<ImageSurface, String> {
+    if path.is_empty() {
+        return Err("background.image is not set".to_string());
+    }
+    let pixbuf = Pixbuf::from_file(path).map_err(|e| e.to_string())?;
+    let surface = ImageSurface::create(Format::ARgb32, pixbuf.width(), pixbuf.height())
+        .map_err(|e| e.to_string())?;
+    let cr = Context::new(&surface).map_err(|e| e.to_string())?;
+    cr.set_source_pixbuf(&pixbuf, 0.0, 0.0);
+    cr.paint().map_err(|e| e.to_string())?;
+    Ok(surface)
+}
+
+/// Scales an image to cover a `width` by `height` device pixel surface, centered and cropped.
+///
+/// The surface gets a device scale of `device_scale`, so it is painted in logical pixels while
+/// the image is sampled once per device pixel.
+fn scale_image(
+    image: &ImageSurface,
+    width: i32,
+    height: i32,
+    device_scale: f64,
+) -> Result<ImageSurface, String> {
+    let surface = ImageSurface::create(Format::ARgb32, width, height).map_err(|e| e.to_string())?;
+    surface.set_device_scale(device_scale, device_scale);
+    let cr = Context::new(&surface).map_err(|e| e.to_string())?;
+    let (scale, x, y) = cover(
+        (image.width() as f64, image.height() as f64),
+        (width as f64 / device_scale, height as f64 / device_scale),
+    );
+    cr.translate(x, y);
+    cr.scale(scale, scale);
+    cr.set_source_surface(image, 0.0, 0.0)
+        .map_err(|e| e.to_string())?;
+    cr.paint().map_err(|e| e.to_string())?;
+    Ok(surface)
+}
+
+/// Computes how an image is scaled and placed to cover an area, keeping its aspect ratio.
+///
+/// # Arguments
+/// - `image`: Width and height of the image.
+/// - `area`: Width and height of the covered area.
+///
+/// # Returns
+/// - The scale factor and the offset of the scaled image's top-left corner, which centers the
+///   image so the overflow is cropped evenly on both sides.
+fn cover(image: (f64, f64), area: (f64, f64)) -> (f64, f64, f64) {
+    if image.0 <= 0.0 || image.1 <= 0.0 {
+        return (1.0, 0.0, 0.0);
+    }
+    let scale = (area.0 / image.0).max(area.1 / image.1);
+    (
+        scale,
+        (area.0 - image.0 * scale) / 2.0,
+        (area.1 - image.1 * scale) / 2.0,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cover_fills_the_area_and_crops_evenly() {
+        // A wide image fills the height and overflows left and right
+        assert_eq!(cover((400.0, 100.0), (200.0, 200.0)), (2.0, -300.0, 0.0));
+        // A tall image fills the width and overflows at the top and bottom
+        assert_eq!(cover((100.0, 400.0), (200.0, 200.0)), (2.0, 0.0, -300.0));
+        assert_eq!(cover((0.0, 10.0), (200.0, 200.0)), (1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn missing_images_fall_back_to_black() {
+        let settings = BackgroundSettings {
+            kind: BackgroundKind::Image,
+            image: "/nonexistent/background.png".to_string(),
+            ..BackgroundSettings::default()
+        };
+        let background = Background::new(&settings);
+        assert_eq!(background.settings.kind, BackgroundKind::Solid);
+        assert_eq!(background.settings.color.to_rgba(1.0), (0.0, 0.0, 0.0, 1.0));
+        assert!(background.image.is_none());
+    }
+}
@@ -0,0 +1,134 @@
+use crate::fft_utils::{band_energy, interpolate};
+use crate::settings::{PulseStyle, Settings};
+use gtk::cairo::{Context, RadialGradient};
+use gtk4 as gtk;
+use rustfft::num_complex::Complex32;
+use std::sync::Arc;
+
+/// Smoothing factor applied when the bass level rises, so hits register immediately.
+const ATTACK_FACTOR: f32 = 0.6;
+/// Smoothing factor applied when the bass level falls, so the pulse fades out gently.
+const RELEASE_FACTOR: f32 = 0.08;
+/// Beat strength, in standard deviations, that produces a full-strength flash.
+const FULL_FLASH_STRENGTH: f32 = 6.0;
+/// Distance of the level from its target below which the pulse counts as settled.
+const SETTLED_LEVEL: f32 = 1e-3;
+
+/// A background effect that pulses with the energy of a low frequency band.
+///
+/// # Fields
+/// - `settings`: Shared settings containing the `[effects]` configuration.
+/// - `level`: The smoothed bass level from the previous frame, in the range [0.0, 1.0].
+/// - `fading`: Whether `level` was still moving toward its target in the previous frame.
+pub struct BackgroundPulse {
+    settings: Arc<Settings>,
+    level: f32,
+    fading: bool,
+}
+
+impl BackgroundPulse {
+    /// Creates a new `BackgroundPulse` instance.
+    ///
+    /// # Arguments
+    /// - `settings`: Shared settings containing the `[effects]` configuration.
+    pub fn new(settings: Arc<Settings>) -> Self {
+        BackgroundPulse {
+            settings,
+            level: 0.0,
+            fading: false,
+        }
+    }
+
+    /// Returns whether the pulse was still rising or fading out in the previous frame.
+    pub fn is_fading(&self) -> bool {
+        self.fading
+    }
+
+    /// Raises the pulse level in response to a detected beat.
+    ///
+    /// # Arguments
+    /// - `strength`: Strength of the beat as reported by the beat detector.
+    ///
+    /// Has no effect unless `beat.flash` is enabled.
+    pub fn kick(&mut self, strength: f32) {
+        if self.settings.beat.flash {
+            let level = (strength / FULL_FLASH_STRENGTH).clamp(0.0, 1.0);
+            self.level = self.level.max(level);
+        }
+    }
+
+    /// Updates the bass level from the current spectra and draws the pulse.
+    ///
+    /// # Arguments
+    /// - `cr`: The Cairo `Context` used for drawing.
+    /// - `width`: The width of the drawing area.
+    /// - `height`: The height of the drawing area.
+    /// - `fft_left`: FFT data for the left audio channel.
+    /// - `fft_right`: FFT data for the right audio channel.
+    ///
+    /// Nothing is drawn when both `effects.bass_pulse` and `beat.flash` are disabled. The pulse
+    /// opacity never exceeds `effects.max_alpha`, keeping the grid and bars drawn on top readable.
+    pub fn draw(
+        &mut self,
+        cr: &Context,
+        width: f64,
+        height: f64,
+        fft_left: &[Complex32],
+        fft_right: &[Complex32],
+    ) {
+        let effects = &self.settings.effects;
+        if !effects.bass_pulse && !self.settings.beat.flash {
+            self.fading = false;
+            return;
+        }
+
+        let energy = if effects.bass_pulse {
+            let (sample_rate, zero_pad_factor) = (
+                self.settings.fft.sample_rate,
+                self.settings.fft.zero_pad_factor,
+            );
+            let [low, high] = effects.band;
+            (band_energy(fft_left, sample_rate, zero_pad_factor, low, high)
+                + band_energy(fft_right, sample_rate, zero_pad_factor, low, high))
+                / 2.0
+        } else {
+            0.0
+        };
+
+        // Rise quickly on bass hits and decay slowly afterwards
+        let target = (energy * effects.intensity).clamp(0.0, 1.0);
+        let factor = if target > self.level {
+            ATTACK_FACTOR
+        } else {
+            RELEASE_FACTOR
+        };
+        self.level = interpolate(self.level, target, factor);
+        self.fading = (target - self.level).abs() > SETTLED_LEVEL;
+
+        let (r, g, b, max_alpha) = effects.color.to_rgba(effects.max_alpha);
+        let alpha = (self.level as f64 * max_alpha).clamp(0.0, max_alpha);
+        if alpha <= 0.0 {
+            return;
+        }
+
+        match effects.pulse_style {
+            PulseStyle::Glow => {
+                // Glow rises from the bottom center, behind the bars
+                let radius = height.max(width / 2.0) * (0.5 + self.level as f64);
+                let gradient =
+                    RadialGradient::new(width / 2.0, height, 0.0, width / 2.0, height, radius);
+                gradient.add_color_stop_rgba(0.0, r, g, b, alpha);
+                gradient.add_color_stop_rgba(1.0, r, g, b, 0.0);
+
+                let _ = cr.set_source(&gradient);
+                cr.rectangle(0.0, 0.0, width, height);
+                let _ = cr.fill();
+            }
+            PulseStyle::Flash => {
+                cr.set_source_rgba(r, g, b, alpha);
+                cr.rectangle(0.0, 0.0, width, height);
+                let _ = cr.fill();
+            }
+        }
+    }
+}
@@ -0,0 +1,427 @@
+use crate::settings::BarDirection;
+use gtk::cairo::{Context, Extend, LinearGradient, Matrix};
+use gtk4 as gtk;
+use std::collections::HashMap;
+use std::f64::consts::{FRAC_PI_2, PI};
+use std::sync::{Mutex, MutexGuard};
+
+/// Number of levels each color channel is quantized to, so bars of nearly equal color share a
+/// fill.
+const COLOR_LEVELS: f32 = 64.0;
+
+/// Number of buckets the bar heights are quantized to for `visualizer.bar_gradient`, so bars of
+/// nearly equal height share a gradient.
+const HEIGHT_BUCKETS: f64 = 32.0;
+
+/// Brightness and opacity of the base of a bar as high as the drawing area, as a fraction of
+/// its tip color; shorter bars fade less, so quiet bars stay visible.
+const BASE_SHADE: f64 = 0.25;
+
+/// Gradients by quantized color and height bucket.
+type GradientMap = HashMap<([u8; 4], u8), LinearGradient>;
+
+/// The gradients of `visualizer.bar_gradient` one visualizer fills its bars with, kept per
+/// quantized color and height bucket across frames.
+///
+/// Each visualizer owns its cache, so visualizers with different palettes, such as layers or
+/// the visualizers of several windows, keep their gradients apart, and the gradients are freed
+/// with the visualizer.
+///
+/// # Fields
+/// - `gradients`: The gradients drawn so far.
+#[derive(Default)]
+pub struct GradientCache {
+    gradients: Mutex<GradientMap>,
+}
+
+// Cairo patterns have an atomic reference count and no ties to the thread that created them,
+// and the mutex lets one thread at a time use the gradients
+unsafe impl Send for GradientCache {}
+unsafe impl Sync for GradientCache {}
+
+impl GradientCache {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        GradientCache::default()
+    }
+
+    /// Drops the gradients of this cache, for when the colors of the bars change, so gradients
+    /// of colors no longer drawn are not kept around.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Locks the gradients for a batch of bars.
+    fn lock(&self) -> MutexGuard<'_, GradientMap> {
+        self.gradients.lock().unwrap()
+    }
+
+    /// Returns the number of gradients kept by this cache.
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.lock().len()
+    }
+}
+
+/// Where the bars of a batch filled with gradients grow from, and the cache of the gradients.
+///
+/// # Fields
+/// - `direction`: Where the bars grow from.
+/// - `plot_height`: Height of the drawing area, which the bar heights are bucketed against.
+/// - `cache`: The cache the gradients are kept in.
+#[derive(Clone, Copy)]
+struct GradientShape<'a> {
+    direction: BarDirection,
+    plot_height: f64,
+    cache: &'a GradientCache,
+}
+
+/// Bars grouped by quantized color, drawn with one path and one `fill()` per color instead of
+/// one per bar.
+///
+/// # Fields
+/// - `bucket_index`: Position of each color and height bucket in `buckets`.
+/// - `buckets`: Colors and height buckets in the order they were first added, with the
+///   rectangles of each; the height bucket is 0 without gradients.
+/// - `radius`: Radius of the rounded corners of the bars, 0 for square corners.
+/// - `gradient`: How the bars grow, if they are filled with vertical gradients instead of flat
+///   colors.
+#[derive(Default)]
+pub struct BarBatch<'a> {
+    bucket_index: HashMap<([u8; 4], u8), usize>,
+    buckets: Vec<(([u8; 4], u8), Vec<[f64; 4]>)>,
+    radius: f64,
+    gradient: Option<GradientShape<'a>>,
+}
+
+impl<'a> BarBatch<'a> {
+    /// Creates a new, empty `BarBatch` instance.
+    ///
+    /// # Arguments
+    /// - `radius`: Radius of the rounded corners of the bars, as for `rounded_rect`.
+    pub fn new(radius: f64) -> Self {
+        BarBatch {
+            radius,
+            ..BarBatch::default()
+        }
+    }
+
+    /// Creates a new, empty `BarBatch` filling each bar with a vertical gradient from a darker,
+    /// more transparent shade at its base to its color at the tip, as set by
+    /// `visualizer.bar_gradient`.
+    ///
+    /// Bars are grouped by quantized color and height, and the gradient of each group is kept
+    /// in `cache` for the next frames.
+    ///
+    /// # Arguments
+    /// - `radius`: Radius of the rounded corners of the bars, as for `rounded_rect`.
+    /// - `direction`: Where the bars grow from, which sets where their bases are.
+    /// - `plot_height`: Height of the drawing area.
+    /// - `cache`: The gradients of the visualizer drawing the bars.
+    pub fn with_gradient(
+        radius: f64,
+        direction: BarDirection,
+        plot_height: f64,
+        cache: &'a GradientCache,
+    ) -> Self {
+        BarBatch {
+            radius,
+            gradient: Some(GradientShape {
+                direction,
+                plot_height,
+                cache,
+            }),
+            ..BarBatch::default()
+        }
+    }
+
+    /// Adds a bar; bars without height or opacity are skipped.
+    ///
+    /// # Arguments
+    /// - `color`: RGBA color of the bar, each component in [0.0, 1.0].
+    /// - `x`, `y`: Top-left corner of the bar.
+    /// - `width`, `height`: Size of the bar.
+    pub fn add(&mut self, color: (f32, f32, f32, f32), x: f64, y: f64, width: f64, height: f64) {
+        if height <= 0.0 || width <= 0.0 {
+            return;
+        }
+        let color_key = quantize(color);
+        if color_key[3] == 0 {
+            return;
+        }
+        let key = match self.gradient {
+            Some(shape) => (color_key, height_bucket(height, shape.plot_height)),
+            None => (color_key, 0),
+        };
+
+        let index = *self.bucket_index.entry(key).or_insert_with(|| {
+            self.buckets.push((key, Vec::new()));
+            self.buckets.len() - 1
+        });
+        self.buckets[index].1.push([x, y, width, height]);
+    }
+
+    /// Fills every added bar and empties the batch.
+    ///
+    /// # Arguments
+    /// - `cr`: The Cairo `Context` used for drawing.
+    pub fn fill(&mut self, cr: &Context) {
+        // The gradients are locked once for the whole batch
+        let mut gradients = self.gradient.map(|shape| (shape, shape.cache.lock()));
+        for ((color_key, height_bucket), rectangles) in self.buckets.drain(..) {
+            match &mut gradients {
+                Some((shape, gradients)) => {
+                    let Some(&[_, y, _, height]) = rectangles.first() else {
+                        continue;
+                    };
+                    let key = (color_key, height_bucket);
+                    set_gradient_source(cr, *shape, gradients, key, y, height);
+                }
+                None => {
+                    let (r, g, b, a) = dequantize(color_key);
+                    cr.set_source_rgba(r, g, b, a);
+                }
+            }
+            for [x, y, width, height] in rectangles {
+                rounded_rect(cr, x, y, width, height, self.radius);
+            }
+            let _ = cr.fill();
+        }
+        self.bucket_index.clear();
+    }
+
+    /// Returns the number of fills `fill` would issue.
+    #[cfg(test)]
+    fn bucket_count(&self) -> usize {
+        self.buckets.len()
+    }
+}
+
+/// Returns the bucket of a bar height, from 1 for the lowest bars to `HEIGHT_BUCKETS` for bars
+/// as high as the drawing area.
+fn height_bucket(height: f64, plot_height: f64) -> u8 {
+    let fraction = if plot_height > 0.0 {
+        height / plot_height
+    } else {
+        1.0
+    };
+    (fraction * HEIGHT_BUCKETS)
+        .ceil()
+        .clamp(1.0, HEIGHT_BUCKETS) as u8
+}
+
+/// Sets the source of `cr` to the gradient of a group of bars, running from their base to the
+/// top of their height bucket.
+///
+/// # Arguments
+/// - `cr`: The Cairo `Context` the bars are filled on.
+/// - `shape`: Where the bars grow from.
+/// - `gradients`: The locked gradients of the cache of `shape`, which a missing gradient is
+///   added to.
+/// - `(color_key, height_bucket)`: Quantized color of the tips of the bars, and their height
+///   bucket, as returned by `height_bucket`.
+/// - `y`, `height`: Top edge and height of one of the bars, which locate their base.
+fn set_gradient_source(
+    cr: &Context,
+    shape: GradientShape,
+    gradients: &mut GradientMap,
+    (color_key, height_bucket): ([u8; 4], u8),
+    y: f64,
+    height: f64,
+) {
+    let length = f64::from(height_bucket) / HEIGHT_BUCKETS * shape.plot_height;
+    // The gradient runs from 0 at the base to 1 at the tip; centered bars grow both ways from
+    // their middle, so their gradient is reflected there
+    let (base, towards_tip, span, extend) = match shape.direction {
+        BarDirection::Up => (y + height, -1.0, length, Extend::Pad),
+        BarDirection::Down => (y, 1.0, length, Extend::Pad),
+        BarDirection::Center => (y + height / 2.0, -1.0, length / 2.0, Extend::Reflect),
+    };
+    if span.is_nan() || span <= 0.0 {
+        let (r, g, b, a) = dequantize(color_key);
+        cr.set_source_rgba(r, g, b, a);
+        return;
+    }
+
+    let gradient = gradients
+        .entry((color_key, height_bucket))
+        .or_insert_with(|| new_gradient(color_key, height_bucket));
+    gradient.set_extend(extend);
+    gradient.set_matrix(Matrix::new(
+        1.0,
+        0.0,
+        0.0,
+        towards_tip / span,
+        0.0,
+        -towards_tip * base / span,
+    ));
+    let _ = cr.set_source(&*gradient);
+}
+
+/// Creates the gradient of bars of a color and height bucket, running from their base at 0 to
+/// their tip at 1 along the y axis.
+fn new_gradient(color_key: [u8; 4], height_bucket: u8) -> LinearGradient {
+    let (r, g, b, a) = dequantize(color_key);
+    let shade = 1.0 - (1.0 - BASE_SHADE) * f64::from(height_bucket) / HEIGHT_BUCKETS;
+    let gradient = LinearGradient::new(0.0, 0.0, 0.0, 1.0);
+    gradient.add_color_stop_rgba(0.0, r * shade, g * shade, b * shade, a * shade);
+    gradient.add_color_stop_rgba(1.0, r, g, b, a);
+    gradient
+}
+
+/// Adds a rectangle with rounded corners to the current path.
+///
+/// # Arguments
+/// - `cr`: The Cairo `Context` the path is added to.
+/// - `x`, `y`: Top-left corner of the rectangle.
+/// - `width`, `height`: Size of the rectangle.
+/// - `radius`: Radius of the corners. It is clamped to half the smaller side, so bars shorter or
+///   narrower than the corners end in a half circle; 0 adds a plain rectangle.
+pub fn rounded_rect(cr: &Context, x: f64, y: f64, width: f64, height: f64, radius: f64) {
+    let radius = radius.min(width / 2.0).min(height / 2.0);
+    if radius.is_nan() || radius <= 0.0 {
+        cr.rectangle(x, y, width, height);
+        return;
+    }
+
+    cr.new_sub_path();
+    cr.arc(x + width - radius, y + radius, radius, -FRAC_PI_2, 0.0);
+    cr.arc(
+        x + width - radius,
+        y + height - radius,
+        radius,
+        0.0,
+        FRAC_PI_2,
+    );
+    cr.arc(x + radius, y + height - radius, radius, FRAC_PI_2, PI);
+    cr.arc(x + radius, y + radius, radius, PI, 3.0 * FRAC_PI_2);
+    cr.close_path();
+}
+
+/// Rounds each color component to the nearest of `COLOR_LEVELS` levels.
+fn quantize((r, g, b, a): (f32, f32, f32, f32)) -> [u8; 4] {
+    let level = |component: f32| (component.clamp(0.0, 1.0) * (COLOR_LEVELS - 1.0)).round() as u8;
+    [level(r), level(g), level(b), level(a)]
+}
+
+/// Converts a quantized color back to RGBA components.
+fn dequantize(key: [u8; 4]) -> (f64, f64, f64, f64) {
+    let component = |level: u8| level as f64 / (COLOR_LEVELS - 1.0) as f64;
+    (
+        component(key[0]),
+        component(key[1]),
+        component(key[2]),
+        component(key[3]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantized_colors_stay_close() {
+        for value in [0.0, 0.1, 0.33, 0.5, 0.99, 1.0] {
+            let (r, _, _, a) = dequantize(quantize((value, 0.0, 0.0, value)));
+            assert!((r - value as f64).abs() <= 0.5 / 63.0 + 1e-6);
+            assert_eq!(r, a);
+        }
+        assert_eq!(quantize((2.0, -1.0, 0.5, 1.0)), [63, 0, 32, 63]);
+    }
+
+    #[test]
+    fn bars_of_similar_color_share_a_fill() {
+        let mut batch = BarBatch::new(0.0);
+        batch.add((1.0, 0.0, 0.0, 1.0), 0.0, 0.0, 1.0, 10.0);
+        batch.add((0.999, 0.001, 0.0, 1.0), 1.0, 0.0, 1.0, 10.0);
+        batch.add((0.0, 0.0, 1.0, 1.0), 2.0, 0.0, 1.0, 10.0);
+        // Invisible bars are skipped
+        batch.add((0.0, 1.0, 0.0, 1.0), 3.0, 10.0, 1.0, 0.0);
+        batch.add((0.0, 1.0, 0.0, 0.0), 4.0, 0.0, 1.0, 10.0);
+        assert_eq!(batch.bucket_count(), 2);
+        assert_eq!(batch.buckets[0].1.len(), 2);
+    }
+
+    #[test]
+    fn gradient_bars_fade_towards_their_base() {
+        use gtk::cairo::{Format, ImageSurface};
+
+        let cache = GradientCache::new();
+        let mut surface = ImageSurface::create(Format::ARgb32, 40, 100).unwrap();
+        let cr = Context::new(&surface).unwrap();
+        let mut batch = BarBatch::with_gradient(0.0, BarDirection::Up, 100.0, &cache);
+        batch.add((0.0, 1.0, 0.0, 1.0), 0.0, 0.0, 10.0, 100.0);
+        batch.add((0.0, 1.0, 0.0, 1.0), 10.0, 1.0, 10.0, 99.0);
+        // A short bar of the same color gets its own gradient
+        batch.add((0.0, 1.0, 0.0, 1.0), 20.0, 80.0, 10.0, 20.0);
+        assert_eq!(batch.bucket_count(), 2);
+        batch.fill(&cr);
+        drop(cr);
+
+        surface.flush();
+        let stride = surface.stride() as usize;
+        let data = surface.data().unwrap();
+        // ARGB32 pixels are native-endian 32-bit words with green in bits 8 to 15
+        let green = |x: usize, y: usize| {
+            let offset = y * stride + x * 4;
+            let pixel = u32::from_ne_bytes(data[offset..offset + 4].try_into().unwrap());
+            (pixel >> 8) as u8
+        };
+        assert!(green(5, 1) > 240);
+        assert!(green(5, 98) < 80);
+        assert!(green(5, 1) > green(5, 50) && green(5, 50) > green(5, 98));
+        assert_eq!(green(5, 50), green(15, 50));
+        // The base of the short bar is darker than its tip, but less than that of the tall one
+        assert!(green(25, 81) > green(25, 98) && green(25, 98) > green(5, 98));
+
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn clearing_a_gradient_cache_keeps_the_others() {
+        use gtk::cairo::{Format, ImageSurface};
+
+        let surface = ImageSurface::create(Format::ARgb32, 10, 100).unwrap();
+        let cr = Context::new(&surface).unwrap();
+        let fill = |cache: &GradientCache, color: (f32, f32, f32, f32)| {
+            let mut batch = BarBatch::with_gradient(0.0, BarDirection::Up, 100.0, cache);
+            batch.add(color, 0.0, 50.0, 10.0, 50.0);
+            batch.fill(&cr);
+        };
+        let (first, second) = (GradientCache::new(), GradientCache::new());
+        fill(&first, (1.0, 0.0, 0.0, 1.0));
+        fill(&second, (0.0, 0.0, 1.0, 1.0));
+        fill(&second, (0.0, 1.0, 0.0, 1.0));
+
+        first.clear();
+        assert_eq!(first.len(), 0);
+        assert_eq!(second.len(), 2);
+    }
+
+    #[test]
+    fn rounded_rects_cut_their_corners() {
+        use gtk::cairo::{Format, ImageSurface};
+
+        let surface = ImageSurface::create(Format::ARgb32, 100, 100).unwrap();
+        let cr = Context::new(&surface).unwrap();
+        rounded_rect(&cr, 10.0, 10.0, 40.0, 60.0, 8.0);
+        assert!(cr.in_fill(30.0, 40.0).unwrap());
+        assert!(cr.in_fill(30.0, 10.5).unwrap());
+        assert!(!cr.in_fill(10.5, 10.5).unwrap());
+        assert!(!cr.in_fill(49.5, 69.5).unwrap());
+
+        // A bar shorter than the radius becomes a pill instead of overlapping arcs
+        cr.new_path();
+        rounded_rect(&cr, 10.0, 80.0, 40.0, 4.0, 8.0);
+        assert!(cr.in_fill(30.0, 82.0).unwrap());
+        assert!(cr.in_fill(12.5, 82.0).unwrap());
+        assert!(!cr.in_fill(10.2, 80.2).unwrap());
+        let (_, top, _, bottom) = cr.fill_extents().unwrap();
+        assert!(top >= 80.0 - 1e-6 && bottom <= 84.0 + 1e-6);
+
+        // Without a radius, the corners are kept
+        cr.new_path();
+        rounded_rect(&cr, 10.0, 10.0, 40.0, 60.0, 0.0);
+        assert!(cr.in_fill(10.5, 10.5).unwrap());
+    }
+}
@@ -0,0 +1,207 @@
+use crate::bar_batch::rounded_rect;
+use crate::color::Palette;
+use crate::fft_utils::{chroma, get_bar_color, smoothing_factor, NOTE_NAMES};
+use crate::level_scale::LevelScale;
+use crate::settings::{ResolvedVisualizerSettings, Settings};
+use crate::smoothing_state::SmoothingState;
+use crate::text::{draw_text, measure, TextAlign, TextStyle};
+use crate::visualizer::{ChannelLayout, Visualizer};
+use gtk::cairo::Context;
+use gtk4 as gtk;
+use rustfft::num_complex::Complex32;
+use std::sync::{Arc, Mutex};
+
+/// Fraction of each pitch class slot covered by its bar; the rest is left as a gap.
+const BAR_FILL: f64 = 0.8;
+/// Font size of the note names, in pixels.
+const LABEL_SIZE: f64 = 14.0;
+/// Height reserved below the bars for the note names, in pixels.
+const LABEL_HEIGHT: f64 = 22.0;
+/// Distance from its target, relative to the target, below which a level counts as settled.
+const SETTLED_FRACTION: f32 = 1e-3;
+
+/// Smoothed pitch class levels carried from one frame to the next.
+///
+/// # Fields
+/// - `levels`: The smoothed level of each pitch class, starting at C.
+/// - `drawn`: Whether a frame was drawn already; the first frame shows its levels unsmoothed.
+/// - `settling`: Whether a level was still moving toward its target in the last frame.
+struct ChromaState {
+    levels: [f32; 12],
+    drawn: bool,
+    settling: bool,
+}
+
+/// A visualizer folding the spectrum into the 12 pitch classes, showing which notes dominate the
+/// music regardless of their octave.
+///
+/// Both channels are combined into one row of 12 bars labeled C through B, colored from the
+/// palette by pitch class.
+///
+/// # Fields
+/// - `settings`: Shared application settings with the `[chromagram]` configuration.
+/// - `visual_settings`: Drawing settings of the `chromagram` visualizer.
+/// - `palette`: Palette the pitch class colors are looked up in.
+/// - `level_scale`: Converts pitch class levels into bar heights.
+/// - `state`: The smoothed levels, updated by every frame.
+pub struct ChromagramVisualizer {
+    settings: Arc<Settings>,
+    visual_settings: ResolvedVisualizerSettings,
+    palette: Palette,
+    level_scale: LevelScale,
+    state: Mutex<ChromaState>,
+}
+
+impl ChromagramVisualizer {
+    /// Creates a new `ChromagramVisualizer` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - Shared application settings to configure visualizer parameters.
+    pub fn new(settings: Arc<Settings>) -> Self {
+        let visual_settings = settings.visualizer_settings("chromagram");
+        let palette = Palette::from_settings(&visual_settings);
+        let level_scale = LevelScale::new(&visual_settings, settings.fft.size);
+        ChromagramVisualizer {
+            settings,
+            visual_settings,
+            palette,
+            level_scale,
+            state: Mutex::new(ChromaState {
+                levels: [0.0; 12],
+                drawn: false,
+                settling: false,
+            }),
+        }
+    }
+
+    /// Folds both channels into pitch classes and smooths them with the previous frames.
+    ///
+    /// # Arguments
+    ///
+    /// * `fft_left` - FFT data for the left audio channel.
+    /// * `fft_right` - FFT data for the right audio channel.
+    /// * `elapsed` - Time since the previous frame, in seconds.
+    ///
+    /// # Returns
+    ///
+    /// The smoothed level of each pitch class, starting at C.
+    fn update_levels(
+        &self,
+        fft_left: &[Complex32],
+        fft_right: &[Complex32],
+        elapsed: f32,
+    ) -> [f32; 12] {
+        let fft = &self.settings.fft;
+        let a4 = self.settings.note_readout.a4;
+        let classify = |spectrum| {
+            chroma(
+                spectrum,
+                fft.sample_rate,
+                a4,
+                fft.min_frequency,
+                fft.max_frequency,
+            )
+        };
+        let (left, right) = (classify(fft_left), classify(fft_right));
+
+        let mut state = self.state.lock().unwrap();
+        let factor = if state.drawn {
+            smoothing_factor(elapsed, self.settings.chromagram.smoothing_secs)
+        } else {
+            1.0
+        };
+        state.drawn = true;
+
+        let mut settling = false;
+        for (class, level) in state.levels.iter_mut().enumerate() {
+            let target = (left[class] + right[class]) / 2.0;
+            *level += (target - *level) * factor;
+            settling |= (target - *level).abs() > target.abs() * SETTLED_FRACTION;
+        }
+        state.settling = settling;
+        state.levels
+    }
+}
+
+impl Visualizer for ChromagramVisualizer {
+    /// Draws one labeled bar per pitch class.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the drawing area.
+    /// * `height` - The height of the drawing area.
+    /// * `fft_left` - FFT data for the left audio channel.
+    /// * `fft_right` - FFT data for the right audio channel.
+    /// * `cr` - The Cairo context for drawing.
+    /// * `_previous_heights_left` - Unused; the levels are smoothed over time instead.
+    /// * `_previous_heights_right` - Unused; the levels are smoothed over time instead.
+    /// * `elapsed` - Time since the previous frame in seconds, setting how far the levels move.
+    fn draw(
+        &self,
+        width: i32,
+        height: i32,
+        fft_left: &[Complex32],
+        fft_right: &[Complex32],
+        cr: &Context,
+        _previous_heights_left: &mut SmoothingState,
+        _previous_heights_right: &mut SmoothingState,
+        elapsed: f32,
+    ) {
+        let visual_settings = &self.visual_settings;
+        let levels = self.update_levels(fft_left, fft_right, elapsed);
+
+        let baseline = height as f64 - LABEL_HEIGHT;
+        let slot_width = width as f64 / NOTE_NAMES.len() as f64;
+        let bar_width = slot_width * BAR_FILL;
+        let label_style = TextStyle {
+            align: TextAlign::Center,
+            ..TextStyle::ui(&self.settings.ui, LABEL_SIZE).bold()
+        };
+        let (_, label_height) = measure(NOTE_NAMES[0], &label_style);
+        let label_top = height as f64 - (LABEL_HEIGHT + label_height) / 2.0;
+
+        for (class, (level, name)) in levels.iter().zip(NOTE_NAMES).enumerate() {
+            let bar_height = (self.level_scale.height(*level) as f64).clamp(0.0, baseline.max(0.0));
+            let (r, g, b, a) = get_bar_color(
+                &self.palette,
+                visual_settings.color_mode,
+                class,
+                NOTE_NAMES.len(),
+                (bar_height / height as f64) as f32,
+            );
+            let alpha = (visual_settings.alpha * a) as f64;
+            cr.set_source_rgba(r as f64, g as f64, b as f64, alpha);
+
+            let center = (class as f64 + 0.5) * slot_width;
+            rounded_rect(
+                cr,
+                center - bar_width / 2.0,
+                baseline - bar_height,
+                bar_width,
+                bar_height,
+                f64::from(visual_settings.bar_radius),
+            );
+            let _ = cr.fill();
+
+            // Names keep the pure palette color, so quiet notes stay readable
+            let style = TextStyle {
+                color: (r as f64, g as f64, b as f64, visual_settings.alpha as f64),
+                ..label_style.clone()
+            };
+            draw_text(cr, name, center, label_top, &style);
+        }
+    }
+
+    fn channel_layout(&self) -> ChannelLayout {
+        ChannelLayout::Combined
+    }
+
+    fn needs_animation(&self) -> bool {
+        self.state.lock().unwrap().settling
+    }
+
+    fn set_palette(&mut self, palette: &Palette) {
+        self.palette = palette.clone();
+    }
+}
@@ -0,0 +1,294 @@
+use crate::file_utils::timestamped_file_name;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// Options given on the command line.
+///
+/// # Fields
+/// - `record`: File the captured audio is recorded to from startup, if recording was requested.
+/// - `render`: Offline rendering to perform instead of opening the window, if requested.
+/// - `force`: Whether invalid settings are clamped to valid values instead of refusing to start.
+/// - `fresh`: Whether the state saved by the previous run is ignored, starting from the
+///   configuration.
+/// - `emit`: Spectrum lines written to standard output, if requested.
+#[derive(Debug, Default, PartialEq)]
+pub struct CliOptions {
+    pub record: Option<PathBuf>,
+    pub render: Option<RenderOptions>,
+    pub force: bool,
+    pub fresh: bool,
+    pub emit: Option<EmitOptions>,
+}
+
+/// Options of the offline rendering mode.
+///
+/// # Fields
+/// - `input`: The WAV file to visualize.
+/// - `out`: Directory the numbered PNG frames are written to.
+/// - `width`: Width of the frames, in pixels.
+/// - `height`: Height of the frames, in pixels.
+/// - `fps`: Number of frames per second of audio.
+#[derive(Debug, PartialEq)]
+pub struct RenderOptions {
+    pub input: PathBuf,
+    pub out: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    pub fps: f32,
+}
+
+/// Options of the spectrum output on standard output.
+///
+/// # Fields
+/// - `format`: Format of the line written for each analysis window.
+/// - `headless`: Whether only the spectrum is written, without opening the window.
+#[derive(Debug, PartialEq)]
+pub struct EmitOptions {
+    pub format: EmitFormat,
+    pub headless: bool,
+}
+
+/// Format of the lines written by `--emit-spectrum`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EmitFormat {
+    /// Comma-separated values, after a header line naming the columns.
+    Csv,
+    /// One JSON object per line.
+    Json,
+}
+
+impl CliOptions {
+    /// Parses the command-line arguments, excluding the program name.
+    ///
+    /// # Arguments
+    /// - `args`: The arguments to parse.
+    ///
+    /// # Returns
+    /// - The parsed options, or an error describing the first invalid argument.
+    ///
+    /// Supported options:
+    /// - `--record [FILE]`: Records the captured audio to `FILE`, or to a timestamped file in the
+    ///   current directory if no file is given.
+    /// - `--render FILE`: Renders `FILE` to PNG frames instead of opening the window, configured
+    ///   by `--out DIR` (default `frames`), `--size WxH` (default `1920x1080`) and `--fps N`
+    ///   (default `60`).
+    /// - `--force`: Starts even if the configuration is invalid, clamping the offending values.
+    /// - `--fresh`: Starts from the configuration, ignoring the visualizer, zoom and other
+    ///   changes saved at the end of the previous run.
+    /// - `--emit-spectrum`: Also writes a line of binned bar heights to standard output for every
+    ///   analysis window, as CSV or JSON depending on `--emit-format csv|json` (default `csv`);
+    ///   `--headless` writes them without opening the window.
+    pub fn parse<I>(args: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut options = CliOptions::default();
+        let mut args = args.into_iter().peekable();
+        let mut render_input = None;
+        let mut out = None;
+        let mut size = None;
+        let mut fps = None;
+        let mut emit = false;
+        let mut emit_format = None;
+        let mut headless = false;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--record" => {
+                    let path = match args.next_if(|value| !value.starts_with("--")) {
+                        Some(path) => PathBuf::from(path),
+                        None => PathBuf::from(timestamped_file_name(SystemTime::now(), "wav")),
+                    };
+                    options.record = Some(path);
+                }
+                "--force" => options.force = true,
+                "--fresh" => options.fresh = true,
+                "--emit-spectrum" => emit = true,
+                "--emit-format" => emit_format = Some(parse_emit_format(&value(&mut args, &arg)?)?),
+                "--headless" => headless = true,
+                "--render" => render_input = Some(PathBuf::from(value(&mut args, &arg)?)),
+                "--out" => out = Some(PathBuf::from(value(&mut args, &arg)?)),
+                "--size" => size = Some(parse_size(&value(&mut args, &arg)?)?),
+                "--fps" => {
+                    let value = value(&mut args, &arg)?;
+                    match value.parse::<f32>() {
+                        Ok(fps_value) if fps_value > 0.0 => fps = Some(fps_value),
+                        _ => return Err(format!("Invalid frame rate: {}", value)),
+                    }
+                }
+                _ => return Err(format!("Unknown argument: {}", arg)),
+            }
+        }
+
+        match render_input {
+            Some(input) => {
+                let (width, height) = size.unwrap_or((1920, 1080));
+                options.render = Some(RenderOptions {
+                    input,
+                    out: out.unwrap_or_else(|| PathBuf::from("frames")),
+                    width,
+                    height,
+                    fps: fps.unwrap_or(60.0),
+                });
+            }
+            None if out.is_some() || size.is_some() || fps.is_some() => {
+                return Err("--out, --size and --fps require --render".to_string());
+            }
+            None => {}
+        }
+
+        if emit {
+            if options.render.is_some() {
+                return Err("--emit-spectrum cannot be combined with --render".to_string());
+            }
+            options.emit = Some(EmitOptions {
+                format: emit_format.unwrap_or(EmitFormat::Csv),
+                headless,
+            });
+        } else if emit_format.is_some() || headless {
+            return Err("--emit-format and --headless require --emit-spectrum".to_string());
+        }
+
+        Ok(options)
+    }
+}
+
+/// Takes the value following the option `name`.
+fn value<I>(args: &mut I, name: &str) -> Result<String, String>
+where
+    I: Iterator<Item = String>,
+{
+    args.next()
+        .ok_or_else(|| format!("Missing value for {}", name))
+}
+
+/// Parses a frame size of the form `WIDTHxHEIGHT`.
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("Invalid size: {}, expected e.g. 1920x1080", value);
+    let (width, height) = value.split_once('x').ok_or_else(invalid)?;
+    let width: u32 = width.parse().map_err(|_| invalid())?;
+    let height: u32 = height.parse().map_err(|_| invalid())?;
+
+    if width == 0 || height == 0 {
+        return Err(invalid());
+    }
+    Ok((width, height))
+}
+
+/// Parses the format given to `--emit-format`.
+fn parse_emit_format(value: &str) -> Result<EmitFormat, String> {
+    match value {
+        "csv" => Ok(EmitFormat::Csv),
+        "json" => Ok(EmitFormat::Json),
+        _ => Err(format!(
+            "Invalid emit format: {}, expected csv or json",
+            value
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliOptions, String> {
+        CliOptions::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn record_takes_an_optional_path() {
+        assert_eq!(parse(&[]).unwrap(), CliOptions::default());
+        assert_eq!(
+            parse(&["--record", "out.wav"]).unwrap().record,
+            Some(PathBuf::from("out.wav"))
+        );
+
+        let record = parse(&["--record"]).unwrap().record.unwrap();
+        let name = record.to_str().unwrap();
+        assert!(name.starts_with("sonic_spectra_") && name.ends_with(".wav"));
+    }
+
+    #[test]
+    fn render_options_have_defaults() {
+        let render = parse(&["--render", "song.wav"]).unwrap().render.unwrap();
+        assert_eq!(
+            render,
+            RenderOptions {
+                input: PathBuf::from("song.wav"),
+                out: PathBuf::from("frames"),
+                width: 1920,
+                height: 1080,
+                fps: 60.0,
+            }
+        );
+
+        let render = parse(&[
+            "--render", "song.wav", "--out", "out", "--size", "640x360", "--fps", "30",
+        ])
+        .unwrap()
+        .render
+        .unwrap();
+        assert_eq!(render.out, PathBuf::from("out"));
+        assert_eq!((render.width, render.height), (640, 360));
+        assert_eq!(render.fps, 30.0);
+    }
+
+    #[test]
+    fn invalid_render_options_are_rejected() {
+        assert!(parse(&["--render"]).is_err());
+        assert!(parse(&["--render", "a.wav", "--size", "1920"]).is_err());
+        assert!(parse(&["--render", "a.wav", "--size", "0x10"]).is_err());
+        assert!(parse(&["--render", "a.wav", "--fps", "-1"]).is_err());
+        assert!(parse(&["--out", "frames"]).is_err());
+    }
+
+    #[test]
+    fn force_is_a_flag() {
+        assert!(!parse(&[]).unwrap().force);
+        assert!(parse(&["--force"]).unwrap().force);
+
+        let options = parse(&["--record", "--force"]).unwrap();
+        assert!(options.force && options.record.is_some());
+    }
+
+    #[test]
+    fn fresh_is_a_flag() {
+        assert!(!parse(&[]).unwrap().fresh);
+        assert!(parse(&["--fresh"]).unwrap().fresh);
+    }
+
+    #[test]
+    fn emit_options_have_defaults() {
+        assert_eq!(parse(&[]).unwrap().emit, None);
+        assert_eq!(
+            parse(&["--emit-spectrum"]).unwrap().emit,
+            Some(EmitOptions {
+                format: EmitFormat::Csv,
+                headless: false,
+            })
+        );
+        assert_eq!(
+            parse(&["--headless", "--emit-spectrum", "--emit-format", "json"])
+                .unwrap()
+                .emit,
+            Some(EmitOptions {
+                format: EmitFormat::Json,
+                headless: true,
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_emit_options_are_rejected() {
+        assert!(parse(&["--emit-spectrum", "--emit-format", "xml"]).is_err());
+        assert!(parse(&["--emit-spectrum", "--emit-format"]).is_err());
+        assert!(parse(&["--headless"]).is_err());
+        assert!(parse(&["--emit-format", "json"]).is_err());
+        assert!(parse(&["--emit-spectrum", "--render", "a.wav"]).is_err());
+    }
+
+    #[test]
+    fn unknown_arguments_are_rejected() {
+        assert!(parse(&["--bogus"]).is_err());
+    }
+}
@@ -0,0 +1,661 @@
+use crate::fft_utils::hsl_to_rgb;
+use crate::settings::{
+    GradientStopSettings, PaletteKind, ResolvedVisualizerSettings, VisualizerSettings,
+};
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Serialize, Serializer};
+
+/// A color read from the configuration, with an optional alpha of its own.
+///
+/// Deserializes from either a `[r, g, b]` array of floats in [0.0, 1.0] or a hex string in
+/// `#rgb`, `#rrggbb` or `#rrggbbaa` form. Serializes as the array, or as `#rrggbbaa` when the
+/// color has an alpha of its own, so it reads back unchanged.
+///
+/// # Fields
+/// - `rgb`: Red, green and blue components, each in the range [0.0, 1.0].
+/// - `alpha`: Opacity given by an `#rrggbbaa` string; overrides the opacity configured next to
+///   the color.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(try_from = "ColorValue")]
+pub struct Color {
+    rgb: [f64; 3],
+    alpha: Option<f64>,
+}
+
+/// The accepted configuration forms of a `Color`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorValue {
+    Rgb([f64; 3]),
+    Hex(String),
+}
+
+impl TryFrom<ColorValue> for Color {
+    type Error = String;
+
+    fn try_from(value: ColorValue) -> Result<Self, Self::Error> {
+        match value {
+            ColorValue::Rgb([r, g, b]) => Ok(Color::rgb(r, g, b)),
+            ColorValue::Hex(hex) => Color::parse(&hex),
+        }
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.alpha {
+            None => {
+                let mut seq = serializer.serialize_seq(Some(3))?;
+                for component in self.rgb {
+                    seq.serialize_element(&component)?;
+                }
+                seq.end()
+            }
+            // Only hex strings carry an alpha, so the components are whole multiples of 1/255
+            Some(alpha) => {
+                let [r, g, b] = self.rgb.map(to_byte);
+                serializer.serialize_str(&format!(
+                    "#{:02x}{:02x}{:02x}{:02x}",
+                    r,
+                    g,
+                    b,
+                    to_byte(alpha)
+                ))
+            }
+        }
+    }
+}
+
+/// Converts a color component in [0.0, 1.0] to its nearest 8-bit value.
+fn to_byte(component: f64) -> u8 {
+    (component.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+impl Color {
+    /// Creates an opaque color without an alpha of its own.
+    pub const fn rgb(r: f64, g: f64, b: f64) -> Self {
+        Color {
+            rgb: [r, g, b],
+            alpha: None,
+        }
+    }
+
+    /// Parses a hex color.
+    ///
+    /// # Arguments
+    /// - `hex`: A color in `#rgb`, `#rrggbb` or `#rrggbbaa` form; the leading `#` is optional.
+    ///
+    /// # Returns
+    /// - The color, or an error message describing why the string could not be parsed.
+    pub fn parse(hex: &str) -> Result<Self, String> {
+        let digits = hex.trim().trim_start_matches('#');
+        let (rgb_digits, alpha_digits) = match digits.len() {
+            8 if digits.is_ascii() => digits.split_at(6),
+            3 | 6 => (digits, ""),
+            _ => return Err(format!("'{}' must have 3, 6 or 8 hex digits", hex)),
+        };
+
+        let (r, g, b) =
+            parse_hex_color(rgb_digits).map_err(|_| format!("'{}' is not a hex color", hex))?;
+        let alpha = if alpha_digits.is_empty() {
+            None
+        } else {
+            let alpha = u8::from_str_radix(alpha_digits, 16)
+                .map_err(|_| format!("'{}' is not a hex color", hex))?;
+            Some(alpha as f64 / 255.0)
+        };
+
+        Ok(Color {
+            rgb: [r as f64, g as f64, b as f64],
+            alpha,
+        })
+    }
+
+    /// Returns the color with an opacity.
+    ///
+    /// # Arguments
+    /// - `alpha`: The opacity configured next to the color, used unless the color has its own.
+    ///
+    /// # Returns
+    /// - The `(r, g, b, a)` components, each in the range [0.0, 1.0].
+    pub fn to_rgba(&self, alpha: f64) -> (f64, f64, f64, f64) {
+        let [r, g, b] = self.rgb;
+        (r, g, b, self.alpha.unwrap_or(alpha))
+    }
+}
+
+/// A single color stop of a gradient palette.
+///
+/// # Fields
+/// - `position`: Position of the stop along the gradient, in the range [0.0, 1.0].
+/// - `color`: RGB color at this position, each component in the range [0.0, 1.0].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorStop {
+    pub position: f32,
+    pub color: (f32, f32, f32),
+}
+
+/// A color palette mapping a normalized position `t` in [0.0, 1.0] to an RGB color.
+///
+/// - `Rainbow`: The HSL hue sweep used historically by the visualizers.
+/// - `Gradient`: Linear interpolation between a sorted list of color stops.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Palette {
+    Rainbow,
+    Gradient(Vec<ColorStop>),
+}
+
+/// Stops approximating matplotlib's `viridis` colormap.
+const VIRIDIS: [(f32, &str); 5] = [
+    (0.0, "#440154"),
+    (0.25, "#3b528b"),
+    (0.5, "#21918c"),
+    (0.75, "#5ec962"),
+    (1.0, "#fde725"),
+];
+
+/// Stops approximating matplotlib's `inferno` colormap.
+const INFERNO: [(f32, &str); 5] = [
+    (0.0, "#000004"),
+    (0.25, "#57106e"),
+    (0.5, "#bc3754"),
+    (0.75, "#f98e09"),
+    (1.0, "#fcffa4"),
+];
+
+/// Stops for a dark-gray to white monochrome ramp.
+const MONO: [(f32, &str); 2] = [(0.0, "#202020"), (1.0, "#ffffff")];
+
+/// Hues of the rainbow palette at which its RGB components change direction. Between them the
+/// components change linearly, so a gradient through these hues is the rainbow exactly.
+const RAINBOW_HUES: [f32; 7] = [0.0, 60.0, 120.0, 180.0, 240.0, 300.0, 360.0];
+
+impl Palette {
+    /// Builds the palette selected in the visualizer settings.
+    ///
+    /// # Arguments
+    /// - `settings`: Resolved visualizer settings providing `palette` and, for `"custom"`,
+    ///   `stops`.
+    ///
+    /// # Returns
+    /// - The configured `Palette`. An invalid custom gradient is reported on stderr and the
+    ///   rainbow palette is used instead.
+    pub fn from_settings(settings: &ResolvedVisualizerSettings) -> Self {
+        Palette::from_kind(settings.palette, &settings.stops)
+    }
+
+    /// Builds a named palette.
+    ///
+    /// # Arguments
+    /// - `kind`: The palette to build.
+    /// - `custom_stops`: Gradient stops used when `kind` is `Custom`.
+    ///
+    /// # Returns
+    /// - The `Palette`. An invalid custom gradient is reported on stderr and the rainbow palette
+    ///   is used instead.
+    pub fn from_kind(kind: PaletteKind, custom_stops: &[GradientStopSettings]) -> Self {
+        match kind {
+            PaletteKind::Rainbow => Palette::Rainbow,
+            PaletteKind::Viridis => Palette::from_preset(&VIRIDIS),
+            PaletteKind::Inferno => Palette::from_preset(&INFERNO),
+            PaletteKind::Mono => Palette::from_preset(&MONO),
+            PaletteKind::Custom => {
+                let stops: Result<Vec<ColorStop>, String> = custom_stops
+                    .iter()
+                    .map(|stop| {
+                        parse_hex_color(&stop.color).map(|color| ColorStop {
+                            position: stop.pos,
+                            color,
+                        })
+                    })
+                    .collect();
+
+                match stops {
+                    Ok(stops) if !stops.is_empty() => Palette::gradient(stops),
+                    Ok(_) => {
+                        eprintln!("Custom palette has no stops, falling back to rainbow.");
+                        Palette::Rainbow
+                    }
+                    Err(e) => {
+                        eprintln!("Invalid custom palette: {}, falling back to rainbow.", e);
+                        Palette::Rainbow
+                    }
+                }
+            }
+        }
+    }
+
+    /// Creates a gradient palette, sorting the stops by position.
+    pub fn gradient(mut stops: Vec<ColorStop>) -> Self {
+        stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+        Palette::Gradient(stops)
+    }
+
+    /// Builds a gradient from a built-in preset table.
+    fn from_preset(preset: &[(f32, &str)]) -> Self {
+        Palette::gradient(
+            preset
+                .iter()
+                .map(|&(position, hex)| ColorStop {
+                    position,
+                    color: parse_hex_color(hex).expect("Invalid built-in palette color"),
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns the palette color at position `t`.
+    ///
+    /// # Arguments
+    /// - `t`: Position along the palette; values outside [0.0, 1.0] are clamped.
+    ///
+    /// # Returns
+    /// - A tuple `(f32, f32, f32)` representing the RGB color values, each in the range [0.0, 1.0].
+    pub fn color_at(&self, t: f32) -> (f32, f32, f32) {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+
+        match self {
+            Palette::Rainbow => hsl_to_rgb(t * 360.0, 1.0, 0.5),
+            Palette::Gradient(stops) => {
+                let (first, last) = match (stops.first(), stops.last()) {
+                    (Some(first), Some(last)) => (first, last),
+                    _ => return (0.0, 0.0, 0.0),
+                };
+
+                if t <= first.position {
+                    return first.color;
+                }
+                if t >= last.position {
+                    return last.color;
+                }
+
+                // Find the segment containing `t` and blend linearly between its ends
+                for pair in stops.windows(2) {
+                    let (start, end) = (&pair[0], &pair[1]);
+                    if t <= end.position {
+                        let span = end.position - start.position;
+                        let fraction = if span > 0.0 {
+                            (t - start.position) / span
+                        } else {
+                            1.0
+                        };
+                        return lerp_color(start.color, end.color, fraction);
+                    }
+                }
+
+                last.color
+            }
+        }
+    }
+
+    /// Returns a gradient blending from this palette into `other`.
+    ///
+    /// Both palettes are linear between their stops, so sampling them at the stops of either
+    /// gives a gradient whose colors at every position are blended in RGB.
+    ///
+    /// # Arguments
+    /// - `other`: The palette blended into.
+    /// - `t`: How far to blend, from 0.0 for this palette to 1.0 for `other`; values outside
+    ///   [0.0, 1.0] are clamped.
+    pub fn lerp(&self, other: &Palette, t: f32) -> Palette {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let (stops, other_stops) = (self.stops(), other.stops());
+        let mut positions: Vec<f32> = stops
+            .iter()
+            .chain(&other_stops)
+            .map(|stop| stop.position)
+            .collect();
+        positions.sort_by(f32::total_cmp);
+        positions.dedup();
+
+        Palette::Gradient(
+            positions
+                .into_iter()
+                .map(|position| ColorStop {
+                    position,
+                    color: lerp_color(self.color_at(position), other.color_at(position), t),
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns the stops the palette interpolates between.
+    fn stops(&self) -> Vec<ColorStop> {
+        match self {
+            Palette::Rainbow => RAINBOW_HUES
+                .iter()
+                .map(|&hue| ColorStop {
+                    position: hue / 360.0,
+                    color: hsl_to_rgb(hue, 1.0, 0.5),
+                })
+                .collect(),
+            Palette::Gradient(stops) => stops.clone(),
+        }
+    }
+
+    /// Returns the palette color at position `t` with its lightness adjusted.
+    ///
+    /// # Arguments
+    /// - `t`: Position along the palette; values outside [0.0, 1.0] are clamped.
+    /// - `lightness`: HSL-style lightness (0.0 to 1.0), where 0.5 leaves the palette color
+    ///   unchanged, 0.0 is black, and 1.0 is white.
+    ///
+    /// # Returns
+    /// - A tuple `(f32, f32, f32)` representing the RGB color values, each in the range [0.0, 1.0].
+    pub fn color_at_lightness(&self, t: f32, lightness: f32) -> (f32, f32, f32) {
+        let color = self.color_at(t);
+        let lightness = lightness.clamp(0.0, 1.0);
+
+        if lightness <= 0.5 {
+            let scale = lightness / 0.5;
+            (color.0 * scale, color.1 * scale, color.2 * scale)
+        } else {
+            lerp_color(color, (1.0, 1.0, 1.0), (lightness - 0.5) / 0.5)
+        }
+    }
+}
+
+/// Palettes the bars fade through over time, set by `visualizer.palette_cycle`.
+///
+/// # Fields
+/// - `palettes`: The palettes in order; the last one fades back into the first.
+/// - `period`: Time each fade into the next palette takes, in seconds.
+/// - `elapsed`: Time since the cycle started, in seconds.
+pub struct PaletteCycle {
+    palettes: Vec<Palette>,
+    period: f64,
+    elapsed: f64,
+}
+
+impl PaletteCycle {
+    /// Creates the cycle configured in the visualizer settings.
+    ///
+    /// # Arguments
+    /// - `settings`: Visualizer settings providing `palette_cycle`, `palette_cycle_seconds` and,
+    ///   for `"custom"`, `stops`.
+    ///
+    /// # Returns
+    /// - The cycle, or `None` if `palette_cycle` is empty.
+    pub fn from_settings(settings: &VisualizerSettings) -> Option<Self> {
+        if settings.palette_cycle.is_empty() {
+            return None;
+        }
+        Some(PaletteCycle {
+            palettes: settings
+                .palette_cycle
+                .iter()
+                .map(|&kind| Palette::from_kind(kind, &settings.stops))
+                .collect(),
+            period: f64::from(settings.palette_cycle_seconds),
+            elapsed: 0.0,
+        })
+    }
+
+    /// Moves the cycle forward.
+    ///
+    /// # Arguments
+    /// - `elapsed`: Time since the previous frame, in seconds.
+    pub fn advance(&mut self, elapsed: f32) {
+        let cycle = self.period * self.palettes.len() as f64;
+        if cycle > 0.0 {
+            self.elapsed = (self.elapsed + f64::from(elapsed)).rem_euclid(cycle);
+        }
+    }
+
+    /// Returns the palette at the current point of the cycle.
+    pub fn palette(&self) -> Palette {
+        let position = if self.period > 0.0 {
+            self.elapsed / self.period
+        } else {
+            0.0
+        };
+        let index = position.floor() as usize % self.palettes.len();
+        let next = (index + 1) % self.palettes.len();
+        self.palettes[index].lerp(&self.palettes[next], position.fract() as f32)
+    }
+}
+
+/// Linearly interpolates between two RGB colors.
+fn lerp_color(a: (f32, f32, f32), b: (f32, f32, f32), fraction: f32) -> (f32, f32, f32) {
+    (
+        a.0 + (b.0 - a.0) * fraction,
+        a.1 + (b.1 - a.1) * fraction,
+        a.2 + (b.2 - a.2) * fraction,
+    )
+}
+
+/// Parses a hex color string into RGB components.
+///
+/// # Arguments
+/// - `hex`: A color in `#rrggbb` or `#rgb` form; the leading `#` is optional.
+///
+/// # Returns
+/// - `Ok((r, g, b))` with each component in the range [0.0, 1.0], or an error message describing
+///   why the string could not be parsed.
+pub fn parse_hex_color(hex: &str) -> Result<(f32, f32, f32), String> {
+    let digits = hex.trim().trim_start_matches('#');
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("'{}' is not a hex color", hex));
+    }
+
+    let component = |s: &str| u8::from_str_radix(s, 16).map(|v| v as f32 / 255.0);
+    let parsed = match digits.len() {
+        3 => {
+            let expand = |c: char| component(&format!("{}{}", c, c));
+            let mut chars = digits.chars();
+            let (r, g, b) = (
+                chars.next().unwrap(),
+                chars.next().unwrap(),
+                chars.next().unwrap(),
+            );
+            (expand(r), expand(g), expand(b))
+        }
+        6 => (
+            component(&digits[0..2]),
+            component(&digits[2..4]),
+            component(&digits[4..6]),
+        ),
+        _ => return Err(format!("'{}' must have 3 or 6 hex digits", hex)),
+    };
+
+    match parsed {
+        (Ok(r), Ok(g), Ok(b)) => Ok((r, g, b)),
+        _ => Err(format!("'{}' is not a hex color", hex)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_color_eq(actual: (f32, f32, f32), expected: (f32, f32, f32)) {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+        assert!(
+            close(actual.0, expected.0)
+                && close(actual.1, expected.1)
+                && close(actual.2, expected.2),
+            "expected {:?}, got {:?}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn parses_six_digit_hex() {
+        assert_color_eq(
+            parse_hex_color("#ff8000").unwrap(),
+            (1.0, 128.0 / 255.0, 0.0),
+        );
+        assert_color_eq(parse_hex_color("00FFcc").unwrap(), (0.0, 1.0, 0.8));
+    }
+
+    #[test]
+    fn parses_three_digit_hex() {
+        assert_color_eq(parse_hex_color("#f0a").unwrap(), (1.0, 0.0, 170.0 / 255.0));
+        assert_color_eq(parse_hex_color("fff").unwrap(), (1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn colors_parse_three_and_six_digit_hex() {
+        assert_eq!(
+            Color::parse("#f00").unwrap().to_rgba(0.5),
+            (1.0, 0.0, 0.0, 0.5)
+        );
+        let (r, g, b, a) = Color::parse("cc1a1a").unwrap().to_rgba(0.1);
+        assert_color_eq((r as f32, g as f32, b as f32), (0.8, 0.102, 0.102));
+        assert_eq!(a, 0.1);
+    }
+
+    #[test]
+    fn colors_with_eight_digits_override_alpha() {
+        let (r, g, b, a) = Color::parse("#00ff0080").unwrap().to_rgba(0.1);
+        assert_eq!((r, g, b), (0.0, 1.0, 0.0));
+        assert!((a - 128.0 / 255.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn colors_reject_invalid_strings() {
+        assert!(Color::parse("#ff00zz").is_err());
+        assert!(Color::parse("#ff00ff0g").is_err());
+        assert!(Color::parse("#ff00f").is_err());
+        assert!(Color::parse("#ff00ff00ff").is_err());
+        assert!(Color::parse("#ÿÿÿÿ").is_err());
+        assert!(Color::parse("").is_err());
+    }
+
+    #[test]
+    fn colors_accept_the_legacy_array_form() {
+        let color = Color::try_from(ColorValue::Rgb([0.8, 0.1, 0.1])).unwrap();
+        assert_eq!(color, Color::rgb(0.8, 0.1, 0.1));
+        assert_eq!(color.to_rgba(0.3), (0.8, 0.1, 0.1, 0.3));
+
+        let hex = Color::try_from(ColorValue::Hex("#fff".to_string())).unwrap();
+        assert_eq!(hex, Color::rgb(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn rejects_invalid_hex() {
+        assert!(parse_hex_color("#12345").is_err());
+        assert!(parse_hex_color("#gggggg").is_err());
+        assert!(parse_hex_color("").is_err());
+        assert!(parse_hex_color("#+1+2+3").is_err());
+    }
+
+    #[test]
+    fn gradient_interpolates_between_stops() {
+        let palette = Palette::gradient(vec![
+            ColorStop {
+                position: 1.0,
+                color: (1.0, 1.0, 1.0),
+            },
+            ColorStop {
+                position: 0.0,
+                color: (0.0, 0.0, 0.0),
+            },
+        ]);
+
+        assert_color_eq(palette.color_at(0.0), (0.0, 0.0, 0.0));
+        assert_color_eq(palette.color_at(0.25), (0.25, 0.25, 0.25));
+        assert_color_eq(palette.color_at(1.0), (1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn gradient_clamps_out_of_range_positions() {
+        let palette = Palette::gradient(vec![
+            ColorStop {
+                position: 0.2,
+                color: (1.0, 0.0, 0.0),
+            },
+            ColorStop {
+                position: 0.8,
+                color: (0.0, 0.0, 1.0),
+            },
+        ]);
+
+        assert_color_eq(palette.color_at(-1.0), (1.0, 0.0, 0.0));
+        assert_color_eq(palette.color_at(0.1), (1.0, 0.0, 0.0));
+        assert_color_eq(palette.color_at(0.5), (0.5, 0.0, 0.5));
+        assert_color_eq(palette.color_at(0.9), (0.0, 0.0, 1.0));
+        assert_color_eq(palette.color_at(5.0), (0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn lightness_darkens_and_brightens() {
+        let palette = Palette::Rainbow;
+
+        assert_color_eq(palette.color_at_lightness(0.0, 0.5), (1.0, 0.0, 0.0));
+        assert_color_eq(palette.color_at_lightness(0.0, 0.25), (0.5, 0.0, 0.0));
+        assert_color_eq(palette.color_at_lightness(0.0, 0.75), (1.0, 0.5, 0.5));
+        assert_color_eq(palette.color_at_lightness(0.0, 0.0), (0.0, 0.0, 0.0));
+        assert_color_eq(palette.color_at_lightness(0.0, 1.0), (1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn lerp_blends_the_colors_in_rgb() {
+        let black = Palette::gradient(vec![ColorStop {
+            position: 0.0,
+            color: (0.0, 0.0, 0.0),
+        }]);
+        let ramp = Palette::gradient(vec![
+            ColorStop {
+                position: 0.0,
+                color: (0.0, 0.0, 1.0),
+            },
+            ColorStop {
+                position: 0.5,
+                color: (1.0, 1.0, 1.0),
+            },
+        ]);
+
+        let blended = black.lerp(&ramp, 0.5);
+        assert_color_eq(blended.color_at(0.0), (0.0, 0.0, 0.5));
+        assert_color_eq(blended.color_at(0.25), (0.25, 0.25, 0.5));
+        assert_color_eq(blended.color_at(1.0), (0.5, 0.5, 0.5));
+        assert_color_eq(black.lerp(&ramp, 0.0).color_at(0.25), (0.0, 0.0, 0.0));
+        assert_color_eq(black.lerp(&ramp, 2.0).color_at(0.25), (0.5, 0.5, 1.0));
+    }
+
+    #[test]
+    fn lerp_keeps_the_rainbow_exact() {
+        let rainbow = Palette::Rainbow.lerp(&Palette::Rainbow, 0.5);
+        for step in 0..=20 {
+            let t = step as f32 / 20.0;
+            assert_color_eq(rainbow.color_at(t), Palette::Rainbow.color_at(t));
+        }
+    }
+
+    #[test]
+    fn palette_cycle_fades_through_the_palettes_and_loops() {
+        let settings = VisualizerSettings {
+            palette_cycle: vec![PaletteKind::Mono, PaletteKind::Rainbow],
+            palette_cycle_seconds: 10.0,
+            ..VisualizerSettings::default()
+        };
+        let mono = Palette::from_kind(PaletteKind::Mono, &[]);
+        let mut cycle = PaletteCycle::from_settings(&settings).unwrap();
+        assert_color_eq(cycle.palette().color_at(0.5), mono.color_at(0.5));
+
+        // Halfway through the fade from mono to rainbow
+        cycle.advance(5.0);
+        let expected = lerp_color(mono.color_at(0.5), Palette::Rainbow.color_at(0.5), 0.5);
+        assert_color_eq(cycle.palette().color_at(0.5), expected);
+
+        cycle.advance(5.0);
+        assert_color_eq(
+            cycle.palette().color_at(0.5),
+            Palette::Rainbow.color_at(0.5),
+        );
+
+        // Back to mono after both fades
+        cycle.advance(10.0);
+        assert_color_eq(cycle.palette().color_at(0.5), mono.color_at(0.5));
+
+        assert!(PaletteCycle::from_settings(&VisualizerSettings::default()).is_none());
+    }
+
+    #[test]
+    fn rainbow_starts_at_red() {
+        assert_color_eq(Palette::Rainbow.color_at(0.0), (1.0, 0.0, 0.0));
+        assert_color_eq(Palette::Rainbow.color_at(-0.5), (1.0, 0.0, 0.0));
+    }
+}
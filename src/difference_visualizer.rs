@@ -0,0 +1,288 @@
+use crate::bar_batch::BarBatch;
+use crate::fft_utils::{band_emphasis, frequency_indices, interpolate, smoothing_factor};
+use crate::frequency_mapper::{Channel, FrequencyMapper};
+use crate::level_scale::LevelScale;
+use crate::settings::{ResolvedVisualizerSettings, Settings};
+use crate::smoothing_state::SmoothingState;
+use crate::visualizer::{BarInstance, ChannelLayout, GlVisualizer, Visualizer};
+use gtk::cairo::Context;
+use gtk4 as gtk;
+use rustfft::num_complex::Complex32;
+use std::sync::Arc;
+
+/// Returns the level difference between the left and right channel magnitudes of a bar.
+///
+/// # Arguments
+/// - `left`: Magnitude of the left channel.
+/// - `right`: Magnitude of the right channel.
+/// - `floor`: Magnitude below which a channel counts as silent, so near-silent bins do not
+///   divide by almost zero.
+/// - `range_db`: Largest difference returned either way, in dB.
+///
+/// # Returns
+/// - `20 * log10(left / right)` in dB, positive where the left channel is louder, clamped to
+///   `±range_db`; `0.0` where both channels are silent.
+pub fn level_difference(left: f32, right: f32, floor: f32, range_db: f32) -> f32 {
+    let floor = floor.max(f32::MIN_POSITIVE);
+    if !(left > floor || right > floor) {
+        return 0.0;
+    }
+    let difference = 20.0 * (left.max(floor) / right.max(floor)).log10();
+    difference.clamp(-range_db, range_db)
+}
+
+/// A visualizer drawing the level difference between the left and right channel of every bar,
+/// up from a center line where the left channel is louder and down where the right one is.
+///
+/// The bars are laid out like those of the frequency visualizer and mirrored at the center of
+/// the drawing area, so they line up with the frequency markers of the grid.
+pub struct DifferenceVisualizer {
+    settings: Arc<Settings>,
+    visual_settings: ResolvedVisualizerSettings,
+    level_scale: LevelScale,
+}
+
+impl DifferenceVisualizer {
+    /// Creates a new `DifferenceVisualizer` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - Shared application settings to configure visualizer parameters.
+    pub fn new(settings: Arc<Settings>) -> Self {
+        let visual_settings = settings.visualizer_settings("difference");
+        let level_scale = LevelScale::new(&visual_settings, settings.fft.size);
+        DifferenceVisualizer {
+            settings,
+            visual_settings,
+            level_scale,
+        }
+    }
+}
+
+impl Visualizer for DifferenceVisualizer {
+    /// Draws the level differences between the left and right audio channels.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the drawing area.
+    /// * `height` - The height of the drawing area.
+    /// * `fft_left` - FFT data for the left audio channel.
+    /// * `fft_right` - FFT data for the right audio channel.
+    /// * `cr` - The Cairo context for drawing.
+    /// * `previous_heights_left` - Stores the previous frame's differences in dB for smooth transitions.
+    /// * `previous_heights_right` - Unused, as both channels make one difference.
+    /// * `elapsed` - Time since the previous frame in seconds, setting how far the bars move.
+    fn draw(
+        &self,
+        width: i32,
+        height: i32,
+        fft_left: &[Complex32],
+        fft_right: &[Complex32],
+        cr: &Context,
+        previous_heights_left: &mut SmoothingState,
+        previous_heights_right: &mut SmoothingState,
+        elapsed: f32,
+    ) {
+        let mut instances = Vec::new();
+        self.bar_instances(
+            width,
+            height,
+            fft_left,
+            fft_right,
+            previous_heights_left,
+            previous_heights_right,
+            elapsed,
+            &mut instances,
+        );
+
+        let mut batch = BarBatch::new(f64::from(self.visual_settings.bar_radius));
+        for BarInstance { rect, color } in instances {
+            let [x, y, bar_width, bar_height] = rect.map(f64::from);
+            let [r, g, b, a] = color;
+            batch.add((r, g, b, a), x, y, bar_width, bar_height);
+        }
+        batch.fill(cr);
+    }
+
+    fn as_gl(&self) -> Option<&dyn GlVisualizer> {
+        Some(self)
+    }
+
+    fn center_line(&self) -> bool {
+        true
+    }
+
+    /// Moves the differences to the bars of the new width, which merges bins differently.
+    fn rescale_heights(&self, heights: &mut SmoothingState, from: (i32, i32), to: (i32, i32)) {
+        let fft_size = self.settings.fft.padded_size();
+        let (min_index, max_index) = frequency_indices(&self.settings.fft, fft_size);
+        FrequencyMapper::from_settings(&self.settings, fft_size).remap_slot_state(
+            min_index..max_index,
+            f64::from(from.0) / 2.0,
+            f64::from(to.0) / 2.0,
+            heights,
+        );
+    }
+
+    /// Both halves show the difference between the channels.
+    fn channel_layout(&self) -> ChannelLayout {
+        ChannelLayout::Combined
+    }
+}
+
+impl GlVisualizer for DifferenceVisualizer {
+    /// Lays out the difference bars, mirrored on both halves of the drawing area.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the drawing area.
+    /// * `height` - The height of the drawing area.
+    /// * `fft_left` - FFT data for the left audio channel.
+    /// * `fft_right` - FFT data for the right audio channel.
+    /// * `previous_heights_left` - Stores the previous frame's differences in dB for smooth transitions.
+    /// * `previous_heights_right` - Unused, as both channels make one difference.
+    /// * `instances` - Receives the bars of the left half followed by those of the right half.
+    fn bar_instances(
+        &self,
+        width: i32,
+        height: i32,
+        fft_left: &[Complex32],
+        fft_right: &[Complex32],
+        previous_heights_left: &mut SmoothingState,
+        _previous_heights_right: &mut SmoothingState,
+        elapsed: f32,
+        instances: &mut Vec<BarInstance>,
+    ) {
+        let visual_settings = &self.visual_settings;
+        let difference_settings = &self.settings.difference;
+        let interpolation_factor = smoothing_factor(elapsed, visual_settings.smoothing_ms / 1000.0);
+        let range_db = difference_settings.range_db.max(f32::EPSILON);
+        let floor = self.level_scale.floor_magnitude();
+
+        let fft_size = fft_left.len();
+        let (min_index, max_index) = frequency_indices(&self.settings.fft, fft_size);
+        let mapper = FrequencyMapper::from_settings(&self.settings, fft_size);
+        let half_width = width as f64 / 2.0;
+        let slots = mapper.bar_slots(min_index..max_index, half_width);
+        let center = f64::from(height) / 2.0;
+        previous_heights_left.ensure_len(slots.len());
+
+        let loudest = |fft: &[Complex32], bins: std::ops::Range<usize>| {
+            fft[bins]
+                .iter()
+                .map(|value| value.norm())
+                .fold(0.0, f32::max)
+        };
+        let differences: Vec<f32> = slots
+            .iter()
+            .enumerate()
+            .map(|(i, slot)| {
+                let target = level_difference(
+                    loudest(fft_left, slot.bins.clone()),
+                    loudest(fft_right, slot.bins.clone()),
+                    floor,
+                    range_db,
+                );
+                previous_heights_left[i] =
+                    interpolate(previous_heights_left[i], target, interpolation_factor);
+                previous_heights_left[i]
+            })
+            .collect();
+
+        instances.reserve(2 * slots.len());
+        for channel in [Channel::Left, Channel::Right] {
+            for (slot, &difference) in slots.iter().zip(&differences) {
+                let color = if difference >= 0.0 {
+                    difference_settings.color_left
+                } else {
+                    difference_settings.color_right
+                };
+                let (r, g, b, a) = color.to_rgba(f64::from(visual_settings.alpha));
+                let emphasis = band_emphasis(self.settings.fft.solo_band, slot.frequency);
+
+                let offset = f64::from(difference / range_db) * center;
+                let (x, bar_width) = slot.bar_span(half_width, channel, visual_settings.bar_gap);
+                let (y, bar_height) = if offset >= 0.0 {
+                    (center - offset, offset)
+                } else {
+                    (center, -offset)
+                };
+
+                instances.push(BarInstance {
+                    rect: [x as f32, y as f32, bar_width as f32, bar_height as f32],
+                    color: [r as f32, g as f32, b as f32, a as f32 * emphasis],
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn silent_bars_show_no_difference() {
+        assert_eq!(level_difference(0.0, 0.0, 0.05, 24.0), 0.0);
+        assert_eq!(level_difference(0.01, 0.04, 0.05, 24.0), 0.0);
+        assert_eq!(level_difference(0.0, 0.0, 0.0, 24.0), 0.0);
+    }
+
+    #[test]
+    fn equal_channels_show_no_difference() {
+        assert_eq!(level_difference(3.0, 3.0, 0.05, 24.0), 0.0);
+        assert_eq!(level_difference(500.0, 500.0, 0.05, 24.0), 0.0);
+    }
+
+    #[test]
+    fn louder_channels_point_their_way_up_to_the_range() {
+        assert!((level_difference(10.0, 1.0, 0.05, 24.0) - 20.0).abs() < 1e-4);
+        assert!((level_difference(1.0, 10.0, 0.05, 24.0) + 20.0).abs() < 1e-4);
+        // One silent channel is measured against the floor and clamped to the range
+        assert_eq!(level_difference(5.0, 0.0, 0.05, 24.0), 24.0);
+        assert_eq!(level_difference(0.0, 5.0, 0.05, 24.0), -24.0);
+        assert!((level_difference(0.5, 0.0, 0.05, 24.0) - 20.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn bars_grow_from_the_center_line() {
+        let settings = Arc::new(Settings::default());
+        let fft_size = settings.fft.size;
+        let visualizer = DifferenceVisualizer::new(settings);
+        let loud: Vec<_> = vec![Complex32::new(100.0, 0.0); fft_size];
+        let quiet: Vec<_> = vec![Complex32::new(10.0, 0.0); fft_size];
+        let mut previous_left = SmoothingState::default();
+        let mut previous_right = SmoothingState::default();
+        let mut instances = Vec::new();
+
+        visualizer.bar_instances(
+            800,
+            400,
+            &loud,
+            &quiet,
+            &mut previous_left,
+            &mut previous_right,
+            1.0,
+            &mut instances,
+        );
+        assert!(!instances.is_empty());
+        for instance in &instances {
+            let [_, y, _, bar_height] = instance.rect;
+            assert!((y + bar_height - 200.0).abs() < 1e-3);
+            assert!(bar_height > 0.0);
+        }
+
+        instances.clear();
+        visualizer.bar_instances(
+            800,
+            400,
+            &quiet,
+            &loud,
+            &mut previous_left,
+            &mut previous_right,
+            1.0,
+            &mut instances,
+        );
+        assert!(instances.iter().all(|instance| instance.rect[1] == 200.0));
+    }
+}
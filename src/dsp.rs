@@ -0,0 +1,1418 @@
+use crate::fft_utils::smooth_bins;
+use crate::settings::{BeatSettings, PowerSettings, VisualizerSettings};
+use rustfft::num_complex::Complex32;
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::time::Duration;
+
+/// Number of past flux values the adaptive threshold is computed from (about one second at
+/// typical frame rates).
+const FLUX_HISTORY_LEN: usize = 43;
+/// Minimum number of flux values collected before any beat is reported.
+const MIN_FLUX_HISTORY: usize = 8;
+/// Largest supported neighbor smoothing radius, in bins.
+const MAX_BIN_SMOOTHING: usize = 5;
+/// Fraction of the drawing height the loudest recent bar is scaled to by the automatic gain.
+const AUTO_GAIN_TARGET: f32 = 0.9;
+/// Largest automatic gain adjustment in either direction, in decades (60 dB).
+const MAX_AUTO_GAIN_DECADES: f32 = 3.0;
+/// Peak magnitude below which the input is treated as silence and the automatic gain is held.
+const AUTO_GAIN_SILENCE: f32 = 1e-3;
+/// Margin above `power.idle_threshold_db` the level must exceed to leave the idle state, in dB.
+const SILENCE_HYSTERESIS_DB: f32 = 6.0;
+/// Center frequency of the high shelf of the K-weighting, in Hz.
+const K_SHELF_FREQUENCY: f64 = 1_681.974_450_955_533;
+/// Gain of the high shelf of the K-weighting, in dB.
+const K_SHELF_GAIN_DB: f64 = 3.999_843_853_973_347;
+/// Quality factor of the high shelf of the K-weighting.
+const K_SHELF_Q: f64 = 0.707_175_236_955_419_6;
+/// Exponent of the shelf gain giving the gain at the band edge of the high shelf.
+const K_SHELF_BAND_EXPONENT: f64 = 0.499_666_774_154_541_6;
+/// Cutoff frequency of the high-pass of the K-weighting, in Hz.
+const K_HIGHPASS_FREQUENCY: f64 = 38.135_470_876_024_44;
+/// Quality factor of the high-pass of the K-weighting.
+const K_HIGHPASS_Q: f64 = 0.500_327_037_323_877_3;
+/// Length of the blocks the loudness windows advance by, in seconds.
+const LOUDNESS_BLOCK_SECS: f64 = 0.1;
+/// Number of blocks in the momentary loudness window (400 ms).
+const MOMENTARY_BLOCKS: usize = 4;
+/// Number of blocks in the short-term loudness window (3 s).
+const SHORT_TERM_BLOCKS: usize = 30;
+/// Offset of the loudness of a K-weighted mean square, in dB.
+const LOUDNESS_OFFSET_DB: f64 = -0.691;
+/// Absolute gate of BS.1770; quieter windows are reported as silent, in LUFS.
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+/// Number of taps of each phase of the true-peak interpolation filter.
+const TRUE_PEAK_TAPS: usize = 12;
+/// Phases of the 4x oversampling filter of ITU-R BS.1770 Annex 2, one per interpolated position.
+const TRUE_PEAK_PHASES: [[f32; TRUE_PEAK_TAPS]; 4] = [
+    [
+        0.001_708_984_4,
+        0.010_986_328,
+        -0.019_653_32,
+        0.033_203_125,
+        -0.059_448_242,
+        0.137_329_1,
+        0.972_167_97,
+        -0.102_294_92,
+        0.047_607_42,
+        -0.026_611_328,
+        0.014_892_578,
+        -0.008_300_781,
+    ],
+    [
+        -0.029_174_805,
+        0.029_296_875,
+        -0.051_757_812,
+        0.089_111_33,
+        -0.166_503_9,
+        0.465_087_9,
+        0.779_785_16,
+        -0.200_317_38,
+        0.101_562_5,
+        -0.058_227_54,
+        0.033_081_055,
+        -0.018_920_898,
+    ],
+    [
+        -0.018_920_898,
+        0.033_081_055,
+        -0.058_227_54,
+        0.101_562_5,
+        -0.200_317_38,
+        0.779_785_16,
+        0.465_087_9,
+        -0.166_503_9,
+        0.089_111_33,
+        -0.051_757_812,
+        0.029_296_875,
+        -0.029_174_805,
+    ],
+    [
+        -0.008_300_781,
+        0.014_892_578,
+        -0.026_611_328,
+        0.047_607_42,
+        -0.102_294_92,
+        0.972_167_97,
+        0.137_329_1,
+        -0.059_448_242,
+        0.033_203_125,
+        -0.019_653_32,
+        0.010_986_328,
+        0.001_708_984_4,
+    ],
+];
+/// True-peak level above which the clip indicator latches, in dBTP.
+const CLIP_THRESHOLD_DBTP: f32 = -1.0;
+/// Time a true peak is held before the meter follows lower levels, in seconds.
+const TRUE_PEAK_HOLD_SECS: f32 = 2.0;
+
+/// A detected beat.
+///
+/// # Fields
+/// - `timestamp`: Time of the analyzed frame, relative to the start of the analysis.
+/// - `strength`: How far the spectral flux exceeded its running mean, in standard deviations.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BeatEvent {
+    pub timestamp: Duration,
+    pub strength: f32,
+}
+
+/// Callback invoked for every detected beat.
+pub type BeatCallback = Box<dyn Fn(BeatEvent) + Send + Sync>;
+
+/// Onset detector based on spectral flux.
+///
+/// Each frame, the positive magnitude differences against the previous spectrum are summed into
+/// a flux value. A beat is reported when the flux exceeds the running mean of recent frames by
+/// `sensitivity` standard deviations, and at least `min_interval` has passed since the last beat.
+pub struct BeatDetector {
+    previous_magnitudes: Vec<f32>,
+    flux_history: VecDeque<f32>,
+    sensitivity: f32,
+    min_interval: Duration,
+    last_beat: Option<Duration>,
+    callbacks: Vec<BeatCallback>,
+}
+
+impl BeatDetector {
+    /// Creates a new `BeatDetector` instance.
+    ///
+    /// # Arguments
+    /// - `settings`: Beat detection settings providing `sensitivity` and `min_interval_ms`.
+    pub fn new(settings: &BeatSettings) -> Self {
+        BeatDetector {
+            previous_magnitudes: Vec::new(),
+            flux_history: VecDeque::with_capacity(FLUX_HISTORY_LEN),
+            sensitivity: settings.sensitivity,
+            min_interval: Duration::from_millis(settings.min_interval_ms),
+            last_beat: None,
+            callbacks: Vec::new(),
+        }
+    }
+
+    /// Registers a callback invoked for every detected beat.
+    ///
+    /// # Arguments
+    /// - `callback`: The function receiving each `BeatEvent`.
+    pub fn on_beat(&mut self, callback: BeatCallback) {
+        self.callbacks.push(callback);
+    }
+
+    /// Analyzes one spectrum frame.
+    ///
+    /// # Arguments
+    /// - `spectrum`: Complex FFT output of the frame, covering the full FFT size.
+    /// - `timestamp`: Time of the frame, relative to the start of the analysis.
+    ///
+    /// # Returns
+    /// - `Some(BeatEvent)` if the frame contains an onset, after notifying all callbacks.
+    pub fn process(&mut self, spectrum: &[Complex32], timestamp: Duration) -> Option<BeatEvent> {
+        let magnitudes: Vec<f32> = spectrum[..spectrum.len() / 2]
+            .iter()
+            .map(|value| value.norm())
+            .collect();
+
+        // Only increases in energy count towards an onset
+        let flux: f32 = if self.previous_magnitudes.len() == magnitudes.len() {
+            magnitudes
+                .iter()
+                .zip(&self.previous_magnitudes)
+                .map(|(current, previous)| (current - previous).max(0.0))
+                .sum()
+        } else {
+            0.0
+        };
+        self.previous_magnitudes = magnitudes;
+
+        let event = self.detect(flux, timestamp);
+
+        if self.flux_history.len() == FLUX_HISTORY_LEN {
+            self.flux_history.pop_front();
+        }
+        self.flux_history.push_back(flux);
+
+        if let Some(event) = event {
+            self.last_beat = Some(timestamp);
+            for callback in &self.callbacks {
+                callback(event);
+            }
+        }
+
+        event
+    }
+
+    /// Compares a flux value against the adaptive threshold and the refractory period.
+    fn detect(&self, flux: f32, timestamp: Duration) -> Option<BeatEvent> {
+        if self.flux_history.len() < MIN_FLUX_HISTORY {
+            return None;
+        }
+
+        if let Some(last_beat) = self.last_beat {
+            if timestamp.saturating_sub(last_beat) < self.min_interval {
+                return None;
+            }
+        }
+
+        let count = self.flux_history.len() as f32;
+        let mean = self.flux_history.iter().sum::<f32>() / count;
+        let variance = self
+            .flux_history
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f32>()
+            / count;
+        let deviation = variance.sqrt();
+
+        let threshold = mean + self.sensitivity * deviation;
+        if flux > threshold && flux > 0.0 {
+            Some(BeatEvent {
+                timestamp,
+                strength: (flux - mean) / deviation.max(f32::EPSILON),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Analysis stage applied to each channel's spectrum before it is drawn.
+///
+/// Bin magnitudes are first smoothed across neighboring bins with a kernel of `bin_smoothing`
+/// bins on each side, then over time with an asymmetric exponential moving average: rising
+/// magnitudes move towards the new value by `attack` per frame, falling magnitudes by `release`.
+/// The phase of each bin is preserved, so downstream code can keep using `norm()`.
+///
+/// When a noise profile is set, it is subtracted from the raw magnitudes (floored at zero) before
+/// any smoothing. The profile is measured by `start_calibration`, which averages the raw
+/// magnitudes of the following frames.
+pub struct SpectrumAnalyzer {
+    smoothed: Vec<f32>,
+    attack: f32,
+    release: f32,
+    bin_smoothing: usize,
+    noise_profile: Option<Vec<f32>>,
+    calibration: Option<Calibration>,
+}
+
+/// Noise floor measurement in progress.
+///
+/// # Fields
+/// - `sums`: Sum of the raw magnitudes of each bin over the frames collected so far.
+/// - `frames`: Number of frames collected so far.
+/// - `remaining`: Number of frames still to collect.
+struct Calibration {
+    sums: Vec<f32>,
+    frames: usize,
+    remaining: usize,
+}
+
+impl SpectrumAnalyzer {
+    /// Creates a new `SpectrumAnalyzer` instance.
+    ///
+    /// # Arguments
+    /// - `settings`: Visualizer settings providing the `attack` and `release` factors.
+    pub fn new(settings: &VisualizerSettings) -> Self {
+        SpectrumAnalyzer {
+            smoothed: Vec::new(),
+            attack: settings.attack.clamp(0.0, 1.0),
+            release: settings.release_factor().clamp(0.0, 1.0),
+            bin_smoothing: settings.bin_smoothing.min(MAX_BIN_SMOOTHING),
+            noise_profile: None,
+            calibration: None,
+        }
+    }
+
+    /// Starts measuring the noise floor over the next `frames` frames.
+    ///
+    /// The current noise profile is discarded; frames are shown unfiltered until the measurement
+    /// completes and its average becomes the new profile.
+    pub fn start_calibration(&mut self, frames: usize) {
+        self.noise_profile = None;
+        self.calibration = Some(Calibration {
+            sums: Vec::new(),
+            frames: 0,
+            remaining: frames.max(1),
+        });
+    }
+
+    /// Returns `true` while a noise floor measurement is in progress.
+    pub fn is_calibrating(&self) -> bool {
+        self.calibration.is_some()
+    }
+
+    /// Returns the per-bin noise magnitudes subtracted from each frame, if any.
+    pub fn noise_profile(&self) -> Option<&[f32]> {
+        self.noise_profile.as_deref()
+    }
+
+    /// Replaces the noise profile; `None` disables noise subtraction.
+    pub fn set_noise_profile(&mut self, profile: Option<Vec<f32>>) {
+        self.noise_profile = profile;
+    }
+
+    /// Adds a frame's raw magnitudes to the calibration in progress, finishing it when enough
+    /// frames have been collected.
+    fn calibrate(&mut self, magnitudes: &[f32]) {
+        let Some(calibration) = self.calibration.as_mut() else {
+            return;
+        };
+
+        if calibration.sums.len() != magnitudes.len() {
+            calibration.sums = vec![0.0; magnitudes.len()];
+            calibration.frames = 0;
+        }
+        for (sum, &magnitude) in calibration.sums.iter_mut().zip(magnitudes) {
+            *sum += magnitude;
+        }
+        calibration.frames += 1;
+        calibration.remaining -= 1;
+
+        if calibration.remaining == 0 {
+            let frames = calibration.frames as f32;
+            let profile = calibration.sums.iter().map(|sum| sum / frames).collect();
+            self.noise_profile = Some(profile);
+            self.calibration = None;
+        }
+    }
+
+    /// Smooths a spectrum frame in place.
+    ///
+    /// # Arguments
+    /// - `spectrum`: Complex FFT output of the frame; each bin's magnitude is replaced by its
+    ///   smoothed magnitude.
+    pub fn process(&mut self, spectrum: &mut [Complex32]) {
+        let mut magnitudes: Vec<f32> = spectrum.iter().map(|value| value.norm()).collect();
+
+        if self.is_calibrating() {
+            self.calibrate(&magnitudes);
+        } else if let Some(profile) = &self.noise_profile {
+            if profile.len() == magnitudes.len() {
+                for (magnitude, &noise) in magnitudes.iter_mut().zip(profile) {
+                    *magnitude = (*magnitude - noise).max(0.0);
+                }
+            }
+        }
+
+        smooth_bins(&mut magnitudes, self.bin_smoothing);
+
+        if self.smoothed.len() != spectrum.len() {
+            self.smoothed = magnitudes.clone();
+        } else {
+            for (smoothed, &magnitude) in self.smoothed.iter_mut().zip(&magnitudes) {
+                let factor = if magnitude > *smoothed {
+                    self.attack
+                } else {
+                    self.release
+                };
+                *smoothed += (magnitude - *smoothed) * factor;
+            }
+        }
+
+        for (value, &smoothed) in spectrum.iter_mut().zip(&self.smoothed) {
+            let magnitude = value.norm();
+            *value = if magnitude > 0.0 {
+                *value * (smoothed / magnitude)
+            } else {
+                Complex32::new(smoothed, 0.0)
+            };
+        }
+    }
+}
+
+/// Automatic gain control keeping the loudest bar of the recent past near the top of the display.
+///
+/// Bars are drawn `log10(magnitude * gain) * scale_factor` pixels high, so the automatic gain is
+/// tracked as a decade offset: each frame the offset moving the loudest bar of the window to
+/// `AUTO_GAIN_TARGET` of the height is computed, and the current offset follows it by `attack`
+/// when decreasing and `release` when increasing. Regardless of smoothing, the offset is limited
+/// so the current frame never exceeds the full height.
+///
+/// # Fields
+/// - `peaks`: Peak bin magnitude of each frame in the window, oldest first.
+/// - `window`: Number of frames in the window.
+/// - `decades`: Current gain offset, in decades.
+/// - `gain`: Manual gain applied before the automatic gain.
+/// - `scale_factor`: Pixels per decade of bar magnitude.
+/// - `attack`: Fraction the offset moves per frame when decreasing.
+/// - `release`: Fraction the offset moves per frame when increasing.
+pub struct AutoGain {
+    peaks: VecDeque<f32>,
+    window: usize,
+    decades: f32,
+    gain: f32,
+    scale_factor: f32,
+    attack: f32,
+    release: f32,
+}
+
+impl AutoGain {
+    /// Creates a new `AutoGain` instance.
+    ///
+    /// # Arguments
+    /// - `settings`: Visualizer settings providing the gain, scale factor, and `auto_gain_*`
+    ///   parameters.
+    /// - `frame_interval`: Time between frames, used to convert the window length to frames.
+    pub fn new(settings: &VisualizerSettings, frame_interval: Duration) -> Self {
+        let window = (settings.auto_gain_window_secs / frame_interval.as_secs_f32()).ceil();
+        AutoGain {
+            peaks: VecDeque::new(),
+            window: (window as usize).max(1),
+            decades: 0.0,
+            gain: settings.gain,
+            scale_factor: settings.scale_factor,
+            attack: settings.auto_gain_attack.clamp(0.0, 1.0),
+            release: settings.auto_gain_release.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Updates the automatic gain from a frame of both channels.
+    ///
+    /// # Arguments
+    /// - `fft_left`: FFT data for the left audio channel.
+    /// - `fft_right`: FFT data for the right audio channel.
+    /// - `height`: Height of the drawing area in pixels.
+    ///
+    /// # Returns
+    /// - The linear factor to multiply both spectra by before drawing.
+    pub fn update(&mut self, fft_left: &[Complex32], fft_right: &[Complex32], height: f32) -> f32 {
+        let peak = peak_magnitude(fft_left).max(peak_magnitude(fft_right));
+        self.peaks.push_back(peak);
+        while self.peaks.len() > self.window {
+            self.peaks.pop_front();
+        }
+
+        // Hold the current gain through silence instead of amplifying the noise floor
+        let window_peak = self.peaks.iter().copied().fold(0.0, f32::max);
+        if window_peak > AUTO_GAIN_SILENCE && self.scale_factor > 0.0 {
+            let target = self
+                .decades_for(window_peak, AUTO_GAIN_TARGET * height)
+                .clamp(-MAX_AUTO_GAIN_DECADES, MAX_AUTO_GAIN_DECADES);
+            let factor = if target < self.decades {
+                self.attack
+            } else {
+                self.release
+            };
+            self.decades += (target - self.decades) * factor;
+
+            if peak > AUTO_GAIN_SILENCE {
+                self.decades = self.decades.min(self.decades_for(peak, height));
+            }
+        }
+
+        10.0_f32.powf(self.decades)
+    }
+
+    /// Gain offset, in decades, at which a bin of `magnitude` is drawn `height` pixels high.
+    fn decades_for(&self, magnitude: f32, height: f32) -> f32 {
+        height / self.scale_factor - (magnitude * self.gain).log10()
+    }
+}
+
+/// Detects sustained silence in the captured audio.
+///
+/// The gate becomes idle once the RMS level of both channels has stayed below the threshold for
+/// the hold time, and leaves the idle state as soon as a channel rises `SILENCE_HYSTERESIS_DB`
+/// above the threshold, so quiet passages near the threshold do not flap between the states.
+///
+/// # Fields
+/// - `enabled`: Whether the gate can become idle at all.
+/// - `threshold`: RMS level below which a frame counts as silent.
+/// - `release_threshold`: RMS level above which an idle gate becomes active again.
+/// - `hold`: Time the input must stay silent before the gate becomes idle.
+/// - `silent_since`: Timestamp of the first frame of the current silence, if silent.
+/// - `idle`: Whether the gate is idle.
+pub struct SilenceGate {
+    enabled: bool,
+    threshold: f32,
+    release_threshold: f32,
+    hold: Duration,
+    silent_since: Option<Duration>,
+    idle: bool,
+}
+
+impl SilenceGate {
+    /// Creates a new `SilenceGate` instance.
+    ///
+    /// # Arguments
+    /// - `settings`: Power settings providing the `idle_*` parameters.
+    pub fn new(settings: &PowerSettings) -> Self {
+        let level = |db: f32| 10_f32.powf(db / 20.0);
+        SilenceGate {
+            enabled: settings.idle_detection,
+            threshold: level(settings.idle_threshold_db),
+            release_threshold: level(settings.idle_threshold_db + SILENCE_HYSTERESIS_DB),
+            hold: Duration::from_secs_f32(settings.idle_after_secs.max(0.0)),
+            silent_since: None,
+            idle: false,
+        }
+    }
+
+    /// Updates the state from the samples of one frame.
+    ///
+    /// # Arguments
+    /// - `left`: The analyzed samples of the left channel.
+    /// - `right`: The analyzed samples of the right channel.
+    /// - `timestamp`: Time of the frame, relative to the start of the analysis.
+    ///
+    /// # Returns
+    /// - Whether the gate is idle after the frame.
+    pub fn process(&mut self, left: &[f32], right: &[f32], timestamp: Duration) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        let level = rms(left).max(rms(right));
+        if self.idle {
+            if level > self.release_threshold {
+                self.idle = false;
+                self.silent_since = None;
+            }
+        } else if level < self.threshold {
+            let silent_since = *self.silent_since.get_or_insert(timestamp);
+            self.idle = timestamp.saturating_sub(silent_since) >= self.hold;
+        } else {
+            self.silent_since = None;
+        }
+        self.idle
+    }
+
+    /// Returns whether the input has been silent for the hold time.
+    pub fn is_idle(&self) -> bool {
+        self.idle
+    }
+}
+
+/// Second-order IIR filter section in direct form I, normalized so `a0` is 1.
+///
+/// # Fields
+/// - `b`: Feedforward coefficients `b0`, `b1` and `b2`.
+/// - `a`: Feedback coefficients `a1` and `a2`.
+/// - `inputs`: The previous two input samples, latest first.
+/// - `outputs`: The previous two output samples, latest first.
+#[derive(Clone, Debug)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    inputs: [f64; 2],
+    outputs: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Biquad {
+            b,
+            a,
+            inputs: [0.0; 2],
+            outputs: [0.0; 2],
+        }
+    }
+
+    /// Filters one sample.
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.b[1] * self.inputs[0] + self.b[2] * self.inputs[1]
+            - self.a[0] * self.outputs[0]
+            - self.a[1] * self.outputs[1];
+        self.inputs = [input, self.inputs[0]];
+        self.outputs = [output, self.outputs[0]];
+        output
+    }
+}
+
+/// The two stages of the K-weighting pre-filter of ITU-R BS.1770 at `sample_rate`: a high shelf
+/// of about +4 dB modelling the acoustic effect of the head, followed by a high-pass at about
+/// 38 Hz (the RLB weighting).
+///
+/// BS.1770 only lists the coefficients at 48 kHz; they are derived here from the analog
+/// prototypes of both stages through the bilinear transform, which reproduces the listed values
+/// at 48 kHz and keeps the response at other device rates.
+fn k_weighting(sample_rate: f64) -> [Biquad; 2] {
+    // High shelf
+    let k = (PI * K_SHELF_FREQUENCY / sample_rate).tan();
+    let gain = 10_f64.powf(K_SHELF_GAIN_DB / 20.0);
+    let band = gain.powf(K_SHELF_BAND_EXPONENT);
+    let a0 = 1.0 + k / K_SHELF_Q + k * k;
+    let shelf = Biquad::new(
+        [
+            (gain + band * k / K_SHELF_Q + k * k) / a0,
+            2.0 * (k * k - gain) / a0,
+            (gain - band * k / K_SHELF_Q + k * k) / a0,
+        ],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / K_SHELF_Q + k * k) / a0],
+    );
+
+    // High-pass, left unnormalized in the passband like the coefficients of the standard
+    let k = (PI * K_HIGHPASS_FREQUENCY / sample_rate).tan();
+    let a0 = 1.0 + k / K_HIGHPASS_Q + k * k;
+    let highpass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [
+            2.0 * (k * k - 1.0) / a0,
+            (1.0 - k / K_HIGHPASS_Q + k * k) / a0,
+        ],
+    );
+
+    [shelf, highpass]
+}
+
+/// Momentary and short-term loudness meter following ITU-R BS.1770 and EBU R 128.
+///
+/// Both channels pass the K-weighting pre-filter, and their mean squares are summed over 100 ms
+/// blocks. The momentary loudness covers the latest 4 blocks (400 ms) and the short-term loudness
+/// the latest 30 blocks (3 s), both updated with every completed block, and are converted with
+/// `-0.691 + 10 * log10(mean square)`. Windows below the absolute gate of -70 LUFS are reported
+/// as silent.
+///
+/// # Fields
+/// - `filters_left`: K-weighting stages of the left channel.
+/// - `filters_right`: K-weighting stages of the right channel.
+/// - `block_len`: Number of samples per block.
+/// - `block_energy`: Sum of the squared weighted samples of both channels in the current block.
+/// - `block_samples`: Number of samples in the current block.
+/// - `blocks`: Mean square of the latest completed blocks, oldest first.
+pub struct LoudnessMeter {
+    filters_left: [Biquad; 2],
+    filters_right: [Biquad; 2],
+    block_len: usize,
+    block_energy: f64,
+    block_samples: usize,
+    blocks: VecDeque<f64>,
+}
+
+impl LoudnessMeter {
+    /// Creates a new `LoudnessMeter` instance.
+    ///
+    /// # Arguments
+    /// - `sample_rate`: Sample rate of the measured audio, in Hz.
+    pub fn new(sample_rate: f32) -> Self {
+        let sample_rate = f64::from(sample_rate.max(1.0));
+        LoudnessMeter {
+            filters_left: k_weighting(sample_rate),
+            filters_right: k_weighting(sample_rate),
+            block_len: ((sample_rate * LOUDNESS_BLOCK_SECS).round() as usize).max(1),
+            block_energy: 0.0,
+            block_samples: 0,
+            blocks: VecDeque::with_capacity(SHORT_TERM_BLOCKS),
+        }
+    }
+
+    /// Measures the next samples of both channels.
+    ///
+    /// # Arguments
+    /// - `left`: Samples of the left channel following the previously pushed ones.
+    /// - `right`: Samples of the right channel, as many as `left`.
+    pub fn push(&mut self, left: &[f32], right: &[f32]) {
+        for (&left, &right) in left.iter().zip(right) {
+            let left = self
+                .filters_left
+                .iter_mut()
+                .fold(f64::from(left), |sample, filter| filter.process(sample));
+            let right = self
+                .filters_right
+                .iter_mut()
+                .fold(f64::from(right), |sample, filter| filter.process(sample));
+            // Both front channels are weighted by 1.0
+            self.block_energy += left * left + right * right;
+            self.block_samples += 1;
+
+            if self.block_samples == self.block_len {
+                if self.blocks.len() == SHORT_TERM_BLOCKS {
+                    self.blocks.pop_front();
+                }
+                self.blocks
+                    .push_back(self.block_energy / self.block_len as f64);
+                self.block_energy = 0.0;
+                self.block_samples = 0;
+            }
+        }
+    }
+
+    /// Returns the loudness of the latest 400 ms, in LUFS.
+    ///
+    /// # Returns
+    /// - `None` until 400 ms have been measured, or while the window is below the absolute gate.
+    pub fn momentary_lufs(&self) -> Option<f32> {
+        self.window_lufs(MOMENTARY_BLOCKS)
+    }
+
+    /// Returns the loudness of the latest 3 s, in LUFS.
+    ///
+    /// # Returns
+    /// - `None` until 3 s have been measured, or while the window is below the absolute gate.
+    pub fn short_term_lufs(&self) -> Option<f32> {
+        self.window_lufs(SHORT_TERM_BLOCKS)
+    }
+
+    /// Loudness of the latest `blocks` blocks, or `None` if they are not complete or gated.
+    fn window_lufs(&self, blocks: usize) -> Option<f32> {
+        if self.blocks.len() < blocks {
+            return None;
+        }
+        let mean_square = self.blocks.iter().rev().take(blocks).sum::<f64>() / blocks as f64;
+        let lufs = LOUDNESS_OFFSET_DB + 10.0 * mean_square.max(f64::MIN_POSITIVE).log10();
+        (lufs >= ABSOLUTE_GATE_LUFS).then_some(lufs as f32)
+    }
+}
+
+/// Detects the true peak of one channel by 4x oversampling with the polyphase interpolation
+/// filter of ITU-R BS.1770 Annex 2.
+///
+/// # Fields
+/// - `history`: The latest `TRUE_PEAK_TAPS` input samples, latest first.
+#[derive(Clone, Debug, Default)]
+struct TruePeakDetector {
+    history: [f32; TRUE_PEAK_TAPS],
+}
+
+impl TruePeakDetector {
+    /// Oversamples the next samples of the channel.
+    ///
+    /// # Returns
+    /// - The largest absolute value of the samples and their interpolated values in between.
+    fn process(&mut self, samples: &[f32]) -> f32 {
+        let mut peak = 0.0_f32;
+        for &sample in samples {
+            self.history.copy_within(..TRUE_PEAK_TAPS - 1, 1);
+            self.history[0] = sample;
+            for phase in &TRUE_PEAK_PHASES {
+                let value: f32 = phase
+                    .iter()
+                    .zip(&self.history)
+                    .map(|(tap, sample)| tap * sample)
+                    .sum();
+                peak = peak.max(value.abs());
+            }
+        }
+        peak
+    }
+}
+
+/// True-peak meter of both channels with a peak hold and a latching clip indicator.
+///
+/// Each channel's true peak is held for `TRUE_PEAK_HOLD_SECS` before following the current
+/// level. The clip indicator latches as soon as either channel exceeds `CLIP_THRESHOLD_DBTP`,
+/// and stays set until cleared.
+///
+/// # Fields
+/// - `detectors`: True-peak detectors of the left and right channels.
+/// - `held`: Held true peak of each channel, as a linear amplitude.
+/// - `held_for`: Number of samples each held peak has been held for.
+/// - `hold_len`: Number of samples a peak is held for.
+/// - `clipped`: Whether a channel exceeded the clip threshold since the last clear.
+pub struct TruePeakMeter {
+    detectors: [TruePeakDetector; 2],
+    held: [f32; 2],
+    held_for: [usize; 2],
+    hold_len: usize,
+    clipped: bool,
+}
+
+impl TruePeakMeter {
+    /// Creates a new `TruePeakMeter` instance.
+    ///
+    /// # Arguments
+    /// - `sample_rate`: Sample rate of the measured audio, in Hz.
+    pub fn new(sample_rate: f32) -> Self {
+        TruePeakMeter {
+            detectors: Default::default(),
+            held: [0.0; 2],
+            held_for: [0; 2],
+            hold_len: (sample_rate.max(1.0) * TRUE_PEAK_HOLD_SECS) as usize,
+            clipped: false,
+        }
+    }
+
+    /// Measures the next samples of both channels.
+    ///
+    /// # Arguments
+    /// - `left`: Samples of the left channel following the previously pushed ones.
+    /// - `right`: Samples of the right channel, as many as `left`.
+    pub fn push(&mut self, left: &[f32], right: &[f32]) {
+        let clip_level = 10_f32.powf(CLIP_THRESHOLD_DBTP / 20.0);
+        for (channel, samples) in [left, right].into_iter().enumerate() {
+            let peak = self.detectors[channel].process(samples);
+            self.held_for[channel] += samples.len();
+            if peak >= self.held[channel] || self.held_for[channel] > self.hold_len {
+                self.held[channel] = peak;
+                self.held_for[channel] = 0;
+            }
+            self.clipped |= peak > clip_level;
+        }
+    }
+
+    /// Returns the held true peak of the left and right channels, in dBTP.
+    ///
+    /// Silent channels report negative infinity.
+    pub fn peak_dbtp(&self) -> (f32, f32) {
+        let dbtp = |peak: f32| 20.0 * peak.log10();
+        (dbtp(self.held[0]), dbtp(self.held[1]))
+    }
+
+    /// Returns whether a channel exceeded `CLIP_THRESHOLD_DBTP` since the last `clear_clip`.
+    pub fn is_clipped(&self) -> bool {
+        self.clipped
+    }
+
+    /// Clears the clip indicator.
+    pub fn clear_clip(&mut self) {
+        self.clipped = false;
+    }
+}
+
+/// Root mean square of a signal, or `0.0` if it is empty.
+fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+/// Largest bin magnitude in the lower half of a spectrum, excluding the DC bin.
+fn peak_magnitude(spectrum: &[Complex32]) -> f32 {
+    spectrum
+        .iter()
+        .take(spectrum.len() / 2)
+        .skip(1)
+        .map(|value| value.norm())
+        .fold(0.0, f32::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustfft::FftPlanner;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const SAMPLE_RATE: usize = 44100;
+    const FRAME_SIZE: usize = 1024;
+
+    fn settings() -> BeatSettings {
+        BeatSettings {
+            enabled: true,
+            sensitivity: 2.0,
+            min_interval_ms: 150,
+            flash: true,
+        }
+    }
+
+    /// Builds a signal with low-level noise and a short click every `interval` samples.
+    fn click_track(seconds: usize, interval: usize) -> (Vec<f32>, usize) {
+        let mut state: u32 = 0x1234_5678;
+        let mut signal: Vec<f32> = (0..seconds * SAMPLE_RATE)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state as f32 / u32::MAX as f32 - 0.5) * 0.01
+            })
+            .collect();
+
+        let mut clicks = 0;
+        let mut position = interval;
+        while position + 32 < signal.len() {
+            for sample in &mut signal[position..position + 32] {
+                *sample += 0.8;
+            }
+            clicks += 1;
+            position += interval;
+        }
+
+        (signal, clicks)
+    }
+
+    /// Runs the detector over consecutive frames of `signal`, returning the number of beats.
+    fn count_beats(detector: &mut BeatDetector, signal: &[f32]) -> usize {
+        let fft = FftPlanner::new().plan_fft_forward(FRAME_SIZE);
+        let mut beats = 0;
+
+        for (index, frame) in signal.chunks_exact(FRAME_SIZE).enumerate() {
+            let mut buffer: Vec<Complex32> =
+                frame.iter().map(|&x| Complex32::new(x, 0.0)).collect();
+            fft.process(&mut buffer);
+
+            let timestamp =
+                Duration::from_secs_f64((index * FRAME_SIZE) as f64 / SAMPLE_RATE as f64);
+            if detector.process(&buffer, timestamp).is_some() {
+                beats += 1;
+            }
+        }
+
+        beats
+    }
+
+    #[test]
+    fn detects_every_click_of_a_click_track() {
+        // A click every half second for ten seconds
+        let (signal, clicks) = click_track(10, SAMPLE_RATE / 2);
+        let mut detector = BeatDetector::new(&settings());
+
+        assert_eq!(count_beats(&mut detector, &signal), clicks);
+    }
+
+    #[test]
+    fn refractory_period_suppresses_close_beats() {
+        // Clicks every half second with a two second minimum interval between beats
+        let (signal, clicks) = click_track(10, SAMPLE_RATE / 2);
+        let mut detector = BeatDetector::new(&BeatSettings {
+            min_interval_ms: 2000,
+            ..settings()
+        });
+
+        let beats = count_beats(&mut detector, &signal);
+        assert!(beats >= 2);
+        assert!(beats * 3 <= clicks, "{} beats for {} clicks", beats, clicks);
+    }
+
+    #[test]
+    fn callbacks_receive_every_beat() {
+        let (signal, clicks) = click_track(5, SAMPLE_RATE / 2);
+        let received = Arc::new(AtomicUsize::new(0));
+        let mut detector = BeatDetector::new(&settings());
+
+        let counter = received.clone();
+        detector.on_beat(Box::new(move |event| {
+            assert!(event.strength > 0.0);
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+
+        let beats = count_beats(&mut detector, &signal);
+        assert_eq!(beats, clicks);
+        assert_eq!(received.load(Ordering::SeqCst), beats);
+    }
+
+    fn analyzer(attack: f32, release: f32) -> SpectrumAnalyzer {
+        SpectrumAnalyzer {
+            smoothed: Vec::new(),
+            attack,
+            release,
+            bin_smoothing: 0,
+            noise_profile: None,
+            calibration: None,
+        }
+    }
+
+    fn run_frame(analyzer: &mut SpectrumAnalyzer, magnitude: f32) -> f32 {
+        let mut spectrum = vec![Complex32::new(magnitude, 0.0); 4];
+        analyzer.process(&mut spectrum);
+        spectrum[0].norm()
+    }
+
+    #[test]
+    fn attack_follows_a_rising_step_with_its_time_constant() {
+        let mut analyzer = analyzer(0.5, 0.1);
+        run_frame(&mut analyzer, 0.0);
+
+        for frame in 1..=5 {
+            let value = run_frame(&mut analyzer, 1.0);
+            let expected = 1.0 - 0.5_f32.powi(frame);
+            assert!(
+                (value - expected).abs() < 1e-6,
+                "frame {}: {}",
+                frame,
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn release_follows_a_falling_step_with_its_time_constant() {
+        let mut analyzer = analyzer(0.5, 0.1);
+        run_frame(&mut analyzer, 1.0);
+
+        for frame in 1..=5 {
+            let value = run_frame(&mut analyzer, 0.0);
+            let expected = 0.9_f32.powi(frame);
+            assert!(
+                (value - expected).abs() < 1e-6,
+                "frame {}: {}",
+                frame,
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn smoothing_preserves_phase() {
+        let mut analyzer = analyzer(0.5, 0.5);
+        let mut spectrum = vec![Complex32::new(0.0, 2.0)];
+        analyzer.process(&mut spectrum);
+
+        let mut spectrum = vec![Complex32::new(0.0, 4.0)];
+        analyzer.process(&mut spectrum);
+        assert!(spectrum[0].re.abs() < 1e-6);
+        assert!((spectrum[0].im - 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn unit_factors_disable_smoothing() {
+        let mut analyzer = analyzer(1.0, 1.0);
+        for magnitude in [0.0, 3.0, 1.0, 5.0, 0.0] {
+            assert_eq!(run_frame(&mut analyzer, magnitude), magnitude);
+        }
+    }
+
+    #[test]
+    fn bin_smoothing_is_applied_before_temporal_smoothing() {
+        let mut analyzer = SpectrumAnalyzer {
+            smoothed: Vec::new(),
+            attack: 0.5,
+            release: 0.5,
+            bin_smoothing: 1,
+            noise_profile: None,
+            calibration: None,
+        };
+
+        let mut spectrum = vec![Complex32::new(0.0, 0.0); 5];
+        analyzer.process(&mut spectrum);
+
+        // Impulse of 4.0 spreads to 1.0, 2.0, 1.0, then moves halfway there
+        let mut spectrum = vec![Complex32::new(0.0, 0.0); 5];
+        spectrum[2] = Complex32::new(4.0, 0.0);
+        analyzer.process(&mut spectrum);
+
+        let magnitudes: Vec<f32> = spectrum.iter().map(|value| value.norm()).collect();
+        let expected = [0.0, 0.5, 1.0, 0.5, 0.0];
+        for (value, expected) in magnitudes.iter().zip(expected) {
+            assert!((value - expected).abs() < 1e-6, "{:?}", magnitudes);
+        }
+    }
+
+    /// Builds `frames` consecutive spectra of white noise, optionally mixed with a sine at `bin`.
+    fn noise_frames(frames: usize, tone: Option<(usize, f32)>) -> Vec<Vec<Complex32>> {
+        let fft = FftPlanner::new().plan_fft_forward(FRAME_SIZE);
+        let mut state: u32 = 0x9e37_79b9;
+
+        (0..frames)
+            .map(|frame| {
+                let mut buffer: Vec<Complex32> = (0..FRAME_SIZE)
+                    .map(|n| {
+                        state ^= state << 13;
+                        state ^= state >> 17;
+                        state ^= state << 5;
+                        let mut sample = (state as f32 / u32::MAX as f32 - 0.5) * 0.05;
+                        if let Some((bin, amplitude)) = tone {
+                            let t = (frame * FRAME_SIZE + n) as f32;
+                            sample += amplitude
+                                * (2.0 * std::f32::consts::PI * bin as f32 * t / FRAME_SIZE as f32)
+                                    .sin();
+                        }
+                        Complex32::new(sample, 0.0)
+                    })
+                    .collect();
+                fft.process(&mut buffer);
+                buffer
+            })
+            .collect()
+    }
+
+    /// Mean magnitude of the first half of the spectrum, excluding bins near `skip`.
+    fn mean_magnitude(spectrum: &[Complex32], skip: usize) -> f32 {
+        let values: Vec<f32> = spectrum[1..FRAME_SIZE / 2]
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| (*index + 1).abs_diff(skip) > 2)
+            .map(|(_, value)| value.norm())
+            .collect();
+        values.iter().sum::<f32>() / values.len() as f32
+    }
+
+    #[test]
+    fn calibration_averages_the_raw_magnitudes() {
+        let mut analyzer = analyzer(1.0, 1.0);
+        analyzer.start_calibration(2);
+        assert!(analyzer.is_calibrating());
+
+        run_frame(&mut analyzer, 1.0);
+        assert!(analyzer.is_calibrating());
+        run_frame(&mut analyzer, 3.0);
+        assert!(!analyzer.is_calibrating());
+        assert_eq!(analyzer.noise_profile(), Some(&[2.0; 4][..]));
+
+        // Magnitudes are reduced by the profile and floored at zero
+        assert_eq!(run_frame(&mut analyzer, 5.0), 3.0);
+        assert_eq!(run_frame(&mut analyzer, 1.0), 0.0);
+
+        analyzer.set_noise_profile(None);
+        assert_eq!(run_frame(&mut analyzer, 1.0), 1.0);
+    }
+
+    #[test]
+    fn noise_subtraction_keeps_the_tone_and_removes_the_noise() {
+        const TONE_BIN: usize = 64;
+        let mut analyzer = analyzer(1.0, 1.0);
+
+        let calibration_frames = noise_frames(40, None);
+        analyzer.start_calibration(calibration_frames.len());
+        for mut frame in calibration_frames {
+            analyzer.process(&mut frame);
+        }
+        assert!(!analyzer.is_calibrating());
+
+        let mut frame = noise_frames(1, Some((TONE_BIN, 0.5))).remove(0);
+        let raw_noise = mean_magnitude(&frame, TONE_BIN);
+        let raw_tone = frame[TONE_BIN].norm();
+        analyzer.process(&mut frame);
+
+        let residual_noise = mean_magnitude(&frame, TONE_BIN);
+        assert!(
+            residual_noise < raw_noise * 0.5,
+            "noise {} -> {}",
+            raw_noise,
+            residual_noise
+        );
+        assert!(frame[TONE_BIN].norm() > raw_tone * 0.9);
+    }
+
+    /// Height of the tallest bar drawn for a frame peaking at `magnitude` after automatic gain.
+    fn auto_gain_frame(auto_gain: &mut AutoGain, magnitude: f32, height: f32) -> f32 {
+        let mut spectrum = vec![Complex32::new(0.0, 0.0); 16];
+        spectrum[3] = Complex32::new(magnitude, 0.0);
+
+        let factor = auto_gain.update(&spectrum, &spectrum, height);
+        (magnitude * auto_gain.gain * factor + 1e-6)
+            .log10()
+            .max(0.0)
+            * auto_gain.scale_factor
+    }
+
+    fn auto_gain() -> AutoGain {
+        AutoGain {
+            peaks: VecDeque::new(),
+            window: 100,
+            decades: 0.0,
+            gain: 20.0,
+            scale_factor: 90.0,
+            attack: 0.5,
+            release: 0.05,
+        }
+    }
+
+    #[test]
+    fn auto_gain_fills_the_display_with_a_quiet_signal() {
+        let mut auto_gain = auto_gain();
+        let height = 250.0;
+
+        // Without automatic gain the bar would only be 90 pixels high
+        let first = auto_gain_frame(&mut auto_gain, 0.5, height);
+        assert!(first < 100.0, "first frame was {} pixels", first);
+
+        let mut bar = 0.0;
+        for _ in 0..300 {
+            bar = auto_gain_frame(&mut auto_gain, 0.5, height);
+        }
+        assert!(
+            (bar - 0.9 * height).abs() < 0.02 * height,
+            "bar was {}",
+            bar
+        );
+    }
+
+    #[test]
+    fn auto_gain_does_not_overshoot_on_a_sudden_loud_signal() {
+        let mut auto_gain = auto_gain();
+        let height = 250.0;
+        for _ in 0..300 {
+            auto_gain_frame(&mut auto_gain, 0.5, height);
+        }
+
+        let mut overshooting_frames = 0;
+        let mut bar = 0.0;
+        for _ in 0..200 {
+            bar = auto_gain_frame(&mut auto_gain, 500.0, height);
+            if bar > height + 1e-3 {
+                overshooting_frames += 1;
+            }
+        }
+        assert!(overshooting_frames <= 2);
+        assert!(
+            (bar - 0.9 * height).abs() < 0.02 * height,
+            "bar was {}",
+            bar
+        );
+    }
+
+    #[test]
+    fn auto_gain_holds_through_silence() {
+        let mut auto_gain = auto_gain();
+        for _ in 0..50 {
+            auto_gain_frame(&mut auto_gain, 0.5, 250.0);
+        }
+
+        // Once the window holds only silence the gain stops moving
+        for _ in 0..auto_gain.window {
+            auto_gain_frame(&mut auto_gain, 0.0, 250.0);
+        }
+        let decades = auto_gain.decades;
+        for _ in 0..50 {
+            auto_gain_frame(&mut auto_gain, 0.0, 250.0);
+        }
+        assert_eq!(auto_gain.decades, decades);
+    }
+
+    fn silence_gate() -> SilenceGate {
+        SilenceGate::new(&PowerSettings {
+            idle_threshold_db: -60.0,
+            idle_after_secs: 2.0,
+            ..PowerSettings::default()
+        })
+    }
+
+    #[test]
+    fn silence_gate_becomes_idle_after_the_hold_time() {
+        let mut gate = silence_gate();
+        let silence = [0.0; 64];
+        assert!(!gate.process(&silence, &silence, Duration::from_secs(10)));
+        assert!(!gate.process(&silence, &silence, Duration::from_millis(11_900)));
+        assert!(gate.process(&silence, &silence, Duration::from_secs(12)));
+    }
+
+    #[test]
+    fn sound_restarts_the_hold_time() {
+        let mut gate = silence_gate();
+        let silence = [0.0; 64];
+        let tone = [0.5; 64];
+        gate.process(&silence, &silence, Duration::from_secs(0));
+        gate.process(&silence, &tone, Duration::from_millis(1500));
+        assert!(!gate.process(&silence, &silence, Duration::from_millis(2500)));
+        assert!(gate.process(&silence, &silence, Duration::from_millis(4500)));
+    }
+
+    #[test]
+    fn silence_gate_leaves_idle_only_above_the_hysteresis() {
+        let mut gate = silence_gate();
+        let silence = [0.0; 64];
+        gate.process(&silence, &silence, Duration::from_secs(0));
+        assert!(gate.process(&silence, &silence, Duration::from_secs(2)));
+
+        // -57 dB is above the threshold but within the hysteresis
+        let quiet = [10_f32.powf(-57.0 / 20.0); 64];
+        assert!(gate.process(&quiet, &quiet, Duration::from_secs(3)));
+        // -50 dB wakes the gate immediately
+        let louder = [10_f32.powf(-50.0 / 20.0); 64];
+        assert!(!gate.process(&louder, &silence, Duration::from_millis(3030)));
+        assert!(!gate.is_idle());
+    }
+
+    #[test]
+    fn disabled_silence_gate_is_never_idle() {
+        let mut gate = SilenceGate::new(&PowerSettings {
+            idle_detection: false,
+            ..PowerSettings::default()
+        });
+        let silence = [0.0; 64];
+        gate.process(&silence, &silence, Duration::from_secs(0));
+        assert!(!gate.process(&silence, &silence, Duration::from_secs(3600)));
+    }
+
+    /// Returns `seconds` of a 1 kHz sine at `level_dbfs` peak level.
+    fn sine(seconds: f32, level_dbfs: f32, sample_rate: f32) -> Vec<f32> {
+        let amplitude = 10_f32.powf(level_dbfs / 20.0);
+        (0..(seconds * sample_rate).round() as usize)
+            .map(|i| amplitude * (std::f32::consts::TAU * 1000.0 * i as f32 / sample_rate).sin())
+            .collect()
+    }
+
+    #[test]
+    fn k_weighting_matches_the_coefficients_of_the_standard() {
+        // Table 1 and Table 2 of ITU-R BS.1770-4, at 48 kHz
+        let [shelf, highpass] = k_weighting(48000.0);
+        let expected_shelf = (
+            [
+                1.535_124_859_586_97,
+                -2.691_696_189_406_38,
+                1.198_392_810_852_85,
+            ],
+            [-1.690_659_293_182_41, 0.732_480_774_215_85],
+        );
+        let expected_highpass = (
+            [1.0, -2.0, 1.0],
+            [-1.990_047_454_833_98, 0.990_072_250_366_21],
+        );
+
+        for (filter, (b, a)) in [(shelf, expected_shelf), (highpass, expected_highpass)] {
+            for (actual, expected) in filter.b.iter().zip(b).chain(filter.a.iter().zip(a)) {
+                assert!(
+                    (actual - expected).abs() < 1e-8,
+                    "{} != {}",
+                    actual,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn calibration_sine_measures_its_level_at_any_sample_rate() {
+        for sample_rate in [44100.0, 48000.0, 96000.0] {
+            // A 1 kHz sine at -23 dBFS in both channels reads -23 LUFS (EBU Tech 3341)
+            let calibration = sine(3.0, -23.0, sample_rate);
+            let mut meter = LoudnessMeter::new(sample_rate);
+            meter.push(&calibration, &calibration);
+            let momentary = meter.momentary_lufs().unwrap();
+            let short_term = meter.short_term_lufs().unwrap();
+            assert!(
+                (momentary + 23.0).abs() < 0.1,
+                "{} Hz: {}",
+                sample_rate,
+                momentary
+            );
+            assert!(
+                (short_term + 23.0).abs() < 0.1,
+                "{} Hz: {}",
+                sample_rate,
+                short_term
+            );
+
+            // A full-scale 1 kHz sine in one channel reads -3.01 LKFS (BS.1770-4, 2.1)
+            let full_scale = sine(0.4, 0.0, sample_rate);
+            let mut meter = LoudnessMeter::new(sample_rate);
+            meter.push(&full_scale, &vec![0.0; full_scale.len()]);
+            let momentary = meter.momentary_lufs().unwrap();
+            assert!(
+                (momentary + 3.01).abs() < 0.1,
+                "{} Hz: {}",
+                sample_rate,
+                momentary
+            );
+        }
+    }
+
+    #[test]
+    fn loudness_needs_full_windows_above_the_absolute_gate() {
+        let sample_rate = 48000.0;
+        let mut meter = LoudnessMeter::new(sample_rate);
+        let tone = sine(0.3, -23.0, sample_rate);
+        meter.push(&tone, &tone);
+        assert_eq!(meter.momentary_lufs(), None);
+
+        // Pushing in small chunks fills the blocks like one long push
+        for chunk in sine(0.1, -23.0, sample_rate).chunks(441) {
+            meter.push(chunk, chunk);
+        }
+        assert!(meter.momentary_lufs().is_some());
+        assert_eq!(meter.short_term_lufs(), None);
+
+        // -80 dBFS in both channels is about -80 LUFS, below the gate
+        let mut meter = LoudnessMeter::new(sample_rate);
+        let quiet = sine(0.4, -80.0, sample_rate);
+        meter.push(&quiet, &quiet);
+        assert_eq!(meter.momentary_lufs(), None);
+
+        let mut meter = LoudnessMeter::new(sample_rate);
+        let silence = vec![0.0; 19200];
+        meter.push(&silence, &silence);
+        assert_eq!(meter.momentary_lufs(), None);
+    }
+
+    #[test]
+    fn true_peak_finds_peaks_between_samples() {
+        // A quarter of the sample rate, with every sample 45 degrees away from the peaks
+        let samples: Vec<f32> = (0..4800)
+            .map(|i| (std::f32::consts::FRAC_PI_2 * i as f32 + std::f32::consts::FRAC_PI_4).sin())
+            .collect();
+        let sample_peak = samples
+            .iter()
+            .fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+        assert!((20.0 * sample_peak.log10() + 3.01).abs() < 0.01);
+
+        let mut meter = TruePeakMeter::new(48000.0);
+        meter.push(&samples, &samples);
+        let (left, right) = meter.peak_dbtp();
+        assert_eq!(left, right);
+        assert!(left > 20.0 * sample_peak.log10() + 2.5, "{} dBTP", left);
+        assert!(left.abs() < 0.5, "{} dBTP", left);
+        assert!(meter.is_clipped());
+    }
+
+    #[test]
+    fn true_peak_is_held_and_clipping_latches_until_cleared() {
+        let sample_rate = 48000.0;
+        let mut meter = TruePeakMeter::new(sample_rate);
+        assert_eq!(meter.peak_dbtp().0, f32::NEG_INFINITY);
+
+        let quiet = sine(0.1, -20.0, sample_rate);
+        meter.push(&quiet, &quiet);
+        assert!(!meter.is_clipped());
+
+        let loud = sine(0.1, -0.5, sample_rate);
+        meter.push(&loud, &vec![0.0; loud.len()]);
+        assert!(meter.is_clipped());
+        let (left, right) = meter.peak_dbtp();
+        assert!((left + 0.5).abs() < 0.2, "{} dBTP", left);
+        assert!((right + 20.0).abs() < 0.2, "{} dBTP", right);
+
+        // The peak is held for two seconds, the clip indicator until it is cleared
+        let one_second = sine(1.0, -20.0, sample_rate);
+        meter.push(&one_second, &one_second);
+        assert!((meter.peak_dbtp().0 + 0.5).abs() < 0.2);
+        meter.push(&one_second, &one_second);
+        meter.push(&one_second, &one_second);
+        assert!((meter.peak_dbtp().0 + 20.0).abs() < 0.2);
+        assert!(meter.is_clipped());
+
+        meter.clear_clip();
+        assert!(!meter.is_clipped());
+    }
+}
@@ -0,0 +1,80 @@
+use gtk::prelude::*;
+use gtk::{Application, ApplicationWindow, Button, Grid, Label, ScrolledWindow, TextView};
+use gtk4 as gtk;
+
+/// Spacing between the rows of the error window, in pixels.
+const SPACING: i32 = 8;
+/// Initial size of the error text, in pixels.
+const TEXT_SIZE: (i32, i32) = (560, 240);
+
+/// Opens a window showing why the visualizer could not start, for users who launched it from a
+/// desktop file and never see its standard error.
+///
+/// # Arguments
+/// - `application`: The application the window belongs to; "Quit" quits it.
+/// - `errors`: The errors preventing the start, each shown as a paragraph of the error text.
+///
+/// # Returns
+/// - The error window, already shown.
+///
+/// The error text can be selected and copied, e.g. for a bug report.
+pub fn show(application: &Application, errors: &[String]) -> ApplicationWindow {
+    let window = ApplicationWindow::new(application);
+    window.set_title(Some("Startup failed"));
+    // The menu of the visualizer windows has nothing to offer here
+    window.set_show_menubar(false);
+
+    let grid = Grid::new();
+    grid.set_row_spacing(SPACING as u32);
+    grid.set_margin_top(2 * SPACING);
+    grid.set_margin_bottom(2 * SPACING);
+    grid.set_margin_start(2 * SPACING);
+    grid.set_margin_end(2 * SPACING);
+
+    let heading = Label::new(Some("The visualizer could not start:"));
+    heading.set_xalign(0.0);
+    heading.set_wrap(true);
+    grid.attach(&heading, 0, 0, 1, 1);
+
+    let text = TextView::new();
+    text.set_editable(false);
+    text.set_cursor_visible(false);
+    text.set_monospace(true);
+    text.set_wrap_mode(gtk::WrapMode::WordChar);
+    text.buffer().set_text(&errors.join("\n\n"));
+    let scrolled = ScrolledWindow::new();
+    scrolled.set_child(Some(&text));
+    scrolled.set_min_content_width(TEXT_SIZE.0);
+    scrolled.set_min_content_height(TEXT_SIZE.1);
+    scrolled.set_hexpand(true);
+    scrolled.set_vexpand(true);
+    grid.attach(&scrolled, 0, 1, 1, 1);
+
+    let quit = Button::with_label("Quit");
+    quit.set_halign(gtk::Align::End);
+    let application = application.clone();
+    quit.connect_clicked(move |_| application.quit());
+    grid.attach(&quit, 0, 2, 1, 1);
+
+    window.set_child(Some(&grid));
+    window.present();
+    window
+}
+
+/// Runs an application showing only the error window, for errors found before the visualizer's
+/// application is set up.
+///
+/// # Arguments
+/// - `application_id`: The application ID of the visualizer.
+/// - `errors`: The errors preventing the start.
+pub fn run(application_id: &str, errors: Vec<String>) {
+    let application = Application::builder()
+        .application_id(application_id)
+        .build();
+    application.connect_activate(move |app| {
+        show(app, &errors);
+    });
+    // Command-line options were handled before the error, so GTK only receives the program name
+    let program = std::env::args().next().unwrap_or_default();
+    application.run_with_args(&[program]);
+}
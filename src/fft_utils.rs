@@ -1,26 +1,101 @@
+use crate::color::Palette;
+use crate::settings::{ColorMode, FFTSettings};
+use rustfft::num_complex::Complex32;
+use rustfft::{Fft, FftPlanner};
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Set once the invalid frequency range warning has been printed, so it is not repeated per frame.
+static RANGE_WARNING_SHOWN: AtomicBool = AtomicBool::new(false);
+
 /// Calculates a color corresponding to a specific frequency range.
 ///
 /// # Arguments
+/// - `palette`: The palette the frequency position is looked up in.
+/// - `position`: Position (0.0 to 1.0) of the frequency in the spectrum.
+/// - `lightness`: The lightness level (0.0 to 1.0), where 0.5 gives the pure palette color.
+///
+/// # Returns
+/// - A tuple `(f32, f32, f32)` representing the RGB color values, each in the range [0.0, 1.0].
+///
+/// This function maps the frequency position to a position in the palette to create a smooth
+/// gradient across the entire frequency range.
+pub fn get_color_for_frequency(
+    palette: &Palette,
+    position: f32,
+    lightness: f32,
+) -> (f32, f32, f32) {
+    palette.color_at_lightness(position, lightness)
+}
+
+/// Calculates the color of a bar according to the configured color mode.
+///
+/// # Arguments
+/// - `palette`: The palette colors are looked up in.
+/// - `color_mode`: Whether the color follows frequency, magnitude, or both.
 /// - `index`: The index of the current frequency bar.
 /// - `total_bars`: The total number of frequency bars in the visualizer.
+/// - `level`: The bar height normalized to the drawing area, in the range [0.0, 1.0].
 ///
 /// # Returns
-/// - A tuple `(f32, f32, f32)` representing the RGB color values, each in the range [0.0, 1.0].
+/// - A tuple `(f32, f32, f32, f32)` of RGB values and an opacity multiplier, each in [0.0, 1.0].
+pub fn get_bar_color(
+    palette: &Palette,
+    color_mode: ColorMode,
+    index: usize,
+    total_bars: usize,
+    level: f32,
+) -> (f32, f32, f32, f32) {
+    let position = index as f32 / total_bars as f32;
+    get_bar_color_at(palette, color_mode, position, level)
+}
+
+/// Calculates the color of a bar at a palette position according to the configured color mode.
+///
+/// # Arguments
+/// - `palette`: The palette colors are looked up in.
+/// - `color_mode`: Whether the color follows frequency, magnitude, or both.
+/// - `position`: Palette position (0.0 to 1.0) of the bar's frequency.
+/// - `level`: The bar height normalized to the drawing area, in the range [0.0, 1.0].
 ///
-/// This function maps the frequency index to a hue value and converts it from HSL to RGB to
-/// create a smooth gradient across the entire frequency range.
-pub fn get_color_for_frequency(index: usize, total_bars: usize) -> (f32, f32, f32) {
-    let frequency_ratio = index as f32 / total_bars as f32; // Calculate the position in the spectrum
-    let hue = frequency_ratio * 360.0; // Map this position to a hue value (0-360 degrees)
-    hsl_to_rgb(hue, 1.0, 0.5) // Convert HSL to RGB with full saturation and 50% lightness
+/// # Returns
+/// - A tuple `(f32, f32, f32, f32)` of RGB values and an opacity multiplier, each in [0.0, 1.0].
+pub fn get_bar_color_at(
+    palette: &Palette,
+    color_mode: ColorMode,
+    position: f32,
+    level: f32,
+) -> (f32, f32, f32, f32) {
+    let level = level.clamp(0.0, 1.0);
+
+    match color_mode {
+        ColorMode::Frequency => {
+            let (r, g, b) = get_color_for_frequency(palette, position, 0.5);
+            (r, g, b, 1.0)
+        }
+        ColorMode::Magnitude => {
+            let (r, g, b) = palette.color_at(level);
+            (r, g, b, 1.0)
+        }
+        ColorMode::Both => {
+            // Quiet bars are darker and more transparent, full-height bars get the pure color
+            let lightness = 0.15 + 0.35 * level;
+            let (r, g, b) = get_color_for_frequency(palette, position, lightness);
+            (r, g, b, 0.4 + 0.6 * level)
+        }
+    }
 }
 
 /// Converts an HSL color value to RGB color space.
 ///
 /// # Arguments
-/// - `hue`: The hue angle in degrees (0-360), where different values represent distinct colors.
+/// - `hue`: The hue angle in degrees, where different values represent distinct colors. Values
+///   outside 0-360 wrap around the color wheel.
 /// - `saturation`: The saturation level (0.0 to 1.0), where 1.0 is fully saturated and 0.0 is grayscale.
+///   Values outside this range are clamped.
 /// - `lightness`: The lightness level (0.0 to 1.0), where 0.5 gives pure color, 0.0 is black, and 1.0 is white.
+///   Values outside this range are clamped.
 ///
 /// # Returns
 /// - A tuple `(f32, f32, f32)` representing the RGB color values, each in the range [0.0, 1.0].
@@ -28,6 +103,10 @@ pub fn get_color_for_frequency(index: usize, total_bars: usize) -> (f32, f32, f3
 /// This function performs calculations based on the HSL color model and handles different hue ranges
 /// to generate the correct RGB output.
 pub fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> (f32, f32, f32) {
+    let hue = hue.rem_euclid(360.0); // Wrap the hue onto the color wheel
+    let saturation = saturation.clamp(0.0, 1.0);
+    let lightness = lightness.clamp(0.0, 1.0);
+
     let c = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation; // Chroma: color intensity
     let x = c * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs()); // Intermediate value for hue transitions
     let m = lightness - c / 2.0; // Match the lightness level
@@ -57,11 +136,1299 @@ pub fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> (f32, f32, f32)
 /// - `current`: The current value to be adjusted.
 /// - `target`: The target value to move towards.
 /// - `factor`: The interpolation factor (0.0 to 1.0), where 0.0 means no change and 1.0 means instant change.
+///   Factors outside the range are clamped into it, and NaN moves to the target at once.
 ///
 /// # Returns
-/// - `f32`: The interpolated value, closer to the target depending on the factor.
+/// - `f32`: The interpolated value, closer to the target depending on the factor, or `0.0` if it
+///   is not finite.
 ///
 /// This function is useful for gradually adjusting values, like animating transitions or smoothing data.
+/// Values it returns are fed back in as `current` every frame, so a single infinite or NaN
+/// target, such as from a glitched audio buffer, is dropped rather than kept forever.
 pub fn interpolate(current: f32, target: f32, factor: f32) -> f32 {
-    current + (target - current) * factor
+    let factor = if factor.is_nan() {
+        1.0
+    } else {
+        factor.clamp(0.0, 1.0)
+    };
+    let value = current + (target - current) * factor;
+    if value.is_finite() {
+        value
+    } else {
+        0.0
+    }
+}
+
+/// Opacity multiplier of the bars outside a soloed band.
+const DIMMED_BAND_ALPHA: f32 = 0.25;
+
+/// Returns the opacity multiplier of a bar while a band may be soloed.
+///
+/// # Arguments
+/// - `solo_band`: The soloed band as `(low, high)` in Hz, or `None` if no band is soloed.
+/// - `frequency`: Center frequency of the bar, in Hz.
+///
+/// # Returns
+/// - `1.0` inside the band or without one, and `DIMMED_BAND_ALPHA` outside of it.
+pub fn band_emphasis(solo_band: Option<(f32, f32)>, frequency: f32) -> f32 {
+    match solo_band {
+        Some((low, high)) if frequency < low || frequency > high => DIMMED_BAND_ALPHA,
+        _ => 1.0,
+    }
+}
+
+/// Frame rate at which a per-frame interpolation factor is converted into a time constant.
+const REFERENCE_FRAME_RATE: f32 = 60.0;
+
+/// Computes how far smoothed values move toward their targets in one frame.
+///
+/// # Arguments
+/// - `elapsed`: Time since the previous frame, in seconds.
+/// - `time_constant`: Time the values take to cover about 63% of a change, in seconds.
+///
+/// # Returns
+/// - The fraction of the change applied, in [0.0, 1.0]; `1.0` without smoothing, or with a
+///   negative or NaN time constant.
+///
+/// Applying the factors of several short frames covers the same change as the factor of one
+/// frame as long as all of them, so the smoothing looks the same at any frame rate.
+pub fn smoothing_factor(elapsed: f32, time_constant: f32) -> f32 {
+    if time_constant.is_nan() || time_constant <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-elapsed.max(0.0) / time_constant).exp()
+    }
+}
+
+/// Converts a per-frame interpolation factor into the equivalent time constant.
+///
+/// # Arguments
+/// - `factor`: Fraction (0.0 to 1.0) of a change covered per frame at 60 fps.
+///
+/// # Returns
+/// - The time constant in milliseconds for which `smoothing_factor` returns `factor` at 60 fps;
+///   `0.0` for a factor of 1.0 and infinity for a factor of 0.0, which never moves.
+pub fn interpolation_time_constant_ms(factor: f32) -> f32 {
+    if factor >= 1.0 {
+        0.0
+    } else if factor <= 0.0 {
+        f32::INFINITY
+    } else {
+        -1000.0 / (REFERENCE_FRAME_RATE * (1.0 - factor).ln())
+    }
+}
+
+/// Converts a polyline into a smooth Catmull-Rom spline expressed as cubic Bézier segments.
+///
+/// # Arguments
+/// - `points`: The points the curve must pass through, in drawing order.
+///
+/// # Returns
+/// - One `[control_1, control_2, end]` triple per segment, suitable for Cairo's `curve_to`
+///   after a `move_to` to the first point. Fewer than two points produce no segments.
+///
+/// The end points are duplicated to provide the missing neighbors, so the curve starts and
+/// ends exactly on the first and last points without overshooting past them.
+pub fn catmull_rom_segments(points: &[(f64, f64)]) -> Vec<[(f64, f64); 3]> {
+    if points.len() < 2 {
+        return Vec::new();
+    }
+
+    let last = points.len() - 1;
+    (0..last)
+        .map(|i| {
+            let p0 = points[i.saturating_sub(1)];
+            let p1 = points[i];
+            let p2 = points[i + 1];
+            let p3 = points[(i + 2).min(last)];
+
+            // Tangents at p1 and p2 are half the distance between their neighbors
+            let control_1 = (p1.0 + (p2.0 - p0.0) / 6.0, p1.1 + (p2.1 - p0.1) / 6.0);
+            let control_2 = (p2.0 - (p3.0 - p1.0) / 6.0, p2.1 - (p3.1 - p1.1) / 6.0);
+            [control_1, control_2, p2]
+        })
+        .collect()
+}
+
+/// Measures the amplitude contained in a frequency band of a spectrum.
+///
+/// # Arguments
+/// - `spectrum`: Complex FFT output of a real signal, covering the full FFT size.
+/// - `sample_rate`: The sample rate of the analyzed audio, in Hz.
+/// - `zero_pad_factor`: The `fft.zero_pad_factor` the spectrum was padded with.
+/// - `low`: Lower edge of the band, in Hz.
+/// - `high`: Upper edge of the band, in Hz.
+///
+/// # Returns
+/// - The root of the summed squared amplitudes of the bins within the band. A sine wave of
+///   amplitude `A` centered on a bin in the band yields approximately `A`. Bands narrower than
+///   a bin use the bin closest to the band's center.
+pub fn band_energy(
+    spectrum: &[Complex32],
+    sample_rate: f32,
+    zero_pad_factor: usize,
+    low: f32,
+    high: f32,
+) -> f32 {
+    let fft_size = spectrum.len();
+    if fft_size == 0 || sample_rate <= 0.0 || high < low {
+        return 0.0;
+    }
+
+    let bin_width = sample_rate / fft_size as f32;
+    let nyquist_index = fft_size / 2;
+    let low_index = (low.max(0.0) / bin_width).ceil() as usize;
+    let high_index = ((high / bin_width).floor() as usize).min(nyquist_index);
+
+    let bins = if low_index <= high_index {
+        low_index..high_index + 1
+    } else {
+        let center = (((low + high) / 2.0) / bin_width).round() as usize;
+        if center > nyquist_index {
+            return 0.0;
+        }
+        center..center + 1
+    };
+
+    // Scale each bin so a full-scale sine maps to its amplitude; padding keeps the magnitudes of
+    // the unpadded FFT but spreads every bin over `zero_pad_factor` bins
+    let scale = 2.0 * zero_pad_factor.max(1) as f32 / fft_size as f32;
+    let spread = bins.len().clamp(1, zero_pad_factor.max(1)) as f32;
+    (spectrum[bins]
+        .iter()
+        .map(|value| (value.norm() * scale).powi(2))
+        .sum::<f32>()
+        / spread)
+        .sqrt()
+}
+
+/// Note names of the chromatic scale, starting at C.
+pub const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Estimates the dominant frequency of a spectrum.
+///
+/// # Arguments
+/// - `spectrum`: Complex FFT output of a real signal, covering the full FFT size.
+/// - `sample_rate`: The sample rate of the analyzed audio, in Hz.
+///
+/// # Returns
+/// - The frequency of the strongest bin in Hz, refined with parabolic interpolation over its
+///   neighbors for sub-bin accuracy, or `None` for an empty or silent spectrum.
+pub fn dominant_frequency(spectrum: &[Complex32], sample_rate: f32) -> Option<f32> {
+    let fft_size = spectrum.len();
+    if fft_size < 4 {
+        return None;
+    }
+
+    // Skip the DC bin and only search the non-mirrored half of the spectrum
+    let magnitudes: Vec<f32> = spectrum[..fft_size / 2]
+        .iter()
+        .map(|value| value.norm())
+        .collect();
+    let (peak_index, &peak) = magnitudes
+        .iter()
+        .enumerate()
+        .skip(1)
+        .max_by(|a, b| a.1.total_cmp(b.1))?;
+    if peak <= 0.0 {
+        return None;
+    }
+
+    // Fit a parabola through the log magnitudes of the peak and its neighbors
+    let offset = if peak_index + 1 < magnitudes.len() {
+        let left = (magnitudes[peak_index - 1] + 1e-12).ln();
+        let center = (peak + 1e-12).ln();
+        let right = (magnitudes[peak_index + 1] + 1e-12).ln();
+        let denominator = left - 2.0 * center + right;
+        if denominator.abs() > f32::EPSILON {
+            (0.5 * (left - right) / denominator).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        }
+    } else {
+        0.0
+    };
+
+    Some((peak_index as f32 + offset) * sample_rate / fft_size as f32)
+}
+
+/// Finds the musical note closest to a frequency.
+///
+/// # Arguments
+/// - `frequency`: The frequency in Hz; must be positive.
+/// - `a4`: The reference tuning of A4 in Hz, usually `440.0`.
+///
+/// # Returns
+/// - A tuple of the note name with its octave (e.g. `"A4"`, `"C#3"`) and the deviation from
+///   that note in cents, in the range [-50.0, 50.0].
+pub fn freq_to_note(frequency: f32, a4: f32) -> (String, f32) {
+    // MIDI note numbers place A4 at 69 with 12 semitones per octave
+    let midi = 69.0 + 12.0 * (frequency / a4).log2();
+    let nearest = midi.round();
+    let cents = (midi - nearest) * 100.0;
+
+    (note_name(nearest as i32), cents)
+}
+
+/// Returns the name of a MIDI note with its octave, such as `"A4"` for 69.
+pub fn note_name(midi: i32) -> String {
+    let name = NOTE_NAMES[midi.rem_euclid(12) as usize];
+    let octave = midi.div_euclid(12) - 1;
+    format!("{}{}", name, octave)
+}
+
+/// Returns the 12-TET frequency of a MIDI note, in Hz.
+///
+/// # Arguments
+/// - `midi`: The MIDI note number; A4 is 69.
+/// - `a4`: The reference tuning of A4 in Hz, usually `440.0`.
+pub fn note_frequency(midi: i32, a4: f32) -> f32 {
+    a4 * 2f32.powf((midi - 69) as f32 / 12.0)
+}
+
+/// Lists every note of one pitch class within a frequency range.
+///
+/// # Arguments
+/// - `pitch_class`: Semitones above C of the notes to list, such as `9` for every A.
+/// - `min_frequency`: Lowest frequency to include, in Hz.
+/// - `max_frequency`: Highest frequency to include, in Hz.
+/// - `a4`: The reference tuning of A4 in Hz, usually `440.0`.
+///
+/// # Returns
+/// - The MIDI note numbers of the notes in the range, lowest first.
+pub fn pitch_class_notes(
+    pitch_class: i32,
+    min_frequency: f32,
+    max_frequency: f32,
+    a4: f32,
+) -> Vec<i32> {
+    if a4 <= 0.0 || max_frequency <= 0.0 {
+        return Vec::new();
+    }
+    let midi = |frequency: f32| 69.0 + 12.0 * (frequency / a4).log2();
+    // Anything below the lowest MIDI note is inaudible anyway; the tolerance keeps range edges
+    // given as rounded note frequencies inclusive
+    let tolerance = 1e-3;
+    let lowest = (midi(min_frequency.max(note_frequency(0, a4))) - tolerance).ceil() as i32;
+    let highest = (midi(max_frequency) + tolerance).floor() as i32;
+
+    (lowest..=highest)
+        .filter(|note| note.rem_euclid(12) == pitch_class.rem_euclid(12))
+        .collect()
+}
+
+/// Folds a spectrum into the 12 pitch classes of the chromatic scale.
+///
+/// # Arguments
+/// - `spectrum`: Complex FFT output of a real signal, covering the full FFT size.
+/// - `sample_rate`: The sample rate of the analyzed audio, in Hz.
+/// - `a4`: The reference tuning of A4 in Hz, usually `440.0`.
+/// - `min_frequency`: Lowest frequency included, in Hz.
+/// - `max_frequency`: Highest frequency included, in Hz; limited to Nyquist.
+///
+/// # Returns
+/// - The summed bin magnitudes of each pitch class, starting at C. Every bin in the range adds
+///   its magnitude to the pitch class of the nearest note.
+pub fn chroma(
+    spectrum: &[Complex32],
+    sample_rate: f32,
+    a4: f32,
+    min_frequency: f32,
+    max_frequency: f32,
+) -> [f32; 12] {
+    let mut classes = [0.0; 12];
+    let fft_size = spectrum.len();
+    if fft_size == 0 || sample_rate <= 0.0 || a4 <= 0.0 {
+        return classes;
+    }
+
+    let bin_width = sample_rate / fft_size as f32;
+    // The DC bin has no pitch
+    let first = ((min_frequency / bin_width).ceil() as usize).max(1);
+    let last = ((max_frequency / bin_width).floor() as usize).min(fft_size / 2);
+    for (index, value) in spectrum.iter().enumerate().take(last + 1).skip(first) {
+        let midi = 69.0 + 12.0 * (index as f32 * bin_width / a4).log2();
+        classes[(midi.round() as i32).rem_euclid(12) as usize] += value.norm();
+    }
+    classes
+}
+
+/// Smooths magnitudes across neighboring frequency bins with a triangular kernel.
+///
+/// # Arguments
+/// - `mags`: The bin magnitudes, smoothed in place.
+/// - `radius`: The number of neighbors on each side contributing to a bin; `0` leaves the
+///   magnitudes unchanged.
+///
+/// Near the edges, the kernel is truncated and renormalized so the output keeps the input's
+/// length and a flat input stays flat.
+pub fn smooth_bins(mags: &mut [f32], radius: usize) {
+    if radius == 0 || mags.len() < 2 {
+        return;
+    }
+
+    let input = mags.to_vec();
+    let len = input.len();
+    for (i, output) in mags.iter_mut().enumerate() {
+        let start = i.saturating_sub(radius);
+        let end = (i + radius).min(len - 1);
+
+        let mut sum = 0.0;
+        let mut weight_sum = 0.0;
+        for (j, &value) in input.iter().enumerate().take(end + 1).skip(start) {
+            // Weights fall off linearly with the distance from the center bin
+            let weight = (radius + 1 - i.abs_diff(j)) as f32;
+            sum += value * weight;
+            weight_sum += weight;
+        }
+
+        *output = sum / weight_sum;
+    }
+}
+
+/// Averages the power of several spectra of equal length bin by bin.
+///
+/// # Arguments
+/// - `spectra`: The spectra to average; must not be empty.
+///
+/// # Returns
+/// - The spectrum whose bin magnitudes are the root mean square of the input magnitudes. Phases
+///   of different windows do not add up meaningfully, so the result holds magnitudes only.
+pub fn average_magnitudes(spectra: &[Vec<Complex32>]) -> Vec<Complex32> {
+    let count = spectra.len() as f32;
+    (0..spectra[0].len())
+        .map(|bin| {
+            let power: f32 = spectra
+                .iter()
+                .map(|spectrum| spectrum[bin].norm_sqr())
+                .sum();
+            Complex32::new((power / count).sqrt(), 0.0)
+        })
+        .collect()
+}
+
+/// Converts a gain in decibels to a linear amplitude factor.
+pub fn db_to_linear(db: f32) -> f32 {
+    10_f32.powf(db / 20.0)
+}
+
+/// Amplifies one sample by the input gain, clipping it to full scale so a gain set too high
+/// cannot push the spectrum beyond a full-scale sine.
+///
+/// # Arguments
+/// - `sample`: The captured sample.
+/// - `gain`: Linear input gain.
+///
+/// # Returns
+/// - The amplified sample, in [-1.0, 1.0].
+pub fn apply_input_gain(sample: f32, gain: f32) -> f32 {
+    (sample * gain).clamp(-1.0, 1.0)
+}
+
+/// Forward FFT of windows padded with zeros to `fft.padded_size()`.
+///
+/// Padding interpolates between the bins of an FFT of `fft.size`, so low frequencies are drawn
+/// from more points without waiting for more samples. It adds no resolution: the magnitude of a
+/// full-scale sine stays `fft.size / 2`, and tones closer than a bin of `fft.size` still merge.
+///
+/// The samples are amplified by `audio.input_gain_db` as they are copied into the window, so
+/// quiet sources are lifted above the noise floor of the analysis instead of shifting the whole
+/// spectrum like `visualizer.gain`.
+///
+/// # Fields
+/// - `fft`: Forward FFT of `fft.padded_size()` samples.
+/// - `buffer`: The padded window, reused by every transform.
+/// - `scratch`: Scratch space of `fft`, reused by every transform.
+/// - `input_gain`: Linear gain applied to the samples before the transform.
+pub struct PaddedFft {
+    fft: Arc<dyn Fft<f32>>,
+    buffer: Vec<Complex32>,
+    scratch: Vec<Complex32>,
+    input_gain: f32,
+}
+
+impl PaddedFft {
+    /// Plans the FFT for the padded size.
+    ///
+    /// # Arguments
+    /// - `fft_settings`: FFT settings providing `size` and `zero_pad_factor`.
+    /// - `input_gain_db`: Gain applied to the samples before the transform, `audio.input_gain_db`.
+    pub fn new(fft_settings: &FFTSettings, input_gain_db: f32) -> Self {
+        let size = fft_settings.padded_size();
+        let fft = FftPlanner::new().plan_fft_forward(size);
+        PaddedFft {
+            buffer: vec![Complex32::default(); size],
+            scratch: vec![Complex32::default(); fft.get_outofplace_scratch_len()],
+            fft,
+            input_gain: db_to_linear(input_gain_db),
+        }
+    }
+
+    /// Runs the FFT over one window of samples.
+    ///
+    /// # Arguments
+    /// - `samples`: The window, `fft.size` samples long; shorter windows are padded further and
+    ///   longer ones cut off at the padded size.
+    ///
+    /// # Returns
+    /// - The spectrum of the window, `fft.padded_size()` bins long.
+    pub fn transform(&mut self, samples: &[f32]) -> Vec<Complex32> {
+        let len = samples.len().min(self.buffer.len());
+        for (value, &sample) in self.buffer.iter_mut().zip(&samples[..len]) {
+            *value = Complex32::new(apply_input_gain(sample, self.input_gain), 0.0);
+        }
+        self.buffer[len..].fill(Complex32::default());
+
+        let mut spectrum = vec![Complex32::default(); self.buffer.len()];
+        self.fft.process_outofplace_with_scratch(
+            &mut self.buffer,
+            &mut spectrum,
+            &mut self.scratch,
+        );
+        spectrum
+    }
+}
+
+/// Converts stereo samples to mid and side signals.
+///
+/// # Arguments
+/// - `left`: Samples of the left channel.
+/// - `right`: Samples of the right channel, as many as `left`.
+///
+/// # Returns
+/// - The mid signal `(L + R) / 2` and the side signal `(L - R) / 2`.
+pub fn to_mid_side(left: &[f32], right: &[f32]) -> (Vec<f32>, Vec<f32>) {
+    left.iter()
+        .zip(right)
+        .map(|(&left, &right)| ((left + right) / 2.0, (left - right) / 2.0))
+        .unzip()
+}
+
+/// Frequency ratio of one octave in the base-ten band system of ISO 266 and IEC 61260.
+const OCTAVE_RATIO: f32 = 1.995_262_3; // 10^(3/10)
+
+/// Preferred numbers of the R10 series, the nominal band centers within one decade.
+const R10: [f32; 10] = [1.0, 1.25, 1.6, 2.0, 2.5, 3.15, 4.0, 5.0, 6.3, 8.0];
+
+/// One band of a fractional-octave analyzer.
+///
+/// # Fields
+/// - `lower`: Lower band edge, in Hz.
+/// - `center`: Exact center frequency, in Hz.
+/// - `upper`: Upper band edge, in Hz.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OctaveBand {
+    pub lower: f32,
+    pub center: f32,
+    pub upper: f32,
+}
+
+impl OctaveBand {
+    /// Returns the nominal center frequency the band is labeled with, such as 31.5 Hz for the
+    /// exact center 31.62 Hz.
+    ///
+    /// Centers on the R10 series snap to it; other centers are rounded to two significant digits.
+    pub fn nominal_center(&self) -> f32 {
+        let decade = 10f32.powf(self.center.log10().floor());
+        let mantissa = self.center / decade;
+        let nearest = R10
+            .iter()
+            .chain(&[10.0])
+            .copied()
+            .min_by(|a, b| (a - mantissa).abs().total_cmp(&(b - mantissa).abs()))
+            .unwrap();
+        if (nearest / mantissa - 1.0).abs() < 0.015 {
+            nearest * decade
+        } else {
+            let step = decade / 10.0;
+            (self.center / step).round() * step
+        }
+    }
+}
+
+/// Generates the 1/`fraction` octave bands of IEC 61260 centered within a frequency range.
+///
+/// # Arguments
+/// - `fraction`: Bands per octave, such as `1` for octaves or `3` for third-octaves.
+/// - `f_min`: Lowest band center to include, in Hz.
+/// - `f_max`: Highest band center to include, in Hz.
+///
+/// # Returns
+/// - The bands, lowest first. Centers lie at `1000 * G^(x / fraction)` Hz for odd fractions and
+///   halfway between those for even ones, with `G = 10^(3/10)`; edges lie half a band away.
+pub fn octave_bands(fraction: u32, f_min: f32, f_max: f32) -> Vec<OctaveBand> {
+    if fraction == 0 || f_min <= 0.0 || f_max < f_min {
+        return Vec::new();
+    }
+
+    // Even fractions place 1 kHz on a band edge instead of a band center
+    let offset = if fraction.is_multiple_of(2) { 0.5 } else { 0.0 };
+    let fraction = fraction as f32;
+    let index_of = |frequency: f32| (frequency / 1000.0).log(OCTAVE_RATIO) * fraction - offset;
+    let center_of = |x: i32| 1000.0 * OCTAVE_RATIO.powf((x as f32 + offset) / fraction);
+    // Allow a tenth of a band, so the 19.95 Hz band counts as the nominal 20 Hz band
+    let first = (index_of(f_min) - 0.1).ceil() as i32;
+    let last = (index_of(f_max) + 0.1).floor() as i32;
+    let half_band = OCTAVE_RATIO.powf(0.5 / fraction);
+
+    (first..=last)
+        .map(|x| {
+            let center = center_of(x);
+            OctaveBand {
+                lower: center / half_band,
+                center,
+                upper: center * half_band,
+            }
+        })
+        .collect()
+}
+
+/// Assigns the FFT bins to the bands they fall into.
+///
+/// # Arguments
+/// - `bands`: The bands, lowest first, as from `octave_bands`.
+/// - `sample_rate`: The sample rate of the analyzed audio, in Hz.
+/// - `fft_size`: The size of the FFT.
+///
+/// # Returns
+/// - For each band, the bins whose frequency lies in `[lower, upper)`, limited to Nyquist. Bands
+///   narrower than a bin use the bin closest to their center, so low bands are not left empty.
+pub fn band_bins(bands: &[OctaveBand], sample_rate: f32, fft_size: usize) -> Vec<Range<usize>> {
+    let bin_width = sample_rate / fft_size as f32;
+    let nyquist_index = fft_size / 2;
+
+    bands
+        .iter()
+        .map(|band| {
+            let low_index = ((band.lower / bin_width).ceil() as usize).min(nyquist_index + 1);
+            let high_index = ((band.upper / bin_width).ceil() as usize).min(nyquist_index + 1);
+            if low_index < high_index {
+                low_index..high_index
+            } else {
+                let center = (band.center / bin_width).round() as usize;
+                if center > nyquist_index {
+                    center..center
+                } else {
+                    center..center + 1
+                }
+            }
+        })
+        .collect()
+}
+
+/// Sums the power of the bins of each band.
+///
+/// # Arguments
+/// - `spectrum`: Complex FFT output of a real signal.
+/// - `bins`: The bins of each band, as from `band_bins`.
+/// - `zero_pad_factor`: The `fft.zero_pad_factor` the spectrum was padded with; the power of a
+///   band is divided by the number of padded bins each unpadded bin is spread over.
+///
+/// # Returns
+/// - For each band, the root of the summed squared bin magnitudes, comparable to the magnitude
+///   of a single bin so `LevelScale` converts it to dBFS.
+pub fn band_magnitudes(
+    spectrum: &[Complex32],
+    bins: &[Range<usize>],
+    zero_pad_factor: usize,
+) -> Vec<f32> {
+    bins.iter()
+        .map(|range| {
+            let end = range.end.min(spectrum.len());
+            let start = range.start.min(end);
+            let spread = (end - start).clamp(1, zero_pad_factor.max(1)) as f32;
+            (spectrum[start..end]
+                .iter()
+                .map(|value| value.norm_sqr())
+                .sum::<f32>()
+                / spread)
+                .sqrt()
+        })
+        .collect()
+}
+
+/// Limits a frequency range to the frequencies an FFT can show.
+///
+/// # Arguments
+/// - `low`: Lowest frequency of the range, in Hz.
+/// - `high`: Highest frequency of the range, in Hz.
+/// - `fft_settings`: FFT settings providing the sample rate and size.
+/// - `min_bins`: Smallest width of the range, in FFT bins.
+///
+/// # Returns
+/// - The range within [1 Hz, Nyquist], widened upwards (or downwards at Nyquist) to at least
+///   `min_bins` bins.
+pub fn clamp_frequency_range(
+    low: f32,
+    high: f32,
+    fft_settings: &FFTSettings,
+    min_bins: usize,
+) -> (f32, f32) {
+    let nyquist = fft_settings.sample_rate / 2.0;
+    let min_span = min_bins as f32 * fft_settings.sample_rate / fft_settings.size as f32;
+    let low = low.clamp(1.0, (nyquist - min_span).max(1.0));
+    let high = high.clamp(low + min_span, nyquist.max(low + min_span));
+    (low, high)
+}
+
+/// Computes the range of FFT bins covering the configured frequency range.
+///
+/// # Arguments
+/// - `fft_settings`: FFT settings providing the sample rate and frequency range.
+/// - `fft_size`: The size of the FFT data array.
+///
+/// # Returns
+/// - A tuple `(min_index, max_index)` of bin indices, clamped to `[0, fft_size / 2]` so frequencies
+///   above Nyquist are ignored. If the configured range is empty or inverted, the full range is
+///   returned instead and a warning is printed once.
+pub fn frequency_indices(fft_settings: &FFTSettings, fft_size: usize) -> (usize, usize) {
+    let nyquist_index = fft_size / 2;
+    let to_index = |frequency: f32| {
+        let index = (frequency * fft_size as f32 / fft_settings.sample_rate).max(0.0);
+        (index as usize).min(nyquist_index)
+    };
+
+    let min_index = to_index(fft_settings.min_frequency);
+    let max_index = to_index(fft_settings.max_frequency);
+
+    if min_index < max_index {
+        (min_index, max_index)
+    } else {
+        if !RANGE_WARNING_SHOWN.swap(true, Ordering::Relaxed) {
+            eprintln!(
+                "Frequency range {}-{} Hz is empty, showing the full spectrum instead.",
+                fft_settings.min_frequency, fft_settings.max_frequency
+            );
+        }
+        (0, nyquist_index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoothing_follows_the_time_constant_regardless_of_frame_rate() {
+        assert_eq!(smoothing_factor(0.016, 0.0), 1.0);
+        assert!((smoothing_factor(0.2, 0.2) - 0.632).abs() < 1e-3);
+
+        // A bar decaying from 1.0 towards 0.0 reaches the same height after one second at any
+        // frame rate
+        let decay = |fps: usize| {
+            let factor = smoothing_factor(1.0 / fps as f32, 0.175);
+            (0..fps).fold(1.0, |height, _| interpolate(height, 0.0, factor))
+        };
+        let expected = (-1.0f32 / 0.175).exp();
+        for fps in [24, 30, 60, 144, 240] {
+            assert!((decay(fps) - expected).abs() < 1e-4, "{} fps", fps);
+        }
+    }
+
+    #[test]
+    fn interpolation_recovers_from_invalid_values() {
+        assert_eq!(interpolate(10.0, 20.0, 1.8), 20.0);
+        assert_eq!(interpolate(10.0, 20.0, -0.5), 10.0);
+        assert_eq!(interpolate(10.0, 20.0, f32::NAN), 20.0);
+        assert_eq!(interpolate(f32::NAN, 20.0, 0.5), 0.0);
+        assert_eq!(interpolate(10.0, f32::INFINITY, 0.5), 0.0);
+        assert_eq!(interpolate(0.0, 20.0, 0.5), 10.0);
+
+        assert_eq!(smoothing_factor(0.016, f32::NAN), 1.0);
+        assert_eq!(smoothing_factor(f32::NAN, 0.1), 0.0);
+    }
+
+    #[test]
+    fn interpolation_factors_convert_to_time_constants() {
+        let time_constant = interpolation_time_constant_ms(0.09);
+        assert!((time_constant - 176.7).abs() < 0.1);
+        let factor = smoothing_factor(1.0 / REFERENCE_FRAME_RATE, time_constant / 1000.0);
+        assert!((factor - 0.09).abs() < 1e-5);
+
+        assert_eq!(interpolation_time_constant_ms(1.0), 0.0);
+        assert_eq!(
+            smoothing_factor(0.016, interpolation_time_constant_ms(0.0)),
+            0.0
+        );
+    }
+
+    #[test]
+    fn bars_outside_a_soloed_band_are_dimmed() {
+        assert_eq!(band_emphasis(None, 50.0), 1.0);
+        let band = Some((2000.0, 5000.0));
+        assert_eq!(band_emphasis(band, 2000.0), 1.0);
+        assert_eq!(band_emphasis(band, 3500.0), 1.0);
+        assert_eq!(band_emphasis(band, 5000.0), 1.0);
+        assert_eq!(band_emphasis(band, 1999.0), DIMMED_BAND_ALPHA);
+        assert_eq!(band_emphasis(band, 5001.0), DIMMED_BAND_ALPHA);
+    }
+
+    fn assert_rgb_eq(actual: (f32, f32, f32), expected: (f32, f32, f32)) {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+        assert!(
+            close(actual.0, expected.0)
+                && close(actual.1, expected.1)
+                && close(actual.2, expected.2),
+            "expected {:?}, got {:?}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn hsl_to_rgb_known_conversions() {
+        let cases = [
+            ((0.0, 1.0, 0.5), (1.0, 0.0, 0.0)),
+            ((60.0, 1.0, 0.5), (1.0, 1.0, 0.0)),
+            ((120.0, 1.0, 0.5), (0.0, 1.0, 0.0)),
+            ((180.0, 1.0, 0.5), (0.0, 1.0, 1.0)),
+            ((240.0, 1.0, 0.5), (0.0, 0.0, 1.0)),
+            ((300.0, 1.0, 0.5), (1.0, 0.0, 1.0)),
+            ((360.0, 1.0, 0.5), (1.0, 0.0, 0.0)),
+            ((480.0, 1.0, 0.5), (0.0, 1.0, 0.0)),
+            ((-120.0, 1.0, 0.5), (0.0, 0.0, 1.0)),
+            ((200.0, 0.0, 0.5), (0.5, 0.5, 0.5)),
+            ((90.0, 1.0, 0.0), (0.0, 0.0, 0.0)),
+            ((90.0, 1.0, 1.0), (1.0, 1.0, 1.0)),
+        ];
+
+        for ((hue, saturation, lightness), expected) in cases {
+            assert_rgb_eq(hsl_to_rgb(hue, saturation, lightness), expected);
+        }
+    }
+
+    #[test]
+    fn hsl_to_rgb_clamps_saturation_and_lightness() {
+        assert_rgb_eq(hsl_to_rgb(0.0, 2.0, 0.5), (1.0, 0.0, 0.0));
+        assert_rgb_eq(hsl_to_rgb(0.0, -1.0, 0.5), (0.5, 0.5, 0.5));
+        assert_rgb_eq(hsl_to_rgb(0.0, 1.0, 3.0), (1.0, 1.0, 1.0));
+        assert_rgb_eq(hsl_to_rgb(0.0, 1.0, -3.0), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn hsl_to_rgb_output_stays_in_unit_range() {
+        // Deterministic sweep over a wide range of inputs, including out-of-range values
+        let mut state: u32 = 0x9E37_79B9;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as f32 / u32::MAX as f32
+        };
+
+        for _ in 0..10_000 {
+            let hue = next() * 2000.0 - 1000.0;
+            let saturation = next() * 3.0 - 1.0;
+            let lightness = next() * 3.0 - 1.0;
+            let (r, g, b) = hsl_to_rgb(hue, saturation, lightness);
+
+            for component in [r, g, b] {
+                assert!(
+                    (0.0..=1.0).contains(&component),
+                    "hsl({}, {}, {}) produced out-of-range component {}",
+                    hue,
+                    saturation,
+                    lightness,
+                    component
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn frequency_color_wraps_to_red_at_the_end_of_the_spectrum() {
+        let color = get_color_for_frequency(&Palette::Rainbow, 1.0, 0.5);
+        assert_rgb_eq(color, (1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn catmull_rom_needs_at_least_two_points() {
+        assert!(catmull_rom_segments(&[]).is_empty());
+        assert!(catmull_rom_segments(&[(1.0, 2.0)]).is_empty());
+    }
+
+    #[test]
+    fn catmull_rom_passes_through_every_point() {
+        let points = [(0.0, 0.0), (1.0, 3.0), (2.0, -1.0), (3.0, 2.0)];
+        let segments = catmull_rom_segments(&points);
+
+        assert_eq!(segments.len(), points.len() - 1);
+        for (segment, point) in segments.iter().zip(&points[1..]) {
+            assert_eq!(segment[2], *point);
+        }
+    }
+
+    #[test]
+    fn catmull_rom_keeps_straight_lines_straight() {
+        let points = [(0.0, 0.0), (1.0, 2.0), (2.0, 4.0), (3.0, 6.0)];
+
+        for segment in catmull_rom_segments(&points) {
+            for (x, y) in segment {
+                assert!((y - 2.0 * x).abs() < 1e-9);
+            }
+        }
+    }
+
+    fn sine_spectrum(fft_size: usize, bin: usize, amplitude: f32) -> Vec<Complex32> {
+        let mut buffer: Vec<Complex32> = (0..fft_size)
+            .map(|n| {
+                let phase = 2.0 * std::f32::consts::PI * bin as f32 * n as f32 / fft_size as f32;
+                Complex32::new(amplitude * phase.sin(), 0.0)
+            })
+            .collect();
+        rustfft::FftPlanner::new()
+            .plan_fft_forward(fft_size)
+            .process(&mut buffer);
+        buffer
+    }
+
+    #[test]
+    fn band_energy_measures_a_sine_inside_the_band() {
+        // With 1024 samples at 44.1 kHz, bin 2 sits at roughly 86 Hz
+        let spectrum = sine_spectrum(1024, 2, 0.5);
+        let energy = band_energy(&spectrum, 44100.0, 1, 30.0, 120.0);
+        assert!((energy - 0.5).abs() < 1e-3, "energy was {}", energy);
+    }
+
+    #[test]
+    fn band_energy_ignores_a_sine_outside_the_band() {
+        let spectrum = sine_spectrum(1024, 100, 0.5);
+        let energy = band_energy(&spectrum, 44100.0, 1, 30.0, 120.0);
+        assert!(energy < 1e-3, "energy was {}", energy);
+    }
+
+    #[test]
+    fn band_energy_handles_degenerate_input() {
+        assert_eq!(band_energy(&[], 44100.0, 1, 30.0, 120.0), 0.0);
+
+        let spectrum = sine_spectrum(64, 4, 1.0);
+        assert_eq!(band_energy(&spectrum, 44100.0, 1, 120.0, 30.0), 0.0);
+        assert_eq!(band_energy(&spectrum, 44100.0, 1, 30000.0, 40000.0), 0.0);
+        // Band narrower than a bin still picks up the nearest bin
+        let narrow = band_energy(&spectrum, 64.0, 1, 3.9, 4.1);
+        assert!((narrow - 1.0).abs() < 1e-3, "energy was {}", narrow);
+    }
+
+    #[test]
+    fn dominant_frequency_finds_a_sine_between_bins() {
+        let fft_size = 4096;
+        let sample_rate = 44100.0;
+        let frequency = 441.0;
+        let mut buffer: Vec<Complex32> = (0..fft_size)
+            .map(|n| {
+                let phase = 2.0 * std::f32::consts::PI * frequency * n as f32 / sample_rate;
+                Complex32::new(0.5 * phase.sin(), 0.0)
+            })
+            .collect();
+        rustfft::FftPlanner::new()
+            .plan_fft_forward(fft_size)
+            .process(&mut buffer);
+
+        let estimate = dominant_frequency(&buffer, sample_rate).unwrap();
+        assert!(
+            (estimate - frequency).abs() < 1.0,
+            "estimate was {}",
+            estimate
+        );
+    }
+
+    #[test]
+    fn zero_padding_interpolates_between_the_bins() {
+        let settings = FFTSettings {
+            size: 256,
+            sample_rate: 48000.0,
+            zero_pad_factor: 4,
+            ..FFTSettings::default()
+        };
+        let samples: Vec<f32> = (0..settings.size)
+            .map(|n| (2.0 * std::f32::consts::PI * 1000.0 * n as f32 / 48000.0).sin())
+            .collect();
+        let mut fft = PaddedFft::new(&settings, 0.0);
+        let spectrum = fft.transform(&samples);
+        assert_eq!(spectrum.len(), 1024);
+        // The reused buffer holds no samples of the previous window
+        assert_eq!(fft.transform(&samples), spectrum);
+
+        let peak = (1..spectrum.len() / 2)
+            .max_by(|&a, &b| spectrum[a].norm().total_cmp(&spectrum[b].norm()))
+            .unwrap();
+        let frequency = peak as f32 * settings.sample_rate / settings.padded_size() as f32;
+        let bin_width = settings.sample_rate / settings.size as f32;
+        assert!(
+            (frequency - 1000.0).abs() < bin_width / 2.0,
+            "peak was at {} Hz",
+            frequency
+        );
+        // Padding keeps the magnitude of the unpadded FFT
+        let magnitude = spectrum[peak].norm() / (settings.size as f32 / 2.0);
+        assert!(
+            magnitude > 0.8 && magnitude <= 1.01,
+            "magnitude {}",
+            magnitude
+        );
+    }
+
+    #[test]
+    fn input_gain_amplifies_the_samples_up_to_full_scale() {
+        let gain = db_to_linear(20.0);
+        assert!((gain - 10.0).abs() < 1e-4);
+        assert!((apply_input_gain(0.05, gain) - 0.5).abs() < 1e-6);
+        assert!((apply_input_gain(-0.02, gain) + 0.2).abs() < 1e-6);
+        // Samples pushed beyond full scale are clipped instead of overflowing the spectrum
+        assert_eq!(apply_input_gain(0.5, gain), 1.0);
+        assert_eq!(apply_input_gain(-0.5, gain), -1.0);
+        assert_eq!(apply_input_gain(0.5, db_to_linear(0.0)), 0.5);
+
+        // The gain lifts the spectrum of a quiet sine by its factor
+        let settings = FFTSettings {
+            size: 256,
+            sample_rate: 48000.0,
+            ..FFTSettings::default()
+        };
+        let samples: Vec<f32> = (0..settings.size)
+            .map(|n| 0.01 * (2.0 * std::f32::consts::PI * 1500.0 * n as f32 / 48000.0).sin())
+            .collect();
+        let plain = PaddedFft::new(&settings, 0.0).transform(&samples);
+        let gained = PaddedFft::new(&settings, 20.0).transform(&samples);
+        let peak = |spectrum: &[Complex32]| spectrum[8].norm();
+        assert!((peak(&gained) / peak(&plain) - 10.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn dominant_frequency_ignores_silence() {
+        let silence = vec![Complex32::new(0.0, 0.0); 1024];
+        assert_eq!(dominant_frequency(&silence, 44100.0), None);
+        assert_eq!(dominant_frequency(&[], 44100.0), None);
+    }
+
+    #[test]
+    fn freq_to_note_names_notes_across_octaves() {
+        let cases = [
+            (440.0, "A4"),
+            (261.63, "C4"),
+            (27.5, "A0"),
+            (16.35, "C0"),
+            (277.18, "C#4"),
+            (1046.5, "C6"),
+            (123.47, "B2"),
+            (8.18, "C-1"),
+        ];
+
+        for (frequency, expected) in cases {
+            let (name, cents) = freq_to_note(frequency, 440.0);
+            assert_eq!(name, expected, "for {} Hz", frequency);
+            assert!(
+                cents.abs() < 1.0,
+                "{} Hz was {} cents off",
+                frequency,
+                cents
+            );
+        }
+    }
+
+    #[test]
+    fn freq_to_note_reports_cents_and_respects_tuning() {
+        let (name, cents) = freq_to_note(442.0, 440.0);
+        assert_eq!(name, "A4");
+        assert!((cents - 7.85).abs() < 0.05, "cents was {}", cents);
+
+        let (name, cents) = freq_to_note(432.0, 432.0);
+        assert_eq!(name, "A4");
+        assert!(cents.abs() < 1e-3);
+    }
+
+    #[test]
+    fn note_frequencies_follow_equal_temperament() {
+        let cases = [
+            (69, 440.0),
+            (60, 261.626),
+            (57, 220.0),
+            (33, 55.0),
+            (72, 523.251),
+        ];
+        for (midi, expected) in cases {
+            let frequency = note_frequency(midi, 440.0);
+            assert!(
+                (frequency - expected).abs() < 0.01,
+                "note {} was {} Hz",
+                midi,
+                frequency
+            );
+        }
+        assert!((note_frequency(57, 432.0) - 216.0).abs() < 1e-3);
+        assert_eq!(note_name(60), "C4");
+        assert_eq!(note_name(33), "A1");
+    }
+
+    #[test]
+    fn pitch_class_notes_stay_within_the_range() {
+        let a_notes: Vec<f32> = pitch_class_notes(9, 50.0, 1000.0, 440.0)
+            .into_iter()
+            .map(|midi| note_frequency(midi, 440.0))
+            .collect();
+        assert_eq!(a_notes.len(), 5);
+        for (frequency, expected) in a_notes.iter().zip([55.0, 110.0, 220.0, 440.0, 880.0]) {
+            assert!((frequency - expected).abs() < 1e-3);
+        }
+
+        // Range edges are inclusive, and a range starting at 0 Hz ends at the lowest MIDI note
+        assert_eq!(pitch_class_notes(0, 261.63, 523.25, 440.0), [60, 72]);
+        assert_eq!(pitch_class_notes(0, 0.0, 20.0, 440.0), [0, 12]);
+        assert!(pitch_class_notes(0, 300.0, 500.0, 440.0).is_empty());
+    }
+
+    #[test]
+    fn clamp_frequency_range_stays_between_1_hz_and_nyquist() {
+        let fft_settings = FFTSettings {
+            size: 1000,
+            sample_rate: 1000.0,
+            ..FFTSettings::default()
+        };
+        assert_eq!(
+            clamp_frequency_range(100.0, 200.0, &fft_settings, 2),
+            (100.0, 200.0)
+        );
+        assert_eq!(
+            clamp_frequency_range(0.0, 900.0, &fft_settings, 2),
+            (1.0, 500.0)
+        );
+        // Narrow ranges are widened to the minimum number of bins
+        assert_eq!(
+            clamp_frequency_range(100.0, 100.5, &fft_settings, 2),
+            (100.0, 102.0)
+        );
+        assert_eq!(
+            clamp_frequency_range(499.5, 500.0, &fft_settings, 2),
+            (498.0, 500.0)
+        );
+    }
+
+    #[test]
+    fn averaged_magnitudes_are_root_mean_square() {
+        let spectra = [
+            vec![Complex32::new(3.0, 4.0), Complex32::new(1.0, 0.0)],
+            vec![Complex32::new(0.0, 0.0), Complex32::new(0.0, -1.0)],
+        ];
+        let average = average_magnitudes(&spectra);
+
+        assert!((average[0].norm() - (25.0_f32 / 2.0).sqrt()).abs() < 1e-6);
+        assert!((average[1].norm() - 1.0).abs() < 1e-6);
+        assert_eq!(average[1].im, 0.0);
+    }
+
+    #[test]
+    fn smooth_bins_keeps_flat_input_flat() {
+        for radius in 0..=5 {
+            let mut mags = vec![0.7; 16];
+            smooth_bins(&mut mags, radius);
+            assert_eq!(mags.len(), 16);
+            for value in mags {
+                assert!((value - 0.7).abs() < 1e-6, "radius {}: {}", radius, value);
+            }
+        }
+    }
+
+    #[test]
+    fn smooth_bins_spreads_an_impulse_symmetrically() {
+        let mut mags = vec![0.0; 11];
+        mags[5] = 9.0;
+        smooth_bins(&mut mags, 2);
+
+        // Triangular weights 1, 2, 3, 2, 1 sum to 9
+        let expected = [0.0, 0.0, 0.0, 1.0, 2.0, 3.0, 2.0, 1.0, 0.0, 0.0, 0.0];
+        for (value, expected) in mags.iter().zip(expected) {
+            assert!((value - expected).abs() < 1e-6, "{:?}", mags);
+        }
+    }
+
+    #[test]
+    fn smooth_bins_radius_zero_is_identity() {
+        let mut mags = vec![1.0, 5.0, 2.0, 8.0];
+        smooth_bins(&mut mags, 0);
+        assert_eq!(mags, vec![1.0, 5.0, 2.0, 8.0]);
+    }
+
+    fn fft_settings(min_frequency: f32, max_frequency: f32) -> FFTSettings {
+        FFTSettings {
+            size: 1024,
+            sample_rate: 44100.0,
+            min_frequency,
+            max_frequency,
+            ..FFTSettings::default()
+        }
+    }
+
+    #[test]
+    fn frequency_indices_map_the_configured_range() {
+        assert_eq!(
+            frequency_indices(&fft_settings(20.0, 10000.0), 1024),
+            (0, 232)
+        );
+        assert_eq!(
+            frequency_indices(&fft_settings(1000.0, 2000.0), 1024),
+            (23, 46)
+        );
+    }
+
+    #[test]
+    fn frequency_indices_clamp_to_nyquist() {
+        assert_eq!(
+            frequency_indices(&fft_settings(20.0, 30000.0), 1024),
+            (0, 512)
+        );
+    }
+
+    #[test]
+    fn frequency_indices_fall_back_to_full_range_when_inverted() {
+        assert_eq!(
+            frequency_indices(&fft_settings(5000.0, 1000.0), 1024),
+            (0, 512)
+        );
+        assert_eq!(
+            frequency_indices(&fft_settings(-100.0, -10.0), 1024),
+            (0, 512)
+        );
+    }
+
+    #[test]
+    fn frequency_indices_fall_back_to_full_range_when_degenerate() {
+        assert_eq!(
+            frequency_indices(&fft_settings(1000.0, 1000.0), 1024),
+            (0, 512)
+        );
+        // Both frequencies above Nyquist collapse onto the same bin
+        assert_eq!(
+            frequency_indices(&fft_settings(25000.0, 30000.0), 1024),
+            (0, 512)
+        );
+        assert_eq!(frequency_indices(&fft_settings(20.0, 10000.0), 0), (0, 0));
+    }
+
+    #[test]
+    fn mid_side_separates_common_and_difference_signals() {
+        let (mid, side) = to_mid_side(&[1.0, 0.5, 0.25], &[1.0, -0.5, 0.75]);
+        assert_eq!(mid, [1.0, 0.0, 0.5]);
+        assert_eq!(side, [0.0, 0.5, -0.25]);
+    }
+
+    /// Nominal third-octave centers of IEC 61260 from 20 Hz to 20 kHz.
+    const THIRD_OCTAVE_CENTERS: [f32; 31] = [
+        20.0, 25.0, 31.5, 40.0, 50.0, 63.0, 80.0, 100.0, 125.0, 160.0, 200.0, 250.0, 315.0, 400.0,
+        500.0, 630.0, 800.0, 1000.0, 1250.0, 1600.0, 2000.0, 2500.0, 3150.0, 4000.0, 5000.0,
+        6300.0, 8000.0, 10000.0, 12500.0, 16000.0, 20000.0,
+    ];
+
+    #[test]
+    fn third_octave_bands_match_the_iso_centers() {
+        let bands = octave_bands(3, 20.0, 20000.0);
+        let nominal: Vec<f32> = bands.iter().map(OctaveBand::nominal_center).collect();
+        assert_eq!(nominal, THIRD_OCTAVE_CENTERS);
+
+        for (band, expected) in bands.iter().zip(THIRD_OCTAVE_CENTERS) {
+            assert!((band.center / expected - 1.0).abs() < 0.02);
+            assert!((band.upper / band.lower - 2f32.powf(1.0 / 3.0)).abs() < 1e-3);
+        }
+        // Neighboring bands share their edges
+        for pair in bands.windows(2) {
+            assert!((pair[0].upper / pair[1].lower - 1.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn octave_and_sixth_octave_bands_are_centered_correctly() {
+        let octaves: Vec<f32> = octave_bands(1, 20.0, 20000.0)
+            .iter()
+            .map(OctaveBand::nominal_center)
+            .collect();
+        assert_eq!(
+            octaves,
+            [31.5, 63.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0]
+        );
+
+        // Even fractions have a band edge at 1 kHz
+        let sixths = octave_bands(6, 20.0, 20000.0);
+        assert_eq!(sixths.len(), 60);
+        assert!(sixths.iter().any(|band| (band.upper - 1000.0).abs() < 0.1));
+        assert!(octave_bands(0, 20.0, 20000.0).is_empty());
+    }
+
+    #[test]
+    fn bins_fall_into_the_band_containing_their_frequency() {
+        let (sample_rate, fft_size) = (48000.0, 4096);
+        let bin_width = sample_rate / fft_size as f32;
+        let bands = octave_bands(3, 20.0, 20000.0);
+        let bins = band_bins(&bands, sample_rate, fft_size);
+
+        for (band, range) in bands.iter().zip(&bins) {
+            assert!(!range.is_empty());
+            if band.upper - band.lower >= bin_width {
+                for bin in range.clone() {
+                    let frequency = bin as f32 * bin_width;
+                    assert!(band.lower <= frequency && frequency < band.upper);
+                }
+            }
+        }
+        // Wide bands cover every bin between them exactly once
+        for pair in bins[20..].windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+        }
+
+        let mut spectrum = vec![Complex32::new(0.0, 0.0); fft_size];
+        let bin_1k = (1000.0 / bin_width).round() as usize;
+        spectrum[bin_1k] = Complex32::new(3.0, 4.0);
+        spectrum[bin_1k + 1] = Complex32::new(0.0, 12.0);
+        let magnitudes = band_magnitudes(&spectrum, &bins, 1);
+        assert!((magnitudes[17] - 13.0).abs() < 1e-4);
+        assert_eq!(magnitudes.iter().filter(|&&m| m > 0.0).count(), 1);
+    }
+
+    /// Returns the spectrum of a Hann-windowed sum of unit sines, so leakage stays in the
+    /// neighboring bins.
+    fn windowed_spectrum(frequencies: &[f32], sample_rate: f32, fft_size: usize) -> Vec<Complex32> {
+        let mut buffer: Vec<Complex32> = (0..fft_size)
+            .map(|n| {
+                let t = n as f32 / sample_rate;
+                let window =
+                    0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / fft_size as f32).cos();
+                let sample: f32 = frequencies
+                    .iter()
+                    .map(|f| (2.0 * std::f32::consts::PI * f * t).sin())
+                    .sum();
+                Complex32::new(sample * window, 0.0)
+            })
+            .collect();
+        rustfft::FftPlanner::new()
+            .plan_fft_forward(fft_size)
+            .process(&mut buffer);
+        buffer
+    }
+
+    #[test]
+    fn a_sine_at_a4_falls_into_pitch_class_a() {
+        let spectrum = windowed_spectrum(&[440.0], 44100.0, 8192);
+        let classes = chroma(&spectrum, 44100.0, 440.0, 20.0, 20000.0);
+        let total: f32 = classes.iter().sum();
+        assert!(classes[9] / total > 0.9, "{:?}", classes);
+    }
+
+    #[test]
+    fn a_c_major_chord_lights_c_e_and_g() {
+        let spectrum = windowed_spectrum(&[261.63, 329.63, 392.0], 44100.0, 8192);
+        let classes = chroma(&spectrum, 44100.0, 440.0, 20.0, 20000.0);
+        let chord_min = [0, 4, 7]
+            .map(|class| classes[class])
+            .into_iter()
+            .fold(f32::MAX, f32::min);
+        let others_max = (0..12)
+            .filter(|class| ![0, 4, 7].contains(class))
+            .map(|class| classes[class])
+            .fold(0.0, f32::max);
+        assert!(chord_min > 2.0 * others_max, "{:?}", classes);
+
+        // A lower reference shifts the same tones up by a pitch class
+        let shifted = chroma(&spectrum, 44100.0, 415.3, 20.0, 20000.0);
+        assert!(shifted[1] > 2.0 * shifted[0]);
+    }
 }
@@ -0,0 +1,228 @@
+use crate::audio::{
+    deinterleave, process_frame, AudioProcessing, AudioSink, ChannelMapping, DcBlocker,
+};
+use crate::recorder::Recorder;
+use crate::settings::{SampleFormat, Settings};
+use std::fs::File;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Number of bytes requested from the stream per read.
+const READ_SIZE: usize = 4096;
+/// Time to wait before reopening the stream after an error or end of file.
+const REOPEN_DELAY: Duration = Duration::from_millis(500);
+
+/// Parses interleaved raw PCM into stereo frames.
+///
+/// # Arguments
+/// - `bytes`: Raw sample data, starting at a frame boundary.
+/// - `format`: Sample format of the data.
+/// - `channels`: Number of interleaved channels.
+/// - `mapping`: The channels shown on each side; mono is duplicated to both sides.
+///
+/// # Returns
+/// - The `(left, right)` frames and the number of bytes consumed. Bytes of a trailing partial
+///   frame are not consumed and should be prepended to the next read.
+pub fn parse_frames(
+    bytes: &[u8],
+    format: SampleFormat,
+    channels: u16,
+    mapping: &ChannelMapping,
+) -> (Vec<(f32, f32)>, usize) {
+    let channels = channels.max(1) as usize;
+    let sample_size = format.sample_size();
+    let frame_size = sample_size * channels;
+
+    let decode = |sample: &[u8]| match format {
+        SampleFormat::S16le => i16::from_le_bytes([sample[0], sample[1]]) as f32 / 32_768.0,
+        SampleFormat::F32le => f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]),
+    };
+
+    let consumed = bytes.len() / frame_size * frame_size;
+    let samples: Vec<f32> = bytes[..consumed]
+        .chunks_exact(sample_size)
+        .map(decode)
+        .collect();
+    let mut frames = Vec::new();
+    deinterleave(&samples, channels, mapping, &mut frames);
+
+    (frames, consumed)
+}
+
+/// Starts a thread reading raw PCM from the configured FIFO, or standard input when `audio.path`
+/// is `"-"`, into `sink`.
+///
+/// # Arguments
+/// - `sink`: Receives the audio after every read.
+/// - `settings`: A shared reference to `Settings` containing the `[audio]` stream format.
+/// - `recorder`: Receives the read samples while a recording is in progress.
+/// - `shutdown`: Set to stop reading; checked after every read, so a writer that stays silent
+///   keeps the thread waiting.
+///
+/// When the writer closes the stream, the buffers are filled with silence and the stream is
+/// reopened, so the visualizer survives players restarting.
+pub fn start_fifo_stream(
+    mut sink: AudioSink,
+    settings: Arc<Settings>,
+    recorder: Arc<Recorder>,
+    shutdown: Arc<AtomicBool>,
+) {
+    let audio_settings = &settings.audio;
+    let processing = AudioProcessing::from_settings(audio_settings);
+    let path = audio_settings.path.clone();
+    let format = audio_settings.format;
+    let channels = audio_settings.channels;
+    let mapping = ChannelMapping::from_settings(audio_settings);
+    if let Err(e) = mapping.check(channels as usize) {
+        eprintln!("{}; using the last channel instead.", e);
+    }
+    let sample_rate = audio_settings.rate;
+    let highpass_hz = audio_settings.highpass_hz;
+    recorder.set_sample_rate(sample_rate);
+    let status = sink.status();
+    {
+        let mut status = status.lock().unwrap();
+        status.device = if path == "-" {
+            "standard input".to_string()
+        } else {
+            path.clone()
+        };
+        status.sample_rate = sample_rate;
+        status.channels = channels;
+    }
+
+    thread::spawn(move || {
+        let mut dc_blocker_left = DcBlocker::new(highpass_hz, sample_rate as f32);
+        let mut dc_blocker_right = DcBlocker::new(highpass_hz, sample_rate as f32);
+        let mut buffer = vec![0u8; READ_SIZE];
+        let mut pending: Vec<u8> = Vec::new();
+
+        while !shutdown.load(Ordering::Relaxed) {
+            let mut stream: Box<dyn Read> = if path == "-" {
+                Box::new(io::stdin())
+            } else {
+                match File::open(&path) {
+                    Ok(file) => Box::new(file),
+                    Err(e) => {
+                        eprintln!("Failed to open audio FIFO {}: {}", path, e);
+                        status.lock().unwrap().set_error(e.to_string());
+                        thread::sleep(REOPEN_DELAY);
+                        continue;
+                    }
+                }
+            };
+
+            while !shutdown.load(Ordering::Relaxed) {
+                let read = match stream.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(read) => read,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        eprintln!("Failed to read audio FIFO {}: {}", path, e);
+                        status.lock().unwrap().set_error(e.to_string());
+                        break;
+                    }
+                };
+
+                pending.extend_from_slice(&buffer[..read]);
+                let (frames, consumed) = parse_frames(&pending, format, channels, &mapping);
+                pending.drain(..consumed);
+
+                if recorder.is_recording() {
+                    recorder.push(frames.iter().flat_map(|&(l, r)| [l, r]).collect());
+                }
+
+                let frames: Vec<(f32, f32)> = frames
+                    .into_iter()
+                    .map(|(left, right)| {
+                        process_frame(
+                            dc_blocker_left.process(left),
+                            dc_blocker_right.process(right),
+                            &processing,
+                        )
+                    })
+                    .collect();
+                sink.push_frames(&frames);
+            }
+
+            // The writer went away: show silence until it comes back
+            sink.clear();
+            pending.clear();
+            thread::sleep(REOPEN_DELAY);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_s16le_stereo() {
+        let bytes: Vec<u8> = [16_384i16, -16_384, 0, 32_767]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+
+        let (frames, consumed) =
+            parse_frames(&bytes, SampleFormat::S16le, 2, &ChannelMapping::default());
+        assert_eq!(consumed, 8);
+        assert_eq!(frames[0], (0.5, -0.5));
+        assert_eq!(frames[1].0, 0.0);
+        assert!((frames[1].1 - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn parses_f32le_mono_to_both_sides() {
+        let bytes: Vec<u8> = [0.25f32, -0.75]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+
+        let (frames, consumed) =
+            parse_frames(&bytes, SampleFormat::F32le, 1, &ChannelMapping::default());
+        assert_eq!(consumed, 8);
+        assert_eq!(frames, [(0.25, 0.25), (-0.75, -0.75)]);
+    }
+
+    #[test]
+    fn leaves_partial_frames_unconsumed() {
+        let bytes: Vec<u8> = [0.5f32, -0.5, 0.25]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+
+        // One and a half stereo frames, cut in the middle of the last sample
+        let (frames, consumed) = parse_frames(
+            &bytes[..10],
+            SampleFormat::F32le,
+            2,
+            &ChannelMapping::default(),
+        );
+        assert_eq!(frames, [(0.5, -0.5)]);
+        assert_eq!(consumed, 8);
+
+        let (frames, consumed) = parse_frames(
+            &bytes[..3],
+            SampleFormat::F32le,
+            2,
+            &ChannelMapping::default(),
+        );
+        assert!(frames.is_empty());
+        assert_eq!(consumed, 0);
+    }
+
+    #[test]
+    fn ignores_channels_beyond_the_second() {
+        let bytes: Vec<u8> = [0.1f32, 0.2, 0.9, 0.3, 0.4, 0.9]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+
+        let (frames, _) = parse_frames(&bytes, SampleFormat::F32le, 3, &ChannelMapping::default());
+        assert_eq!(frames, [(0.1, 0.2), (0.3, 0.4)]);
+    }
+}
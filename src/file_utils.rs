@@ -0,0 +1,120 @@
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Reads a resource file, falling back to the copy embedded at compile time.
+///
+/// # Arguments
+/// - `path`: The file, relative to the working directory.
+/// - `embedded`: The contents used when the file cannot be read.
+/// - `what`: What the resource is, e.g. `"UI definition"`, for describing the embedded copy.
+///
+/// # Returns
+/// - The contents, and where they were read from for error messages: the absolute path of the
+///   file, or the embedded copy along with the path that was searched.
+pub fn read_resource(path: &str, embedded: &str, what: &str) -> (String, String) {
+    let searched = std::env::current_dir()
+        .map(|dir| dir.join(path))
+        .unwrap_or_else(|_| Path::new(path).to_path_buf());
+    match fs::read_to_string(path) {
+        Ok(contents) => (contents, searched.display().to_string()),
+        Err(e) => (
+            embedded.to_string(),
+            format!(
+                "the embedded {} ({} could not be read: {})",
+                what,
+                searched.display(),
+                e
+            ),
+        ),
+    }
+}
+
+/// Builds a file name of the form `sonic_spectra_YYYYMMDD_HHMMSS.<extension>`.
+///
+/// # Arguments
+/// - `time`: The time encoded in the name, formatted in UTC.
+/// - `extension`: The file extension, without the leading dot.
+///
+/// # Returns
+/// - The file name.
+pub fn timestamped_file_name(time: SystemTime, extension: &str) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let seconds_of_day = seconds % 86_400;
+
+    format!(
+        "sonic_spectra_{:04}{:02}{:02}_{:02}{:02}{:02}.{}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        extension
+    )
+}
+
+/// Converts a number of days since 1970-01-01 to a `(year, month, day)` date.
+///
+/// Uses the proleptic Gregorian calendar, following Howard Hinnant's `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn converts_days_to_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+
+    #[test]
+    fn missing_resources_fall_back_to_the_embedded_copy() {
+        let (contents, source) = read_resource("Cargo.toml", "embedded", "manifest");
+        assert!(contents.contains("[package]"));
+        assert!(source.ends_with("Cargo.toml"), "{}", source);
+
+        let (contents, source) = read_resource("no/such/file.ui", "embedded", "UI definition");
+        assert_eq!(contents, "embedded");
+        assert!(
+            source.starts_with("the embedded UI definition ("),
+            "{}",
+            source
+        );
+        assert!(source.contains("no/such/file.ui"), "{}", source);
+    }
+
+    #[test]
+    fn file_names_encode_the_utc_time() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_709_217_045);
+        assert_eq!(
+            timestamped_file_name(time, "wav"),
+            "sonic_spectra_20240229_143045.wav"
+        );
+    }
+}
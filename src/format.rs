@@ -0,0 +1,104 @@
+/// Levels below this are shown as `-inf dB`, in dB.
+const DB_FLOOR: f32 = -120.0;
+
+/// Formats a frequency for display, such as `440 Hz`, `1.2 kHz` or `18.5 kHz`.
+///
+/// Frequencies that would round to 1000 Hz are shown in kHz, so the unit changes where the
+/// digits do.
+///
+/// # Arguments
+/// - `frequency`: The frequency, in Hz.
+/// - `decimal_comma`: Whether the decimal separator is a comma, as set by `ui.decimal_comma`.
+pub fn format_frequency(frequency: f32, decimal_comma: bool) -> String {
+    let text = if frequency >= 999.5 {
+        format!("{:.1} kHz", frequency / 1000.0)
+    } else {
+        format!("{:.0} Hz", frequency)
+    };
+    with_separator(text, decimal_comma)
+}
+
+/// Formats a level for display, such as `-12.4 dB`, or `-inf dB` for silence.
+///
+/// # Arguments
+/// - `db`: The level, in dB; levels below -120 dB, negative infinity and NaN are shown as
+///   `-inf dB`.
+/// - `decimal_comma`: Whether the decimal separator is a comma, as set by `ui.decimal_comma`.
+pub fn format_db(db: f32, decimal_comma: bool) -> String {
+    if db.is_nan() || db < DB_FLOOR {
+        return "-inf dB".to_string();
+    }
+    // Levels just below zero round to zero without a sign
+    let rounded = (db * 10.0).round() / 10.0;
+    let rounded = if rounded == 0.0 { 0.0 } else { rounded };
+    with_separator(format!("{:.1} dB", rounded), decimal_comma)
+}
+
+/// Formats a duration for display, such as `16.7 ms` or `1.25 s`.
+///
+/// # Arguments
+/// - `ms`: The duration, in milliseconds.
+/// - `decimal_comma`: Whether the decimal separator is a comma, as set by `ui.decimal_comma`.
+pub fn format_duration(ms: f32, decimal_comma: bool) -> String {
+    let text = if ms >= 999.95 {
+        format!("{:.2} s", ms / 1000.0)
+    } else {
+        format!("{:.1} ms", ms)
+    };
+    with_separator(text, decimal_comma)
+}
+
+/// Replaces the decimal point of a formatted number with a comma when asked to.
+fn with_separator(text: String, decimal_comma: bool) -> String {
+    if decimal_comma {
+        text.replace('.', ",")
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frequencies_switch_to_khz_at_1000_hz() {
+        assert_eq!(format_frequency(440.0, false), "440 Hz");
+        assert_eq!(format_frequency(999.0, false), "999 Hz");
+        assert_eq!(format_frequency(999.7, false), "1.0 kHz");
+        assert_eq!(format_frequency(1000.0, false), "1.0 kHz");
+        assert_eq!(format_frequency(1234.0, false), "1.2 kHz");
+        assert_eq!(format_frequency(18500.0, false), "18.5 kHz");
+        assert_eq!(format_frequency(0.0, false), "0 Hz");
+    }
+
+    #[test]
+    fn levels_round_to_a_tenth_above_the_floor() {
+        assert_eq!(format_db(-12.44, false), "-12.4 dB");
+        assert_eq!(format_db(0.0, false), "0.0 dB");
+        assert_eq!(format_db(-0.04, false), "0.0 dB");
+        assert_eq!(format_db(3.0, false), "3.0 dB");
+        assert_eq!(format_db(-120.0, false), "-120.0 dB");
+        assert_eq!(format_db(-120.1, false), "-inf dB");
+        assert_eq!(format_db(f32::NEG_INFINITY, false), "-inf dB");
+        assert_eq!(format_db(f32::NAN, false), "-inf dB");
+    }
+
+    #[test]
+    fn durations_switch_to_seconds_at_1000_ms() {
+        assert_eq!(format_duration(16.67, false), "16.7 ms");
+        assert_eq!(format_duration(999.9, false), "999.9 ms");
+        assert_eq!(format_duration(999.96, false), "1.00 s");
+        assert_eq!(format_duration(1250.0, false), "1.25 s");
+        assert_eq!(format_duration(0.0, false), "0.0 ms");
+    }
+
+    #[test]
+    fn decimal_comma_replaces_the_point() {
+        assert_eq!(format_frequency(1234.0, true), "1,2 kHz");
+        assert_eq!(format_frequency(440.0, true), "440 Hz");
+        assert_eq!(format_db(-12.44, true), "-12,4 dB");
+        assert_eq!(format_db(f32::NEG_INFINITY, true), "-inf dB");
+        assert_eq!(format_duration(1250.0, true), "1,25 s");
+    }
+}
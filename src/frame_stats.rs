@@ -0,0 +1,249 @@
+use crate::settings::UiSettings;
+use crate::text::{draw_text, measure, TextStyle};
+use gtk::cairo::Context;
+use gtk4 as gtk;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Number of frames the averages are taken over.
+const WINDOW_LEN: usize = 60;
+/// Font size of the overlay text, in pixels.
+const FONT_SIZE: f64 = 12.0;
+/// Distance of the overlay from the window edges, in pixels.
+const MARGIN: f64 = 12.0;
+
+/// Average of the most recent `WINDOW_LEN` values.
+///
+/// # Fields
+/// - `values`: The values in the window, oldest first.
+/// - `sum`: Sum of `values`, kept up to date as values enter and leave the window.
+#[derive(Default)]
+struct RollingAverage {
+    values: VecDeque<f64>,
+    sum: f64,
+}
+
+impl RollingAverage {
+    /// Adds a value, dropping the oldest one once the window is full.
+    fn push(&mut self, value: f64) {
+        if self.values.len() == WINDOW_LEN {
+            if let Some(oldest) = self.values.pop_front() {
+                self.sum -= oldest;
+            }
+        }
+        self.values.push_back(value);
+        self.sum += value;
+    }
+
+    /// Returns the average of the window, or `None` before the first value.
+    fn average(&self) -> Option<f64> {
+        if self.values.is_empty() {
+            None
+        } else {
+            Some(self.sum / self.values.len() as f64)
+        }
+    }
+}
+
+/// Rolling timing measurements of the draw loop, shown by the debug overlay.
+///
+/// Recording only stores a few numbers per frame, so the measurements are always taken and
+/// only the overlay is optional.
+///
+/// # Fields
+/// - `frame_intervals`: Seconds between the starts of consecutive frames.
+/// - `analysis_times`: Seconds spent in the FFT and analysis of each frame.
+/// - `draw_times`: Seconds spent drawing each frame with Cairo.
+/// - `fill_rates`: Captured frames per frame, as a fraction of the FFT size.
+/// - `latencies`: Estimated seconds the displayed spectrum lags behind the captured audio.
+/// - `skip_rates`: Fraction of the redraw timer ticks before each frame that skipped the redraw.
+/// - `gains`: The input gain in dB and the display gain of the latest frame, if recorded.
+/// - `last_frame`: Start of the previous frame.
+#[derive(Default)]
+pub struct FrameStats {
+    frame_intervals: RollingAverage,
+    analysis_times: RollingAverage,
+    draw_times: RollingAverage,
+    fill_rates: RollingAverage,
+    latencies: RollingAverage,
+    skip_rates: RollingAverage,
+    gains: Option<(f32, f32)>,
+    last_frame: Option<Instant>,
+}
+
+impl FrameStats {
+    /// Creates a new `FrameStats` instance without measurements.
+    pub fn new() -> Self {
+        FrameStats::default()
+    }
+
+    /// Records the start of a frame.
+    pub fn start_frame(&mut self, now: Instant) {
+        if let Some(last_frame) = self.last_frame {
+            self.frame_intervals
+                .push(now.duration_since(last_frame).as_secs_f64());
+        }
+        self.last_frame = Some(now);
+    }
+
+    /// Records the time spent analyzing a frame.
+    pub fn record_analysis(&mut self, elapsed: Duration) {
+        self.analysis_times.push(elapsed.as_secs_f64());
+    }
+
+    /// Records the time spent drawing a frame.
+    pub fn record_draw(&mut self, elapsed: Duration) {
+        self.draw_times.push(elapsed.as_secs_f64());
+    }
+
+    /// Records how much of the audio buffer was refilled since the previous frame.
+    ///
+    /// # Arguments
+    /// - `received_frames`: Frames captured since the previous frame.
+    /// - `fft_size`: Length of the audio buffer; above `1.0`, samples were never analyzed.
+    pub fn record_fill(&mut self, received_frames: usize, fft_size: usize) {
+        self.fill_rates
+            .push(received_frames as f64 / fft_size.max(1) as f64);
+    }
+
+    /// Records the estimated latency of a frame, from `latency::visual_latency`.
+    pub fn record_latency(&mut self, latency: Duration) {
+        self.latencies.push(latency.as_secs_f64());
+    }
+
+    /// Records the redraws skipped before a frame because nothing changed.
+    ///
+    /// # Arguments
+    /// - `skipped`: Timer ticks since the previous frame that did not redraw.
+    pub fn record_skipped(&mut self, skipped: usize) {
+        self.skip_rates
+            .push(skipped as f64 / (skipped as f64 + 1.0));
+    }
+
+    /// Records the gains the latest frame was analyzed and drawn with.
+    ///
+    /// # Arguments
+    /// - `input_gain_db`: Gain applied to the samples before the FFT, in dB.
+    /// - `display_gain`: Factor the drawn magnitudes were multiplied by.
+    pub fn record_gains(&mut self, input_gain_db: f32, display_gain: f32) {
+        self.gains = Some((input_gain_db, display_gain));
+    }
+
+    /// Returns the measured frame rate, in frames per second.
+    pub fn fps(&self) -> Option<f64> {
+        self.frame_intervals
+            .average()
+            .filter(|&interval| interval > 0.0)
+            .map(|interval| 1.0 / interval)
+    }
+
+    /// Formats the averages as one line per measurement.
+    fn lines(&self) -> Vec<String> {
+        let millis = |average: &RollingAverage| match average.average() {
+            Some(seconds) => format!("{:6.2} ms", seconds * 1000.0),
+            None => "     - ms".to_string(),
+        };
+        let fps = match self.fps() {
+            Some(fps) => format!("{:6.1}", fps),
+            None => "     -".to_string(),
+        };
+        let percent = |average: &RollingAverage| match average.average() {
+            Some(fraction) => format!("{:6.0} %", fraction * 100.0),
+            None => "     - %".to_string(),
+        };
+
+        let (input_gain, display_gain) = match self.gains {
+            Some((input_gain_db, display_gain)) => (
+                format!("{:+6.1} dB", input_gain_db),
+                format!("{:6.1} x", display_gain),
+            ),
+            None => ("     - dB".to_string(), "     - x".to_string()),
+        };
+
+        vec![
+            format!("fps      {}", fps),
+            format!("analysis {}", millis(&self.analysis_times)),
+            format!("draw     {}", millis(&self.draw_times)),
+            format!("fill     {}", percent(&self.fill_rates)),
+            format!("latency  {}", millis(&self.latencies)),
+            format!("skipped  {}", percent(&self.skip_rates)),
+            format!("in gain  {}", input_gain),
+            format!("bar gain {}", display_gain),
+        ]
+    }
+
+    /// Draws the averages as monospace text in the top-left corner.
+    ///
+    /// # Arguments
+    /// - `cr`: The Cairo `Context` used for drawing.
+    /// - `ui`: The `[ui]` settings providing the font.
+    pub fn draw(&self, cr: &Context, ui: &UiSettings) {
+        let style = TextStyle {
+            color: (1.0, 1.0, 1.0, 0.9),
+            ..TextStyle::monospace(ui, FONT_SIZE)
+        };
+        for (i, line) in self.lines().iter().enumerate() {
+            let (_, line_height) = measure(line, &style);
+            draw_text(cr, line, MARGIN, MARGIN + line_height * i as f64, &style);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rolling_average_keeps_the_last_values() {
+        let mut average = RollingAverage::default();
+        assert_eq!(average.average(), None);
+
+        for value in 0..WINDOW_LEN {
+            average.push(value as f64);
+        }
+        average.push(1000.0);
+        assert_eq!(average.values.len(), WINDOW_LEN);
+        // 0 left the window, 1000 entered it
+        let expected = ((1..WINDOW_LEN).sum::<usize>() as f64 + 1000.0) / WINDOW_LEN as f64;
+        assert!((average.average().unwrap() - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fps_follows_the_frame_intervals() {
+        let mut stats = FrameStats::new();
+        let start = Instant::now();
+        stats.start_frame(start);
+        assert_eq!(stats.fps(), None);
+
+        for frame in 1..=10 {
+            stats.start_frame(start + Duration::from_millis(25 * frame));
+        }
+        assert!((stats.fps().unwrap() - 40.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn lines_show_every_measurement() {
+        let mut stats = FrameStats::new();
+        stats.record_analysis(Duration::from_micros(1500));
+        stats.record_draw(Duration::from_millis(4));
+        stats.record_fill(512, 1024);
+        stats.record_latency(Duration::from_millis(85));
+        stats.record_skipped(3);
+        stats.record_skipped(0);
+
+        let lines = stats.lines();
+        assert_eq!(lines[0], "fps           -");
+        assert_eq!(lines[1], "analysis   1.50 ms");
+        assert_eq!(lines[2], "draw       4.00 ms");
+        assert_eq!(lines[3], "fill         50 %");
+        assert_eq!(lines[4], "latency   85.00 ms");
+        // Three of four ticks skipped, then none of one
+        assert_eq!(lines[5], "skipped      38 %");
+        assert_eq!(lines[6], "in gain       - dB");
+
+        stats.record_gains(6.0, 20.0);
+        let lines = stats.lines();
+        assert_eq!(lines[6], "in gain    +6.0 dB");
+        assert_eq!(lines[7], "bar gain   20.0 x");
+    }
+}
@@ -1,5 +1,12 @@
-use crate::fft_utils::{get_color_for_frequency, interpolate};
-use crate::settings::Settings;
+use crate::bar_batch::rounded_rect;
+use crate::color::Palette;
+use crate::fft_utils::{
+    band_emphasis, frequency_indices, get_bar_color_at, interpolate, smoothing_factor,
+};
+use crate::frequency_mapper::{Channel, FrequencyMapper};
+use crate::level_scale::{bar_rect, LevelScale};
+use crate::settings::{ResolvedVisualizerSettings, Settings};
+use crate::smoothing_state::SmoothingState;
 use crate::visualizer::Visualizer;
 use gtk4::cairo::{Context, RadialGradient}; // Use gtk4::cairo
 use rustfft::num_complex::Complex32;
@@ -10,6 +17,9 @@ use std::sync::Arc;
 /// and right audio channels.
 pub struct HolographicGlowVisualizer {
     settings: Arc<Settings>,
+    visual_settings: ResolvedVisualizerSettings,
+    palette: Palette,
+    level_scale: LevelScale,
 }
 
 impl HolographicGlowVisualizer {
@@ -19,27 +29,15 @@ impl HolographicGlowVisualizer {
     ///
     /// * `settings` - Shared application settings that control visualizer parameters.
     pub fn new(settings: Arc<Settings>) -> Self {
-        HolographicGlowVisualizer { settings }
-    }
-
-    /// Calculates the minimum and maximum FFT indices based on frequency bounds.
-    ///
-    /// # Arguments
-    ///
-    /// * `fft_size` - The size of the FFT data array.
-    ///
-    /// # Returns
-    ///
-    /// A tuple containing the minimum and maximum indices for the specified frequency range.
-    fn get_frequency_indices(&self, fft_size: usize) -> (usize, usize) {
-        let fft_settings = &self.settings.fft;
-        let min_freq = fft_settings.min_frequency;
-        let max_freq = fft_settings.max_frequency;
-
-        let min_index = (min_freq * fft_size as f32 / fft_settings.sample_rate) as usize;
-        let max_index = (max_freq * fft_size as f32 / fft_settings.sample_rate) as usize;
-
-        (min_index, max_index)
+        let visual_settings = settings.visualizer_settings("holographic_glow");
+        let palette = Palette::from_settings(&visual_settings);
+        let level_scale = LevelScale::new(&visual_settings, settings.fft.size);
+        HolographicGlowVisualizer {
+            settings,
+            visual_settings,
+            palette,
+            level_scale,
+        }
     }
 }
 
@@ -55,6 +53,7 @@ impl Visualizer for HolographicGlowVisualizer {
     /// * `cr` - The Cairo context to draw on.
     /// * `previous_heights_left` - Stores previous heights of left channel bars for smooth animation.
     /// * `previous_heights_right` - Stores previous heights of right channel bars for smooth animation.
+    /// * `elapsed` - Time since the previous frame in seconds, setting how far the bars move.
     fn draw(
         &self,
         width: i32,
@@ -62,127 +61,206 @@ impl Visualizer for HolographicGlowVisualizer {
         fft_left: &[Complex32],
         fft_right: &[Complex32],
         cr: &Context,
-        previous_heights_left: &mut Vec<f32>,
-        previous_heights_right: &mut Vec<f32>,
+        previous_heights_left: &mut SmoothingState,
+        previous_heights_right: &mut SmoothingState,
+        elapsed: f32,
     ) {
-        let visual_settings = &self.settings.visualizer;
-        let gain = visual_settings.gain;
-        let scale_factor = visual_settings.scale_factor;
-        let interpolation_factor = visual_settings.interpolation_factor;
+        let visual_settings = &self.visual_settings;
+        let interpolation_factor = smoothing_factor(elapsed, visual_settings.smoothing_ms / 1000.0);
         let alpha = visual_settings.alpha;
+        let color_mode = visual_settings.color_mode;
+        let min_bar_height = self.settings.visualizer.min_bar_height;
+        let radius = f64::from(visual_settings.bar_radius);
+        // Without gradients the bars are filled flat, which is cheaper on battery
+        let center = self
+            .settings
+            .effects
+            .glow_gradients
+            .then_some((width as f64 / 2.0, height as f64 / 2.0));
 
         let fft_size = fft_left.len();
-        let (min_index, max_index) = self.get_frequency_indices(fft_size);
+        let (min_index, max_index) = frequency_indices(&self.settings.fft, fft_size);
 
-        // Select the FFT data range for visualization
-        let fft_left = &fft_left[min_index..max_index];
-        let fft_right = &fft_right[min_index..max_index];
+        // Bins narrower than a pixel share a bar, so a narrow window draws fewer bars
+        let mapper = FrequencyMapper::from_settings(&self.settings, fft_size);
+        let half_width = width as f64 / 2.0;
+        let slots = mapper.bar_slots(min_index..max_index, half_width);
+        // Colors follow the frequency of each bar over the whole spectrum, so changing the
+        // displayed range moves the bars without recoloring them
+        let palette_mapper = FrequencyMapper::full_range(&self.settings, fft_size);
 
-        let num_bars = fft_left.len();
-        let bar_width = width as f32 / (2.0 * num_bars as f32).max(1.0);
+        // Draw each channel with a glowing effect
+        for (channel, fft, previous_heights) in [
+            (Channel::Left, fft_left, previous_heights_left),
+            (Channel::Right, fft_right, previous_heights_right),
+        ] {
+            previous_heights.ensure_len(slots.len());
+            for (i, slot) in slots.iter().enumerate() {
+                // A merged bar shows its loudest bin, so narrow peaks stay visible
+                let magnitude = fft[slot.bins.clone()]
+                    .iter()
+                    .map(|value| value.norm())
+                    .fold(0.0, f32::max);
+                let target_height = self.level_scale.height(magnitude);
 
-        // Draw the left channel with a glowing effect
-        for i in 0..num_bars {
-            let magnitude_left = fft_left[i].norm() * gain;
-            let target_height_left = (magnitude_left + 1e-6).log10().max(0.0) * scale_factor;
+                previous_heights[i] =
+                    interpolate(previous_heights[i], target_height, interpolation_factor);
 
-            previous_heights_left[i] = interpolate(
-                previous_heights_left[i],
-                target_height_left,
-                interpolation_factor,
-            );
+                // The height keeps decaying, but invisible bars skip their gradient and fill
+                if previous_heights[i] < min_bar_height {
+                    continue;
+                }
 
-            let color_left = get_color_for_frequency(i, num_bars);
+                let level = previous_heights[i] / height as f32;
+                let position = palette_mapper.position(slot.frequency).unwrap_or(0.0);
+                let color = get_bar_color_at(&self.palette, color_mode, position, level);
+                // Bars outside a soloed band are dimmed, keeping the axis unchanged
+                let emphasis = band_emphasis(self.settings.fft.solo_band, slot.frequency);
+                let rect = bar_rect(
+                    visual_settings.direction,
+                    previous_heights[i] as f64,
+                    slot.bar_span(half_width, channel, visual_settings.bar_gap),
+                    height as f64,
+                );
+                fill_glow_bar(cr, center, color, alpha * emphasis, rect, radius);
+            }
+        }
+    }
 
-            // Create a radial gradient for the glowing effect
-            let gradient = RadialGradient::new(
-                width as f64 / 2.0,
-                height as f64 / 2.0,
-                0.0,
-                width as f64 / 2.0,
-                height as f64 / 2.0,
-                previous_heights_left[i] as f64 * 2.0,
-            );
+    /// Moves the heights to the bars of the new width, which merges bins differently.
+    fn rescale_heights(&self, heights: &mut SmoothingState, from: (i32, i32), to: (i32, i32)) {
+        let fft_size = self.settings.fft.padded_size();
+        let (min_index, max_index) = frequency_indices(&self.settings.fft, fft_size);
+        FrequencyMapper::from_settings(&self.settings, fft_size).remap_slot_state(
+            min_index..max_index,
+            f64::from(from.0) / 2.0,
+            f64::from(to.0) / 2.0,
+            heights,
+        );
+    }
 
-            gradient.add_color_stop_rgba(
-                0.0,
-                color_left.0.into(),
-                color_left.1.into(),
-                color_left.2.into(),
-                alpha as f64,
-            );
-            gradient.add_color_stop_rgba(
-                1.0,
-                color_left.0.into(),
-                color_left.1.into(),
-                color_left.2.into(),
+    fn set_palette(&mut self, palette: &Palette) {
+        self.palette = palette.clone();
+    }
+}
+
+/// Fills one bar with a radial gradient fading out from the center of the display, or with a
+/// flat color without `effects.glow_gradients`.
+///
+/// The center lies halfway between the edges bars grow from in every `visualizer.direction`,
+/// so the glow is the same mirrored image whichever way the bars grow.
+///
+/// # Arguments
+/// - `cr`: The Cairo `Context` used for drawing.
+/// - `center`: Center of the drawing area, where the glow starts; `None` fills the bar with the
+///   flat color.
+/// - `color`: RGB color and opacity multiplier of the bar.
+/// - `alpha`: Opacity of the visualizer.
+/// - `(x, y, bar_width, bar_height)`: The bar, as placed by `bar_rect`.
+/// - `radius`: Radius of the rounded corners of the bar.
+fn fill_glow_bar(
+    cr: &Context,
+    center: Option<(f64, f64)>,
+    color: (f32, f32, f32, f32),
+    alpha: f32,
+    (x, y, bar_width, bar_height): (f64, f64, f64, f64),
+    radius: f64,
+) {
+    let (r, g, b) = (color.0 as f64, color.1 as f64, color.2 as f64);
+    let alpha = (alpha * color.3) as f64;
+    match center {
+        Some(center) => {
+            let gradient = RadialGradient::new(
+                center.0,
+                center.1,
                 0.0,
+                center.0,
+                center.1,
+                bar_height * 2.0,
             );
-
+            gradient.add_color_stop_rgba(0.0, r, g, b, alpha);
+            gradient.add_color_stop_rgba(1.0, r, g, b, 0.0);
             let _ = cr.set_source(&gradient);
-
-            let x = (num_bars as f32 - i as f32 - 1.0) * bar_width;
-            let y = height as f32 - previous_heights_left[i];
-
-            cr.rectangle(
-                x as f64,
-                y as f64,
-                bar_width as f64,
-                previous_heights_left[i] as f64,
-            );
-            cr.fill().unwrap();
         }
+        None => cr.set_source_rgba(r, g, b, alpha),
+    }
 
-        // Draw the right channel with a glowing effect
-        for i in 0..num_bars {
-            let magnitude_right = fft_right[i].norm() * gain;
-            let target_height_right = (magnitude_right + 1e-6).log10().max(0.0) * scale_factor;
-
-            previous_heights_right[i] = interpolate(
-                previous_heights_right[i],
-                target_height_right,
-                interpolation_factor,
-            );
+    rounded_rect(cr, x, y, bar_width, bar_height, radius);
+    let _ = cr.fill();
+}
 
-            let color_right = get_color_for_frequency(i, num_bars);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gtk4::cairo::{Format, ImageSurface};
 
-            // Create a radial gradient for the glowing effect
-            let gradient = RadialGradient::new(
-                width as f64 / 2.0,
-                height as f64 / 2.0,
-                0.0,
-                width as f64 / 2.0,
-                height as f64 / 2.0,
-                previous_heights_right[i] as f64 * 2.0,
+    /// Draws one frame of silence after setting the heights of the left channel.
+    ///
+    /// # Returns
+    /// - The heights of the left channel after the frame, and the surface it was drawn on.
+    fn render_silence(
+        settings: Settings,
+        set_heights: impl Fn(&mut SmoothingState),
+    ) -> (Vec<f32>, ImageSurface) {
+        let fft_size = settings.fft.size;
+        let visualizer = HolographicGlowVisualizer::new(Arc::new(settings));
+        let surface = ImageSurface::create(Format::ARgb32, 1280, 720).unwrap();
+        let cr = Context::new(&surface).unwrap();
+        let silence = vec![Complex32::new(0.0, 0.0); fft_size];
+        let mut heights_left = SmoothingState::default();
+        let mut heights_right = SmoothingState::default();
+        // The first frame sizes the heights to the bars
+        for frame in 0..2 {
+            if frame == 1 {
+                set_heights(&mut heights_left);
+            }
+            visualizer.draw(
+                1280,
+                720,
+                &silence,
+                &silence,
+                &cr,
+                &mut heights_left,
+                &mut heights_right,
+                1.0 / 60.0,
             );
+        }
+        (heights_left.to_vec(), surface)
+    }
 
-            gradient.add_color_stop_rgba(
-                0.0,
-                color_right.0.into(),
-                color_right.1.into(),
-                color_right.2.into(),
-                alpha as f64,
-            );
-            gradient.add_color_stop_rgba(
-                1.0,
-                color_right.0.into(),
-                color_right.1.into(),
-                color_right.2.into(),
-                0.0,
-            );
+    #[test]
+    fn skipped_bars_keep_decaying() {
+        let mut settings = Settings::default();
+        settings.fft.min_frequency = 0.0;
+        let (heights, _) = render_silence(settings, |heights| heights[100] = 50.0);
+        assert!(heights[100] < 50.0 && heights[100] > 0.0);
 
-            let _ = cr.set_source(&gradient);
+        let mut settings = Settings::default();
+        settings.fft.min_frequency = 0.0;
+        settings.visualizer.min_bar_height = 100.0;
+        let (skipped_heights, _) = render_silence(settings, |heights| heights[100] = 50.0);
+        assert_eq!(skipped_heights[100], heights[100]);
+    }
 
-            let x = width as f32 - (num_bars as f32 - i as f32 - 1.0) * bar_width;
-            let y = height as f32 - previous_heights_right[i];
+    #[test]
+    fn bars_below_the_minimum_height_are_not_filled() {
+        let lit_pixels = |mut surface: ImageSurface| {
+            surface.flush();
+            let data = surface.data().unwrap();
+            data.iter().filter(|&&byte| byte != 0).count()
+        };
+        let faint = |heights: &mut SmoothingState| heights.fill(0.4);
+        // Flat fills, as the glow of bars this low fades out before reaching them
+        let mut settings = Settings::default();
+        settings.effects.glow_gradients = false;
 
-            cr.rectangle(
-                x as f64,
-                y as f64,
-                bar_width as f64,
-                previous_heights_right[i] as f64,
-            );
-            cr.fill().unwrap();
-        }
+        let (_, skipped) = render_silence(settings.clone(), faint);
+        assert_eq!(lit_pixels(skipped), 0);
+
+        // The same bars leave a trace when every bar is filled
+        let mut every_bar = settings;
+        every_bar.visualizer.min_bar_height = 0.0;
+        let (_, filled) = render_silence(every_bar, faint);
+        assert!(lit_pixels(filled) > 0);
     }
 }
@@ -0,0 +1,627 @@
+use crate::fft_utils::frequency_indices;
+use crate::settings::{FrequencyScale, MarginLength, Settings};
+use crate::smoothing_state::SmoothingState;
+use std::ops::Range;
+
+/// Narrowest bar laid out by `FrequencyMapper::bar_slots`, in pixels.
+const MIN_BAR_WIDTH: f64 = 1.0;
+
+/// Half of the display.
+///
+/// - `Left`: The left half; frequencies grow from the center towards the left edge, or from the
+///   left edge towards the center when `layout.mirror_left` is off.
+/// - `Right`: The right half; frequencies grow from the center towards the right edge.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Channel {
+    Left,
+    Right,
+}
+
+/// One bar on a half of the display, covering one or more adjacent FFT bins.
+///
+/// # Fields
+/// - `bins`: Indices of the covered bins in the full FFT data array.
+/// - `frequency`: Center frequency of the covered bins, in Hz.
+/// - `inner`: Distance of the bar's lowest frequency edge from the low end of its half, in
+///   pixels.
+/// - `outer`: Distance of the bar's highest frequency edge from the low end of its half, in
+///   pixels.
+/// - `mirror_left`: Whether the bar is mirrored at the center on the left half, as laid out by
+///   a mapper with `layout.mirror_left` on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BarSlot {
+    pub bins: Range<usize>,
+    pub frequency: f32,
+    pub inner: f64,
+    pub outer: f64,
+    pub mirror_left: bool,
+}
+
+impl BarSlot {
+    /// Returns the left edge and width of the bar on one half of the display.
+    ///
+    /// # Arguments
+    /// - `half_width`: Half of the width of the drawing area.
+    /// - `channel`: The half the bar is drawn on.
+    ///
+    /// # Returns
+    /// - The left edge, clamped to the drawing area, and the width of the bar, in pixels.
+    pub fn span(&self, half_width: f64, channel: Channel) -> (f64, f64) {
+        let inner = half_x(self.inner, half_width, channel, self.mirror_left);
+        let outer = half_x(self.outer, half_width, channel, self.mirror_left);
+        (
+            inner.min(outer).clamp(0.0, 2.0 * half_width),
+            self.outer - self.inner,
+        )
+    }
+
+    /// Returns the left edge and width of the bar drawn in the slot, leaving a gap to its
+    /// neighbors.
+    ///
+    /// The gap is taken out of the width, half on each side, so the bar keeps its center and
+    /// the bars of all slots still span the whole width.
+    ///
+    /// # Arguments
+    /// - `half_width`: Half of the width of the drawing area.
+    /// - `channel`: The half the bar is drawn on.
+    /// - `gap`: Gap in pixels or as a percentage of the slot width; at most half the slot is
+    ///   left empty, so bars never vanish.
+    ///
+    /// # Returns
+    /// - The left edge and width of the bar, in pixels.
+    pub fn bar_span(&self, half_width: f64, channel: Channel, gap: MarginLength) -> (f64, f64) {
+        let (x, width) = self.span(half_width, channel);
+        let gap = gap.pixels(width).min(width / 2.0);
+        (x + gap / 2.0, width - gap)
+    }
+}
+
+/// Maps frequencies to horizontal positions, shared by the grid and the visualizers so markers
+/// line up with the bars drawn at their frequency.
+///
+/// # Fields
+/// - `min_frequency`: Frequency at position `0.0`, in Hz.
+/// - `max_frequency`: Frequency at position `1.0`, in Hz.
+/// - `bin_width`: Width of one FFT bin, in Hz.
+/// - `scale`: Whether positions are linear or logarithmic in frequency.
+/// - `mirror_left`: Whether the left half is mirrored at the center; see
+///   `LayoutSettings::mirror_left`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrequencyMapper {
+    min_frequency: f32,
+    max_frequency: f32,
+    bin_width: f32,
+    scale: FrequencyScale,
+    mirror_left: bool,
+}
+
+impl FrequencyMapper {
+    /// Creates a new `FrequencyMapper` instance, with the left half mirrored at the center.
+    ///
+    /// # Arguments
+    /// - `min_frequency`: Frequency at position `0.0`, in Hz.
+    /// - `max_frequency`: Frequency at position `1.0`, in Hz.
+    /// - `bin_width`: Width of one FFT bin, in Hz; also the lowest frequency of a log scale
+    ///   starting at 0 Hz.
+    /// - `scale`: Whether positions are linear or logarithmic in frequency.
+    pub fn new(
+        min_frequency: f32,
+        max_frequency: f32,
+        bin_width: f32,
+        scale: FrequencyScale,
+    ) -> Self {
+        let min_frequency = match scale {
+            FrequencyScale::Linear => min_frequency,
+            FrequencyScale::Log => min_frequency.max(bin_width / 2.0),
+        };
+        FrequencyMapper {
+            min_frequency,
+            max_frequency: max_frequency.max(min_frequency + f32::EPSILON),
+            bin_width,
+            scale,
+            mirror_left: true,
+        }
+    }
+
+    /// Creates the mapper of the displayed FFT range.
+    ///
+    /// # Arguments
+    /// - `settings`: Settings providing the frequency range, `visualizer.frequency_scale` and
+    ///   `layout.mirror_left`.
+    /// - `fft_size`: The size of the FFT data array.
+    pub fn from_settings(settings: &Settings, fft_size: usize) -> Self {
+        let (min_index, max_index) = frequency_indices(&settings.fft, fft_size);
+        let bin_width = settings.fft.sample_rate / fft_size as f32;
+        FrequencyMapper {
+            mirror_left: settings.layout.mirror_left,
+            ..FrequencyMapper::new(
+                min_index as f32 * bin_width,
+                max_index as f32 * bin_width,
+                bin_width,
+                settings.visualizer.frequency_scale,
+            )
+        }
+    }
+
+    /// Creates the mapper of the whole FFT range, from 0 Hz to half of `fft.sample_rate`.
+    ///
+    /// Bar colors are looked up at the positions of this mapper, so a frequency keeps its color
+    /// when the displayed range changes.
+    ///
+    /// # Arguments
+    /// - `settings`: Settings providing the sample rate and `visualizer.frequency_scale`.
+    /// - `fft_size`: The size of the FFT data array.
+    pub fn full_range(settings: &Settings, fft_size: usize) -> Self {
+        let bin_width = settings.fft.sample_rate / fft_size as f32;
+        FrequencyMapper::new(
+            0.0,
+            settings.fft.sample_rate / 2.0,
+            bin_width,
+            settings.visualizer.frequency_scale,
+        )
+    }
+
+    /// Returns the position of a frequency.
+    ///
+    /// # Returns
+    /// - The position in [0.0, 1.0], or `None` if the frequency is outside the mapped range.
+    pub fn position(&self, frequency: f32) -> Option<f32> {
+        if frequency < self.min_frequency || frequency > self.max_frequency {
+            return None;
+        }
+        Some(self.unclamped_position(frequency))
+    }
+
+    /// Returns the position of the lower edge of an FFT bin, clamped to [0.0, 1.0].
+    ///
+    /// # Arguments
+    /// - `index`: Index of the bin in the full FFT data array.
+    pub fn bin_position(&self, index: usize) -> f32 {
+        let frequency = index as f32 * self.bin_width;
+        self.unclamped_position(frequency.max(self.min_frequency))
+            .clamp(0.0, 1.0)
+    }
+
+    /// Converts a position to an x coordinate on one half of the display, mirrored at the
+    /// center on the left half unless `layout.mirror_left` is off.
+    ///
+    /// # Arguments
+    /// - `position`: Position returned by `position` or `bin_position`.
+    /// - `half_width`: Half of the width of the drawing area.
+    /// - `channel`: The half the position is drawn on.
+    pub fn mirrored_x(&self, position: f32, half_width: f64, channel: Channel) -> f64 {
+        half_x(
+            position as f64 * half_width,
+            half_width,
+            channel,
+            self.mirror_left,
+        )
+    }
+
+    /// Returns whether frequencies grow towards the left edge on a half, so its bars and labels
+    /// are walked from right to left.
+    pub fn descending(&self, channel: Channel) -> bool {
+        channel == Channel::Left && self.mirror_left
+    }
+
+    /// Lays out the bars of a range of FFT bins on one half of the display.
+    ///
+    /// # Arguments
+    /// - `bins`: Indices of the displayed bins in the full FFT data array.
+    /// - `half_width`: Half of the width of the drawing area.
+    ///
+    /// # Returns
+    /// - One slot per bar, from the center outward. Bins narrower than a pixel are merged with
+    ///   the following bins until the bar is at least a pixel wide, so a narrow window draws
+    ///   fewer bars instead of collapsed ones. No bars fit into less than a pixel.
+    pub fn bar_slots(&self, bins: Range<usize>, half_width: f64) -> Vec<BarSlot> {
+        let mut slots = Vec::new();
+        if half_width.is_nan() || half_width < MIN_BAR_WIDTH {
+            return slots;
+        }
+        let offset = |index: usize| self.bin_position(index) as f64 * half_width;
+        let slot = |bins: Range<usize>, inner: f64, outer: f64| BarSlot {
+            frequency: (bins.start + bins.end) as f32 / 2.0 * self.bin_width,
+            bins,
+            inner,
+            outer,
+            mirror_left: self.mirror_left,
+        };
+
+        let mut start = bins.start;
+        for index in bins.clone() {
+            let (inner, outer) = (offset(start), offset(index + 1));
+            if outer - inner >= MIN_BAR_WIDTH {
+                slots.push(slot(start..index + 1, inner, outer));
+                start = index + 1;
+            }
+        }
+
+        // The highest bins are too narrow for a bar of their own, so the last bar covers them
+        if start < bins.end {
+            match slots.last_mut() {
+                Some(last) => *last = slot(last.bins.start..bins.end, last.inner, offset(bins.end)),
+                None => slots.push(slot(start..bins.end, offset(start), offset(bins.end))),
+            }
+        }
+        slots
+    }
+
+    /// Moves the heights of the bars laid out by `bar_slots` at one width to the bars laid out
+    /// at another width.
+    ///
+    /// # Arguments
+    /// - `bins`: Indices of the displayed bins, as passed to `bar_slots`.
+    /// - `from`: Half of the width the heights were laid out at.
+    /// - `to`: Half of the new width.
+    /// - `heights`: Heights of the bars at `from`, replaced by the heights at `to`.
+    ///
+    /// Each new bar takes the tallest of the previous bars sharing a bin with it, as merged
+    /// bars show their loudest bin. Heights past the last new bar are reset to zero.
+    pub fn remap_slot_heights(&self, bins: Range<usize>, from: f64, to: f64, heights: &mut [f32]) {
+        let previous_slots = self.bar_slots(bins.clone(), from);
+        let previous_heights: Vec<f32> =
+            heights.iter().take(previous_slots.len()).copied().collect();
+        let slots = self.bar_slots(bins, to);
+        for (i, height) in heights.iter_mut().enumerate() {
+            *height = match slots.get(i) {
+                Some(slot) => previous_slots
+                    .iter()
+                    .zip(&previous_heights)
+                    .filter(|(previous, _)| {
+                        previous.bins.start < slot.bins.end && slot.bins.start < previous.bins.end
+                    })
+                    .map(|(_, &height)| height)
+                    .fold(0.0, f32::max),
+                None => 0.0,
+            };
+        }
+    }
+
+    /// Moves the smoothing state of the bars of `bar_slots` to another width like
+    /// `remap_slot_heights`, and resizes it to the number of bars at the new width.
+    ///
+    /// # Arguments
+    /// - `bins`: The bins the bars are laid out over.
+    /// - `from`: Half of the previous width.
+    /// - `to`: Half of the new width.
+    /// - `state`: Heights of the bars at `from`, replaced by the heights at `to`.
+    pub fn remap_slot_state(
+        &self,
+        bins: Range<usize>,
+        from: f64,
+        to: f64,
+        state: &mut SmoothingState,
+    ) {
+        let len = self.bar_slots(bins.clone(), to).len();
+        state.set_len(state.len().max(len));
+        self.remap_slot_heights(bins, from, to, state);
+        state.set_len(len);
+    }
+
+    /// Returns the frequency at a position; the inverse of `position`.
+    pub fn frequency_at(&self, position: f32) -> f32 {
+        match self.scale {
+            FrequencyScale::Linear => {
+                self.min_frequency + position * (self.max_frequency - self.min_frequency)
+            }
+            FrequencyScale::Log => {
+                self.min_frequency * (self.max_frequency / self.min_frequency).powf(position)
+            }
+        }
+    }
+
+    /// Converts an x coordinate on the display back to a position; the inverse of `mirrored_x`.
+    ///
+    /// # Arguments
+    /// - `x`: The x coordinate, in pixels.
+    /// - `half_width`: Half of the width of the drawing area.
+    ///
+    /// # Returns
+    /// - The position in [0.0, 1.0] and the half `x` lies on.
+    pub fn unmirrored_position(&self, x: f64, half_width: f64) -> (f32, Channel) {
+        let channel = if x < half_width {
+            Channel::Left
+        } else {
+            Channel::Right
+        };
+        let offset = match channel {
+            Channel::Left if !self.mirror_left => x,
+            _ => (x - half_width).abs(),
+        };
+        let position = (offset / half_width.max(f64::EPSILON)).clamp(0.0, 1.0);
+        (position as f32, channel)
+    }
+
+    /// Returns the frequency range covered by a horizontal selection.
+    ///
+    /// # Arguments
+    /// - `start_x`: The x coordinate the selection started at.
+    /// - `end_x`: The x coordinate the selection ended at.
+    /// - `half_width`: Half of the width of the drawing area.
+    ///
+    /// # Returns
+    /// - The lowest and highest selected frequency, in Hz. A selection crossing the center covers
+    ///   the low end of both halves, so it starts at the lowest mapped frequency; without a
+    ///   mirrored left half it covers the high end of the left half as well, so it spans the
+    ///   whole mapped range.
+    pub fn selected_range(&self, start_x: f64, end_x: f64, half_width: f64) -> (f32, f32) {
+        let (start, start_channel) = self.unmirrored_position(start_x, half_width);
+        let (end, end_channel) = self.unmirrored_position(end_x, half_width);
+        let (low, high) = if start_channel == end_channel {
+            (start.min(end), start.max(end))
+        } else if self.mirror_left {
+            (0.0, start.max(end))
+        } else {
+            (0.0, 1.0)
+        };
+        (self.frequency_at(low), self.frequency_at(high))
+    }
+
+    /// Returns the position of a frequency, extrapolated outside the mapped range.
+    fn unclamped_position(&self, frequency: f32) -> f32 {
+        match self.scale {
+            FrequencyScale::Linear => {
+                (frequency - self.min_frequency) / (self.max_frequency - self.min_frequency)
+            }
+            FrequencyScale::Log => {
+                (frequency / self.min_frequency).ln()
+                    / (self.max_frequency / self.min_frequency).ln()
+            }
+        }
+    }
+}
+
+/// Converts a distance from the low frequency end of one half of the display to an x
+/// coordinate.
+///
+/// # Arguments
+/// - `offset`: The distance from the low frequency end of the half, in pixels.
+/// - `half_width`: Half of the width of the drawing area.
+/// - `channel`: The half the distance is measured on.
+/// - `mirror_left`: Whether the left half is mirrored at the center, with its low frequency end
+///   at the center instead of the left edge.
+fn half_x(offset: f64, half_width: f64, channel: Channel, mirror_left: bool) -> f64 {
+    match channel {
+        Channel::Left if mirror_left => half_width - offset,
+        Channel::Left => offset,
+        Channel::Right => half_width + offset,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn linear_scale_respects_min_frequency() {
+        let mapper = FrequencyMapper::new(50.0, 1050.0, 10.0, FrequencyScale::Linear);
+        assert_close(mapper.position(50.0).unwrap(), 0.0);
+        assert_close(mapper.position(550.0).unwrap(), 0.5);
+        assert_close(mapper.position(1050.0).unwrap(), 1.0);
+        // Below the displayed range, so no marker at the center line
+        assert_eq!(mapper.position(20.0), None);
+        assert_eq!(mapper.position(2000.0), None);
+    }
+
+    #[test]
+    fn log_scale_spaces_octaves_evenly() {
+        let mapper = FrequencyMapper::new(100.0, 1600.0, 10.0, FrequencyScale::Log);
+        assert_close(mapper.position(100.0).unwrap(), 0.0);
+        assert_close(mapper.position(200.0).unwrap(), 0.25);
+        assert_close(mapper.position(400.0).unwrap(), 0.5);
+        assert_close(mapper.position(1600.0).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn log_scale_starting_at_zero_uses_half_a_bin() {
+        let mapper = FrequencyMapper::new(0.0, 1000.0, 10.0, FrequencyScale::Log);
+        assert_close(mapper.bin_position(0), 0.0);
+        assert_close(mapper.position(5.0).unwrap(), 0.0);
+        assert!(mapper.bin_position(1) > 0.0);
+    }
+
+    #[test]
+    fn bin_positions_follow_the_scale() {
+        let linear = FrequencyMapper::new(100.0, 500.0, 100.0, FrequencyScale::Linear);
+        assert_close(linear.bin_position(1), 0.0);
+        assert_close(linear.bin_position(3), 0.5);
+        assert_close(linear.bin_position(5), 1.0);
+        assert_close(linear.bin_position(9), 1.0);
+
+        let log = FrequencyMapper::new(100.0, 400.0, 100.0, FrequencyScale::Log);
+        assert_close(log.bin_position(2), 0.5);
+    }
+
+    #[test]
+    fn frequency_at_inverts_position() {
+        let linear = FrequencyMapper::new(50.0, 1050.0, 10.0, FrequencyScale::Linear);
+        let log = FrequencyMapper::new(100.0, 1600.0, 10.0, FrequencyScale::Log);
+        for frequency in [100.0, 440.0, 1000.0] {
+            assert_close(
+                linear.frequency_at(linear.position(frequency).unwrap()),
+                frequency,
+            );
+            assert_close(
+                log.frequency_at(log.position(frequency).unwrap()) / frequency,
+                1.0,
+            );
+        }
+    }
+
+    #[test]
+    fn unmirrored_position_inverts_mirrored_x() {
+        let mapper = FrequencyMapper::new(0.0, 100.0, 1.0, FrequencyScale::Linear);
+        for channel in [Channel::Left, Channel::Right] {
+            let x = mapper.mirrored_x(0.25, 200.0, channel);
+            assert_eq!(mapper.unmirrored_position(x, 200.0), (0.25, channel));
+        }
+        assert_eq!(
+            mapper.unmirrored_position(200.0, 200.0),
+            (0.0, Channel::Right)
+        );
+        assert_eq!(
+            mapper.unmirrored_position(500.0, 200.0),
+            (1.0, Channel::Right)
+        );
+    }
+
+    #[test]
+    fn selected_range_handles_both_halves() {
+        let mapper = FrequencyMapper::new(0.0, 1000.0, 1.0, FrequencyScale::Linear);
+        // Dragging right to left on the right half
+        assert_eq!(mapper.selected_range(300.0, 240.0, 200.0), (200.0, 500.0));
+        // On the left half frequencies grow towards the left edge
+        assert_eq!(mapper.selected_range(160.0, 100.0, 200.0), (200.0, 500.0));
+        // Crossing the center includes the low end of both halves
+        assert_eq!(mapper.selected_range(100.0, 240.0, 200.0), (0.0, 500.0));
+    }
+
+    #[test]
+    fn narrow_bins_are_merged_into_whole_pixels() {
+        // 2048 bins on a 200 pixel window
+        let mapper = FrequencyMapper::new(0.0, 2048.0, 1.0, FrequencyScale::Linear);
+        let slots = mapper.bar_slots(0..2048, 100.0);
+        assert!(!slots.is_empty() && slots.len() <= 100);
+        assert_eq!(slots[0].bins.start, 0);
+        assert_eq!(slots[slots.len() - 1].bins.end, 2048);
+        for pair in slots.windows(2) {
+            assert_eq!(pair[0].bins.end, pair[1].bins.start);
+        }
+        for slot in &slots {
+            assert!(slot.outer - slot.inner >= MIN_BAR_WIDTH - 1e-9);
+            for channel in [Channel::Left, Channel::Right] {
+                let (x, width) = slot.span(100.0, channel);
+                assert!(x >= 0.0 && x + width <= 200.0 + 1e-9);
+            }
+        }
+
+        // Wide bins keep one bar each
+        let slots = mapper.bar_slots(0..2048, 4096.0);
+        assert_eq!(slots.len(), 2048);
+        assert_eq!(slots[5].bins, 5..6);
+    }
+
+    #[test]
+    fn gaps_narrow_the_bars_without_moving_them() {
+        // 2048 bars, 2 pixels apart, on each half of an 8192 pixel window
+        let mapper = FrequencyMapper::new(0.0, 2048.0, 1.0, FrequencyScale::Linear);
+        let slots = mapper.bar_slots(0..2048, 4096.0);
+        let coverage = |gap: MarginLength| {
+            let mut covered = 0.0;
+            for slot in &slots {
+                for channel in [Channel::Left, Channel::Right] {
+                    let (slot_x, slot_width) = slot.span(4096.0, channel);
+                    let (x, width) = slot.bar_span(4096.0, channel, gap);
+                    // Centered in the slot
+                    assert!((x + width / 2.0 - (slot_x + slot_width / 2.0)).abs() < 1e-9);
+                    covered += width;
+                }
+            }
+            covered
+        };
+
+        assert_eq!(coverage(MarginLength::Pixels(0.0)), 8192.0);
+        // The plot width minus one gap per bar
+        let bars = 2.0 * slots.len() as f64;
+        assert_eq!(coverage(MarginLength::Pixels(1.0)), 8192.0 - bars);
+        assert_eq!(coverage(MarginLength::Percent(50.0)), 8192.0 - bars);
+        // Gaps wider than the bars leave half of each slot
+        assert_eq!(coverage(MarginLength::Pixels(10.0)), 4096.0);
+    }
+
+    #[test]
+    fn log_scale_merges_only_the_narrow_high_bins() {
+        let mapper = FrequencyMapper::new(0.0, 1024.0, 1.0, FrequencyScale::Log);
+        let slots = mapper.bar_slots(0..1024, 50.0);
+        assert!(slots.len() <= 50);
+        assert_eq!(slots[slots.len() - 1].bins.end, 1024);
+        // The lowest bins span several pixels each
+        assert_eq!(slots[1].bins.len(), 1);
+        assert!(slots[slots.len() - 1].bins.len() > 1);
+    }
+
+    #[test]
+    fn no_bars_fit_into_less_than_a_pixel() {
+        let mapper = FrequencyMapper::new(0.0, 2048.0, 1.0, FrequencyScale::Linear);
+        assert!(mapper.bar_slots(0..2048, 0.0).is_empty());
+        assert!(mapper.bar_slots(0..2048, 0.5).is_empty());
+        assert!(mapper.bar_slots(0..2048, f64::NAN).is_empty());
+        assert!(mapper.bar_slots(0..0, 100.0).is_empty());
+
+        let slots = mapper.bar_slots(0..2048, 1.0);
+        assert_eq!(slots.len(), 1);
+        assert_eq!(slots[0].bins, 0..2048);
+    }
+
+    #[test]
+    fn resizing_moves_heights_to_the_bars_of_their_bins() {
+        let mapper = FrequencyMapper::new(0.0, 2048.0, 1.0, FrequencyScale::Linear);
+        let narrow = mapper.bar_slots(0..2048, 100.0);
+
+        // One loud bar on a narrow window
+        let mut heights = vec![0.0; 2048];
+        heights[3] = 50.0;
+        let loud = narrow[3].bins.clone();
+        mapper.remap_slot_heights(0..2048, 100.0, 4096.0, &mut heights);
+        // Every bin of the loud bar gets a bar of its own, at the same height
+        for (bin, &height) in heights.iter().enumerate() {
+            let expected = if loud.contains(&bin) { 50.0 } else { 0.0 };
+            assert_eq!(height, expected, "bin {}", bin);
+        }
+
+        // Back on the narrow window, the loud bar is where it was
+        mapper.remap_slot_heights(0..2048, 4096.0, 100.0, &mut heights);
+        assert_eq!(heights[3], 50.0);
+        assert_eq!(heights.iter().filter(|&&height| height > 0.0).count(), 1);
+
+        // No bars fit into a collapsed window, and none come back from it
+        mapper.remap_slot_heights(0..2048, 100.0, 0.0, &mut heights);
+        assert!(heights.iter().all(|&height| height == 0.0));
+    }
+
+    #[test]
+    fn channels_mirror_at_the_center() {
+        let mapper = FrequencyMapper::new(0.0, 100.0, 1.0, FrequencyScale::Linear);
+        let position = mapper.position(25.0).unwrap();
+        assert_eq!(mapper.mirrored_x(position, 200.0, Channel::Left), 150.0);
+        assert_eq!(mapper.mirrored_x(position, 200.0, Channel::Right), 250.0);
+        assert_eq!(mapper.mirrored_x(0.0, 200.0, Channel::Left), 200.0);
+        assert_eq!(mapper.mirrored_x(1.0, 200.0, Channel::Right), 400.0);
+    }
+
+    #[test]
+    fn unmirrored_left_half_runs_left_to_right() {
+        let mapper = FrequencyMapper {
+            mirror_left: false,
+            ..FrequencyMapper::new(0.0, 100.0, 1.0, FrequencyScale::Linear)
+        };
+        assert_eq!(mapper.mirrored_x(0.25, 200.0, Channel::Left), 50.0);
+        assert_eq!(mapper.mirrored_x(0.25, 200.0, Channel::Right), 250.0);
+        assert!(!mapper.descending(Channel::Left));
+        for channel in [Channel::Left, Channel::Right] {
+            let x = mapper.mirrored_x(0.25, 200.0, channel);
+            assert_eq!(mapper.unmirrored_position(x, 200.0), (0.25, channel));
+        }
+
+        // Both halves lay out their bars from low to high, left to right
+        let slots = mapper.bar_slots(0..100, 200.0);
+        assert_eq!(slots[0].span(200.0, Channel::Left), (0.0, 2.0));
+        assert_eq!(slots[0].span(200.0, Channel::Right), (200.0, 2.0));
+        let (x, width) = slots[slots.len() - 1].span(200.0, Channel::Left);
+        assert_eq!(x + width, 200.0);
+
+        assert_eq!(mapper.selected_range(50.0, 25.0, 200.0), (12.5, 25.0));
+        // Crossing the center covers the high end of the left and the low end of the right half
+        assert_eq!(mapper.selected_range(150.0, 250.0, 200.0), (0.0, 100.0));
+    }
+}
@@ -1,6 +1,13 @@
-use crate::fft_utils::{get_color_for_frequency, interpolate};
-use crate::settings::Settings;
-use crate::visualizer::Visualizer;
+use crate::bar_batch::{BarBatch, GradientCache};
+use crate::color::Palette;
+use crate::fft_utils::{
+    band_emphasis, frequency_indices, get_bar_color_at, interpolate, smoothing_factor,
+};
+use crate::frequency_mapper::{Channel, FrequencyMapper};
+use crate::level_scale::{bar_rect, LevelScale};
+use crate::settings::{ResolvedVisualizerSettings, Settings};
+use crate::smoothing_state::SmoothingState;
+use crate::visualizer::{BarInstance, GlVisualizer, Visualizer};
 use gtk::cairo::Context;
 use gtk4 as gtk;
 use rustfft::num_complex::Complex32;
@@ -10,6 +17,10 @@ use std::sync::Arc;
 /// audio channels using the specified FFT data and settings.
 pub struct FrequencyRangeVisualizer {
     settings: Arc<Settings>,
+    visual_settings: ResolvedVisualizerSettings,
+    palette: Palette,
+    level_scale: LevelScale,
+    gradients: GradientCache,
 }
 
 impl FrequencyRangeVisualizer {
@@ -19,32 +30,103 @@ impl FrequencyRangeVisualizer {
     ///
     /// * `settings` - Shared application settings to configure visualizer parameters.
     pub fn new(settings: Arc<Settings>) -> Self {
-        FrequencyRangeVisualizer { settings }
+        let visual_settings = settings.visualizer_settings("frequency");
+        let palette = Palette::from_settings(&visual_settings);
+        let level_scale = LevelScale::new(&visual_settings, settings.fft.size);
+        FrequencyRangeVisualizer {
+            settings,
+            visual_settings,
+            palette,
+            level_scale,
+            gradients: GradientCache::new(),
+        }
     }
+}
 
-    /// Computes the minimum and maximum FFT indices for the desired frequency range.
+impl Visualizer for FrequencyRangeVisualizer {
+    /// Draws the frequency bars for the left and right audio channels.
     ///
     /// # Arguments
     ///
-    /// * `fft_size` - The size of the FFT data array.
-    ///
-    /// # Returns
-    ///
-    /// A tuple of minimum and maximum frequency indices within the FFT data array.
-    fn get_frequency_indices(&self, fft_size: usize) -> (usize, usize) {
-        let fft_settings = &self.settings.fft;
-        let min_freq = fft_settings.min_frequency;
-        let max_freq = fft_settings.max_frequency;
+    /// * `width` - The width of the drawing area.
+    /// * `height` - The height of the drawing area.
+    /// * `fft_left` - FFT data for the left audio channel.
+    /// * `fft_right` - FFT data for the right audio channel.
+    /// * `cr` - The Cairo context for drawing.
+    /// * `previous_heights_left` - Stores the previous frame's left channel heights for smooth transitions.
+    /// * `previous_heights_right` - Stores the previous frame's right channel heights for smooth transitions.
+    /// * `elapsed` - Time since the previous frame in seconds, setting how far the bars move.
+    fn draw(
+        &self,
+        width: i32,
+        height: i32,
+        fft_left: &[Complex32],
+        fft_right: &[Complex32],
+        cr: &Context,
+        previous_heights_left: &mut SmoothingState,
+        previous_heights_right: &mut SmoothingState,
+        elapsed: f32,
+    ) {
+        let mut instances = Vec::new();
+        self.bar_instances(
+            width,
+            height,
+            fft_left,
+            fft_right,
+            previous_heights_left,
+            previous_heights_right,
+            elapsed,
+            &mut instances,
+        );
 
-        let min_index = (min_freq * fft_size as f32 / fft_settings.sample_rate) as usize;
-        let max_index = (max_freq * fft_size as f32 / fft_settings.sample_rate) as usize;
+        // Bars are collected by color and filled together, which is far cheaper than one fill
+        // per bar
+        let visual_settings = &self.visual_settings;
+        let radius = f64::from(visual_settings.bar_radius);
+        let mut batch = if visual_settings.bar_gradient {
+            BarBatch::with_gradient(
+                radius,
+                visual_settings.direction,
+                f64::from(height),
+                &self.gradients,
+            )
+        } else {
+            BarBatch::new(radius)
+        };
+        for BarInstance { rect, color } in instances {
+            let [x, y, bar_width, bar_height] = rect.map(f64::from);
+            let [r, g, b, a] = color;
+            batch.add((r, g, b, a), x, y, bar_width, bar_height);
+        }
+        batch.fill(cr);
+    }
+
+    fn as_gl(&self) -> Option<&dyn GlVisualizer> {
+        Some(self)
+    }
 
-        (min_index, max_index)
+    /// Moves the heights to the bars of the new width, which merges bins differently.
+    fn rescale_heights(&self, heights: &mut SmoothingState, from: (i32, i32), to: (i32, i32)) {
+        let fft_size = self.settings.fft.padded_size();
+        let (min_index, max_index) = frequency_indices(&self.settings.fft, fft_size);
+        FrequencyMapper::from_settings(&self.settings, fft_size).remap_slot_state(
+            min_index..max_index,
+            f64::from(from.0) / 2.0,
+            f64::from(to.0) / 2.0,
+            heights,
+        );
+    }
+
+    fn set_palette(&mut self, palette: &Palette) {
+        if *palette != self.palette {
+            self.gradients.clear();
+            self.palette = palette.clone();
+        }
     }
 }
 
-impl Visualizer for FrequencyRangeVisualizer {
-    /// Draws the frequency bars for the left and right audio channels.
+impl GlVisualizer for FrequencyRangeVisualizer {
+    /// Lays out the frequency bars for the left and right audio channels.
     ///
     /// # Arguments
     ///
@@ -52,95 +134,259 @@ impl Visualizer for FrequencyRangeVisualizer {
     /// * `height` - The height of the drawing area.
     /// * `fft_left` - FFT data for the left audio channel.
     /// * `fft_right` - FFT data for the right audio channel.
-    /// * `cr` - The Cairo context for drawing.
     /// * `previous_heights_left` - Stores the previous frame's left channel heights for smooth transitions.
     /// * `previous_heights_right` - Stores the previous frame's right channel heights for smooth transitions.
-    fn draw(
+    /// * `instances` - Receives the left channel bars followed by the right channel bars.
+    fn bar_instances(
         &self,
         width: i32,
         height: i32,
         fft_left: &[Complex32],
         fft_right: &[Complex32],
-        cr: &Context,
-        previous_heights_left: &mut Vec<f32>,
-        previous_heights_right: &mut Vec<f32>,
+        previous_heights_left: &mut SmoothingState,
+        previous_heights_right: &mut SmoothingState,
+        elapsed: f32,
+        instances: &mut Vec<BarInstance>,
     ) {
-        let visual_settings = &self.settings.visualizer;
-        let gain = visual_settings.gain;
-        let scale_factor = visual_settings.scale_factor;
-        let interpolation_factor = visual_settings.interpolation_factor;
+        let visual_settings = &self.visual_settings;
+        let interpolation_factor = smoothing_factor(elapsed, visual_settings.smoothing_ms / 1000.0);
         let alpha = visual_settings.alpha;
+        let color_mode = visual_settings.color_mode;
 
         let fft_size = fft_left.len();
-        let (min_index, max_index) = self.get_frequency_indices(fft_size);
+        let (min_index, max_index) = frequency_indices(&self.settings.fft, fft_size);
 
-        // Select the FFT data range for visualization
-        let fft_left = &fft_left[min_index..max_index];
-        let fft_right = &fft_right[min_index..max_index];
+        // Bins narrower than a pixel share a bar, so a narrow window draws fewer bars
+        let mapper = FrequencyMapper::from_settings(&self.settings, fft_size);
+        let half_width = width as f64 / 2.0;
+        let slots = mapper.bar_slots(min_index..max_index, half_width);
+        // Colors follow the frequency of each bar over the whole spectrum, so changing the
+        // displayed range moves the bars without recoloring them
+        let palette_mapper = FrequencyMapper::full_range(&self.settings, fft_size);
 
-        let num_bars = fft_left.len();
-        let bar_width = width as f32 / (2.0 * num_bars as f32).max(1.0);
+        instances.reserve(2 * slots.len());
+        for (channel, fft, previous_heights) in [
+            (Channel::Left, fft_left, previous_heights_left),
+            (Channel::Right, fft_right, previous_heights_right),
+        ] {
+            previous_heights.ensure_len(slots.len());
+            for (i, slot) in slots.iter().enumerate() {
+                // A merged bar shows its loudest bin, so narrow peaks stay visible
+                let magnitude = fft[slot.bins.clone()]
+                    .iter()
+                    .map(|value| value.norm())
+                    .fold(0.0, f32::max);
+                let target_height = self.level_scale.height(magnitude);
 
-        // Draw left channel bars
-        for i in 0..num_bars {
-            let magnitude_left = fft_left[i].norm() * gain;
-            let target_height_left = (magnitude_left + 1e-6).log10().max(0.0) * scale_factor;
+                previous_heights[i] =
+                    interpolate(previous_heights[i], target_height, interpolation_factor);
 
-            previous_heights_left[i] = interpolate(
-                previous_heights_left[i],
-                target_height_left,
-                interpolation_factor,
-            );
+                let level = previous_heights[i] / height as f32;
+                let position = palette_mapper.position(slot.frequency).unwrap_or(0.0);
+                let (r, g, b, a) = get_bar_color_at(&self.palette, color_mode, position, level);
+                // Bars outside a soloed band are dimmed, keeping the axis unchanged
+                let emphasis = band_emphasis(self.settings.fft.solo_band, slot.frequency);
 
-            let color_left = get_color_for_frequency(i, num_bars);
-            cr.set_source_rgba(
-                color_left.0 as f64,
-                color_left.1 as f64,
-                color_left.2 as f64,
-                alpha as f64,
-            );
+                let (x, y, bar_width, bar_height) = bar_rect(
+                    visual_settings.direction,
+                    f64::from(previous_heights[i]),
+                    slot.bar_span(half_width, channel, visual_settings.bar_gap),
+                    f64::from(height),
+                );
 
-            let x = (num_bars as f32 - i as f32 - 1.0) * bar_width;
-            let y = height as f32 - previous_heights_left[i];
+                instances.push(BarInstance {
+                    rect: [x as f32, y as f32, bar_width as f32, bar_height as f32],
+                    color: [r, g, b, alpha * a * emphasis],
+                });
+            }
+        }
+    }
+}
 
-            cr.rectangle(
-                x as f64,
-                y as f64,
-                bar_width as f64,
-                previous_heights_left[i] as f64,
-            );
-            cr.fill().unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::FrequencyScale;
+
+    #[test]
+    fn bar_instances_mirror_the_channels() {
+        let settings = Arc::new(Settings::default());
+        let fft_size = settings.fft.size;
+        let visualizer = FrequencyRangeVisualizer::new(settings);
+        let spectrum: Vec<_> = (0..fft_size)
+            .map(|i| Complex32::new((i % 7) as f32, 0.0))
+            .collect();
+        let mut previous_left = SmoothingState::default();
+        let mut previous_right = SmoothingState::default();
+        let mut instances = Vec::new();
+
+        visualizer.bar_instances(
+            800,
+            400,
+            &spectrum,
+            &spectrum,
+            &mut previous_left,
+            &mut previous_right,
+            1.0 / 60.0,
+            &mut instances,
+        );
+
+        let (left, right) = instances.split_at(instances.len() / 2);
+        assert!(!left.is_empty());
+        for (left, right) in left.iter().zip(right) {
+            let [left_x, left_y, left_width, left_height] = left.rect;
+            let [right_x, right_y, right_width, right_height] = right.rect;
+            // Mirrored around the center, with equal heights and colors
+            assert!((400.0 - (left_x + left_width) - (right_x - 400.0)).abs() < 1e-3);
+            assert!((left_width - right_width).abs() < 1e-3);
+            assert_eq!((left_y, left_height), (right_y, right_height));
+            assert_eq!(left.color, right.color);
+            assert!((left_y + left_height - 400.0).abs() < 1e-3);
         }
+        assert_eq!(&previous_left[..left.len()], &previous_right[..left.len()]);
+    }
 
-        // Draw right channel bars
-        for i in 0..num_bars {
-            let magnitude_right = fft_right[i].norm() * gain;
-            let target_height_right = (magnitude_right + 1e-6).log10().max(0.0) * scale_factor;
+    /// Returns the right channel bar drawn over the x coordinate of a frequency's grid marker,
+    /// and that x coordinate.
+    fn bar_at_marker(settings: Settings, frequency: f32) -> (BarInstance, f32) {
+        let fft_size = settings.fft.size;
+        let mapper = FrequencyMapper::from_settings(&settings, fft_size);
+        let marker_x =
+            mapper.mirrored_x(mapper.position(frequency).unwrap(), 400.0, Channel::Right);
 
-            previous_heights_right[i] = interpolate(
-                previous_heights_right[i],
-                target_height_right,
-                interpolation_factor,
-            );
+        let visualizer = FrequencyRangeVisualizer::new(Arc::new(settings));
+        let spectrum = vec![Complex32::new(1.0, 0.0); fft_size];
+        let mut instances = Vec::new();
+        visualizer.bar_instances(
+            800,
+            400,
+            &spectrum,
+            &spectrum,
+            &mut SmoothingState::default(),
+            &mut SmoothingState::default(),
+            1.0 / 60.0,
+            &mut instances,
+        );
+        let right = &instances[instances.len() / 2..];
+        let bar = right
+            .iter()
+            .find(|bar| {
+                bar.rect[0] <= marker_x as f32 && marker_x as f32 <= bar.rect[0] + bar.rect[2]
+            })
+            .expect("no bar at the marker");
+        (*bar, marker_x as f32)
+    }
+
+    #[test]
+    fn bars_line_up_with_grid_markers_and_keep_their_colors() {
+        for frequency_scale in [FrequencyScale::Linear, FrequencyScale::Log] {
+            let mut settings = Settings::default();
+            settings.visualizer.frequency_scale = frequency_scale;
+            settings.fft.min_frequency = 0.0;
+            let (bar, marker_x) = bar_at_marker(settings.clone(), 1000.0);
+            // The 1 kHz marker lies within the 1 kHz bin, less than half a bar from its center
+            let bar_center = bar.rect[0] + bar.rect[2] / 2.0;
+            assert!((bar_center - marker_x).abs() <= bar.rect[2] / 2.0);
+
+            // Raising the lowest frequency moves the bar but keeps its color
+            settings.fft.min_frequency = 500.0;
+            let (raised_bar, raised_marker_x) = bar_at_marker(settings, 1000.0);
+            assert!(raised_marker_x != marker_x);
+            assert_eq!(raised_bar.color, bar.color);
+        }
+    }
 
-            let color_right = get_color_for_frequency(i, num_bars);
-            cr.set_source_rgba(
-                color_right.0 as f64,
-                color_right.1 as f64,
-                color_right.2 as f64,
-                alpha as f64,
+    #[test]
+    fn soloing_a_band_dims_only_the_bars_outside_of_it() {
+        let mut settings = Settings::default();
+        let (inside, _) = bar_at_marker(settings.clone(), 3000.0);
+        let (outside, _) = bar_at_marker(settings.clone(), 500.0);
+
+        settings.fft.solo_band = Some((2000.0, 5000.0));
+        let (soloed_inside, _) = bar_at_marker(settings.clone(), 3000.0);
+        let (soloed_outside, _) = bar_at_marker(settings, 500.0);
+        assert_eq!(soloed_inside.color, inside.color);
+        assert!(soloed_outside.color[3] < outside.color[3]);
+        // The axis does not change
+        assert_eq!(soloed_inside.rect, inside.rect);
+    }
+
+    #[test]
+    fn glitched_frames_do_not_poison_the_heights() {
+        let mut settings = Settings::default();
+        // Out of range, as if the validation had been bypassed
+        settings.visualizer.interpolation_factor = 1.8;
+        let fft_size = settings.fft.size;
+        let visualizer = FrequencyRangeVisualizer::new(Arc::new(settings));
+        let clean: Vec<_> = (0..fft_size)
+            .map(|i| Complex32::new((i % 7) as f32 * 10.0, 0.0))
+            .collect();
+        let mut glitched = clean.clone();
+        for (i, value) in glitched.iter_mut().enumerate() {
+            match i % 3 {
+                0 => *value = Complex32::new(f32::NAN, 0.0),
+                1 => *value = Complex32::new(f32::INFINITY, f32::NEG_INFINITY),
+                _ => {}
+            }
+        }
+
+        let mut previous_left = SmoothingState::default();
+        let mut previous_right = SmoothingState::default();
+        let mut instances = Vec::new();
+        let mut frame = |spectrum: &[Complex32], instances: &mut Vec<BarInstance>| {
+            instances.clear();
+            visualizer.bar_instances(
+                800,
+                400,
+                spectrum,
+                spectrum,
+                &mut previous_left,
+                &mut previous_right,
+                1.0 / 60.0,
+                instances,
             );
+        };
+
+        frame(&glitched, &mut instances);
+        assert!(instances
+            .iter()
+            .all(|instance| instance.rect.iter().all(|value| value.is_finite())));
+        frame(&clean, &mut instances);
+        let recovered = instances.clone();
+        frame(&clean, &mut instances);
+        // The clamped factor moves the bars to their targets at once, and keeps them there
+        assert_eq!(recovered, instances);
+        assert!(recovered.iter().any(|instance| instance.rect[3] > 0.0));
+    }
 
-            let x = width as f32 - (num_bars as f32 - i as f32 - 1.0) * bar_width;
-            let y = height as f32 - previous_heights_right[i];
+    #[test]
+    fn narrow_areas_get_fewer_whole_pixel_bars() {
+        let mut settings = Settings::default();
+        settings.fft.size = 4096;
+        settings.fft.min_frequency = 0.0;
+        let visualizer = FrequencyRangeVisualizer::new(Arc::new(settings));
+        let spectrum = vec![Complex32::new(1.0, 0.0); 4096];
+        let mut previous_left = SmoothingState::default();
+        let mut previous_right = SmoothingState::default();
 
-            cr.rectangle(
-                x as f64,
-                y as f64,
-                bar_width as f64,
-                previous_heights_right[i] as f64,
+        for (width, max_bars) in [(0, 0), (1, 0), (200, 200)] {
+            let mut instances = Vec::new();
+            visualizer.bar_instances(
+                width,
+                100,
+                &spectrum,
+                &spectrum,
+                &mut previous_left,
+                &mut previous_right,
+                1.0 / 60.0,
+                &mut instances,
             );
-            cr.fill().unwrap();
+            assert!(instances.len() <= max_bars, "{} bars", instances.len());
+            for instance in &instances {
+                let [x, _, bar_width, _] = instance.rect;
+                assert!(bar_width >= 1.0 - 1e-4);
+                assert!(x >= 0.0 && x + bar_width <= width as f32 + 1e-3);
+            }
         }
     }
 }
@@ -0,0 +1,301 @@
+use crate::visualizer::BarInstance;
+use glow::HasContext;
+use gtk::prelude::*;
+use gtk::{glib, DrawingArea};
+use gtk4 as gtk;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+/// Shared library the OpenGL functions are loaded from; GTK itself draws through libepoxy.
+#[cfg(target_os = "macos")]
+const LIBEPOXY: &str = "libepoxy.0.dylib";
+#[cfg(all(unix, not(target_os = "macos")))]
+const LIBEPOXY: &str = "libepoxy.so.0";
+#[cfg(windows)]
+const LIBEPOXY: &str = "libepoxy-0.dll";
+
+/// Expands each instance into a quad; the corner comes from the vertex index, so no vertex
+/// buffer is needed besides the instances.
+const VERTEX_SHADER: &str = r#"#version 330 core
+layout(location = 0) in vec4 rect;
+layout(location = 1) in vec4 color;
+uniform vec2 viewport;
+out vec4 bar_color;
+
+void main() {
+    vec2 corner = vec2(gl_VertexID & 1, gl_VertexID >> 1);
+    vec2 position = rect.xy + corner * rect.zw;
+    gl_Position = vec4(
+        position.x / viewport.x * 2.0 - 1.0,
+        1.0 - position.y / viewport.y * 2.0,
+        0.0,
+        1.0
+    );
+    // GTK composites the area with premultiplied alpha
+    bar_color = vec4(color.rgb * color.a, color.a);
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"#version 330 core
+in vec4 bar_color;
+out vec4 frag_color;
+
+void main() {
+    frag_color = bar_color;
+}
+"#;
+
+/// Shader program and buffers drawing `BarInstance`s as instanced quads.
+///
+/// # Fields
+/// - `gl`: The OpenGL functions of the context the resources were created in.
+/// - `program`: The linked vertex and fragment shaders.
+/// - `vertex_array`: The per-instance attribute layout of `BarInstance`.
+/// - `instance_buffer`: Buffer the instances of each frame are uploaded to.
+/// - `viewport_location`: Location of the uniform holding the drawing area size in pixels.
+struct GlBarRenderer {
+    gl: glow::Context,
+    program: glow::Program,
+    vertex_array: glow::VertexArray,
+    instance_buffer: glow::Buffer,
+    viewport_location: Option<glow::UniformLocation>,
+}
+
+impl GlBarRenderer {
+    /// Compiles the shaders and creates the buffers; the context must be current.
+    ///
+    /// # Returns
+    /// - The renderer, or the OpenGL error message if a resource cannot be created.
+    fn new(gl: glow::Context) -> Result<Self, String> {
+        unsafe {
+            let program = gl.create_program()?;
+            let mut shaders = Vec::new();
+            for (kind, source) in [
+                (glow::VERTEX_SHADER, VERTEX_SHADER),
+                (glow::FRAGMENT_SHADER, FRAGMENT_SHADER),
+            ] {
+                let shader = gl.create_shader(kind)?;
+                gl.shader_source(shader, source);
+                gl.compile_shader(shader);
+                if !gl.get_shader_compile_status(shader) {
+                    return Err(gl.get_shader_info_log(shader));
+                }
+                gl.attach_shader(program, shader);
+                shaders.push(shader);
+            }
+            gl.link_program(program);
+            if !gl.get_program_link_status(program) {
+                return Err(gl.get_program_info_log(program));
+            }
+            for shader in shaders {
+                gl.detach_shader(program, shader);
+                gl.delete_shader(shader);
+            }
+
+            let vertex_array = gl.create_vertex_array()?;
+            let instance_buffer = gl.create_buffer()?;
+            gl.bind_vertex_array(Some(vertex_array));
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(instance_buffer));
+            let stride = std::mem::size_of::<BarInstance>() as i32;
+            let color_offset = std::mem::size_of::<[f32; 4]>() as i32;
+            for (location, offset) in [(0, 0), (1, color_offset)] {
+                gl.enable_vertex_attrib_array(location);
+                gl.vertex_attrib_pointer_f32(location, 4, glow::FLOAT, false, stride, offset);
+                gl.vertex_attrib_divisor(location, 1);
+            }
+            gl.bind_vertex_array(None);
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+
+            let viewport_location = gl.get_uniform_location(program, "viewport");
+            Ok(GlBarRenderer {
+                gl,
+                program,
+                vertex_array,
+                instance_buffer,
+                viewport_location,
+            })
+        }
+    }
+
+    /// Clears the area and draws the bars.
+    ///
+    /// # Arguments
+    /// - `width`: The width of the area in pixels, as used by the bar layout.
+    /// - `height`: The height of the area in pixels, as used by the bar layout.
+    /// - `scale_factor`: Device pixels per pixel of the area.
+    /// - `instances`: The bars to draw.
+    fn draw(&self, width: i32, height: i32, scale_factor: i32, instances: &[BarInstance]) {
+        let gl = &self.gl;
+        unsafe {
+            gl.viewport(0, 0, width * scale_factor, height * scale_factor);
+            gl.clear_color(0.0, 0.0, 0.0, 0.0);
+            gl.clear(glow::COLOR_BUFFER_BIT);
+            if instances.is_empty() {
+                return;
+            }
+
+            // The instances are plain `f32`s, laid out as the vertex attributes expect
+            let bytes = std::slice::from_raw_parts(
+                instances.as_ptr() as *const u8,
+                std::mem::size_of_val(instances),
+            );
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.instance_buffer));
+            gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, bytes, glow::STREAM_DRAW);
+
+            gl.enable(glow::BLEND);
+            gl.blend_func(glow::ONE, glow::ONE_MINUS_SRC_ALPHA);
+            gl.use_program(Some(self.program));
+            gl.uniform_2_f32(self.viewport_location.as_ref(), width as f32, height as f32);
+            gl.bind_vertex_array(Some(self.vertex_array));
+            gl.draw_arrays_instanced(glow::TRIANGLE_STRIP, 0, 4, instances.len() as i32);
+
+            gl.bind_vertex_array(None);
+            gl.use_program(None);
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+        }
+    }
+
+    /// Frees the OpenGL resources; the context must be current.
+    fn delete(self) {
+        unsafe {
+            self.gl.delete_program(self.program);
+            self.gl.delete_vertex_array(self.vertex_array);
+            self.gl.delete_buffer(self.instance_buffer);
+        }
+    }
+}
+
+/// A `GLArea` below the drawing area, drawing the bars the Cairo draw function lays out.
+///
+/// The drawing area stays on top and keeps drawing the background effects, the grid and every
+/// overlay with Cairo; only the bars move to the GPU.
+///
+/// # Fields
+/// - `area`: The `GLArea` drawing the bars.
+/// - `instances`: The bars of the last frame.
+/// - `ready`: Whether the OpenGL resources were created; until then, and if creating them
+///   fails, the bars are drawn with Cairo.
+#[derive(Clone)]
+pub struct GlBars {
+    area: gtk::GLArea,
+    instances: Rc<RefCell<Vec<BarInstance>>>,
+    ready: Rc<Cell<bool>>,
+}
+
+impl GlBars {
+    /// Puts a `GLArea` below `drawing_area`, replacing it as the child of its window.
+    ///
+    /// # Returns
+    /// - The bars of the `GLArea`, or `None` if `drawing_area` is not the child of a window.
+    pub fn attach(drawing_area: &DrawingArea) -> Option<Self> {
+        let Some(window) = drawing_area
+            .parent()
+            .and_then(|parent| parent.downcast::<gtk::Window>().ok())
+        else {
+            eprintln!("The drawing area is not the child of a window, drawing with Cairo.");
+            return None;
+        };
+
+        let area = gtk::GLArea::new();
+        area.set_required_version(3, 3);
+        area.set_hexpand(true);
+        area.set_vexpand(true);
+
+        let overlay = gtk::Overlay::new();
+        window.set_child(None::<&gtk::Widget>);
+        overlay.set_child(Some(&area));
+        overlay.add_overlay(drawing_area);
+        window.set_child(Some(&overlay));
+
+        let gl_bars = GlBars {
+            area,
+            instances: Rc::new(RefCell::new(Vec::new())),
+            ready: Rc::new(Cell::new(false)),
+        };
+        gl_bars.connect_signals();
+        Some(gl_bars)
+    }
+
+    /// Returns whether the bars are drawn by the `GLArea`.
+    pub fn is_ready(&self) -> bool {
+        self.ready.get()
+    }
+
+    /// Replaces the bars and schedules a render of the `GLArea`.
+    ///
+    /// # Arguments
+    /// - `layout`: Function writing the bars of the new frame.
+    pub fn update<F: FnOnce(&mut Vec<BarInstance>)>(&self, layout: F) {
+        layout(&mut self.instances.borrow_mut());
+        self.area.queue_render();
+    }
+
+    /// Creates the OpenGL resources with the context, draws on every render, and frees the
+    /// resources with the context.
+    fn connect_signals(&self) {
+        let renderer: Rc<RefCell<Option<GlBarRenderer>>> = Rc::new(RefCell::new(None));
+
+        let renderer_clone = renderer.clone();
+        let ready = self.ready.clone();
+        self.area.connect_realize(move |area| {
+            area.make_current();
+            if let Some(error) = area.error() {
+                eprintln!(
+                    "Failed to create an OpenGL context, drawing with Cairo: {}",
+                    error
+                );
+                return;
+            }
+            match load_gl().and_then(GlBarRenderer::new) {
+                Ok(gl_renderer) => {
+                    *renderer_clone.borrow_mut() = Some(gl_renderer);
+                    ready.set(true);
+                }
+                Err(e) => eprintln!("Failed to set up OpenGL, drawing with Cairo: {}", e),
+            }
+        });
+
+        let renderer_clone = renderer.clone();
+        let ready = self.ready.clone();
+        self.area.connect_unrealize(move |area| {
+            ready.set(false);
+            area.make_current();
+            if let Some(gl_renderer) = renderer_clone.borrow_mut().take() {
+                gl_renderer.delete();
+            }
+        });
+
+        let instances = self.instances.clone();
+        self.area.connect_render(move |area, _| {
+            if let Some(gl_renderer) = renderer.borrow().as_ref() {
+                gl_renderer.draw(
+                    area.width(),
+                    area.height(),
+                    area.scale_factor(),
+                    &instances.borrow(),
+                );
+            }
+            glib::Propagation::Stop
+        });
+    }
+}
+
+/// Loads the OpenGL functions of the current context through libepoxy.
+///
+/// # Returns
+/// - The OpenGL functions, or an error if libepoxy cannot be loaded.
+fn load_gl() -> Result<glow::Context, String> {
+    // GTK already links libepoxy, so unloading this handle leaves the functions loaded
+    let library = unsafe { libloading::Library::new(LIBEPOXY) }
+        .map_err(|e| format!("Failed to load {}: {}", LIBEPOXY, e))?;
+    // libepoxy exports each function as a pointer named with an `epoxy_` prefix, which resolves
+    // the function of the current context on its first call
+    Ok(unsafe {
+        glow::Context::from_loader_function(|name| {
+            library
+                .get::<*const *const std::ffi::c_void>(format!("epoxy_{}", name).as_bytes())
+                .map(|symbol| **symbol)
+                .unwrap_or(std::ptr::null())
+        })
+    })
+}
@@ -1,14 +1,168 @@
-use crate::settings::Settings;
+use crate::color::Palette;
+use crate::fft_utils::{get_color_for_frequency, note_frequency, note_name, pitch_class_notes};
+use crate::format::format_frequency;
+use crate::frequency_mapper::{Channel, FrequencyMapper};
+use crate::level_scale::{oriented_ys, LevelScale};
+use crate::settings::{BarDirection, Color, GridColorMode, MarkerMode, PlotRect, Settings};
+use crate::text::{draw_text, measure, TextAlign, TextStyle};
 use gtk::cairo::Context;
 use gtk4 as gtk;
 use std::sync::Arc;
 
+/// Font size of the dB and marker labels, in pixels.
+const LABEL_FONT_SIZE: f64 = 10.0;
+/// Distance of the labels from the plot edges and from their line, in pixels.
+const LABEL_MARGIN: f64 = 4.0;
+/// Semitones above C of the notes marked by `MarkerMode::Octaves`.
+const PITCH_CLASS_A: i32 = 9;
+/// Semitones above C of the notes marked by `MarkerMode::Notes`.
+const PITCH_CLASS_C: i32 = 0;
+
+/// Snaps a coordinate to the center of the device pixel containing it, so a line one device
+/// pixel wide drawn there covers exactly one row or column of pixels instead of blurring over two.
+///
+/// # Arguments
+/// - `x`: The coordinate, in logical pixels.
+/// - `scale`: Device pixels per logical pixel.
+pub fn snap_to_pixel(x: f64, scale: f64) -> f64 {
+    ((x * scale).floor() + 0.5) / scale
+}
+
+/// Rounds a line width to a whole number of device pixels, at least one.
+fn pixel_line_width(line_width: f64, scale: f64) -> f64 {
+    (line_width * scale).round().max(1.0) / scale
+}
+
+/// Snaps the center of a line so its edges fall on device pixel boundaries.
+///
+/// # Arguments
+/// - `x`: The center of the line, in logical pixels.
+/// - `line_width`: Width of the line, a whole number of device pixels from `pixel_line_width`.
+/// - `scale`: Device pixels per logical pixel.
+fn snap_line(x: f64, line_width: f64, scale: f64) -> f64 {
+    if (line_width * scale).round() % 2.0 == 1.0 {
+        snap_to_pixel(x, scale)
+    } else {
+        (x * scale).round() / scale
+    }
+}
+
+/// A vertical line of the grid.
+///
+/// # Fields
+/// - `frequency`: The marked frequency, in Hz.
+/// - `label`: Text drawn next to the line when `grid.marker_labels` is set.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Marker {
+    pub frequency: f32,
+    pub label: String,
+}
+
+/// Generates the vertical markers selected by `grid.marker_mode`.
+///
+/// # Arguments
+/// - `settings`: Settings providing the grid mode, the FFT frequency range, and the
+///   `note_readout.a4` tuning.
+///
+/// # Returns
+/// - The markers in the range from `fft.min_frequency` to `fft.max_frequency` for the note
+///   modes, or every configured `fft.frequencies` entry.
+pub fn markers(settings: &Settings) -> Vec<Marker> {
+    let fft_settings = &settings.fft;
+    let pitch_class = match settings.grid.marker_mode {
+        MarkerMode::Frequencies => {
+            let Some(frequencies) = &fft_settings.frequencies else {
+                eprintln!("Frequencies are not set in FFT settings");
+                return Vec::new();
+            };
+            return frequencies
+                .iter()
+                .map(|&frequency| Marker {
+                    frequency,
+                    label: format_frequency(frequency, settings.ui.decimal_comma),
+                })
+                .collect();
+        }
+        MarkerMode::Octaves => PITCH_CLASS_A,
+        MarkerMode::Notes => PITCH_CLASS_C,
+    };
+
+    let a4 = settings.note_readout.a4;
+    pitch_class_notes(
+        pitch_class,
+        fft_settings.min_frequency,
+        fft_settings.max_frequency,
+        a4,
+    )
+    .into_iter()
+    .map(|midi| Marker {
+        frequency: note_frequency(midi, a4),
+        label: note_name(midi),
+    })
+    .collect()
+}
+
+/// Which parts of the grid are drawn; changed at runtime by the keyboard controls.
+///
+/// # Fields
+/// - `enabled`: Whether the grid is drawn at all.
+/// - `horizontal`: Whether the horizontal lines are drawn.
+/// - `vertical`: Whether the vertical frequency markers are drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GridVisibility {
+    pub enabled: bool,
+    pub horizontal: bool,
+    pub vertical: bool,
+}
+
+impl GridVisibility {
+    /// Returns the visibility configured by `grid.enabled`, `grid.show_horizontal`, and
+    /// `grid.show_vertical`.
+    pub fn from_settings(settings: &Settings) -> Self {
+        GridVisibility {
+            enabled: settings.grid.enabled,
+            horizontal: settings.grid.show_horizontal,
+            vertical: settings.grid.show_vertical,
+        }
+    }
+
+    /// Describes what changed compared to `previous`, such as `grid: off`.
+    ///
+    /// # Returns
+    /// - A short status message, or `None` if nothing changed.
+    pub fn describe_change(&self, previous: &GridVisibility) -> Option<String> {
+        let on_off = |visible: bool| if visible { "on" } else { "off" };
+        if self.enabled != previous.enabled {
+            Some(format!("grid: {}", on_off(self.enabled)))
+        } else if self.horizontal != previous.horizontal {
+            Some(format!("grid lines: {}", on_off(self.horizontal)))
+        } else if self.vertical != previous.vertical {
+            Some(format!("frequency markers: {}", on_off(self.vertical)))
+        } else {
+            None
+        }
+    }
+}
+
 /// A structure representing the frequency grid used for visualizing audio data.
 ///
 /// # Fields
 /// - `settings`: A reference-counted `Settings` object that contains grid and FFT configurations.
+/// - `visibility`: Which parts of the grid are drawn.
+/// - `markers`: The vertical lines, generated once from `grid.marker_mode`.
+/// - `direction`: Edge the bars of the visualizer drawn over the grid grow from, which the
+///   horizontal lines are mirrored along with.
+/// - `center_line`: Whether a stronger horizontal line is drawn through the middle of the plot,
+///   for bars growing up and down from it.
+/// - `palette`: The palette of the visualizer the markers take their colors from, when
+///   `grid.color_mode` is `"palette"`.
 pub struct FrequencyGrid {
     settings: Arc<Settings>, // Stores settings related to grid and FFT configuration
+    visibility: GridVisibility,
+    markers: Vec<Marker>,
+    direction: BarDirection,
+    center_line: bool,
+    palette: Option<Palette>,
 }
 
 impl FrequencyGrid {
@@ -16,86 +170,464 @@ impl FrequencyGrid {
     ///
     /// # Arguments
     /// - `settings`: Shared settings containing grid and FFT configurations.
+    /// - `visualizer`: Name of the visualizer drawn over the grid, whose palette colors the
+    ///   markers in `"palette"` mode.
     ///
     /// # Returns
     /// - A new `FrequencyGrid` instance configured with the provided settings.
-    pub fn new(settings: Arc<Settings>) -> Self {
-        FrequencyGrid { settings }
+    pub fn new(settings: Arc<Settings>, visualizer: &str) -> Self {
+        let mut grid = FrequencyGrid {
+            visibility: GridVisibility::from_settings(&settings),
+            markers: markers(&settings),
+            direction: BarDirection::default(),
+            center_line: false,
+            palette: None,
+            settings,
+        };
+        grid.set_visualizer(visualizer);
+        grid
+    }
+
+    /// Orients the horizontal lines like the bars of another visualizer and colors the markers
+    /// with its palette in `"palette"` mode, starting with the next frame.
+    ///
+    /// # Arguments
+    /// - `visualizer`: Name of the visualizer now drawn over the grid.
+    pub fn set_visualizer(&mut self, visualizer: &str) {
+        let visual_settings = self.settings.visualizer_settings(visualizer);
+        self.direction = visual_settings.direction;
+        self.palette = match self.settings.grid.color_mode {
+            GridColorMode::Fixed => None,
+            GridColorMode::Palette => Some(Palette::from_settings(&visual_settings)),
+        };
+    }
+
+    /// Replaces the palette of the markers in `"palette"` mode, such as while
+    /// `visualizer.palette_cycle` fades between palettes.
+    pub fn set_palette(&mut self, palette: &Palette) {
+        if self.palette.is_some() {
+            self.palette = Some(palette.clone());
+        }
+    }
+
+    /// Returns which parts of the grid are drawn.
+    pub fn visibility(&self) -> GridVisibility {
+        self.visibility
+    }
+
+    /// Changes which parts of the grid are drawn, starting with the next frame.
+    pub fn set_visibility(&mut self, visibility: GridVisibility) {
+        self.visibility = visibility;
+    }
+
+    /// Sets whether the line through the middle of the plot is emphasized, for visualizers whose
+    /// bars grow from it.
+    pub fn set_center_line(&mut self, center_line: bool) {
+        self.center_line = center_line;
     }
 
     /// Draws the frequency grid on a drawing area, including horizontal and vertical lines.
     ///
     /// # Arguments
     /// - `cr`: The Cairo `Context` used for drawing.
-    /// - `width`: The width of the drawing area.
-    /// - `height`: The height of the drawing area.
+    /// - `plot`: The part of the drawing area spanned by the lines. Labels move into the margins
+    ///   around it where they fit.
+    /// - `level_scale`: Height mapping of the bars, used to place the lines of `grid.db_lines`.
+    /// - `scale`: Device pixels per logical pixel; lines are snapped to whole device pixels.
     ///
     /// This function draws a grid with horizontal lines and vertical frequency markers for both left
-    /// and right audio channels. The grid appearance is customizable through the settings.
-    pub fn draw(&self, cr: &Context, width: f64, height: f64) {
+    /// and right audio channels. The grid appearance is customizable through the settings, and
+    /// the lines and markers are skipped when hidden by `visibility`.
+    pub fn draw(&self, cr: &Context, plot: PlotRect, level_scale: &LevelScale, scale: f64) {
+        let _ = cr.save();
+        cr.translate(plot.x, plot.y);
+        self.draw_lines(cr, plot, level_scale, scale);
+        let _ = cr.restore();
+    }
+
+    /// Draws the lines and labels of the grid with the origin at the top-left corner of the plot.
+    fn draw_lines(&self, cr: &Context, plot: PlotRect, level_scale: &LevelScale, scale: f64) {
+        let (width, height) = (plot.width, plot.height);
         let grid_settings = &self.settings.grid; // Access grid-related settings
         let fft_settings = &self.settings.fft; // Access FFT-related settings
 
-        // Set the color and line thickness for the horizontal grid lines
-        cr.set_source_rgba(
-            grid_settings.color_horizontal[0],
-            grid_settings.color_horizontal[1],
-            grid_settings.color_horizontal[2],
-            grid_settings.alpha,
-        );
-        cr.set_line_width(grid_settings.line_width); // Set grid line thickness
+        if self.visibility.horizontal {
+            // Set the color and line thickness for the horizontal grid lines
+            let (r, g, b, a) = grid_settings.color_horizontal.to_rgba(grid_settings.alpha);
+            cr.set_source_rgba(r, g, b, a);
+            let line_width = pixel_line_width(grid_settings.line_width, scale);
+            cr.set_line_width(line_width); // Set grid line thickness
+
+            if grid_settings.db_lines {
+                self.draw_db_lines(cr, plot, level_scale, line_width, scale);
+            } else {
+                // Draw horizontal grid lines based on the number of lines specified in settings,
+                // mirrored along with the bars
+                let direction = self.direction;
+                for i in 0..grid_settings.lines {
+                    let y = height * (i as f64 / grid_settings.lines as f64);
+                    for y in oriented_ys(direction, y, height) {
+                        let y = snap_line(y, line_width, scale);
+                        cr.move_to(0.0, y);
+                        cr.line_to(width, y);
+                    }
+                }
+                let _ = cr.stroke();
+            }
 
-        // Draw horizontal grid lines based on the number of lines specified in settings
-        for i in 0..grid_settings.lines {
-            let y = height * (i as f64 / grid_settings.lines as f64);
-            cr.move_to(0.0, y);
-            cr.line_to(width, y);
+            if self.center_line {
+                // Twice as wide and as opaque as labels, so it stands out from the other lines
+                let (r, g, b, a) = grid_settings
+                    .color_horizontal
+                    .to_rgba((grid_settings.alpha * 4.0).min(1.0));
+                cr.set_source_rgba(r, g, b, a);
+                let line_width = pixel_line_width(2.0 * grid_settings.line_width, scale);
+                cr.set_line_width(line_width);
+                let y = snap_line(height / 2.0, line_width, scale);
+                cr.move_to(0.0, y);
+                cr.line_to(width, y);
+                let _ = cr.stroke();
+            }
+        }
+        if !self.visibility.vertical {
+            return;
         }
-        cr.stroke().expect("Failed to draw horizontal grid lines");
 
         // Set half of the width as a reference for drawing symmetrical lines
         let half_width = width / 2.0;
 
-        // Calculate the scale factor for the X-axis based on the maximum frequency
-        let scale_factor = half_width as f32 / fft_settings.max_frequency;
-
-        // Exit if there are no frequencies set in the FFT settings
-        if let Some(frequencies) = &fft_settings.frequencies {
-            // Draw vertical frequency lines for both left and right audio channels
-            for &frequency in frequencies.iter() {
-                let x_position = frequency * scale_factor;
-
-                // Draw lines for the left channel (red color)
-                if x_position >= 0.0 && x_position <= half_width as f32 {
-                    cr.set_source_rgba(
-                        grid_settings.color_left[0],
-                        grid_settings.color_left[1],
-                        grid_settings.color_left[2],
-                        grid_settings.alpha,
-                    );
-                    cr.set_line_width(1.0);
-                    cr.move_to(half_width - x_position as f64, 0.0); // Line on the left of the center
-                    cr.line_to(half_width - x_position as f64, height);
-                    cr.stroke().expect("Failed to draw left channel grid lines");
-                }
+        // Place markers where the visualizers draw their frequencies
+        let mapper = FrequencyMapper::from_settings(&self.settings, fft_settings.padded_size());
+        let palette_mapper =
+            FrequencyMapper::full_range(&self.settings, fft_settings.padded_size());
+        let line_width = pixel_line_width(1.0, scale);
 
-                // Draw lines for the right channel (green color)
-                if x_position >= 0.0 && x_position <= half_width as f32 {
-                    cr.set_source_rgba(
-                        grid_settings.color_right[0],
-                        grid_settings.color_right[1],
-                        grid_settings.color_right[2],
-                        grid_settings.alpha,
-                    );
-                    cr.set_line_width(1.0);
-                    cr.move_to(half_width + x_position as f64, 0.0); // Line on the right of the center
-                    cr.line_to(half_width + x_position as f64, height);
-                    cr.stroke()
-                        .expect("Failed to draw right channel grid lines");
-                }
+        // Draw vertical marker lines for both left and right audio channels
+        for marker in &self.markers {
+            let Some(position) = mapper.position(marker.frequency) else {
+                continue;
+            };
+
+            // Draw lines for the left channel (red color)
+            let x = snap_line(
+                mapper.mirrored_x(position, half_width, Channel::Left),
+                line_width,
+                scale,
+            );
+            let palette_position = palette_mapper.position(marker.frequency);
+            let (r, g, b, a) = self.marker_color(
+                grid_settings.color_left,
+                palette_position,
+                grid_settings.alpha,
+            );
+            cr.set_source_rgba(r, g, b, a);
+            cr.set_line_width(line_width);
+            cr.move_to(x, 0.0);
+            cr.line_to(x, height);
+            let _ = cr.stroke();
+
+            // Draw lines for the right channel (green color)
+            let x = snap_line(
+                mapper.mirrored_x(position, half_width, Channel::Right),
+                line_width,
+                scale,
+            );
+            let (r, g, b, a) = self.marker_color(
+                grid_settings.color_right,
+                palette_position,
+                grid_settings.alpha,
+            );
+            cr.set_source_rgba(r, g, b, a);
+            cr.set_line_width(line_width);
+            cr.move_to(x, 0.0);
+            cr.line_to(x, height);
+            let _ = cr.stroke();
+
+            if grid_settings.marker_labels {
+                self.draw_marker_label(
+                    cr,
+                    &mapper,
+                    position,
+                    palette_position,
+                    plot,
+                    &marker.label,
+                );
+            }
+        }
+    }
+
+    /// Returns the color of a marker line or label of one channel.
+    ///
+    /// # Arguments
+    /// - `fixed`: The color of the channel in `"fixed"` mode.
+    /// - `palette_position`: Position of the marked frequency in the palette, as the bars look
+    ///   up their colors; markers outside the spectrum keep `fixed`.
+    /// - `alpha`: Opacity of the color, unless `fixed` carries its own.
+    fn marker_color(
+        &self,
+        fixed: Color,
+        palette_position: Option<f32>,
+        alpha: f64,
+    ) -> (f64, f64, f64, f64) {
+        match (&self.palette, palette_position) {
+            (Some(palette), Some(position)) => {
+                let (r, g, b) = get_color_for_frequency(palette, position, 0.5);
+                (f64::from(r), f64::from(g), f64::from(b), alpha)
             }
+            _ => fixed.to_rgba(alpha),
+        }
+    }
+
+    /// Draws the label of a marker at the top of both of its lines, on the side of the higher
+    /// frequencies; above the plot when the top margin fits it.
+    ///
+    /// # Arguments
+    /// - `cr`: The Cairo `Context` used for drawing, with the origin at the top-left corner of
+    ///   the plot.
+    /// - `mapper`: The mapper that placed the marker lines.
+    /// - `position`: Position of the marker returned by `mapper`.
+    /// - `palette_position`: Position of the marker in the palette, for `marker_color`.
+    /// - `plot`: The part of the drawing area spanned by the lines.
+    /// - `label`: The text to draw.
+    fn draw_marker_label(
+        &self,
+        cr: &Context,
+        mapper: &FrequencyMapper,
+        position: f32,
+        palette_position: Option<f32>,
+        plot: PlotRect,
+        label: &str,
+    ) {
+        let grid_settings = &self.settings.grid;
+        let label_alpha = (grid_settings.alpha * 4.0).min(1.0);
+        let style = TextStyle::ui(&self.settings.ui, LABEL_FONT_SIZE);
+        let half_width = plot.width / 2.0;
+        let (_, label_height) = measure(label, &style);
+        let top = if plot.y >= label_height + 2.0 * LABEL_MARGIN {
+            -LABEL_MARGIN - label_height
+        } else {
+            LABEL_MARGIN
+        };
+
+        let x = mapper.mirrored_x(position, half_width, Channel::Left);
+        let color = self.marker_color(grid_settings.color_left, palette_position, label_alpha);
+        if mapper.descending(Channel::Left) {
+            let left_style = TextStyle {
+                color,
+                align: TextAlign::Right,
+                ..style.clone()
+            };
+            draw_text(cr, label, x - LABEL_MARGIN, top, &left_style);
         } else {
-            eprintln!("Frequencies are not set in FFT settings");
+            let left_style = TextStyle {
+                color,
+                ..style.clone()
+            };
+            draw_text(cr, label, x + LABEL_MARGIN, top, &left_style);
+        }
+
+        let x = mapper.mirrored_x(position, half_width, Channel::Right);
+        let right_style = TextStyle {
+            color: self.marker_color(grid_settings.color_right, palette_position, label_alpha),
+            ..style
+        };
+        draw_text(cr, label, x + LABEL_MARGIN, top, &right_style);
+    }
+
+    /// Draws horizontal lines at every `grid.db_step` dB visible on the bars, labelled on the
+    /// left edge; in the left margin when it fits the labels. The lines mark the ends of the
+    /// bars, whichever way `visualizer.direction` grows them.
+    ///
+    /// # Arguments
+    /// - `cr`: The Cairo `Context` used for drawing, with the origin at the top-left corner of
+    ///   the plot.
+    /// - `plot`: The part of the drawing area spanned by the lines.
+    /// - `level_scale`: Height mapping of the bars.
+    /// - `line_width`: Width of the lines, a whole number of device pixels.
+    /// - `scale`: Device pixels per logical pixel.
+    fn draw_db_lines(
+        &self,
+        cr: &Context,
+        plot: PlotRect,
+        level_scale: &LevelScale,
+        line_width: f64,
+        scale: f64,
+    ) {
+        let (width, height) = (plot.width, plot.height);
+        let grid_settings = &self.settings.grid;
+        let direction = self.direction;
+        let levels = level_scale.db_lines(height as f32, grid_settings.db_step);
+        let level_ys = |db: f32| {
+            oriented_ys(
+                direction,
+                height - level_scale.db_to_height(db) as f64,
+                height,
+            )
+        };
+
+        for &db in &levels {
+            for y in level_ys(db) {
+                let y = snap_line(y, line_width, scale);
+                cr.move_to(0.0, y);
+                cr.line_to(width, y);
+            }
         }
+        let _ = cr.stroke();
+
+        // Labels use the line color at a higher opacity so they stay readable
+        let style = TextStyle {
+            color: grid_settings
+                .color_horizontal
+                .to_rgba((grid_settings.alpha * 4.0).min(1.0)),
+            ..TextStyle::ui(&self.settings.ui, LABEL_FONT_SIZE)
+        };
+        let labels: Vec<String> = levels
+            .iter()
+            .map(|db| format!("{} dB", db.round() as i32))
+            .collect();
+        let label_width = labels
+            .iter()
+            .map(|label| measure(label, &style).0)
+            .fold(0.0, f64::max);
+        let in_margin = plot.x >= label_width + 2.0 * LABEL_MARGIN;
+        let style = TextStyle {
+            align: if in_margin {
+                TextAlign::Right
+            } else {
+                TextAlign::Left
+            },
+            ..style
+        };
+        for (&db, label) in levels.iter().zip(&labels) {
+            let (_, label_height) = measure(label, &style);
+            // With centered bars, only the line above the middle is labelled
+            let y = level_ys(db)[0];
+            if in_margin {
+                // Labels in the margin are centered on their line
+                draw_text(cr, label, -LABEL_MARGIN, y - label_height / 2.0, &style);
+            } else if direction == BarDirection::Down {
+                // Labels sit below their line, but stay above the bottom edge
+                let top = (y + LABEL_MARGIN).min(height - label_height);
+                draw_text(cr, label, LABEL_MARGIN, top, &style);
+            } else {
+                // Labels sit above their line, but stay below the top edge
+                let top = (y - LABEL_MARGIN - label_height).max(0.0);
+                draw_text(cr, label, LABEL_MARGIN, top, &style);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn octave_markers_are_every_a() {
+        let mut settings = Settings::default();
+        settings.grid.marker_mode = MarkerMode::Octaves;
+        settings.fft.min_frequency = 50.0;
+        settings.fft.max_frequency = 1000.0;
+
+        let markers = markers(&settings);
+        let labels: Vec<&str> = markers.iter().map(|marker| marker.label.as_str()).collect();
+        assert_eq!(labels, ["A1", "A2", "A3", "A4", "A5"]);
+        assert!((markers[3].frequency - 440.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn note_markers_follow_the_tuning() {
+        let mut settings = Settings::default();
+        settings.grid.marker_mode = MarkerMode::Notes;
+        settings.note_readout.a4 = 432.0;
+        settings.fft.min_frequency = 200.0;
+        settings.fft.max_frequency = 600.0;
+
+        let markers = markers(&settings);
+        assert_eq!(markers.len(), 2);
+        assert_eq!(markers[0].label, "C4");
+        assert!((markers[0].frequency - 256.87).abs() < 0.01);
+        assert_eq!(markers[1].label, "C5");
+    }
+
+    #[test]
+    fn frequency_markers_use_the_configured_list() {
+        let mut settings = Settings::default();
+        settings.fft.frequencies = Some(vec![440.0, 2500.0]);
+
+        let labels: Vec<String> = markers(&settings)
+            .into_iter()
+            .map(|marker| marker.label)
+            .collect();
+        assert_eq!(labels, ["440 Hz", "2.5 kHz"]);
+    }
+
+    #[test]
+    fn visibility_changes_are_described() {
+        let all = GridVisibility {
+            enabled: true,
+            horizontal: true,
+            vertical: true,
+        };
+        assert_eq!(all.describe_change(&all), None);
+
+        let hidden = GridVisibility {
+            enabled: false,
+            ..all
+        };
+        assert_eq!(hidden.describe_change(&all).unwrap(), "grid: off");
+        assert_eq!(all.describe_change(&hidden).unwrap(), "grid: on");
+
+        let markers_only = GridVisibility {
+            horizontal: false,
+            ..all
+        };
+        assert_eq!(
+            markers_only.describe_change(&all).unwrap(),
+            "grid lines: off"
+        );
+    }
+
+    #[test]
+    fn palette_mode_colors_markers_like_the_bars() {
+        let fixed = FrequencyGrid::new(Arc::new(Settings::default()), "frequency");
+        let red = fixed.settings.grid.color_left;
+        assert_eq!(fixed.marker_color(red, Some(0.5), 0.1), red.to_rgba(0.1));
+
+        let mut settings = Settings::default();
+        settings.grid.color_mode = GridColorMode::Palette;
+        let grid = FrequencyGrid::new(Arc::new(settings), "frequency");
+        let (r, g, b) = get_color_for_frequency(&Palette::Rainbow, 0.5, 0.5);
+        assert_eq!(
+            grid.marker_color(red, Some(0.5), 0.1),
+            (f64::from(r), f64::from(g), f64::from(b), 0.1)
+        );
+        assert_ne!(
+            grid.marker_color(red, Some(0.1), 0.1),
+            grid.marker_color(red, Some(0.5), 0.1)
+        );
+        // Frequencies outside the spectrum keep the fixed color
+        assert_eq!(grid.marker_color(red, None, 0.1), red.to_rgba(0.1));
+    }
+
+    #[test]
+    fn snapping_centers_lines_on_device_pixels() {
+        assert_eq!(snap_to_pixel(10.0, 1.0), 10.5);
+        assert_eq!(snap_to_pixel(10.7, 1.0), 10.5);
+        // At 200% a logical pixel holds two device pixels
+        assert_eq!(snap_to_pixel(10.0, 2.0), 10.25);
+        assert_eq!(snap_to_pixel(10.7, 2.0), 10.75);
+    }
+
+    #[test]
+    fn lines_cover_whole_device_pixels() {
+        assert_eq!(pixel_line_width(0.5, 1.0), 1.0);
+        assert_eq!(pixel_line_width(1.0, 2.0), 1.0);
+        assert_eq!(pixel_line_width(0.5, 2.0), 0.5);
+
+        // Odd device widths are centered on a pixel, even ones on a pixel boundary
+        assert_eq!(snap_line(10.3, 1.0, 1.0), 10.5);
+        assert_eq!(snap_line(10.3, 1.0, 2.0), 10.5);
+        assert_eq!(snap_line(10.3, 0.5, 2.0), 10.25);
     }
 }
@@ -0,0 +1,165 @@
+/// Schedules analysis windows at a fixed hop through the captured audio.
+///
+/// A window ends after every `hop_size` captured samples, so the analyzed windows are aligned to
+/// the audio rather than to the moments frames happen to be drawn.
+///
+/// # Fields
+/// - `window_size`: Length of each analysis window, in samples.
+/// - `hop_size`: Number of samples between the ends of consecutive windows.
+/// - `last_received`: Total number of captured samples seen by the previous call to `windows`.
+/// - `since_last_hop`: Samples captured after the end of the latest scheduled window.
+pub struct HopScheduler {
+    window_size: usize,
+    hop_size: usize,
+    last_received: usize,
+    since_last_hop: usize,
+}
+
+impl HopScheduler {
+    /// Creates a new `HopScheduler` instance, starting at the first captured sample.
+    ///
+    /// # Arguments
+    /// - `window_size`: Length of each analysis window, in samples.
+    /// - `hop_size`: Number of samples between the ends of consecutive windows.
+    pub fn new(window_size: usize, hop_size: usize) -> Self {
+        HopScheduler {
+            window_size,
+            hop_size: hop_size.max(1),
+            last_received: 0,
+            since_last_hop: 0,
+        }
+    }
+
+    /// Returns the number of samples captured after the end of the latest scheduled window, as
+    /// of the previous call to `windows`.
+    pub fn samples_after_window(&self) -> usize {
+        self.since_last_hop
+    }
+
+    /// Returns whether a window completed since the previous call to `windows`, without
+    /// scheduling it.
+    ///
+    /// # Arguments
+    /// - `received`: Total number of samples captured so far, wrapping around on overflow.
+    pub fn has_window(&self, received: usize) -> bool {
+        self.since_last_hop
+            .saturating_add(received.wrapping_sub(self.last_received))
+            >= self.hop_size
+    }
+
+    /// Returns the windows completed since the previous call.
+    ///
+    /// # Arguments
+    /// - `received`: Total number of samples captured so far, wrapping around on overflow.
+    /// - `buffer_len`: Length of the capture buffer, which holds the latest `buffer_len` samples.
+    ///
+    /// # Returns
+    /// - The start of each completed window in the capture buffer, oldest first. Windows that
+    ///   have already left the buffer are skipped.
+    pub fn windows(&mut self, received: usize, buffer_len: usize) -> Vec<usize> {
+        let pending = self
+            .since_last_hop
+            .saturating_add(received.wrapping_sub(self.last_received));
+        self.last_received = received;
+        self.since_last_hop = pending % self.hop_size;
+
+        // Only the latest windows are still in the buffer
+        let in_buffer = match buffer_len.checked_sub(self.window_size + self.since_last_hop) {
+            Some(room) => room / self.hop_size + 1,
+            None => 0,
+        };
+
+        (1..=(pending / self.hop_size).min(in_buffer))
+            .rev()
+            .map(|hop| {
+                // Samples captured after the end of the window
+                let after_end = self.since_last_hop + (hop - 1) * self.hop_size;
+                buffer_len - after_end - self.window_size
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds chunks of a sample counter through a capture buffer, returning the first sample of
+    /// every scheduled window.
+    fn scheduled_window_starts(
+        scheduler: &mut HopScheduler,
+        chunks: &[usize],
+        buffer_len: usize,
+    ) -> Vec<Vec<usize>> {
+        let mut buffer = vec![0; buffer_len];
+        let mut received = 0;
+        chunks
+            .iter()
+            .map(|&chunk| {
+                for _ in 0..chunk {
+                    buffer.rotate_left(1);
+                    received += 1;
+                    buffer[buffer_len - 1] = received;
+                }
+                scheduler
+                    .windows(received, buffer_len)
+                    .into_iter()
+                    .map(|start| buffer[start])
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn windows_end_at_every_hop() {
+        let mut scheduler = HopScheduler::new(8, 4);
+        let starts = scheduled_window_starts(&mut scheduler, &[10, 3, 0, 7], 16);
+
+        // Windows end after samples 4, 8, 12, 16 and 20; samples are counted from 1
+        assert_eq!(starts, [vec![0, 1], vec![5], vec![], vec![9, 13]]);
+    }
+
+    #[test]
+    fn has_window_counts_across_the_wraparound() {
+        let mut scheduler = HopScheduler::new(8, 4);
+        let start = usize::MAX - 3;
+        scheduler.windows(start, 16);
+        assert!(!scheduler.has_window(start));
+        assert!(!scheduler.has_window(start.wrapping_add(3)));
+        // The counter wraps around to 0 with the fourth sample
+        assert_eq!(start.wrapping_add(4), 0);
+        assert!(scheduler.has_window(0));
+        assert!(scheduler.has_window(1));
+
+        // Samples left over after the latest window count toward the next one
+        assert_eq!(scheduler.windows(start.wrapping_add(6), 16).len(), 1);
+        assert!(!scheduler.has_window(start.wrapping_add(7)));
+        assert!(scheduler.has_window(start.wrapping_add(8)));
+    }
+
+    #[test]
+    fn windows_do_not_depend_on_the_chunk_sizes() {
+        let mut one_chunk = HopScheduler::new(8, 3);
+        let mut small_chunks = HopScheduler::new(8, 3);
+
+        let all = scheduled_window_starts(&mut one_chunk, &[20], 32).concat();
+        let chunked = scheduled_window_starts(&mut small_chunks, &[1, 5, 2, 7, 5], 32).concat();
+        assert_eq!(all, chunked);
+    }
+
+    #[test]
+    fn windows_that_left_the_buffer_are_skipped() {
+        let mut scheduler = HopScheduler::new(8, 2);
+        let starts = scheduled_window_starts(&mut scheduler, &[20], 12);
+
+        // Only windows ending after samples 16, 18 and 20 are still in the 12 sample buffer
+        assert_eq!(starts, [vec![9, 11, 13]]);
+    }
+
+    #[test]
+    fn received_count_may_wrap_around() {
+        let mut scheduler = HopScheduler::new(4, 4);
+        scheduler.windows(usize::MAX - 1, 8);
+        assert_eq!(scheduler.windows(2, 8).len(), 1);
+    }
+}
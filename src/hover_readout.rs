@@ -0,0 +1,233 @@
+use crate::fft_utils::frequency_indices;
+use crate::format::{format_db, format_frequency};
+use crate::frequency_mapper::{Channel, FrequencyMapper};
+use crate::level_scale::LevelScale;
+use crate::renderer::Spectrum;
+use crate::settings::Settings;
+use crate::text::{draw_text, measure, rounded_rectangle, TextStyle};
+use gtk::cairo::Context;
+use gtk4 as gtk;
+use std::sync::Arc;
+
+/// Font size of the readout text, in pixels.
+const FONT_SIZE: f64 = 12.0;
+/// Space between the readout text and its background edges, in pixels.
+const PADDING: f64 = 6.0;
+/// Radius of the corners of the readout background, in pixels.
+const CORNER_RADIUS: f64 = 4.0;
+/// Distance of the readout from the pointer, in pixels.
+const POINTER_OFFSET: f64 = 14.0;
+
+/// The frequency and level of the bar under the pointer.
+///
+/// # Fields
+/// - `frequency`: Frequency at the pointer, in Hz.
+/// - `channel`: The half of the display the pointer is on.
+/// - `level_db`: Level of the bar under the pointer, in dBFS.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Probe {
+    pub frequency: f32,
+    pub channel: Channel,
+    pub level_db: f32,
+}
+
+impl Probe {
+    /// Formats the probe as `L 440 Hz  -23.5 dB`, with a decimal comma when `decimal_comma` is
+    /// set.
+    fn display_text(&self, decimal_comma: bool) -> String {
+        let channel = match self.channel {
+            Channel::Left => "L",
+            Channel::Right => "R",
+        };
+        format!(
+            "{} {}  {}",
+            channel,
+            format_frequency(self.frequency, decimal_comma),
+            format_db(self.level_db, decimal_comma)
+        )
+    }
+}
+
+/// A crosshair with the frequency and level under the pointer, shown while hovering.
+///
+/// # Fields
+/// - `settings`: Shared settings providing the frequency range of the bars.
+pub struct HoverReadout {
+    settings: Arc<Settings>,
+}
+
+impl HoverReadout {
+    /// Creates a new `HoverReadout` instance.
+    ///
+    /// # Arguments
+    /// - `settings`: Shared settings providing the frequency range of the bars.
+    pub fn new(settings: Arc<Settings>) -> Self {
+        HoverReadout { settings }
+    }
+
+    /// Finds the frequency and level of the bar at an x coordinate.
+    ///
+    /// # Arguments
+    /// - `spectrum`: The analyzed frame the bars were drawn from.
+    /// - `level_scale`: Height mapping of the bars, converting magnitudes to dBFS.
+    /// - `x`: The x coordinate of the pointer.
+    /// - `width`: The width of the drawing area.
+    ///
+    /// # Returns
+    /// - The probe of the nearest bar, or `None` if no bars are drawn.
+    pub fn probe(
+        &self,
+        spectrum: &Spectrum,
+        level_scale: &LevelScale,
+        x: f64,
+        width: f64,
+    ) -> Option<Probe> {
+        let fft_size = spectrum.left.len();
+        let (min_index, max_index) = frequency_indices(&self.settings.fft, fft_size);
+        if min_index >= max_index {
+            return None;
+        }
+
+        let mapper = FrequencyMapper::from_settings(&self.settings, fft_size);
+        let (position, channel) = mapper.unmirrored_position(x, width / 2.0);
+        let frequency = mapper.frequency_at(position);
+
+        // The bar of a bin spans from its lower edge to the lower edge of the next bin
+        let bin_width = self.settings.fft.sample_rate / fft_size as f32;
+        let index = ((frequency / bin_width) as usize).clamp(min_index, max_index - 1);
+        let bins = match channel {
+            Channel::Left => &spectrum.left,
+            Channel::Right => &spectrum.right,
+        };
+
+        Some(Probe {
+            frequency,
+            channel,
+            level_db: level_scale.magnitude_to_db(bins[index].norm()),
+        })
+    }
+
+    /// Draws the crosshair and readout at the pointer.
+    ///
+    /// # Arguments
+    /// - `cr`: The Cairo `Context` used for drawing.
+    /// - `width`: The width of the drawing area.
+    /// - `height`: The height of the drawing area.
+    /// - `pointer`: Position of the pointer in the drawing area.
+    /// - `spectrum`: The analyzed frame the bars were drawn from.
+    /// - `level_scale`: Height mapping of the bars.
+    pub fn draw(
+        &self,
+        cr: &Context,
+        width: f64,
+        height: f64,
+        pointer: (f64, f64),
+        spectrum: &Spectrum,
+        level_scale: &LevelScale,
+    ) {
+        let Some(probe) = self.probe(spectrum, level_scale, pointer.0, width) else {
+            return;
+        };
+
+        cr.set_source_rgba(1.0, 1.0, 1.0, 0.4);
+        cr.set_line_width(1.0);
+        cr.move_to(pointer.0, 0.0);
+        cr.line_to(pointer.0, height);
+        cr.move_to(0.0, pointer.1);
+        cr.line_to(width, pointer.1);
+        let _ = cr.stroke();
+
+        let text = probe.display_text(self.settings.ui.decimal_comma);
+        let style = TextStyle {
+            color: (1.0, 1.0, 1.0, 0.9),
+            ..TextStyle::ui(&self.settings.ui, FONT_SIZE)
+        };
+        let (text_width, text_height) = measure(&text, &style);
+        let size = (text_width + 2.0 * PADDING, text_height + 2.0 * PADDING);
+        let (x, y) = readout_origin(pointer, size, (width, height));
+
+        cr.set_source_rgba(0.0, 0.0, 0.0, 0.7);
+        rounded_rectangle(cr, (x, y, size.0, size.1), CORNER_RADIUS);
+        let _ = cr.fill();
+        draw_text(cr, &text, x + PADDING, y + PADDING, &style);
+    }
+}
+
+/// Places the readout below and right of the pointer, flipping to the other side near the
+/// window edges so it stays inside the window.
+///
+/// # Arguments
+/// - `pointer`: Position of the pointer.
+/// - `size`: Width and height of the readout.
+/// - `bounds`: Width and height of the window.
+///
+/// # Returns
+/// - The top-left corner of the readout.
+fn readout_origin(pointer: (f64, f64), size: (f64, f64), bounds: (f64, f64)) -> (f64, f64) {
+    let place = |pointer: f64, size: f64, bound: f64| {
+        let after = pointer + POINTER_OFFSET;
+        let start = if after + size > bound {
+            pointer - POINTER_OFFSET - size
+        } else {
+            after
+        };
+        start.clamp(0.0, (bound - size).max(0.0))
+    };
+    (
+        place(pointer.0, size.0, bounds.0),
+        place(pointer.1, size.1, bounds.1),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustfft::num_complex::Complex32;
+
+    #[test]
+    fn probe_reads_the_bar_under_the_pointer() {
+        let mut settings = Settings::default();
+        settings.fft.sample_rate = 1000.0;
+        settings.fft.min_frequency = 0.0;
+        settings.fft.max_frequency = 400.0;
+        let settings = Arc::new(settings);
+
+        // 10 Hz bins; a full-scale sine in bin 11 of the right channel
+        let mut spectrum = Spectrum {
+            left: vec![Complex32::new(0.0, 0.0); 100],
+            right: vec![Complex32::new(0.0, 0.0); 100],
+        };
+        spectrum.right[11] = Complex32::new(50.0, 0.0);
+        let level_scale = LevelScale::new(&settings.visualizer_settings("frequency"), 100);
+
+        let readout = HoverReadout::new(settings);
+        // Each pixel away from the center is 1 Hz
+        let probe = readout
+            .probe(&spectrum, &level_scale, 515.0, 800.0)
+            .unwrap();
+        assert_eq!(probe.channel, Channel::Right);
+        assert!((probe.frequency - 115.0).abs() < 1e-3);
+        assert!(probe.level_db.abs() < 1e-3);
+
+        let probe = readout
+            .probe(&spectrum, &level_scale, 285.0, 800.0)
+            .unwrap();
+        assert_eq!(probe.channel, Channel::Left);
+        assert!((probe.frequency - 115.0).abs() < 1e-3);
+        assert!(probe.level_db < -100.0);
+    }
+
+    #[test]
+    fn readout_stays_inside_the_window() {
+        let bounds = (800.0, 600.0);
+        let size = (120.0, 24.0);
+        assert_eq!(readout_origin((100.0, 100.0), size, bounds), (114.0, 114.0));
+        // Near the right and bottom edges the readout flips to the other side of the pointer
+        assert_eq!(readout_origin((790.0, 590.0), size, bounds), (656.0, 552.0));
+        // A window smaller than the readout pins it to the top-left corner
+        assert_eq!(
+            readout_origin((50.0, 10.0), size, (100.0, 20.0)),
+            (0.0, 0.0)
+        );
+    }
+}
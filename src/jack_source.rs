@@ -0,0 +1,212 @@
+use crate::audio::{process_frame, AudioProcessing, AudioSink, AudioSource, DcBlocker};
+use crate::recorder::Recorder;
+use crate::settings::{self, Settings};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Name of the JACK client; its ports appear as `sonic_spectra:in_L` and `sonic_spectra:in_R`.
+const CLIENT_NAME: &str = "sonic_spectra";
+/// Time to wait before connecting again after the server went away or could not be reached.
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+/// How often the connection thread checks for a server shutdown or a visualizer shutdown.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A JACK client with two input ports that can be patched from any other client.
+///
+/// The client is started without starting a server. While no server is running, and after the
+/// server shuts down, the visualizer shows silence and the client keeps trying to connect.
+///
+/// # Fields
+/// - `settings`: Shared application settings with the `[audio]` processing configuration.
+/// - `recorder`: Receives the captured samples while a recording is in progress.
+pub struct JackSource {
+    settings: Arc<Settings>,
+    recorder: Arc<Recorder>,
+}
+
+impl JackSource {
+    /// Creates a new `JackSource` instance.
+    pub fn new(settings: Arc<Settings>, recorder: Arc<Recorder>) -> Self {
+        JackSource { settings, recorder }
+    }
+}
+
+impl AudioSource for JackSource {
+    fn start(self: Box<Self>, sink: AudioSink, shutdown: Arc<AtomicBool>) -> Result<(), String> {
+        let status = sink.status();
+        {
+            let mut status = status.lock().unwrap();
+            status.device = "JACK".to_string();
+            status.sample_rate = self.settings.fft.sample_rate as u32;
+            status.channels = 2;
+        }
+
+        thread::spawn(move || {
+            // Only this thread locks the sink besides the process callback, and only while the
+            // client is inactive, so the callback never waits for it
+            let sink = Arc::new(Mutex::new(sink));
+            let mut reported_failure = false;
+
+            while !shutdown.load(Ordering::Relaxed) {
+                let server_gone = Arc::new(AtomicBool::new(false));
+                match connect(&self, sink.clone(), server_gone.clone()) {
+                    Ok(client) => {
+                        reported_failure = false;
+                        while !shutdown.load(Ordering::Relaxed)
+                            && !server_gone.load(Ordering::Relaxed)
+                        {
+                            thread::sleep(POLL_INTERVAL);
+                        }
+                        let _ = client.deactivate();
+                        if server_gone.load(Ordering::Relaxed) {
+                            status.lock().unwrap().set_error("JACK server shut down");
+                        }
+                        // Show silence until the server is back
+                        sink.lock().unwrap().clear();
+                    }
+                    Err(e) => {
+                        status.lock().unwrap().set_error(e.to_string());
+                        if !reported_failure {
+                            eprintln!("Failed to connect to the JACK server, retrying: {}", e);
+                            reported_failure = true;
+                        }
+                    }
+                }
+
+                if !shutdown.load(Ordering::Relaxed) {
+                    thread::sleep(RECONNECT_DELAY);
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Opens the client, registers the input ports and activates the client.
+///
+/// # Arguments
+/// - `source`: The source with the settings and recorder.
+/// - `sink`: Receives the captured audio.
+/// - `server_gone`: Set when the server shuts down.
+fn connect(
+    source: &JackSource,
+    sink: Arc<Mutex<AudioSink>>,
+    server_gone: Arc<AtomicBool>,
+) -> Result<jack::AsyncClient<ServerWatch, Capture>, jack::Error> {
+    let (client, _) = jack::Client::new(CLIENT_NAME, jack::ClientOptions::NO_START_SERVER)?;
+    let in_left = client.register_port("in_L", jack::AudioIn::default())?;
+    let in_right = client.register_port("in_R", jack::AudioIn::default())?;
+
+    let sample_rate = client.sample_rate() as u32;
+    if sample_rate as f32 != source.settings.fft.sample_rate {
+        eprintln!(
+            "Warning: the JACK server runs at {} Hz but fft.sample_rate is {} Hz; restart to follow the server.",
+            sample_rate, source.settings.fft.sample_rate
+        );
+    }
+    source.recorder.set_sample_rate(sample_rate);
+
+    let audio_settings = &source.settings.audio;
+    let capture = Capture {
+        in_left,
+        in_right,
+        sink,
+        recorder: source.recorder.clone(),
+        processing: AudioProcessing::from_settings(audio_settings),
+        dc_blocker_left: DcBlocker::new(audio_settings.highpass_hz, sample_rate as f32),
+        dc_blocker_right: DcBlocker::new(audio_settings.highpass_hz, sample_rate as f32),
+        frames: Vec::with_capacity(client.buffer_size() as usize),
+    };
+    client.activate_async(ServerWatch { server_gone }, capture)
+}
+
+/// Sets `fft.sample_rate` to the sample rate of the running JACK server when `audio.source` is
+/// `"jack"`, so the analysis matches the captured audio.
+///
+/// # Arguments
+/// - `settings`: The settings, before they are shared.
+///
+/// `fft.max_frequency` is lowered below the Nyquist frequency of the server if needed. Without a
+/// running server the configured sample rate is kept.
+pub fn use_server_sample_rate(settings: &mut Settings) {
+    if settings.audio.source != settings::AudioSource::Jack {
+        return;
+    }
+
+    match jack::Client::new(CLIENT_NAME, jack::ClientOptions::NO_START_SERVER) {
+        Ok((client, _)) => {
+            let fft = &mut settings.fft;
+            fft.sample_rate = client.sample_rate() as f32;
+            fft.max_frequency = fft.max_frequency.min(fft.sample_rate / 2.0 - 1.0);
+        }
+        Err(e) => eprintln!(
+            "Failed to query the JACK sample rate, assuming {} Hz: {}",
+            settings.fft.sample_rate, e
+        ),
+    }
+}
+
+/// Process callback of the client, feeding the input ports to the sink.
+///
+/// # Fields
+/// - `in_left`: The left input port, `in_L`.
+/// - `in_right`: The right input port, `in_R`.
+/// - `sink`: Receives the captured audio.
+/// - `recorder`: Receives the captured samples while a recording is in progress.
+/// - `processing`: Gains, swapping and downmixing applied to the captured audio.
+/// - `dc_blocker_left`: DC blocking of the left channel.
+/// - `dc_blocker_right`: DC blocking of the right channel.
+/// - `frames`: Processed frames of the current cycle, reused so the callback does not allocate.
+struct Capture {
+    in_left: jack::Port<jack::AudioIn>,
+    in_right: jack::Port<jack::AudioIn>,
+    sink: Arc<Mutex<AudioSink>>,
+    recorder: Arc<Recorder>,
+    processing: AudioProcessing,
+    dc_blocker_left: DcBlocker,
+    dc_blocker_right: DcBlocker,
+    frames: Vec<(f32, f32)>,
+}
+
+impl jack::ProcessHandler for Capture {
+    fn process(&mut self, _: &jack::Client, scope: &jack::ProcessScope) -> jack::Control {
+        let left = self.in_left.as_slice(scope);
+        let right = self.in_right.as_slice(scope);
+
+        if self.recorder.is_recording() {
+            // Record the ports as captured, before any processing
+            let samples = left.iter().zip(right).flat_map(|(&l, &r)| [l, r]).collect();
+            self.recorder.push(samples);
+        }
+
+        self.frames.clear();
+        for (&l, &r) in left.iter().zip(right) {
+            self.frames.push(process_frame(
+                self.dc_blocker_left.process(l),
+                self.dc_blocker_right.process(r),
+                &self.processing,
+            ));
+        }
+        if let Ok(mut sink) = self.sink.try_lock() {
+            sink.push_frames(&self.frames);
+        }
+        jack::Control::Continue
+    }
+}
+
+/// Notification handler reporting a server shutdown to the connection thread.
+///
+/// # Fields
+/// - `server_gone`: Set when the server shuts down.
+struct ServerWatch {
+    server_gone: Arc<AtomicBool>,
+}
+
+impl jack::NotificationHandler for ServerWatch {
+    unsafe fn shutdown(&mut self, _: jack::ClientStatus, reason: &str) {
+        eprintln!("The JACK server shut down, showing silence: {}", reason);
+        self.server_gone.store(true, Ordering::Relaxed);
+    }
+}
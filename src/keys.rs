@@ -0,0 +1,436 @@
+use gtk::gdk;
+use gtk4 as gtk;
+use std::collections::HashMap;
+
+/// Modifiers a key binding can require; other modifiers such as Caps Lock are ignored.
+const BINDING_MODIFIERS: [(&str, gdk::ModifierType); 4] = [
+    ("ctrl", gdk::ModifierType::CONTROL_MASK),
+    ("shift", gdk::ModifierType::SHIFT_MASK),
+    ("alt", gdk::ModifierType::ALT_MASK),
+    ("super", gdk::ModifierType::SUPER_MASK),
+];
+
+/// Something the keyboard can do, named in the `[keys]` table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Exits the application.
+    Quit,
+    /// Opens the preferences window.
+    Preferences,
+    /// Toggles between the window and fullscreen.
+    Fullscreen,
+    /// Switches to the next visualizer.
+    CycleVisualizer,
+    /// Shows or hides the dominant frequency and note readout.
+    NoteReadout,
+    /// Shows or hides the loudness and true-peak meter.
+    LoudnessMeter,
+    /// Clears the clip light of the true-peak meter.
+    ClearClip,
+    /// Calibrates the noise floor.
+    Calibrate,
+    /// Clears the noise profile.
+    ClearNoiseProfile,
+    /// Stops soloing the frequency band selected with shift+drag.
+    ClearSoloBand,
+    /// Starts or stops recording the captured audio.
+    Record,
+    /// Saves the current frame as a PNG screenshot.
+    Screenshot,
+    /// Shows or hides the frequency grid.
+    Grid,
+    /// Shows or hides the horizontal grid lines.
+    HorizontalGrid,
+    /// Shows or hides the vertical frequency markers.
+    VerticalGrid,
+    /// Resets a zoomed frequency range.
+    ResetZoom,
+    /// Shows or hides the frame rate and timing overlay.
+    Stats,
+    /// Toggles between the left/right and mid/side channels.
+    ChannelMode,
+    /// Shows or hides the input device and activity status line.
+    AudioStatus,
+    /// Resets the max-hold curve.
+    ClearMaxHold,
+    /// Applies the `[[scene]]` table at this index, counting from 0.
+    Scene(usize),
+}
+
+impl Action {
+    /// Every action with its name in the `[keys]` table and its default key.
+    const ALL: [(Action, &'static str, &'static str); 29] = [
+        (Action::Quit, "quit", "q"),
+        (Action::Preferences, "preferences", "ctrl+comma"),
+        (Action::Fullscreen, "fullscreen", "F11"),
+        (Action::CycleVisualizer, "cycle_visualizer", "v"),
+        (Action::NoteReadout, "note_readout", "n"),
+        (Action::LoudnessMeter, "loudness_meter", "l"),
+        (Action::ClearClip, "clear_clip", "p"),
+        (Action::Calibrate, "calibrate", "c"),
+        (Action::ClearNoiseProfile, "clear_noise_profile", "shift+x"),
+        (Action::ClearSoloBand, "clear_solo_band", "x"),
+        (Action::Record, "record", "r"),
+        (Action::Screenshot, "screenshot", "s"),
+        (Action::Grid, "grid", "g"),
+        (Action::HorizontalGrid, "horizontal_grid", "h"),
+        (Action::VerticalGrid, "vertical_grid", "m"),
+        (Action::ResetZoom, "reset_zoom", "z"),
+        (Action::Stats, "stats", "d"),
+        (Action::ChannelMode, "channel_mode", "t"),
+        (Action::AudioStatus, "audio_status", "i"),
+        (Action::ClearMaxHold, "clear_max_hold", "shift+h"),
+        (Action::Scene(0), "scene_1", "1"),
+        (Action::Scene(1), "scene_2", "2"),
+        (Action::Scene(2), "scene_3", "3"),
+        (Action::Scene(3), "scene_4", "4"),
+        (Action::Scene(4), "scene_5", "5"),
+        (Action::Scene(5), "scene_6", "6"),
+        (Action::Scene(6), "scene_7", "7"),
+        (Action::Scene(7), "scene_8", "8"),
+        (Action::Scene(8), "scene_9", "9"),
+    ];
+
+    /// Returns the action called `name` in the `[keys]` table.
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL
+            .iter()
+            .find(|(_, action_name, _)| *action_name == name)
+            .map(|(action, _, _)| *action)
+    }
+
+    /// Returns every action, in the order of the `[keys]` documentation.
+    pub fn all() -> impl Iterator<Item = Action> {
+        Action::ALL.iter().map(|(action, _, _)| *action)
+    }
+
+    /// Returns the name of the GTK action, the `[keys]` name with dashes for underscores, as
+    /// GTK does not allow underscores in action names.
+    pub fn gtk_name(self) -> String {
+        let (_, name, _) = Action::ALL
+            .iter()
+            .find(|(action, _, _)| *action == self)
+            .expect("every action is listed in Action::ALL");
+        name.replace('_', "-")
+    }
+
+    /// Returns the GTK action with its group: `app.` for actions of the whole application,
+    /// `win.` for actions of the window that has the focus.
+    pub fn detailed_name(self) -> String {
+        let group = match self {
+            Action::Quit => "app",
+            _ => "win",
+        };
+        format!("{}.{}", group, self.gtk_name())
+    }
+}
+
+/// A key together with the modifiers that must be held.
+///
+/// # Fields
+/// - `key`: The key, lowercase for letters.
+/// - `modifiers`: The modifiers from `BINDING_MODIFIERS` that must be held.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub key: gdk::Key,
+    pub modifiers: gdk::ModifierType,
+}
+
+impl KeyBinding {
+    /// Parses a key binding such as `"q"`, `"F11"`, `"space"` or `"ctrl+shift+s"`.
+    ///
+    /// Keys take their GDK names, e.g. `"Tab"`, `"plus"` or `"comma"`, and letters match
+    /// regardless of case. Modifiers are `ctrl` (or `control`), `shift`, `alt` and `super`,
+    /// joined to the key with `+`.
+    ///
+    /// # Arguments
+    /// - `text`: The key binding as written in the `[keys]` table.
+    ///
+    /// # Returns
+    /// - The binding, or an error describing the part that is not a modifier or key.
+    pub fn parse(text: &str) -> Result<KeyBinding, String> {
+        let text = text.trim();
+        // A trailing "+" is the plus key itself, as in "ctrl++"
+        let (modifier_names, key_name) = match text.strip_suffix("++") {
+            Some(modifier_names) => (modifier_names, "plus"),
+            None => match text.rsplit_once('+') {
+                Some((_, "")) if text == "+" => ("", "plus"),
+                Some((modifier_names, key_name)) => (modifier_names, key_name),
+                None => ("", text),
+            },
+        };
+
+        let mut modifiers = gdk::ModifierType::empty();
+        for name in modifier_names.split('+').filter(|name| !name.is_empty()) {
+            let name = name.trim().to_lowercase();
+            let name = if name == "control" { "ctrl" } else { &name };
+            match BINDING_MODIFIERS
+                .iter()
+                .find(|(modifier, _)| *modifier == name)
+            {
+                Some((_, mask)) => modifiers.insert(*mask),
+                None => return Err(format!("unknown modifier \"{}\"", name)),
+            }
+        }
+
+        let key_name = key_name.trim();
+        if key_name.is_empty() {
+            return Err(format!("no key in \"{}\"", text));
+        }
+        let key =
+            gdk::Key::from_name(key_name).ok_or_else(|| format!("unknown key \"{}\"", key_name))?;
+        Ok(KeyBinding {
+            key: key.to_lower(),
+            modifiers,
+        })
+    }
+
+    /// Returns the binding as a GTK accelerator, such as `"<Control>comma"`.
+    ///
+    /// # Arguments
+    /// - `modifiers`: The modifiers held, `modifiers` of the binding or more.
+    fn accelerator(&self, modifiers: gdk::ModifierType) -> Option<String> {
+        let key = self.key.name()?;
+        let prefix: String = BINDING_MODIFIERS
+            .iter()
+            .filter(|(_, mask)| modifiers.contains(*mask))
+            .map(|(name, _)| match *name {
+                "ctrl" => "<Control>",
+                "shift" => "<Shift>",
+                "alt" => "<Alt>",
+                _ => "<Super>",
+            })
+            .collect();
+        Some(format!("{}{}", prefix, key))
+    }
+}
+
+/// The actions bound to keys, built from the `[keys]` table with the defaults filling gaps.
+///
+/// # Fields
+/// - `bindings`: Each bound key with its action.
+#[derive(Clone)]
+pub struct KeyMap {
+    bindings: Vec<(KeyBinding, Action)>,
+}
+
+impl KeyMap {
+    /// Creates a new `KeyMap` from the `[keys]` table.
+    ///
+    /// Unknown actions and keys are reported as warnings and leave the default in place.
+    ///
+    /// # Arguments
+    /// - `keys`: Key bindings by action name, as written in the `[keys]` table.
+    pub fn new(keys: &HashMap<String, String>) -> Self {
+        let mut configured: Vec<(&String, &String)> = keys.iter().collect();
+        configured.sort();
+        for (name, _) in &configured {
+            if Action::from_name(name).is_none() {
+                eprintln!("Ignoring unknown config key: keys.{}", name);
+            }
+        }
+
+        let bindings = Action::ALL
+            .iter()
+            .filter_map(|(action, name, default)| {
+                let configured = keys.get(*name).and_then(|text| {
+                    KeyBinding::parse(text)
+                        .map_err(|e| {
+                            eprintln!("Ignoring keys.{}: {}, using \"{}\"", name, e, default)
+                        })
+                        .ok()
+                });
+                configured
+                    .or_else(|| KeyBinding::parse(default).ok())
+                    .map(|binding| (binding, *action))
+            })
+            .collect();
+        KeyMap { bindings }
+    }
+
+    /// Returns the action bound to a pressed key.
+    ///
+    /// Shift is only significant when a binding requires it, so `"n"` also matches Shift+N
+    /// unless `"shift+n"` is bound to another action.
+    ///
+    /// # Arguments
+    /// - `key`: The pressed key.
+    /// - `modifiers`: The modifiers held while pressing it.
+    pub fn action(&self, key: gdk::Key, modifiers: gdk::ModifierType) -> Option<Action> {
+        let key = key.to_lower();
+        let modifiers =
+            BINDING_MODIFIERS
+                .iter()
+                .fold(gdk::ModifierType::empty(), |held, (_, mask)| {
+                    if modifiers.contains(*mask) {
+                        held | *mask
+                    } else {
+                        held
+                    }
+                });
+        let find = |modifiers: gdk::ModifierType| {
+            self.bindings
+                .iter()
+                .find(|(binding, _)| binding.key == key && binding.modifiers == modifiers)
+                .map(|(_, action)| *action)
+        };
+        find(modifiers).or_else(|| {
+            if modifiers.contains(gdk::ModifierType::SHIFT_MASK) {
+                find(modifiers.difference(gdk::ModifierType::SHIFT_MASK))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Returns the GTK accelerators of every bound action, for `set_accels_for_action`.
+    ///
+    /// Like `action`, a binding without shift is also triggered with shift held, unless that
+    /// combination is bound to another action.
+    pub fn accelerators(&self) -> Vec<(Action, Vec<String>)> {
+        let shift = gdk::ModifierType::SHIFT_MASK;
+        Action::all()
+            .map(|action| {
+                let accelerators = self
+                    .bindings
+                    .iter()
+                    .filter(|(_, bound)| *bound == action)
+                    .flat_map(|(binding, _)| {
+                        let shifted = binding.modifiers | shift;
+                        let shift_free = !binding.modifiers.contains(shift)
+                            && self.action(binding.key, shifted) == Some(action);
+                        let mut accelerators = vec![binding.accelerator(binding.modifiers)];
+                        if shift_free {
+                            accelerators.push(binding.accelerator(shifted));
+                        }
+                        accelerators.into_iter().flatten()
+                    })
+                    .collect();
+                (action, accelerators)
+            })
+            .collect()
+    }
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        KeyMap::new(&HashMap::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(key: gdk::Key, modifiers: gdk::ModifierType) -> KeyBinding {
+        KeyBinding { key, modifiers }
+    }
+
+    #[test]
+    fn key_bindings_parse_keys_and_modifiers() {
+        let none = gdk::ModifierType::empty();
+        let ctrl = gdk::ModifierType::CONTROL_MASK;
+        let shift = gdk::ModifierType::SHIFT_MASK;
+        assert_eq!(KeyBinding::parse("q"), Ok(binding(gdk::Key::q, none)));
+        assert_eq!(KeyBinding::parse("Q"), Ok(binding(gdk::Key::q, none)));
+        assert_eq!(
+            KeyBinding::parse("space"),
+            Ok(binding(gdk::Key::space, none))
+        );
+        assert_eq!(KeyBinding::parse("F11"), Ok(binding(gdk::Key::F11, none)));
+        assert_eq!(KeyBinding::parse("Tab"), Ok(binding(gdk::Key::Tab, none)));
+        assert_eq!(KeyBinding::parse("ctrl+q"), Ok(binding(gdk::Key::q, ctrl)));
+        assert_eq!(
+            KeyBinding::parse(" Control + Shift + x "),
+            Ok(binding(gdk::Key::x, ctrl | shift))
+        );
+        assert_eq!(KeyBinding::parse("plus"), Ok(binding(gdk::Key::plus, none)));
+        assert_eq!(KeyBinding::parse("+"), Ok(binding(gdk::Key::plus, none)));
+        assert_eq!(
+            KeyBinding::parse("ctrl++"),
+            Ok(binding(gdk::Key::plus, ctrl))
+        );
+    }
+
+    #[test]
+    fn unknown_keys_and_modifiers_are_errors() {
+        assert!(KeyBinding::parse("").is_err());
+        assert!(KeyBinding::parse("ctrl+").is_err());
+        assert!(KeyBinding::parse("hyper+q").is_err());
+        assert!(KeyBinding::parse("NoSuchKey").is_err());
+    }
+
+    #[test]
+    fn configured_keys_replace_the_defaults_of_their_actions() {
+        let keys = HashMap::from([
+            ("quit".to_string(), "ctrl+q".to_string()),
+            ("cycle_visualizer".to_string(), "Tab".to_string()),
+            ("screenshot".to_string(), "NoSuchKey".to_string()),
+            ("no_such_action".to_string(), "y".to_string()),
+        ]);
+        let map = KeyMap::new(&keys);
+        let none = gdk::ModifierType::empty();
+        let ctrl = gdk::ModifierType::CONTROL_MASK;
+        let shift = gdk::ModifierType::SHIFT_MASK;
+
+        assert_eq!(map.action(gdk::Key::q, ctrl), Some(Action::Quit));
+        assert_eq!(map.action(gdk::Key::q, none), None);
+        assert_eq!(
+            map.action(gdk::Key::Tab, none),
+            Some(Action::CycleVisualizer)
+        );
+        assert_eq!(map.action(gdk::Key::v, none), None);
+        // Invalid keys keep the default, and the rest keep theirs
+        assert_eq!(map.action(gdk::Key::s, none), Some(Action::Screenshot));
+        assert_eq!(map.action(gdk::Key::G, shift), Some(Action::Grid));
+        assert_eq!(map.action(gdk::Key::x, none), Some(Action::ClearSoloBand));
+        assert_eq!(
+            map.action(gdk::Key::X, shift),
+            Some(Action::ClearNoiseProfile)
+        );
+        assert_eq!(map.action(gdk::Key::comma, ctrl), Some(Action::Preferences));
+        assert_eq!(map.action(gdk::Key::h, none), Some(Action::HorizontalGrid));
+        assert_eq!(map.action(gdk::Key::H, shift), Some(Action::ClearMaxHold));
+    }
+
+    #[test]
+    fn accelerators_follow_the_configured_keys() {
+        let keys = HashMap::from([("quit".to_string(), "ctrl+q".to_string())]);
+        let accelerators = KeyMap::new(&keys).accelerators();
+        let of = |action| {
+            accelerators
+                .iter()
+                .find(|(bound, _)| *bound == action)
+                .map(|(_, accelerators)| accelerators.clone())
+                .unwrap()
+        };
+
+        assert_eq!(of(Action::Quit), ["<Control>q", "<Control><Shift>q"]);
+        assert_eq!(
+            of(Action::Preferences),
+            ["<Control>comma", "<Control><Shift>comma"]
+        );
+        assert_eq!(of(Action::Fullscreen), ["F11", "<Shift>F11"]);
+        // Shift+H is bound to another action, so H only triggers without shift
+        assert_eq!(of(Action::HorizontalGrid), ["h"]);
+        assert_eq!(of(Action::ClearMaxHold), ["<Shift>h"]);
+    }
+
+    #[test]
+    fn gtk_action_names_use_dashes() {
+        assert_eq!(Action::Quit.detailed_name(), "app.quit");
+        assert_eq!(
+            Action::CycleVisualizer.detailed_name(),
+            "win.cycle-visualizer"
+        );
+        assert_eq!(Action::Scene(2).detailed_name(), "win.scene-3");
+        assert_eq!(Action::all().count(), Action::ALL.len());
+    }
+
+    #[test]
+    fn every_default_key_parses() {
+        for (_, name, default) in Action::ALL {
+            assert!(KeyBinding::parse(default).is_ok(), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::time::{Duration, Instant};
+
+/// Share of the latency left after the analysis window that the wait for the next hop takes on
+/// average when `fft.latency_target_ms` picks the hop size; the smoothing gets the rest.
+const HOP_SHARE: f32 = 0.25;
+
+/// Smallest hop size `latency_budget` picks, as a fraction of the FFT size, so a tight target
+/// cannot schedule a window for nearly every captured sample.
+const MIN_HOP_FRACTION: usize = 16;
+
+/// Estimates how far the displayed spectrum lags behind the audio.
+///
+/// # Arguments
+/// - `now`: The time the frame is drawn.
+/// - `newest_frame_at`: When the newest captured frame was pushed, if any was.
+/// - `window_offset`: Time between the center of the analyzed window and the newest captured
+///   frame, from `FrameRenderer::window_center_offset`.
+///
+/// # Returns
+/// - The age of the audio at the center of the analyzed window, or `None` before the first
+///   frame was captured.
+pub fn visual_latency(
+    now: Instant,
+    newest_frame_at: Option<Instant>,
+    window_offset: Duration,
+) -> Option<Duration> {
+    newest_frame_at.map(|newest| now.saturating_duration_since(newest) + window_offset)
+}
+
+/// Returns the time between the center of the latest analysis window and the newest captured
+/// frame.
+///
+/// # Arguments
+/// - `samples_after_window`: Samples captured after the end of the window.
+/// - `fft_size`: Length of the window, in samples.
+/// - `sample_rate`: Sample rate of the captured audio, in Hz.
+pub fn window_center_offset(
+    samples_after_window: usize,
+    fft_size: usize,
+    sample_rate: f32,
+) -> Duration {
+    let samples = samples_after_window as f32 + fft_size as f32 / 2.0;
+    Duration::from_secs_f32((samples / sample_rate.max(1.0)).max(0.0))
+}
+
+/// Hop size and smoothing time picked to approximately meet a latency target.
+///
+/// # Fields
+/// - `hop_size`: Samples between the ends of consecutive analysis windows.
+/// - `smoothing_ms`: Time constant of the bar smoothing, in milliseconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LatencyBudget {
+    pub hop_size: usize,
+    pub smoothing_ms: f32,
+}
+
+/// Splits a latency target between the analysis window, the wait for the next hop and the
+/// smoothing of the bars.
+///
+/// The center of the window lags half of the FFT size behind the newest sample, and a window
+/// ends on average half a hop before it. What remains of the target after the window goes for
+/// a `HOP_SHARE` to the hop and for the rest to the smoothing.
+///
+/// # Arguments
+/// - `target_ms`: The latency to aim for, in milliseconds.
+/// - `fft_size`: The FFT size, which the target cannot change.
+/// - `sample_rate`: Sample rate of the captured audio, in Hz.
+///
+/// # Returns
+/// - The hop size, between a sixteenth of the FFT size and the FFT size, and the smoothing time,
+///   `0.0` when the window alone exceeds the target.
+pub fn latency_budget(target_ms: f32, fft_size: usize, sample_rate: f32) -> LatencyBudget {
+    let samples_to_ms = |samples: f32| samples / sample_rate * 1000.0;
+    let remaining_ms = (target_ms - samples_to_ms(fft_size as f32 / 2.0)).max(0.0);
+    let hop_size = (2.0 * remaining_ms * HOP_SHARE / 1000.0 * sample_rate).round() as usize;
+    let hop_size = hop_size.clamp((fft_size / MIN_HOP_FRACTION).max(1), fft_size);
+    LatencyBudget {
+        hop_size,
+        smoothing_ms: (remaining_ms - samples_to_ms(hop_size as f32 / 2.0)).max(0.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latency_adds_the_age_of_the_newest_frame_to_the_window_offset() {
+        let newest = Instant::now();
+        let now = newest + Duration::from_millis(20);
+        let offset = window_center_offset(256, 1024, 44100.0);
+        // Half of the window and the samples after it: 768 samples at 44.1 kHz
+        assert!((offset.as_secs_f64() - 768.0 / 44100.0).abs() < 1e-6);
+
+        let latency = visual_latency(now, Some(newest), offset).unwrap();
+        assert!((latency.as_secs_f64() - (0.020 + 768.0 / 44100.0)).abs() < 1e-6);
+        assert_eq!(visual_latency(now, None, offset), None);
+        // A frame pushed after the frame started drawing is not in the future
+        assert_eq!(visual_latency(newest, Some(now), offset), Some(offset));
+    }
+
+    #[test]
+    fn budget_splits_the_target_after_the_window() {
+        let budget = latency_budget(100.0, 4096, 44100.0);
+        let window_ms = 2048.0 / 44100.0 * 1000.0;
+        let hop_ms = budget.hop_size as f32 / 2.0 / 44100.0 * 1000.0;
+        assert!((window_ms + hop_ms + budget.smoothing_ms - 100.0).abs() < 0.1);
+        assert!((hop_ms - (100.0 - window_ms) * HOP_SHARE).abs() < 0.1);
+    }
+
+    #[test]
+    fn budget_clamps_the_hop_size() {
+        // A generous target would ask for hops longer than the window
+        assert_eq!(latency_budget(1000.0, 1024, 44100.0).hop_size, 1024);
+
+        // The window alone exceeds a tight target
+        let budget = latency_budget(5.0, 4096, 44100.0);
+        assert_eq!(budget.hop_size, 4096 / MIN_HOP_FRACTION);
+        assert_eq!(budget.smoothing_ms, 0.0);
+    }
+}
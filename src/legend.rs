@@ -0,0 +1,241 @@
+use crate::frequency_mapper::Channel;
+use crate::settings::Settings;
+use crate::text::{draw_text, measure, TextAlign, TextStyle};
+use crate::visualizer::ChannelLayout;
+use gtk::cairo::Context;
+use gtk4 as gtk;
+use std::time::{Duration, Instant};
+
+/// Time the legend stays visible after the window turned fullscreen.
+const HIDE_AFTER_FULLSCREEN: Duration = Duration::from_secs(3);
+/// Font size of the labels, in pixels.
+const LABEL_SIZE: f64 = 12.0;
+/// Distance of the labels from the edges of the region they label, in pixels.
+const MARGIN: f64 = 8.0;
+/// Space between the labels of overlaid channels, in pixels.
+const LABEL_SPACING: f64 = 8.0;
+
+/// Where one label of the legend is drawn.
+///
+/// # Fields
+/// - `channel`: The channel the label names.
+/// - `x`: The x coordinate `align` places the label at.
+/// - `bottom`: The y coordinate of the bottom edge of the label.
+/// - `align`: Placement of the label relative to `x`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LabelPlacement {
+    pub channel: Channel,
+    pub x: f64,
+    pub bottom: f64,
+    pub align: TextAlign,
+}
+
+/// Places the labels of the channels in the lower corners of the parts of a region they are
+/// drawn in.
+///
+/// # Arguments
+/// - `layout`: Where the visualizer draws the channels.
+/// - `(x, y, width, height)`: The region of the visualizer, inside the plot margins.
+/// - `label_width`: Width of the widest label, which spaces the labels of overlaid channels.
+///
+/// # Returns
+/// - The placements of the left and right labels, or none for combined channels.
+pub fn label_placements(
+    layout: ChannelLayout,
+    (x, y, width, height): (f64, f64, f64, f64),
+    label_width: f64,
+) -> Vec<LabelPlacement> {
+    let (left, right, bottom) = (x + MARGIN, x + width - MARGIN, y + height - MARGIN);
+    let place = |channel, x, bottom, align| LabelPlacement {
+        channel,
+        x,
+        bottom,
+        align,
+    };
+    match layout {
+        ChannelLayout::SideBySide => vec![
+            place(Channel::Left, left, bottom, TextAlign::Left),
+            place(Channel::Right, right, bottom, TextAlign::Right),
+        ],
+        ChannelLayout::TopBottom => vec![
+            place(
+                Channel::Left,
+                left,
+                y + height / 2.0 - MARGIN,
+                TextAlign::Left,
+            ),
+            place(Channel::Right, left, bottom, TextAlign::Left),
+        ],
+        ChannelLayout::Overlay => vec![
+            place(Channel::Left, left, bottom, TextAlign::Left),
+            place(
+                Channel::Right,
+                left + label_width + LABEL_SPACING,
+                bottom,
+                TextAlign::Left,
+            ),
+        ],
+        ChannelLayout::Combined => Vec::new(),
+    }
+}
+
+/// Labels of the channels in the colors of the channels, hidden a few seconds into fullscreen.
+///
+/// # Fields
+/// - `fullscreen_since`: When the window turned fullscreen, while it is.
+/// - `hidden`: Whether the legend has been fullscreen for `HIDE_AFTER_FULLSCREEN`.
+#[derive(Default)]
+pub struct Legend {
+    fullscreen_since: Option<Instant>,
+    hidden: bool,
+}
+
+impl Legend {
+    /// Creates a new, visible `Legend`.
+    pub fn new() -> Self {
+        Legend::default()
+    }
+
+    /// Tells the legend whether the window is fullscreen, hiding it once the window has been
+    /// fullscreen for a few seconds and showing it again when the window leaves fullscreen.
+    ///
+    /// # Arguments
+    /// - `fullscreen`: Whether the window is fullscreen.
+    /// - `now`: The current time.
+    pub fn set_fullscreen(&mut self, fullscreen: bool, now: Instant) {
+        if !fullscreen {
+            self.fullscreen_since = None;
+        } else if self.fullscreen_since.is_none() {
+            self.fullscreen_since = Some(now);
+        }
+        self.hidden = self
+            .fullscreen_since
+            .is_some_and(|since| now.saturating_duration_since(since) >= HIDE_AFTER_FULLSCREEN);
+    }
+
+    /// Returns whether the legend is shown in fullscreen and about to hide.
+    pub fn is_counting_down(&self) -> bool {
+        self.fullscreen_since.is_some() && !self.hidden
+    }
+
+    /// Returns whether the legend is drawn.
+    ///
+    /// # Arguments
+    /// - `settings`: Settings providing `ui.show_legend`.
+    pub fn is_visible(&self, settings: &Settings) -> bool {
+        settings.ui.show_legend && !self.hidden
+    }
+
+    /// Draws the labels of the channels.
+    ///
+    /// # Arguments
+    /// - `cr`: The Cairo `Context` to draw to.
+    /// - `settings`: Settings providing the channel colors and the font.
+    /// - `layout`: Where the visualizer draws the channels.
+    /// - `region`: The region of the visualizer, inside the plot margins.
+    /// - `(left, right)`: Labels of the left and right channels.
+    pub fn draw(
+        &self,
+        cr: &Context,
+        settings: &Settings,
+        layout: ChannelLayout,
+        region: (f64, f64, f64, f64),
+        (left, right): (&str, &str),
+    ) {
+        let style = TextStyle::ui(&settings.ui, LABEL_SIZE).bold();
+        let (left_width, label_height) = measure(left, &style);
+        let (right_width, _) = measure(right, &style);
+        for placement in label_placements(layout, region, left_width.max(right_width)) {
+            let (text, color) = match placement.channel {
+                Channel::Left => (left, &settings.grid.color_left),
+                Channel::Right => (right, &settings.grid.color_right),
+            };
+            let style = TextStyle {
+                color: color.to_rgba(1.0),
+                align: placement.align,
+                ..style.clone()
+            };
+            draw_text(
+                cr,
+                text,
+                placement.x,
+                placement.bottom - label_height,
+                &style,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REGION: (f64, f64, f64, f64) = (20.0, 10.0, 400.0, 200.0);
+
+    fn sides(layout: ChannelLayout) -> Vec<(Channel, f64, f64, TextAlign)> {
+        label_placements(layout, REGION, 10.0)
+            .into_iter()
+            .map(|placement| {
+                (
+                    placement.channel,
+                    placement.x,
+                    placement.bottom,
+                    placement.align,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn side_by_side_labels_sit_in_the_lower_corners_of_their_halves() {
+        assert_eq!(
+            sides(ChannelLayout::SideBySide),
+            [
+                (Channel::Left, 28.0, 202.0, TextAlign::Left),
+                (Channel::Right, 412.0, 202.0, TextAlign::Right),
+            ]
+        );
+    }
+
+    #[test]
+    fn top_bottom_labels_sit_at_the_bottom_of_their_halves() {
+        assert_eq!(
+            sides(ChannelLayout::TopBottom),
+            [
+                (Channel::Left, 28.0, 102.0, TextAlign::Left),
+                (Channel::Right, 28.0, 202.0, TextAlign::Left),
+            ]
+        );
+    }
+
+    #[test]
+    fn overlaid_labels_sit_next_to_each_other() {
+        assert_eq!(
+            sides(ChannelLayout::Overlay),
+            [
+                (Channel::Left, 28.0, 202.0, TextAlign::Left),
+                (Channel::Right, 46.0, 202.0, TextAlign::Left),
+            ]
+        );
+        assert!(sides(ChannelLayout::Combined).is_empty());
+    }
+
+    #[test]
+    fn legend_hides_a_while_into_fullscreen() {
+        let mut settings = Settings::default();
+        settings.ui.show_legend = true;
+        let start = Instant::now();
+        let mut legend = Legend::new();
+        assert!(legend.is_visible(&settings));
+
+        legend.set_fullscreen(true, start);
+        assert!(legend.is_visible(&settings));
+        legend.set_fullscreen(true, start + HIDE_AFTER_FULLSCREEN);
+        assert!(!legend.is_visible(&settings));
+        legend.set_fullscreen(false, start + HIDE_AFTER_FULLSCREEN * 2);
+        assert!(legend.is_visible(&settings));
+
+        settings.ui.show_legend = false;
+        assert!(!legend.is_visible(&settings));
+    }
+}
@@ -0,0 +1,280 @@
+use crate::settings::{BarDirection, ResolvedVisualizerSettings};
+
+/// Maps bin magnitudes and dB levels to bar heights, shared by the visualizers and the grid so
+/// level lines agree with the bars.
+///
+/// Bars are `log10(magnitude * gain)` decades tall, scaled by `scale_factor` pixels per decade.
+/// Levels are in dB relative to a full-scale sine (dBFS), whose bin magnitude is `fft_size / 2`.
+/// With a curve exponent other than 1.0, heights are then raised to the exponent relative to the
+/// height of a full-scale sine, which keeps the floor and full scale in place.
+///
+/// # Fields
+/// - `gain`: Amplification applied to the magnitudes.
+/// - `scale_factor`: Height of one decade of magnitude (20 dB), in pixels.
+/// - `floor_db`: Level drawn at height zero, in dBFS.
+/// - `curve_exponent`: Exponent applied to the heights relative to a full-scale sine.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelScale {
+    gain: f32,
+    scale_factor: f32,
+    floor_db: f32,
+    curve_exponent: f32,
+}
+
+impl LevelScale {
+    /// Creates a new `LevelScale` instance.
+    ///
+    /// # Arguments
+    /// - `settings`: Drawing settings of the visualizer providing `gain`, `scale_factor` and
+    ///   `curve_exponent`.
+    /// - `fft_size`: The size of the FFT, which sets the magnitude of a full-scale sine; `fft.size`
+    ///   also with zero-padding, which leaves the magnitudes unchanged.
+    pub fn new(settings: &ResolvedVisualizerSettings, fft_size: usize) -> Self {
+        let full_scale = fft_size as f32 / 2.0;
+        LevelScale {
+            gain: settings.gain,
+            scale_factor: settings.scale_factor,
+            floor_db: -20.0 * (settings.gain * full_scale).max(f32::MIN_POSITIVE).log10(),
+            curve_exponent: settings.curve_exponent,
+        }
+    }
+
+    /// Returns the scale of bars whose magnitudes are multiplied by `factor`, such as by the
+    /// automatic gain.
+    pub fn amplified(&self, factor: f32) -> Self {
+        LevelScale {
+            gain: self.gain * factor,
+            scale_factor: self.scale_factor,
+            floor_db: self.floor_db - 20.0 * factor.max(f32::MIN_POSITIVE).log10(),
+            curve_exponent: self.curve_exponent,
+        }
+    }
+
+    /// Returns the amplification applied to the magnitudes.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Returns the target bar height of a bin magnitude, in pixels.
+    pub fn height(&self, magnitude: f32) -> f32 {
+        self.curve((magnitude * self.gain + 1e-6).log10().max(0.0) * self.scale_factor)
+    }
+
+    /// Returns the magnitude below which bars have no height.
+    pub fn floor_magnitude(&self) -> f32 {
+        1.0 / self.gain.max(f32::MIN_POSITIVE)
+    }
+
+    /// Returns the level of a bin magnitude, in dBFS.
+    pub fn magnitude_to_db(&self, magnitude: f32) -> f32 {
+        20.0 * (magnitude * self.gain).max(f32::MIN_POSITIVE).log10() + self.floor_db
+    }
+
+    /// Returns the height a level is drawn at, in pixels; levels below the floor are at zero.
+    pub fn db_to_height(&self, db: f32) -> f32 {
+        self.curve(((db - self.floor_db) / 20.0 * self.scale_factor).max(0.0))
+    }
+
+    /// Returns the level drawn at `height` pixels, in dBFS.
+    pub fn ceiling_db(&self, height: f32) -> f32 {
+        let full_scale = self.full_scale_height();
+        let height = if self.curve_exponent != 1.0 && full_scale > 0.0 {
+            (height.max(0.0) / full_scale).powf(1.0 / self.curve_exponent) * full_scale
+        } else {
+            height
+        };
+        self.floor_db + height / self.scale_factor.max(f32::EPSILON) * 20.0
+    }
+
+    /// Returns the height of a full-scale sine before the curve, in pixels.
+    fn full_scale_height(&self) -> f32 {
+        -self.floor_db / 20.0 * self.scale_factor
+    }
+
+    /// Raises a height to the curve exponent relative to the height of a full-scale sine.
+    fn curve(&self, height: f32) -> f32 {
+        let full_scale = self.full_scale_height();
+        if self.curve_exponent == 1.0 || full_scale <= 0.0 {
+            return height;
+        }
+        (height / full_scale).powf(self.curve_exponent) * full_scale
+    }
+
+    /// Returns the levels of the lines drawn between the floor and the ceiling.
+    ///
+    /// # Arguments
+    /// - `height`: Height of the drawing area, in pixels.
+    /// - `step_db`: Distance between neighboring lines, in dB.
+    ///
+    /// # Returns
+    /// - The multiples of `step_db` above the floor and up to the ceiling, lowest first.
+    pub fn db_lines(&self, height: f32, step_db: f32) -> Vec<f32> {
+        if step_db <= 0.0 {
+            return Vec::new();
+        }
+        let first = (self.floor_db / step_db).ceil() as i32;
+        let last = (self.ceiling_db(height) / step_db).floor() as i32;
+        (first..=last).map(|n| n as f32 * step_db).collect()
+    }
+}
+
+/// Returns the rectangle a bar covers.
+///
+/// # Arguments
+/// - `direction`: Where the bar grows from.
+/// - `height_px`: Height of the bar, in pixels.
+/// - `(x, width)`: Left edge and width of the bar.
+/// - `plot_height`: Height of the drawing area.
+///
+/// # Returns
+/// - `(x, y, width, height)` of the bar.
+pub fn bar_rect(
+    direction: BarDirection,
+    height_px: f64,
+    (x, width): (f64, f64),
+    plot_height: f64,
+) -> (f64, f64, f64, f64) {
+    let y = match direction {
+        BarDirection::Up => plot_height - height_px,
+        BarDirection::Down => 0.0,
+        BarDirection::Center => (plot_height - height_px) / 2.0,
+    };
+    (x, y, width, height_px)
+}
+
+/// Moves a horizontal line from where it is drawn for bars growing up to where it is drawn for
+/// bars growing in `direction`, so level lines keep marking the ends of the bars.
+///
+/// # Arguments
+/// - `direction`: Where the bars grow from.
+/// - `y`: The line for bars growing up, in pixels from the top.
+/// - `plot_height`: Height of the drawing area.
+///
+/// # Returns
+/// - The line, or for `BarDirection::Center` the lines above and below the middle.
+pub fn oriented_ys(direction: BarDirection, y: f64, plot_height: f64) -> Vec<f64> {
+    match direction {
+        BarDirection::Up => vec![y],
+        BarDirection::Down => vec![plot_height - y],
+        BarDirection::Center => vec![y / 2.0, plot_height - y / 2.0],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+
+    fn scale(gain: f32, scale_factor: f32, fft_size: usize) -> LevelScale {
+        let mut settings = Settings::default().visualizer_settings("frequency");
+        settings.gain = gain;
+        settings.scale_factor = scale_factor;
+        LevelScale::new(&settings, fft_size)
+    }
+
+    #[test]
+    fn floor_follows_gain_and_fft_size() {
+        // A full-scale sine of a 2000-bin FFT has magnitude 1000, so a gain of 1/1000 puts it at 0
+        let level_scale = scale(0.001, 90.0, 2000);
+        assert!(level_scale.floor_db.abs() < 1e-3);
+
+        let level_scale = scale(1.0, 90.0, 2000);
+        assert!((level_scale.floor_db + 60.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn level_heights_match_bar_heights() {
+        let level_scale = scale(20.0, 90.0, 1024);
+
+        // A sine at -20 dBFS has a tenth of the full-scale magnitude
+        let magnitude = 512.0 * 0.1;
+        let bar = level_scale.height(magnitude);
+        assert!((level_scale.db_to_height(-20.0) - bar).abs() < 1e-2);
+
+        assert!((level_scale.magnitude_to_db(magnitude) + 20.0).abs() < 1e-3);
+        assert_eq!(level_scale.db_to_height(level_scale.floor_db - 10.0), 0.0);
+        assert!((level_scale.ceiling_db(90.0) - level_scale.floor_db - 20.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn amplified_scale_lowers_the_floor() {
+        let level_scale = scale(1.0, 90.0, 2000);
+        let amplified = level_scale.amplified(10.0);
+        assert!((amplified.floor_db + 80.0).abs() < 1e-3);
+        assert!((amplified.height(100.0) - level_scale.height(1000.0)).abs() < 1e-3);
+        assert!((amplified.db_to_height(-40.0) - amplified.height(10.0)).abs() < 1e-2);
+    }
+
+    #[test]
+    fn curve_keeps_the_floor_and_full_scale_in_place() {
+        let mut settings = Settings::default().visualizer_settings("frequency");
+        settings.gain = 1.0;
+        settings.curve_exponent = 0.5;
+        // Floor at -60 dBFS, full scale 270 pixels high
+        let level_scale = LevelScale::new(&settings, 2000);
+        let linear = scale(1.0, 90.0, 2000);
+
+        assert!((level_scale.height(1000.0) - linear.height(1000.0)).abs() < 1e-2);
+        assert_eq!(level_scale.height(0.5), 0.0);
+        // -30 dBFS is halfway up without the curve, and raised by it
+        assert!((linear.db_to_height(-30.0) - 135.0).abs() < 1e-3);
+        let raised = level_scale.db_to_height(-30.0);
+        assert!((raised - 270.0 * 0.5_f32.sqrt()).abs() < 1e-2);
+        assert!((level_scale.ceiling_db(raised) + 30.0).abs() < 1e-3);
+        // Bars and level lines still agree
+        let magnitude = 1000.0 * 10f32.powf(-30.0 / 20.0);
+        assert!((level_scale.height(magnitude) - raised).abs() < 1e-1);
+    }
+
+    #[test]
+    fn db_lines_cover_the_visible_range() {
+        // Floor at -60 dBFS and 20 dB per 90 pixels
+        let level_scale = scale(1.0, 90.0, 2000);
+        assert_eq!(
+            level_scale.db_lines(180.0, 10.0),
+            [-60.0, -50.0, -40.0, -30.0, -20.0]
+        );
+        assert_eq!(level_scale.db_lines(100.0, 20.0), [-60.0, -40.0]);
+        assert!(level_scale.db_lines(100.0, 0.0).is_empty());
+    }
+
+    #[test]
+    fn bars_grow_from_their_direction() {
+        let span = (10.0, 4.0);
+        assert_eq!(
+            bar_rect(BarDirection::Up, 30.0, span, 200.0),
+            (10.0, 170.0, 4.0, 30.0)
+        );
+        assert_eq!(
+            bar_rect(BarDirection::Down, 30.0, span, 200.0),
+            (10.0, 0.0, 4.0, 30.0)
+        );
+        assert_eq!(
+            bar_rect(BarDirection::Center, 30.0, span, 200.0),
+            (10.0, 85.0, 4.0, 30.0)
+        );
+        // Empty bars sit on their baseline
+        assert_eq!(
+            bar_rect(BarDirection::Center, 0.0, span, 200.0),
+            (10.0, 100.0, 4.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn level_lines_mark_the_ends_of_the_bars() {
+        for direction in [BarDirection::Up, BarDirection::Down, BarDirection::Center] {
+            let (_, y, _, height) = bar_rect(direction, 30.0, (0.0, 1.0), 200.0);
+            let ys = oriented_ys(direction, 170.0, 200.0);
+            let far_end = if direction == BarDirection::Down {
+                y + height
+            } else {
+                y
+            };
+            assert_eq!(ys[0], far_end, "{:?}", direction);
+        }
+        assert_eq!(
+            oriented_ys(BarDirection::Center, 170.0, 200.0),
+            [85.0, 115.0]
+        );
+    }
+}
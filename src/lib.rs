@@ -1,93 +1,776 @@
-use crate::frequency_holographic_glow_visualizer::HolographicGlowVisualizer;
-use crate::frequency_range_visualizer::FrequencyRangeVisualizer;
-use crate::settings::Settings;
+use crate::audio::CaptureSource;
+pub use crate::audio::{
+    audio_channel, AudioData, AudioReader, AudioSink, AudioSource, SineTestSource, SinkWatch,
+};
+pub use crate::bar_batch::{BarBatch, GradientCache};
+use crate::cli::CliOptions;
+pub use crate::cli::EmitFormat;
+pub use crate::color::Palette;
+use crate::dsp::BeatCallback;
+pub use crate::fft_utils::{band_bins, band_magnitudes, octave_bands, OctaveBand};
+use crate::file_utils::{read_resource, timestamped_file_name};
+pub use crate::format::{format_db, format_duration, format_frequency};
+use crate::frame_stats::FrameStats;
+pub use crate::frequency_mapper::{BarSlot, Channel, FrequencyMapper};
+#[cfg(feature = "gl")]
+use crate::gl_renderer::GlBars;
+use crate::grid::GridVisibility;
+use crate::keys::{Action, KeyMap};
+use crate::now_playing::{NowPlayingOverlay, TrackInfo};
+use crate::power_state::PowerState;
+use crate::recorder::Recorder;
+use crate::redraw_timer::RedrawTimer;
+pub use crate::renderer::{FrameRenderer, Spectrum};
+use crate::session_state::SessionState;
+use crate::settings::{
+    ChannelMode, RendererKind, Settings, UiSettings, CONFIG_PATH, DEFAULT_CONFIG,
+};
+use crate::shutdown::Shutdown;
+pub use crate::smoothing_state::SmoothingState;
+pub use crate::spectrum_emitter::SpectrumEmitter;
+#[cfg(feature = "http")]
+pub use crate::spectrum_server::SpectrumPublisher;
+use crate::text::{draw_text, measure, TextAlign, TextStyle};
+use crate::visualizer::{Visualizer, VisualizerConstructor, VisualizerRegistry};
+use gtk::gio;
 use gtk::prelude::*;
 use gtk::{gdk, Application, ApplicationWindow, CssProvider, DrawingArea};
 use gtk4 as gtk;
-use rustfft::FftPlanner;
-use std::fs;
+use std::cell::{Cell, RefCell};
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::watch;
 
+/// Prints a status message like `println!`, but to standard error while standard output carries
+/// the lines of `--emit-spectrum`.
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if $crate::STATUS_TO_STDERR.load(std::sync::atomic::Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
 mod audio;
+mod audio_status;
+mod background;
+mod background_pulse;
+mod bar_batch;
+mod chromagram_visualizer;
+mod cli;
+mod color;
+mod difference_visualizer;
+pub mod dsp;
+mod error_window;
 mod fft_utils;
+mod fifo_source;
+mod file_utils;
+mod format;
+mod frame_stats;
 mod frequency_holographic_glow_visualizer;
+mod frequency_mapper;
 mod frequency_range_visualizer;
+#[cfg(feature = "gl")]
+mod gl_renderer;
 mod grid;
-mod settings;
-mod visualizer;
+mod hop_scheduler;
+mod hover_readout;
+#[cfg(feature = "jack")]
+mod jack_source;
+mod keys;
+mod latency;
+mod legend;
+mod level_scale;
+mod line_spectrum_visualizer;
+mod loudness_overlay;
+#[cfg(feature = "mpris")]
+mod mpris;
+mod noise_profile;
+mod note_readout;
+mod now_playing;
+mod octave_band_visualizer;
+mod offline;
+mod osc_output;
+mod power_state;
+mod preferences;
+mod radial_visualizer;
+mod recorder;
+mod redraw_timer;
+mod reflection;
+mod renderer;
+mod screenshot;
+mod session_state;
+pub mod settings;
+mod shutdown;
+mod smoothing_state;
+mod spectrum_curves;
+mod spectrum_emitter;
+#[cfg(feature = "http")]
+mod spectrum_server;
+mod text;
+mod trails;
+mod triggers;
+mod triple_buffer;
+#[cfg(feature = "battery")]
+mod upower;
+pub mod visualizer;
+mod wav;
+mod waveform;
+mod waveform_spectrum_visualizer;
 
 const APP_ID: &str = "com.sonic_spectra";
 
+/// Interval between redraws of the visualizer.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(30);
+
+/// How long status messages such as "Saved to ..." stay on screen.
+const STATUS_MESSAGE_DURATION: Duration = Duration::from_secs(2);
+
+/// Time the audio sources and worker threads get to stop at exit, and then the asynchronous
+/// tasks.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Beat callbacks registered through `on_beat` before the visualizer starts.
+static BEAT_CALLBACKS: Mutex<Vec<BeatCallback>> = Mutex::new(Vec::new());
+
+/// Set while standard output carries the lines of `--emit-spectrum`, see `status!`.
+static STATUS_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Visualizers registered through `register_visualizer` before the visualizer starts, shared by
+/// the registries of all windows.
+static VISUALIZER_REGISTRATIONS: Mutex<Vec<(String, Arc<VisualizerConstructor>)>> =
+    Mutex::new(Vec::new());
+
+/// On-disk UI definition; overrides the embedded copy when present.
+const UI_PATH: &str = "resources/ui/main.ui";
+/// On-disk stylesheet; overrides the embedded copy when present.
+const CSS_PATH: &str = "resources/style.css";
+
+/// Built-in UI definition used when `UI_PATH` cannot be read.
+const EMBEDDED_UI: &str = include_str!("../resources/ui/main.ui");
+/// Built-in stylesheet used when `CSS_PATH` cannot be read.
+const EMBEDDED_CSS: &str = include_str!("../resources/style.css");
+/// Stylesheet removing the backgrounds and borders of the window for `window.transparent`.
+const TRANSPARENT_CSS: &str =
+    "window, drawing_area, #main_box { background: none; border: none; box-shadow: none; }";
+
+/// Flags set by the keyboard handler and read by the draw function.
+///
+/// # Fields
+/// - `show_note_readout`: Whether the dominant frequency and note readout is drawn.
+/// - `show_loudness`: Whether the loudness and true-peak meter is drawn.
+/// - `clear_clip`: Set to clear the clip indicator of the true-peak meter on the next frame.
+/// - `clear_max_hold`: Set to reset the max-hold curve on the next frame.
+/// - `calibrate`: Set to start a noise floor calibration on the next frame.
+/// - `clear_noise_profile`: Set to discard the noise profile on the next frame.
+/// - `next_visualizer`: Set to switch to the next registered visualizer on the next frame.
+/// - `scene`: Index of the scene to apply on the next frame, if one was selected.
+/// - `screenshot`: Set to save the next frame as a PNG file.
+/// - `show_grid`: Whether the frequency grid is drawn.
+/// - `show_grid_horizontal`: Whether the horizontal grid lines are drawn.
+/// - `show_grid_vertical`: Whether the vertical frequency markers are drawn.
+/// - `reset_zoom`: Set to restore the configured frequency range on the next frame.
+/// - `clear_solo_band`: Set to stop soloing a frequency band on the next frame.
+/// - `show_stats`: Whether the frame rate and timing overlay is drawn.
+/// - `mid_side`: Whether the mid and side signals are shown instead of left and right.
+/// - `show_audio_status`: Whether the input device and activity status line is drawn.
+/// - `settings`: The settings as last changed in the preferences window.
+/// - `settings_changed`: Set to apply `settings` on the next frame.
+/// - `redraw`: Set after every change above, so the next frame is drawn even if the spectrum
+///   did not change.
+/// - `session`: The state saved at exit, taken over from the renderer every frame.
+/// - `spectrum_publisher`: Receives the analyzed frames for the spectrum server, when
+///   `output.http_port` is set.
+#[derive(Clone)]
+struct Controls {
+    show_note_readout: Arc<AtomicBool>,
+    show_loudness: Arc<AtomicBool>,
+    clear_clip: Arc<AtomicBool>,
+    clear_max_hold: Arc<AtomicBool>,
+    calibrate: Arc<AtomicBool>,
+    clear_noise_profile: Arc<AtomicBool>,
+    next_visualizer: Arc<AtomicBool>,
+    scene: Arc<Mutex<Option<usize>>>,
+    screenshot: Arc<AtomicBool>,
+    show_grid: Arc<AtomicBool>,
+    show_grid_horizontal: Arc<AtomicBool>,
+    show_grid_vertical: Arc<AtomicBool>,
+    reset_zoom: Arc<AtomicBool>,
+    clear_solo_band: Arc<AtomicBool>,
+    show_stats: Arc<AtomicBool>,
+    mid_side: Arc<AtomicBool>,
+    show_audio_status: Arc<AtomicBool>,
+    settings: Arc<Mutex<Settings>>,
+    settings_changed: Arc<AtomicBool>,
+    redraw: Arc<AtomicBool>,
+    session: Arc<Mutex<SessionState>>,
+    #[cfg(feature = "http")]
+    spectrum_publisher: Option<SpectrumPublisher>,
+}
+
+impl Controls {
+    fn new(settings: &Settings) -> Self {
+        Controls {
+            show_note_readout: Arc::new(AtomicBool::new(settings.note_readout.enabled)),
+            show_loudness: Arc::new(AtomicBool::new(settings.loudness.enabled)),
+            clear_clip: Arc::new(AtomicBool::new(false)),
+            clear_max_hold: Arc::new(AtomicBool::new(false)),
+            calibrate: Arc::new(AtomicBool::new(settings.calibration.calibrate_on_start)),
+            clear_noise_profile: Arc::new(AtomicBool::new(false)),
+            next_visualizer: Arc::new(AtomicBool::new(false)),
+            scene: Arc::new(Mutex::new(None)),
+            screenshot: Arc::new(AtomicBool::new(false)),
+            show_grid: Arc::new(AtomicBool::new(settings.grid.enabled)),
+            show_grid_horizontal: Arc::new(AtomicBool::new(settings.grid.show_horizontal)),
+            show_grid_vertical: Arc::new(AtomicBool::new(settings.grid.show_vertical)),
+            reset_zoom: Arc::new(AtomicBool::new(false)),
+            clear_solo_band: Arc::new(AtomicBool::new(false)),
+            show_stats: Arc::new(AtomicBool::new(settings.debug.show_stats)),
+            mid_side: Arc::new(AtomicBool::new(
+                settings.fft.channel_mode == ChannelMode::Ms,
+            )),
+            show_audio_status: Arc::new(AtomicBool::new(settings.ui.audio_status)),
+            settings: Arc::new(Mutex::new(settings.clone())),
+            settings_changed: Arc::new(AtomicBool::new(false)),
+            redraw: Arc::new(AtomicBool::new(false)),
+            session: Arc::new(Mutex::new(SessionState::default())),
+            #[cfg(feature = "http")]
+            spectrum_publisher: None,
+        }
+    }
+
+    /// Returns the channel mode selected by the keyboard.
+    fn channel_mode(&self) -> ChannelMode {
+        if self.mid_side.load(Ordering::Relaxed) {
+            ChannelMode::Ms
+        } else {
+            ChannelMode::Lr
+        }
+    }
+
+    /// Returns the grid visibility selected by the keyboard.
+    fn grid_visibility(&self) -> GridVisibility {
+        GridVisibility {
+            enabled: self.show_grid.load(Ordering::Relaxed),
+            horizontal: self.show_grid_horizontal.load(Ordering::Relaxed),
+            vertical: self.show_grid_vertical.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A visualizer for embedding in an existing GTK application.
+///
+/// The host application keeps its `Application` and windows; the widget wires up the analysis,
+/// the redraw timer and the visualizer for a drawing area the host places like any other widget.
+/// `run_application` shows the same visualizer in a window of its own.
+///
+/// # Threads
+/// Widgets must be created on the GTK main thread once GTK is initialized, e.g. in the `activate`
+/// handler of the host application. `AudioSource::start` runs on that thread as well, while the
+/// samples may be pushed from any thread: pushing never waits for the main thread, and the main
+/// thread never waits for the audio.
+///
+/// Keyboard shortcuts, recording, the now playing overlay and the `gl` renderer are only
+/// available in the window of `run_application`.
+pub struct SpectrumWidget;
+
+impl SpectrumWidget {
+    /// Creates a drawing area visualizing the input configured in `[audio]`.
+    ///
+    /// # Arguments
+    /// - `settings`: Shared application settings, e.g. from `Settings::new`.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(settings: Arc<Settings>) -> DrawingArea {
+        let recorder = Arc::new(Recorder::new(settings.fft.sample_rate as u32));
+        Self::with_source(settings.clone(), CaptureSource::new(settings, recorder))
+    }
+
+    /// Creates a drawing area visualizing the audio delivered by `source`.
+    ///
+    /// # Arguments
+    /// - `settings`: Shared application settings; `fft.sample_rate` should match the source.
+    /// - `source`: The source of the visualized audio, started immediately and shut down when
+    ///   the drawing area is destroyed.
+    pub fn with_source<S: AudioSource>(settings: Arc<Settings>, source: S) -> DrawingArea {
+        let (sink, audio_reader) = audio::audio_channel(&settings.fft);
+        let shutdown = Arc::new(AtomicBool::new(false));
+        start_audio_source(Box::new(source), sink, shutdown.clone());
+
+        let drawing_area = DrawingArea::new();
+        drawing_area.connect_destroy(move |_| shutdown.store(true, Ordering::Relaxed));
+        drawing_area.set_hexpand(true);
+        drawing_area.set_vexpand(true);
+        let recorder = Arc::new(Recorder::new(settings.fft.sample_rate as u32));
+        let redraw_timer = RedrawTimer::new(&drawing_area, REDRAW_INTERVAL, &settings.power);
+        initialize_visualizer(
+            &drawing_area,
+            Rc::new(RefCell::new(audio_reader)),
+            settings.clone(),
+            Controls::new(&settings),
+            recorder,
+            None,
+            None,
+            redraw_timer.clone(),
+        );
+        redraw_timer.start();
+        drawing_area
+    }
+}
+
 /// Run the main application loop with the visualizer setup.
 ///
 /// # Returns
 /// - `Result` with no value if the program runs successfully, or an error if initialization fails.
 pub fn run_application() -> Result<(), Box<dyn std::error::Error>> {
-    let _rt = tokio::runtime::Builder::new_multi_thread()
+    let options = CliOptions::parse(std::env::args().skip(1))?;
+    let headless = options.emit.as_ref().is_some_and(|emit| emit.headless);
+    let (mut settings, config_source) = match load_settings(options.force) {
+        Ok(loaded) => loaded,
+        // Also shown in a window, as standard error is not seen when started from a desktop file
+        Err(e) if options.render.is_none() && !headless => {
+            error_window::run(APP_ID, vec![e.to_string()]);
+            return Err(e);
+        }
+        Err(e) => return Err(e),
+    };
+    if let Some(render) = &options.render {
+        return offline::render(
+            render,
+            Arc::new(settings),
+            visualizer_registry(),
+            take_beat_callbacks(),
+        );
+    }
+    if let Some(emit) = &options.emit {
+        STATUS_TO_STDERR.store(true, Ordering::Relaxed);
+        if emit.headless {
+            return run_headless(Arc::new(settings), emit.format, options.record.clone());
+        }
+    }
+    let state_path = SessionState::default_path();
+    let session = Arc::new(Mutex::new(restore_session(
+        &mut settings,
+        state_path.as_deref(),
+        options.fresh,
+    )));
+    let settings = Arc::new(settings);
+    let key_map = KeyMap::new(&settings.keys);
+
+    // Runs the asynchronous tasks: the now playing watcher and the spectrum server
+    let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()?;
 
-    let settings = Arc::new(Settings::new());
     let application = Application::builder().application_id(APP_ID).build();
-    let (tx, rx) = watch::channel(());
 
-    let audio_data = Arc::new(Mutex::new(audio::AudioData::new(settings.fft.size)));
-    audio::start_audio_stream(audio_data.clone(), settings.clone());
+    let recorder = Arc::new(Recorder::new(settings.fft.sample_rate as u32));
+    let (mut sink, audio_reader) = audio::audio_channel(&settings.fft);
+    let shutdown = Rc::new(RefCell::new(Shutdown::new()));
+    shutdown.borrow_mut().watch_sink(audio_reader.sink_watch());
+    if let Some(emit) = &options.emit {
+        let (emit_sink, emit_reader) = audio::audio_channel(&settings.fft);
+        sink = sink.with_mirror(emit_sink);
+        let (settings, format) = (settings.clone(), emit.format);
+        let mut shutdown = shutdown.borrow_mut();
+        let flag = shutdown.flag();
+        shutdown.spawn("spectrum emitter", move || {
+            spectrum_emitter::emit_to_stdout(settings, format, emit_reader, &flag)
+        })?;
+    }
+    let flag = shutdown.borrow().flag();
+    start_audio_source(
+        Box::new(CaptureSource::new(settings.clone(), recorder.clone())),
+        sink,
+        flag,
+    );
+    let audio_reader = Rc::new(RefCell::new(audio_reader));
+    let track_info = now_playing::start(&settings, runtime.handle());
+    let power_state = power_state::start(&settings, runtime.handle());
+    #[cfg(feature = "http")]
+    let spectrum_publisher = SpectrumPublisher::start(settings.clone(), runtime.handle());
+    #[cfg(not(feature = "http"))]
+    if settings.output.http_port.is_some() {
+        eprintln!("http_port is set, but sonic_spectra was built without the http feature.");
+    }
 
+    let shared = SharedWindowState {
+        audio_reader,
+        recorder: recorder.clone(),
+        track_info,
+        power_state,
+    };
+    let shutdown_clone = shutdown.clone();
+    application.connect_startup(move |app| {
+        setup_app_actions(app, shutdown_clone.clone(), &key_map, config_source.clone())
+    });
+    let session_clone = session.clone();
     application.connect_activate(move |app| {
-        if let Ok((window, drawing_area)) = load_ui(app) {
-            if let Ok(css_provider) = load_css() {
-                setup_css(&css_provider);
-                initialize_visualizer(
-                    &drawing_area,
-                    audio_data.clone(),
-                    settings.clone(),
-                    tx.clone(),
-                );
-                setup_window_controls(&window, tx.clone());
-                window.present();
-                schedule_redraw(&drawing_area);
-            } else {
-                eprintln!("Error loading CSS.");
+        let entries = settings.window_entries();
+        // Everything is loaded before any error is reported, so the error window lists every error
+        let uis: Vec<_> = entries.iter().map(|_| load_ui(app)).collect();
+        let css = load_css();
+        let mut errors: Vec<String> = uis
+            .iter()
+            .filter_map(|ui| ui.as_ref().err().cloned())
+            .chain(css.as_ref().err().cloned())
+            .collect();
+        let css_provider = match css {
+            Ok(css_provider) if errors.is_empty() => css_provider,
+            _ => {
+                // A main window left open would keep the application running
+                for (window, _) in uis.iter().flatten() {
+                    window.close();
+                }
+                // Every window reads the same UI definition, and fails the same way
+                errors.dedup();
+                for error in &errors {
+                    eprintln!("{}", error);
+                }
+                error_window::show(app, &errors);
+                return;
             }
-        } else {
-            eprintln!("Error loading UI.");
+        };
+        setup_css(&css_provider);
+
+        // Start here rather than earlier so the audio device has reported its sample rate
+        if let Some(path) = &options.record {
+            start_recording(&shared.recorder, path.clone());
+        }
+        let windows = uis.into_iter().flatten().zip(&entries);
+        for (i, ((window, drawing_area), entry)) in windows.enumerate() {
+            let mut window_settings = settings.for_window(entry);
+            // Only the first window saves its state, publishes its frames and runs the triggers
+            if i > 0 {
+                window_settings.triggers.clear();
+            }
+            let controls = if i == 0 {
+                Controls {
+                    session: session_clone.clone(),
+                    #[cfg(feature = "http")]
+                    spectrum_publisher: spectrum_publisher.clone(),
+                    ..Controls::new(&window_settings)
+                }
+            } else {
+                Controls::new(&window_settings)
+            };
+            setup_window(
+                &window,
+                &drawing_area,
+                Arc::new(window_settings),
+                controls,
+                &shared,
+                entry.monitor,
+            );
         }
     });
 
-    handle_exit(rx.clone());
+    // Command-line options are handled above, so GTK only receives the program name
+    let program = std::env::args().next().unwrap_or_default();
+    application.run_with_args(&[program]);
 
-    application.run();
+    // Quitting stopped the audio and the workers already, closing the last window did not
+    stop_workers(&mut shutdown.borrow_mut());
+    stop_recording(&recorder);
+    save_session(&session, state_path.as_deref());
+    runtime.shutdown_timeout(SHUTDOWN_TIMEOUT);
+    status!("Exiting the program...");
 
     Ok(())
 }
 
-/// Load the UI components from the specified resource file.
-fn load_ui(
-    application: &Application,
-) -> Result<(ApplicationWindow, DrawingArea), Box<dyn std::error::Error>> {
-    let builder = gtk::Builder::from_file("resources/ui/main.ui");
+/// What the windows of `run_application` share.
+///
+/// # Fields
+/// - `audio_reader`: The captured audio, analyzed by the renderer of every window.
+/// - `recorder`: Records the captured audio, started and stopped from any window.
+/// - `track_info`: The playing track, when `now_playing` is enabled.
+/// - `power_state`: Whether the machine runs on battery, when `power.adaptive` is enabled.
+struct SharedWindowState {
+    audio_reader: Rc<RefCell<AudioReader>>,
+    recorder: Arc<Recorder>,
+    track_info: Option<watch::Receiver<Option<TrackInfo>>>,
+    power_state: Option<watch::Receiver<PowerState>>,
+}
+
+/// Set up one window of `run_application` with a visualizer of its own, and show it.
+///
+/// # Arguments
+/// - `window`: The window, as loaded by `load_ui`.
+/// - `drawing_area`: The drawing area of the window.
+/// - `settings`: The settings of the window, from `Settings::for_window`.
+/// - `controls`: The flags set by the keyboard in this window.
+/// - `shared`: What all windows share.
+/// - `monitor`: Index of the monitor the window is shown fullscreen on, if any.
+///
+/// The window belongs to the application, which keeps running until its last window is closed.
+fn setup_window(
+    window: &ApplicationWindow,
+    drawing_area: &DrawingArea,
+    settings: Arc<Settings>,
+    controls: Controls,
+    shared: &SharedWindowState,
+    monitor: Option<u32>,
+) {
+    let redraw_timer = RedrawTimer::new(drawing_area, REDRAW_INTERVAL, &settings.power);
+    initialize_visualizer(
+        drawing_area,
+        shared.audio_reader.clone(),
+        settings.clone(),
+        controls.clone(),
+        shared.recorder.clone(),
+        shared.track_info.clone(),
+        shared.power_state.clone(),
+        redraw_timer.clone(),
+    );
+    setup_window_actions(window, controls, shared.recorder.clone());
+    if settings.window.transparent {
+        setup_transparency(window, settings.window.click_through);
+    }
+    window.set_show_menubar(settings.window.menu_bar && !settings.window.transparent);
+    if let Some(index) = monitor {
+        let monitor = window
+            .display()
+            .monitors()
+            .item(index)
+            .and_downcast::<gdk::Monitor>();
+        match monitor {
+            Some(monitor) => window.fullscreen_on_monitor(&monitor),
+            None => eprintln!(
+                "Monitor {} not found; opening the window on the default monitor.",
+                index
+            ),
+        }
+    }
+
+    window.present();
+    redraw_timer.start();
+}
+
+/// Write the spectrum of the configured input to standard output without opening the window,
+/// until standard output is closed.
+///
+/// # Arguments
+/// - `settings`: The loaded settings.
+/// - `format`: Format of the written lines.
+/// - `record`: File the captured audio is recorded to, if requested.
+fn run_headless(
+    settings: Arc<Settings>,
+    format: EmitFormat,
+    record: Option<std::path::PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let recorder = Arc::new(Recorder::new(settings.fft.sample_rate as u32));
+    let (sink, audio_reader) = audio::audio_channel(&settings.fft);
+    let shutdown = Arc::new(AtomicBool::new(false));
+    start_audio_source(
+        Box::new(CaptureSource::new(settings.clone(), recorder.clone())),
+        sink,
+        shutdown.clone(),
+    );
+    if let Some(path) = record {
+        start_recording(&recorder, path);
+    }
+
+    spectrum_emitter::emit_to_stdout(settings, format, audio_reader, &shutdown);
+    shutdown.store(true, Ordering::Relaxed);
+    stop_recording(&recorder);
+    Ok(())
+}
+
+/// Load the state saved by the previous run and apply it over the settings.
+///
+/// # Arguments
+/// - `settings`: The settings loaded from the configuration.
+/// - `path`: The state file, if the state directory is known.
+/// - `fresh`: Whether the saved state is ignored.
+///
+/// # Returns
+/// - The restored state, or an empty state if there is none, `fresh` is set, or it would make
+///   the settings invalid.
+fn restore_session(settings: &mut Settings, path: Option<&Path>, fresh: bool) -> SessionState {
+    let Some(path) = path.filter(|_| !fresh) else {
+        return SessionState::default();
+    };
+    let state = SessionState::load(path);
+    let mut restored = settings.clone();
+    state.apply(&mut restored);
+    if let Err(errors) = restored.validate() {
+        eprintln!(
+            "Ignoring the saved state in {}: {} invalid values",
+            path.display(),
+            errors.len()
+        );
+        return SessionState::default();
+    }
+    *settings = restored;
+    state
+}
+
+/// Save the state for the next run, reporting a failure.
+fn save_session(session: &Mutex<SessionState>, path: Option<&Path>) {
+    if let Some(path) = path {
+        if let Err(e) = session.lock().unwrap().save(path) {
+            eprintln!("Failed to save the state to {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Start an audio source, falling back to silence if it fails.
+///
+/// # Arguments
+/// - `source`: The source to start.
+/// - `sink`: Receives the audio of the source.
+/// - `shutdown`: Set once the visualizer goes away.
+fn start_audio_source(source: Box<dyn AudioSource>, sink: AudioSink, shutdown: Arc<AtomicBool>) {
+    if let Err(e) = source.start(sink, shutdown) {
+        eprintln!("Failed to start the audio source, showing silence: {}", e);
+    }
+}
+
+/// Load the settings and check them.
+///
+/// # Arguments
+/// - `force`: Whether invalid values are clamped to the nearest valid value instead of failing.
+///
+/// # Returns
+/// - The settings and where they were read from, or an error naming the configuration that was
+///   read if it cannot be parsed, or listing every invalid value if they are invalid and `force`
+///   is not set.
+fn load_settings(force: bool) -> Result<(Settings, String), Box<dyn std::error::Error>> {
+    let (config, source) = read_resource(CONFIG_PATH, DEFAULT_CONFIG, "configuration");
+    let mut settings =
+        Settings::from_config(&config).map_err(|e| format!("Failed to load {}: {}", source, e))?;
+    #[cfg(feature = "jack")]
+    jack_source::use_server_sample_rate(&mut settings);
+    if let Err(errors) = settings.validate() {
+        if !force {
+            let invalid: Vec<String> = errors
+                .iter()
+                .map(|error| format!("Invalid setting {}", error))
+                .collect();
+            return Err(format!(
+                "{} invalid settings in {}:\n{}\nFix the configuration or start with --force to clamp them.",
+                errors.len(),
+                source,
+                invalid.join("\n")
+            )
+            .into());
+        }
+        for error in &errors {
+            eprintln!("Invalid setting {}", error);
+        }
+        settings.clamp_to_valid();
+    }
+    if let Some(budget) = settings.apply_latency_target() {
+        eprintln!(
+            "Latency target of {} ms: hop_size = {}, smoothing_ms = {:.1}",
+            settings.fft.latency_target_ms.unwrap_or_default(),
+            budget.hop_size,
+            budget.smoothing_ms
+        );
+    }
+    Ok((settings, source))
+}
+
+/// Register a callback invoked on the UI thread for every beat detected in the input.
+///
+/// # Arguments
+/// - `callback`: The function receiving each `dsp::BeatEvent`.
+///
+/// Callbacks must be registered before calling `run_application`.
+pub fn on_beat<F>(callback: F)
+where
+    F: Fn(dsp::BeatEvent) + Send + Sync + 'static,
+{
+    BEAT_CALLBACKS.lock().unwrap().push(Box::new(callback));
+}
+
+/// Register a visualizer selectable through `visualizer.kind` and the `V` key.
+///
+/// # Arguments
+/// - `name`: The name of the visualizer; registering a built-in name replaces the built-in.
+/// - `constructor`: Function creating the visualizer from the application settings.
+///
+/// Visualizers must be registered before calling `run_application`.
+pub fn register_visualizer<F>(name: &str, constructor: F)
+where
+    F: Fn(Arc<Settings>) -> Box<dyn Visualizer> + Send + Sync + 'static,
+{
+    VISUALIZER_REGISTRATIONS
+        .lock()
+        .unwrap()
+        .push((name.to_string(), Arc::new(Box::new(constructor))));
+}
+
+/// Build a visualizer registry from the built-ins and the visualizers added through
+/// `register_visualizer`; every window gets a registry of its own.
+fn visualizer_registry() -> VisualizerRegistry {
+    let mut registry = VisualizerRegistry::new();
+    for (name, constructor) in VISUALIZER_REGISTRATIONS.lock().unwrap().iter() {
+        let constructor = constructor.clone();
+        registry.register(name, move |settings| constructor(settings));
+    }
+    registry
+}
+
+/// Take the callbacks registered through `on_beat`, which only the first window then calls.
+fn take_beat_callbacks() -> Vec<BeatCallback> {
+    BEAT_CALLBACKS.lock().unwrap().drain(..).collect()
+}
+
+/// Load the UI components from the on-disk resource file, falling back to the embedded copy.
+///
+/// # Returns
+/// - The main window and its drawing area, or an error naming the UI definition that was read.
+fn load_ui(application: &Application) -> Result<(ApplicationWindow, DrawingArea), String> {
+    let (ui_data, source) = read_resource(UI_PATH, EMBEDDED_UI, "UI definition");
+    let builder = gtk::Builder::new();
+    builder
+        .add_from_string(&ui_data)
+        .map_err(|e| format!("Failed to load the UI from {}: {}", source, e))?;
     let window: ApplicationWindow = builder
         .object("main_window")
-        .ok_or("Failed to find main_window in UI file.")?;
+        .ok_or_else(|| format!("Failed to find main_window in the UI from {}.", source))?;
     let drawing_area: DrawingArea = builder
         .object("drawing_area")
-        .ok_or("Failed to find drawing_area in UI file.")?;
+        .ok_or_else(|| format!("Failed to find drawing_area in the UI from {}.", source))?;
 
     window.set_application(Some(application));
     Ok((window, drawing_area))
 }
 
-/// Load CSS styling for the application.
-fn load_css() -> Result<CssProvider, Box<dyn std::error::Error>> {
+/// Load CSS styling for the application, falling back to the embedded stylesheet.
+///
+/// # Returns
+/// - The stylesheet, or an error naming the stylesheet that was read and listing its parsing
+///   errors.
+fn load_css() -> Result<CssProvider, String> {
     let css_provider = CssProvider::new();
-    let css_data = fs::read_to_string("resources/style.css")?;
+    let (css_data, source) = read_resource(CSS_PATH, EMBEDDED_CSS, "stylesheet");
+    let parsing_errors = Rc::new(RefCell::new(Vec::new()));
+    let parsing_errors_clone = parsing_errors.clone();
+    css_provider.connect_parsing_error(move |_, section, error| {
+        parsing_errors_clone
+            .borrow_mut()
+            .push(format!("{}: {}", section.to_str(), error));
+    });
     std::panic::catch_unwind(|| {
         css_provider.load_from_data(&css_data);
     })
-    .map_err(|_| Box::<dyn std::error::Error>::from("Failed to load CSS due to panic"))?;
+    .map_err(|_| format!("Failed to load the stylesheet from {} due to panic", source))?;
+
+    let parsing_errors = parsing_errors.take();
+    if !parsing_errors.is_empty() {
+        return Err(format!(
+            "Failed to load the stylesheet from {}:\n{}",
+            source,
+            parsing_errors.join("\n")
+        ));
+    }
     Ok(css_provider)
 }
 
@@ -101,106 +784,686 @@ fn setup_css(css_provider: &CssProvider) {
     );
 }
 
+/// Makes the window borderless and transparent apart from what is drawn.
+///
+/// # Arguments
+/// - `window`: The application window.
+/// - `click_through`: Whether clicks pass through the window to the windows below it.
+///
+/// Without a compositing display the window stays opaque and a warning is printed.
+fn setup_transparency(window: &ApplicationWindow, click_through: bool) {
+    let display = window.display();
+    if !transparency_supported(&display) {
+        eprintln!(
+            "window.transparent is set, but the display does not support transparent windows; keeping the window opaque."
+        );
+        return;
+    }
+
+    window.set_decorated(false);
+    let css_provider = CssProvider::new();
+    css_provider.load_from_data(TRANSPARENT_CSS);
+    gtk::style_context_add_provider_for_display(
+        &display,
+        &css_provider,
+        gtk::STYLE_PROVIDER_PRIORITY_USER,
+    );
+
+    if click_through {
+        // An empty input region sends all pointer input to the windows below
+        window.connect_realize(|window| match window.surface() {
+            Some(surface) => surface.set_input_region(&gtk::cairo::Region::create()),
+            None => eprintln!("Failed to make the window click-through."),
+        });
+    }
+}
+
+/// Returns whether `display` composites windows with an alpha channel.
+fn transparency_supported(display: &gdk::Display) -> bool {
+    display.is_composited() && display.is_rgba()
+}
+
 /// Initialize and configure the visualizer for drawing.
 fn initialize_visualizer(
     drawing_area: &DrawingArea,
-    audio_data: Arc<Mutex<audio::AudioData>>,
+    audio_reader: Rc<RefCell<audio::AudioReader>>,
     settings: Arc<Settings>,
-    tx: watch::Sender<()>,
+    controls: Controls,
+    recorder: Arc<Recorder>,
+    track_info: Option<watch::Receiver<Option<TrackInfo>>>,
+    power_state: Option<watch::Receiver<PowerState>>,
+    redraw_timer: Rc<RedrawTimer>,
 ) {
-    let planner = Arc::new(Mutex::new(FftPlanner::new()));
-    let visualizer_type = "frequency";
+    let renderer = Rc::new(RefCell::new(FrameRenderer::new(
+        settings.clone(),
+        visualizer_registry(),
+        take_beat_callbacks(),
+        REDRAW_INTERVAL,
+    )));
+    if let Some(zoom) = controls.session.lock().unwrap().zoom() {
+        renderer.borrow_mut().zoom_to(zoom);
+    }
+    #[cfg(feature = "http")]
+    if let Some(spectrum_publisher) = controls.spectrum_publisher.clone() {
+        renderer
+            .borrow_mut()
+            .set_spectrum_publisher(spectrum_publisher);
+    }
+    let now_playing = track_info
+        .map(|track_info| RefCell::new(NowPlayingOverlay::new(settings.clone(), track_info)));
+    // A transparent window shows the desktop instead of the configured background
+    let transparent =
+        settings.window.transparent && transparency_supported(&drawing_area.display());
+    if transparent {
+        renderer.borrow_mut().disable_background();
+    }
+    let status_message: RefCell<Option<(String, Instant)>> = RefCell::new(None);
+    let frame_stats = RefCell::new(FrameStats::new());
+    let start = Instant::now();
 
-    let visualizer: Box<dyn visualizer::Visualizer> = match visualizer_type {
-        "frequency" => Box::new(FrequencyRangeVisualizer::new(settings.clone())),
-        "holographic_glow" => Box::new(HolographicGlowVisualizer::new(settings.clone())),
-        _ => Box::new(FrequencyRangeVisualizer::new(settings.clone())),
+    #[cfg(feature = "gl")]
+    let gl_bars = if settings.visualizer.renderer == RendererKind::Gl {
+        GlBars::attach(drawing_area)
+    } else {
+        None
     };
+    #[cfg(not(feature = "gl"))]
+    if settings.visualizer.renderer == RendererKind::Gl {
+        eprintln!(
+            "renderer is \"gl\", but sonic_spectra was built without the gl feature; drawing with Cairo."
+        );
+    }
 
-    let num_bars = settings.fft.size / 2;
-    let mut previous_heights_left = vec![0.0; num_bars];
-    let mut previous_heights_right = vec![0.0; num_bars];
-    let grid = Arc::new(grid::FrequencyGrid::new(settings.clone()));
+    let pointer = Rc::new(Cell::new(None));
+    if settings.ui.hover_readout {
+        track_pointer(drawing_area, pointer.clone());
+    }
+    let selection = Rc::new(Cell::new(None));
+    let zoom_request = Rc::new(Cell::new(None));
+    let solo_request = Rc::new(Cell::new(None));
+    track_zoom_selection(
+        drawing_area,
+        selection.clone(),
+        zoom_request.clone(),
+        solo_request.clone(),
+        controls.reset_zoom.clone(),
+    );
+
+    // Adapt the bars and caches to the new size as soon as it is allocated; a resize emitted
+    // while a frame is drawn is left to that frame, which applies its own size
+    let renderer_clone = renderer.clone();
+    drawing_area.connect_resize(move |_, width, height| {
+        if let Ok(mut renderer) = renderer_clone.try_borrow_mut() {
+            renderer.resize(f64::from(width), f64::from(height));
+        }
+    });
+
+    // Skip the redraws that would draw the same frame again: no new window of audio completed,
+    // nothing is animating and no control changed
+    let animating = Rc::new(Cell::new(true));
+    {
+        let (renderer, audio_reader, controls) =
+            (renderer.clone(), audio_reader.clone(), controls.clone());
+        let (animating, pointer, selection) =
+            (animating.clone(), pointer.clone(), selection.clone());
+        let (zoom_request, solo_request) = (zoom_request.clone(), solo_request.clone());
+        redraw_timer.set_frame_check(move || {
+            animating.get()
+                || controls.redraw.swap(false, Ordering::Relaxed)
+                || pointer.get().is_some()
+                || selection.get().is_some()
+                || zoom_request.get().is_some()
+                || solo_request.get().is_some()
+                || controls.reset_zoom.load(Ordering::Relaxed)
+                || renderer
+                    .borrow()
+                    .has_new_window(audio_reader.borrow_mut().read().received_frames)
+        });
+    }
 
     let drawing_area_clone = drawing_area.clone();
-    let audio_data_clone = audio_data.clone();
-    let planner_clone = planner.clone();
+    let last_received_frames = Cell::new(0);
     let settings_clone = settings.clone();
-    let grid_clone = grid.clone();
+    let power_state = RefCell::new(power_state);
 
-    drawing_area.set_draw_func(move |_widget, cr, _, _| {
-        let width = drawing_area_clone.width() as f64;
-        let height = drawing_area_clone.height() as f64;
+    drawing_area.set_draw_func(move |_widget, cr, width, height| {
+        // The size of this frame, rather than one read from the widget that may be newer
+        let (width, height) = (f64::from(width), f64::from(height));
+        let frame_start = Instant::now();
+        let mut frame_stats = frame_stats.borrow_mut();
+        frame_stats.start_frame(frame_start);
+        frame_stats.record_skipped(redraw_timer.take_skipped());
+        let mut renderer = renderer.borrow_mut();
+        renderer.set_scale_factor(drawing_area_clone.scale_factor() as f64);
+        if transparent {
+            let _ = cr.save();
+            cr.set_operator(gtk::cairo::Operator::Clear);
+            let _ = cr.paint();
+            let _ = cr.restore();
+        }
 
-        let audio = audio_data_clone.lock().unwrap();
-        let input_left: Vec<f32> = audio.left_buffer.clone();
-        let input_right: Vec<f32> = audio.right_buffer.clone();
+        renderer.set_show_note_readout(controls.show_note_readout.load(Ordering::Relaxed));
+        renderer.set_show_loudness(controls.show_loudness.load(Ordering::Relaxed));
+        let fullscreen = drawing_area_clone
+            .root()
+            .and_downcast::<gtk::Window>()
+            .is_some_and(|window| window.is_fullscreen());
+        renderer.set_fullscreen(fullscreen, frame_start);
+        if controls.clear_clip.swap(false, Ordering::Relaxed) {
+            renderer.clear_clip();
+        }
+        if controls.clear_max_hold.swap(false, Ordering::Relaxed) {
+            renderer.clear_max_hold();
+        }
+        if controls.calibrate.swap(false, Ordering::Relaxed) {
+            renderer.start_calibration();
+        }
+        if controls.clear_noise_profile.swap(false, Ordering::Relaxed) {
+            renderer.clear_noise_profile();
+        }
+        if controls.next_visualizer.swap(false, Ordering::Relaxed) {
+            renderer.next_visualizer();
+        }
+        // On battery the frame rate drops and the expensive effects are turned off until the
+        // machine is plugged in again; a new power state keeps the zoom
+        let (power_changed, power) = match power_state.borrow_mut().as_mut() {
+            Some(receiver) => (
+                receiver.has_changed().unwrap_or(false),
+                *receiver.borrow_and_update(),
+            ),
+            None => (false, PowerState::LinePower),
+        };
+        let settings_changed = controls.settings_changed.swap(false, Ordering::Relaxed);
+        if power_changed || settings_changed {
+            redraw_timer.set_on_battery(power == PowerState::Battery);
+            let zoom = renderer.zoomed_range().filter(|_| !settings_changed);
+            renderer.apply_settings(power.adapt(&controls.settings.lock().unwrap()));
+            if let Some(zoom) = zoom {
+                renderer.zoom_to(zoom);
+            }
+        }
+        let scene = controls.scene.lock().unwrap().take();
+        if let Some(name) = scene.and_then(|index| renderer.apply_scene(index)) {
+            *status_message.borrow_mut() = Some((format!("scene: {}", name), Instant::now()));
+        }
+        if controls.reset_zoom.swap(false, Ordering::Relaxed) {
+            renderer.reset_zoom();
+            *status_message.borrow_mut() = Some(("zoom: reset".to_string(), Instant::now()));
+        }
+        if let Some((start_x, end_x)) = zoom_request.take() {
+            if let Some((low, high)) = renderer.zoom_to_selection(start_x, end_x, width) {
+                let decimal_comma = renderer.settings().ui.decimal_comma;
+                let message = format!(
+                    "zoom: {} – {}",
+                    format_frequency(low, decimal_comma),
+                    format_frequency(high, decimal_comma)
+                );
+                *status_message.borrow_mut() = Some((message, Instant::now()));
+            }
+        }
+        if controls.clear_solo_band.swap(false, Ordering::Relaxed) {
+            renderer.clear_solo_band();
+            *status_message.borrow_mut() = Some(("solo: cleared".to_string(), Instant::now()));
+        }
+        if let Some((start_x, end_x)) = solo_request.take() {
+            if let Some((low, high)) = renderer.solo_selection(start_x, end_x, width) {
+                let decimal_comma = renderer.settings().ui.decimal_comma;
+                let message = format!(
+                    "solo: {} – {}",
+                    format_frequency(low, decimal_comma),
+                    format_frequency(high, decimal_comma)
+                );
+                *status_message.borrow_mut() = Some((message, Instant::now()));
+            }
+        }
+        let channel_mode = controls.channel_mode();
+        if channel_mode != renderer.channel_mode() {
+            renderer.set_channel_mode(channel_mode);
+            let message = match channel_mode {
+                ChannelMode::Lr => "channels: left/right",
+                ChannelMode::Ms => "channels: mid/side",
+            };
+            *status_message.borrow_mut() = Some((message.to_string(), Instant::now()));
+        }
+        let grid_visibility = controls.grid_visibility();
+        if let Some(message) = grid_visibility.describe_change(&renderer.grid_visibility()) {
+            renderer.set_grid_visibility(grid_visibility);
+            *status_message.borrow_mut() = Some((message, Instant::now()));
+        }
+        controls.session.lock().unwrap().update(&renderer);
 
-        let mut input_left_clone: Vec<rustfft::num_complex::Complex32> = input_left
-            .iter()
-            .map(|&x| rustfft::num_complex::Complex32::new(x, 0.0))
-            .collect();
-        let mut input_right_clone: Vec<rustfft::num_complex::Complex32> = input_right
-            .iter()
-            .map(|&x| rustfft::num_complex::Complex32::new(x, 0.0))
-            .collect();
+        let spectrum = {
+            // The latest captured buffers, read without ever blocking the capture callback
+            let mut audio_reader = audio_reader.borrow_mut();
+            let audio = audio_reader.read();
+            let received_frames = audio
+                .received_frames
+                .wrapping_sub(last_received_frames.replace(audio.received_frames));
+            frame_stats.record_fill(received_frames, settings_clone.fft.size);
+            let spectrum = renderer.analyze_capture(
+                &audio.left_buffer,
+                &audio.right_buffer,
+                audio.received_frames,
+                start.elapsed(),
+            );
+            if let Some(latency) = latency::visual_latency(
+                Instant::now(),
+                audio.newest_frame_at,
+                renderer.window_center_offset(),
+            ) {
+                frame_stats.record_latency(latency);
+            }
+            spectrum
+        };
+        let analyzed_at = Instant::now();
+        frame_stats.record_analysis(analyzed_at - frame_start);
+        // Drop to the idle frame rate during silence, and back as soon as the audio returns
+        redraw_timer.set_idle(renderer.is_idle());
+
+        if controls.screenshot.swap(false, Ordering::Relaxed) {
+            // Render once into an image and show that image, so the frame is only analyzed once
+            let output = &settings_clone.output;
+            match screenshot::render_to_surface(
+                &mut renderer,
+                &spectrum,
+                width as i32,
+                height as i32,
+                output.background,
+            ) {
+                Ok(surface) => {
+                    let _ = cr.set_source_surface(&surface, 0.0, 0.0);
+                    let _ = cr.paint();
 
-        let fft_left = planner_clone
-            .lock()
-            .unwrap()
-            .plan_fft_forward(settings_clone.fft.size);
-        let fft_right = planner_clone
-            .lock()
-            .unwrap()
-            .plan_fft_forward(settings_clone.fft.size);
-
-        fft_left.process(&mut input_left_clone);
-        fft_right.process(&mut input_right_clone);
-
-        grid_clone.draw(cr, width, height);
-        visualizer.draw(
-            width as i32,
-            height as i32,
-            &input_left_clone,
-            &input_right_clone,
-            cr,
-            &mut previous_heights_left,
-            &mut previous_heights_right,
+                    let message = match screenshot::save_png(&surface, &output.screenshot_dir) {
+                        Ok(path) => format!("Saved to {}", path.display()),
+                        Err(e) => format!("Failed to save screenshot: {}", e),
+                    };
+                    status!("{}", message);
+                    *status_message.borrow_mut() = Some((message, Instant::now()));
+                }
+                Err(e) => {
+                    eprintln!("Failed to create screenshot surface: {}", e);
+                    renderer.render_frame(cr, width, height, &spectrum);
+                }
+            }
+        } else {
+            // The OpenGL renderer draws the bars below this drawing area once it is ready
+            #[cfg(feature = "gl")]
+            match gl_bars.as_ref().filter(|gl_bars| gl_bars.is_ready()) {
+                Some(gl_bars) => gl_bars.update(|instances| {
+                    renderer.render_frame_gl(cr, width, height, &spectrum, instances)
+                }),
+                None => renderer.render_frame(cr, width, height, &spectrum),
+            }
+            #[cfg(not(feature = "gl"))]
+            renderer.render_frame(cr, width, height, &spectrum);
+        }
+
+        if let Some(pointer) = pointer.get() {
+            renderer.draw_hover_readout(cr, width, height, &spectrum, pointer);
+        }
+        if let Some((start_x, end_x)) = selection.get() {
+            draw_selection(cr, start_x, end_x, height);
+        }
+        if let Some(now_playing) = &now_playing {
+            now_playing.borrow_mut().draw(cr, width, height);
+        }
+        if recorder.is_recording() {
+            draw_recording_indicator(cr, width);
+        }
+        if settings_clone.power.show_idle_label && renderer.is_idle() {
+            draw_idle_label(cr, width, height, &renderer.settings().ui);
+        }
+
+        let show_audio_status = controls.show_audio_status.load(Ordering::Relaxed);
+        if show_audio_status {
+            let audio_status = audio_reader.borrow().status();
+            audio_status.draw(cr, height, Instant::now(), &renderer.settings().ui);
+        }
+
+        let mut status = status_message.borrow_mut();
+        match status.as_ref() {
+            Some((text, shown_at)) if shown_at.elapsed() < STATUS_MESSAGE_DURATION => {
+                draw_status_message(cr, height, text, &renderer.settings().ui);
+            }
+            Some(_) => *status = None,
+            None => {}
+        }
+        animating.set(
+            renderer.needs_animation()
+                || status.is_some()
+                || show_audio_status
+                || now_playing
+                    .as_ref()
+                    .is_some_and(|now_playing| now_playing.borrow().needs_animation()),
+        );
+
+        frame_stats.record_draw(analyzed_at.elapsed());
+        frame_stats.record_gains(
+            renderer.settings().audio.input_gain_db,
+            renderer.display_gain(),
         );
+        if controls.show_stats.load(Ordering::Relaxed) {
+            frame_stats.draw(cr, &renderer.settings().ui);
+        }
     });
 }
 
-/// Set up window controls for key press handling and application exit.
-fn setup_window_controls(window: &ApplicationWindow, tx: watch::Sender<()>) {
-    let key_controller = gtk::EventControllerKey::new();
-    key_controller.connect_key_pressed(move |_, keyval, _, _| {
-        if keyval == gdk::Key::Q {
-            let _ = tx.send(());
-            gtk::glib::Propagation::Proceed
-        } else {
-            gtk::glib::Propagation::Stop
+/// Keep `pointer` at the position of the pointer while it hovers the drawing area.
+fn track_pointer(drawing_area: &DrawingArea, pointer: Rc<Cell<Option<(f64, f64)>>>) {
+    let motion_controller = gtk::EventControllerMotion::new();
+    let pointer_clone = pointer.clone();
+    motion_controller.connect_motion(move |_, x, y| pointer_clone.set(Some((x, y))));
+    motion_controller.connect_leave(move |_| pointer.set(None));
+    drawing_area.add_controller(motion_controller);
+}
+
+/// Zoom into horizontal selections dragged on the drawing area, solo the band of selections
+/// dragged with shift held, and reset the zoom on a right click.
+///
+/// `selection` holds the start and current x coordinate while dragging, and `zoom_request` or
+/// `solo_request` the finished selection until the next frame applies it.
+fn track_zoom_selection(
+    drawing_area: &DrawingArea,
+    selection: Rc<Cell<Option<(f64, f64)>>>,
+    zoom_request: Rc<Cell<Option<(f64, f64)>>>,
+    solo_request: Rc<Cell<Option<(f64, f64)>>>,
+    reset_zoom: Arc<AtomicBool>,
+) {
+    let drag = gtk::GestureDrag::new();
+    drag.set_button(gdk::BUTTON_PRIMARY);
+    // Whether the drag started with shift held, selecting a band to solo instead of zooming
+    let soloing = Rc::new(Cell::new(false));
+    let selection_clone = selection.clone();
+    let soloing_clone = soloing.clone();
+    drag.connect_drag_begin(move |drag, x, _| {
+        let shift = drag
+            .current_event_state()
+            .contains(gdk::ModifierType::SHIFT_MASK);
+        soloing_clone.set(shift);
+        selection_clone.set(Some((x, x)));
+    });
+    let selection_clone = selection.clone();
+    drag.connect_drag_update(move |_, offset_x, _| {
+        if let Some((start_x, _)) = selection_clone.get() {
+            selection_clone.set(Some((start_x, start_x + offset_x)));
         }
     });
-    window.add_controller(key_controller);
+    drag.connect_drag_end(move |_, offset_x, _| {
+        if let Some((start_x, _)) = selection.take() {
+            let request = if soloing.get() {
+                &solo_request
+            } else {
+                &zoom_request
+            };
+            request.set(Some((start_x, start_x + offset_x)));
+        }
+    });
+    drawing_area.add_controller(drag);
+
+    let right_click = gtk::GestureClick::new();
+    right_click.set_button(gdk::BUTTON_SECONDARY);
+    right_click.connect_pressed(move |_, _, _, _| reset_zoom.store(true, Ordering::Relaxed));
+    drawing_area.add_controller(right_click);
 }
 
-/// Schedule redraw events for smooth animation.
-fn schedule_redraw(drawing_area: &DrawingArea) {
-    let drawing_area_clone = drawing_area.clone(); // Clone the DrawingArea
-    gtk::glib::timeout_add_local(Duration::from_millis(30), move || {
-        drawing_area_clone.queue_draw(); // Use the clone inside the closure
-        gtk::glib::ControlFlow::Continue
+/// Register the actions of the whole application, their accelerators and the menu.
+///
+/// `quit` exits the application and `about` shows the about dialog; the actions of the windows
+/// are added by `setup_window_actions`. The accelerators of every action come from the `[keys]`
+/// table.
+///
+/// # Arguments
+/// - `application`: The application.
+/// - `shutdown`: The audio and worker threads, stopped before quitting.
+/// - `key_map`: The keys bound to actions.
+/// - `config_source`: Where the configuration was read from, shown in the about dialog.
+fn setup_app_actions(
+    application: &Application,
+    shutdown: Rc<RefCell<Shutdown>>,
+    key_map: &KeyMap,
+    config_source: String,
+) {
+    let quit = gio::SimpleAction::new(&Action::Quit.gtk_name(), None);
+    let application_clone = application.clone();
+    // The audio stops before the windows close, so no frame is drawn from a stopping source
+    quit.connect_activate(move |_, _| {
+        stop_workers(&mut shutdown.borrow_mut());
+        application_clone.quit();
     });
+    application.add_action(&quit);
+
+    let about = gio::SimpleAction::new("about", None);
+    let application_clone = application.clone();
+    about.connect_activate(move |_, _| {
+        show_about_dialog(application_clone.active_window().as_ref(), &config_source)
+    });
+    application.add_action(&about);
+
+    for (action, accelerators) in key_map.accelerators() {
+        let accelerators: Vec<&str> = accelerators.iter().map(String::as_str).collect();
+        application.set_accels_for_action(&action.detailed_name(), &accelerators);
+    }
+    application.set_menubar(Some(&app_menu()));
 }
 
-/// Handle application exit on receiving a shutdown signal.
-fn handle_exit(rx: watch::Receiver<()>) {
-    std::thread::spawn(move || {
-        let mut rx = rx.clone();
-        futures::executor::block_on(async {
-            let _ = rx.changed().await;
-            println!("Exiting the program...");
-            std::process::exit(0);
+/// Build the menu bar of the windows, listing the actions worth discovering along with their
+/// accelerators.
+fn app_menu() -> gio::Menu {
+    let application_menu = gio::Menu::new();
+    application_menu.append(
+        Some("Preferences"),
+        Some(&Action::Preferences.detailed_name()),
+    );
+    application_menu.append(Some("About"), Some("app.about"));
+    application_menu.append(Some("Quit"), Some(&Action::Quit.detailed_name()));
+
+    let view_menu = gio::Menu::new();
+    view_menu.append(
+        Some("Fullscreen"),
+        Some(&Action::Fullscreen.detailed_name()),
+    );
+    view_menu.append(
+        Some("Next Visualizer"),
+        Some(&Action::CycleVisualizer.detailed_name()),
+    );
+
+    let menu = gio::Menu::new();
+    menu.append_submenu(Some("Sonic Spectra"), &application_menu);
+    menu.append_submenu(Some("View"), &view_menu);
+    menu
+}
+
+/// Show the about dialog with the version, the license and the configuration in use.
+///
+/// # Arguments
+/// - `parent`: The window the dialog stays on top of, if any.
+/// - `config_source`: Where the configuration was read from.
+fn show_about_dialog(parent: Option<&gtk::Window>, config_source: &str) {
+    let license = match env!("CARGO_PKG_LICENSE") {
+        "" => "No license is declared in Cargo.toml.",
+        license => license,
+    };
+    let dialog = gtk::AboutDialog::builder()
+        .program_name("Sonic Spectra")
+        .version(env!("CARGO_PKG_VERSION"))
+        .comments(format!("Configuration: {}", config_source))
+        .license(license)
+        .modal(true)
+        .build();
+    dialog.set_transient_for(parent);
+    dialog.present();
+}
+
+/// Add the actions of one window, run by their accelerators from the `[keys]` table and by the
+/// menu.
+///
+/// The keys are configured in the `[keys]` table by action name; these are the defaults:
+/// - `quit` (`Q`) exits the application.
+/// - `preferences` (`Ctrl+,`) opens the preferences window.
+/// - `fullscreen` (`F11`) toggles between the window and fullscreen.
+/// - `cycle_visualizer` (`V`) switches to the next visualizer.
+/// - `note_readout` (`N`) toggles the dominant frequency and note readout.
+/// - `loudness_meter` (`L`) shows or hides the loudness and true-peak meter.
+/// - `clear_clip` (`P`) clears the clip light of the true-peak meter.
+/// - `calibrate` (`C`) calibrates the noise floor.
+/// - `clear_solo_band` (`X`) stops soloing the frequency band selected with shift+drag.
+/// - `clear_noise_profile` (`Shift+X`) clears the noise profile.
+/// - `record` (`R`) starts or stops recording the captured audio.
+/// - `screenshot` (`S`) saves the current frame as a PNG screenshot.
+/// - `grid` (`G`) shows or hides the frequency grid.
+/// - `horizontal_grid` (`H`) shows or hides the horizontal grid lines.
+/// - `vertical_grid` (`M`) shows or hides the vertical frequency markers.
+/// - `reset_zoom` (`Z`) resets a zoomed frequency range.
+/// - `stats` (`D`) shows or hides the frame rate and timing overlay.
+/// - `channel_mode` (`T`) toggles between the left/right and mid/side channels.
+/// - `audio_status` (`I`) shows or hides the input device and activity status line.
+/// - `clear_max_hold` (`Shift+H`) resets the max-hold curve.
+///
+/// `quit` belongs to the application and is added by `setup_app_actions`; the other actions act
+/// on the window that has the focus.
+fn setup_window_actions(window: &ApplicationWindow, controls: Controls, recorder: Arc<Recorder>) {
+    let preferences_window: Rc<RefCell<Option<gtk::Window>>> = Rc::new(RefCell::new(None));
+    for action in Action::all().filter(|action| *action != Action::Quit) {
+        let simple_action = gio::SimpleAction::new(&action.gtk_name(), None);
+        let parent = window.clone();
+        let redraw = controls.redraw.clone();
+        let controls = controls.clone();
+        let recorder = recorder.clone();
+        let preferences_window = preferences_window.clone();
+        simple_action.connect_activate(move |_, _| match action {
+            Action::Quit => {}
+            Action::Preferences => {
+                let mut preferences_window = preferences_window.borrow_mut();
+                match preferences_window.as_ref() {
+                    Some(window) if window.is_visible() => window.present(),
+                    _ => *preferences_window = Some(preferences::show(&parent, &controls)),
+                }
+            }
+            Action::Fullscreen => parent.set_fullscreened(!parent.is_fullscreen()),
+            Action::CycleVisualizer => controls.next_visualizer.store(true, Ordering::Relaxed),
+            Action::Scene(index) => *controls.scene.lock().unwrap() = Some(index),
+            Action::NoteReadout => {
+                controls
+                    .show_note_readout
+                    .fetch_xor(true, Ordering::Relaxed);
+            }
+            Action::LoudnessMeter => {
+                controls.show_loudness.fetch_xor(true, Ordering::Relaxed);
+            }
+            Action::ClearClip => controls.clear_clip.store(true, Ordering::Relaxed),
+            Action::ClearMaxHold => controls.clear_max_hold.store(true, Ordering::Relaxed),
+            Action::Calibrate => controls.calibrate.store(true, Ordering::Relaxed),
+            Action::ClearSoloBand => controls.clear_solo_band.store(true, Ordering::Relaxed),
+            Action::ClearNoiseProfile => {
+                controls.clear_noise_profile.store(true, Ordering::Relaxed)
+            }
+            Action::Record => {
+                if recorder.is_recording() {
+                    stop_recording(&recorder);
+                } else {
+                    let path = timestamped_file_name(SystemTime::now(), "wav");
+                    start_recording(&recorder, path.into());
+                }
+            }
+            Action::Screenshot => controls.screenshot.store(true, Ordering::Relaxed),
+            Action::Grid => {
+                controls.show_grid.fetch_xor(true, Ordering::Relaxed);
+            }
+            Action::HorizontalGrid => {
+                controls
+                    .show_grid_horizontal
+                    .fetch_xor(true, Ordering::Relaxed);
+            }
+            Action::VerticalGrid => {
+                controls
+                    .show_grid_vertical
+                    .fetch_xor(true, Ordering::Relaxed);
+            }
+            Action::ResetZoom => controls.reset_zoom.store(true, Ordering::Relaxed),
+            Action::Stats => {
+                controls.show_stats.fetch_xor(true, Ordering::Relaxed);
+            }
+            Action::ChannelMode => {
+                controls.mid_side.fetch_xor(true, Ordering::Relaxed);
+            }
+            Action::AudioStatus => {
+                controls
+                    .show_audio_status
+                    .fetch_xor(true, Ordering::Relaxed);
+            }
         });
-    });
+        // Every action changes what is drawn, so its frame must not be skipped
+        simple_action.connect_activate(move |_, _| redraw.store(true, Ordering::Relaxed));
+        window.add_action(&simple_action);
+    }
+}
+
+/// Start recording the captured audio to `path`, reporting the outcome.
+fn start_recording(recorder: &Recorder, path: std::path::PathBuf) {
+    match recorder.start(path.clone()) {
+        Ok(()) => status!("Recording to {}...", path.display()),
+        Err(e) => eprintln!("Failed to start recording to {}: {}", path.display(), e),
+    }
+}
+
+/// Stop the recording in progress, if any, reporting the outcome.
+fn stop_recording(recorder: &Recorder) {
+    match recorder.stop() {
+        Some(Ok(path)) => status!("Recording saved to {}.", path.display()),
+        Some(Err(e)) => eprintln!("Failed to save recording: {}", e),
+        None => {}
+    }
+}
+
+/// Draw a red dot in the top-right corner while recording.
+fn draw_recording_indicator(cr: &gtk::cairo::Context, width: f64) {
+    cr.set_source_rgba(0.9, 0.1, 0.1, 0.9);
+    cr.arc(width - 20.0, 20.0, 8.0, 0.0, 2.0 * std::f64::consts::PI);
+    let _ = cr.fill();
+}
+
+/// Draw a dim "idle" label in the center while the frame rate is reduced.
+fn draw_idle_label(cr: &gtk::cairo::Context, width: f64, height: f64, ui: &UiSettings) {
+    let style = TextStyle {
+        color: (1.0, 1.0, 1.0, 0.4),
+        align: TextAlign::Center,
+        ..TextStyle::ui(ui, 16.0)
+    };
+    let (_, text_height) = measure("idle", &style);
+    draw_text(
+        cr,
+        "idle",
+        width / 2.0,
+        (height - text_height) / 2.0,
+        &style,
+    );
+}
+
+/// Highlight the horizontal selection being dragged to zoom or solo.
+fn draw_selection(cr: &gtk::cairo::Context, start_x: f64, end_x: f64, height: f64) {
+    cr.set_source_rgba(1.0, 1.0, 1.0, 0.15);
+    cr.rectangle(start_x.min(end_x), 0.0, (end_x - start_x).abs(), height);
+    let _ = cr.fill();
+}
+
+/// Draw a transient status message in the bottom-left corner.
+fn draw_status_message(cr: &gtk::cairo::Context, height: f64, message: &str, ui: &UiSettings) {
+    let style = TextStyle {
+        color: (1.0, 1.0, 1.0, 0.9),
+        ..TextStyle::ui(ui, 14.0)
+    };
+    let (_, text_height) = measure(message, &style);
+    draw_text(cr, message, 12.0, height - 12.0 - text_height, &style);
+}
+
+/// Stop the audio sources, then the worker threads, reporting what did not stop in time.
+fn stop_workers(shutdown: &mut Shutdown) {
+    if let Err(running) = shutdown.stop(SHUTDOWN_TIMEOUT) {
+        eprintln!(
+            "Exiting while still running after {} ms: {}",
+            SHUTDOWN_TIMEOUT.as_millis(),
+            running.join(", ")
+        );
+    }
 }
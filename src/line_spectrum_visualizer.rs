@@ -0,0 +1,260 @@
+use crate::color::Palette;
+use crate::fft_utils::{catmull_rom_segments, frequency_indices, interpolate, smoothing_factor};
+use crate::frequency_mapper::FrequencyMapper;
+use crate::level_scale::LevelScale;
+use crate::settings::{LineMode, ResolvedVisualizerSettings, Settings};
+use crate::smoothing_state::SmoothingState;
+use crate::visualizer::{ChannelLayout, Visualizer};
+use gtk::cairo::{Context, LinearGradient};
+use gtk4 as gtk;
+use rustfft::num_complex::Complex32;
+use std::sync::Arc;
+
+/// Opacity of the fill at the curve relative to the configured alpha.
+const FILL_OPACITY: f64 = 0.6;
+
+/// Vertical placement of one channel's curve.
+///
+/// # Fields
+/// - `baseline`: Y coordinate the curve grows from.
+/// - `direction`: `-1.0` to grow upward, `1.0` to grow downward.
+/// - `extent`: Maximum distance from the baseline in pixels.
+/// - `scale`: Factor applied to bar heights before drawing.
+struct CurveLayout {
+    baseline: f64,
+    direction: f64,
+    extent: f64,
+    scale: f64,
+}
+
+/// A visualizer drawing the spectrum as a smooth curve with an optional gradient fill under it,
+/// similar to the analyzers found in many DAWs.
+pub struct LineSpectrumVisualizer {
+    settings: Arc<Settings>,
+    visual_settings: ResolvedVisualizerSettings,
+    palette: Palette,
+    level_scale: LevelScale,
+}
+
+impl LineSpectrumVisualizer {
+    /// Creates a new `LineSpectrumVisualizer` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - Shared application settings to configure visualizer parameters.
+    pub fn new(settings: Arc<Settings>) -> Self {
+        let visual_settings = settings.visualizer_settings("line");
+        let palette = Palette::from_settings(&visual_settings);
+        let level_scale = LevelScale::new(&visual_settings, settings.fft.size);
+        LineSpectrumVisualizer {
+            settings,
+            visual_settings,
+            palette,
+            level_scale,
+        }
+    }
+
+    /// Updates the smoothed heights of one channel and draws its curve.
+    ///
+    /// # Arguments
+    ///
+    /// * `cr` - The Cairo context for drawing.
+    /// * `fft` - FFT data of the channel, already restricted to the visible range.
+    /// * `x_positions` - Horizontal position in pixels of each point of the curve.
+    /// * `layout` - Vertical placement of the curve.
+    /// * `color` - RGB color of the curve.
+    /// * `previous_heights` - The previous frame's heights for smooth transitions.
+    /// * `factor` - Fraction of the change in height the smoothed heights move this frame.
+    fn draw_channel(
+        &self,
+        cr: &Context,
+        fft: &[Complex32],
+        x_positions: &[f64],
+        layout: &CurveLayout,
+        color: (f32, f32, f32),
+        previous_heights: &mut [f32],
+        factor: f32,
+    ) {
+        let visual_settings = &self.visual_settings;
+        let line_settings = &self.settings.line;
+        let num_points = fft.len();
+        if num_points < 2 {
+            return;
+        }
+
+        let points: Vec<(f64, f64)> = fft
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let target_height = self.level_scale.height(value.norm());
+
+                previous_heights[i] = interpolate(previous_heights[i], target_height, factor);
+
+                let offset = (previous_heights[i] as f64 * layout.scale).min(layout.extent);
+                (x_positions[i], layout.baseline + layout.direction * offset)
+            })
+            .collect();
+
+        let (r, g, b) = (color.0 as f64, color.1 as f64, color.2 as f64);
+        let alpha = visual_settings.alpha as f64;
+
+        if line_settings.fill {
+            trace_curve(cr, &points, layout);
+            cr.line_to(points[num_points - 1].0, layout.baseline);
+            cr.line_to(points[0].0, layout.baseline);
+            cr.close_path();
+
+            // Fade the fill from the curve's maximum extent towards the baseline
+            let gradient = LinearGradient::new(
+                0.0,
+                layout.baseline + layout.direction * layout.extent,
+                0.0,
+                layout.baseline,
+            );
+            gradient.add_color_stop_rgba(0.0, r, g, b, alpha * FILL_OPACITY);
+            gradient.add_color_stop_rgba(1.0, r, g, b, 0.0);
+
+            let _ = cr.set_source(&gradient);
+            let _ = cr.fill();
+        }
+
+        trace_curve(cr, &points, layout);
+        cr.set_source_rgba(r, g, b, alpha);
+        cr.set_line_width(line_settings.line_width);
+        let _ = cr.stroke();
+    }
+}
+
+impl Visualizer for LineSpectrumVisualizer {
+    /// Draws the spectrum of both channels as smooth curves.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the drawing area.
+    /// * `height` - The height of the drawing area.
+    /// * `fft_left` - FFT data for the left audio channel.
+    /// * `fft_right` - FFT data for the right audio channel.
+    /// * `cr` - The Cairo context for drawing.
+    /// * `previous_heights_left` - Stores the previous frame's left channel heights for smooth transitions.
+    /// * `previous_heights_right` - Stores the previous frame's right channel heights for smooth transitions.
+    /// * `elapsed` - Time since the previous frame in seconds, setting how far the bars move.
+    fn draw(
+        &self,
+        width: i32,
+        height: i32,
+        fft_left: &[Complex32],
+        fft_right: &[Complex32],
+        cr: &Context,
+        previous_heights_left: &mut SmoothingState,
+        previous_heights_right: &mut SmoothingState,
+        elapsed: f32,
+    ) {
+        let fft_size = fft_left.len();
+        let (min_index, max_index) = frequency_indices(&self.settings.fft, fft_size);
+
+        // Select the FFT data range for visualization
+        let fft_left = &fft_left[min_index..max_index];
+        let fft_right = &fft_right[min_index..max_index];
+        previous_heights_left.ensure_len(fft_left.len());
+        previous_heights_right.ensure_len(fft_right.len());
+
+        // Points sit at the center of their bin on the shared frequency axis
+        let mapper = FrequencyMapper::from_settings(&self.settings, fft_size);
+        let x_positions: Vec<f64> = (min_index..max_index)
+            .map(|index| {
+                let position = (mapper.bin_position(index) + mapper.bin_position(index + 1)) / 2.0;
+                position as f64 * width as f64
+            })
+            .collect();
+
+        let factor = smoothing_factor(elapsed, self.visual_settings.smoothing_ms / 1000.0);
+        let height = height as f64;
+        let (layout_left, layout_right) = match self.settings.line.mode {
+            // Both channels grow upward from the bottom edge
+            LineMode::Overlay => (
+                CurveLayout {
+                    baseline: height,
+                    direction: -1.0,
+                    extent: height,
+                    scale: 1.0,
+                },
+                CurveLayout {
+                    baseline: height,
+                    direction: -1.0,
+                    extent: height,
+                    scale: 1.0,
+                },
+            ),
+            // Left channel grows upward and right channel downward from the center line
+            LineMode::Mirrored => (
+                CurveLayout {
+                    baseline: height / 2.0,
+                    direction: -1.0,
+                    extent: height / 2.0,
+                    scale: 0.5,
+                },
+                CurveLayout {
+                    baseline: height / 2.0,
+                    direction: 1.0,
+                    extent: height / 2.0,
+                    scale: 0.5,
+                },
+            ),
+        };
+
+        self.draw_channel(
+            cr,
+            fft_left,
+            &x_positions,
+            &layout_left,
+            self.palette.color_at(0.25),
+            previous_heights_left,
+            factor,
+        );
+        self.draw_channel(
+            cr,
+            fft_right,
+            &x_positions,
+            &layout_right,
+            self.palette.color_at(0.75),
+            previous_heights_right,
+            factor,
+        );
+    }
+
+    fn channel_layout(&self) -> ChannelLayout {
+        match self.settings.line.mode {
+            LineMode::Overlay => ChannelLayout::Overlay,
+            LineMode::Mirrored => ChannelLayout::TopBottom,
+        }
+    }
+
+    fn set_palette(&mut self, palette: &Palette) {
+        self.palette = palette.clone();
+    }
+}
+
+/// Builds the smoothed curve through `points` as the current Cairo path.
+///
+/// Control points are clamped between the baseline and the maximum extent so the spline's
+/// overshoot never crosses the baseline or leaves the drawing area.
+fn trace_curve(cr: &Context, points: &[(f64, f64)], layout: &CurveLayout) {
+    let limit = layout.baseline + layout.direction * layout.extent;
+    let (low, high) = if limit < layout.baseline {
+        (limit, layout.baseline)
+    } else {
+        (layout.baseline, limit)
+    };
+
+    cr.move_to(points[0].0, points[0].1);
+    for [control_1, control_2, end] in catmull_rom_segments(points) {
+        cr.curve_to(
+            control_1.0,
+            control_1.1.clamp(low, high),
+            control_2.0,
+            control_2.1.clamp(low, high),
+            end.0,
+            end.1,
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use crate::dsp::{LoudnessMeter, TruePeakMeter};
+use crate::settings::{Corner, Settings};
+use crate::text::{draw_text, measure, TextStyle};
+use gtk::cairo::Context;
+use gtk4 as gtk;
+use std::sync::Arc;
+
+/// Loudness at 0 LU on the EBU scale, the target level of EBU R 128, in LUFS.
+const TARGET_LUFS: f32 = -23.0;
+/// Lowest and highest level of the EBU +9 scale, in LU relative to `TARGET_LUFS`.
+const SCALE_RANGE_LU: (f32, f32) = (-18.0, 9.0);
+/// Distance between the ticks of the scale, in LU.
+const TICK_STEP_LU: f32 = 3.0;
+/// Height of one LU on the meter, in pixels.
+const PIXELS_PER_LU: f64 = 6.0;
+/// Width of the meter bar, in pixels.
+const BAR_WIDTH: f64 = 12.0;
+/// Width of the tick labels and readout next to the bar, in pixels.
+const LABEL_WIDTH: f64 = 72.0;
+/// Font size of the labels and the readout, in pixels.
+const FONT_SIZE: f64 = 11.0;
+/// Distance of the overlay from the window edges, in pixels.
+const MARGIN: f64 = 12.0;
+/// Additional distance from the top edge, keeping the channel labels visible.
+const TOP_OFFSET: f64 = 24.0;
+/// Height of the true-peak readout below the meter, in pixels.
+const TRUE_PEAK_HEIGHT: f64 = 36.0;
+/// Radius of the clip light, in pixels.
+const CLIP_LIGHT_RADIUS: f64 = 5.0;
+/// Side of the squares keying the true peaks to the channel colors, in pixels.
+const KEY_SIZE: f64 = 6.0;
+
+/// A meter of the momentary and short-term loudness on the EBU +9 scale, with a numeric readout,
+/// and the true peaks of both channels below it.
+///
+/// The bar shows the momentary loudness, green up to the target and orange above, and a white
+/// line marks the short-term loudness. The clip light next to the true peaks turns red once a
+/// channel exceeded -1 dBTP, until it is cleared.
+///
+/// # Fields
+/// - `settings`: Shared settings containing the `[loudness]` configuration.
+pub struct LoudnessOverlay {
+    settings: Arc<Settings>,
+}
+
+impl LoudnessOverlay {
+    /// Creates a new `LoudnessOverlay` instance.
+    ///
+    /// # Arguments
+    /// - `settings`: Shared settings containing the `[loudness]` configuration.
+    pub fn new(settings: Arc<Settings>) -> Self {
+        LoudnessOverlay { settings }
+    }
+
+    /// Draws the meter in the configured corner.
+    ///
+    /// # Arguments
+    /// - `cr`: The Cairo `Context` used for drawing.
+    /// - `width`: The width of the drawing area.
+    /// - `height`: The height of the drawing area.
+    /// - `meter`: The meter providing the loudness of the latest audio.
+    /// - `true_peak`: The meter providing the true peaks of the latest audio.
+    /// - `channel_key`: Whether the true peaks are keyed to the channel colors of the legend.
+    pub fn draw(
+        &self,
+        cr: &Context,
+        width: f64,
+        height: f64,
+        meter: &LoudnessMeter,
+        true_peak: &TruePeakMeter,
+        channel_key: bool,
+    ) {
+        let meter_height = f64::from(SCALE_RANGE_LU.1 - SCALE_RANGE_LU.0) * PIXELS_PER_LU;
+        let x = match self.settings.loudness.corner {
+            Corner::TopLeft | Corner::BottomLeft => MARGIN,
+            Corner::TopRight | Corner::BottomRight => width - MARGIN - BAR_WIDTH - LABEL_WIDTH,
+        };
+        let top = match self.settings.loudness.corner {
+            Corner::TopLeft | Corner::TopRight => MARGIN + TOP_OFFSET,
+            Corner::BottomLeft | Corner::BottomRight => {
+                height - MARGIN - meter_height - TRUE_PEAK_HEIGHT
+            }
+        };
+        let bottom = top + meter_height;
+
+        cr.set_source_rgba(0.0, 0.0, 0.0, 0.4);
+        cr.rectangle(x, top, BAR_WIDTH, meter_height);
+        let _ = cr.fill();
+
+        let momentary = meter.momentary_lufs();
+        if let Some(lufs) = momentary {
+            let level = bottom - scale_fraction(lufs) * meter_height;
+            let target = bottom - scale_fraction(TARGET_LUFS) * meter_height;
+            cr.set_source_rgba(0.2, 0.8, 0.3, 0.9);
+            cr.rectangle(x, level.max(target), BAR_WIDTH, bottom - level.max(target));
+            let _ = cr.fill();
+            if level < target {
+                cr.set_source_rgba(1.0, 0.6, 0.1, 0.9);
+                cr.rectangle(x, level, BAR_WIDTH, target - level);
+                let _ = cr.fill();
+            }
+        }
+
+        let short_term = meter.short_term_lufs();
+        if let Some(lufs) = short_term {
+            let level = bottom - scale_fraction(lufs) * meter_height;
+            cr.set_source_rgba(1.0, 1.0, 1.0, 0.9);
+            cr.rectangle(x - 2.0, level - 1.0, BAR_WIDTH + 4.0, 2.0);
+            let _ = cr.fill();
+        }
+
+        // Ticks every 3 LU, labeled every 9 LU
+        let tick_y = |lu: f32| (bottom - scale_fraction(TARGET_LUFS + lu) * meter_height).round();
+        let labeled = |lu: f32| lu % 9.0 == 0.0;
+        let tick_levels: Vec<f32> = (0..)
+            .map(|i| SCALE_RANGE_LU.0 + TICK_STEP_LU * i as f32)
+            .take_while(|&lu| lu <= SCALE_RANGE_LU.1)
+            .collect();
+        cr.set_source_rgba(1.0, 1.0, 1.0, 0.6);
+        for &lu in &tick_levels {
+            let tick = if labeled(lu) { 6.0 } else { 3.0 };
+            cr.move_to(x + BAR_WIDTH, tick_y(lu) + 0.5);
+            cr.line_to(x + BAR_WIDTH + tick, tick_y(lu) + 0.5);
+        }
+        cr.set_line_width(1.0);
+        let _ = cr.stroke();
+
+        let text_x = x + BAR_WIDTH + 8.0;
+        let style = TextStyle {
+            color: (1.0, 1.0, 1.0, 0.6),
+            ..TextStyle::ui(&self.settings.ui, FONT_SIZE)
+        };
+        let (_, line_height) = measure("+0", &style);
+        for lu in tick_levels.into_iter().filter(|&lu| labeled(lu)) {
+            let label = format!("{:+.0}", lu);
+            draw_text(cr, &label, text_x, tick_y(lu) - line_height / 2.0, &style);
+        }
+
+        // Readout below the +9 label
+        let style = TextStyle {
+            color: (1.0, 1.0, 1.0, 0.9),
+            ..style
+        };
+        for (i, (label, lufs)) in [("M", momentary), ("S", short_term)].iter().enumerate() {
+            let y = top + line_height * (1.5 + 1.1 * i as f64);
+            draw_text(cr, &format_loudness(label, *lufs), text_x, y, &style);
+        }
+
+        // True peaks below the meter, with the clip light in the column of the bar
+        let (left, right) = true_peak.peak_dbtp();
+        let grid = &self.settings.grid;
+        let channels = [
+            ("L", left, &grid.color_left),
+            ("R", right, &grid.color_right),
+        ];
+        for (i, (label, dbtp, color)) in channels.iter().enumerate() {
+            let y = bottom + line_height * (0.6 + 1.1 * i as f64);
+            let mut label_x = text_x;
+            if channel_key {
+                let (r, g, b, a) = color.to_rgba(0.9);
+                cr.set_source_rgba(r, g, b, a);
+                cr.rectangle(
+                    text_x,
+                    y + (line_height - KEY_SIZE) / 2.0,
+                    KEY_SIZE,
+                    KEY_SIZE,
+                );
+                let _ = cr.fill();
+                label_x += KEY_SIZE + 4.0;
+            }
+            draw_text(cr, &format_true_peak(label, *dbtp), label_x, y, &style);
+        }
+        if true_peak.is_clipped() {
+            cr.set_source_rgba(0.9, 0.1, 0.1, 0.95);
+        } else {
+            cr.set_source_rgba(0.4, 0.4, 0.4, 0.6);
+        }
+        cr.arc(
+            x + BAR_WIDTH / 2.0,
+            bottom + line_height * 1.7,
+            CLIP_LIGHT_RADIUS,
+            0.0,
+            2.0 * std::f64::consts::PI,
+        );
+        let _ = cr.fill();
+    }
+}
+
+/// Height of a loudness on the EBU +9 scale, from `0.0` at -18 LU to `1.0` at +9 LU.
+fn scale_fraction(lufs: f32) -> f64 {
+    let lu = lufs - TARGET_LUFS;
+    f64::from((lu - SCALE_RANGE_LU.0) / (SCALE_RANGE_LU.1 - SCALE_RANGE_LU.0)).clamp(0.0, 1.0)
+}
+
+/// Formats one line of the readout, showing a dash while the loudness is unknown or gated.
+fn format_loudness(label: &str, lufs: Option<f32>) -> String {
+    match lufs {
+        Some(lufs) => format!("{} {:.1} LUFS", label, lufs),
+        None => format!("{} — LUFS", label),
+    }
+}
+
+/// Formats the true peak of one channel, showing a dash for silence.
+fn format_true_peak(label: &str, dbtp: f32) -> String {
+    if dbtp.is_finite() {
+        format!("{} {:.1} dBTP", label, dbtp)
+    } else {
+        format!("{} — dBTP", label)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ebu_scale_spans_minus_18_to_plus_9_lu() {
+        assert_eq!(scale_fraction(-41.0), 0.0);
+        assert!((scale_fraction(-23.0) - 18.0 / 27.0).abs() < 1e-6);
+        assert_eq!(scale_fraction(-14.0), 1.0);
+        assert_eq!(scale_fraction(-60.0), 0.0);
+        assert_eq!(scale_fraction(0.0), 1.0);
+        assert_eq!(format_loudness("M", Some(-23.04)), "M -23.0 LUFS");
+        assert_eq!(format_loudness("S", None), "S — LUFS");
+        assert_eq!(format_true_peak("L", -0.96), "L -1.0 dBTP");
+        assert_eq!(format_true_peak("R", f32::NEG_INFINITY), "R — dBTP");
+    }
+}
@@ -1,5 +1,12 @@
 use sonic_spectra::run_application;
+use std::process::ExitCode;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    run_application()
+fn main() -> ExitCode {
+    match run_application() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
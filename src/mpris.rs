@@ -0,0 +1,113 @@
+use crate::now_playing::TrackInfo;
+use futures::StreamExt;
+use std::collections::HashMap;
+use tokio::sync::watch;
+use zbus::fdo::DBusProxy;
+use zbus::zvariant::OwnedValue;
+use zbus::{proxy, Connection};
+
+/// Prefix of the bus names media players register under.
+const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+
+#[proxy(
+    interface = "org.mpris.MediaPlayer2.Player",
+    default_path = "/org/mpris/MediaPlayer2"
+)]
+trait Player {
+    #[zbus(property)]
+    fn metadata(&self) -> zbus::Result<HashMap<String, OwnedValue>>;
+}
+
+/// Watches every MPRIS media player on the session bus, publishing the latest track to `tx`.
+///
+/// Players already running are watched right away, and players appearing later once they
+/// claim their bus name. Runs until the session bus connection fails.
+pub async fn watch_players(tx: watch::Sender<Option<TrackInfo>>) {
+    if let Err(e) = watch_bus(tx).await {
+        eprintln!("Failed to watch media players: {}", e);
+    }
+}
+
+/// Connects to the session bus and spawns a watcher per player.
+async fn watch_bus(tx: watch::Sender<Option<TrackInfo>>) -> zbus::Result<()> {
+    let connection = Connection::session().await?;
+    let dbus = DBusProxy::new(&connection).await?;
+
+    // Subscribe before listing so players starting in between are not missed
+    let mut owner_changes = dbus.receive_name_owner_changed().await?;
+    for name in dbus.list_names().await? {
+        if name.as_str().starts_with(MPRIS_PREFIX) {
+            tokio::spawn(watch_player(
+                connection.clone(),
+                name.as_str().to_string(),
+                tx.clone(),
+            ));
+        }
+    }
+
+    while let Some(change) = owner_changes.next().await {
+        let Ok(args) = change.args() else {
+            continue;
+        };
+        // A new owner without an old one is a player that just started
+        if args.name().as_str().starts_with(MPRIS_PREFIX)
+            && args.old_owner().is_none()
+            && args.new_owner().is_some()
+        {
+            let name = args.name().as_str().to_string();
+            tokio::spawn(watch_player(connection.clone(), name, tx.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Publishes the tracks of one player until it exits.
+async fn watch_player(
+    connection: Connection,
+    name: String,
+    tx: watch::Sender<Option<TrackInfo>>,
+) -> zbus::Result<()> {
+    let player = PlayerProxy::builder(&connection)
+        .destination(name)?
+        .build()
+        .await?;
+
+    let mut published = None;
+    let mut changes = player.receive_metadata_changed().await;
+    while let Some(change) = changes.next().await {
+        let Ok(metadata) = change.get().await else {
+            continue;
+        };
+        let track = track_info(&metadata);
+        published = track.clone();
+        tx.send_replace(track);
+    }
+
+    // Clear the overlay if the exiting player is the one it shows
+    tx.send_if_modified(|current| {
+        let shown = published.is_some() && *current == published;
+        if shown {
+            *current = None;
+        }
+        shown
+    });
+    Ok(())
+}
+
+/// Extracts the title and artists from MPRIS metadata.
+fn track_info(metadata: &HashMap<String, OwnedValue>) -> Option<TrackInfo> {
+    let title = metadata
+        .get("xesam:title")
+        .and_then(|value| <&str>::try_from(value).ok())
+        .filter(|title| !title.is_empty())
+        .map(str::to_string);
+    let artist = metadata
+        .get("xesam:artist")
+        .and_then(|value| value.try_clone().ok())
+        .and_then(|value| Vec::<String>::try_from(value).ok())
+        .filter(|artists| !artists.is_empty())
+        .map(|artists| artists.join(", "));
+
+    let track = TrackInfo { title, artist };
+    track.display_text().map(|_| track)
+}
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+/// Per-bin average magnitudes of the background noise for both channels.
+///
+/// # Fields
+/// - `left`: Noise magnitudes of the left channel, one per FFT bin.
+/// - `right`: Noise magnitudes of the right channel, one per FFT bin.
+#[derive(Serialize, Deserialize)]
+pub struct NoiseProfile {
+    pub left: Vec<f32>,
+    pub right: Vec<f32>,
+}
+
+impl NoiseProfile {
+    /// Loads a noise profile saved by `save`.
+    ///
+    /// # Arguments
+    /// - `path`: The profile file to read.
+    /// - `fft_size`: The current FFT size; profiles measured with another size are rejected.
+    ///
+    /// # Returns
+    /// - The profile, or `None` if the file is missing, invalid or does not match `fft_size`.
+    pub fn load(path: &str, fft_size: usize) -> Option<Self> {
+        let data = fs::read_to_string(path).ok()?;
+        let profile: NoiseProfile = match toml::from_str(&data) {
+            Ok(profile) => profile,
+            Err(e) => {
+                eprintln!("Ignoring invalid noise profile {}: {}", path, e);
+                return None;
+            }
+        };
+
+        if profile.left.len() != fft_size || profile.right.len() != fft_size {
+            eprintln!(
+                "Ignoring noise profile {} measured with a different FFT size.",
+                path
+            );
+            return None;
+        }
+
+        Some(profile)
+    }
+
+    /// Writes the profile to `path` as TOML.
+    ///
+    /// # Returns
+    /// - `Result` with no value on success, or an error if the file cannot be written.
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Deletes the profile file at `path`, if any.
+    pub fn remove(path: &str) {
+        if let Err(e) = fs::remove_file(path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                eprintln!("Failed to remove noise profile {}: {}", path, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use crate::fft_utils::{dominant_frequency, freq_to_note};
+use crate::settings::Settings;
+use crate::text::{draw_text, TextStyle};
+use gtk::cairo::Context;
+use gtk4 as gtk;
+use rustfft::num_complex::Complex32;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A text overlay showing the dominant frequency and its nearest musical note.
+///
+/// # Fields
+/// - `settings`: Shared settings containing the `[note_readout]` configuration.
+/// - `text`: The readout currently displayed, or `None` while the input is below the threshold.
+/// - `last_update`: When `text` was last refreshed.
+pub struct NoteReadout {
+    settings: Arc<Settings>,
+    text: Option<String>,
+    last_update: Option<Instant>,
+}
+
+impl NoteReadout {
+    /// Creates a new `NoteReadout` instance.
+    ///
+    /// # Arguments
+    /// - `settings`: Shared settings containing the `[note_readout]` configuration.
+    pub fn new(settings: Arc<Settings>) -> Self {
+        NoteReadout {
+            settings,
+            text: None,
+            last_update: None,
+        }
+    }
+
+    /// Refreshes the readout from the current spectrum and draws it in the top-left corner.
+    ///
+    /// # Arguments
+    /// - `cr`: The Cairo `Context` used for drawing.
+    /// - `spectrum`: FFT data the dominant frequency is measured in.
+    ///
+    /// The text is only refreshed every `update_interval_ms` to avoid flicker, and is hidden
+    /// while the peak amplitude stays below `threshold`.
+    pub fn draw(&mut self, cr: &Context, spectrum: &[Complex32]) {
+        let readout_settings = &self.settings.note_readout;
+        let interval = Duration::from_millis(readout_settings.update_interval_ms);
+
+        let due = match self.last_update {
+            Some(last_update) => last_update.elapsed() >= interval,
+            None => true,
+        };
+        if due {
+            self.text = self.measure(spectrum);
+            self.last_update = Some(Instant::now());
+        }
+
+        if let Some(text) = &self.text {
+            let style = TextStyle {
+                color: (1.0, 1.0, 1.0, 0.9),
+                ..TextStyle::ui(&self.settings.ui, 16.0).bold()
+            };
+            draw_text(cr, text, 12.0, 8.0, &style);
+        }
+    }
+
+    /// Formats the readout for a spectrum, or returns `None` if the peak is below the threshold.
+    fn measure(&self, spectrum: &[Complex32]) -> Option<String> {
+        let readout_settings = &self.settings.note_readout;
+        let fft_size = spectrum.len();
+
+        // Normalize so a full-scale sine peaks at its amplitude
+        let peak = spectrum[..fft_size / 2]
+            .iter()
+            .map(|value| value.norm() * 2.0 / fft_size as f32)
+            .fold(0.0, f32::max);
+        if peak < readout_settings.threshold {
+            return None;
+        }
+
+        let frequency = dominant_frequency(spectrum, self.settings.fft.sample_rate)?;
+        let (note, cents) = freq_to_note(frequency, readout_settings.a4);
+        Some(format!(
+            "{:.1} Hz — {} {:+.0} cents",
+            frequency, note, cents
+        ))
+    }
+}
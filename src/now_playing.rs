@@ -0,0 +1,189 @@
+use crate::settings::{Corner, Settings};
+use crate::text::{draw_text, measure, TextAlign, TextStyle};
+use gtk::cairo::Context;
+use gtk4 as gtk;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+use tokio::sync::watch;
+
+/// Duration of the fade out once `fade_secs` have passed.
+const FADE_OUT_DURATION: Duration = Duration::from_secs(1);
+/// Distance of the overlay from the window edges, in pixels.
+const MARGIN: f64 = 12.0;
+
+/// The track reported by a media player.
+///
+/// # Fields
+/// - `title`: Title of the track, if known.
+/// - `artist`: Artists of the track joined with commas, if known.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(not(feature = "mpris"), allow(dead_code))]
+pub struct TrackInfo {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+}
+
+impl TrackInfo {
+    /// Formats the track as `Artist — Title`, or `None` if neither is known.
+    pub fn display_text(&self) -> Option<String> {
+        match (&self.artist, &self.title) {
+            (Some(artist), Some(title)) => Some(format!("{} — {}", artist, title)),
+            (Some(text), None) | (None, Some(text)) => Some(text.clone()),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Starts watching media players if `now_playing.enabled` is set.
+///
+/// # Arguments
+/// - `settings`: Shared settings containing the `[now_playing]` configuration.
+/// - `runtime`: Runtime the D-Bus listener runs on.
+///
+/// # Returns
+/// - A receiver of the latest track, or `None` if the overlay is disabled or unavailable.
+pub fn start(settings: &Settings, runtime: &Handle) -> Option<watch::Receiver<Option<TrackInfo>>> {
+    if !settings.now_playing.enabled {
+        return None;
+    }
+
+    #[cfg(feature = "mpris")]
+    {
+        let (tx, rx) = watch::channel(None);
+        runtime.spawn(crate::mpris::watch_players(tx));
+        Some(rx)
+    }
+
+    #[cfg(not(feature = "mpris"))]
+    {
+        let _ = runtime;
+        eprintln!("now_playing is enabled, but sonic_spectra was built without the mpris feature.");
+        None
+    }
+}
+
+/// Computes the opacity of the overlay.
+///
+/// # Arguments
+/// - `elapsed`: Time since the track changed.
+/// - `fade_secs`: Time the overlay stays fully visible; `0.0` keeps it visible.
+///
+/// # Returns
+/// - The opacity, from `1.0` while visible down to `0.0` once faded out.
+fn fade_alpha(elapsed: Duration, fade_secs: f32) -> f64 {
+    if fade_secs <= 0.0 {
+        return 1.0;
+    }
+    let fading = elapsed.as_secs_f64() - fade_secs as f64;
+    (1.0 - fading / FADE_OUT_DURATION.as_secs_f64()).clamp(0.0, 1.0)
+}
+
+/// A text overlay showing the track playing in a media player.
+///
+/// # Fields
+/// - `settings`: Shared settings containing the `[now_playing]` configuration.
+/// - `track_info`: Receiver of the latest track published by the D-Bus listener.
+/// - `changed_at`: When the displayed track last changed.
+pub struct NowPlayingOverlay {
+    settings: Arc<Settings>,
+    track_info: watch::Receiver<Option<TrackInfo>>,
+    changed_at: Instant,
+}
+
+impl NowPlayingOverlay {
+    /// Creates a new `NowPlayingOverlay` instance.
+    ///
+    /// # Arguments
+    /// - `settings`: Shared settings containing the `[now_playing]` configuration.
+    /// - `track_info`: Receiver returned by `start`.
+    pub fn new(settings: Arc<Settings>, track_info: watch::Receiver<Option<TrackInfo>>) -> Self {
+        NowPlayingOverlay {
+            settings,
+            track_info,
+            changed_at: Instant::now(),
+        }
+    }
+
+    /// Returns whether the overlay changes without a new spectrum: a new track is waiting to be
+    /// shown, or the shown one has yet to fade out.
+    pub fn needs_animation(&self) -> bool {
+        let fade_secs = self.settings.now_playing.fade_secs;
+        self.track_info.has_changed().unwrap_or(false)
+            || (fade_secs > 0.0 && fade_alpha(self.changed_at.elapsed(), fade_secs) > 0.0)
+    }
+
+    /// Draws the latest track in the configured corner, fading it out after a while.
+    ///
+    /// # Arguments
+    /// - `cr`: The Cairo `Context` used for drawing.
+    /// - `width`: The width of the drawing area.
+    /// - `height`: The height of the drawing area.
+    pub fn draw(&mut self, cr: &Context, width: f64, height: f64) {
+        if self.track_info.has_changed().unwrap_or(false) {
+            self.track_info.borrow_and_update();
+            self.changed_at = Instant::now();
+        }
+
+        let overlay_settings = &self.settings.now_playing;
+        let alpha = fade_alpha(self.changed_at.elapsed(), overlay_settings.fade_secs);
+        if alpha <= 0.0 {
+            return;
+        }
+        let Some(text) = self
+            .track_info
+            .borrow()
+            .as_ref()
+            .and_then(TrackInfo::display_text)
+        else {
+            return;
+        };
+
+        // Long titles are cut off with an ellipsis instead of running out of the window
+        let (x, align) = match overlay_settings.corner {
+            Corner::TopLeft | Corner::BottomLeft => (MARGIN, TextAlign::Left),
+            Corner::TopRight | Corner::BottomRight => (width - MARGIN, TextAlign::Right),
+        };
+        let style = TextStyle {
+            color: (1.0, 1.0, 1.0, 0.9 * alpha),
+            align,
+            max_width: Some(width - 2.0 * MARGIN),
+            ..TextStyle::new(&self.settings.ui.font, overlay_settings.font_size).bold()
+        };
+        let (_, text_height) = measure(&text, &style);
+        let y = match overlay_settings.corner {
+            Corner::TopLeft | Corner::TopRight => MARGIN,
+            Corner::BottomLeft | Corner::BottomRight => height - MARGIN - text_height,
+        };
+        draw_text(cr, &text, x, y, &style);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_text_joins_artist_and_title() {
+        let track = TrackInfo {
+            title: Some("Song".to_string()),
+            artist: Some("Band".to_string()),
+        };
+        assert_eq!(track.display_text().unwrap(), "Band — Song");
+
+        let title_only = TrackInfo {
+            title: Some("Song".to_string()),
+            artist: None,
+        };
+        assert_eq!(title_only.display_text().unwrap(), "Song");
+        assert_eq!(TrackInfo::default().display_text(), None);
+    }
+
+    #[test]
+    fn overlay_fades_out_after_the_delay() {
+        assert_eq!(fade_alpha(Duration::from_secs(2), 5.0), 1.0);
+        assert!((fade_alpha(Duration::from_millis(5500), 5.0) - 0.5).abs() < 1e-9);
+        assert_eq!(fade_alpha(Duration::from_secs(7), 5.0), 0.0);
+        assert_eq!(fade_alpha(Duration::from_secs(600), 0.0), 1.0);
+    }
+}
@@ -0,0 +1,288 @@
+use crate::bar_batch::rounded_rect;
+use crate::color::Palette;
+use crate::fft_utils::{
+    band_bins, band_magnitudes, get_bar_color, interpolate, octave_bands, smoothing_factor,
+    OctaveBand,
+};
+use crate::format::format_frequency;
+use crate::frequency_mapper::{Channel, FrequencyMapper};
+use crate::level_scale::LevelScale;
+use crate::settings::{ResolvedVisualizerSettings, Settings};
+use crate::smoothing_state::SmoothingState;
+use crate::text::{draw_text, measure, TextStyle};
+use crate::visualizer::Visualizer;
+use gtk::cairo::Context;
+use gtk4 as gtk;
+use rustfft::num_complex::Complex32;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Fraction of each band slot covered by its bar; the rest is left as a gap.
+const BAR_FILL: f64 = 0.8;
+/// Font size of the band labels, in pixels.
+const LABEL_SIZE: f64 = 10.0;
+/// Height reserved below the bars for the band labels, in pixels.
+const LABEL_HEIGHT: f64 = 16.0;
+/// Smallest horizontal gap between neighboring labels; labels that would crowd are skipped.
+const LABEL_SPACING: f64 = 4.0;
+/// Lowest and highest band center shown, the audible range covered by the standard bands.
+const BAND_RANGE: (f32, f32) = (20.0, 20000.0);
+
+/// A visualizer showing the RMS level of standard fractional-octave bands, like a hardware
+/// real-time analyzer.
+///
+/// The power of the FFT bins within each band is summed, so a band shows the energy of all the
+/// partials it contains. The left channel is drawn on the left half and the right channel on the
+/// right half, with the lowest band at the center.
+///
+/// # Fields
+/// - `settings`: Shared application settings with the `[octave]` configuration.
+/// - `visual_settings`: Drawing settings of the `octave` visualizer.
+/// - `palette`: Palette the band colors are looked up in.
+/// - `level_scale`: Converts band magnitudes into bar heights.
+/// - `bands`: The bands whose centers lie in the configured frequency range, within 20 Hz to
+///   20 kHz.
+/// - `bins`: The FFT bins summed into each band, for a spectrum of `fft.padded_size()` bins.
+pub struct OctaveBandVisualizer {
+    settings: Arc<Settings>,
+    visual_settings: ResolvedVisualizerSettings,
+    palette: Palette,
+    level_scale: LevelScale,
+    bands: Vec<OctaveBand>,
+    bins: Vec<Range<usize>>,
+}
+
+impl OctaveBandVisualizer {
+    /// Creates a new `OctaveBandVisualizer` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - Shared application settings to configure visualizer parameters.
+    pub fn new(settings: Arc<Settings>) -> Self {
+        let visual_settings = settings.visualizer_settings("octave");
+        let palette = Palette::from_settings(&visual_settings);
+        let level_scale = LevelScale::new(&visual_settings, settings.fft.size);
+        let fft = &settings.fft;
+        let bands = octave_bands(
+            settings.octave.fraction,
+            fft.min_frequency.max(BAND_RANGE.0),
+            fft.max_frequency.min(BAND_RANGE.1),
+        );
+        let bins = band_bins(&bands, fft.sample_rate, fft.padded_size());
+        OctaveBandVisualizer {
+            settings,
+            visual_settings,
+            palette,
+            level_scale,
+            bands,
+            bins,
+        }
+    }
+
+    /// Computes the level of each band of one channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `fft` - FFT data of the channel, covering the full FFT size.
+    ///
+    /// # Returns
+    ///
+    /// The summed magnitude of each band, comparable to the magnitude of a single bin.
+    fn band_levels(&self, fft: &[Complex32]) -> Vec<f32> {
+        let zero_pad_factor = self.settings.fft.zero_pad_factor;
+        if fft.len() == self.settings.fft.padded_size() {
+            band_magnitudes(fft, &self.bins, zero_pad_factor)
+        } else {
+            let bins = band_bins(&self.bands, self.settings.fft.sample_rate, fft.len());
+            band_magnitudes(fft, &bins, zero_pad_factor)
+        }
+    }
+
+    /// Writes the nominal center frequency below each band of one half.
+    ///
+    /// # Arguments
+    ///
+    /// * `cr` - The Cairo context for drawing.
+    /// * `channel` - The half the labels are written on.
+    /// * `half_width` - Half of the width of the drawing area.
+    /// * `height` - The height of the drawing area.
+    fn draw_labels(&self, cr: &Context, channel: Channel, half_width: f64, height: f64) {
+        let num_bands = self.bands.len();
+        let mapper =
+            FrequencyMapper::from_settings(&self.settings, self.settings.fft.padded_size());
+        let grid = &self.settings.grid;
+        let color = match channel {
+            Channel::Left => &grid.color_left,
+            Channel::Right => &grid.color_right,
+        };
+        let style = TextStyle {
+            color: color.to_rgba(grid.alpha),
+            ..TextStyle::ui(&self.settings.ui, LABEL_SIZE)
+        };
+
+        // Labels are placed from the low bands up, skipping any that would overlap the last
+        let descending = mapper.descending(channel);
+        let mut last_edge: Option<f64> = None;
+        for (i, band) in self.bands.iter().enumerate() {
+            let text = label(band.nominal_center(), self.settings.ui.decimal_comma);
+            let (text_width, text_height) = measure(&text, &style);
+            let center =
+                mapper.mirrored_x((i as f32 + 0.5) / num_bands as f32, half_width, channel);
+            let (start, end) = (center - text_width / 2.0, center + text_width / 2.0);
+            let clear = match last_edge {
+                None => true,
+                Some(edge) if descending => end + LABEL_SPACING <= edge,
+                Some(edge) => start >= edge + LABEL_SPACING,
+            };
+            if !clear || start < 0.0 || end > 2.0 * half_width {
+                continue;
+            }
+
+            let top = height - (LABEL_HEIGHT + text_height) / 2.0;
+            draw_text(cr, &text, start, top, &style);
+            last_edge = Some(if descending { start } else { end });
+        }
+    }
+}
+
+impl Visualizer for OctaveBandVisualizer {
+    /// Draws the band levels of the left and right audio channels.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the drawing area.
+    /// * `height` - The height of the drawing area.
+    /// * `fft_left` - FFT data for the left audio channel.
+    /// * `fft_right` - FFT data for the right audio channel.
+    /// * `cr` - The Cairo context for drawing.
+    /// * `previous_heights_left` - Stores the previous frame's left channel heights for smooth transitions.
+    /// * `previous_heights_right` - Stores the previous frame's right channel heights for smooth transitions.
+    /// * `elapsed` - Time since the previous frame in seconds, setting how far the bars move.
+    fn draw(
+        &self,
+        width: i32,
+        height: i32,
+        fft_left: &[Complex32],
+        fft_right: &[Complex32],
+        cr: &Context,
+        previous_heights_left: &mut SmoothingState,
+        previous_heights_right: &mut SmoothingState,
+        elapsed: f32,
+    ) {
+        let visual_settings = &self.visual_settings;
+        let num_bands = self.bands.len();
+        if num_bands == 0 {
+            return;
+        }
+
+        let labels = self.settings.octave.labels;
+        let baseline = if labels {
+            height as f64 - LABEL_HEIGHT
+        } else {
+            height as f64
+        };
+        let half_width = width as f64 / 2.0;
+        let slot_width = half_width / num_bands as f64;
+        let mapper = FrequencyMapper::from_settings(&self.settings, fft_left.len());
+        let factor = smoothing_factor(elapsed, visual_settings.smoothing_ms / 1000.0);
+
+        for (channel, fft, previous_heights) in [
+            (Channel::Left, fft_left, previous_heights_left),
+            (Channel::Right, fft_right, previous_heights_right),
+        ] {
+            let levels = self.band_levels(fft);
+            previous_heights.ensure_len(num_bands);
+
+            for (i, level) in levels.into_iter().enumerate() {
+                let target_height = self.level_scale.height(level);
+                previous_heights[i] = interpolate(previous_heights[i], target_height, factor);
+                let bar_height = (previous_heights[i] as f64).min(baseline);
+
+                let (r, g, b, a) = get_bar_color(
+                    &self.palette,
+                    visual_settings.color_mode,
+                    i,
+                    num_bands,
+                    previous_heights[i] / height as f32,
+                );
+                cr.set_source_rgba(
+                    r as f64,
+                    g as f64,
+                    b as f64,
+                    (visual_settings.alpha * a) as f64,
+                );
+
+                let center =
+                    mapper.mirrored_x((i as f32 + 0.5) / num_bands as f32, half_width, channel);
+                let bar_width = slot_width * BAR_FILL;
+                rounded_rect(
+                    cr,
+                    center - bar_width / 2.0,
+                    baseline - bar_height,
+                    bar_width,
+                    bar_height,
+                    f64::from(visual_settings.bar_radius),
+                );
+                let _ = cr.fill();
+            }
+
+            if labels {
+                self.draw_labels(cr, channel, half_width, height as f64);
+            }
+        }
+    }
+
+    fn set_palette(&mut self, palette: &Palette) {
+        self.palette = palette.clone();
+    }
+}
+
+/// Formats a nominal band center compactly, such as `31.5`, `250` or `12.5k`, with a decimal
+/// comma when `decimal_comma` is set.
+fn label(frequency: f32, decimal_comma: bool) -> String {
+    let text = if frequency >= 1000.0 {
+        format!("{}k", frequency / 1000.0)
+    } else {
+        format!("{}", frequency)
+    };
+    if text.len() > 6 {
+        format_frequency(frequency, decimal_comma)
+    } else if decimal_comma {
+        text.replace('.', ",")
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_use_the_nominal_centers() {
+        assert_eq!(label(31.5, false), "31.5");
+        assert_eq!(label(250.0, false), "250");
+        assert_eq!(label(12500.0, false), "12.5k");
+        assert_eq!(label(1000.0, false), "1k");
+        assert_eq!(label(12500.0, true), "12,5k");
+    }
+
+    #[test]
+    fn bands_follow_the_configured_fraction_and_range() {
+        let mut settings = Settings::default();
+        settings.fft.min_frequency = 20.0;
+        settings.fft.max_frequency = 20000.0;
+        settings.octave.fraction = 1;
+        let visualizer = OctaveBandVisualizer::new(Arc::new(settings.clone()));
+        assert_eq!(visualizer.bands.len(), 10);
+
+        // A 1 kHz bin lights only the 1 kHz octave
+        let fft_size = settings.fft.size;
+        let mut spectrum = vec![Complex32::new(0.0, 0.0); fft_size];
+        let bin = (1000.0 * fft_size as f32 / settings.fft.sample_rate).round() as usize;
+        spectrum[bin] = Complex32::new(100.0, 0.0);
+        let levels = visualizer.band_levels(&spectrum);
+        assert_eq!(levels[5], 100.0);
+        assert_eq!(levels.iter().filter(|&&level| level > 0.0).count(), 1);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::audio::{process_frame, AudioProcessing, DcBlocker};
+use crate::cli::RenderOptions;
+use crate::dsp::BeatCallback;
+use crate::renderer::FrameRenderer;
+use crate::screenshot::{render_to_surface, write_png};
+use crate::settings::Settings;
+use crate::visualizer::VisualizerRegistry;
+use crate::wav::read_wav_file;
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Renders the visualization of an audio file to numbered PNG frames without opening a window.
+///
+/// # Arguments
+/// - `options`: The input file, output directory, frame size and frame rate.
+/// - `settings`: Shared application settings.
+/// - `registry`: Visualizers available by name.
+/// - `beat_callbacks`: Functions invoked for every detected beat.
+///
+/// # Returns
+/// - `Result` with no value once every frame has been written, or the first error encountered.
+///
+/// Each frame analyzes the `fft.size` samples preceding its timestamp, which is the latest window
+/// the live capture would show with a hop of one sample, and advances all smoothing state by one
+/// frame.
+/// The frames can be assembled into a video with e.g.
+/// `ffmpeg -framerate 60 -i frames/frame_%06d.png -i input.wav out.mp4`.
+pub fn render(
+    options: &RenderOptions,
+    settings: Arc<Settings>,
+    registry: VisualizerRegistry,
+    beat_callbacks: Vec<BeatCallback>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let wav = read_wav_file(&options.input)
+        .map_err(|e| format!("Failed to read {}: {}", options.input.display(), e))?;
+    if wav.sample_rate as f32 != settings.fft.sample_rate {
+        eprintln!(
+            "Warning: {} is sampled at {} Hz but fft.sample_rate is {} Hz.",
+            options.input.display(),
+            wav.sample_rate,
+            settings.fft.sample_rate
+        );
+    }
+    let (left, right) = split_channels(&wav.samples, wav.channels, wav.sample_rate, &settings);
+
+    fs::create_dir_all(&options.out)?;
+
+    let fps = options.fps as f64;
+    let mut renderer = FrameRenderer::new(
+        settings.clone(),
+        registry,
+        beat_callbacks,
+        Duration::from_secs_f64(1.0 / fps),
+    );
+    let fft_size = settings.fft.size;
+    let duration = left.len() as f64 / wav.sample_rate as f64;
+    let total_frames = (duration * fps).ceil() as usize;
+
+    for frame in 0..total_frames {
+        let timestamp = frame as f64 / fps;
+        let end = (timestamp * wav.sample_rate as f64) as usize;
+        let window_left = window(&left, end, fft_size);
+        let window_right = window(&right, end, fft_size);
+
+        let spectrum = renderer.analyze(
+            &window_left,
+            &window_right,
+            Duration::from_secs_f64(timestamp),
+        );
+        let surface = render_to_surface(
+            &mut renderer,
+            &spectrum,
+            options.width as i32,
+            options.height as i32,
+            settings.output.background,
+        )?;
+        write_png(
+            &surface,
+            &options.out.join(format!("frame_{:06}.png", frame + 1)),
+        )?;
+
+        eprint!("\rRendered frame {}/{}", frame + 1, total_frames);
+    }
+    eprintln!();
+
+    Ok(())
+}
+
+/// Splits interleaved samples into left and right channels, applying the same DC blocking,
+/// gains, swapping and downmixing as the live capture.
+fn split_channels(
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    settings: &Settings,
+) -> (Vec<f32>, Vec<f32>) {
+    let processing = AudioProcessing::from_settings(&settings.audio);
+    let mut dc_blocker_left = DcBlocker::new(settings.audio.highpass_hz, sample_rate as f32);
+    let mut dc_blocker_right = DcBlocker::new(settings.audio.highpass_hz, sample_rate as f32);
+    let right_offset = if channels >= 2 { 1 } else { 0 };
+
+    samples
+        .chunks_exact(channels as usize)
+        .map(|frame| {
+            process_frame(
+                dc_blocker_left.process(frame[0]),
+                dc_blocker_right.process(frame[right_offset]),
+                &processing,
+            )
+        })
+        .unzip()
+}
+
+/// Returns the `size` samples preceding `end`, padded with silence before the start of `samples`.
+fn window(samples: &[f32], end: usize, size: usize) -> Vec<f32> {
+    let end = end.min(samples.len());
+    let start = end.saturating_sub(size);
+
+    let mut window = vec![0.0; size - (end - start)];
+    window.extend_from_slice(&samples[start..end]);
+    window
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_are_padded_before_the_start() {
+        let samples = [1.0, 2.0, 3.0, 4.0, 5.0];
+
+        assert_eq!(window(&samples, 0, 3), [0.0, 0.0, 0.0]);
+        assert_eq!(window(&samples, 2, 3), [0.0, 1.0, 2.0]);
+        assert_eq!(window(&samples, 4, 3), [2.0, 3.0, 4.0]);
+        assert_eq!(window(&samples, 9, 3), [3.0, 4.0, 5.0]);
+    }
+}
@@ -0,0 +1,293 @@
+use crate::fft_utils::frequency_indices;
+use crate::settings::Settings;
+use rustfft::num_complex::Complex32;
+use std::io;
+use std::net::UdpSocket;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// OSC time tag meaning "immediately".
+const OSC_IMMEDIATELY: u64 = 1;
+
+/// Sends analyzed frames as OSC bundles over UDP.
+///
+/// Each bundle holds three messages: `/sonic_spectra/left` and `/sonic_spectra/right` with the
+/// binned bar heights of each channel, and `/sonic_spectra/rms` with the RMS level of the left
+/// and right samples. Bar heights are the log10 of the gained magnitude, like the drawn bars
+/// before `scale_factor` is applied.
+///
+/// The socket is non-blocking, so frames the network cannot take right away are dropped instead
+/// of delaying the render path.
+///
+/// # Fields
+/// - `settings`: Shared application settings.
+/// - `socket`: Non-blocking socket connected to `output.osc_address`.
+/// - `interval`: Minimum time between two bundles.
+/// - `last_sent`: Timestamp of the last bundle sent.
+/// - `dropped`: Number of bundles dropped because the socket was busy.
+pub struct OscOutput {
+    settings: Arc<Settings>,
+    socket: UdpSocket,
+    interval: Duration,
+    last_sent: Option<Duration>,
+    dropped: usize,
+}
+
+impl OscOutput {
+    /// Creates an `OscOutput` sending to `output.osc_address`.
+    ///
+    /// # Returns
+    /// - The output, or `None` if no address is configured or the socket cannot be set up.
+    pub fn from_settings(settings: Arc<Settings>) -> Option<Self> {
+        let address = settings.output.osc_address.clone()?;
+        match Self::connect(&address) {
+            Ok(socket) => Some(OscOutput {
+                interval: Duration::from_secs_f32(1.0 / settings.output.osc_rate_hz.max(0.1)),
+                settings,
+                socket,
+                last_sent: None,
+                dropped: 0,
+            }),
+            Err(e) => {
+                eprintln!("Failed to open OSC output to {}: {}", address, e);
+                None
+            }
+        }
+    }
+
+    /// Sends one analyzed frame, unless the previous bundle was sent less than `interval` ago.
+    ///
+    /// # Arguments
+    /// - `timestamp`: Time of the frame, relative to the start of the analysis.
+    /// - `samples`: The left and right samples the frame was computed from.
+    /// - `spectrum`: The analyzed left and right spectra.
+    pub fn send(
+        &mut self,
+        timestamp: Duration,
+        samples: (&[f32], &[f32]),
+        spectrum: (&[Complex32], &[Complex32]),
+    ) {
+        if let Some(last_sent) = self.last_sent {
+            if timestamp.saturating_sub(last_sent) < self.interval {
+                return;
+            }
+        }
+        self.last_sent = Some(timestamp);
+
+        let bins = self.settings.output.osc_bins;
+        let gain = self.settings.visualizer.gain;
+        let range = frequency_indices(&self.settings.fft, spectrum.0.len());
+        let bundle = encode_bundle(
+            &bin_heights(spectrum.0, range, bins, gain),
+            &bin_heights(spectrum.1, range, bins, gain),
+            (rms(samples.0), rms(samples.1)),
+        );
+
+        match self.socket.send(&bundle) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                self.dropped += 1;
+                if self.dropped.is_power_of_two() {
+                    eprintln!("OSC output is too slow, dropped {} frames.", self.dropped);
+                }
+            }
+            // Nothing listening yet is expected; keep sending
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {}
+            Err(e) => eprintln!("Failed to send OSC frame: {}", e),
+        }
+    }
+
+    /// Opens a non-blocking UDP socket connected to `address`.
+    fn connect(address: &str) -> io::Result<UdpSocket> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(address)?;
+        socket.set_nonblocking(true)?;
+        Ok(socket)
+    }
+}
+
+/// Groups a range of the spectrum into evenly sized bins.
+///
+/// # Arguments
+/// - `spectrum`: FFT data of one channel.
+/// - `range`: The `(min_index, max_index)` range of bins to use.
+/// - `bins`: Number of bins to produce.
+/// - `gain`: Gain applied to the magnitudes.
+///
+/// # Returns
+/// - For each bin, the log10 of its loudest gained magnitude, floored at zero.
+pub fn bin_heights(
+    spectrum: &[Complex32],
+    range: (usize, usize),
+    bins: usize,
+    gain: f32,
+) -> Vec<f32> {
+    let (min_index, max_index) = range;
+    let width = max_index - min_index;
+
+    (0..bins)
+        .map(|bin| {
+            let start = min_index + bin * width / bins;
+            let end = (min_index + (bin + 1) * width / bins).max(start + 1);
+            let peak = spectrum[start..end.min(spectrum.len())]
+                .iter()
+                .map(|value| value.norm())
+                .fold(0.0, f32::max);
+            (peak * gain + 1e-6).log10().max(0.0)
+        })
+        .collect()
+}
+
+/// Computes the root mean square of `samples`.
+pub fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+/// Encodes one frame as an OSC bundle.
+///
+/// # Arguments
+/// - `left`: Bar heights of the left channel.
+/// - `right`: Bar heights of the right channel.
+/// - `rms`: RMS levels of the left and right channel.
+///
+/// # Returns
+/// - The bundle, ready to be sent as one datagram.
+pub fn encode_bundle(left: &[f32], right: &[f32], rms: (f32, f32)) -> Vec<u8> {
+    let mut bundle = Vec::new();
+    write_osc_string(&mut bundle, "#bundle");
+    bundle.extend_from_slice(&OSC_IMMEDIATELY.to_be_bytes());
+
+    for message in [
+        encode_message("/sonic_spectra/left", left),
+        encode_message("/sonic_spectra/right", right),
+        encode_message("/sonic_spectra/rms", &[rms.0, rms.1]),
+    ] {
+        bundle.extend_from_slice(&(message.len() as i32).to_be_bytes());
+        bundle.extend_from_slice(&message);
+    }
+    bundle
+}
+
+/// Encodes an OSC message with float arguments.
+fn encode_message(address: &str, arguments: &[f32]) -> Vec<u8> {
+    let mut message = Vec::new();
+    write_osc_string(&mut message, address);
+    write_osc_string(&mut message, &format!(",{}", "f".repeat(arguments.len())));
+    for argument in arguments {
+        message.extend_from_slice(&argument.to_be_bytes());
+    }
+    message
+}
+
+/// Writes a null-terminated string padded to a multiple of four bytes.
+fn write_osc_string(buffer: &mut Vec<u8>, value: &str) {
+    buffer.extend_from_slice(value.as_bytes());
+    let padding = 4 - value.len() % 4;
+    buffer.resize(buffer.len() + padding, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads a padded OSC string, returning it and the bytes after it.
+    fn read_osc_string(bytes: &[u8]) -> (&str, &[u8]) {
+        let end = bytes.iter().position(|&byte| byte == 0).unwrap();
+        let padded = (end / 4 + 1) * 4;
+        (
+            std::str::from_utf8(&bytes[..end]).unwrap(),
+            &bytes[padded..],
+        )
+    }
+
+    /// Decodes a bundle into its messages' addresses and float arguments.
+    fn decode_bundle(bytes: &[u8]) -> Vec<(String, Vec<f32>)> {
+        let (tag, mut rest) = read_osc_string(bytes);
+        assert_eq!(tag, "#bundle");
+        assert_eq!(u64::from_be_bytes(rest[..8].try_into().unwrap()), 1);
+        rest = &rest[8..];
+
+        let mut messages = Vec::new();
+        while !rest.is_empty() {
+            let size = i32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let message = &rest[4..4 + size];
+            rest = &rest[4 + size..];
+
+            let (address, message) = read_osc_string(message);
+            let (types, arguments) = read_osc_string(message);
+            assert!(types.starts_with(',') && types[1..].chars().all(|c| c == 'f'));
+            let arguments: Vec<f32> = arguments
+                .chunks_exact(4)
+                .map(|bytes| f32::from_be_bytes(bytes.try_into().unwrap()))
+                .collect();
+            assert_eq!(arguments.len(), types.len() - 1);
+            messages.push((address.to_string(), arguments));
+        }
+        messages
+    }
+
+    #[test]
+    fn strings_are_padded_to_four_bytes() {
+        let mut buffer = Vec::new();
+        write_osc_string(&mut buffer, "abcd");
+        assert_eq!(buffer, b"abcd\0\0\0\0");
+
+        buffer.clear();
+        write_osc_string(&mut buffer, ",ff");
+        assert_eq!(buffer, b",ff\0");
+    }
+
+    #[test]
+    fn bins_hold_the_loudest_magnitude() {
+        let mut spectrum = vec![Complex32::new(0.0, 0.0); 8];
+        spectrum[2] = Complex32::new(10.0, 0.0);
+        spectrum[5] = Complex32::new(100.0, 0.0);
+
+        let heights = bin_heights(&spectrum, (0, 8), 2, 1.0);
+        assert!((heights[0] - 1.0).abs() < 1e-4);
+        assert!((heights[1] - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn sends_frames_to_a_udp_socket() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        let mut settings = Settings::default();
+        settings.output.osc_address = Some(receiver.local_addr().unwrap().to_string());
+        settings.output.osc_bins = 4;
+        settings.output.osc_rate_hz = 10.0;
+        let fft_size = settings.fft.size;
+        let mut output = OscOutput::from_settings(Arc::new(settings)).unwrap();
+
+        let samples = vec![0.5; fft_size];
+        let spectrum = vec![Complex32::new(0.0, 0.0); fft_size];
+        output.send(Duration::ZERO, (&samples, &samples), (&spectrum, &spectrum));
+        // Within the rate limit, so not sent
+        output.send(
+            Duration::from_millis(50),
+            (&samples, &samples),
+            (&spectrum, &spectrum),
+        );
+
+        let mut buffer = [0u8; 1024];
+        let size = receiver.recv(&mut buffer).unwrap();
+        let messages = decode_bundle(&buffer[..size]);
+        assert_eq!(
+            messages,
+            [
+                ("/sonic_spectra/left".to_string(), vec![0.0; 4]),
+                ("/sonic_spectra/right".to_string(), vec![0.0; 4]),
+                ("/sonic_spectra/rms".to_string(), vec![0.5, 0.5]),
+            ]
+        );
+
+        receiver.set_nonblocking(true).unwrap();
+        assert!(receiver.recv(&mut buffer).is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use crate::settings::Settings;
+use tokio::runtime::Handle;
+use tokio::sync::watch;
+
+/// Where the machine draws its power from, as reported by UPower.
+///
+/// - `LinePower`: Plugged in, or no battery; the configured settings apply.
+/// - `Battery`: Running on battery; the frame rate drops to `power.battery_fps` and the
+///   expensive effects are turned off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerState {
+    LinePower,
+    Battery,
+}
+
+impl PowerState {
+    /// Returns the state matching UPower's `OnBattery` property.
+    #[cfg_attr(not(feature = "battery"), allow(dead_code))]
+    pub fn from_on_battery(on_battery: bool) -> Self {
+        if on_battery {
+            PowerState::Battery
+        } else {
+            PowerState::LinePower
+        }
+    }
+
+    /// Returns the settings to draw with in this state.
+    ///
+    /// On battery the trails, the reflection and the gradients of the holographic glow bars are
+    /// turned off; on line power the settings are returned as configured.
+    ///
+    /// # Arguments
+    /// - `settings`: The configured settings, restored once back on line power.
+    pub fn adapt(self, settings: &Settings) -> Settings {
+        let mut adapted = settings.clone();
+        if self == PowerState::Battery {
+            let effects = &mut adapted.effects;
+            effects.persistence = 0.0;
+            effects.reflection = false;
+            effects.glow_gradients = false;
+        }
+        adapted
+    }
+
+    /// Returns the message logged when the machine switches to this state, so the drop in
+    /// quality on battery does not come as a surprise.
+    ///
+    /// # Arguments
+    /// - `battery_fps`: The frame rate drawn at on battery, `power.battery_fps`.
+    #[cfg_attr(not(feature = "battery"), allow(dead_code))]
+    pub fn change_message(self, battery_fps: f32) -> String {
+        match self {
+            PowerState::Battery => format!(
+                "On battery power: drawing at most {} fps without trails, reflection and glow \
+                 gradients (power.adaptive).",
+                battery_fps
+            ),
+            PowerState::LinePower => {
+                "On line power: restored the configured frame rate and effects.".to_string()
+            }
+        }
+    }
+}
+
+/// Starts watching the power source if `power.adaptive` is set.
+///
+/// # Arguments
+/// - `settings`: Settings containing the `[power]` configuration.
+/// - `runtime`: Runtime the D-Bus listener runs on.
+///
+/// # Returns
+/// - A receiver of the current power state, starting at `LinePower`, or `None` if adaptive
+///   power use is disabled or unavailable.
+pub fn start(settings: &Settings, runtime: &Handle) -> Option<watch::Receiver<PowerState>> {
+    if !settings.power.adaptive {
+        return None;
+    }
+
+    #[cfg(feature = "battery")]
+    {
+        let (tx, rx) = watch::channel(PowerState::LinePower);
+        runtime.spawn(crate::upower::watch_power(tx, settings.power.battery_fps));
+        Some(rx)
+    }
+
+    #[cfg(not(feature = "battery"))]
+    {
+        let _ = runtime;
+        eprintln!(
+            "power.adaptive is set, but sonic_spectra was built without the battery feature."
+        );
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn battery_turns_off_the_expensive_effects() {
+        let mut settings = Settings::default();
+        settings.effects.persistence = 0.6;
+        settings.effects.reflection = true;
+
+        let adapted = PowerState::Battery.adapt(&settings);
+        assert_eq!(adapted.effects.persistence, 0.0);
+        assert!(!adapted.effects.reflection);
+        assert!(!adapted.effects.glow_gradients);
+        // The rest of the configuration is kept
+        assert_eq!(
+            adapted.effects.reflection_alpha,
+            settings.effects.reflection_alpha
+        );
+
+        let restored = PowerState::LinePower.adapt(&settings);
+        assert_eq!(restored.effects.persistence, 0.6);
+        assert!(restored.effects.reflection);
+        assert!(restored.effects.glow_gradients);
+    }
+
+    #[test]
+    fn changes_are_explained() {
+        assert_eq!(PowerState::from_on_battery(true), PowerState::Battery);
+        assert_eq!(PowerState::from_on_battery(false), PowerState::LinePower);
+        assert!(PowerState::Battery.change_message(20.0).contains("20 fps"));
+        assert!(PowerState::LinePower
+            .change_message(20.0)
+            .contains("restored"));
+    }
+}
@@ -0,0 +1,218 @@
+use crate::settings::{PaletteKind, Settings, CONFIG_PATH};
+use crate::Controls;
+use gtk::prelude::*;
+use gtk::{ApplicationWindow, Button, CheckButton, DropDown, Grid, Label, SpinButton};
+use gtk4 as gtk;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Spacing between the rows and columns of the preferences window, in pixels.
+const SPACING: i32 = 8;
+
+/// A numeric setting edited with a spin button: its label, its range and step, its current
+/// value, and the function storing a new value.
+type SpinButtonRow = (&'static str, (f64, f64, f64), f32, fn(&mut Settings, f32));
+
+/// Palettes offered in the preferences window, with their labels.
+const PALETTES: [(&str, PaletteKind); 5] = [
+    ("Rainbow", PaletteKind::Rainbow),
+    ("Viridis", PaletteKind::Viridis),
+    ("Inferno", PaletteKind::Inferno),
+    ("Mono", PaletteKind::Mono),
+    ("Custom", PaletteKind::Custom),
+];
+
+/// Opens a window for tuning the visualizer while it runs.
+///
+/// # Arguments
+/// - `parent`: The main window, which the preferences window stays on top of.
+/// - `controls`: The flags and live settings shared with the draw function.
+///
+/// # Returns
+/// - The preferences window, already shown.
+///
+/// Every change is applied from the next frame on. A change that fails validation is not applied
+/// and the reason is shown at the bottom of the window. The values edit the common `[visualizer]`
+/// settings, so a `[visualizer.<kind>]` table setting the same key still takes precedence.
+///
+/// "Save" writes the applied settings, including the grid toggles, to `resources/config.toml`.
+pub fn show(parent: &ApplicationWindow, controls: &Controls) -> gtk::Window {
+    let window = gtk::Window::new();
+    window.set_title(Some("Preferences"));
+    window.set_transient_for(Some(parent));
+    window.set_resizable(false);
+
+    let grid = Grid::new();
+    grid.set_row_spacing(SPACING as u32);
+    grid.set_column_spacing(2 * SPACING as u32);
+    grid.set_margin_top(2 * SPACING);
+    grid.set_margin_bottom(2 * SPACING);
+    grid.set_margin_start(2 * SPACING);
+    grid.set_margin_end(2 * SPACING);
+    let status = Label::new(None);
+    status.set_xalign(0.0);
+
+    let settings = controls.settings.lock().unwrap().clone();
+    let visualizer = &settings.visualizer;
+    let nyquist = settings.fft.sample_rate as f64 / 2.0;
+    let spin_buttons: [SpinButtonRow; 6] = [
+        ("Gain", (0.0, 1000.0, 0.5), visualizer.gain, |s, v| {
+            s.visualizer.gain = v
+        }),
+        (
+            "Scale factor",
+            (0.0, 1000.0, 1.0),
+            visualizer.scale_factor,
+            |s, v| s.visualizer.scale_factor = v,
+        ),
+        (
+            "Smoothing (ms)",
+            (0.0, 2000.0, 5.0),
+            visualizer.smoothing_ms(),
+            |s, v| {
+                s.visualizer.smoothing_ms = Some(v);
+                // A chosen time replaces the one picked for the latency target
+                s.visualizer.target_smoothing_ms = None;
+            },
+        ),
+        ("Alpha", (0.0, 1.0, 0.01), visualizer.alpha, |s, v| {
+            s.visualizer.alpha = v
+        }),
+        (
+            "Lowest frequency (Hz)",
+            (1.0, nyquist, 10.0),
+            settings.fft.min_frequency,
+            |s, v| s.fft.min_frequency = v,
+        ),
+        (
+            "Highest frequency (Hz)",
+            (1.0, nyquist, 10.0),
+            settings.fft.max_frequency,
+            |s, v| s.fft.max_frequency = v,
+        ),
+    ];
+    let mut row = 0;
+    for (label, range, value, update) in spin_buttons {
+        let spin_button = add_spin_button(&grid, row, label, range, value);
+        let (controls, status) = (controls.clone(), status.clone());
+        spin_button.connect_value_changed(move |spin_button| {
+            let value = spin_button.value() as f32;
+            update_settings(&controls, &status, |s| update(s, value));
+        });
+        row += 1;
+    }
+
+    let palette = DropDown::from_strings(&PALETTES.map(|(label, _)| label));
+    let selected = PALETTES
+        .iter()
+        .position(|(_, kind)| *kind == visualizer.palette.unwrap_or_default())
+        .unwrap_or(0);
+    palette.set_selected(selected as u32);
+    {
+        let (controls, status) = (controls.clone(), status.clone());
+        palette.connect_selected_notify(move |palette| {
+            if let Some((_, kind)) = PALETTES.get(palette.selected() as usize) {
+                // Picking a palette ends a palette cycle, which cannot be combined with it
+                update_settings(&controls, &status, |s| {
+                    s.visualizer.palette = Some(*kind);
+                    s.visualizer.palette_cycle.clear();
+                });
+            }
+        });
+    }
+    add_row(&grid, row, "Palette", &palette);
+    row += 1;
+
+    // The grid toggles share the flags of the G, H and M keys
+    for (label, flag) in [
+        ("Show grid", &controls.show_grid),
+        ("Horizontal grid lines", &controls.show_grid_horizontal),
+        ("Vertical frequency markers", &controls.show_grid_vertical),
+    ] {
+        grid.attach(&toggle(label, flag.clone()), 1, row, 1, 1);
+        row += 1;
+    }
+
+    let save = Button::with_label("Save");
+    {
+        let (controls, status) = (controls.clone(), status.clone());
+        save.connect_clicked(move |_| {
+            let mut settings = controls.settings.lock().unwrap().clone();
+            let visibility = controls.grid_visibility();
+            settings.grid.enabled = visibility.enabled;
+            settings.grid.show_horizontal = visibility.horizontal;
+            settings.grid.show_vertical = visibility.vertical;
+            match settings.save(Path::new(CONFIG_PATH)) {
+                Ok(()) => status.set_text(&format!("Saved to {}", CONFIG_PATH)),
+                Err(e) => {
+                    eprintln!("Failed to save the settings to {}: {}", CONFIG_PATH, e);
+                    status.set_text(&format!("Failed to save: {}", e));
+                }
+            }
+        });
+    }
+    grid.attach(&status, 0, row, 1, 1);
+    grid.attach(&save, 1, row, 1, 1);
+
+    window.set_child(Some(&grid));
+    window.present();
+    window
+}
+
+/// Adds a labeled spin button showing `value` to a row of the grid.
+fn add_spin_button(
+    grid: &Grid,
+    row: i32,
+    label: &str,
+    (min, max, step): (f64, f64, f64),
+    value: f32,
+) -> SpinButton {
+    let spin_button = SpinButton::with_range(min, max, step);
+    spin_button.set_digits(if step < 1.0 { 2 } else { 0 });
+    spin_button.set_value(value as f64);
+    add_row(grid, row, label, &spin_button);
+    spin_button
+}
+
+/// Adds a label and the widget it describes to a row of the grid.
+fn add_row(grid: &Grid, row: i32, label: &str, widget: &impl IsA<gtk::Widget>) {
+    let label = Label::new(Some(label));
+    label.set_xalign(0.0);
+    grid.attach(&label, 0, row, 1, 1);
+    grid.attach(widget, 1, row, 1, 1);
+}
+
+/// Creates a check button that shows and sets `flag`.
+fn toggle(label: &str, flag: Arc<AtomicBool>) -> CheckButton {
+    let check_button = CheckButton::with_label(label);
+    check_button.set_active(flag.load(Ordering::Relaxed));
+    check_button.connect_toggled(move |check_button| {
+        flag.store(check_button.is_active(), Ordering::Relaxed)
+    });
+    check_button
+}
+
+/// Applies a change to the live settings if the changed settings are valid.
+///
+/// # Arguments
+/// - `controls`: The live settings, and the flag telling the draw function to apply them.
+/// - `status`: Label showing why an invalid change was not applied.
+/// - `update`: The change to apply.
+fn update_settings(controls: &Controls, status: &Label, update: impl FnOnce(&mut Settings)) {
+    let mut settings = controls.settings.lock().unwrap();
+    let mut changed = settings.clone();
+    update(&mut changed);
+    match changed.validate() {
+        Ok(()) => {
+            *settings = changed;
+            controls.settings_changed.store(true, Ordering::Relaxed);
+            controls.redraw.store(true, Ordering::Relaxed);
+            status.set_text("");
+        }
+        Err(errors) => {
+            let errors: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+            status.set_text(&format!("Not applied: {}", errors.join("; ")));
+        }
+    }
+}
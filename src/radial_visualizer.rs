@@ -0,0 +1,358 @@
+use crate::color::Palette;
+use crate::fft_utils::{frequency_indices, get_bar_color, interpolate, smoothing_factor};
+use crate::level_scale::LevelScale;
+use crate::settings::{ResolvedVisualizerSettings, Settings};
+use crate::smoothing_state::SmoothingState;
+use crate::visualizer::Visualizer;
+use gtk::cairo::Context;
+use gtk4 as gtk;
+use rustfft::num_complex::Complex32;
+use std::f64::consts::{FRAC_PI_2, PI};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Fraction of each angular slot covered by a bar; the rest is left as a gap.
+const BAR_FILL: f64 = 0.8;
+
+/// The semicircle a channel is drawn on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Side {
+    Left,
+    Right,
+}
+
+/// Geometry shared by every bar of a frame.
+///
+/// # Fields
+/// - `center`: Center of the circle in pixels.
+/// - `inner_radius`: Radius at which bars start, in pixels.
+/// - `radial_scale`: Factor converting a bar height in pixels into a radial length.
+/// - `step`: Angle between neighboring bars, in radians.
+/// - `rotation`: Rotation applied to the whole circle, in radians.
+struct RadialLayout {
+    center: (f64, f64),
+    inner_radius: f64,
+    radial_scale: f64,
+    step: f64,
+    rotation: f64,
+}
+
+/// A visualizer arranging the frequency bars around a circle, with the left channel on the
+/// left semicircle and the right channel on the right one.
+pub struct RadialVisualizer {
+    settings: Arc<Settings>,
+    visual_settings: ResolvedVisualizerSettings,
+    palette: Palette,
+    level_scale: LevelScale,
+    start: Instant,
+}
+
+impl RadialVisualizer {
+    /// Creates a new `RadialVisualizer` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - Shared application settings to configure visualizer parameters.
+    pub fn new(settings: Arc<Settings>) -> Self {
+        let visual_settings = settings.visualizer_settings("radial");
+        let palette = Palette::from_settings(&visual_settings);
+        let level_scale = LevelScale::new(&visual_settings, settings.fft.size);
+        RadialVisualizer {
+            settings,
+            visual_settings,
+            palette,
+            level_scale,
+            start: Instant::now(),
+        }
+    }
+
+    /// Draws the bars of one channel on its semicircle.
+    ///
+    /// # Arguments
+    ///
+    /// * `cr` - The Cairo context for drawing.
+    /// * `side` - The semicircle the channel is drawn on.
+    /// * `fft` - FFT data of the channel, already restricted to the visible range.
+    /// * `height` - The height of the drawing area, used to normalize bar heights.
+    /// * `layout` - Geometry shared by every bar of the frame.
+    /// * `previous_heights` - The previous frame's heights for smooth transitions.
+    /// * `factor` - Fraction of the change in height the smoothed heights move this frame.
+    fn draw_channel(
+        &self,
+        cr: &Context,
+        side: Side,
+        fft: &[Complex32],
+        height: i32,
+        layout: &RadialLayout,
+        previous_heights: &mut [f32],
+        factor: f32,
+    ) {
+        let visual_settings = &self.visual_settings;
+        let num_bars = fft.len();
+        let half_width = layout.step * BAR_FILL / 2.0;
+
+        for (i, value) in fft.iter().enumerate() {
+            let target_height = self.level_scale.height(value.norm());
+
+            previous_heights[i] = interpolate(previous_heights[i], target_height, factor);
+
+            let level = previous_heights[i] / height as f32;
+            let color = get_bar_color(
+                &self.palette,
+                visual_settings.color_mode,
+                i,
+                num_bars,
+                level,
+            );
+            cr.set_source_rgba(
+                color.0 as f64,
+                color.1 as f64,
+                color.2 as f64,
+                (visual_settings.alpha * color.3) as f64,
+            );
+
+            let angle = bar_angle(i, layout.step, side, layout.rotation);
+            let length = previous_heights[i] as f64 * layout.radial_scale;
+            let corners = bar_quad(
+                layout.center,
+                angle,
+                half_width,
+                layout.inner_radius,
+                layout.inner_radius + length,
+            );
+
+            cr.move_to(corners[0].0, corners[0].1);
+            for corner in &corners[1..] {
+                cr.line_to(corner.0, corner.1);
+            }
+            cr.close_path();
+            let _ = cr.fill();
+        }
+    }
+}
+
+impl Visualizer for RadialVisualizer {
+    /// Draws the frequency bars radiating outward around the center of the drawing area.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the drawing area.
+    /// * `height` - The height of the drawing area.
+    /// * `fft_left` - FFT data for the left audio channel.
+    /// * `fft_right` - FFT data for the right audio channel.
+    /// * `cr` - The Cairo context for drawing.
+    /// * `previous_heights_left` - Stores the previous frame's left channel heights for smooth transitions.
+    /// * `previous_heights_right` - Stores the previous frame's right channel heights for smooth transitions.
+    /// * `elapsed` - Time since the previous frame in seconds, setting how far the bars move.
+    fn draw(
+        &self,
+        width: i32,
+        height: i32,
+        fft_left: &[Complex32],
+        fft_right: &[Complex32],
+        cr: &Context,
+        previous_heights_left: &mut SmoothingState,
+        previous_heights_right: &mut SmoothingState,
+        elapsed: f32,
+    ) {
+        let radial_settings = &self.settings.radial;
+
+        let fft_size = fft_left.len();
+        let (min_index, max_index) = frequency_indices(&self.settings.fft, fft_size);
+
+        // Select the FFT data range for visualization
+        let fft_left = &fft_left[min_index..max_index];
+        let fft_right = &fft_right[min_index..max_index];
+        previous_heights_left.ensure_len(fft_left.len());
+        previous_heights_right.ensure_len(fft_right.len());
+
+        let center = (width as f64 / 2.0, height as f64 / 2.0);
+        let max_radius = center.0.min(center.1);
+        let inner_radius = max_radius * radial_settings.inner_radius.clamp(0.0, 1.0);
+
+        // Spin the circle based on the time elapsed since the visualizer was created
+        let running = self.start.elapsed().as_secs_f64();
+        let rotation = radial_settings.rotation_offset + radial_settings.rotation_speed * running;
+        let factor = smoothing_factor(elapsed, self.visual_settings.smoothing_ms / 1000.0);
+
+        let layout = RadialLayout {
+            center,
+            inner_radius,
+            radial_scale: (max_radius - inner_radius) / (height as f64).max(1.0),
+            step: bar_angle_step(fft_left.len(), radial_settings.angle_step.to_radians()),
+            rotation: rotation.to_radians(),
+        };
+
+        self.draw_channel(
+            cr,
+            Side::Left,
+            fft_left,
+            height,
+            &layout,
+            previous_heights_left,
+            factor,
+        );
+        self.draw_channel(
+            cr,
+            Side::Right,
+            fft_right,
+            height,
+            &layout,
+            previous_heights_right,
+            factor,
+        );
+    }
+
+    fn needs_animation(&self) -> bool {
+        self.settings.radial.rotation_speed != 0.0
+    }
+
+    fn set_palette(&mut self, palette: &Palette) {
+        self.palette = palette.clone();
+    }
+}
+
+/// Computes the angle between neighboring bars.
+///
+/// # Arguments
+/// - `num_bars`: The number of bars drawn on each semicircle.
+/// - `requested_step`: The configured step in radians; `0.0` or less spreads the bars evenly.
+///
+/// # Returns
+/// - The step in radians, never larger than what fits `num_bars` bars into a semicircle.
+fn bar_angle_step(num_bars: usize, requested_step: f64) -> f64 {
+    let max_step = PI / num_bars.max(1) as f64;
+    if requested_step <= 0.0 {
+        max_step
+    } else {
+        requested_step.min(max_step)
+    }
+}
+
+/// Computes the center angle of a bar.
+///
+/// # Arguments
+/// - `index`: The index of the bar, starting with the lowest frequency at the top.
+/// - `step`: The angle between neighboring bars, in radians.
+/// - `side`: The semicircle the bar belongs to.
+/// - `rotation`: Rotation applied to the whole circle, in radians.
+///
+/// # Returns
+/// - The angle in radians, measured clockwise from the positive x-axis in screen coordinates.
+fn bar_angle(index: usize, step: f64, side: Side, rotation: f64) -> f64 {
+    let offset = (index as f64 + 0.5) * step;
+    match side {
+        Side::Right => -FRAC_PI_2 + offset + rotation,
+        Side::Left => -FRAC_PI_2 - offset + rotation,
+    }
+}
+
+/// Computes the corners of a bar radiating from the center.
+///
+/// # Arguments
+/// - `center`: Center of the circle in pixels.
+/// - `angle`: Center angle of the bar, in radians.
+/// - `half_width`: Half of the angular width of the bar, in radians.
+/// - `inner_radius`: Radius at which the bar starts.
+/// - `outer_radius`: Radius at which the bar ends.
+///
+/// # Returns
+/// - The four corners of the bar, in drawing order.
+fn bar_quad(
+    center: (f64, f64),
+    angle: f64,
+    half_width: f64,
+    inner_radius: f64,
+    outer_radius: f64,
+) -> [(f64, f64); 4] {
+    let outer_radius = outer_radius.max(inner_radius);
+    let polar = |radius: f64, theta: f64| {
+        (
+            center.0 + radius * theta.cos(),
+            center.1 + radius * theta.sin(),
+        )
+    };
+
+    [
+        polar(inner_radius, angle - half_width),
+        polar(outer_radius, angle - half_width),
+        polar(outer_radius, angle + half_width),
+        polar(inner_radius, angle + half_width),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    #[test]
+    fn step_defaults_to_even_spread_over_a_semicircle() {
+        assert!((bar_angle_step(4, 0.0) - PI / 4.0).abs() < EPSILON);
+        assert!((bar_angle_step(0, 0.0) - PI).abs() < EPSILON);
+    }
+
+    #[test]
+    fn step_is_clamped_so_channels_do_not_overlap() {
+        assert!((bar_angle_step(10, 1.0) - PI / 10.0).abs() < EPSILON);
+        assert!((bar_angle_step(10, 0.1) - 0.1).abs() < EPSILON);
+    }
+
+    #[test]
+    fn bars_are_evenly_spaced_and_stay_on_their_side() {
+        let num_bars = 16;
+        let step = bar_angle_step(num_bars, 0.0);
+
+        for i in 0..num_bars - 1 {
+            let right = bar_angle(i, step, Side::Right, 0.0);
+            let next_right = bar_angle(i + 1, step, Side::Right, 0.0);
+            assert!((next_right - right - step).abs() < EPSILON);
+            // Right semicircle has a positive x component
+            assert!(right.cos() > 0.0);
+
+            let left = bar_angle(i, step, Side::Left, 0.0);
+            let next_left = bar_angle(i + 1, step, Side::Left, 0.0);
+            assert!((left - next_left - step).abs() < EPSILON);
+            assert!(left.cos() < 0.0);
+        }
+    }
+
+    #[test]
+    fn neighboring_bars_do_not_overlap() {
+        let num_bars = 32;
+        let step = bar_angle_step(num_bars, 0.0);
+        let half_width = step * BAR_FILL / 2.0;
+
+        for i in 0..num_bars - 1 {
+            let current = bar_angle(i, step, Side::Right, 0.3);
+            let next = bar_angle(i + 1, step, Side::Right, 0.3);
+            assert!(current + half_width < next - half_width);
+        }
+    }
+
+    #[test]
+    fn bars_never_reach_inside_the_inner_radius() {
+        let center = (200.0, 100.0);
+        let inner_radius = 40.0;
+
+        for i in 0..64 {
+            let angle = bar_angle(i, PI / 64.0, Side::Left, 1.2);
+            for outer_radius in [0.0, 40.0, 95.0] {
+                let corners = bar_quad(center, angle, 0.02, inner_radius, outer_radius);
+                for (x, y) in corners {
+                    let distance = ((x - center.0).powi(2) + (y - center.1).powi(2)).sqrt();
+                    assert!(distance >= inner_radius - EPSILON);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn quad_spans_from_inner_to_outer_radius() {
+        let corners = bar_quad((0.0, 0.0), 0.0, 0.0, 10.0, 25.0);
+        assert!((corners[0].0 - 10.0).abs() < EPSILON);
+        assert!((corners[1].0 - 25.0).abs() < EPSILON);
+        assert!(corners[0].1.abs() < EPSILON && corners[1].1.abs() < EPSILON);
+    }
+}
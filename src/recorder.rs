@@ -0,0 +1,164 @@
+use crate::wav::WavWriter;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+
+/// Number of captured chunks that may wait for the writer thread before new ones are dropped.
+const RECORDING_QUEUE_LEN: usize = 256;
+/// Number of channels written to recordings.
+const RECORDING_CHANNELS: u16 = 2;
+/// A recording in progress.
+///
+/// # Fields
+/// - `sender`: Queue of interleaved stereo chunks consumed by the writer thread.
+/// - `writer`: The thread writing the file; finalizes it once `sender` is dropped.
+/// - `path`: The file being written.
+struct RecordingSession {
+    sender: SyncSender<Vec<f32>>,
+    writer: JoinHandle<io::Result<()>>,
+    path: PathBuf,
+}
+
+/// Records the captured audio to WAV files.
+///
+/// The audio thread hands chunks to `push` without blocking; a writer thread per recording
+/// writes them to disk. Chunks arriving while the queue is full are dropped and counted.
+///
+/// # Fields
+/// - `session`: The recording in progress, if any.
+/// - `recording`: Whether a recording is in progress, readable without locking `session`.
+/// - `sample_rate`: Sample rate of the captured audio, in Hz.
+/// - `dropped`: Number of chunks dropped during the current recording.
+pub struct Recorder {
+    session: Mutex<Option<RecordingSession>>,
+    recording: AtomicBool,
+    sample_rate: AtomicU32,
+    dropped: AtomicUsize,
+}
+
+impl Recorder {
+    /// Creates a new `Recorder` instance.
+    ///
+    /// # Arguments
+    /// - `sample_rate`: Sample rate assumed until the audio device reports its own.
+    pub fn new(sample_rate: u32) -> Self {
+        Recorder {
+            session: Mutex::new(None),
+            recording: AtomicBool::new(false),
+            sample_rate: AtomicU32::new(sample_rate),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// Sets the sample rate written to recordings started afterwards.
+    pub fn set_sample_rate(&self, sample_rate: u32) {
+        self.sample_rate.store(sample_rate, Ordering::Relaxed);
+    }
+
+    /// Returns `true` while a recording is in progress.
+    pub fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::Relaxed)
+    }
+
+    /// Starts recording to `path`, stopping any recording in progress first.
+    ///
+    /// # Returns
+    /// - `Result` with no value once the file has been created, or an error if it cannot be.
+    pub fn start(&self, path: PathBuf) -> io::Result<()> {
+        self.stop();
+
+        let mut wav = WavWriter::create(
+            &path,
+            RECORDING_CHANNELS,
+            self.sample_rate.load(Ordering::Relaxed),
+        )?;
+        let (sender, receiver) = mpsc::sync_channel::<Vec<f32>>(RECORDING_QUEUE_LEN);
+        let writer = thread::spawn(move || {
+            for chunk in receiver {
+                wav.write_samples(&chunk)?;
+            }
+            wav.finalize()?;
+            Ok(())
+        });
+
+        self.dropped.store(0, Ordering::Relaxed);
+        *self.session.lock().unwrap() = Some(RecordingSession {
+            sender,
+            writer,
+            path,
+        });
+        self.recording.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Stops the recording in progress and waits for its file to be finalized.
+    ///
+    /// # Returns
+    /// - The path of the finished file, an error if writing it failed, or `None` if no recording
+    ///   was in progress.
+    pub fn stop(&self) -> Option<io::Result<PathBuf>> {
+        let session = self.session.lock().unwrap().take()?;
+        self.recording.store(false, Ordering::Relaxed);
+
+        // Dropping the sender ends the writer thread's loop
+        drop(session.sender);
+        let result = match session.writer.join() {
+            Ok(result) => result,
+            Err(_) => Err(io::Error::other("recording writer thread panicked")),
+        };
+
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            eprintln!("Recording dropped {} chunks of audio.", dropped);
+        }
+
+        Some(result.map(|()| session.path))
+    }
+
+    /// Queues interleaved stereo samples for the recording in progress.
+    ///
+    /// Never blocks: the samples are dropped if the recorder is busy or its queue is full.
+    pub fn push(&self, samples: Vec<f32>) {
+        let Ok(session) = self.session.try_lock() else {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+
+        if let Some(session) = session.as_ref() {
+            if let Err(TrySendError::Full(_)) = session.sender.try_send(samples) {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wav::read_wav;
+
+    #[test]
+    fn recorder_finalizes_the_file_on_stop() {
+        let path = std::env::temp_dir().join(format!(
+            "sonic_spectra_recorder_test_{}.wav",
+            std::process::id()
+        ));
+        let recorder = Recorder::new(44100);
+
+        recorder.start(path.clone()).unwrap();
+        assert!(recorder.is_recording());
+        recorder.push(vec![0.25, -0.25, 0.5, -0.5]);
+        let finished = recorder.stop().unwrap().unwrap();
+        assert!(!recorder.is_recording());
+        assert!(recorder.stop().is_none());
+
+        let bytes = std::fs::read(&finished).unwrap();
+        std::fs::remove_file(&finished).unwrap();
+        let wav = read_wav(&bytes).unwrap();
+        assert_eq!((wav.channels, wav.sample_rate), (2, 44100));
+        assert_eq!(wav.samples, [0.25, -0.25, 0.5, -0.5]);
+    }
+}
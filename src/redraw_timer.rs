@@ -0,0 +1,215 @@
+use crate::settings::PowerSettings;
+use gtk::prelude::*;
+use gtk::{gdk, glib, DrawingArea};
+use gtk4 as gtk;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Timer queueing a redraw of a drawing area at a fixed interval.
+///
+/// The frames are analyzed in the draw function, so stopping the timer pauses both drawing and
+/// analysis while the audio keeps being captured. With a frame check set, ticks that would draw
+/// the same frame again are skipped.
+///
+/// # Fields
+/// - `drawing_area`: The redrawn drawing area; a weak reference lets embedding applications
+///   destroy it.
+/// - `interval`: Time between redraws.
+/// - `idle_interval`: Time between redraws while the input is silent.
+/// - `battery_interval`: Shortest time between redraws while on battery.
+/// - `pause_when_hidden`: Whether redraws pause while the drawing area is unmapped or its window
+///   is minimized.
+/// - `idle`: Whether the input is silent, as last reported through `set_idle`.
+/// - `on_battery`: Whether the machine runs on battery, as last reported through
+///   `set_on_battery`.
+/// - `source`: The running timer, or `None` while paused.
+/// - `frame_check`: Returns whether the next frame would differ from the last one; without it,
+///   every tick redraws.
+/// - `skipped`: Ticks that skipped the redraw since the last call to `take_skipped`.
+pub struct RedrawTimer {
+    drawing_area: glib::WeakRef<DrawingArea>,
+    interval: Duration,
+    idle_interval: Duration,
+    battery_interval: Duration,
+    pause_when_hidden: bool,
+    idle: Cell<bool>,
+    on_battery: Cell<bool>,
+    source: RefCell<Option<glib::SourceId>>,
+    frame_check: RefCell<Option<Box<dyn Fn() -> bool>>>,
+    skipped: Cell<usize>,
+}
+
+impl RedrawTimer {
+    /// Creates a new `RedrawTimer` instance; redraws begin with `start`.
+    ///
+    /// # Arguments
+    /// - `drawing_area`: The drawing area to redraw.
+    /// - `interval`: Time between redraws.
+    /// - `settings`: Power settings with the idle and battery frame rates and whether to pause
+    ///   while hidden.
+    pub fn new(
+        drawing_area: &DrawingArea,
+        interval: Duration,
+        settings: &PowerSettings,
+    ) -> Rc<Self> {
+        Rc::new(RedrawTimer {
+            drawing_area: drawing_area.downgrade(),
+            interval,
+            idle_interval: Duration::from_secs_f32(1.0 / settings.idle_fps),
+            battery_interval: Duration::from_secs_f32(1.0 / settings.battery_fps),
+            pause_when_hidden: settings.pause_when_hidden,
+            idle: Cell::new(false),
+            on_battery: Cell::new(false),
+            source: RefCell::new(None),
+            frame_check: RefCell::new(None),
+            skipped: Cell::new(0),
+        })
+    }
+
+    /// Skips the redraws of ticks for which `check` returns `false`, because the frame would
+    /// look the same as the one drawn last.
+    ///
+    /// # Arguments
+    /// - `check`: Returns whether the next frame would differ from the last one.
+    pub fn set_frame_check(&self, check: impl Fn() -> bool + 'static) {
+        *self.frame_check.borrow_mut() = Some(Box::new(check));
+    }
+
+    /// Returns the number of ticks that skipped the redraw since the previous call.
+    pub fn take_skipped(&self) -> usize {
+        self.skipped.take()
+    }
+
+    /// Starts redrawing, until the drawing area is destroyed.
+    pub fn start(self: &Rc<Self>) {
+        let Some(drawing_area) = self.drawing_area.upgrade() else {
+            return;
+        };
+        if !self.pause_when_hidden {
+            self.resume();
+            return;
+        }
+
+        self.update(&drawing_area);
+        let timer = self.clone();
+        drawing_area.connect_map(move |drawing_area| timer.update(drawing_area));
+        let timer = self.clone();
+        drawing_area.connect_unmap(move |_| timer.pause());
+
+        // Minimized windows stay mapped on some platforms, so watch the window state as well
+        let timer = self.clone();
+        drawing_area.connect_realize(move |drawing_area| {
+            if let Some(toplevel) = toplevel(drawing_area) {
+                let timer = timer.clone();
+                let drawing_area = drawing_area.downgrade();
+                toplevel.connect_state_notify(move |_| {
+                    if let Some(drawing_area) = drawing_area.upgrade() {
+                        timer.update(&drawing_area);
+                    }
+                });
+            }
+        });
+    }
+
+    /// Switches between the regular and the idle frame rate.
+    ///
+    /// # Arguments
+    /// - `idle`: Whether the input is silent.
+    pub fn set_idle(self: &Rc<Self>, idle: bool) {
+        if self.idle.replace(idle) != idle {
+            self.reschedule();
+        }
+    }
+
+    /// Switches between the regular and the battery frame rate.
+    ///
+    /// # Arguments
+    /// - `on_battery`: Whether the machine runs on battery.
+    pub fn set_on_battery(self: &Rc<Self>, on_battery: bool) {
+        if self.on_battery.replace(on_battery) != on_battery {
+            self.reschedule();
+        }
+    }
+
+    /// Restarts a running timer at the rate of the current state right away.
+    fn reschedule(self: &Rc<Self>) {
+        // Taken first, as `schedule` stores the new timer in `source`
+        let running = self.source.borrow_mut().take();
+        if let Some(source) = running {
+            source.remove();
+            self.schedule();
+        }
+    }
+
+    /// Resumes or pauses the redraws depending on whether `drawing_area` can be seen.
+    fn update(self: &Rc<Self>, drawing_area: &DrawingArea) {
+        let minimized = toplevel(drawing_area)
+            .is_some_and(|toplevel| toplevel.state().contains(gdk::ToplevelState::MINIMIZED));
+        if drawing_area.is_mapped() && !minimized {
+            self.resume();
+        } else {
+            self.pause();
+        }
+    }
+
+    /// Starts the timer unless it is running; the first redraw is queued right away.
+    fn resume(self: &Rc<Self>) {
+        if self.source.borrow().is_some() {
+            return;
+        }
+        if let Some(drawing_area) = self.drawing_area.upgrade() {
+            drawing_area.queue_draw();
+        }
+        self.schedule();
+    }
+
+    /// Stops the timer if it is running.
+    fn pause(&self) {
+        if let Some(source) = self.source.borrow_mut().take() {
+            source.remove();
+        }
+    }
+
+    /// Adds the timer at the interval of the current state.
+    fn schedule(self: &Rc<Self>) {
+        let interval = if self.idle.get() {
+            self.idle_interval
+        } else if self.on_battery.get() {
+            self.interval.max(self.battery_interval)
+        } else {
+            self.interval
+        };
+        let timer = self.clone();
+        let source = glib::timeout_add_local(interval, move || {
+            match timer.drawing_area.upgrade() {
+                Some(drawing_area) => {
+                    let changed = match timer.frame_check.borrow().as_ref() {
+                        Some(check) => check(),
+                        None => true,
+                    };
+                    if changed {
+                        drawing_area.queue_draw();
+                    } else {
+                        timer.skipped.set(timer.skipped.get() + 1);
+                    }
+                    glib::ControlFlow::Continue
+                }
+                None => {
+                    // The timer ends here, so it must not be removed again
+                    timer.source.borrow_mut().take();
+                    glib::ControlFlow::Break
+                }
+            }
+        });
+        *self.source.borrow_mut() = Some(source);
+    }
+}
+
+/// Returns the window surface `drawing_area` is shown in, once it is realized.
+fn toplevel(drawing_area: &DrawingArea) -> Option<gdk::Toplevel> {
+    drawing_area
+        .native()
+        .and_then(|native| native.surface())
+        .and_then(|surface| surface.downcast::<gdk::Toplevel>().ok())
+}
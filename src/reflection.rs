@@ -0,0 +1,216 @@
+use gtk::cairo::{self, Context, Format, ImageSurface, LinearGradient, Operator};
+use gtk4 as gtk;
+
+use crate::settings::PlotRect;
+
+/// Flipped, fading copy of the visualizer below the plot, like a glossy floor.
+///
+/// The visualizer draws into an intermediate surface, which is painted upright and then again
+/// mirrored at the bottom of the plot, through a gradient fading it out toward the bottom of the
+/// reflection.
+///
+/// # Fields
+/// - `alpha`: Opacity of the reflection at the bottom of the plot; `0.0` disables it.
+/// - `scale`: Device pixels per logical pixel of the drawing area.
+/// - `surface`: The frame being reflected at device resolution, reused between frames and
+///   reallocated when the drawing area is resized or moves to a display with another scale.
+pub struct Reflection {
+    alpha: f64,
+    scale: f64,
+    surface: Option<ImageSurface>,
+}
+
+impl Reflection {
+    /// Creates a new `Reflection` instance.
+    ///
+    /// # Arguments
+    /// - `alpha`: Opacity of the reflection at the bottom of the plot, `effects.reflection_alpha`.
+    pub fn new(alpha: f64) -> Self {
+        Reflection {
+            alpha: alpha.clamp(0.0, 1.0),
+            scale: 1.0,
+            surface: None,
+        }
+    }
+
+    /// Sets the device pixels per logical pixel, reallocating the surface when it changes.
+    pub fn set_scale(&mut self, scale: f64) {
+        if scale != self.scale {
+            self.scale = scale;
+            self.surface = None;
+        }
+    }
+
+    /// Releases the intermediate surface until the next frame.
+    pub fn clear(&mut self) {
+        self.surface = None;
+    }
+
+    /// Draws a frame and its reflection.
+    ///
+    /// # Arguments
+    /// - `cr`: The Cairo `Context` the frame and its reflection are drawn to.
+    /// - `width`: The width of the drawing area.
+    /// - `height`: The height of the drawing area.
+    /// - `strip`: The strip below the plot the reflection is drawn in; its top is the line the
+    ///   frame is mirrored at.
+    /// - `draw`: Function drawing the frame to the context it is given.
+    ///
+    /// Without a strip or opacity, or if the intermediate surface cannot be created, the frame is
+    /// drawn to `cr` directly.
+    pub fn draw<F: FnOnce(&Context)>(
+        &mut self,
+        cr: &Context,
+        width: f64,
+        height: f64,
+        strip: PlotRect,
+        draw: F,
+    ) {
+        let alpha = self.alpha;
+        if alpha <= 0.0 || strip.height <= 0.0 || strip.width <= 0.0 {
+            draw(cr);
+            return;
+        }
+        let surface = match self.surface_for(width, height) {
+            Ok(surface) => surface,
+            Err(e) => {
+                eprintln!(
+                    "Failed to create the reflection surface, drawing without a reflection: {}",
+                    e
+                );
+                self.alpha = 0.0;
+                draw(cr);
+                return;
+            }
+        };
+
+        match Context::new(surface) {
+            Ok(frame_cr) => {
+                frame_cr.set_operator(Operator::Clear);
+                let _ = frame_cr.paint();
+                frame_cr.set_operator(Operator::Over);
+                draw(&frame_cr);
+            }
+            Err(_) => {
+                draw(cr);
+                return;
+            }
+        }
+
+        let _ = cr.save();
+        let _ = cr.set_source_surface(surface, 0.0, 0.0);
+        let _ = cr.paint();
+        let _ = cr.restore();
+
+        // Mirror at the top of the strip; the fade is given in the mirrored coordinates, where
+        // the strip lies above the mirror line
+        let baseline = strip.y;
+        let fade = LinearGradient::new(0.0, baseline, 0.0, baseline - strip.height);
+        fade.add_color_stop_rgba(0.0, 0.0, 0.0, 0.0, alpha);
+        fade.add_color_stop_rgba(1.0, 0.0, 0.0, 0.0, 0.0);
+        let _ = cr.save();
+        cr.rectangle(strip.x, strip.y, strip.width, strip.height);
+        cr.clip();
+        cr.translate(0.0, 2.0 * baseline);
+        cr.scale(1.0, -1.0);
+        let _ = cr.set_source_surface(surface, 0.0, 0.0);
+        let _ = cr.mask(&fade);
+        let _ = cr.restore();
+    }
+
+    /// Returns the intermediate surface, reallocating it if the size changed.
+    ///
+    /// The surface has one pixel per device pixel, with a device scale so it is drawn to and
+    /// painted in logical pixels.
+    fn surface_for(&mut self, width: f64, height: f64) -> Result<&ImageSurface, cairo::Error> {
+        let scale = self.scale;
+        let (width, height) = (
+            (width * scale).ceil().max(1.0) as i32,
+            (height * scale).ceil().max(1.0) as i32,
+        );
+        let resized = !matches!(
+            &self.surface,
+            Some(surface) if surface.width() == width && surface.height() == height
+        );
+        if resized {
+            let surface = ImageSurface::create(Format::ARgb32, width, height)?;
+            surface.set_device_scale(scale, scale);
+            self.surface = Some(surface);
+        }
+        Ok(self.surface.as_ref().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the alpha of the pixel at `(x, y)` of `surface`.
+    fn alpha_at(surface: &mut ImageSurface, x: usize, y: usize) -> u8 {
+        surface.flush();
+        let stride = surface.stride() as usize;
+        let data = surface.data().unwrap();
+        let offset = y * stride + x * 4;
+        // ARGB32 pixels are native-endian 32-bit words with alpha in the high byte
+        let pixel = u32::from_ne_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ]);
+        (pixel >> 24) as u8
+    }
+
+    fn fill_plot(cr: &Context) {
+        cr.set_source_rgba(1.0, 1.0, 1.0, 1.0);
+        cr.rectangle(0.0, 0.0, 4.0, 8.0);
+        cr.fill().unwrap();
+    }
+
+    #[test]
+    fn reflections_fade_below_the_plot() {
+        let mut target = ImageSurface::create(Format::ARgb32, 4, 16).unwrap();
+        let mut reflection = Reflection::new(0.5);
+        let strip = PlotRect {
+            x: 0.0,
+            y: 8.0,
+            width: 4.0,
+            height: 8.0,
+        };
+
+        // The context holds a reference to the target until it is dropped
+        {
+            let cr = Context::new(&target).unwrap();
+            reflection.draw(&cr, 4.0, 16.0, strip, fill_plot);
+        }
+        assert_eq!(alpha_at(&mut target, 0, 7), 255);
+        let (top, bottom) = (alpha_at(&mut target, 0, 8), alpha_at(&mut target, 0, 15));
+        assert!((110..=128).contains(&top), "top of the reflection: {}", top);
+        assert!(bottom < 16, "bottom of the reflection: {}", bottom);
+    }
+
+    #[test]
+    fn resizing_reallocates_the_surface() {
+        let target = ImageSurface::create(Format::ARgb32, 8, 8).unwrap();
+        let cr = Context::new(&target).unwrap();
+        let mut reflection = Reflection::new(0.4);
+        let strip = PlotRect {
+            x: 0.0,
+            y: 3.0,
+            width: 4.0,
+            height: 1.0,
+        };
+
+        reflection.set_scale(2.0);
+        reflection.draw(&cr, 4.0, 4.0, strip, fill_plot);
+        let surface = reflection.surface.as_ref().unwrap();
+        assert_eq!((surface.width(), surface.height()), (8, 8));
+        assert_eq!(surface.device_scale(), (2.0, 2.0));
+
+        reflection.set_scale(1.0);
+        assert!(reflection.surface.is_none());
+        reflection.draw(&cr, 8.0, 2.5, strip, |_| {});
+        let surface = reflection.surface.as_ref().unwrap();
+        assert_eq!((surface.width(), surface.height()), (8, 3));
+    }
+}
@@ -0,0 +1,1623 @@
+use crate::background::Background;
+use crate::background_pulse::BackgroundPulse;
+use crate::color::{Palette, PaletteCycle};
+use crate::dsp::{
+    AutoGain, BeatCallback, BeatDetector, LoudnessMeter, SilenceGate, SpectrumAnalyzer,
+    TruePeakMeter,
+};
+use crate::fft_utils::{average_magnitudes, clamp_frequency_range, to_mid_side, PaddedFft};
+use crate::frequency_mapper::{Channel, FrequencyMapper};
+use crate::grid::{FrequencyGrid, GridVisibility};
+use crate::hop_scheduler::HopScheduler;
+use crate::hover_readout::HoverReadout;
+use crate::latency;
+use crate::legend::Legend;
+use crate::level_scale::LevelScale;
+use crate::loudness_overlay::LoudnessOverlay;
+use crate::noise_profile::NoiseProfile;
+use crate::note_readout::NoteReadout;
+use crate::osc_output::OscOutput;
+use crate::reflection::Reflection;
+use crate::settings::{BackgroundSettings, ChannelMode, LayerSettings, PlotRect, Settings};
+use crate::smoothing_state::SmoothingState;
+use crate::spectrum_curves::SpectrumCurves;
+#[cfg(feature = "http")]
+use crate::spectrum_server::SpectrumPublisher;
+use crate::text::{draw_text, TextAlign, TextStyle};
+use crate::trails::Trails;
+use crate::triggers::TriggerEngine;
+use crate::visualizer::{BarInstance, Visualizer, VisualizerRegistry};
+use crate::waveform::{draw_waveform, WaveformHistory};
+use gtk::cairo::{self, Context};
+use gtk4 as gtk;
+use rustfft::num_complex::Complex32;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Narrowest horizontal selection that zooms the frequency range, in pixels.
+const MIN_ZOOM_SELECTION: f64 = 4.0;
+/// Narrowest zoomed frequency range, in FFT bins.
+const MIN_ZOOM_BINS: usize = 2;
+/// Font size of the channel labels, in pixels.
+const CHANNEL_LABEL_SIZE: f64 = 14.0;
+/// Distance of the channel labels from the window edges, in pixels.
+const CHANNEL_LABEL_MARGIN: f64 = 12.0;
+/// Opacity of the rectangle marking a soloed band.
+const SOLO_BAND_ALPHA: f64 = 0.08;
+/// Smallest width and height drawn to, in pixels; the first frame may come before the drawing
+/// area has its size.
+const MIN_DRAW_SIZE: f64 = 2.0;
+/// Change of a bar height below which the bars count as settled, in pixels.
+const SETTLED_HEIGHT: f32 = 0.1;
+
+/// Spectra of one analyzed frame, ready to be rendered.
+///
+/// # Fields
+/// - `left`: Analyzed FFT data for the left audio channel.
+/// - `right`: Analyzed FFT data for the right audio channel.
+pub struct Spectrum {
+    pub left: Vec<Complex32>,
+    pub right: Vec<Complex32>,
+}
+
+/// A visualizer drawn above the first one, into its own region and with its own smoothing state.
+///
+/// # Fields
+/// - `settings`: Kind and region of the layer.
+/// - `visualizer`: The visualizer drawn into the region.
+/// - `previous_heights_left`: The previous frame's left channel heights for smooth transitions.
+/// - `previous_heights_right`: The previous frame's right channel heights for smooth transitions.
+struct Layer {
+    settings: LayerSettings,
+    visualizer: Box<dyn Visualizer>,
+    previous_heights_left: SmoothingState,
+    previous_heights_right: SmoothingState,
+}
+
+/// A visualizer fading out after a scene replaced it.
+///
+/// # Fields
+/// - `layer`: The replaced visualizer with its region and heights, still following the audio.
+/// - `duration`: Time the fade takes, in seconds.
+/// - `elapsed`: Time since the fade started, in seconds.
+struct Crossfade {
+    layer: Layer,
+    duration: f32,
+    elapsed: f32,
+}
+
+impl Crossfade {
+    /// Returns how far the fade has progressed, from 0.0 when it starts to 1.0 when it is done.
+    fn progress(&self) -> f32 {
+        (self.elapsed / self.duration).clamp(0.0, 1.0)
+    }
+}
+
+/// Analysis and drawing state shared by every frame of the visualization.
+///
+/// Splitting a frame into `analyze` and `render_frame` lets the same frame be drawn to any Cairo
+/// target, such as the window or an image surface, at any size.
+///
+/// # Fields
+/// - `settings`: Shared application settings, with the zoomed frequency range if zoomed.
+/// - `configured_range`: The frequency range of the configuration, restored when the zoom is
+///   reset.
+/// - `fft`: Forward FFT of `fft.size` samples padded to `fft.padded_size()`.
+/// - `registry`: Visualizers available for `next_visualizer`.
+/// - `visualizer`: The visualizer currently drawn; the first layer when `visualizers` is set.
+/// - `visualizer_name`: Registry name of `visualizer`.
+/// - `region`: Kind and region of `visualizer`, which fills the window unless it is the first of
+///   several layers.
+/// - `layers`: The layers after the first, drawn above it in order.
+/// - `crossfade`: The visualizer fading out after the last scene switch, while it fades.
+/// - `palette_cycle`: The palettes the visualizers fade through, if `visualizer.palette_cycle` is
+///   set.
+/// - `level_scale`: Height mapping of the bars of `visualizer`, shared with the grid.
+/// - `previous_heights_left`: The previous frame's left channel heights for smooth transitions.
+/// - `previous_heights_right`: The previous frame's right channel heights for smooth transitions.
+/// - `background`: The configured background, drawn first.
+/// - `grid`: The frequency grid drawn behind the visualizer.
+/// - `curves`: The long-term average and max-hold curves drawn over the visualizer.
+/// - `background_pulse`: The bass and beat background effect.
+/// - `beat_detector`: Onset detector driving the beat flash and beat callbacks.
+/// - `analyzer_left`: Smoothing and noise subtraction of the left channel.
+/// - `analyzer_right`: Smoothing and noise subtraction of the right channel.
+/// - `auto_gain`: Automatic gain applied to the bars when `visualizer.auto_gain` is set.
+/// - `note_readout`: The dominant frequency and note overlay.
+/// - `show_note_readout`: Whether `note_readout` is drawn.
+/// - `loudness_meter`: Loudness of the left and right channels, measured over every sample.
+/// - `true_peak_meter`: True peaks of the left and right channels, measured over every sample.
+/// - `waveform`: The last `waveform.seconds` of both channels mixed, drawn by visualizers with a
+///   waveform.
+/// - `loudness_overlay`: The loudness and true-peak meter overlay.
+/// - `show_loudness`: Whether `loudness_overlay` is drawn.
+/// - `legend`: Labels of the channels in the lower corners of their halves.
+/// - `loudness_received`: Total number of captured samples measured by `loudness_meter` and
+///   `true_peak_meter`.
+/// - `calibration_frames`: Number of frames a noise floor calibration lasts.
+/// - `calibrating`: Whether a calibration is in progress whose profile still has to be saved.
+/// - `osc_output`: Receives every analyzed frame when `output.osc_address` is set.
+/// - `spectrum_publisher`: Receives every analyzed frame for the spectrum server, once set.
+/// - `triggers`: Runs the `[[trigger]]` tables over every analyzed frame, when any are set.
+/// - `hover_readout`: The crosshair with the frequency and level under the pointer.
+/// - `hop_scheduler`: Windows of the capture buffer analyzed by `analyze_capture`.
+/// - `latest_hop`: Spectra of the windows analyzed last by `analyze_capture`, shown again until
+///   the next window completes.
+/// - `silence_gate`: Detects sustained silence in the analyzed samples.
+/// - `trails`: Fading trails of the previous frames behind the visualizer.
+/// - `reflection`: Flipped copy of the visualizer and its trails below the plot.
+/// - `channel_mode`: Whether the left and right channels or the mid and side signals are
+///   analyzed.
+/// - `scale`: Device pixels per logical pixel of the drawing area.
+/// - `size`: Width and height of the drawing area the last frame was drawn at, or `None` before
+///   the first frame.
+/// - `frame_interval`: Nominal time between consecutive frames, assumed before the second frame.
+/// - `last_timestamp`: Time of the previously analyzed frame, or `None` before the first frame.
+/// - `elapsed`: Time between the last two analyzed frames in seconds, which the visualizer
+///   smooths its bars over.
+/// - `drawn_heights`: The bar heights of all visualizers in the last frame, one channel after
+///   the other.
+/// - `animating`: Whether the next frame would differ from the last one without a new window.
+/// - `display_gain`: Gain the bars of the last frame were drawn with, including the automatic
+///   gain.
+/// - `failed_frames`: Number of frames skipped because the context entered an error state,
+///   logged at every power of two.
+pub struct FrameRenderer {
+    settings: Arc<Settings>,
+    configured_range: (f32, f32),
+    fft: PaddedFft,
+    registry: VisualizerRegistry,
+    visualizer: Box<dyn Visualizer>,
+    visualizer_name: String,
+    region: LayerSettings,
+    layers: Vec<Layer>,
+    crossfade: Option<Crossfade>,
+    palette_cycle: Option<PaletteCycle>,
+    level_scale: LevelScale,
+    previous_heights_left: SmoothingState,
+    previous_heights_right: SmoothingState,
+    background: Background,
+    grid: FrequencyGrid,
+    curves: SpectrumCurves,
+    background_pulse: BackgroundPulse,
+    beat_detector: BeatDetector,
+    analyzer_left: SpectrumAnalyzer,
+    analyzer_right: SpectrumAnalyzer,
+    auto_gain: AutoGain,
+    note_readout: NoteReadout,
+    show_note_readout: bool,
+    loudness_meter: LoudnessMeter,
+    true_peak_meter: TruePeakMeter,
+    waveform: WaveformHistory,
+    loudness_overlay: LoudnessOverlay,
+    show_loudness: bool,
+    legend: Legend,
+    loudness_received: usize,
+    calibration_frames: usize,
+    calibrating: bool,
+    osc_output: Option<OscOutput>,
+    #[cfg(feature = "http")]
+    spectrum_publisher: Option<SpectrumPublisher>,
+    triggers: Option<TriggerEngine>,
+    hover_readout: HoverReadout,
+    hop_scheduler: HopScheduler,
+    latest_hop: Option<(Vec<Complex32>, Vec<Complex32>)>,
+    silence_gate: SilenceGate,
+    trails: Trails,
+    reflection: Reflection,
+    channel_mode: ChannelMode,
+    scale: f64,
+    size: Option<(f64, f64)>,
+    frame_interval: Duration,
+    last_timestamp: Option<Duration>,
+    elapsed: f32,
+    drawn_heights: Vec<f32>,
+    animating: bool,
+    display_gain: f32,
+    failed_frames: u64,
+}
+
+impl FrameRenderer {
+    /// Creates a new `FrameRenderer` instance.
+    ///
+    /// # Arguments
+    /// - `settings`: Shared application settings.
+    /// - `registry`: Visualizers available by name; `visualizer.kind` selects the initial one.
+    /// - `beat_callbacks`: Functions invoked for every detected beat.
+    /// - `frame_interval`: Time between consecutive frames, used by time-based settings.
+    pub fn new(
+        settings: Arc<Settings>,
+        registry: VisualizerRegistry,
+        beat_callbacks: Vec<BeatCallback>,
+        frame_interval: Duration,
+    ) -> Self {
+        // The first layer takes the place of the single visualizer
+        let region = settings
+            .visualizers
+            .first()
+            .cloned()
+            .unwrap_or_else(|| LayerSettings {
+                kind: settings.visualizer.kind.clone(),
+                ..LayerSettings::default()
+            });
+        let mut visualizer_name = region.kind.clone();
+        let visualizer = match registry.create(&visualizer_name, settings.clone()) {
+            Ok(visualizer) => visualizer,
+            Err(e) => {
+                eprintln!("{}", e);
+                visualizer_name = "frequency".to_string();
+                registry
+                    .create(&visualizer_name, settings.clone())
+                    .expect("Built-in visualizer is missing")
+            }
+        };
+
+        let mut beat_detector = BeatDetector::new(&settings.beat);
+        for callback in beat_callbacks {
+            beat_detector.on_beat(callback);
+        }
+
+        let mut analyzer_left = SpectrumAnalyzer::new(&settings.visualizer);
+        let mut analyzer_right = SpectrumAnalyzer::new(&settings.visualizer);
+        let profile_path = &settings.calibration.profile_path;
+        if let Some(profile) = NoiseProfile::load(profile_path, settings.fft.padded_size()) {
+            analyzer_left.set_noise_profile(Some(profile.left));
+            analyzer_right.set_noise_profile(Some(profile.right));
+        }
+
+        let level_scale = LevelScale::new(
+            &settings.visualizer_settings(&visualizer_name),
+            settings.fft.size,
+        );
+        let layers = settings
+            .visualizers
+            .iter()
+            .skip(1)
+            .filter_map(
+                |layer| match registry.create(&layer.kind, settings.clone()) {
+                    Ok(visualizer) => Some(Layer {
+                        settings: layer.clone(),
+                        visualizer,
+                        previous_heights_left: SmoothingState::default(),
+                        previous_heights_right: SmoothingState::default(),
+                    }),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        None
+                    }
+                },
+            )
+            .collect();
+        let calibration_frames =
+            (settings.calibration.duration_secs / frame_interval.as_secs_f32()).ceil() as usize;
+        let grid = FrequencyGrid::new(settings.clone(), &visualizer_name);
+
+        FrameRenderer {
+            configured_range: (settings.fft.min_frequency, settings.fft.max_frequency),
+            fft: PaddedFft::new(&settings.fft, settings.audio.input_gain_db),
+            registry,
+            visualizer,
+            visualizer_name,
+            region,
+            layers,
+            crossfade: None,
+            palette_cycle: PaletteCycle::from_settings(&settings.visualizer),
+            level_scale,
+            previous_heights_left: SmoothingState::default(),
+            previous_heights_right: SmoothingState::default(),
+            background: Background::new(&settings.background),
+            grid,
+            curves: SpectrumCurves::new(settings.clone(), &visualizer_name),
+            background_pulse: BackgroundPulse::new(settings.clone()),
+            beat_detector,
+            analyzer_left,
+            analyzer_right,
+            auto_gain: AutoGain::new(&settings.visualizer, frame_interval),
+            note_readout: NoteReadout::new(settings.clone()),
+            show_note_readout: settings.note_readout.enabled,
+            loudness_meter: LoudnessMeter::new(settings.fft.sample_rate),
+            true_peak_meter: TruePeakMeter::new(settings.fft.sample_rate),
+            waveform: WaveformHistory::new(settings.fft.sample_rate, settings.waveform.seconds),
+            loudness_overlay: LoudnessOverlay::new(settings.clone()),
+            show_loudness: settings.loudness.enabled,
+            legend: Legend::new(),
+            loudness_received: 0,
+            calibration_frames,
+            calibrating: false,
+            osc_output: OscOutput::from_settings(settings.clone()),
+            #[cfg(feature = "http")]
+            spectrum_publisher: None,
+            triggers: TriggerEngine::from_settings(&settings),
+            hover_readout: HoverReadout::new(settings.clone()),
+            hop_scheduler: HopScheduler::new(settings.fft.size, settings.fft.hop_length()),
+            latest_hop: None,
+            silence_gate: SilenceGate::new(&settings.power),
+            trails: Trails::new(settings.effects.persistence),
+            reflection: Reflection::new(settings.effects.reflection_alpha),
+            channel_mode: settings.fft.channel_mode,
+            scale: 1.0,
+            size: None,
+            frame_interval,
+            last_timestamp: None,
+            elapsed: frame_interval.as_secs_f32(),
+            drawn_heights: Vec::new(),
+            animating: true,
+            display_gain: settings.visualizer.gain,
+            failed_frames: 0,
+            settings,
+        }
+    }
+
+    /// Sets the device pixels per logical pixel of the drawing area, e.g. 2 on a 200% display.
+    ///
+    /// Grid lines are snapped to device pixels, and the trail and background images are kept at
+    /// device resolution; they are recreated when the scale changes, such as when the window
+    /// moves to another monitor. Text is drawn in logical pixels and rendered at device
+    /// resolution by the Cairo context of the drawing area.
+    pub fn set_scale_factor(&mut self, scale: f64) {
+        let scale = scale.max(1.0);
+        if scale != self.scale {
+            self.scale = scale;
+            self.trails.set_scale(scale);
+            self.reflection.set_scale(scale);
+            self.background.set_scale(scale);
+        }
+    }
+
+    /// Adapts the drawing state to a new size of the drawing area.
+    ///
+    /// The visualizers are told the size of their regions, and the previous heights are moved
+    /// to the bars of the new size so the bars keep their levels instead of growing from zero.
+    /// The trails and the scaled background image are released rather than kept at the old
+    /// size. Every frame calls this with its size, so frames drawn at another size, such as
+    /// screenshots, are handled too; sizes too small to draw to are ignored.
+    ///
+    /// # Arguments
+    /// - `width`: The new width of the drawing area.
+    /// - `height`: The new height of the drawing area.
+    pub fn resize(&mut self, width: f64, height: f64) {
+        if !(width >= MIN_DRAW_SIZE && height >= MIN_DRAW_SIZE) {
+            return;
+        }
+        // Nothing was laid out before the first frame
+        let previous = self.size.replace((width, height)).unwrap_or((0.0, 0.0));
+        if previous == (width, height) {
+            return;
+        }
+
+        let settings = &self.settings;
+        let visualizer = self.visualizer.as_ref();
+        let from = region_size(settings, &self.region, visualizer, previous);
+        let to = region_size(settings, &self.region, visualizer, (width, height));
+        for heights in [
+            &mut self.previous_heights_left,
+            &mut self.previous_heights_right,
+        ] {
+            visualizer.rescale_heights(heights, from, to);
+        }
+        let fading = self
+            .crossfade
+            .as_mut()
+            .map(|crossfade| &mut crossfade.layer);
+        for layer in self.layers.iter_mut().chain(fading) {
+            let visualizer = layer.visualizer.as_ref();
+            let from = region_size(settings, &layer.settings, visualizer, previous);
+            let to = region_size(settings, &layer.settings, visualizer, (width, height));
+            for heights in [
+                &mut layer.previous_heights_left,
+                &mut layer.previous_heights_right,
+            ] {
+                visualizer.rescale_heights(heights, from, to);
+            }
+        }
+
+        self.trails.clear();
+        self.reflection.clear();
+        self.background.clear_scaled();
+        self.notify_size();
+    }
+
+    /// Publishes every analyzed frame to the spectrum server of `spectrum_publisher`.
+    #[cfg(feature = "http")]
+    pub fn set_spectrum_publisher(&mut self, spectrum_publisher: SpectrumPublisher) {
+        self.spectrum_publisher = Some(spectrum_publisher);
+    }
+
+    /// Shows or hides the dominant frequency and note readout.
+    pub fn set_show_note_readout(&mut self, show: bool) {
+        self.show_note_readout = show;
+    }
+
+    /// Shows or hides the loudness and true-peak meter.
+    pub fn set_show_loudness(&mut self, show: bool) {
+        self.show_loudness = show;
+    }
+
+    /// Tells the legend whether the window is fullscreen, which hides it after a few seconds.
+    ///
+    /// # Arguments
+    /// - `fullscreen`: Whether the window is fullscreen.
+    /// - `now`: The current time.
+    pub fn set_fullscreen(&mut self, fullscreen: bool, now: Instant) {
+        self.legend.set_fullscreen(fullscreen, now);
+    }
+
+    /// Clears the clip indicator of the true-peak meter.
+    pub fn clear_clip(&mut self) {
+        self.true_peak_meter.clear_clip();
+    }
+
+    /// Starts the max-hold curve over.
+    pub fn clear_max_hold(&mut self) {
+        self.curves.clear_max_hold();
+    }
+
+    /// Returns the registry name of the visualizer filling the window, or of the first layer.
+    pub fn visualizer_name(&self) -> &str {
+        &self.visualizer_name
+    }
+
+    /// Returns the settings in use, including the changes made while running.
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Returns which parts of the grid are drawn.
+    pub fn grid_visibility(&self) -> GridVisibility {
+        self.grid.visibility()
+    }
+
+    /// Shows or hides the grid, its horizontal lines, and its frequency markers.
+    pub fn set_grid_visibility(&mut self, visibility: GridVisibility) {
+        self.grid.set_visibility(visibility);
+    }
+
+    /// Applies settings changed while running, such as in the preferences window.
+    ///
+    /// # Arguments
+    /// - `settings`: The new settings; the new frequency range replaces any zoom.
+    ///
+    /// Only drawing settings take effect. Settings read once at startup, such as `fft.size`,
+    /// `fft.zero_pad_factor`, `waveform.seconds`, the `[audio]` section and the `[[trigger]]`
+    /// tables, keep their startup values.
+    pub fn apply_settings(&mut self, mut settings: Settings) {
+        settings.fft.size = self.settings.fft.size;
+        settings.fft.zero_pad_factor = self.settings.fft.zero_pad_factor;
+        settings.fft.sample_rate = self.settings.fft.sample_rate;
+        settings.fft.solo_band = self.settings.fft.solo_band;
+        settings.waveform.seconds = self.settings.waveform.seconds;
+        settings.audio.input_gain_db = self.settings.audio.input_gain_db;
+        self.configured_range = (settings.fft.min_frequency, settings.fft.max_frequency);
+        self.palette_cycle = PaletteCycle::from_settings(&settings.visualizer);
+        self.level_scale = LevelScale::new(
+            &settings.visualizer_settings(&self.visualizer_name),
+            settings.fft.size,
+        );
+        // Trails and reflection are turned off on battery, so they follow the new settings too
+        self.trails = Trails::new(settings.effects.persistence);
+        self.trails.set_scale(self.scale);
+        self.reflection = Reflection::new(settings.effects.reflection_alpha);
+        self.reflection.set_scale(self.scale);
+        self.replace_settings(Arc::new(settings));
+        self.reset_heights();
+    }
+
+    /// Stops drawing the configured background, leaving whatever is below the drawing area
+    /// visible, such as the desktop behind a transparent window.
+    pub fn disable_background(&mut self) {
+        self.background = Background::new(&BackgroundSettings::default());
+        self.background.set_scale(self.scale);
+    }
+
+    /// Returns whether the left and right channels or the mid and side signals are analyzed.
+    pub fn channel_mode(&self) -> ChannelMode {
+        self.channel_mode
+    }
+
+    /// Switches between analyzing the left and right channels and the mid and side signals.
+    pub fn set_channel_mode(&mut self, channel_mode: ChannelMode) {
+        self.channel_mode = channel_mode;
+    }
+
+    /// Switches to the visualizer registered after the current one.
+    pub fn next_visualizer(&mut self) {
+        let Some(next) = self
+            .registry
+            .next_name(&self.visualizer_name)
+            .map(str::to_string)
+        else {
+            return;
+        };
+
+        match self.registry.create(&next, self.settings.clone()) {
+            Ok(visualizer) => {
+                self.visualizer = visualizer;
+                self.level_scale = LevelScale::new(
+                    &self.settings.visualizer_settings(&next),
+                    self.settings.fft.size,
+                );
+                self.grid.set_visualizer(&next);
+                self.curves.set_visualizer(&next);
+                self.visualizer_name = next;
+                self.notify_size();
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    /// Applies one of the `[[scene]]` tables: its visualizer replaces the visualizer and the
+    /// layers, drawn with the drawing settings of the scene.
+    ///
+    /// The analysis keeps running and the bars keep their heights, so the switch does not
+    /// interrupt the audio. With `crossfade_ms` set, the replaced visualizer keeps following the
+    /// audio while it fades out and the scene's visualizer fades in.
+    ///
+    /// # Arguments
+    /// - `index`: Index of the scene in `scenes`, counting from 0.
+    ///
+    /// # Returns
+    /// - The name of the scene, or `None` if there is no scene at `index` or its visualizer
+    ///   could not be created, leaving the current one in place.
+    pub fn apply_scene(&mut self, index: usize) -> Option<String> {
+        let scene = self.settings.scenes.get(index)?.clone();
+        let settings = Arc::new(self.settings.with_scene(&scene));
+        let previous_name = std::mem::replace(&mut self.visualizer_name, scene.kind.clone());
+        let previous_region = std::mem::replace(
+            &mut self.region,
+            LayerSettings {
+                kind: scene.kind.clone(),
+                ..LayerSettings::default()
+            },
+        );
+        let previous_layers = std::mem::take(&mut self.layers);
+        let Some(previous) = self.replace_settings(settings) else {
+            self.visualizer_name = previous_name;
+            self.region = previous_region;
+            self.layers = previous_layers;
+            return None;
+        };
+
+        self.level_scale = LevelScale::new(
+            &self.settings.visualizer_settings(&scene.kind),
+            self.settings.fft.size,
+        );
+        self.crossfade = (scene.crossfade_ms > 0.0).then(|| Crossfade {
+            layer: Layer {
+                settings: previous_region,
+                visualizer: previous,
+                previous_heights_left: self.previous_heights_left.clone(),
+                previous_heights_right: self.previous_heights_right.clone(),
+            },
+            duration: scene.crossfade_ms / 1000.0,
+            elapsed: 0.0,
+        });
+        Some(scene.name)
+    }
+
+    /// Zooms the bars and the grid into the frequencies of a horizontal selection.
+    ///
+    /// # Arguments
+    /// - `start_x`: The x coordinate the selection started at.
+    /// - `end_x`: The x coordinate the selection ended at.
+    /// - `width`: The width of the drawing area.
+    ///
+    /// # Returns
+    /// - The new frequency range, or `None` if the selection was too narrow and was ignored.
+    pub fn zoom_to_selection(
+        &mut self,
+        start_x: f64,
+        end_x: f64,
+        width: f64,
+    ) -> Option<(f32, f32)> {
+        if (end_x - start_x).abs() < MIN_ZOOM_SELECTION {
+            return None;
+        }
+
+        let mapper =
+            FrequencyMapper::from_settings(&self.settings, self.settings.fft.padded_size());
+        let plot = self.settings.plot_rect(width, 0.0);
+        let (low, high) = mapper.selected_range(start_x - plot.x, end_x - plot.x, plot.width / 2.0);
+        Some(self.zoom_to((low, high)))
+    }
+
+    /// Zooms the bars and the grid into a frequency range, such as one saved by a previous run.
+    ///
+    /// # Arguments
+    /// - `(low, high)`: The frequency range in Hz, widened to a few bins and limited to the
+    ///   frequencies the FFT resolves.
+    ///
+    /// # Returns
+    /// - The new frequency range.
+    pub fn zoom_to(&mut self, (low, high): (f32, f32)) -> (f32, f32) {
+        let range = clamp_frequency_range(low, high, &self.settings.fft, MIN_ZOOM_BINS);
+        self.set_frequency_range(range);
+        range
+    }
+
+    /// Returns the zoomed frequency range, or `None` while the configured range is shown.
+    pub fn zoomed_range(&self) -> Option<(f32, f32)> {
+        let range = (
+            self.settings.fft.min_frequency,
+            self.settings.fft.max_frequency,
+        );
+        (range != self.configured_range).then_some(range)
+    }
+
+    /// Restores the frequency range of the configuration.
+    pub fn reset_zoom(&mut self) {
+        self.set_frequency_range(self.configured_range);
+    }
+
+    /// Solos the frequencies of a horizontal selection, dimming the bars outside of them without
+    /// changing the frequency range.
+    ///
+    /// # Arguments
+    /// - `start_x`: The x coordinate the selection started at.
+    /// - `end_x`: The x coordinate the selection ended at.
+    /// - `width`: The width of the drawing area.
+    ///
+    /// # Returns
+    /// - The soloed band, or `None` if the selection was too narrow and was ignored.
+    pub fn solo_selection(&mut self, start_x: f64, end_x: f64, width: f64) -> Option<(f32, f32)> {
+        if (end_x - start_x).abs() < MIN_ZOOM_SELECTION {
+            return None;
+        }
+
+        let mapper =
+            FrequencyMapper::from_settings(&self.settings, self.settings.fft.padded_size());
+        let plot = self.settings.plot_rect(width, 0.0);
+        let band = mapper.selected_range(start_x - plot.x, end_x - plot.x, plot.width / 2.0);
+        self.set_solo_band(Some(band));
+        Some(band)
+    }
+
+    /// Stops soloing a band, showing all bars at full opacity again.
+    pub fn clear_solo_band(&mut self) {
+        self.set_solo_band(None);
+    }
+
+    /// Starts measuring the noise floor; the profile is saved once the measurement completes.
+    pub fn start_calibration(&mut self) {
+        status!("Calibrating noise floor, keep the room quiet...");
+        self.analyzer_left
+            .start_calibration(self.calibration_frames);
+        self.analyzer_right
+            .start_calibration(self.calibration_frames);
+        self.calibrating = true;
+    }
+
+    /// Discards the noise profile and deletes its file.
+    pub fn clear_noise_profile(&mut self) {
+        self.analyzer_left.set_noise_profile(None);
+        self.analyzer_right.set_noise_profile(None);
+        NoiseProfile::remove(&self.settings.calibration.profile_path);
+        status!("Noise profile cleared.");
+    }
+
+    /// Transforms and analyzes one frame of audio, advancing all smoothing state by one frame.
+    ///
+    /// # Arguments
+    /// - `left`: `fft.size` samples of the left audio channel.
+    /// - `right`: `fft.size` samples of the right audio channel.
+    /// - `timestamp`: Time of the frame, relative to the start of the analysis.
+    ///
+    /// # Returns
+    /// - The analyzed spectra of both channels.
+    pub fn analyze(&mut self, left: &[f32], right: &[f32], timestamp: Duration) -> Spectrum {
+        // Consecutive windows overlap, so only the samples since the previous frame are new
+        let since_last_frame = match self.last_timestamp {
+            Some(last_timestamp) => timestamp.saturating_sub(last_timestamp),
+            None => self.frame_interval,
+        };
+        let new_samples = (since_last_frame.as_secs_f32() * self.settings.fft.sample_rate).round();
+        self.measure_levels(left, right, new_samples as usize);
+
+        let mid_side;
+        let (left, right) = match self.channel_mode {
+            ChannelMode::Lr => (left, right),
+            ChannelMode::Ms => {
+                mid_side = to_mid_side(left, right);
+                (&mid_side.0[..], &mid_side.1[..])
+            }
+        };
+        let fft_left = self.transform(left);
+        let fft_right = self.transform(right);
+        self.process_spectrum(fft_left, fft_right, (left, right), timestamp)
+    }
+
+    /// Analyzes the windows of a capture buffer completed since the previous frame, advancing
+    /// all smoothing state by one frame.
+    ///
+    /// A window ends after every `fft.hop_size` captured samples. The frame shows the latest
+    /// completed window, or the average of all of them with `fft.average_hops`; without a new
+    /// window, the previous one is shown again.
+    ///
+    /// # Arguments
+    /// - `left`: The latest captured samples of the left audio channel, at least `fft.size`.
+    /// - `right`: The latest captured samples of the right audio channel, as long as `left`.
+    /// - `received`: Total number of samples captured so far, wrapping around on overflow.
+    /// - `timestamp`: Time of the frame, relative to the start of the analysis.
+    ///
+    /// # Returns
+    /// - The analyzed spectra of both channels.
+    pub fn analyze_capture(
+        &mut self,
+        left: &[f32],
+        right: &[f32],
+        received: usize,
+        timestamp: Duration,
+    ) -> Spectrum {
+        let new_samples = received.wrapping_sub(self.loudness_received);
+        self.loudness_received = received;
+        self.measure_levels(left, right, new_samples);
+
+        let mid_side;
+        let (left, right) = match self.channel_mode {
+            ChannelMode::Lr => (left, right),
+            ChannelMode::Ms => {
+                mid_side = to_mid_side(left, right);
+                (&mid_side.0[..], &mid_side.1[..])
+            }
+        };
+        let (size, padded_size) = (self.settings.fft.size, self.settings.fft.padded_size());
+        let mut starts = self.hop_scheduler.windows(received, left.len());
+        if !self.settings.fft.average_hops {
+            starts.drain(..starts.len().saturating_sub(1));
+        }
+
+        if !starts.is_empty() {
+            let mut transform_windows = |samples: &[f32]| -> Vec<Vec<Complex32>> {
+                starts
+                    .iter()
+                    .map(|&start| self.transform(&samples[start..start + size]))
+                    .collect()
+            };
+            let (mut windows_left, mut windows_right) =
+                (transform_windows(left), transform_windows(right));
+            self.latest_hop = Some(if starts.len() == 1 {
+                (windows_left.remove(0), windows_right.remove(0))
+            } else {
+                (
+                    average_magnitudes(&windows_left),
+                    average_magnitudes(&windows_right),
+                )
+            });
+        }
+
+        let (fft_left, fft_right) = self.latest_hop.clone().unwrap_or_else(|| {
+            (
+                vec![Complex32::default(); padded_size],
+                vec![Complex32::default(); padded_size],
+            )
+        });
+        let window = left.len() - size..;
+        self.process_spectrum(
+            fft_left,
+            fft_right,
+            (&left[window.clone()], &right[window]),
+            timestamp,
+        )
+    }
+
+    /// Runs the silence detection, beat detection, smoothing, noise subtraction and OSC output
+    /// over the spectra of one frame.
+    ///
+    /// # Arguments
+    /// - `fft_left`: FFT data of the left audio channel.
+    /// - `fft_right`: FFT data of the right audio channel.
+    /// - `samples`: The analyzed samples of both channels.
+    /// - `timestamp`: Time of the frame, relative to the start of the analysis.
+    fn process_spectrum(
+        &mut self,
+        mut fft_left: Vec<Complex32>,
+        mut fft_right: Vec<Complex32>,
+        (left, right): (&[f32], &[f32]),
+        timestamp: Duration,
+    ) -> Spectrum {
+        let elapsed = match self.last_timestamp {
+            Some(last_timestamp) => timestamp.saturating_sub(last_timestamp),
+            None => self.frame_interval,
+        };
+        self.elapsed = elapsed.as_secs_f32();
+        self.last_timestamp = Some(timestamp);
+
+        self.silence_gate.process(left, right, timestamp);
+        if self.settings.beat.enabled {
+            if let Some(beat) = self.beat_detector.process(&fft_left, timestamp) {
+                self.background_pulse.kick(beat.strength);
+            }
+        }
+        if let Some(triggers) = &mut self.triggers {
+            triggers.process(timestamp, (&fft_left, &fft_right));
+        }
+
+        self.analyzer_left.process(&mut fft_left);
+        self.analyzer_right.process(&mut fft_right);
+
+        // Persist the profile once the calibration has finished
+        if self.calibrating && !self.analyzer_left.is_calibrating() {
+            self.calibrating = false;
+            self.save_noise_profile();
+        }
+
+        if let Some(osc_output) = &mut self.osc_output {
+            osc_output.send(timestamp, (left, right), (&fft_left, &fft_right));
+        }
+        #[cfg(feature = "http")]
+        if let Some(spectrum_publisher) = &mut self.spectrum_publisher {
+            spectrum_publisher.publish(timestamp, (left, right), (&fft_left, &fft_right));
+        }
+
+        Spectrum {
+            left: fft_left,
+            right: fft_right,
+        }
+    }
+
+    /// Measures the loudness and true peaks of the latest samples of both channels, and adds
+    /// them to the waveform.
+    ///
+    /// # Arguments
+    /// - `left`: Samples of the left channel, ending with the new ones.
+    /// - `right`: Samples of the right channel, as many as `left`.
+    /// - `new_samples`: Number of samples at the end not measured yet; only the latest ones are
+    ///   measured if more samples arrived than are given.
+    fn measure_levels(&mut self, left: &[f32], right: &[f32], new_samples: usize) {
+        let start = left.len() - new_samples.min(left.len());
+        self.loudness_meter.push(&left[start..], &right[start..]);
+        self.true_peak_meter.push(&left[start..], &right[start..]);
+        self.waveform.push(
+            left[start..]
+                .iter()
+                .zip(&right[start..])
+                .map(|(left, right)| (left + right) / 2.0),
+        );
+    }
+
+    /// Returns the time between the center of the window shown by the latest analysis and the
+    /// newest captured sample, for estimating the latency of the display.
+    pub fn window_center_offset(&self) -> Duration {
+        latency::window_center_offset(
+            self.hop_scheduler.samples_after_window(),
+            self.settings.fft.size,
+            self.settings.fft.sample_rate,
+        )
+    }
+
+    /// Returns whether `analyze_capture` would analyze a new window, given the total number of
+    /// samples captured so far.
+    ///
+    /// # Arguments
+    /// - `received`: Total number of samples captured so far, wrapping around on overflow.
+    pub fn has_new_window(&self, received: usize) -> bool {
+        self.hop_scheduler.has_window(received)
+    }
+
+    /// Returns whether the last frame was still moving, so the next one has to be drawn even
+    /// when no new window completed; e.g. the bars are still settling or a scene fades in.
+    pub fn needs_animation(&self) -> bool {
+        self.animating
+    }
+
+    /// Returns the gain the bars of the last frame were drawn with: the `gain` of the visualizer,
+    /// multiplied by the automatic gain when `visualizer.auto_gain` is set. Unlike
+    /// `audio.input_gain_db` it only affects the display.
+    pub fn display_gain(&self) -> f32 {
+        self.display_gain
+    }
+
+    /// Returns whether the input has been silent for `power.idle_after_secs`.
+    pub fn is_idle(&self) -> bool {
+        self.silence_gate.is_idle()
+    }
+
+    /// Draws one analyzed frame: background pulse, grid, visualizer, note readout, and loudness
+    /// meter.
+    ///
+    /// # Arguments
+    /// - `cr`: The Cairo `Context` to draw to.
+    /// - `width`: The width of the drawing area.
+    /// - `height`: The height of the drawing area.
+    /// - `spectrum`: The frame returned by `analyze`.
+    pub fn render_frame(&mut self, cr: &Context, width: f64, height: f64, spectrum: &Spectrum) {
+        self.render(cr, width, height, spectrum, None);
+    }
+
+    /// Draws one analyzed frame like `render_frame`, but lays out the bars into `bar_instances`
+    /// instead of drawing them when the visualizer has an OpenGL port.
+    ///
+    /// # Arguments
+    /// - `cr`: The Cairo `Context` to draw to.
+    /// - `width`: The width of the drawing area.
+    /// - `height`: The height of the drawing area.
+    /// - `spectrum`: The frame returned by `analyze`.
+    /// - `bar_instances`: Receives the bars for the OpenGL renderer; left empty when the
+    ///   visualizer is drawn with Cairo.
+    #[cfg(feature = "gl")]
+    pub fn render_frame_gl(
+        &mut self,
+        cr: &Context,
+        width: f64,
+        height: f64,
+        spectrum: &Spectrum,
+        bar_instances: &mut Vec<BarInstance>,
+    ) {
+        self.render(cr, width, height, spectrum, Some(bar_instances));
+    }
+
+    /// Draws one analyzed frame, drawing the bars of visualizers with an OpenGL port into
+    /// `bar_instances` when given.
+    ///
+    /// A context in an error state ignores everything drawn to it, e.g. after a resize to an
+    /// empty surface, so the rest of the frame is skipped and the error is logged; the next frame
+    /// draws to a new context.
+    fn render(
+        &mut self,
+        cr: &Context,
+        width: f64,
+        height: f64,
+        spectrum: &Spectrum,
+        bar_instances: Option<&mut Vec<BarInstance>>,
+    ) {
+        if let Err(e) = self.draw_frame(cr, width, height, spectrum, bar_instances) {
+            self.failed_frames += 1;
+            if self.failed_frames.is_power_of_two() {
+                eprintln!(
+                    "Failed to draw a frame ({} so far), skipping it: {}",
+                    self.failed_frames, e
+                );
+            }
+        }
+    }
+
+    /// Draws the components of one frame like `render`, each between `save` and `restore`.
+    ///
+    /// # Returns
+    /// - The error of the first component that left the context in an error state.
+    fn draw_frame(
+        &mut self,
+        cr: &Context,
+        width: f64,
+        height: f64,
+        spectrum: &Spectrum,
+        mut bar_instances: Option<&mut Vec<BarInstance>>,
+    ) -> Result<(), cairo::Error> {
+        if let Some(bar_instances) = &mut bar_instances {
+            bar_instances.clear();
+        }
+        if width < MIN_DRAW_SIZE || height < MIN_DRAW_SIZE {
+            return Ok(());
+        }
+        self.resize(width, height);
+        if let Some(cycle) = &mut self.palette_cycle {
+            cycle.advance(self.elapsed);
+            let palette = cycle.palette();
+            self.set_palette(&palette);
+        }
+
+        // OpenGL bars fill the window, so a first layer in a smaller region, below a waveform or
+        // inside plot margins is drawn with Cairo
+        let plot = self.settings.plot_rect(width, height);
+        let (waveform_region, region) = layout_regions(
+            &self.settings,
+            &self.region,
+            self.visualizer.as_ref(),
+            width,
+            height,
+        );
+        let gl_visualizer = self
+            .visualizer
+            .as_gl()
+            .filter(|_| region == (0.0, 0.0, width, height));
+
+        // Bars drawn with OpenGL lie below this surface, where the background would hide them
+        let draw_background = bar_instances.is_none() || gl_visualizer.is_none();
+        draw_component(cr, |cr| {
+            if draw_background {
+                self.background.draw(cr, width, height);
+            }
+            self.background_pulse
+                .draw(cr, width, height, &spectrum.left, &spectrum.right);
+        })?;
+
+        // Only the bars follow the automatic gain; effects and readouts see the analyzed levels
+        let scaled;
+        let (bars_left, bars_right, level_scale) = if self.settings.visualizer.auto_gain {
+            let factor = self
+                .auto_gain
+                .update(&spectrum.left, &spectrum.right, plot.height as f32);
+            let scale = |spectrum: &[Complex32]| -> Vec<_> {
+                spectrum.iter().map(|&value| value * factor).collect()
+            };
+            scaled = (scale(&spectrum.left), scale(&spectrum.right));
+            (&scaled.0, &scaled.1, self.level_scale.amplified(factor))
+        } else {
+            (&spectrum.left, &spectrum.right, self.level_scale.clone())
+        };
+        self.display_gain = level_scale.gain();
+
+        // dB lines move with the automatic gain so they keep matching the bars
+        if self.grid.visibility().enabled {
+            self.grid.set_center_line(self.visualizer.center_line());
+            draw_component(cr, |cr| self.grid.draw(cr, plot, &level_scale, self.scale))?;
+        }
+
+        // A scene fades in over the visualizer it replaced, drawn with OpenGL at full opacity
+        let fade_in = self
+            .crossfade
+            .as_ref()
+            .map_or(1.0, |crossfade| f64::from(crossfade.progress()));
+        match (bar_instances, gl_visualizer) {
+            (Some(bar_instances), Some(visualizer)) => {
+                visualizer.bar_instances(
+                    width as i32,
+                    height as i32,
+                    bars_left,
+                    bars_right,
+                    &mut self.previous_heights_left,
+                    &mut self.previous_heights_right,
+                    self.elapsed,
+                    bar_instances,
+                );
+            }
+            _ => {
+                // The trails are reflected with the bars, the background below them is not
+                let strip = self.settings.reflection_rect(width, height);
+                let trails = &mut self.trails;
+                draw_component(cr, |cr| {
+                    self.reflection.draw(cr, width, height, strip, |cr| {
+                        trails.draw(cr, width, height, |cr| {
+                            draw_in_region(cr, region, |cr, region_width, region_height| {
+                                draw_with_alpha(cr, fade_in, |cr| {
+                                    self.visualizer.draw(
+                                        region_width,
+                                        region_height,
+                                        bars_left,
+                                        bars_right,
+                                        cr,
+                                        &mut self.previous_heights_left,
+                                        &mut self.previous_heights_right,
+                                        self.elapsed,
+                                    )
+                                })
+                            })
+                        })
+                    })
+                })?;
+            }
+        }
+        if let Some(crossfade) = &mut self.crossfade {
+            let layer = &mut crossfade.layer;
+            let (_, fading_region) = layout_regions(
+                &self.settings,
+                &layer.settings,
+                layer.visualizer.as_ref(),
+                width,
+                height,
+            );
+            draw_component(cr, |cr| {
+                draw_in_region(cr, fading_region, |cr, region_width, region_height| {
+                    draw_with_alpha(cr, 1.0 - fade_in, |cr| {
+                        layer.visualizer.draw(
+                            region_width,
+                            region_height,
+                            bars_left,
+                            bars_right,
+                            cr,
+                            &mut layer.previous_heights_left,
+                            &mut layer.previous_heights_right,
+                            self.elapsed,
+                        )
+                    })
+                })
+            })?;
+            crossfade.elapsed += self.elapsed;
+        }
+        if self
+            .crossfade
+            .as_ref()
+            .is_some_and(|crossfade| crossfade.progress() >= 1.0)
+        {
+            self.crossfade = None;
+        }
+        if self.curves.enabled() {
+            draw_component(cr, |cr| {
+                draw_in_region(cr, region, |cr, region_width, region_height| {
+                    let (region_width, region_height) =
+                        (f64::from(region_width), f64::from(region_height));
+                    self.curves.update(
+                        bars_left,
+                        bars_right,
+                        &self.level_scale,
+                        region_width,
+                        self.elapsed,
+                    );
+                    self.curves.draw(cr, region_width, region_height);
+                })
+            })?;
+        }
+        if let Some(waveform_region) = waveform_region {
+            draw_component(cr, |cr| {
+                draw_in_region(cr, waveform_region, |cr, region_width, region_height| {
+                    draw_waveform(cr, region_width, region_height, &self.waveform)
+                })
+            })?;
+        }
+
+        // Later layers are composited over the earlier ones
+        let mut follows_samples = waveform_region.is_some();
+        for layer in &mut self.layers {
+            let (waveform_region, region) = layout_regions(
+                &self.settings,
+                &layer.settings,
+                layer.visualizer.as_ref(),
+                width,
+                height,
+            );
+            draw_component(cr, |cr| {
+                draw_in_region(cr, region, |cr, region_width, region_height| {
+                    layer.visualizer.draw(
+                        region_width,
+                        region_height,
+                        bars_left,
+                        bars_right,
+                        cr,
+                        &mut layer.previous_heights_left,
+                        &mut layer.previous_heights_right,
+                        self.elapsed,
+                    )
+                })
+            })?;
+            if let Some(waveform_region) = waveform_region {
+                draw_component(cr, |cr| {
+                    draw_in_region(cr, waveform_region, |cr, region_width, region_height| {
+                        draw_waveform(cr, region_width, region_height, &self.waveform)
+                    })
+                })?;
+                follows_samples = true;
+            }
+        }
+
+        // The solo band, channel labels and readouts lie above every layer
+        let labels = match self.channel_mode {
+            ChannelMode::Ms => ("M", "S"),
+            ChannelMode::Lr => ("L", "R"),
+        };
+        let show_legend = self.legend.is_visible(&self.settings);
+        draw_component(cr, |cr| {
+            draw_in_region(
+                cr,
+                in_plot(plot, (0.0, 0.0, plot.width, plot.height)),
+                |cr, plot_width, plot_height| {
+                    self.draw_solo_band(cr, f64::from(plot_width), f64::from(plot_height))
+                },
+            );
+            if show_legend {
+                self.legend.draw(
+                    cr,
+                    &self.settings,
+                    self.visualizer.channel_layout(),
+                    region,
+                    labels,
+                );
+            } else if self.channel_mode == ChannelMode::Ms {
+                draw_channel_labels(cr, width, &self.settings, labels);
+            }
+            if self.show_note_readout {
+                self.note_readout.draw(cr, &spectrum.left);
+            }
+            if self.show_loudness {
+                self.loudness_overlay.draw(
+                    cr,
+                    width,
+                    height,
+                    &self.loudness_meter,
+                    &self.true_peak_meter,
+                    show_legend,
+                );
+            }
+        })?;
+
+        let mut heights = vec![
+            &self.previous_heights_left[..],
+            &self.previous_heights_right[..],
+        ];
+        for layer in &self.layers {
+            heights.push(&layer.previous_heights_left);
+            heights.push(&layer.previous_heights_right);
+        }
+        let moved = heights_moved(&mut self.drawn_heights, &heights);
+        self.animating = moved
+            || self.crossfade.is_some()
+            || self.palette_cycle.is_some()
+            || self.visualizer.needs_animation()
+            || self
+                .layers
+                .iter()
+                .any(|layer| layer.visualizer.needs_animation())
+            || self.trails.is_enabled()
+            || self.background_pulse.is_fading()
+            || self.curves.enabled()
+            || self.legend.is_counting_down()
+            || self.triggers.as_ref().is_some_and(TriggerEngine::is_pending)
+            // Meters and waveforms follow every captured sample rather than the analyzed windows
+            || self.show_loudness
+            || follows_samples;
+        Ok(())
+    }
+
+    /// Draws the crosshair with the frequency and level of the bar under the pointer, while it
+    /// is over the plot.
+    ///
+    /// # Arguments
+    /// - `cr`: The Cairo `Context` to draw to.
+    /// - `width`: The width of the drawing area.
+    /// - `height`: The height of the drawing area.
+    /// - `spectrum`: The frame last drawn by `render_frame`.
+    /// - `pointer`: Position of the pointer in the drawing area.
+    pub fn draw_hover_readout(
+        &self,
+        cr: &Context,
+        width: f64,
+        height: f64,
+        spectrum: &Spectrum,
+        pointer: (f64, f64),
+    ) {
+        let plot = self.settings.plot_rect(width, height);
+        if !plot.contains(pointer) {
+            return;
+        }
+        let _ = cr.save();
+        cr.translate(plot.x, plot.y);
+        self.hover_readout.draw(
+            cr,
+            plot.width,
+            plot.height,
+            (pointer.0 - plot.x, pointer.1 - plot.y),
+            spectrum,
+            &self.level_scale,
+        );
+        let _ = cr.restore();
+    }
+
+    /// Shows a new frequency range, rebuilding everything that depends on it.
+    fn set_frequency_range(&mut self, (min_frequency, max_frequency): (f32, f32)) {
+        let mut settings = (*self.settings).clone();
+        settings.fft.min_frequency = min_frequency;
+        settings.fft.max_frequency = max_frequency;
+        self.replace_settings(Arc::new(settings));
+        self.reset_heights();
+    }
+
+    /// Solos a band, or stops soloing with `None`; the bars keep their heights.
+    fn set_solo_band(&mut self, solo_band: Option<(f32, f32)>) {
+        let mut settings = (*self.settings).clone();
+        settings.fft.solo_band = solo_band;
+        self.replace_settings(Arc::new(settings));
+    }
+
+    /// Marks the soloed band with a translucent rectangle on both halves of the plot, so its edges
+    /// stay visible where no bars are drawn.
+    fn draw_solo_band(&self, cr: &Context, width: f64, height: f64) {
+        let Some((low, high)) = self.settings.fft.solo_band else {
+            return;
+        };
+        let fft = &self.settings.fft;
+        let mapper = FrequencyMapper::from_settings(&self.settings, fft.padded_size());
+        // Only the part of the band within the displayed range is marked
+        let position = |frequency: f32| {
+            let frequency = frequency.clamp(fft.min_frequency, fft.max_frequency);
+            mapper.position(frequency).unwrap_or(0.0)
+        };
+        let (low, high) = (position(low), position(high));
+        if high <= low {
+            return;
+        }
+
+        let half_width = width / 2.0;
+        cr.set_source_rgba(1.0, 1.0, 1.0, SOLO_BAND_ALPHA);
+        for channel in [Channel::Left, Channel::Right] {
+            let start = mapper.mirrored_x(low, half_width, channel);
+            let end = mapper.mirrored_x(high, half_width, channel);
+            cr.rectangle(start.min(end), 0.0, (end - start).abs(), height);
+        }
+        let _ = cr.fill();
+    }
+
+    /// Recreates the visualizer, grid and hover readout from new settings.
+    ///
+    /// # Returns
+    /// - The replaced visualizer, or `None` if the visualizer could not be created and nothing
+    ///   was replaced.
+    fn replace_settings(&mut self, settings: Arc<Settings>) -> Option<Box<dyn Visualizer>> {
+        let previous = match self
+            .registry
+            .create(&self.visualizer_name, settings.clone())
+        {
+            Ok(visualizer) => std::mem::replace(&mut self.visualizer, visualizer),
+            Err(e) => {
+                eprintln!("{}", e);
+                return None;
+            }
+        };
+        let visibility = self.grid.visibility();
+        self.grid = FrequencyGrid::new(settings.clone(), &self.visualizer_name);
+        self.grid.set_visibility(visibility);
+        self.hover_readout = HoverReadout::new(settings.clone());
+        // Zooming shows other bins, so the curves start over
+        self.curves = SpectrumCurves::new(settings.clone(), &self.visualizer_name);
+        for layer in &mut self.layers {
+            match self.registry.create(&layer.settings.kind, settings.clone()) {
+                Ok(visualizer) => layer.visualizer = visualizer,
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        self.settings = settings;
+        self.notify_size();
+        Some(previous)
+    }
+
+    /// Gives the visualizers following `visualizer.palette_cycle`, and the grid markers colored
+    /// like the first of them, the current palette of the cycle.
+    fn set_palette(&mut self, palette: &Palette) {
+        let visualizer_settings = &self.settings.visualizer;
+        if visualizer_settings.follows_palette_cycle(&self.visualizer_name) {
+            self.visualizer.set_palette(palette);
+            self.grid.set_palette(palette);
+        }
+        let fading = self
+            .crossfade
+            .as_mut()
+            .map(|crossfade| &mut crossfade.layer);
+        for layer in self.layers.iter_mut().chain(fading) {
+            if visualizer_settings.follows_palette_cycle(&layer.settings.kind) {
+                layer.visualizer.set_palette(palette);
+            }
+        }
+    }
+
+    /// Tells every visualizer the size of its region in the drawing area of the last frame.
+    fn notify_size(&mut self) {
+        let Some(size) = self.size else {
+            return;
+        };
+        let (width, height) =
+            region_size(&self.settings, &self.region, self.visualizer.as_ref(), size);
+        self.visualizer.resized(width, height);
+        let fading = self
+            .crossfade
+            .as_mut()
+            .map(|crossfade| &mut crossfade.layer);
+        for layer in self.layers.iter_mut().chain(fading) {
+            let (width, height) = region_size(
+                &self.settings,
+                &layer.settings,
+                layer.visualizer.as_ref(),
+                size,
+            );
+            layer.visualizer.resized(width, height);
+        }
+    }
+
+    /// Lets the bars grow from zero, after their heights stopped matching their frequencies.
+    fn reset_heights(&mut self) {
+        self.previous_heights_left.fill(0.0);
+        self.previous_heights_right.fill(0.0);
+        let fading = self
+            .crossfade
+            .as_mut()
+            .map(|crossfade| &mut crossfade.layer);
+        for layer in self.layers.iter_mut().chain(fading) {
+            layer.previous_heights_left.fill(0.0);
+            layer.previous_heights_right.fill(0.0);
+        }
+    }
+
+    /// Runs the FFT over one channel's samples.
+    fn transform(&mut self, samples: &[f32]) -> Vec<Complex32> {
+        self.fft.transform(samples)
+    }
+
+    /// Writes the noise profiles of both analyzers to the configured profile file.
+    fn save_noise_profile(&self) {
+        let (Some(left), Some(right)) = (
+            self.analyzer_left.noise_profile(),
+            self.analyzer_right.noise_profile(),
+        ) else {
+            return;
+        };
+
+        let profile = NoiseProfile {
+            left: left.to_vec(),
+            right: right.to_vec(),
+        };
+        let profile_path = &self.settings.calibration.profile_path;
+        match profile.save(profile_path) {
+            Ok(()) => status!("Noise profile saved to {}.", profile_path),
+            Err(e) => eprintln!("Failed to save noise profile: {}", e),
+        }
+    }
+}
+
+/// Compares the bar heights of a frame with those of the previous frame, remembering them for
+/// the next comparison.
+///
+/// # Arguments
+/// - `drawn`: The heights of the previous frame, replaced by `heights`.
+/// - `heights`: The heights of every channel of every visualizer in this frame.
+///
+/// # Returns
+/// - Whether a bar moved by at least `SETTLED_HEIGHT`, or the bars changed in number.
+fn heights_moved(drawn: &mut Vec<f32>, heights: &[&[f32]]) -> bool {
+    let current = heights.iter().flat_map(|channel| channel.iter().copied());
+    let moved = drawn.len() != heights.iter().map(|channel| channel.len()).sum::<usize>()
+        || drawn
+            .iter()
+            .zip(current.clone())
+            .any(|(drawn, height)| (height - drawn).abs() >= SETTLED_HEIGHT);
+    drawn.clear();
+    drawn.extend(current);
+    moved
+}
+
+/// Draws one component of a frame, such as the grid, a visualizer or the overlays, between
+/// `save` and `restore`, so the transformation, clip and source it leaves behind do not carry
+/// over to the next component.
+///
+/// # Returns
+/// - The error the context is in after drawing, if any; an error state is kept by the context,
+///   so nothing drawn after it would show.
+fn draw_component(cr: &Context, draw: impl FnOnce(&Context)) -> Result<(), cairo::Error> {
+    cr.save()?;
+    draw(cr);
+    cr.restore()?;
+    cr.status()
+}
+
+/// Draws into a region of the drawing area, with the origin moved to the region's top-left corner
+/// and everything outside of it clipped.
+///
+/// # Arguments
+/// - `cr`: The Cairo `Context` to draw to.
+/// - `(x, y, width, height)`: The region, in the coordinates of the drawing area.
+/// - `draw`: Draws the region, given the context and the width and height of the region; not
+///   called for regions too small to draw to.
+fn draw_in_region(
+    cr: &Context,
+    (x, y, width, height): (f64, f64, f64, f64),
+    draw: impl FnOnce(&Context, i32, i32),
+) {
+    if width < MIN_DRAW_SIZE || height < MIN_DRAW_SIZE {
+        return;
+    }
+    let _ = cr.save();
+    cr.translate(x, y);
+    cr.rectangle(0.0, 0.0, width, height);
+    cr.clip();
+    draw(cr, width as i32, height as i32);
+    let _ = cr.restore();
+}
+
+/// Draws at an opacity, through an intermediate group while translucent so overlapping shapes
+/// do not show through each other.
+///
+/// # Arguments
+/// - `cr`: The Cairo `Context` to draw to.
+/// - `alpha`: The opacity, from 0.0 for invisible to 1.0 for opaque.
+/// - `draw`: Draws at full opacity.
+fn draw_with_alpha(cr: &Context, alpha: f64, draw: impl FnOnce(&Context)) {
+    if alpha >= 1.0 {
+        draw(cr);
+        return;
+    }
+    cr.push_group();
+    draw(cr);
+    if cr.pop_group_to_source().is_ok() {
+        let _ = cr.paint_with_alpha(alpha.max(0.0));
+    }
+}
+
+/// A region of the drawing area: left edge, top edge, width and height.
+type Rect = (f64, f64, f64, f64);
+
+/// Moves a region given within the plot into the coordinates of the drawing area.
+fn in_plot(plot: PlotRect, (x, y, width, height): Rect) -> Rect {
+    (plot.x + x, plot.y + y, width, height)
+}
+
+/// Lays out a visualizer in the plot of a drawing area.
+///
+/// # Arguments
+/// - `settings`: Settings providing the plot margins.
+/// - `region`: Region of the visualizer within the plot.
+/// - `visualizer`: The visualizer, which may take a waveform strip above it.
+/// - `width`: The width of the drawing area.
+/// - `height`: The height of the drawing area.
+///
+/// # Returns
+/// - The waveform strip, if any, and the region of the visualizer, in the coordinates of the
+///   drawing area.
+fn layout_regions(
+    settings: &Settings,
+    region: &LayerSettings,
+    visualizer: &dyn Visualizer,
+    width: f64,
+    height: f64,
+) -> (Option<Rect>, Rect) {
+    let plot = settings.plot_rect(width, height);
+    split_waveform(
+        in_plot(plot, region.rect(plot.width, plot.height)),
+        visualizer.waveform_fraction(),
+    )
+}
+
+/// Returns the width and height `draw` receives for a visualizer in a drawing area of `size`.
+fn region_size(
+    settings: &Settings,
+    region: &LayerSettings,
+    visualizer: &dyn Visualizer,
+    (width, height): (f64, f64),
+) -> (i32, i32) {
+    let (_, (_, _, width, height)) = layout_regions(settings, region, visualizer, width, height);
+    (width as i32, height as i32)
+}
+
+/// Splits a region into a waveform strip at the top and the region of the visualizer below it.
+///
+/// # Arguments
+/// - `region`: The region of the visualizer, in the coordinates of the drawing area.
+/// - `waveform_fraction`: The fraction of the height taken by the waveform, or `None` for a
+///   visualizer without one.
+///
+/// # Returns
+/// - The waveform strip, if any, and the region left for the visualizer.
+fn split_waveform(
+    (x, y, width, height): Rect,
+    waveform_fraction: Option<f64>,
+) -> (Option<Rect>, Rect) {
+    match waveform_fraction {
+        Some(fraction) => {
+            let waveform_height = height * fraction.clamp(0.0, 1.0);
+            (
+                Some((x, y, width, waveform_height)),
+                (x, y + waveform_height, width, height - waveform_height),
+            )
+        }
+        None => (None, (x, y, width, height)),
+    }
+}
+
+/// Labels the halves of the visualizer in the top corners, in the grid colors of their channels.
+///
+/// # Arguments
+/// - `cr`: The Cairo `Context` to draw to.
+/// - `width`: The width of the drawing area.
+/// - `settings`: Settings providing the channel colors and the font.
+/// - `(left, right)`: Labels of the left and right halves.
+fn draw_channel_labels(cr: &Context, width: f64, settings: &Settings, (left, right): (&str, &str)) {
+    let style = TextStyle::ui(&settings.ui, CHANNEL_LABEL_SIZE).bold();
+    let left_style = TextStyle {
+        color: settings.grid.color_left.to_rgba(1.0),
+        ..style.clone()
+    };
+    draw_text(
+        cr,
+        left,
+        CHANNEL_LABEL_MARGIN,
+        CHANNEL_LABEL_MARGIN,
+        &left_style,
+    );
+
+    let right_style = TextStyle {
+        color: settings.grid.color_right.to_rgba(1.0),
+        align: TextAlign::Right,
+        ..style
+    };
+    draw_text(
+        cr,
+        right,
+        width - CHANNEL_LABEL_MARGIN,
+        CHANNEL_LABEL_MARGIN,
+        &right_style,
+    );
+}
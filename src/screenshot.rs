@@ -0,0 +1,326 @@
+use crate::color::Color;
+use crate::file_utils::timestamped_file_name;
+use crate::renderer::{FrameRenderer, Spectrum};
+use gtk::cairo::{self, Context, Format, ImageSurface};
+use gtk4 as gtk;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Renders an analyzed frame into a new image surface.
+///
+/// # Arguments
+/// - `renderer`: The renderer drawing the frame.
+/// - `spectrum`: The frame returned by `FrameRenderer::analyze`.
+/// - `width`: The width of the image in pixels.
+/// - `height`: The height of the image in pixels.
+/// - `background`: Color the image is filled with before drawing.
+///
+/// # Returns
+/// - The rendered image, or an error if the surface cannot be created.
+pub fn render_to_surface(
+    renderer: &mut FrameRenderer,
+    spectrum: &Spectrum,
+    width: i32,
+    height: i32,
+    background: Color,
+) -> Result<ImageSurface, cairo::Error> {
+    let surface = ImageSurface::create(Format::ARgb32, width, height)?;
+    {
+        let cr = Context::new(&surface)?;
+        let (r, g, b, a) = background.to_rgba(1.0);
+        cr.set_source_rgba(r, g, b, a);
+        cr.paint()?;
+        renderer.render_frame(&cr, width as f64, height as f64, spectrum);
+    }
+    Ok(surface)
+}
+
+/// Writes an image to `directory` as a timestamped PNG file, creating the directory if needed.
+///
+/// # Returns
+/// - The path of the written file, or an error if it cannot be written.
+pub fn save_png(
+    surface: &ImageSurface,
+    directory: &str,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    fs::create_dir_all(directory)?;
+    let path = Path::new(directory).join(timestamped_file_name(SystemTime::now(), "png"));
+    write_png(surface, &path)?;
+    Ok(path)
+}
+
+/// Writes an image to `path` as a PNG file.
+pub fn write_png(surface: &ImageSurface, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = File::create(path)?;
+    surface.write_to_png(&mut file)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{LayoutSettings, MarginLength, Margins, Settings};
+    use crate::smoothing_state::SmoothingState;
+    use crate::visualizer::VisualizerRegistry;
+    use std::f32::consts::TAU;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Size of the rendered images, in pixels.
+    const SIZE: (i32, i32) = (240, 160);
+    /// Time between frames.
+    const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+    /// Creates a renderer of the built-in visualizers.
+    fn renderer(settings: &Arc<Settings>) -> FrameRenderer {
+        FrameRenderer::new(
+            settings.clone(),
+            VisualizerRegistry::new(),
+            Vec::new(),
+            FRAME_INTERVAL,
+        )
+    }
+
+    /// Returns one FFT window of a 1 kHz sine.
+    fn sine(settings: &Settings, amplitude: f32) -> Vec<f32> {
+        let sample_rate = settings.fft.sample_rate;
+        (0..settings.fft.size)
+            .map(|i| amplitude * (TAU * 1000.0 * i as f32 / sample_rate).sin())
+            .collect()
+    }
+
+    /// Returns the number of pixels of an image that are not black.
+    fn lit_pixels(surface: &mut ImageSurface) -> usize {
+        surface.flush();
+        let data = surface.data().unwrap();
+        data.chunks(4)
+            .filter(|pixel| pixel[..3].iter().any(|&byte| byte != 0))
+            .count()
+    }
+
+    /// Renders one frame of a 1 kHz sine, or of silence, and returns the pixels of the image.
+    fn render_pixels(settings: Settings, amplitude: f32) -> Vec<u8> {
+        let settings = Arc::new(settings);
+        let mut renderer = renderer(&settings);
+        let samples = sine(&settings, amplitude);
+        let spectrum = renderer.analyze(&samples, &samples, Duration::ZERO);
+
+        let mut surface = render_to_surface(
+            &mut renderer,
+            &spectrum,
+            SIZE.0,
+            SIZE.1,
+            Color::rgb(0.0, 0.0, 0.0),
+        )
+        .unwrap();
+        surface.flush();
+        let data = surface.data().unwrap().to_vec();
+        data
+    }
+
+    /// Returns settings with the same margin on every side.
+    fn with_margin(margin: MarginLength) -> Settings {
+        Settings {
+            layout: LayoutSettings {
+                margin: Margins {
+                    top: margin,
+                    bottom: margin,
+                    left: margin,
+                    right: margin,
+                },
+                ..LayoutSettings::default()
+            },
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn zero_margins_draw_the_same_image_as_the_defaults() {
+        let default = render_pixels(Settings::default(), 0.5);
+        assert!(default.iter().any(|&byte| byte != 0), "nothing was drawn");
+        assert!(default == render_pixels(with_margin(MarginLength::Pixels(0.0)), 0.5));
+        assert!(default == render_pixels(with_margin(MarginLength::Percent(0.0)), 0.5));
+    }
+
+    #[test]
+    fn visualizers_stay_inside_the_plot() {
+        // The glow reaches past the bars, and the grid labels would be drawn in the margins
+        let settings = || {
+            let mut settings = with_margin(MarginLength::Pixels(20.0));
+            settings.visualizer.kind = "holographic_glow".to_string();
+            settings.grid.enabled = false;
+            settings
+        };
+        let loud = render_pixels(settings(), 0.5);
+        let silent = render_pixels(settings(), 0.0);
+
+        let plot = settings().plot_rect(f64::from(SIZE.0), f64::from(SIZE.1));
+        let stride = loud.len() / SIZE.1 as usize;
+        let in_margin = |index: usize| {
+            let (x, y) = ((index % stride) / 4, index / stride);
+            !plot.contains((x as f64, y as f64))
+        };
+        let changed: Vec<usize> = (0..loud.len())
+            .filter(|&index| loud[index] != silent[index])
+            .collect();
+        assert!(!changed.is_empty(), "the sine was not drawn");
+        assert!(
+            changed.iter().all(|&index| !in_margin(index)),
+            "the audio changed pixels outside of {:?}",
+            plot
+        );
+    }
+
+    #[test]
+    fn resizing_keeps_the_bar_levels() {
+        let settings = Arc::new(Settings::default());
+        let samples = sine(&settings, 0.5);
+        let wide = (2 * SIZE.0, SIZE.1);
+        let frame = |renderer: &mut FrameRenderer, index: u32, (width, height): (i32, i32)| {
+            let spectrum = renderer.analyze(&samples, &samples, FRAME_INTERVAL * index);
+            let black = Color::rgb(0.0, 0.0, 0.0);
+            let mut surface = render_to_surface(renderer, &spectrum, width, height, black).unwrap();
+            lit_pixels(&mut surface)
+        };
+
+        // Bars settled on a small window, then drawn once on a wide one
+        let mut resized = renderer(&settings);
+        for index in 0..30 {
+            frame(&mut resized, index, SIZE);
+        }
+        let after_resize = frame(&mut resized, 30, wide);
+
+        // Bars growing from zero on the wide window, and settled on it
+        let mut fresh = renderer(&settings);
+        let first = frame(&mut fresh, 0, wide);
+        let settled = (1..=30).map(|index| frame(&mut fresh, index, wide)).last();
+
+        assert!(
+            after_resize > first,
+            "the bars started over after the resize"
+        );
+        // Bars split by the wider window take the loudest bin of their merged bar, so they may
+        // start a little above their settled height
+        let settled = settled.unwrap();
+        assert!(
+            2 * after_resize > settled,
+            "{} pixels lit after the resize, {} when settled",
+            after_resize,
+            settled
+        );
+    }
+
+    #[test]
+    fn a_context_in_an_error_state_skips_the_frame() {
+        let settings = Arc::new(Settings::default());
+        let samples = sine(&settings, 0.5);
+        let surface = ImageSurface::create(Format::ARgb32, SIZE.0, SIZE.1).unwrap();
+        let broken = Context::new(&surface).unwrap();
+        // A matrix that cannot be inverted puts the context into an error state
+        broken.scale(0.0, 0.0);
+        assert!(broken.status().is_err());
+
+        let spectrum = renderer(&settings).analyze(&samples, &samples, FRAME_INTERVAL);
+        let registry = VisualizerRegistry::new();
+        for name in registry.names() {
+            let visualizer = registry.create(name, settings.clone()).unwrap();
+            let (mut left, mut right) = (SmoothingState::default(), SmoothingState::default());
+            visualizer.draw(
+                SIZE.0,
+                SIZE.1,
+                &spectrum.left,
+                &spectrum.right,
+                &broken,
+                &mut left,
+                &mut right,
+                0.016,
+            );
+        }
+
+        let mut renderer = renderer(&settings);
+        let spectrum = renderer.analyze(&samples, &samples, FRAME_INTERVAL);
+        renderer.render_frame(&broken, SIZE.0 as f64, SIZE.1 as f64, &spectrum);
+        // The next frame draws to a new context
+        let spectrum = renderer.analyze(&samples, &samples, FRAME_INTERVAL * 2);
+        let black = Color::rgb(0.0, 0.0, 0.0);
+        let mut surface =
+            render_to_surface(&mut renderer, &spectrum, SIZE.0, SIZE.1, black).unwrap();
+        assert!(lit_pixels(&mut surface) > 0);
+    }
+
+    #[test]
+    fn changing_the_bar_count_keeps_drawing() {
+        let settings = Arc::new(Settings::default());
+        let mut renderer = renderer(&settings);
+        let samples = sine(&settings, 0.5);
+        let mut index = 0;
+        let mut frame = |renderer: &mut FrameRenderer| {
+            let spectrum = renderer.analyze(&samples, &samples, FRAME_INTERVAL * index);
+            index += 1;
+            let black = Color::rgb(0.0, 0.0, 0.0);
+            let mut surface =
+                render_to_surface(renderer, &spectrum, SIZE.0, SIZE.1, black).unwrap();
+            lit_pixels(&mut surface)
+        };
+
+        // Zooming, switching visualizers and applying settings each change the number of bars
+        // between frames
+        for step in 0..12 {
+            match step % 4 {
+                0 => {
+                    renderer.zoom_to((500.0, 2000.0));
+                }
+                1 => renderer.reset_zoom(),
+                2 => renderer.next_visualizer(),
+                _ => {
+                    let mut changed = Settings::default();
+                    changed.octave.fraction = 6;
+                    changed.fft.max_frequency = 4000.0 + 2000.0 * step as f32;
+                    renderer.apply_settings(changed);
+                }
+            }
+            for _ in 0..3 {
+                frame(&mut renderer);
+            }
+        }
+        assert!(frame(&mut renderer) > 0, "nothing was drawn");
+    }
+
+    #[test]
+    fn rapid_resizing_keeps_drawing() {
+        // Trails and a waveform layer keep state of the size between frames
+        let mut settings = Settings::default();
+        settings.effects.persistence = 0.8;
+        settings.visualizer.kind = "waveform_spectrum".to_string();
+        let settings = Arc::new(settings);
+        let mut renderer = renderer(&settings);
+        let samples = sine(&settings, 0.5);
+
+        // xorshift32, so every run resizes the same way
+        let mut state = 0x9e37_79b9_u32;
+        let mut random_size = || {
+            let mut next = || {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state % 400) as i32
+            };
+            (next(), next())
+        };
+        for index in 0..200 {
+            let (width, height) = random_size();
+            renderer.resize(f64::from(width), f64::from(height));
+            let (width, height) = random_size();
+            let spectrum = renderer.analyze(&samples, &samples, FRAME_INTERVAL * index);
+            let black = Color::rgb(0.0, 0.0, 0.0);
+            render_to_surface(&mut renderer, &spectrum, width, height, black).unwrap();
+        }
+
+        let spectrum = renderer.analyze(&samples, &samples, FRAME_INTERVAL * 200);
+        let black = Color::rgb(0.0, 0.0, 0.0);
+        let mut surface =
+            render_to_surface(&mut renderer, &spectrum, SIZE.0, SIZE.1, black).unwrap();
+        assert!(lit_pixels(&mut surface) > 0, "nothing was drawn");
+    }
+}
@@ -0,0 +1,226 @@
+use crate::renderer::FrameRenderer;
+use crate::settings::Settings;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the directory of the state file, below the XDG state directory.
+const STATE_DIR: &str = "sonic_spectra";
+/// Name of the state file.
+const STATE_FILE: &str = "state.toml";
+
+/// What was changed while running, saved at exit and restored at the next start, where it
+/// overrides the configuration.
+///
+/// Every field is optional, so a state file written by an older version still loads.
+///
+/// # Fields
+/// - `visualizer`: Name of the visualizer filling the window, as switched with the keyboard.
+/// - `gain`: Gain of the bars, as changed in the preferences window.
+/// - `scale_factor`: Height of 20 dB, as changed in the preferences window.
+/// - `show_grid`: Whether the frequency grid is drawn.
+/// - `show_grid_horizontal`: Whether the horizontal grid lines are drawn.
+/// - `show_grid_vertical`: Whether the vertical frequency markers are drawn.
+/// - `zoom`: The zoomed frequency range in Hz, or `None` while the configured range is shown.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct SessionState {
+    pub visualizer: Option<String>,
+    pub gain: Option<f32>,
+    pub scale_factor: Option<f32>,
+    pub show_grid: Option<bool>,
+    pub show_grid_horizontal: Option<bool>,
+    pub show_grid_vertical: Option<bool>,
+    pub zoom: Option<[f32; 2]>,
+}
+
+impl SessionState {
+    /// Returns the state file in the XDG state directory, `$XDG_STATE_HOME` or
+    /// `~/.local/state`, or `None` if neither is known.
+    pub fn default_path() -> Option<PathBuf> {
+        let state_home = std::env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state"))
+            })?;
+        Some(state_home.join(STATE_DIR).join(STATE_FILE))
+    }
+
+    /// Loads the state saved by a previous run.
+    ///
+    /// # Arguments
+    /// - `path`: The state file.
+    ///
+    /// # Returns
+    /// - The saved state, or an empty state if there is none. A state file that cannot be read
+    ///   or parsed is ignored with a warning.
+    pub fn load(path: &Path) -> Self {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                eprintln!("Ignoring the saved state in {}: {}", path.display(), e);
+                return Self::default();
+            }
+        };
+        match toml::from_str(&contents) {
+            Ok(state) => state,
+            Err(e) => {
+                eprintln!("Ignoring the saved state in {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Writes the state, creating the state directory if needed.
+    ///
+    /// # Arguments
+    /// - `path`: The state file, replaced through a temporary file so an interrupted save leaves
+    ///   the previous state intact.
+    ///
+    /// # Returns
+    /// - An error if the state cannot be converted to TOML or the file cannot be written.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let state = toml::to_string(self).map_err(|e| e.to_string())?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+
+        let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(".tmp");
+        let temp_path = path.with_file_name(temp_name);
+        fs::write(&temp_path, state)
+            .and_then(|()| fs::rename(&temp_path, path))
+            .map_err(|e| {
+                let _ = fs::remove_file(&temp_path);
+                e.to_string()
+            })
+    }
+
+    /// Overrides the configuration with the saved state; the zoom is restored separately, by
+    /// the renderer.
+    ///
+    /// # Arguments
+    /// - `settings`: The settings loaded from the configuration.
+    pub fn apply(&self, settings: &mut Settings) {
+        if let Some(visualizer) = &self.visualizer {
+            match settings.visualizers.first_mut() {
+                Some(layer) => layer.kind = visualizer.clone(),
+                None => settings.visualizer.kind = visualizer.clone(),
+            }
+        }
+        if let Some(gain) = self.gain {
+            settings.visualizer.gain = gain;
+        }
+        if let Some(scale_factor) = self.scale_factor {
+            settings.visualizer.scale_factor = scale_factor;
+        }
+        if let Some(show_grid) = self.show_grid {
+            settings.grid.enabled = show_grid;
+        }
+        if let Some(show_grid_horizontal) = self.show_grid_horizontal {
+            settings.grid.show_horizontal = show_grid_horizontal;
+        }
+        if let Some(show_grid_vertical) = self.show_grid_vertical {
+            settings.grid.show_vertical = show_grid_vertical;
+        }
+    }
+
+    /// Returns the saved zoom, if it is a valid frequency range.
+    pub fn zoom(&self) -> Option<(f32, f32)> {
+        self.zoom
+            .filter(|[low, high]| low.is_finite() && high.is_finite() && 0.0 < *low && low < high)
+            .map(|[low, high]| (low, high))
+    }
+
+    /// Takes over the current state of the renderer, called every frame.
+    ///
+    /// # Arguments
+    /// - `renderer`: The renderer, including the changes made while running.
+    pub fn update(&mut self, renderer: &FrameRenderer) {
+        if self.visualizer.as_deref() != Some(renderer.visualizer_name()) {
+            self.visualizer = Some(renderer.visualizer_name().to_string());
+        }
+        let settings = renderer.settings();
+        self.gain = Some(settings.visualizer.gain);
+        self.scale_factor = Some(settings.visualizer.scale_factor);
+        let grid = renderer.grid_visibility();
+        self.show_grid = Some(grid.enabled);
+        self.show_grid_horizontal = Some(grid.horizontal);
+        self.show_grid_vertical = Some(grid.vertical);
+        self.zoom = renderer.zoomed_range().map(|(low, high)| [low, high]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("sonic_spectra_state_{}", std::process::id()))
+            .join(name)
+    }
+
+    fn tweaked() -> SessionState {
+        SessionState {
+            visualizer: Some("radial".to_string()),
+            gain: Some(2.5),
+            scale_factor: Some(120.0),
+            show_grid: Some(false),
+            show_grid_horizontal: Some(true),
+            show_grid_vertical: Some(false),
+            zoom: Some([200.0, 4000.0]),
+        }
+    }
+
+    #[test]
+    fn saved_state_loads_back_identically() {
+        let path = temp_path("round_trip.toml");
+        for state in [tweaked(), SessionState::default()] {
+            state.save(&path).unwrap();
+            assert_eq!(SessionState::load(&path), state);
+        }
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn missing_and_corrupt_state_is_ignored() {
+        let path = temp_path("corrupt.toml");
+        assert_eq!(SessionState::load(&path), SessionState::default());
+
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "gain = \"loud\"\n[[[").unwrap();
+        assert_eq!(SessionState::load(&path), SessionState::default());
+
+        // Unknown keys, e.g. from a newer version, do not discard the rest
+        fs::write(&path, "gain = 3.0\nfuture_key = 1\n").unwrap();
+        assert_eq!(SessionState::load(&path).gain, Some(3.0));
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn state_overrides_the_configuration() {
+        let mut settings = Settings::default();
+        SessionState::default().apply(&mut settings);
+        assert_eq!(
+            settings.visualizer.kind,
+            Settings::default().visualizer.kind
+        );
+
+        tweaked().apply(&mut settings);
+        assert_eq!(settings.visualizer.kind, "radial");
+        assert_eq!(settings.visualizer.gain, 2.5);
+        assert_eq!(settings.visualizer.scale_factor, 120.0);
+        assert!(!settings.grid.enabled && settings.grid.show_horizontal);
+        assert!(!settings.grid.show_vertical);
+        assert_eq!(tweaked().zoom(), Some((200.0, 4000.0)));
+
+        let reversed = SessionState {
+            zoom: Some([4000.0, 200.0]),
+            ..SessionState::default()
+        };
+        assert_eq!(reversed.zoom(), None);
+    }
+}
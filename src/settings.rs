@@ -1,5 +1,17 @@
-use serde::Deserialize;
+pub use crate::color::Color;
+use crate::fft_utils::interpolation_time_constant_ms;
+use crate::latency::{latency_budget, LatencyBudget};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
 use std::fs;
+use std::path::Path;
+
+/// On-disk configuration file; overrides the embedded defaults when present.
+pub(crate) const CONFIG_PATH: &str = "resources/config.toml";
+
+/// Built-in configuration used when `CONFIG_PATH` cannot be read.
+pub(crate) const DEFAULT_CONFIG: &str = include_str!("../resources/config.toml");
 
 /// FFT (Fast Fourier Transform) settings used for audio processing.
 ///
@@ -9,106 +21,3608 @@ use std::fs;
 /// - `min_frequency`: The minimum frequency for visualization, in Hz.
 /// - `max_frequency`: The maximum frequency for visualization, in Hz.
 /// - `frequencies`: An optional list of specific frequencies for grid visualization.
-#[derive(Deserialize)]
+/// - `hop_size`: Number of captured samples between the ends of consecutive analysis windows;
+///   `size / 4` when not set.
+/// - `average_hops`: Whether a frame shows the average of the windows analyzed since the
+///   previous frame instead of the latest one.
+/// - `latency_target_ms`: Latency of the display to aim for, in milliseconds; when set, the
+///   hop size and `visualizer.smoothing_ms` are picked to approximately meet it at startup.
+/// - `channel_mode`: Whether the two halves show the left and right channels or the mid and side
+///   signals; toggled with `T`.
+/// - `zero_pad_factor`: 1, 2 or 4; the `size` samples of a window are padded with zeros to this
+///   many times their length before the FFT, which interpolates between the bins without adding
+///   latency. The interpolated bins are smoother, not sharper: two tones closer than the bin width
+///   of `size` stay unresolved.
+/// - `generated_frequencies`: Whether `frequencies` was generated from the frequency range rather
+///   than configured; generated frequencies are not saved.
+/// - `solo_band`: Frequency band emphasized by dimming the bars outside of it, in Hz; selected
+///   while running with shift+drag and never saved.
+/// - `target_hop_size`: Hop size picked for `latency_target_ms`, used in place of `hop_size`;
+///   never saved, so the configured `hop_size` is kept.
+///
+/// Missing fields take the values of `FFTSettings::default()`, which match the shipped
+/// `config.toml`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct FFTSettings {
     pub size: usize,
     pub sample_rate: f32,
     pub min_frequency: f32,
     pub max_frequency: f32,
     pub frequencies: Option<Vec<f32>>, // Optional field for custom frequencies
+    pub hop_size: Option<usize>,
+    pub average_hops: bool,
+    pub latency_target_ms: Option<f32>,
+    pub channel_mode: ChannelMode,
+    pub zero_pad_factor: usize,
+    #[serde(skip)]
+    pub(crate) generated_frequencies: bool,
+    #[serde(skip)]
+    pub solo_band: Option<(f32, f32)>,
+    #[serde(skip)]
+    pub(crate) target_hop_size: Option<usize>,
+}
+
+impl Default for FFTSettings {
+    fn default() -> Self {
+        FFTSettings {
+            size: 1024,
+            sample_rate: 44100.0,
+            min_frequency: 20.0,
+            max_frequency: 10000.0,
+            frequencies: None,
+            hop_size: None,
+            average_hops: false,
+            latency_target_ms: None,
+            channel_mode: ChannelMode::Lr,
+            zero_pad_factor: 1,
+            generated_frequencies: false,
+            solo_band: None,
+            target_hop_size: None,
+        }
+    }
+}
+
+/// Signals shown by the two halves of the visualizer, selected through `fft.channel_mode`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelMode {
+    /// The left and right channels.
+    Lr,
+    /// The mid signal `(L + R) / 2` and the side signal `(L - R) / 2`.
+    Ms,
+}
+
+impl FFTSettings {
+    /// Returns the number of captured samples between consecutive analysis windows.
+    ///
+    /// # Returns
+    /// - The hop size picked for `latency_target_ms`, otherwise `hop_size` if set, otherwise a
+    ///   quarter of `size`.
+    pub fn hop_length(&self) -> usize {
+        self.target_hop_size
+            .or(self.hop_size)
+            .unwrap_or(self.size / 4)
+            .max(1)
+    }
+
+    /// Returns the length of the transformed spectra, `size` times `zero_pad_factor`, which bin
+    /// frequencies are computed from.
+    pub fn padded_size(&self) -> usize {
+        self.size * self.zero_pad_factor
+    }
 }
 
 /// Visualizer settings that control the appearance and behavior of the visualizer.
 ///
 /// # Fields
-/// - `gain`: Amplification factor for the visualized data.
+/// - `gain`: Amplification factor of the drawn magnitudes. It only affects the display, moving
+///   the bars and the noise floor up together; `audio.input_gain_db` amplifies the analyzed
+///   samples instead.
 /// - `scale_factor`: Factor to scale visual elements on the screen.
-/// - `interpolation_factor`: Factor controlling interpolation for smoother animations.
+/// - `interpolation_factor`: Fraction (0.0 to 1.0) the bars move towards their new heights per
+///   frame at 60 fps; only read when `smoothing_ms` is not set, and converted into a time constant.
+/// - `smoothing_ms`: Time in milliseconds the bars take to cover about 63% of a change in height,
+///   independent of the frame rate.
 /// - `alpha`: Opacity level of visual elements.
-/// - `smooth_factor`: Smoothing factor to reduce visual jitter.
-#[derive(Deserialize)]
+/// - `smooth_factor`: Smoothing factor to reduce visual jitter; used to derive `release` when it is
+///   not set.
+/// - `attack`: Fraction (0.0 to 1.0) a rising bin magnitude moves towards its new value per frame.
+/// - `release`: Fraction (0.0 to 1.0) a falling bin magnitude moves towards its new value per frame.
+/// - `bin_smoothing`: Number of neighboring bins (0 to 5) on each side averaged into each bin.
+/// - `palette`: Color palette used to color the bars; the rainbow when neither it nor
+///   `palette_cycle` is set.
+/// - `palette_cycle`: Palettes the bars fade through over time instead of `palette`, looping
+///   back to the first; per-visualizer tables picking a palette of their own keep it.
+/// - `palette_cycle_seconds`: Time each fade into the next palette of `palette_cycle` takes, in
+///   seconds.
+/// - `stops`: Gradient stops used when `palette` or an entry of `palette_cycle` is `"custom"`.
+/// - `color_mode`: Whether bar colors follow frequency, magnitude, or both.
+/// - `kind`: Name of the visualizer to display (`"frequency"`, `"holographic_glow"`, `"radial"`,
+///   `"line"`, `"octave"`, `"chromagram"`, `"waveform_spectrum"`, `"difference"`, or any
+///   visualizer added with `register_visualizer`).
+/// - `auto_gain`: Whether the gain is adjusted automatically so the loudest recent bar reaches
+///   about 90% of the drawing height; `gain` is then applied before the automatic gain.
+/// - `auto_gain_window_secs`: Length of the window the loudest bar is tracked over, in seconds.
+/// - `auto_gain_attack`: Fraction (0.0 to 1.0) the automatic gain moves per frame when decreasing.
+/// - `auto_gain_release`: Fraction (0.0 to 1.0) the automatic gain moves per frame when increasing.
+/// - `frequency_scale`: Whether frequencies are spread linearly or logarithmically across the
+///   width, for both the bars and the grid.
+/// - `min_bar_height`: Bars lower than this many pixels are skipped by the holographic glow
+///   visualizer, saving their gradients during silence.
+/// - `direction`: Edge the bars grow from; the horizontal grid lines follow it.
+/// - `bar_gap`: Space left between neighboring bars of the frequency, holographic glow and
+///   waveform spectrum visualizers, in pixels or as a percentage of the space of each bar; at
+///   most half of that space is left empty.
+/// - `bar_radius`: Radius of the rounded corners of the bars, in pixels; 0 draws square corners.
+///   Ignored by the OpenGL renderer.
+/// - `bar_gradient`: Whether the frequency and waveform spectrum bars fade from a darker, more
+///   transparent shade at their base to their color at the tip instead of being filled flat.
+///   Ignored by the OpenGL renderer.
+/// - `curve`: Named exponent applied to the bar heights, relative to the height of a full-scale
+///   sine; only read when `curve_exponent` is not set.
+/// - `curve_exponent`: Exponent applied to the bar heights relative to the height of a full-scale
+///   sine; above 1.0 quiet content is lowered and the loud peaks stand out, below 1.0 mid-level
+///   content is raised.
+/// - `renderer`: Whether the window draws with Cairo or OpenGL; OpenGL requires building with
+///   the `gl` feature.
+/// - `overrides`: Per-visualizer override tables such as `[visualizer.frequency]`, keyed by
+///   visualizer name.
+/// - `unrecognized`: Every key of `[visualizer]` that is not a setting, including the override
+///   tables; `parse_config` reports the ones that are not tables of a registered visualizer.
+/// - `target_smoothing_ms`: Smoothing time picked for `fft.latency_target_ms`, used in place of
+///   `smoothing_ms`; never saved, so the configured `smoothing_ms` is kept.
+///
+/// Missing fields take the values of `VisualizerSettings::default()`, which match the shipped
+/// `config.toml`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct VisualizerSettings {
     pub gain: f32,
     pub scale_factor: f32,
     pub interpolation_factor: f32,
+    pub smoothing_ms: Option<f32>,
     pub alpha: f32,
     pub smooth_factor: f32,
+    pub attack: f32,
+    pub release: Option<f32>,
+    pub bin_smoothing: usize,
+    pub palette: Option<PaletteKind>,
+    pub palette_cycle: Vec<PaletteKind>,
+    pub palette_cycle_seconds: f32,
+    pub stops: Vec<GradientStopSettings>,
+    pub color_mode: ColorMode,
+    pub kind: String,
+    pub auto_gain: bool,
+    pub auto_gain_window_secs: f32,
+    pub auto_gain_attack: f32,
+    pub auto_gain_release: f32,
+    pub frequency_scale: FrequencyScale,
+    pub min_bar_height: f32,
+    pub direction: BarDirection,
+    pub bar_gap: MarginLength,
+    pub bar_radius: f32,
+    pub bar_gradient: bool,
+    pub curve: HeightCurve,
+    pub curve_exponent: Option<f32>,
+    pub renderer: RendererKind,
+    #[serde(flatten, deserialize_with = "deserialize_override_tables")]
+    pub overrides: HashMap<String, VisualizerOverrides>,
+    #[serde(flatten, skip_serializing)]
+    pub(crate) unrecognized: HashMap<String, toml::Value>,
+    #[serde(skip)]
+    pub(crate) target_smoothing_ms: Option<f32>,
+}
+
+impl Default for VisualizerSettings {
+    fn default() -> Self {
+        VisualizerSettings {
+            gain: 20.0,
+            scale_factor: 90.0,
+            interpolation_factor: 0.09,
+            smoothing_ms: None,
+            alpha: 0.8,
+            smooth_factor: 0.7,
+            attack: 0.8,
+            release: None,
+            bin_smoothing: 0,
+            palette: None,
+            palette_cycle: Vec::new(),
+            palette_cycle_seconds: 120.0,
+            stops: Vec::new(),
+            color_mode: ColorMode::default(),
+            kind: "frequency".to_string(),
+            auto_gain: false,
+            auto_gain_window_secs: 3.0,
+            auto_gain_attack: 0.5,
+            auto_gain_release: 0.02,
+            frequency_scale: FrequencyScale::default(),
+            min_bar_height: 0.5,
+            direction: BarDirection::default(),
+            bar_gap: MarginLength::default(),
+            bar_radius: 0.0,
+            bar_gradient: false,
+            curve: HeightCurve::default(),
+            curve_exponent: None,
+            renderer: RendererKind::default(),
+            overrides: HashMap::new(),
+            unrecognized: HashMap::new(),
+            target_smoothing_ms: None,
+        }
+    }
+}
+
+/// Drawing settings of one visualizer, overriding the common `[visualizer]` values.
+///
+/// Each field left unset falls back to the common value of the same name.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct VisualizerOverrides {
+    pub gain: Option<f32>,
+    pub scale_factor: Option<f32>,
+    pub interpolation_factor: Option<f32>,
+    pub smoothing_ms: Option<f32>,
+    pub alpha: Option<f32>,
+    pub palette: Option<PaletteKind>,
+    pub stops: Option<Vec<GradientStopSettings>>,
+    pub color_mode: Option<ColorMode>,
+    pub curve_exponent: Option<f32>,
+    pub direction: Option<BarDirection>,
+    pub bar_gap: Option<MarginLength>,
+    pub bar_radius: Option<f32>,
+    pub bar_gradient: Option<bool>,
+}
+
+impl VisualizerOverrides {
+    /// Merges these overrides over another table, keeping the values of `base` this table leaves
+    /// unset.
+    ///
+    /// # Arguments
+    /// - `base`: The overrides taking effect where this table has none.
+    pub fn over(&self, base: &VisualizerOverrides) -> VisualizerOverrides {
+        VisualizerOverrides {
+            gain: self.gain.or(base.gain),
+            scale_factor: self.scale_factor.or(base.scale_factor),
+            interpolation_factor: self.interpolation_factor.or(base.interpolation_factor),
+            smoothing_ms: self.smoothing_ms.or(base.smoothing_ms),
+            alpha: self.alpha.or(base.alpha),
+            palette: self.palette.or(base.palette),
+            stops: self.stops.clone().or_else(|| base.stops.clone()),
+            color_mode: self.color_mode.or(base.color_mode),
+            curve_exponent: self.curve_exponent.or(base.curve_exponent),
+            direction: self.direction.or(base.direction),
+            bar_gap: self.bar_gap.or(base.bar_gap),
+            bar_radius: self.bar_radius.or(base.bar_radius),
+            bar_gradient: self.bar_gradient.or(base.bar_gradient),
+        }
+    }
+}
+
+/// Deserializes the tables among the keys of `[visualizer]` that are not settings as override
+/// tables; other values are left for `parse_config` to report as unknown keys, instead of
+/// failing the whole configuration.
+fn deserialize_override_tables<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<String, VisualizerOverrides>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    HashMap::<String, toml::Value>::deserialize(deserializer)?
+        .into_iter()
+        .filter(|(_, value)| value.is_table())
+        .map(|(kind, table)| {
+            VisualizerOverrides::deserialize(table)
+                .map(|overrides| (kind.clone(), overrides))
+                .map_err(|e| serde::de::Error::custom(format!("visualizer.{}: {}", kind, e)))
+        })
+        .collect()
+}
+
+/// Drawing settings of one visualizer after merging its overrides over the common values.
+///
+/// # Fields
+/// - `gain`: Amplification factor for the visualized data.
+/// - `scale_factor`: Factor to scale visual elements on the screen.
+/// - `smoothing_ms`: Time in milliseconds the bars take to cover about 63% of a change in height.
+/// - `alpha`: Opacity level of visual elements.
+/// - `palette`: Color palette used to color the bars.
+/// - `stops`: Gradient stops used when `palette` is `"custom"`.
+/// - `color_mode`: Whether bar colors follow frequency, magnitude, or both.
+/// - `curve_exponent`: Exponent applied to the bar heights relative to a full-scale sine.
+/// - `direction`: Edge the bars grow from.
+/// - `bar_gap`: Space left between neighboring bars.
+/// - `bar_radius`: Radius of the rounded corners of the bars, in pixels.
+/// - `bar_gradient`: Whether the bars fade from a darker shade at their base to their color.
+#[derive(Clone, Debug)]
+pub struct ResolvedVisualizerSettings {
+    pub gain: f32,
+    pub scale_factor: f32,
+    pub smoothing_ms: f32,
+    pub alpha: f32,
+    pub palette: PaletteKind,
+    pub stops: Vec<GradientStopSettings>,
+    pub color_mode: ColorMode,
+    pub curve_exponent: f32,
+    pub direction: BarDirection,
+    pub bar_gap: MarginLength,
+    pub bar_radius: f32,
+    pub bar_gradient: bool,
+}
+
+impl VisualizerSettings {
+    /// Returns the release factor of the temporal smoothing.
+    ///
+    /// # Returns
+    /// - `release` if set, otherwise `1.0 - smooth_factor`.
+    pub fn release_factor(&self) -> f32 {
+        self.release.unwrap_or(1.0 - self.smooth_factor)
+    }
+
+    /// Returns the time constant of the bar smoothing.
+    ///
+    /// # Returns
+    /// - The smoothing time picked for `fft.latency_target_ms`, otherwise `smoothing_ms` if set,
+    ///   otherwise `interpolation_factor` converted into milliseconds.
+    pub fn smoothing_ms(&self) -> f32 {
+        self.target_smoothing_ms
+            .or(self.smoothing_ms)
+            .unwrap_or_else(|| interpolation_time_constant_ms(self.interpolation_factor))
+    }
+
+    /// Returns the exponent applied to the bar heights.
+    ///
+    /// # Returns
+    /// - `curve_exponent` if set, otherwise the exponent of `curve`.
+    pub fn curve_exponent(&self) -> f32 {
+        self.curve_exponent.unwrap_or_else(|| self.curve.exponent())
+    }
+
+    /// Returns whether a visualizer takes its colors from `palette_cycle`: a cycle is set and the
+    /// table of the visualizer does not pick a palette of its own.
+    ///
+    /// # Arguments
+    /// - `kind`: Name of the visualizer.
+    pub fn follows_palette_cycle(&self, kind: &str) -> bool {
+        !self.palette_cycle.is_empty()
+            && self
+                .overrides
+                .get(kind)
+                .and_then(|overrides| overrides.palette)
+                .is_none()
+    }
+
+    /// Merges the override table of a visualizer over the common values.
+    ///
+    /// # Arguments
+    /// - `kind`: Name of the visualizer; a visualizer without a table uses the common values.
+    ///
+    /// # Returns
+    /// - The drawing settings of the visualizer.
+    ///
+    /// Fractions outside [0.0, 1.0] that bypassed `Settings::validate` are clamped, so they
+    /// cannot make the bars overshoot or turn NaN. They are not reported here, as this runs for
+    /// every visualizer whenever the settings change; `validate` reports them once instead.
+    pub fn resolve(&self, kind: &str) -> ResolvedVisualizerSettings {
+        let overrides = self.overrides.get(kind).cloned().unwrap_or_default();
+        let smoothing_time =
+            |factor: f32| interpolation_time_constant_ms(drawable_fraction(factor));
+        ResolvedVisualizerSettings {
+            gain: overrides.gain.unwrap_or(self.gain),
+            scale_factor: overrides.scale_factor.unwrap_or(self.scale_factor),
+            smoothing_ms: match (overrides.smoothing_ms, overrides.interpolation_factor) {
+                (Some(smoothing_ms), _) => smoothing_ms,
+                (None, Some(factor)) => smoothing_time(factor),
+                (None, None) => self
+                    .target_smoothing_ms
+                    .or(self.smoothing_ms)
+                    .unwrap_or_else(|| smoothing_time(self.interpolation_factor)),
+            },
+            alpha: drawable_fraction(overrides.alpha.unwrap_or(self.alpha)),
+            palette: overrides.palette.or(self.palette).unwrap_or_default(),
+            stops: overrides.stops.unwrap_or_else(|| self.stops.clone()),
+            color_mode: overrides.color_mode.unwrap_or(self.color_mode),
+            curve_exponent: overrides
+                .curve_exponent
+                .unwrap_or_else(|| self.curve_exponent()),
+            direction: overrides.direction.unwrap_or(self.direction),
+            bar_gap: overrides.bar_gap.unwrap_or(self.bar_gap),
+            bar_radius: overrides.bar_radius.unwrap_or(self.bar_radius),
+            bar_gradient: overrides.bar_gradient.unwrap_or(self.bar_gradient),
+        }
+    }
+}
+
+/// Clamps a fraction the visualizers draw with into [0.0, 1.0], silently, as `validate`
+/// already reports values outside of it.
+///
+/// # Arguments
+/// - `value`: The fraction; NaN is replaced by 1.0.
+fn drawable_fraction(value: f32) -> f32 {
+    if value.is_nan() {
+        1.0
+    } else {
+        value.clamp(0.0, 1.0)
+    }
+}
+
+/// Named color palettes selectable through `visualizer.palette`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PaletteKind {
+    #[default]
+    Rainbow,
+    Viridis,
+    Inferno,
+    Mono,
+    Custom,
+}
+
+/// Named exponents of the bar heights, selected through `visualizer.curve`.
+///
+/// - `Linear`: Heights follow the level in dB.
+/// - `Punchy`: Quiet content is lowered, so the loud peaks stand out.
+/// - `Soft`: Mid-level content is raised, so it moves more.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HeightCurve {
+    #[default]
+    Linear,
+    Punchy,
+    Soft,
+}
+
+impl HeightCurve {
+    /// Returns the exponent applied to the bar heights relative to a full-scale sine.
+    pub fn exponent(self) -> f32 {
+        match self {
+            HeightCurve::Linear => 1.0,
+            HeightCurve::Punchy => 1.6,
+            HeightCurve::Soft => 0.6,
+        }
+    }
+}
+
+/// How bar colors are looked up in the palette, selected through `visualizer.color_mode`.
+///
+/// - `Frequency`: Palette position follows the bar's frequency.
+/// - `Magnitude`: Palette position follows the bar's normalized height.
+/// - `Both`: Palette position follows frequency while lightness and opacity follow height.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    #[default]
+    Frequency,
+    Magnitude,
+    Both,
+}
+
+/// How frequencies are spread across the width, selected through `visualizer.frequency_scale`.
+///
+/// - `Linear`: Equal frequency differences take equal widths.
+/// - `Log`: Equal frequency ratios, such as octaves, take equal widths.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FrequencyScale {
+    #[default]
+    Linear,
+    Log,
+}
+
+/// Where the bars grow from, selected through `visualizer.direction`.
+///
+/// - `Up`: Bars stand on the bottom edge.
+/// - `Down`: Bars hang from the top edge.
+/// - `Center`: Bars grow both ways from the middle, half of their height on each side.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BarDirection {
+    #[default]
+    Up,
+    Down,
+    Center,
+}
+
+/// How the window is drawn, selected through `visualizer.renderer`.
+///
+/// - `Cairo`: Everything is drawn with Cairo.
+/// - `Gl`: Visualizers with an OpenGL port draw their bars as instanced quads in a `GLArea`;
+///   everything else is still drawn with Cairo.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RendererKind {
+    #[default]
+    Cairo,
+    Gl,
+}
+
+/// Frequencies marked by the vertical grid lines, selected through `grid.marker_mode`.
+///
+/// - `Frequencies`: The configured `fft.frequencies`.
+/// - `Octaves`: Every A, the octaves of A4 (55, 110, 220, 440, 880 Hz...).
+/// - `Notes`: Every C, the start of each octave of note names.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MarkerMode {
+    #[default]
+    Frequencies,
+    Octaves,
+    Notes,
+}
+
+/// Where the vertical markers of the grid take their colors from.
+///
+/// - `Fixed`: `grid.color_left` and `grid.color_right`.
+/// - `Palette`: The palette of the visualizer, at the color its bars have at the marked
+///   frequency.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GridColorMode {
+    #[default]
+    Fixed,
+    Palette,
+}
+
+/// A gradient stop of a custom palette.
+///
+/// # Fields
+/// - `pos`: Position of the stop along the gradient, in the range [0.0, 1.0].
+/// - `color`: Hex color string, either `#rrggbb` or `#rgb`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GradientStopSettings {
+    pub pos: f32,
+    pub color: String,
 }
 
 /// Grid settings for configuring the frequency grid in the visualizer.
 ///
 /// # Fields
+/// - `enabled`: Whether the grid is drawn at startup; toggled with `G`.
+/// - `show_horizontal`: Whether the horizontal lines are drawn at startup; toggled with `H`.
+/// - `show_vertical`: Whether the vertical frequency markers are drawn at startup; toggled
+///   with `M`.
 /// - `lines`: The number of horizontal lines in the grid.
-/// - `color_left`: RGB color for the left channel lines.
-/// - `color_right`: RGB color for the right channel lines.
-/// - `color_horizontal`: RGB color for horizontal grid lines.
+/// - `color_left`: Color of the left channel lines.
+/// - `color_right`: Color of the right channel lines.
+/// - `color_horizontal`: Color of the horizontal grid lines.
 /// - `alpha`: Transparency level for the grid lines.
 /// - `line_width`: Width of each grid line.
-#[derive(Deserialize)]
+/// - `db_lines`: Whether horizontal lines mark dB levels of the bars instead of `lines` even
+///   divisions.
+/// - `db_step`: Distance between neighboring dB lines, in dB.
+/// - `marker_mode`: Frequencies marked by the vertical lines; note modes are tuned by
+///   `note_readout.a4`.
+/// - `marker_labels`: Whether the vertical lines are labelled with their frequency or note.
+/// - `color_mode`: Whether the vertical lines and their labels take `color_left` and
+///   `color_right`, or the palette color of the bars at their frequency; both at `alpha`.
+///
+/// Missing fields take the values of `GridSettings::default()`, which match the shipped
+/// `config.toml`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct GridSettings {
+    pub enabled: bool,
+    pub show_horizontal: bool,
+    pub show_vertical: bool,
     pub lines: usize,
-    pub color_left: [f64; 3],
-    pub color_right: [f64; 3],
-    pub color_horizontal: [f64; 3],
+    pub color_left: Color,
+    pub color_right: Color,
+    pub color_horizontal: Color,
     pub alpha: f64,
     pub line_width: f64,
+    pub db_lines: bool,
+    pub db_step: f32,
+    pub marker_mode: MarkerMode,
+    pub marker_labels: bool,
+    pub color_mode: GridColorMode,
 }
 
-/// Root settings structure containing all configuration settings, including FFT, visualizer,
-/// and grid configurations.
-#[derive(Deserialize)]
-pub struct Settings {
-    pub fft: FFTSettings,
-    pub visualizer: VisualizerSettings,
-    pub grid: GridSettings,
+impl Default for GridSettings {
+    fn default() -> Self {
+        GridSettings {
+            enabled: true,
+            show_horizontal: true,
+            show_vertical: true,
+            lines: 10,
+            color_left: Color::rgb(1.0, 0.0, 0.0),
+            color_right: Color::rgb(0.0, 1.0, 0.0),
+            color_horizontal: Color::rgb(1.0, 1.0, 1.0),
+            alpha: 0.1,
+            line_width: 0.5,
+            db_lines: false,
+            db_step: 10.0,
+            marker_mode: MarkerMode::default(),
+            marker_labels: false,
+            color_mode: GridColorMode::default(),
+        }
+    }
 }
 
-impl FFTSettings {
-    /// Generates logarithmically spaced frequencies if they are not provided in the configuration.
-    ///
-    /// # Arguments
-    /// - `num_frequencies`: The number of frequencies to generate.
-    ///
-    /// # Returns
-    /// - A vector of `num_frequencies` logarithmically spaced frequencies between `min_frequency`
-    ///   and `max_frequency`.
-    ///
-    /// This function is useful when a specific list of frequencies is not provided, ensuring that
-    /// the visualizer has frequency markers spread across the audible range.
-    pub fn generate_frequencies(&self, num_frequencies: usize) -> Vec<f32> {
-        let log_min = self.min_frequency.log10();
-        let log_max = self.max_frequency.log10();
+/// Settings for the radial visualizer.
+///
+/// # Fields
+/// - `inner_radius`: Fraction of the available radius left empty in the center (0.0 to 1.0).
+/// - `angle_step`: Angle between neighboring bars in degrees; `0.0` spreads the bars evenly
+///   over each semicircle.
+/// - `rotation_offset`: Rotation of the whole circle in degrees.
+/// - `rotation_speed`: Spin speed of the circle in degrees per second.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RadialSettings {
+    pub inner_radius: f64,
+    pub angle_step: f64,
+    pub rotation_offset: f64,
+    pub rotation_speed: f64,
+}
 
-        // Create a vector of frequencies spaced logarithmically between `min_frequency` and `max_frequency`
-        (0..num_frequencies)
-            .map(|i| {
-                let fraction = i as f32 / (num_frequencies - 1) as f32;
-                let log_freq = log_min + fraction * (log_max - log_min);
-                10_f32.powf(log_freq)
-            })
-            .collect()
+impl Default for RadialSettings {
+    fn default() -> Self {
+        RadialSettings {
+            inner_radius: 0.3,
+            angle_step: 0.0,
+            rotation_offset: 0.0,
+            rotation_speed: 0.0,
+        }
     }
 }
 
-impl Settings {
-    /// Loads and initializes settings from a configuration file (`config.toml`).
-    ///
-    /// # Returns
-    /// - A `Settings` instance populated with data from the configuration file.
-    ///
-    /// If the `frequencies` field in `fft` settings is `None`, this method will auto-generate
-    /// 15 logarithmically spaced frequencies between `min_frequency` and `max_frequency`.
-    pub fn new() -> Self {
-        let config_str =
-            fs::read_to_string("resources/config.toml").expect("Unable to read config file");
-        let mut settings: Settings = toml::from_str(&config_str).expect("Invalid config format");
+/// Settings for the line spectrum visualizer.
+///
+/// # Fields
+/// - `line_width`: Width of the curve outline in pixels.
+/// - `fill`: Whether the area under the curve is filled with a fading gradient.
+/// - `mode`: Whether the channels are overlaid or mirrored top/bottom.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LineSettings {
+    pub line_width: f64,
+    pub fill: bool,
+    pub mode: LineMode,
+}
 
-        // Generate frequencies if they are not set in the configuration
-        if settings.fft.frequencies.is_none() {
-            settings.fft.frequencies = Some(settings.fft.generate_frequencies(15));
-            // Generate 15 logarithmic frequencies
+impl Default for LineSettings {
+    fn default() -> Self {
+        LineSettings {
+            line_width: 2.0,
+            fill: true,
+            mode: LineMode::Overlay,
         }
+    }
+}
 
-        settings
+/// Settings for the octave band visualizer.
+///
+/// # Fields
+/// - `fraction`: Bands per octave: `1` for octaves, `3` for third-octaves or `6` for
+///   sixth-octaves.
+/// - `labels`: Whether the nominal center frequency is written below each band.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct OctaveSettings {
+    pub fraction: u32,
+    pub labels: bool,
+}
+
+impl Default for OctaveSettings {
+    fn default() -> Self {
+        OctaveSettings {
+            fraction: 3,
+            labels: true,
+        }
+    }
+}
+
+/// Settings for the chromagram visualizer.
+///
+/// Notes are tuned to `note_readout.a4`.
+///
+/// # Fields
+/// - `smoothing_secs`: Time constant of the smoothing of the pitch class levels, in seconds;
+///   `0.0` shows every frame as analyzed.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ChromagramSettings {
+    pub smoothing_secs: f32,
+}
+
+impl Default for ChromagramSettings {
+    fn default() -> Self {
+        ChromagramSettings {
+            smoothing_secs: 0.15,
+        }
+    }
+}
+
+/// Settings for the left/right difference visualizer.
+///
+/// # Fields
+/// - `range_db`: Level difference reaching the top or bottom of the drawing area, in dB; larger
+///   differences are clamped to it.
+/// - `color_left`: Color of the bars rising above the center line, where the left channel is
+///   louder.
+/// - `color_right`: Color of the bars hanging below the center line, where the right channel is
+///   louder.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct DifferenceSettings {
+    pub range_db: f32,
+    pub color_left: Color,
+    pub color_right: Color,
+}
+
+impl Default for DifferenceSettings {
+    fn default() -> Self {
+        DifferenceSettings {
+            range_db: 24.0,
+            color_left: Color::rgb(1.0, 0.0, 0.0),
+            color_right: Color::rgb(0.0, 1.0, 0.0),
+        }
+    }
+}
+
+/// Settings for the combined waveform and spectrum visualizer.
+///
+/// # Fields
+/// - `split`: Fraction of the height taken by the scrolling waveform above the bars.
+/// - `seconds`: Length of the audio shown by the waveform, in seconds.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct WaveformSettings {
+    pub split: f32,
+    pub seconds: f32,
+}
+
+impl Default for WaveformSettings {
+    fn default() -> Self {
+        WaveformSettings {
+            split: 0.3,
+            seconds: 4.0,
+        }
+    }
+}
+
+/// Placement of the channels in the line spectrum visualizer, selected through `line.mode`.
+///
+/// - `Overlay`: Both channels grow upward from the bottom edge in different colors.
+/// - `Mirrored`: The left channel grows upward and the right channel downward from the center.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineMode {
+    Overlay,
+    Mirrored,
+}
+
+/// Settings for what is drawn behind the grid and the visualizer.
+///
+/// # Fields
+/// - `kind`: Whether the background comes from the stylesheet, a color, a gradient or an image.
+/// - `color`: Color of the `"solid"` background.
+/// - `gradient`: Top and bottom colors of the `"gradient"` background.
+/// - `image`: Path of the PNG or JPEG file shown by the `"image"` background.
+/// - `image_mode`: Whether the image is scaled to cover the window or tiled.
+///
+/// The `gl` renderer leaves the background to the stylesheet while it draws the bars.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct BackgroundSettings {
+    pub kind: BackgroundKind,
+    pub color: Color,
+    pub gradient: [Color; 2],
+    pub image: String,
+    pub image_mode: ImageMode,
+}
+
+impl Default for BackgroundSettings {
+    fn default() -> Self {
+        BackgroundSettings {
+            kind: BackgroundKind::Css,
+            color: Color::rgb(0.0, 0.0, 0.0),
+            gradient: [
+                Color::rgb(0.0, 0.0, 0.0),
+                Color::rgb(26.0 / 255.0, 26.0 / 255.0, 64.0 / 255.0),
+            ],
+            image: String::new(),
+            image_mode: ImageMode::Scale,
+        }
+    }
+}
+
+/// Source of the background, selected through `background.kind`.
+///
+/// - `Css`: Nothing is drawn, leaving the background to `resources/style.css`.
+/// - `Solid`: A single color.
+/// - `Gradient`: A vertical gradient between two colors.
+/// - `Image`: An image file.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackgroundKind {
+    Css,
+    Solid,
+    Gradient,
+    Image,
+}
+
+/// Placement of the background image, selected through `background.image_mode`.
+///
+/// - `Scale`: The image is scaled to cover the window, keeping its aspect ratio and cropping
+///   the overflow.
+/// - `Tile`: The image is repeated at its original size.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageMode {
+    Scale,
+    Tile,
+}
+
+/// Settings for optional background effects.
+///
+/// # Fields
+/// - `bass_pulse`: Whether the bass-reactive background pulse is drawn.
+/// - `band`: Lower and upper edge of the measured bass band, in Hz.
+/// - `intensity`: Factor converting the measured band amplitude into pulse strength.
+/// - `color`: Color of the pulse; an alpha given in the color replaces `max_alpha`.
+/// - `max_alpha`: Upper bound for the pulse opacity, keeping grid and bars readable.
+/// - `pulse_style`: Whether the pulse is a radial glow or a full-background flash.
+/// - `persistence`: Fraction of the previous frames kept behind the visualizer each frame, in
+///   [0.0, 0.95]; `0.0` disables the trails. The `gl` renderer draws its bars without trails.
+/// - `reflection`: Whether a flipped, fading copy of the visualizer and its trails is drawn
+///   below the plot, like a glossy floor. Later `visualizers` layers are not reflected.
+/// - `reflection_height`: Fraction (0.0 to 1.0) of the plot height taken by the reflection; the
+///   bars keep the rest.
+/// - `reflection_alpha`: Opacity (0.0 to 1.0) of the reflection at the bottom of the bars,
+///   fading to transparent at the bottom of the reflection.
+/// - `glow_gradients`: Whether the holographic glow bars are filled with a radial gradient
+///   fading out from the center, or with a flat color.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct EffectsSettings {
+    pub bass_pulse: bool,
+    pub band: [f32; 2],
+    pub intensity: f32,
+    pub color: Color,
+    pub max_alpha: f64,
+    pub pulse_style: PulseStyle,
+    pub persistence: f64,
+    pub reflection: bool,
+    pub reflection_height: f64,
+    pub reflection_alpha: f64,
+    pub glow_gradients: bool,
+}
+
+impl Default for EffectsSettings {
+    fn default() -> Self {
+        EffectsSettings {
+            bass_pulse: false,
+            band: [30.0, 120.0],
+            intensity: 4.0,
+            color: Color::rgb(0.4, 0.2, 1.0),
+            max_alpha: 0.35,
+            pulse_style: PulseStyle::Glow,
+            persistence: 0.0,
+            reflection: false,
+            reflection_height: 0.25,
+            reflection_alpha: 0.4,
+            glow_gradients: true,
+        }
+    }
+}
+
+/// Settings for the long-term average and max-hold curves drawn over the bars.
+///
+/// Both curves follow the bars of the first visualizer, one point per bar, and use the same
+/// heights the bars are smoothed toward, so a steady tone puts them on top of its bar.
+///
+/// # Fields
+/// - `average`: Whether the long-term average of every bar is drawn.
+/// - `average_secs`: Time the average takes to cover about 63% of a change, in seconds.
+/// - `average_color`: Color of the average curve.
+/// - `max_hold`: Whether the highest level every bar reached is drawn; reset at runtime with
+///   the `Shift+H` key.
+/// - `max_hold_color`: Color of the max-hold curve.
+/// - `alpha`: Opacity (0.0 to 1.0) of the curves, unless their colors carry their own.
+/// - `line_width`: Width of the curves, in pixels.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct CurvesSettings {
+    pub average: bool,
+    pub average_secs: f32,
+    pub average_color: Color,
+    pub max_hold: bool,
+    pub max_hold_color: Color,
+    pub alpha: f64,
+    pub line_width: f64,
+}
+
+impl Default for CurvesSettings {
+    fn default() -> Self {
+        CurvesSettings {
+            average: false,
+            average_secs: 10.0,
+            average_color: Color::rgb(1.0, 1.0, 1.0),
+            max_hold: false,
+            max_hold_color: Color::rgb(1.0, 0.3, 0.2),
+            alpha: 0.8,
+            line_width: 1.5,
+        }
+    }
+}
+
+/// Appearance of the bass pulse, selected through `effects.pulse_style`.
+///
+/// - `Glow`: A radial glow rising from the bottom center behind the bars.
+/// - `Flash`: A uniform brightness pulse over the whole background.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PulseStyle {
+    Glow,
+    Flash,
+}
+
+/// Settings for beat detection.
+///
+/// # Fields
+/// - `enabled`: Whether beats are detected at all.
+/// - `sensitivity`: Number of standard deviations the spectral flux must exceed its running mean by.
+/// - `min_interval_ms`: Minimum time between two reported beats, in milliseconds.
+/// - `flash`: Whether detected beats briefly flash the background pulse.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct BeatSettings {
+    pub enabled: bool,
+    pub sensitivity: f32,
+    pub min_interval_ms: u64,
+    pub flash: bool,
+}
+
+impl Default for BeatSettings {
+    fn default() -> Self {
+        BeatSettings {
+            enabled: true,
+            sensitivity: 2.0,
+            min_interval_ms: 150,
+            flash: true,
+        }
+    }
+}
+
+/// Settings for the dominant frequency and note readout overlay.
+///
+/// # Fields
+/// - `enabled`: Whether the readout is shown at startup; toggled at runtime with the `N` key.
+/// - `a4`: Reference tuning of A4, in Hz.
+/// - `threshold`: Minimum peak amplitude (0.0 to 1.0) for the readout to be shown.
+/// - `update_interval_ms`: Time between readout refreshes, in milliseconds.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct NoteReadoutSettings {
+    pub enabled: bool,
+    pub a4: f32,
+    pub threshold: f32,
+    pub update_interval_ms: u64,
+}
+
+impl Default for NoteReadoutSettings {
+    fn default() -> Self {
+        NoteReadoutSettings {
+            enabled: false,
+            a4: 440.0,
+            threshold: 0.01,
+            update_interval_ms: 250,
+        }
+    }
+}
+
+/// Settings for the loudness and true-peak meter overlay.
+///
+/// # Fields
+/// - `enabled`: Whether the loudness and true-peak meter is shown at startup; toggled at runtime
+///   with the `L` key.
+/// - `corner`: Corner of the window the meter is drawn in.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LoudnessSettings {
+    pub enabled: bool,
+    pub corner: Corner,
+}
+
+impl Default for LoudnessSettings {
+    fn default() -> Self {
+        LoudnessSettings {
+            enabled: false,
+            corner: Corner::TopRight,
+        }
+    }
+}
+
+/// Named part of the window a visualizer layer is drawn in.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Anchor {
+    /// The whole window; the layer's `size` is ignored.
+    Full,
+    /// A strip along the top edge, `size` of the height tall.
+    Top,
+    /// A strip along the bottom edge, `size` of the height tall.
+    Bottom,
+    /// A strip along the left edge, `size` of the width wide.
+    Left,
+    /// A strip along the right edge, `size` of the width wide.
+    Right,
+    /// `size` of the width and height, centered.
+    Center,
+    /// `size` of the width and height, in the top-left corner.
+    TopLeft,
+    /// `size` of the width and height, in the top-right corner.
+    TopRight,
+    /// `size` of the width and height, in the bottom-left corner.
+    BottomLeft,
+    /// `size` of the width and height, in the bottom-right corner.
+    BottomRight,
+}
+
+/// Part of the window a visualizer layer is drawn in.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Region {
+    /// A named part of the window, sized by the layer's `size`.
+    Anchor(Anchor),
+    /// An explicit rectangle `[x, y, width, height]`, as fractions (0.0 to 1.0) of the window.
+    Rect([f32; 4]),
+}
+
+/// One visualizer of the layer stack configured through `visualizers`.
+///
+/// # Fields
+/// - `kind`: Name of the visualizer, as in `visualizer.kind`.
+/// - `region`: Part of the window the layer is drawn in.
+/// - `size`: Fraction (0.0 to 1.0) of the window taken by anchored regions other than `"full"`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct LayerSettings {
+    pub kind: String,
+    pub region: Region,
+    pub size: f32,
+}
+
+impl Default for LayerSettings {
+    fn default() -> Self {
+        LayerSettings {
+            kind: "frequency".to_string(),
+            region: Region::Anchor(Anchor::Full),
+            size: 0.25,
+        }
+    }
+}
+
+impl LayerSettings {
+    /// Returns the rectangle the layer is drawn in.
+    ///
+    /// # Arguments
+    /// - `width`: The width of the drawing area.
+    /// - `height`: The height of the drawing area.
+    ///
+    /// # Returns
+    /// - `(x, y, width, height)` of the layer, within the drawing area.
+    pub fn rect(&self, width: f64, height: f64) -> (f64, f64, f64, f64) {
+        let size = f64::from(self.size.clamp(0.0, 1.0));
+        let (w, h) = (width * size, height * size);
+        let (x, y, w, h) = match self.region {
+            Region::Anchor(Anchor::Full) => (0.0, 0.0, width, height),
+            Region::Anchor(Anchor::Top) => (0.0, 0.0, width, h),
+            Region::Anchor(Anchor::Bottom) => (0.0, height - h, width, h),
+            Region::Anchor(Anchor::Left) => (0.0, 0.0, w, height),
+            Region::Anchor(Anchor::Right) => (width - w, 0.0, w, height),
+            Region::Anchor(Anchor::Center) => ((width - w) / 2.0, (height - h) / 2.0, w, h),
+            Region::Anchor(Anchor::TopLeft) => (0.0, 0.0, w, h),
+            Region::Anchor(Anchor::TopRight) => (width - w, 0.0, w, h),
+            Region::Anchor(Anchor::BottomLeft) => (0.0, height - h, w, h),
+            Region::Anchor(Anchor::BottomRight) => (width - w, height - h, w, h),
+            Region::Rect(rect) => {
+                let [x, y, w, h] = rect.map(|value| f64::from(value.clamp(0.0, 1.0)));
+                (x * width, y * height, w * width, h * height)
+            }
+        };
+        // Rectangles reaching past the window are cut at its edges
+        (x, y, w.min(width - x), h.min(height - y))
+    }
+}
+
+/// One window of those configured through `windows`.
+///
+/// # Fields
+/// - `visualizer`: Name of the visualizer shown in the window, as in `visualizer.kind`; the
+///   window shows `visualizer.kind` and the `visualizers` layers when not set.
+/// - `monitor`: Index of the monitor the window is shown fullscreen on, counting from 0; when not
+///   set, the window opens wherever the window manager places it.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct WindowEntry {
+    pub visualizer: Option<String>,
+    pub monitor: Option<u32>,
+}
+
+/// A named scene of those configured through `[[scene]]` tables, applied with the number keys.
+///
+/// # Fields
+/// - `name`: Name of the scene, shown when it is applied.
+/// - `kind`: Name of the visualizer the scene shows, as in `visualizer.kind`.
+/// - `crossfade_ms`: Time in milliseconds the previous visualizer takes to fade out while the
+///   scene's fades in; `0.0` switches at once.
+/// - `overrides`: Drawing settings of the scene, such as `palette` or `smoothing_ms`, merged over
+///   the `[visualizer.<kind>]` table of its visualizer.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct SceneSettings {
+    pub name: String,
+    pub kind: String,
+    pub crossfade_ms: f32,
+    #[serde(flatten)]
+    pub overrides: VisualizerOverrides,
+}
+
+/// What a trigger does when its band crosses the threshold, selected through `trigger.action`.
+///
+/// - `Exec`: Runs `command` through `sh -c`, with `%STATE%` replaced by `on` or `off` and
+///   `%LEVEL%` by the level of the band in dB.
+/// - `Print`: Writes a line with the band, the state and the level to stdout.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerAction {
+    Exec,
+    Print,
+}
+
+/// A band trigger of those configured through `[[trigger]]` tables, switching on while the level
+/// of a frequency band stays above a threshold.
+///
+/// # Fields
+/// - `low`: Lower edge of the band, in Hz.
+/// - `high`: Upper edge of the band, in Hz.
+/// - `threshold_db`: Level of the band in dBFS, averaged over both channels, above which the
+///   trigger switches on.
+/// - `attack_ms`: Time in milliseconds the level has to stay above the threshold before the
+///   trigger switches on.
+/// - `release_ms`: Time in milliseconds the trigger holds after the level fell below the
+///   threshold before it switches off.
+/// - `action`: What happens when the trigger switches on or off.
+/// - `command`: The command run by the `exec` action.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct TriggerSettings {
+    pub low: f32,
+    pub high: f32,
+    pub threshold_db: f32,
+    pub attack_ms: f32,
+    pub release_ms: f32,
+    pub action: TriggerAction,
+    pub command: String,
+}
+
+impl Default for TriggerSettings {
+    fn default() -> Self {
+        TriggerSettings {
+            low: 40.0,
+            high: 120.0,
+            threshold_db: -30.0,
+            attack_ms: 20.0,
+            release_ms: 200.0,
+            action: TriggerAction::Print,
+            command: String::new(),
+        }
+    }
+}
+
+/// Length of a plot margin or bar gap, in pixels or as a percentage of a size it is measured
+/// against.
+///
+/// Deserializes from a number of pixels, such as `24`, or a string such as `"5%"`. Serializes
+/// back to the same form.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(try_from = "MarginValue")]
+pub enum MarginLength {
+    /// A fixed number of logical pixels.
+    Pixels(f64),
+    /// A percentage (0.0 to 100.0) of the window width for the left and right margins, of its
+    /// height for the top and bottom margins, or of the space of each bar for bar gaps.
+    Percent(f64),
+}
+
+/// The accepted configuration forms of a `MarginLength`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MarginValue {
+    Pixels(f64),
+    Percent(String),
+}
+
+impl TryFrom<MarginValue> for MarginLength {
+    type Error = String;
+
+    fn try_from(value: MarginValue) -> Result<Self, Self::Error> {
+        match value {
+            MarginValue::Pixels(pixels) => Ok(MarginLength::Pixels(pixels)),
+            MarginValue::Percent(text) => text
+                .trim()
+                .strip_suffix('%')
+                .and_then(|percent| percent.trim().parse().ok())
+                .map(MarginLength::Percent)
+                .ok_or_else(|| format!("'{}' must be a number of pixels or a percentage", text)),
+        }
+    }
+}
+
+impl Serialize for MarginLength {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            MarginLength::Pixels(pixels) => serializer.serialize_f64(pixels),
+            MarginLength::Percent(_) => serializer.serialize_str(&self.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for MarginLength {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MarginLength::Pixels(pixels) => write!(f, "{}", pixels),
+            MarginLength::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+impl Default for MarginLength {
+    fn default() -> Self {
+        MarginLength::Pixels(0.0)
+    }
+}
+
+impl MarginLength {
+    /// Returns the length in whole logical pixels.
+    ///
+    /// # Arguments
+    /// - `size`: The size a percentage is taken of, such as the width or height of the window
+    ///   a margin runs along.
+    pub fn pixels(&self, size: f64) -> f64 {
+        match *self {
+            MarginLength::Pixels(pixels) => pixels.max(0.0).round(),
+            MarginLength::Percent(percent) => (size * percent.clamp(0.0, 100.0) / 100.0).round(),
+        }
+    }
+
+    /// Returns whether the length is a non-negative number of pixels or a percentage from 0.0 to
+    /// 100.0.
+    fn is_valid(&self) -> bool {
+        match *self {
+            MarginLength::Pixels(pixels) => pixels.is_finite() && pixels >= 0.0,
+            MarginLength::Percent(percent) => (0.0..=100.0).contains(&percent),
+        }
+    }
+}
+
+/// Space left around the plot on each side of the window.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Margins {
+    pub top: MarginLength,
+    pub bottom: MarginLength,
+    pub left: MarginLength,
+    pub right: MarginLength,
+}
+
+/// Settings for the placement of the plot in the window.
+///
+/// # Fields
+/// - `margin`: Space around the plot, where the grid labels are drawn when they fit. The grid
+///   and the visualizers are clipped to the plot.
+/// - `mirror_left`: Whether the left channel runs from the center towards the left edge,
+///   mirroring the right channel, or from the left edge towards the center like the right
+///   channel does on its half.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct LayoutSettings {
+    pub margin: Margins,
+    pub mirror_left: bool,
+}
+
+impl Default for LayoutSettings {
+    fn default() -> Self {
+        LayoutSettings {
+            margin: Margins::default(),
+            mirror_left: true,
+        }
+    }
+}
+
+/// The part of the window the grid and the visualizers are drawn in.
+///
+/// # Fields
+/// - `x`: Left edge, in logical pixels from the left of the window.
+/// - `y`: Top edge, in logical pixels from the top of the window.
+/// - `width`: Width of the plot, in logical pixels.
+/// - `height`: Height of the plot, in logical pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlotRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl PlotRect {
+    /// Returns whether a point of the window lies within the plot.
+    pub fn contains(&self, (x, y): (f64, f64)) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+}
+
+impl LayoutSettings {
+    /// Returns the plot within a window, inside the margins.
+    ///
+    /// # Arguments
+    /// - `width`: The width of the window.
+    /// - `height`: The height of the window.
+    ///
+    /// # Returns
+    /// - The plot, on whole pixels; empty when the margins take up the whole window.
+    pub fn plot_rect(&self, width: f64, height: f64) -> PlotRect {
+        let margin = &self.margin;
+        let (left, top) = (margin.left.pixels(width), margin.top.pixels(height));
+        let x = left.min(width);
+        let y = top.min(height);
+        PlotRect {
+            x,
+            y,
+            width: (width - x - margin.right.pixels(width)).max(0.0),
+            height: (height - y - margin.bottom.pixels(height)).max(0.0),
+        }
+    }
+}
+
+/// Corner of the window an overlay is drawn in.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Settings for the "now playing" overlay fed by MPRIS media players.
+///
+/// # Fields
+/// - `enabled`: Whether media players are watched; requires building with the `mpris` feature.
+/// - `corner`: Corner of the window the overlay is drawn in.
+/// - `font_size`: Font size of the overlay, in points.
+/// - `fade_secs`: Time after a track change before the overlay fades out; `0.0` keeps it shown.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct NowPlayingSettings {
+    pub enabled: bool,
+    pub corner: Corner,
+    pub font_size: f64,
+    pub fade_secs: f32,
+}
+
+impl Default for NowPlayingSettings {
+    fn default() -> Self {
+        NowPlayingSettings {
+            enabled: false,
+            corner: Corner::BottomRight,
+            font_size: 18.0,
+            fade_secs: 5.0,
+        }
+    }
+}
+
+/// Processing applied to the captured audio before analysis.
+///
+/// # Fields
+/// - `gain_left`: Gain of the captured left channel, in dB.
+/// - `gain_right`: Gain of the captured right channel, in dB.
+/// - `input_gain_db`: Gain applied to the samples before the FFT, in dB. Unlike `gain_left` and
+///   `gain_right` it only feeds the analysis, leaving the meters, waveform and recordings as
+///   captured; unlike `visualizer.gain` it lifts quiet sources above the noise floor instead of
+///   shifting the whole spectrum. Amplified samples are clipped to full scale.
+/// - `swap_channels`: Whether the left and right channels are exchanged.
+/// - `mono`: Whether both sides show the downmix of left and right.
+/// - `highpass_hz`: Cutoff of the DC-blocking high-pass filter, in Hz; `0.0` disables it.
+/// - `source`: Where audio is captured from; also accepted as `backend`.
+/// - `path`: FIFO read when `source` is `"fifo"`; `"-"` reads standard input.
+/// - `format`: Sample format of the FIFO stream.
+/// - `rate`: Sample rate of the FIFO stream, in Hz.
+/// - `channels`: Number of interleaved channels in the FIFO stream.
+/// - `channel_left`: Index of the input channel shown on the left side, starting at 0.
+/// - `channel_right`: Index of the input channel shown on the right side.
+/// - `downmix`: Whether both sides show the average of all input channels, for surround input.
+/// - `average_pairs`: Whether inputs with more than two channels show the average of the
+///   even-numbered channels (0, 2, ...) on the left and of the odd-numbered ones on the right,
+///   instead of `channel_left` and `channel_right`. `downmix` takes precedence.
+/// - `test_frequency`: Frequency of the tone generated when `source` is `"test"`, in Hz; the
+///   right channel plays the octave above.
+/// - `test_amplitude`: Peak amplitude of the generated tone, in [0.0, 1.0].
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub gain_left: f32,
+    pub gain_right: f32,
+    pub input_gain_db: f32,
+    pub swap_channels: bool,
+    pub mono: bool,
+    pub highpass_hz: f32,
+    #[serde(alias = "backend")]
+    pub source: AudioSource,
+    pub path: String,
+    pub format: SampleFormat,
+    pub rate: u32,
+    pub channels: u16,
+    pub channel_left: usize,
+    pub channel_right: usize,
+    pub downmix: bool,
+    pub average_pairs: bool,
+    pub test_frequency: f32,
+    pub test_amplitude: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            gain_left: 0.0,
+            gain_right: 0.0,
+            input_gain_db: 0.0,
+            swap_channels: false,
+            mono: false,
+            highpass_hz: 5.0,
+            source: AudioSource::Device,
+            path: "/tmp/mpd.fifo".to_string(),
+            format: SampleFormat::S16le,
+            rate: 44100,
+            channels: 2,
+            channel_left: 0,
+            channel_right: 1,
+            downmix: false,
+            average_pairs: false,
+            test_frequency: 440.0,
+            test_amplitude: 0.5,
+        }
+    }
+}
+
+/// Audio sources selectable through `audio.source`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioSource {
+    /// The default input device, captured through cpal.
+    #[serde(alias = "cpal")]
+    Device,
+    /// Raw PCM read from a FIFO or standard input.
+    Fifo,
+    /// A generated test tone, for running without audio hardware.
+    Test,
+    /// A JACK client with the input ports `in_L` and `in_R`; requires the `jack` feature.
+    Jack,
+}
+
+/// Raw PCM sample formats selectable through `audio.format`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SampleFormat {
+    /// Signed 16-bit little-endian integers.
+    S16le,
+    /// 32-bit little-endian floats.
+    F32le,
+}
+
+impl SampleFormat {
+    /// Returns the size of one sample, in bytes.
+    pub fn sample_size(self) -> usize {
+        match self {
+            SampleFormat::S16le => 2,
+            SampleFormat::F32le => 4,
+        }
+    }
+}
+
+/// Settings for the noise floor calibration.
+///
+/// # Fields
+/// - `calibrate_on_start`: Whether the noise floor is measured when the application starts.
+/// - `duration_secs`: How long the spectrum is sampled during calibration, in seconds.
+/// - `profile_path`: File the measured noise profile is saved to and loaded from at startup.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct CalibrationSettings {
+    pub calibrate_on_start: bool,
+    pub duration_secs: f32,
+    pub profile_path: String,
+}
+
+impl Default for CalibrationSettings {
+    fn default() -> Self {
+        CalibrationSettings {
+            calibrate_on_start: false,
+            duration_secs: 2.0,
+            profile_path: "noise_profile.toml".to_string(),
+        }
+    }
+}
+
+/// Settings for images exported from the visualizer.
+///
+/// # Fields
+/// - `screenshot_dir`: Directory screenshots taken with the `S` key are saved to.
+/// - `background`: Color exported images are filled with before drawing; an alpha given in the
+///   color makes the background translucent.
+/// - `osc_address`: UDP address spectrum frames are sent to as OSC bundles, if set.
+/// - `osc_rate_hz`: Maximum number of OSC bundles sent per second.
+/// - `osc_bins`: Number of bar heights sent per channel.
+/// - `http_port`: Port on `127.0.0.1` serving spectrum frames over WebSocket and HTTP, if set;
+///   requires the `http` feature.
+/// - `http_rate_hz`: Maximum number of frames pushed to WebSocket clients per second.
+/// - `http_bins`: Number of bar heights served per channel.
+/// - `emit_bins`: Number of bar heights per channel in each line written by `--emit-spectrum`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct OutputSettings {
+    pub screenshot_dir: String,
+    pub background: Color,
+    pub osc_address: Option<String>,
+    pub osc_rate_hz: f32,
+    pub osc_bins: usize,
+    pub http_port: Option<u16>,
+    pub http_rate_hz: f32,
+    pub http_bins: usize,
+    pub emit_bins: usize,
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        OutputSettings {
+            screenshot_dir: ".".to_string(),
+            background: Color::rgb(0.0, 0.0, 0.0),
+            osc_address: None,
+            osc_rate_hz: 30.0,
+            osc_bins: 32,
+            http_port: None,
+            http_rate_hz: 30.0,
+            http_bins: 32,
+            emit_bins: 32,
+        }
+    }
+}
+
+/// Settings for the application window.
+///
+/// # Fields
+/// - `transparent`: Whether the window is borderless and transparent apart from the visualizer,
+///   for use as a desktop widget. The transparency replaces the `[background]` and the
+///   stylesheet backgrounds. Displays without compositing keep an opaque window.
+/// - `click_through`: Whether clicks on a transparent window reach the windows below it, where
+///   the display supports it. Keyboard shortcuts still work once the window has the focus.
+/// - `menu_bar`: Whether the window shows a menu bar with the preferences, about dialog and
+///   view actions; transparent windows never show it.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct WindowSettings {
+    pub transparent: bool,
+    pub click_through: bool,
+    pub menu_bar: bool,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        WindowSettings {
+            transparent: false,
+            click_through: false,
+            menu_bar: true,
+        }
+    }
+}
+
+/// Settings for interactive parts of the window.
+///
+/// # Fields
+/// - `hover_readout`: Whether hovering the visualization shows a crosshair with the frequency
+///   and level under the pointer.
+/// - `audio_status`: Whether a status line with the input device and whether samples are
+///   flowing is shown at startup; toggled with `I`.
+/// - `show_legend`: Whether the channels are labeled in the lower corners of the plot, and the
+///   colors of the channels keyed next to the level meters; hidden a few seconds into
+///   fullscreen.
+/// - `font`: Pango font family of the overlay text, or a comma-separated list of families.
+/// - `monospace_font`: Pango font family of text laid out in columns, such as the stats overlay.
+/// - `font_size`: Size of the overlay text in pixels; smaller labels and larger readouts keep
+///   their proportions to it.
+/// - `decimal_comma`: Whether frequencies, levels and durations in labels and readouts are
+///   written with a decimal comma, such as `1,2 kHz`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct UiSettings {
+    pub hover_readout: bool,
+    pub audio_status: bool,
+    pub show_legend: bool,
+    pub font: String,
+    pub monospace_font: String,
+    pub font_size: f64,
+    pub decimal_comma: bool,
+}
+
+impl Default for UiSettings {
+    fn default() -> Self {
+        UiSettings {
+            hover_readout: true,
+            audio_status: false,
+            show_legend: false,
+            font: "Sans".to_string(),
+            monospace_font: "Monospace".to_string(),
+            font_size: 12.0,
+            decimal_comma: false,
+        }
+    }
+}
+
+/// Settings for debugging the visualizer.
+///
+/// # Fields
+/// - `show_stats`: Whether the frame rate and timing overlay is shown at startup; toggled with
+///   `D`.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct DebugSettings {
+    pub show_stats: bool,
+}
+
+/// Settings for the power use of the visualizer.
+///
+/// # Fields
+/// - `pause_when_hidden`: Whether drawing and analysis pause while the visualizer is unmapped or
+///   its window minimized. Audio keeps being captured, so resuming shows the current audio.
+/// - `idle_detection`: Whether the frame rate drops to `idle_fps` during sustained silence.
+/// - `idle_threshold_db`: RMS level below which the input counts as silent, in dBFS.
+/// - `idle_after_secs`: Time the input must stay silent before the frame rate drops.
+/// - `idle_fps`: Frame rate while idle.
+/// - `show_idle_label`: Whether an "idle" label is drawn while idle.
+/// - `adaptive`: Whether the frame rate drops to `battery_fps` and the trails, the reflection and
+///   the glow gradients are turned off while UPower reports the machine on battery. Needs the
+///   `battery` feature.
+/// - `battery_fps`: Highest frame rate on battery when `adaptive` is set.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PowerSettings {
+    pub pause_when_hidden: bool,
+    pub idle_detection: bool,
+    pub idle_threshold_db: f32,
+    pub idle_after_secs: f32,
+    pub idle_fps: f32,
+    pub show_idle_label: bool,
+    pub adaptive: bool,
+    pub battery_fps: f32,
+}
+
+impl Default for PowerSettings {
+    fn default() -> Self {
+        PowerSettings {
+            pause_when_hidden: true,
+            idle_detection: true,
+            idle_threshold_db: -60.0,
+            idle_after_secs: 5.0,
+            idle_fps: 2.0,
+            show_idle_label: false,
+            adaptive: false,
+            battery_fps: 20.0,
+        }
+    }
+}
+
+/// Root settings structure containing all configuration settings, including FFT, visualizer,
+/// and grid configurations.
+///
+/// Every section is optional; missing sections take their default values. `visualizers`
+/// stacks several visualizers as layers; without it, `visualizer.kind` fills the window.
+/// `windows` opens several windows showing different visualizers of the same audio. `scenes`,
+/// the `[[scene]]` tables, are switched to with the number keys. `triggers`, the `[[trigger]]`
+/// tables, act when frequency bands cross their thresholds. `keys` binds keys to actions by
+/// name, with the default keys filling gaps.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Settings {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub visualizers: Vec<LayerSettings>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub windows: Vec<WindowEntry>,
+    #[serde(rename = "scene", skip_serializing_if = "Vec::is_empty")]
+    pub scenes: Vec<SceneSettings>,
+    #[serde(rename = "trigger", skip_serializing_if = "Vec::is_empty")]
+    pub triggers: Vec<TriggerSettings>,
+    pub fft: FFTSettings,
+    pub visualizer: VisualizerSettings,
+    pub grid: GridSettings,
+    pub layout: LayoutSettings,
+    pub radial: RadialSettings,
+    pub line: LineSettings,
+    pub octave: OctaveSettings,
+    pub chromagram: ChromagramSettings,
+    pub difference: DifferenceSettings,
+    pub waveform: WaveformSettings,
+    pub background: BackgroundSettings,
+    pub effects: EffectsSettings,
+    pub curves: CurvesSettings,
+    pub beat: BeatSettings,
+    pub note_readout: NoteReadoutSettings,
+    pub loudness: LoudnessSettings,
+    pub audio: AudioSettings,
+    pub calibration: CalibrationSettings,
+    pub output: OutputSettings,
+    pub now_playing: NowPlayingSettings,
+    pub window: WindowSettings,
+    pub ui: UiSettings,
+    pub debug: DebugSettings,
+    pub power: PowerSettings,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub keys: HashMap<String, String>,
+}
+
+impl FFTSettings {
+    /// Generates logarithmically spaced frequencies if they are not provided in the configuration.
+    ///
+    /// # Arguments
+    /// - `num_frequencies`: The number of frequencies to generate.
+    ///
+    /// # Returns
+    /// - A vector of `num_frequencies` logarithmically spaced frequencies between `min_frequency`
+    ///   and `max_frequency`.
+    ///
+    /// This function is useful when a specific list of frequencies is not provided, ensuring that
+    /// the visualizer has frequency markers spread across the audible range.
+    pub fn generate_frequencies(&self, num_frequencies: usize) -> Vec<f32> {
+        let log_min = self.min_frequency.log10();
+        let log_max = self.max_frequency.log10();
+
+        // Create a vector of frequencies spaced logarithmically between `min_frequency` and `max_frequency`
+        (0..num_frequencies)
+            .map(|i| {
+                let fraction = i as f32 / (num_frequencies - 1) as f32;
+                let log_freq = log_min + fraction * (log_max - log_min);
+                10_f32.powf(log_freq)
+            })
+            .collect()
+    }
+}
+
+impl Settings {
+    /// Loads and initializes settings from a configuration file (`config.toml`).
+    ///
+    /// # Returns
+    /// - A `Settings` instance populated with data from the configuration file.
+    ///
+    /// If `resources/config.toml` is missing, the configuration embedded at compile time is used.
+    ///
+    /// If the `frequencies` field in `fft` settings is `None`, this method will auto-generate
+    /// 15 logarithmically spaced frequencies between `min_frequency` and `max_frequency`.
+    pub fn new() -> Self {
+        let config_str =
+            fs::read_to_string(CONFIG_PATH).unwrap_or_else(|_| DEFAULT_CONFIG.to_string());
+        Self::from_config(&config_str).expect("Invalid config format")
+    }
+
+    /// Loads the settings from a configuration file.
+    ///
+    /// # Arguments
+    /// - `path`: The TOML configuration file.
+    ///
+    /// # Returns
+    /// - The settings, or an error if the file cannot be read or parsed.
+    ///
+    /// Frequencies are generated as in `Settings::new` when `fft.frequencies` is not set.
+    pub fn load(path: &Path) -> Result<Self, SettingsError> {
+        let config_str = fs::read_to_string(path).map_err(SettingsError::Io)?;
+        Self::from_config(&config_str)
+    }
+
+    /// Parses a configuration, reporting unknown keys and generating missing frequencies.
+    pub(crate) fn from_config(config_str: &str) -> Result<Self, SettingsError> {
+        let (mut settings, unknown_keys) =
+            parse_config(config_str).map_err(SettingsError::Parse)?;
+        for key in unknown_keys {
+            eprintln!("Ignoring unknown config key: {}", key);
+        }
+
+        // Generate frequencies if they are not set in the configuration
+        if settings.fft.frequencies.is_none() {
+            settings.fft.frequencies = Some(settings.fft.generate_frequencies(15));
+            settings.fft.generated_frequencies = true;
+        }
+
+        Ok(settings)
+    }
+
+    /// Writes the settings to a configuration file that `Settings::load` reads back identically.
+    ///
+    /// # Arguments
+    /// - `path`: The file to write, replaced if it exists.
+    ///
+    /// # Returns
+    /// - An error if the settings cannot be converted to TOML or the file cannot be written.
+    ///
+    /// The settings are written to a temporary file next to `path` that then replaces it, so an
+    /// interrupted save leaves the previous file intact. Comments and unknown keys of an existing
+    /// file are not kept. Generated frequencies are left out, so they are generated again from
+    /// the saved frequency range.
+    pub fn save(&self, path: &Path) -> Result<(), SettingsError> {
+        let mut settings = self.clone();
+        if settings.fft.generated_frequencies {
+            settings.fft.frequencies = None;
+        }
+        let config = toml::to_string(&settings).map_err(SettingsError::Serialize)?;
+
+        let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(".tmp");
+        let temp_path = path.with_file_name(temp_name);
+        fs::write(&temp_path, config)
+            .and_then(|()| fs::rename(&temp_path, path))
+            .map_err(|e| {
+                let _ = fs::remove_file(&temp_path);
+                SettingsError::Io(e)
+            })
+    }
+
+    /// Returns the drawing settings of a visualizer, merging its `[visualizer.<kind>]` table over
+    /// the common `[visualizer]` values.
+    ///
+    /// # Arguments
+    /// - `kind`: Name of the visualizer, as used in `visualizer.kind`.
+    pub fn visualizer_settings(&self, kind: &str) -> ResolvedVisualizerSettings {
+        self.visualizer.resolve(kind)
+    }
+
+    /// Returns the plot the grid and the visualizers are drawn in: the plot of `layout`,
+    /// shortened by the reflection below it when `effects.reflection` is set.
+    ///
+    /// # Arguments
+    /// - `width`: The width of the window.
+    /// - `height`: The height of the window.
+    pub fn plot_rect(&self, width: f64, height: f64) -> PlotRect {
+        let plot = self.layout.plot_rect(width, height);
+        PlotRect {
+            height: plot.height - self.reflection_height(plot.height),
+            ..plot
+        }
+    }
+
+    /// Returns the strip below the plot the reflection is drawn in, empty unless
+    /// `effects.reflection` is set.
+    ///
+    /// # Arguments
+    /// - `width`: The width of the window.
+    /// - `height`: The height of the window.
+    pub fn reflection_rect(&self, width: f64, height: f64) -> PlotRect {
+        let plot = self.layout.plot_rect(width, height);
+        let reflection_height = self.reflection_height(plot.height);
+        PlotRect {
+            y: plot.y + plot.height - reflection_height,
+            height: reflection_height,
+            ..plot
+        }
+    }
+
+    /// Returns the height of the reflection below a plot of `plot_height`, on whole pixels.
+    fn reflection_height(&self, plot_height: f64) -> f64 {
+        if !self.effects.reflection {
+            return 0.0;
+        }
+        (plot_height * self.effects.reflection_height.clamp(0.0, 1.0)).round()
+    }
+
+    /// Picks the hop size and the smoothing time meeting `fft.latency_target_ms`, used in place of
+    /// `fft.hop_size` and `visualizer.smoothing_ms` while running. The configured values are left
+    /// untouched, so `save` does not replace them with the picked ones.
+    ///
+    /// # Returns
+    /// - The picked values, or `None` if no target is set.
+    pub(crate) fn apply_latency_target(&mut self) -> Option<LatencyBudget> {
+        let target = self.fft.latency_target_ms?;
+        let budget = latency_budget(target, self.fft.size, self.fft.sample_rate);
+        self.fft.target_hop_size = Some(budget.hop_size);
+        self.visualizer.target_smoothing_ms = Some(budget.smoothing_ms);
+        Some(budget)
+    }
+
+    /// Returns the windows `run_application` opens.
+    ///
+    /// # Returns
+    /// - `windows`, or a single window showing the configured visualizer when it is empty.
+    pub fn window_entries(&self) -> Vec<WindowEntry> {
+        if self.windows.is_empty() {
+            vec![WindowEntry::default()]
+        } else {
+            self.windows.clone()
+        }
+    }
+
+    /// Returns the settings of one window, showing its visualizer in place of `visualizer.kind`
+    /// and the `visualizers` layers.
+    ///
+    /// # Arguments
+    /// - `entry`: The window, from `window_entries`.
+    pub fn for_window(&self, entry: &WindowEntry) -> Settings {
+        let mut settings = self.clone();
+        if let Some(kind) = &entry.visualizer {
+            settings.visualizer.kind = kind.clone();
+            settings.visualizers.clear();
+        }
+        settings
+    }
+
+    /// Returns the settings of a scene: its visualizer fills the window in place of
+    /// `visualizer.kind` and the `visualizers` layers, with the scene's drawing settings merged
+    /// over the `[visualizer.<kind>]` table.
+    ///
+    /// # Arguments
+    /// - `scene`: The scene, one of `scenes`.
+    pub fn with_scene(&self, scene: &SceneSettings) -> Settings {
+        let mut settings = self.clone();
+        let overrides = &mut settings.visualizer.overrides;
+        let merged = scene
+            .overrides
+            .over(&overrides.get(&scene.kind).cloned().unwrap_or_default());
+        overrides.insert(scene.kind.clone(), merged);
+        settings.visualizer.kind = scene.kind.clone();
+        settings.visualizers.clear();
+        settings
+    }
+
+    /// Checks that every numeric setting is within its valid range.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the settings are valid, or every violation found.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+
+        let fft = &self.fft;
+        if !fft.size.is_power_of_two() || !(MIN_FFT_SIZE..=MAX_FFT_SIZE).contains(&fft.size) {
+            errors.push(ValidationError::new(
+                "fft.size",
+                fft.size,
+                format!(
+                    "must be a power of two between {} and {}",
+                    MIN_FFT_SIZE, MAX_FFT_SIZE
+                ),
+            ));
+        }
+        if let Some(hop_size) = fft.hop_size {
+            if !(1..=fft.size).contains(&hop_size) {
+                errors.push(ValidationError::new(
+                    "fft.hop_size",
+                    hop_size,
+                    format!("must be between 1 and fft.size ({})", fft.size),
+                ));
+            }
+        }
+        if !ZERO_PAD_FACTORS.contains(&fft.zero_pad_factor) {
+            errors.push(ValidationError::new(
+                "fft.zero_pad_factor",
+                fft.zero_pad_factor,
+                "must be 1, 2 or 4",
+            ));
+        }
+        if let Some(target) = fft.latency_target_ms {
+            if target.is_nan() || target <= 0.0 {
+                errors.push(ValidationError::new(
+                    "fft.latency_target_ms",
+                    target,
+                    "must be positive",
+                ));
+            }
+        }
+        if fft.min_frequency >= fft.max_frequency {
+            errors.push(ValidationError::new(
+                "fft.min_frequency",
+                fft.min_frequency,
+                format!("must be below fft.max_frequency ({})", fft.max_frequency),
+            ));
+        }
+        if fft.max_frequency >= fft.sample_rate / 2.0 {
+            errors.push(ValidationError::new(
+                "fft.max_frequency",
+                fft.max_frequency,
+                format!(
+                    "must be below half of fft.sample_rate ({})",
+                    fft.sample_rate / 2.0
+                ),
+            ));
+        }
+
+        let audio = &self.audio;
+        if audio.test_frequency <= 0.0 || audio.test_frequency >= fft.sample_rate / 4.0 {
+            errors.push(ValidationError::new(
+                "audio.test_frequency",
+                audio.test_frequency,
+                format!(
+                    "must be above 0.0 and below a quarter of fft.sample_rate ({})",
+                    fft.sample_rate / 4.0
+                ),
+            ));
+        }
+        if !(-MAX_INPUT_GAIN_DB..=MAX_INPUT_GAIN_DB).contains(&audio.input_gain_db) {
+            errors.push(ValidationError::new(
+                "audio.input_gain_db",
+                audio.input_gain_db,
+                format!(
+                    "must be between {} and {}",
+                    -MAX_INPUT_GAIN_DB, MAX_INPUT_GAIN_DB
+                ),
+            ));
+        }
+
+        let visualizer = &self.visualizer;
+        let mut unit_values = vec![
+            (
+                "visualizer.interpolation_factor".to_string(),
+                visualizer.interpolation_factor,
+            ),
+            (
+                "visualizer.smooth_factor".to_string(),
+                visualizer.smooth_factor,
+            ),
+            ("visualizer.attack".to_string(), visualizer.attack),
+            ("visualizer.alpha".to_string(), visualizer.alpha),
+            ("audio.test_amplitude".to_string(), audio.test_amplitude),
+            (
+                "visualizer.auto_gain_attack".to_string(),
+                visualizer.auto_gain_attack,
+            ),
+            (
+                "visualizer.auto_gain_release".to_string(),
+                visualizer.auto_gain_release,
+            ),
+            ("grid.alpha".to_string(), self.grid.alpha as f32),
+            (
+                "effects.max_alpha".to_string(),
+                self.effects.max_alpha as f32,
+            ),
+            ("waveform.split".to_string(), self.waveform.split),
+            (
+                "effects.reflection_height".to_string(),
+                self.effects.reflection_height as f32,
+            ),
+            (
+                "effects.reflection_alpha".to_string(),
+                self.effects.reflection_alpha as f32,
+            ),
+            ("curves.alpha".to_string(), self.curves.alpha as f32),
+        ];
+        if let Some(release) = visualizer.release {
+            unit_values.push(("visualizer.release".to_string(), release));
+        }
+        for (i, layer) in self.visualizers.iter().enumerate() {
+            unit_values.push((format!("visualizers[{}].size", i), layer.size));
+            if let Region::Rect(rect) = layer.region {
+                for value in rect {
+                    unit_values.push((format!("visualizers[{}].region", i), value));
+                }
+            }
+        }
+        // Scenes are merged over the visualizer tables, so they are held to the same ranges
+        let override_tables: Vec<(String, &VisualizerOverrides)> = visualizer
+            .overrides
+            .iter()
+            .map(|(kind, overrides)| (format!("visualizer.{}", kind), overrides))
+            .chain(
+                self.scenes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, scene)| (format!("scene[{}]", i), &scene.overrides)),
+            )
+            .collect();
+        for (table, overrides) in &override_tables {
+            if let Some(factor) = overrides.interpolation_factor {
+                unit_values.push((format!("{}.interpolation_factor", table), factor));
+            }
+            if let Some(alpha) = overrides.alpha {
+                unit_values.push((format!("{}.alpha", table), alpha));
+            }
+        }
+        for (path, value) in unit_values {
+            if !(0.0..=1.0).contains(&value) {
+                errors.push(ValidationError::new(
+                    &path,
+                    value,
+                    "must be between 0.0 and 1.0",
+                ));
+            }
+        }
+        let mut smoothing_values = Vec::new();
+        if let Some(smoothing_ms) = visualizer.smoothing_ms {
+            smoothing_values.push(("visualizer.smoothing_ms".to_string(), smoothing_ms));
+        }
+        for (table, overrides) in &override_tables {
+            if let Some(smoothing_ms) = overrides.smoothing_ms {
+                smoothing_values.push((format!("{}.smoothing_ms", table), smoothing_ms));
+            }
+        }
+        for (i, scene) in self.scenes.iter().enumerate() {
+            smoothing_values.push((format!("scene[{}].crossfade_ms", i), scene.crossfade_ms));
+        }
+        for (i, trigger) in self.triggers.iter().enumerate() {
+            smoothing_values.push((format!("trigger[{}].attack_ms", i), trigger.attack_ms));
+            smoothing_values.push((format!("trigger[{}].release_ms", i), trigger.release_ms));
+        }
+        for (path, value) in smoothing_values {
+            if value < 0.0 {
+                errors.push(ValidationError::new(&path, value, "must be at least 0.0"));
+            }
+        }
+        let mut gaps = vec![("visualizer.bar_gap".to_string(), visualizer.bar_gap)];
+        for (table, overrides) in &override_tables {
+            if let Some(gap) = overrides.bar_gap {
+                gaps.push((format!("{}.bar_gap", table), gap));
+            }
+        }
+        for (path, gap) in gaps {
+            if !gap.is_valid() {
+                errors.push(ValidationError::new(
+                    &path,
+                    gap,
+                    "must be at least 0 pixels, or a percentage from 0% to 100%",
+                ));
+            }
+        }
+        let mut exponents = Vec::new();
+        if let Some(exponent) = visualizer.curve_exponent {
+            exponents.push(("visualizer.curve_exponent".to_string(), exponent));
+        }
+        for (table, overrides) in &override_tables {
+            if let Some(exponent) = overrides.curve_exponent {
+                exponents.push((format!("{}.curve_exponent", table), exponent));
+            }
+        }
+        for (path, exponent) in exponents {
+            if !(MIN_CURVE_EXPONENT..=MAX_CURVE_EXPONENT).contains(&exponent) {
+                errors.push(ValidationError::new(
+                    &path,
+                    exponent,
+                    format!(
+                        "must be between {} and {}",
+                        MIN_CURVE_EXPONENT, MAX_CURVE_EXPONENT
+                    ),
+                ));
+            }
+        }
+        let mut radii = vec![("visualizer.bar_radius".to_string(), visualizer.bar_radius)];
+        for (table, overrides) in &override_tables {
+            if let Some(radius) = overrides.bar_radius {
+                radii.push((format!("{}.bar_radius", table), radius));
+            }
+        }
+        for (path, radius) in radii {
+            if radius < 0.0 {
+                errors.push(ValidationError::new(&path, radius, "must be at least 0.0"));
+            }
+        }
+        if let Some(palette) = visualizer
+            .palette
+            .filter(|_| !visualizer.palette_cycle.is_empty())
+        {
+            errors.push(ValidationError::new(
+                "visualizer.palette",
+                format!("{:?}", palette).to_lowercase(),
+                "cannot be combined with visualizer.palette_cycle; remove one of them",
+            ));
+        }
+        if visualizer.palette_cycle_seconds.is_nan() || visualizer.palette_cycle_seconds <= 0.0 {
+            errors.push(ValidationError::new(
+                "visualizer.palette_cycle_seconds",
+                visualizer.palette_cycle_seconds,
+                "must be above 0.0",
+            ));
+        }
+
+        let power = &self.power;
+        if power.idle_fps <= 0.0 {
+            errors.push(ValidationError::new(
+                "power.idle_fps",
+                power.idle_fps,
+                "must be above 0.0",
+            ));
+        }
+        if power.idle_after_secs < 0.0 {
+            errors.push(ValidationError::new(
+                "power.idle_after_secs",
+                power.idle_after_secs,
+                "must be at least 0.0",
+            ));
+        }
+        if power.battery_fps.is_nan() || power.battery_fps <= 0.0 {
+            errors.push(ValidationError::new(
+                "power.battery_fps",
+                power.battery_fps,
+                "must be above 0.0",
+            ));
+        }
+
+        let curves = &self.curves;
+        if curves.average_secs < 0.0 {
+            errors.push(ValidationError::new(
+                "curves.average_secs",
+                curves.average_secs,
+                "must be at least 0.0",
+            ));
+        }
+        if curves.line_width <= 0.0 {
+            errors.push(ValidationError::new(
+                "curves.line_width",
+                curves.line_width,
+                "must be above 0.0",
+            ));
+        }
+
+        let persistence = self.effects.persistence;
+        if !(0.0..=MAX_PERSISTENCE).contains(&persistence) {
+            errors.push(ValidationError::new(
+                "effects.persistence",
+                persistence,
+                format!("must be between 0.0 and {}", MAX_PERSISTENCE),
+            ));
+        }
+
+        if !OCTAVE_FRACTIONS.contains(&self.octave.fraction) {
+            errors.push(ValidationError::new(
+                "octave.fraction",
+                self.octave.fraction,
+                "must be 1, 3 or 6",
+            ));
+        }
+
+        if self.chromagram.smoothing_secs < 0.0 {
+            errors.push(ValidationError::new(
+                "chromagram.smoothing_secs",
+                self.chromagram.smoothing_secs,
+                "must be at least 0.0",
+            ));
+        }
+
+        if self.difference.range_db <= 0.0 {
+            errors.push(ValidationError::new(
+                "difference.range_db",
+                self.difference.range_db,
+                "must be above 0.0",
+            ));
+        }
+
+        if self.waveform.seconds <= 0.0 || self.waveform.seconds > MAX_WAVEFORM_SECS {
+            errors.push(ValidationError::new(
+                "waveform.seconds",
+                self.waveform.seconds,
+                format!("must be above 0.0 and at most {}", MAX_WAVEFORM_SECS),
+            ));
+        }
+
+        if self.ui.font_size <= 0.0 || self.ui.font_size > MAX_FONT_SIZE {
+            errors.push(ValidationError::new(
+                "ui.font_size",
+                self.ui.font_size,
+                format!("must be above 0.0 and at most {}", MAX_FONT_SIZE),
+            ));
+        }
+
+        let margin = &self.layout.margin;
+        for (side, length) in [
+            ("top", margin.top),
+            ("bottom", margin.bottom),
+            ("left", margin.left),
+            ("right", margin.right),
+        ] {
+            if !length.is_valid() {
+                errors.push(ValidationError::new(
+                    &format!("layout.margin.{}", side),
+                    length,
+                    "must be at least 0 pixels, or a percentage from 0% to 100%",
+                ));
+            }
+        }
+
+        if self.grid.lines < 1 {
+            errors.push(ValidationError::new(
+                "grid.lines",
+                self.grid.lines,
+                "must be at least 1",
+            ));
+        }
+
+        for (i, trigger) in self.triggers.iter().enumerate() {
+            if trigger.low < 0.0 {
+                errors.push(ValidationError::new(
+                    &format!("trigger[{}].low", i),
+                    trigger.low,
+                    "must be at least 0.0",
+                ));
+            }
+            if trigger.high <= trigger.low {
+                errors.push(ValidationError::new(
+                    &format!("trigger[{}].high", i),
+                    trigger.high,
+                    format!("must be above trigger[{}].low", i),
+                ));
+            }
+            if trigger.action == TriggerAction::Exec && trigger.command.trim().is_empty() {
+                errors.push(ValidationError::new(
+                    &format!("trigger[{}].command", i),
+                    format!("{:?}", trigger.command),
+                    "must be set for the exec action",
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Moves every setting rejected by `validate` to the nearest valid value.
+    ///
+    /// Used when the application is started with `--force`.
+    pub fn clamp_to_valid(&mut self) {
+        let fft = &mut self.fft;
+        fft.size = fft
+            .size
+            .clamp(MIN_FFT_SIZE, MAX_FFT_SIZE)
+            .next_power_of_two();
+        if let Some(hop_size) = &mut fft.hop_size {
+            *hop_size = (*hop_size).clamp(1, fft.size);
+        }
+        if !ZERO_PAD_FACTORS.contains(&fft.zero_pad_factor) {
+            fft.zero_pad_factor = fft.zero_pad_factor.clamp(1, 4).next_power_of_two();
+        }
+        if fft
+            .latency_target_ms
+            .is_some_and(|target| target.is_nan() || target <= 0.0)
+        {
+            fft.latency_target_ms = None;
+        }
+        fft.max_frequency = fft.max_frequency.min(fft.sample_rate / 2.0 - 1.0);
+        if fft.min_frequency >= fft.max_frequency {
+            fft.min_frequency = 0.0;
+        }
+
+        let audio = &mut self.audio;
+        audio.test_frequency = audio.test_frequency.clamp(1.0, fft.sample_rate / 4.0 - 1.0);
+        audio.input_gain_db = if audio.input_gain_db.is_nan() {
+            0.0
+        } else {
+            audio
+                .input_gain_db
+                .clamp(-MAX_INPUT_GAIN_DB, MAX_INPUT_GAIN_DB)
+        };
+
+        let unit = |value: &mut f32| *value = value.clamp(0.0, 1.0);
+        unit(&mut audio.test_amplitude);
+        let visualizer = &mut self.visualizer;
+        unit(&mut visualizer.interpolation_factor);
+        unit(&mut visualizer.smooth_factor);
+        unit(&mut visualizer.attack);
+        unit(&mut visualizer.alpha);
+        unit(&mut visualizer.auto_gain_attack);
+        unit(&mut visualizer.auto_gain_release);
+        if let Some(release) = &mut visualizer.release {
+            unit(release);
+        }
+        if let Some(smoothing_ms) = &mut visualizer.smoothing_ms {
+            *smoothing_ms = smoothing_ms.max(0.0);
+        }
+        visualizer.bar_radius = visualizer.bar_radius.max(0.0);
+        // The cycle is the more specific choice, so it wins over a manual palette
+        if !visualizer.palette_cycle.is_empty() {
+            visualizer.palette = None;
+        }
+        if visualizer.palette_cycle_seconds.is_nan() || visualizer.palette_cycle_seconds <= 0.0 {
+            visualizer.palette_cycle_seconds = VisualizerSettings::default().palette_cycle_seconds;
+        }
+        if let Some(exponent) = &mut visualizer.curve_exponent {
+            // NaN takes the exponent of the named curve
+            *exponent = if exponent.is_nan() {
+                visualizer.curve.exponent()
+            } else {
+                exponent.clamp(MIN_CURVE_EXPONENT, MAX_CURVE_EXPONENT)
+            };
+        }
+        for trigger in &mut self.triggers {
+            trigger.low = trigger.low.max(0.0);
+            if trigger.high <= trigger.low {
+                trigger.high = trigger.low + 1.0;
+            }
+            trigger.attack_ms = trigger.attack_ms.max(0.0);
+            trigger.release_ms = trigger.release_ms.max(0.0);
+            // A trigger without a command can still report its band
+            if trigger.command.trim().is_empty() {
+                trigger.action = TriggerAction::Print;
+            }
+        }
+        let scene_overrides = self.scenes.iter_mut().map(|scene| {
+            scene.crossfade_ms = scene.crossfade_ms.max(0.0);
+            &mut scene.overrides
+        });
+        for overrides in visualizer.overrides.values_mut().chain(scene_overrides) {
+            if let Some(factor) = &mut overrides.interpolation_factor {
+                unit(factor);
+            }
+            if let Some(smoothing_ms) = &mut overrides.smoothing_ms {
+                *smoothing_ms = smoothing_ms.max(0.0);
+            }
+            if let Some(alpha) = &mut overrides.alpha {
+                unit(alpha);
+            }
+            if let Some(radius) = &mut overrides.bar_radius {
+                *radius = radius.max(0.0);
+            }
+            // NaN leaves the exponent to the common settings
+            overrides.curve_exponent = overrides
+                .curve_exponent
+                .filter(|exponent| !exponent.is_nan())
+                .map(|exponent| exponent.clamp(MIN_CURVE_EXPONENT, MAX_CURVE_EXPONENT));
+        }
+
+        for layer in &mut self.visualizers {
+            unit(&mut layer.size);
+            if let Region::Rect(rect) = &mut layer.region {
+                rect.iter_mut().for_each(unit);
+            }
+        }
+
+        self.grid.alpha = self.grid.alpha.clamp(0.0, 1.0);
+        self.effects.max_alpha = self.effects.max_alpha.clamp(0.0, 1.0);
+        self.effects.persistence = self.effects.persistence.clamp(0.0, MAX_PERSISTENCE);
+        self.effects.reflection_height = self.effects.reflection_height.clamp(0.0, 1.0);
+        self.effects.reflection_alpha = self.effects.reflection_alpha.clamp(0.0, 1.0);
+        self.grid.lines = self.grid.lines.max(1);
+        if !OCTAVE_FRACTIONS.contains(&self.octave.fraction) {
+            self.octave.fraction = OctaveSettings::default().fraction;
+        }
+        self.chromagram.smoothing_secs = self.chromagram.smoothing_secs.max(0.0);
+        if self.difference.range_db <= 0.0 {
+            self.difference.range_db = DifferenceSettings::default().range_db;
+        }
+        unit(&mut self.waveform.split);
+        if self.waveform.seconds <= 0.0 {
+            self.waveform.seconds = WaveformSettings::default().seconds;
+        }
+        self.waveform.seconds = self.waveform.seconds.min(MAX_WAVEFORM_SECS);
+        if self.ui.font_size <= 0.0 {
+            self.ui.font_size = UiSettings::default().font_size;
+        }
+        self.ui.font_size = self.ui.font_size.min(MAX_FONT_SIZE);
+        let margin = &mut self.layout.margin;
+        let override_gaps = self
+            .visualizer
+            .overrides
+            .values_mut()
+            .chain(self.scenes.iter_mut().map(|scene| &mut scene.overrides))
+            .filter_map(|overrides| overrides.bar_gap.as_mut());
+        let lengths = [
+            &mut margin.top,
+            &mut margin.bottom,
+            &mut margin.left,
+            &mut margin.right,
+            &mut self.visualizer.bar_gap,
+        ];
+        for length in lengths.into_iter().chain(override_gaps) {
+            *length = match *length {
+                MarginLength::Pixels(pixels) if pixels.is_finite() => {
+                    MarginLength::Pixels(pixels.max(0.0))
+                }
+                MarginLength::Percent(percent) if !percent.is_nan() => {
+                    MarginLength::Percent(percent.clamp(0.0, 100.0))
+                }
+                _ => MarginLength::default(),
+            };
+        }
+
+        let curves = &mut self.curves;
+        curves.average_secs = curves.average_secs.max(0.0);
+        curves.alpha = curves.alpha.clamp(0.0, 1.0);
+        if curves.line_width <= 0.0 {
+            curves.line_width = CurvesSettings::default().line_width;
+        }
+
+        let power = &mut self.power;
+        if power.idle_fps <= 0.0 {
+            power.idle_fps = PowerSettings::default().idle_fps;
+        }
+        power.idle_after_secs = power.idle_after_secs.max(0.0);
+        if power.battery_fps.is_nan() || power.battery_fps <= 0.0 {
+            power.battery_fps = PowerSettings::default().battery_fps;
+        }
+    }
+}
+
+/// Smallest FFT size accepted by `Settings::validate`.
+const MIN_FFT_SIZE: usize = 256;
+/// Largest FFT size accepted by `Settings::validate`.
+const MAX_FFT_SIZE: usize = 32768;
+/// Zero-padding factors accepted for `fft.zero_pad_factor`.
+const ZERO_PAD_FACTORS: [usize; 3] = [1, 2, 4];
+/// Largest `effects.persistence` accepted by `Settings::validate`; longer trails never fade.
+const MAX_PERSISTENCE: f64 = 0.95;
+/// Bands per octave accepted for `octave.fraction`.
+const OCTAVE_FRACTIONS: [u32; 3] = [1, 3, 6];
+/// Longest `waveform.seconds` accepted by `Settings::validate`.
+const MAX_WAVEFORM_SECS: f32 = 60.0;
+/// Largest `audio.input_gain_db`, and the negated smallest, accepted by `Settings::validate`.
+const MAX_INPUT_GAIN_DB: f32 = 40.0;
+/// Largest `ui.font_size` accepted by `Settings::validate`.
+const MAX_FONT_SIZE: f64 = 72.0;
+/// Smallest `visualizer.curve_exponent` accepted by `Settings::validate`.
+const MIN_CURVE_EXPONENT: f32 = 0.1;
+/// Largest `visualizer.curve_exponent` accepted by `Settings::validate`.
+const MAX_CURVE_EXPONENT: f32 = 10.0;
+
+/// A setting whose value is outside its valid range.
+///
+/// # Fields
+/// - `path`: Dotted path of the setting, such as `grid.lines`.
+/// - `value`: The offending value.
+/// - `message`: Description of the valid range.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationError {
+    pub path: String,
+    pub value: String,
+    pub message: String,
+}
+
+impl ValidationError {
+    fn new(path: &str, value: impl fmt::Display, message: impl Into<String>) -> Self {
+        ValidationError {
+            path: path.to_string(),
+            value: value.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} = {}: {}", self.path, self.value, self.message)
+    }
+}
+
+/// A configuration file that could not be loaded or saved.
+#[derive(Debug)]
+pub enum SettingsError {
+    /// The file could not be read or written.
+    Io(std::io::Error),
+    /// The file is not a valid configuration.
+    Parse(toml::de::Error),
+    /// The settings could not be converted to TOML.
+    Serialize(toml::ser::Error),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Io(e) => write!(f, "{}", e),
+            SettingsError::Parse(e) => write!(f, "invalid configuration: {}", e),
+            SettingsError::Serialize(e) => write!(f, "cannot write configuration: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+/// Parses a configuration, collecting the keys that do not match any setting.
+///
+/// # Arguments
+/// - `config`: The TOML configuration; missing keys and sections take their default values.
+///
+/// # Returns
+/// - The settings and the dotted paths of the unknown keys, or the parse error.
+fn parse_config(config: &str) -> Result<(Settings, Vec<String>), toml::de::Error> {
+    let mut unknown_keys = Vec::new();
+    let mut settings: Settings =
+        serde_ignored::deserialize(toml::Deserializer::new(config), |path| {
+            unknown_keys.push(path.to_string())
+        })?;
+    unknown_keys.extend(unknown_visualizer_keys(&mut settings.visualizer));
+    Ok((settings, unknown_keys))
+}
+
+/// Returns the dotted paths of the keys of `[visualizer]` that are neither settings nor tables of
+/// a registered visualizer, and of the unknown keys inside the tables of registered ones.
+///
+/// Every key of `[visualizer]` that is not a setting is collected into `unrecognized`, as the
+/// override tables are keyed by visualizer name, so they are checked here instead of by
+/// `serde_ignored`; `unrecognized` is emptied.
+///
+/// # Arguments
+/// - `visualizer`: The parsed `[visualizer]` section.
+fn unknown_visualizer_keys(visualizer: &mut VisualizerSettings) -> Vec<String> {
+    let registry = crate::visualizer_registry();
+    let kinds = registry.names();
+    let mut entries: Vec<_> = visualizer.unrecognized.drain().collect();
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut unknown_keys = Vec::new();
+    for (key, value) in entries {
+        let path = format!("visualizer.{}", key);
+        if !value.is_table() || !kinds.contains(&key.as_str()) {
+            unknown_keys.push(path);
+            continue;
+        }
+        // Type errors in the table already failed the parse, so only the keys are of interest
+        let _: Result<VisualizerOverrides, _> = serde_ignored::deserialize(value, |field| {
+            unknown_keys.push(format!("{}.{}", path, field))
+        });
+    }
+    unknown_keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMMON: &str = r#"
+        gain = 20.0
+        scale_factor = 90.0
+        interpolation_factor = 0.09
+        alpha = 0.8
+        smooth_factor = 0.7
+        palette = "viridis"
+    "#;
+
+    #[test]
+    fn overrides_take_precedence_over_common_values() {
+        let config = format!(
+            "{}\n[glow]\nscale_factor = 40.0\npalette = \"inferno\"\n",
+            COMMON
+        );
+        let visualizer: VisualizerSettings = toml::from_str(&config).unwrap();
+
+        let glow = visualizer.resolve("glow");
+        assert_eq!(glow.scale_factor, 40.0);
+        assert_eq!(glow.palette, PaletteKind::Inferno);
+        // Fields missing from the table keep the common value
+        assert_eq!(glow.gain, 20.0);
+        assert_eq!(glow.alpha, 0.8);
+    }
+
+    #[test]
+    fn bar_shapes_can_be_overridden_per_visualizer() {
+        let config = format!(
+            "{}\nbar_radius = 2.0\n[frequency]\n{}",
+            COMMON, "direction = \"center\"\nbar_radius = 6.0\nbar_gradient = true\n"
+        );
+        let visualizer: VisualizerSettings = toml::from_str(&config).unwrap();
+
+        let frequency = visualizer.resolve("frequency");
+        assert_eq!(frequency.direction, BarDirection::Center);
+        assert_eq!(frequency.bar_radius, 6.0);
+        assert!(frequency.bar_gradient);
+        let glow = visualizer.resolve("holographic_glow");
+        assert_eq!(glow.direction, BarDirection::Up);
+        assert_eq!(glow.bar_radius, 2.0);
+        assert!(!glow.bar_gradient);
+
+        let paths = invalid_paths(|s| {
+            s.visualizer.overrides.insert(
+                "octave".to_string(),
+                VisualizerOverrides {
+                    bar_radius: Some(-1.0),
+                    ..VisualizerOverrides::default()
+                },
+            );
+        });
+        assert_eq!(paths, ["visualizer.octave.bar_radius"]);
+    }
+
+    #[test]
+    fn visualizers_without_a_table_use_common_values() {
+        let visualizer: VisualizerSettings = toml::from_str(COMMON).unwrap();
+        assert!(visualizer.overrides.is_empty());
+
+        let frequency = visualizer.resolve("frequency");
+        assert_eq!(frequency.gain, 20.0);
+        assert_eq!(frequency.scale_factor, 90.0);
+        assert_eq!(frequency.palette, PaletteKind::Viridis);
+        assert_eq!(frequency.color_mode, ColorMode::Frequency);
+    }
+
+    #[test]
+    fn smoothing_time_falls_back_to_the_interpolation_factor() {
+        let mut visualizer = VisualizerSettings::default();
+        let converted = visualizer.resolve("frequency").smoothing_ms;
+        assert!((converted - interpolation_time_constant_ms(0.09)).abs() < 1e-3);
+
+        visualizer.smoothing_ms = Some(250.0);
+        visualizer.overrides.insert(
+            "line".to_string(),
+            VisualizerOverrides {
+                interpolation_factor: Some(1.0),
+                ..VisualizerOverrides::default()
+            },
+        );
+        assert_eq!(visualizer.resolve("frequency").smoothing_ms, 250.0);
+        // An override of either key takes precedence over both common keys
+        assert_eq!(visualizer.resolve("line").smoothing_ms, 0.0);
+
+        assert_eq!(
+            invalid_paths(|s| s.visualizer.smoothing_ms = Some(-1.0)),
+            ["visualizer.smoothing_ms"]
+        );
+    }
+
+    #[test]
+    fn empty_config_uses_defaults() {
+        let (settings, unknown_keys) = parse_config("").unwrap();
+        assert!(unknown_keys.is_empty());
+        assert_eq!(settings.fft.size, 1024);
+        assert_eq!(settings.fft.sample_rate, 44100.0);
+        assert_eq!(settings.visualizer.kind, "frequency");
+        assert_eq!(settings.visualizer.gain, 20.0);
+        assert_eq!(settings.grid.lines, 10);
+        assert_eq!(settings.grid.line_width, 0.5);
+    }
+
+    #[test]
+    fn minimal_config_keeps_other_defaults() {
+        let (settings, _) = parse_config("[fft]\nsize = 2048\n").unwrap();
+        assert_eq!(settings.fft.size, 2048);
+        assert_eq!(settings.fft.max_frequency, 10000.0);
+        assert_eq!(settings.visualizer.scale_factor, 90.0);
+        assert_eq!(settings.grid.alpha, 0.1);
+    }
+
+    #[test]
+    fn shipped_config_parses_without_unknown_keys() {
+        let (settings, unknown_keys) = parse_config(DEFAULT_CONFIG).unwrap();
+        assert!(unknown_keys.is_empty(), "unknown keys: {:?}", unknown_keys);
+        assert_eq!(settings.fft.size, 1024);
+        assert_eq!(settings.visualizer.smoothing_ms, Some(175.0));
+        assert_eq!(settings.grid.color_left, Color::rgb(1.0, 0.0, 0.0));
+        assert_eq!(settings.keys["clear_noise_profile"], "shift+x");
+    }
+
+    #[test]
+    fn saved_settings_load_back_identically() {
+        let path = std::env::temp_dir().join(format!(
+            "sonic_spectra_round_trip_{}.toml",
+            std::process::id()
+        ));
+        let mut settings = Settings::from_config(DEFAULT_CONFIG).unwrap();
+        settings.visualizer.gain = 35.5;
+        settings.grid.color_left = Color::parse("#ff800080").unwrap();
+        settings.save(&path).unwrap();
+        let loaded = Settings::load(&path);
+        let _ = fs::remove_file(&path);
+
+        let loaded = loaded.unwrap();
+        assert_eq!(
+            toml::to_string(&loaded).unwrap(),
+            toml::to_string(&settings).unwrap()
+        );
+        assert_eq!(loaded.fft.frequencies, settings.fft.frequencies);
+    }
+
+    #[test]
+    fn generated_frequencies_are_not_saved() {
+        let path = std::env::temp_dir().join(format!(
+            "sonic_spectra_generated_{}.toml",
+            std::process::id()
+        ));
+        let mut settings = Settings::from_config("").unwrap();
+        assert!(settings.fft.generated_frequencies);
+        settings.fft.min_frequency = 100.0;
+        settings.save(&path).unwrap();
+        let saved = fs::read_to_string(&path);
+        let loaded = Settings::load(&path);
+        let _ = fs::remove_file(&path);
+
+        assert!(!saved.unwrap().contains("frequencies = ["));
+        // Loading generates them again from the saved range
+        assert_eq!(loaded.unwrap().fft.frequencies.unwrap()[0], 100.0);
+    }
+
+    #[test]
+    fn latency_target_values_are_not_saved() {
+        let path =
+            std::env::temp_dir().join(format!("sonic_spectra_latency_{}.toml", std::process::id()));
+        let config =
+            "[fft]\nhop_size = 128\nlatency_target_ms = 60.0\n[visualizer]\nsmoothing_ms = 40.0";
+        let mut settings = Settings::from_config(config).unwrap();
+        let budget = settings.apply_latency_target().unwrap();
+        assert_eq!(settings.fft.hop_length(), budget.hop_size);
+        assert_eq!(settings.visualizer.smoothing_ms(), budget.smoothing_ms);
+        assert_eq!(
+            settings.visualizer_settings("frequency").smoothing_ms,
+            budget.smoothing_ms
+        );
+        settings.save(&path).unwrap();
+        let loaded = Settings::load(&path);
+        let _ = fs::remove_file(&path);
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.fft.hop_size, Some(128));
+        assert_eq!(loaded.visualizer.smoothing_ms, Some(40.0));
+    }
+
+    #[test]
+    fn unknown_keys_are_reported() {
+        let (settings, unknown_keys) =
+            parse_config("[fft]\nsize = 512\nsmoothness = 3\n[grid]\nlinewidth = 2.0\n").unwrap();
+        assert_eq!(settings.fft.size, 512);
+        assert_eq!(unknown_keys, ["fft.smoothness", "grid.linewidth"]);
+
+        // Misspelled settings and visualizer names in [visualizer] are reported instead of
+        // failing the configuration or being kept as override tables that are never applied
+        let config = "[visualizer]\ngain = 3.0\ngian = 3.0\n\
+            [visualizer.freqency]\ngain = 2.0\n[visualizer.radial]\ngain = 4.0\nscale = 2.0\n";
+        let (settings, unknown_keys) = parse_config(config).unwrap();
+        assert_eq!(
+            unknown_keys,
+            [
+                "visualizer.freqency",
+                "visualizer.gian",
+                "visualizer.radial.scale"
+            ]
+        );
+        assert_eq!(settings.visualizer.gain, 3.0);
+        assert_eq!(settings.visualizer_settings("radial").gain, 4.0);
+        assert!(settings.visualizer.unrecognized.is_empty());
+    }
+
+    #[test]
+    fn visualizer_layers_parse_anchors_and_rects() {
+        let config = "visualizers = [\
+            { kind = \"frequency\", region = \"full\" },\
+            { kind = \"radial\", region = \"bottom_right\", size = 0.25 },\
+            { kind = \"octave\", region = [0.0, 0.0, 0.5, 0.1] },\
+        ]\n[fft]\nsize = 2048\n";
+        let (settings, unknown_keys) = parse_config(config).unwrap();
+        assert!(unknown_keys.is_empty(), "unknown keys: {:?}", unknown_keys);
+        assert_eq!(settings.fft.size, 2048);
+
+        let layers = &settings.visualizers;
+        assert_eq!(layers.len(), 3);
+        assert_eq!(layers[0].region, Region::Anchor(Anchor::Full));
+        assert_eq!(layers[1].kind, "radial");
+        assert_eq!(layers[1].region, Region::Anchor(Anchor::BottomRight));
+        assert_eq!(layers[2].region, Region::Rect([0.0, 0.0, 0.5, 0.1]));
+        assert_eq!(layers[2].size, LayerSettings::default().size);
+        assert!(Settings::from_config("").unwrap().visualizers.is_empty());
+    }
+
+    #[test]
+    fn windows_override_the_visualizer_of_each_window() {
+        let config = "windows = [\
+            { visualizer = \"frequency\" },\
+            { visualizer = \"chromagram\", monitor = 1 },\
+            {},\
+        ]\nvisualizers = [{ kind = \"radial\" }]\n";
+        let (settings, unknown_keys) = parse_config(config).unwrap();
+        assert!(unknown_keys.is_empty(), "unknown keys: {:?}", unknown_keys);
+
+        let entries = settings.window_entries();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1].monitor, Some(1));
+        let second = settings.for_window(&entries[1]);
+        assert_eq!(second.visualizer.kind, "chromagram");
+        assert!(second.visualizers.is_empty());
+        let third = settings.for_window(&entries[2]);
+        assert_eq!(third.visualizers, settings.visualizers);
+
+        let single = Settings::from_config("").unwrap().window_entries();
+        assert_eq!(single, [WindowEntry::default()]);
+    }
+
+    #[test]
+    fn scenes_merge_over_the_table_of_their_visualizer() {
+        let config = "visualizers = [{ kind = \"radial\" }]\n\
+            [visualizer.holographic_glow]\nalpha = 0.5\ngain = 10.0\n\
+            [[scene]]\nname = \"chill\"\nkind = \"holographic_glow\"\ncrossfade_ms = 300.0\n\
+            smoothing_ms = 400.0\npalette = \"viridis\"\ngain = 15.0\n";
+        let (settings, unknown_keys) = parse_config(config).unwrap();
+        assert!(unknown_keys.is_empty(), "unknown keys: {:?}", unknown_keys);
+        assert_eq!(settings.scenes.len(), 1);
+        assert_eq!(settings.scenes[0].crossfade_ms, 300.0);
+
+        let chill = settings.with_scene(&settings.scenes[0]);
+        assert_eq!(chill.visualizer.kind, "holographic_glow");
+        assert!(chill.visualizers.is_empty());
+        let resolved = chill.visualizer_settings("holographic_glow");
+        assert_eq!(resolved.gain, 15.0);
+        assert_eq!(resolved.alpha, 0.5);
+        assert_eq!(resolved.smoothing_ms, 400.0);
+        assert_eq!(resolved.palette, PaletteKind::Viridis);
+        // Other visualizers keep their settings
+        assert_eq!(
+            chill.visualizer_settings("radial").gain,
+            settings.visualizer.gain
+        );
+
+        let mut invalid = settings.clone();
+        invalid.scenes[0].overrides.alpha = Some(2.0);
+        invalid.scenes[0].crossfade_ms = -1.0;
+        let paths: Vec<_> = invalid
+            .validate()
+            .unwrap_err()
+            .into_iter()
+            .map(|error| error.path)
+            .collect();
+        assert!(paths.contains(&"scene[0].alpha".to_string()));
+        assert!(paths.contains(&"scene[0].crossfade_ms".to_string()));
+    }
+
+    #[test]
+    fn triggers_need_an_ordered_band_and_a_command_to_exec() {
+        let config = "[[trigger]]\nlow = 40\nhigh = 120\nthreshold_db = -24.0\n\
+            action = \"exec\"\ncommand = \"lights %STATE% %LEVEL%\"\n\
+            [[trigger]]\nlow = 2000\nhigh = 4000\n";
+        let (settings, unknown_keys) = parse_config(config).unwrap();
+        assert!(unknown_keys.is_empty(), "unknown keys: {:?}", unknown_keys);
+        assert_eq!(settings.triggers.len(), 2);
+        assert_eq!(settings.triggers[0].action, TriggerAction::Exec);
+        assert_eq!(settings.triggers[0].threshold_db, -24.0);
+        assert_eq!(settings.triggers[1].action, TriggerAction::Print);
+        assert!(settings.validate().is_ok());
+
+        let mut invalid = settings.clone();
+        invalid.triggers[0].high = 20.0;
+        invalid.triggers[0].command.clear();
+        invalid.triggers[1].release_ms = -5.0;
+        let paths: Vec<_> = invalid
+            .validate()
+            .unwrap_err()
+            .into_iter()
+            .map(|error| error.path)
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "trigger[1].release_ms",
+                "trigger[0].high",
+                "trigger[0].command"
+            ]
+        );
+
+        invalid.clamp_to_valid();
+        assert!(invalid.validate().is_ok());
+        assert_eq!(invalid.triggers[0].action, TriggerAction::Print);
+    }
+
+    #[test]
+    fn layer_regions_resolve_to_window_rectangles() {
+        let layer = |region, size| LayerSettings {
+            kind: "frequency".to_string(),
+            region,
+            size,
+        };
+        let (width, height) = (800.0, 600.0);
+        let anchored = |anchor| layer(Region::Anchor(anchor), 0.25).rect(width, height);
+
+        assert_eq!(anchored(Anchor::Full), (0.0, 0.0, 800.0, 600.0));
+        assert_eq!(anchored(Anchor::BottomRight), (600.0, 450.0, 200.0, 150.0));
+        assert_eq!(anchored(Anchor::TopLeft), (0.0, 0.0, 200.0, 150.0));
+        assert_eq!(anchored(Anchor::Center), (300.0, 225.0, 200.0, 150.0));
+        assert_eq!(anchored(Anchor::Left), (0.0, 0.0, 200.0, 600.0));
+        assert_eq!(anchored(Anchor::Bottom), (0.0, 450.0, 800.0, 150.0));
+
+        let rect = layer(Region::Rect([0.5, 0.0, 0.5, 0.25]), 1.0);
+        assert_eq!(rect.rect(width, height), (400.0, 0.0, 400.0, 150.0));
+        // Rectangles reaching past the window are cut at its edges
+        let overflowing = layer(Region::Rect([0.75, 0.5, 0.5, 1.0]), 1.0);
+        assert_eq!(
+            overflowing.rect(width, height),
+            (600.0, 300.0, 200.0, 300.0)
+        );
+    }
+
+    #[test]
+    fn margins_are_pixels_or_percentages_of_the_window() {
+        let config = r#"
+            [layout.margin]
+            top = 20
+            left = "10%"
+            right = 12.4
+        "#;
+        let (settings, unknown_keys) = parse_config(config).unwrap();
+        assert!(unknown_keys.is_empty(), "unknown keys: {:?}", unknown_keys);
+        let margin = settings.layout.margin;
+        assert_eq!(margin.left, MarginLength::Percent(10.0));
+        assert_eq!(margin.bottom, MarginLength::Pixels(0.0));
+        assert_eq!(
+            settings.layout.plot_rect(800.0, 600.0),
+            PlotRect {
+                x: 80.0,
+                y: 20.0,
+                width: 708.0,
+                height: 580.0
+            }
+        );
+        assert!(Settings::from_config("layout.margin.top = \"wide\"").is_err());
+
+        // Margins wider than the window leave an empty plot
+        let layout = LayoutSettings {
+            margin: Margins {
+                left: MarginLength::Percent(60.0),
+                right: MarginLength::Percent(60.0),
+                ..Margins::default()
+            },
+            ..LayoutSettings::default()
+        };
+        assert_eq!(layout.plot_rect(100.0, 50.0).width, 0.0);
+        assert_eq!(
+            LayoutSettings::default().plot_rect(100.0, 50.0),
+            PlotRect {
+                x: 0.0,
+                y: 0.0,
+                width: 100.0,
+                height: 50.0
+            }
+        );
+
+        let serialized = toml::to_string(&settings.layout).unwrap();
+        assert!(serialized.contains("left = \"10%\""), "{}", serialized);
+        assert!(serialized.contains("top = 20.0"), "{}", serialized);
+    }
+
+    /// Returns the paths rejected by `validate` after applying `change` to the defaults.
+    fn invalid_paths(change: impl FnOnce(&mut Settings)) -> Vec<String> {
+        let mut settings = Settings::default();
+        change(&mut settings);
+        match settings.validate() {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.into_iter().map(|error| error.path).collect(),
+        }
+    }
+
+    #[test]
+    fn default_settings_are_valid() {
+        assert!(Settings::default().validate().is_ok());
+    }
+
+    #[test]
+    fn layer_sizes_and_rects_must_be_fractions() {
+        let layers = |size, rect| {
+            vec![
+                LayerSettings {
+                    size,
+                    ..LayerSettings::default()
+                },
+                LayerSettings {
+                    region: Region::Rect(rect),
+                    ..LayerSettings::default()
+                },
+            ]
+        };
+        assert!(invalid_paths(|s| s.visualizers = layers(0.5, [0.0, 0.5, 1.0, 0.5])).is_empty());
+        assert_eq!(
+            invalid_paths(|s| s.visualizers = layers(1.5, [0.0, 0.5, 1.0, -0.5])),
+            ["visualizers[0].size", "visualizers[1].region"]
+        );
+
+        let mut settings = Settings {
+            visualizers: layers(1.5, [0.0, 0.5, 1.0, -0.5]),
+            ..Settings::default()
+        };
+        settings.clamp_to_valid();
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn fft_size_must_be_a_power_of_two_in_range() {
+        assert_eq!(invalid_paths(|s| s.fft.size = 1000), ["fft.size"]);
+        assert_eq!(invalid_paths(|s| s.fft.size = 128), ["fft.size"]);
+        assert_eq!(invalid_paths(|s| s.fft.size = 65536), ["fft.size"]);
+        assert!(invalid_paths(|s| s.fft.size = 32768).is_empty());
+    }
+
+    #[test]
+    fn latency_target_replaces_the_hop_size_and_smoothing() {
+        let mut settings = Settings::default();
+        assert_eq!(settings.apply_latency_target(), None);
+        assert_eq!(settings.fft.hop_size, None);
+
+        settings.fft.latency_target_ms = Some(100.0);
+        let budget = settings.apply_latency_target().unwrap();
+        assert_eq!(settings.fft.hop_size, Some(budget.hop_size));
+        assert_eq!(settings.visualizer.smoothing_ms, Some(budget.smoothing_ms));
+        assert!(settings.validate().is_ok());
+
+        assert_eq!(
+            invalid_paths(|s| s.fft.latency_target_ms = Some(0.0)),
+            ["fft.latency_target_ms"]
+        );
+    }
+
+    #[test]
+    fn hop_size_must_fit_in_the_fft() {
+        assert_eq!(
+            invalid_paths(|s| s.fft.hop_size = Some(0)),
+            ["fft.hop_size"]
+        );
+        assert_eq!(
+            invalid_paths(|s| s.fft.hop_size = Some(2048)),
+            ["fft.hop_size"]
+        );
+        assert!(invalid_paths(|s| s.fft.hop_size = Some(1024)).is_empty());
+    }
+
+    #[test]
+    fn zero_pad_factor_must_be_1_2_or_4() {
+        assert_eq!(
+            invalid_paths(|s| s.fft.zero_pad_factor = 3),
+            ["fft.zero_pad_factor"]
+        );
+        assert_eq!(
+            invalid_paths(|s| s.fft.zero_pad_factor = 0),
+            ["fft.zero_pad_factor"]
+        );
+        assert!(invalid_paths(|s| s.fft.zero_pad_factor = 4).is_empty());
+
+        let mut settings = Settings::default();
+        settings.fft.zero_pad_factor = 3;
+        settings.clamp_to_valid();
+        assert_eq!(settings.fft.zero_pad_factor, 4);
+        assert_eq!(settings.fft.padded_size(), 4 * settings.fft.size);
+    }
+
+    #[test]
+    fn test_tone_and_its_octave_must_be_below_nyquist() {
+        assert_eq!(
+            invalid_paths(|s| s.audio.test_frequency = 0.0),
+            ["audio.test_frequency"]
+        );
+        assert_eq!(
+            invalid_paths(|s| s.audio.test_frequency = 12_000.0),
+            ["audio.test_frequency"]
+        );
+        assert_eq!(
+            invalid_paths(|s| s.audio.test_amplitude = 1.5),
+            ["audio.test_amplitude"]
+        );
+        assert!(invalid_paths(|s| s.audio.test_frequency = 10_000.0).is_empty());
+    }
+
+    #[test]
+    fn input_gain_must_be_in_range() {
+        assert!(invalid_paths(|s| s.audio.input_gain_db = -40.0).is_empty());
+        assert!(invalid_paths(|s| s.audio.input_gain_db = 24.0).is_empty());
+        assert_eq!(
+            invalid_paths(|s| s.audio.input_gain_db = 60.0),
+            ["audio.input_gain_db"]
+        );
+        assert_eq!(
+            invalid_paths(|s| s.audio.input_gain_db = f32::NAN),
+            ["audio.input_gain_db"]
+        );
+
+        let mut settings = Settings::default();
+        settings.audio.input_gain_db = 60.0;
+        settings.clamp_to_valid();
+        assert_eq!(settings.audio.input_gain_db, 40.0);
+    }
+
+    #[test]
+    fn trails_must_fade() {
+        assert_eq!(
+            invalid_paths(|s| s.effects.persistence = 0.99),
+            ["effects.persistence"]
+        );
+        assert!(invalid_paths(|s| s.effects.persistence = 0.95).is_empty());
+    }
+
+    #[test]
+    fn octave_fraction_must_be_supported() {
+        assert_eq!(
+            invalid_paths(|s| s.octave.fraction = 2),
+            ["octave.fraction"]
+        );
+        assert!(invalid_paths(|s| s.octave.fraction = 6).is_empty());
+        assert_eq!(
+            invalid_paths(|s| s.chromagram.smoothing_secs = -0.1),
+            ["chromagram.smoothing_secs"]
+        );
+        assert_eq!(
+            invalid_paths(|s| s.difference.range_db = 0.0),
+            ["difference.range_db"]
+        );
+    }
+
+    #[test]
+    fn waveform_split_and_length_must_be_in_range() {
+        assert_eq!(
+            invalid_paths(|s| s.waveform.split = 1.2),
+            ["waveform.split"]
+        );
+        assert_eq!(
+            invalid_paths(|s| s.waveform.seconds = 0.0),
+            ["waveform.seconds"]
+        );
+        assert_eq!(
+            invalid_paths(|s| s.waveform.seconds = 120.0),
+            ["waveform.seconds"]
+        );
+
+        let mut settings = Settings {
+            waveform: WaveformSettings {
+                split: -0.5,
+                seconds: -1.0,
+            },
+            ..Settings::default()
+        };
+        settings.clamp_to_valid();
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn ui_font_size_must_be_in_range() {
+        assert_eq!(invalid_paths(|s| s.ui.font_size = 0.0), ["ui.font_size"]);
+        assert_eq!(invalid_paths(|s| s.ui.font_size = 100.0), ["ui.font_size"]);
+        assert!(invalid_paths(|s| s.ui.font_size = 20.0).is_empty());
+    }
+
+    #[test]
+    fn margins_must_not_be_negative() {
+        assert_eq!(
+            invalid_paths(|s| s.layout.margin.left = MarginLength::Pixels(-1.0)),
+            ["layout.margin.left"]
+        );
+        assert_eq!(
+            invalid_paths(|s| s.layout.margin.bottom = MarginLength::Percent(150.0)),
+            ["layout.margin.bottom"]
+        );
+        assert!(invalid_paths(|s| s.layout.margin.top = MarginLength::Percent(5.0)).is_empty());
+
+        let mut settings = Settings::default();
+        settings.layout.margin.right = MarginLength::Percent(-5.0);
+        settings.clamp_to_valid();
+        assert_eq!(settings.layout.margin.right, MarginLength::Percent(0.0));
+    }
+
+    #[test]
+    fn bar_gaps_and_radii_must_not_be_negative() {
+        assert_eq!(
+            invalid_paths(|s| s.visualizer.bar_gap = MarginLength::Pixels(-2.0)),
+            ["visualizer.bar_gap"]
+        );
+        assert_eq!(
+            invalid_paths(|s| s.visualizer.bar_radius = -1.0),
+            ["visualizer.bar_radius"]
+        );
+        let config = "[visualizer]\nbar_gap = \"20%\"\nbar_radius = 3.0";
+        let settings = Settings::from_config(config).unwrap();
+        assert_eq!(settings.visualizer.bar_gap, MarginLength::Percent(20.0));
+
+        let mut settings = Settings::default();
+        settings.visualizer.bar_gap = MarginLength::Percent(120.0);
+        settings.visualizer.bar_radius = -4.0;
+        settings.clamp_to_valid();
+        assert_eq!(settings.visualizer.bar_gap, MarginLength::Percent(100.0));
+        assert_eq!(settings.visualizer.bar_radius, 0.0);
+    }
+
+    #[test]
+    fn bar_gaps_can_be_overridden_per_visualizer() {
+        let config =
+            "[visualizer]\nbar_gap = 2.0\n[visualizer.holographic_glow]\nbar_gap = \"25%\"";
+        let settings = Settings::from_config(config).unwrap();
+        assert_eq!(
+            settings.visualizer_settings("holographic_glow").bar_gap,
+            MarginLength::Percent(25.0)
+        );
+        assert_eq!(
+            settings.visualizer_settings("frequency").bar_gap,
+            MarginLength::Pixels(2.0)
+        );
+
+        let gap = |gap| VisualizerOverrides {
+            bar_gap: Some(gap),
+            ..VisualizerOverrides::default()
+        };
+        let paths = invalid_paths(|s| {
+            s.visualizer
+                .overrides
+                .insert("difference".to_string(), gap(MarginLength::Pixels(-1.0)));
+        });
+        assert_eq!(paths, ["visualizer.difference.bar_gap"]);
+
+        let mut settings = Settings::default();
+        settings
+            .visualizer
+            .overrides
+            .insert("difference".to_string(), gap(MarginLength::Percent(150.0)));
+        settings.clamp_to_valid();
+        assert_eq!(
+            settings.visualizer.overrides["difference"].bar_gap,
+            Some(MarginLength::Percent(100.0))
+        );
+    }
+
+    #[test]
+    fn palette_cycle_excludes_a_manual_palette() {
+        let config = "[visualizer]\npalette_cycle = [\"viridis\", \"inferno\"]\n\
+            palette_cycle_seconds = 60.0\n[visualizer.radial]\npalette = \"mono\"";
+        let settings = Settings::from_config(config).unwrap();
+        assert!(settings.validate().is_ok());
+        assert_eq!(
+            settings.visualizer.palette_cycle,
+            [PaletteKind::Viridis, PaletteKind::Inferno]
+        );
+        assert!(settings.visualizer.follows_palette_cycle("frequency"));
+        assert!(!settings.visualizer.follows_palette_cycle("radial"));
+        assert!(!Settings::default()
+            .visualizer
+            .follows_palette_cycle("frequency"));
+
+        let both = |s: &mut Settings| {
+            s.visualizer.palette = Some(PaletteKind::Rainbow);
+            s.visualizer.palette_cycle = vec![PaletteKind::Mono];
+        };
+        assert_eq!(invalid_paths(both), ["visualizer.palette"]);
+        assert_eq!(
+            invalid_paths(|s| s.visualizer.palette_cycle_seconds = 0.0),
+            ["visualizer.palette_cycle_seconds"]
+        );
+
+        let mut settings = Settings::default();
+        both(&mut settings);
+        settings.clamp_to_valid();
+        assert_eq!(settings.visualizer.palette, None);
+        assert_eq!(settings.visualizer.palette_cycle, [PaletteKind::Mono]);
+    }
+
+    #[test]
+    fn curve_exponent_replaces_the_named_curve() {
+        let config = "[visualizer]\ncurve = \"punchy\"";
+        let settings = Settings::from_config(config).unwrap();
+        assert_eq!(settings.visualizer.curve, HeightCurve::Punchy);
+        assert_eq!(
+            settings.visualizer_settings("frequency").curve_exponent,
+            HeightCurve::Punchy.exponent()
+        );
+
+        let config = "[visualizer]\ncurve = \"soft\"\ncurve_exponent = 2.0";
+        let settings = Settings::from_config(config).unwrap();
+        assert_eq!(
+            settings.visualizer_settings("frequency").curve_exponent,
+            2.0
+        );
+
+        assert_eq!(
+            invalid_paths(|s| s.visualizer.curve_exponent = Some(0.0)),
+            ["visualizer.curve_exponent"]
+        );
+        let mut settings = Settings::default();
+        settings.visualizer.curve_exponent = Some(50.0);
+        settings.clamp_to_valid();
+        assert_eq!(settings.visualizer.curve_exponent, Some(MAX_CURVE_EXPONENT));
+    }
+
+    #[test]
+    fn curve_exponent_can_be_overridden_per_visualizer() {
+        let config = "[visualizer]\ncurve = \"punchy\"\n[visualizer.octave]\ncurve_exponent = 0.5";
+        let settings = Settings::from_config(config).unwrap();
+        assert_eq!(settings.visualizer_settings("octave").curve_exponent, 0.5);
+        assert_eq!(
+            settings.visualizer_settings("frequency").curve_exponent,
+            HeightCurve::Punchy.exponent()
+        );
+
+        let exponent = |exponent| VisualizerOverrides {
+            curve_exponent: Some(exponent),
+            ..VisualizerOverrides::default()
+        };
+        let paths = invalid_paths(|s| {
+            s.visualizer
+                .overrides
+                .insert("octave".to_string(), exponent(50.0));
+        });
+        assert_eq!(paths, ["visualizer.octave.curve_exponent"]);
+
+        let mut settings = Settings::default();
+        let overrides = &mut settings.visualizer.overrides;
+        overrides.insert("octave".to_string(), exponent(50.0));
+        overrides.insert("chromagram".to_string(), exponent(f32::NAN));
+        settings.clamp_to_valid();
+        let overrides = &settings.visualizer.overrides;
+        assert_eq!(overrides["octave"].curve_exponent, Some(MAX_CURVE_EXPONENT));
+        assert_eq!(overrides["chromagram"].curve_exponent, None);
+    }
+
+    #[test]
+    fn fractions_out_of_range_are_clamped_when_resolved() {
+        let mut settings = Settings::default();
+        settings.visualizer.alpha = 1.5;
+        settings.visualizer.interpolation_factor = 1.8;
+        settings.visualizer.overrides.insert(
+            "radial".to_string(),
+            VisualizerOverrides {
+                alpha: Some(f32::NAN),
+                interpolation_factor: Some(-0.2),
+                ..VisualizerOverrides::default()
+            },
+        );
+
+        let resolved = settings.visualizer_settings("frequency");
+        assert_eq!(resolved.alpha, 1.0);
+        assert_eq!(resolved.smoothing_ms, 0.0);
+        let resolved = settings.visualizer_settings("radial");
+        assert_eq!(resolved.alpha, 1.0);
+        assert_eq!(resolved.smoothing_ms, f32::INFINITY);
+    }
+
+    #[test]
+    fn reflections_take_their_height_from_the_plot() {
+        let (width, height) = (800.0, 600.0);
+        let settings = Settings::default();
+        assert_eq!(
+            settings.plot_rect(width, height),
+            settings.layout.plot_rect(width, height)
+        );
+        assert_eq!(settings.reflection_rect(width, height).height, 0.0);
+
+        let settings = Settings {
+            effects: EffectsSettings {
+                reflection: true,
+                ..EffectsSettings::default()
+            },
+            ..Settings::default()
+        };
+        let layout = settings.layout.plot_rect(width, height);
+        let (plot, reflection) = (
+            settings.plot_rect(width, height),
+            settings.reflection_rect(width, height),
+        );
+        assert_eq!(reflection.height, (layout.height * 0.25).round());
+        assert_eq!(plot.height + reflection.height, layout.height);
+        assert_eq!(reflection.y, plot.y + plot.height);
+        assert_eq!((reflection.x, reflection.width), (plot.x, plot.width));
+
+        assert_eq!(
+            invalid_paths(|s| s.effects.reflection_height = 1.5),
+            ["effects.reflection_height"]
+        );
+        assert_eq!(
+            invalid_paths(|s| s.effects.reflection_alpha = -0.1),
+            ["effects.reflection_alpha"]
+        );
+    }
+
+    #[test]
+    fn curves_need_a_positive_width() {
+        assert_eq!(
+            invalid_paths(|s| s.curves.line_width = 0.0),
+            ["curves.line_width"]
+        );
+        assert_eq!(
+            invalid_paths(|s| s.curves.average_secs = -1.0),
+            ["curves.average_secs"]
+        );
+        assert_eq!(invalid_paths(|s| s.curves.alpha = 2.0), ["curves.alpha"]);
+
+        let mut settings = Settings::default();
+        settings.curves.line_width = -1.0;
+        settings.curves.average_secs = -3.0;
+        settings.clamp_to_valid();
+        assert_eq!(settings.curves.line_width, 1.5);
+        assert_eq!(settings.curves.average_secs, 0.0);
+    }
+
+    #[test]
+    fn idle_frame_rate_must_be_positive() {
+        assert_eq!(
+            invalid_paths(|s| s.power.idle_fps = 0.0),
+            ["power.idle_fps"]
+        );
+        assert_eq!(
+            invalid_paths(|s| s.power.idle_after_secs = -1.0),
+            ["power.idle_after_secs"]
+        );
+        assert_eq!(
+            invalid_paths(|s| s.power.battery_fps = 0.0),
+            ["power.battery_fps"]
+        );
+        assert_eq!(Settings::default().fft.hop_length(), 256);
+    }
+
+    #[test]
+    fn frequency_range_must_be_ordered_and_below_nyquist() {
+        assert_eq!(
+            invalid_paths(|s| s.fft.min_frequency = 10000.0),
+            ["fft.min_frequency"]
+        );
+        assert_eq!(
+            invalid_paths(|s| s.fft.max_frequency = 22050.0),
+            ["fft.max_frequency"]
+        );
+    }
+
+    #[test]
+    fn factors_must_be_between_zero_and_one() {
+        assert_eq!(
+            invalid_paths(|s| s.visualizer.interpolation_factor = 5.0),
+            ["visualizer.interpolation_factor"]
+        );
+        assert_eq!(
+            invalid_paths(|s| s.visualizer.smooth_factor = -0.1),
+            ["visualizer.smooth_factor"]
+        );
+        assert_eq!(
+            invalid_paths(|s| s.visualizer.attack = 1.5),
+            ["visualizer.attack"]
+        );
+        assert_eq!(
+            invalid_paths(|s| s.visualizer.release = Some(2.0)),
+            ["visualizer.release"]
+        );
+        assert_eq!(
+            invalid_paths(|s| s.visualizer.auto_gain_attack = 1.1),
+            ["visualizer.auto_gain_attack"]
+        );
+        assert_eq!(
+            invalid_paths(|s| s.visualizer.auto_gain_release = -1.0),
+            ["visualizer.auto_gain_release"]
+        );
+    }
+
+    #[test]
+    fn alphas_must_be_between_zero_and_one() {
+        assert_eq!(
+            invalid_paths(|s| s.visualizer.alpha = 3.0),
+            ["visualizer.alpha"]
+        );
+        assert_eq!(invalid_paths(|s| s.grid.alpha = 1.5), ["grid.alpha"]);
+        assert_eq!(
+            invalid_paths(|s| s.effects.max_alpha = -0.5),
+            ["effects.max_alpha"]
+        );
+    }
+
+    #[test]
+    fn overrides_are_validated() {
+        let paths = invalid_paths(|s| {
+            s.visualizer.overrides.insert(
+                "line".to_string(),
+                VisualizerOverrides {
+                    interpolation_factor: Some(2.0),
+                    ..VisualizerOverrides::default()
+                },
+            );
+        });
+        assert_eq!(paths, ["visualizer.line.interpolation_factor"]);
+    }
+
+    #[test]
+    fn grid_needs_at_least_one_line() {
+        assert_eq!(invalid_paths(|s| s.grid.lines = 0), ["grid.lines"]);
+    }
+
+    #[test]
+    fn all_violations_are_reported_with_their_values() {
+        let mut settings = Settings::default();
+        settings.grid.lines = 0;
+        settings.visualizer.alpha = 3.0;
+
+        let errors = settings.validate().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors
+            .iter()
+            .any(|error| error.to_string() == "visualizer.alpha = 3: must be between 0.0 and 1.0"));
+    }
+
+    #[test]
+    fn clamping_fixes_every_violation() {
+        let mut settings = Settings::default();
+        settings.fft.size = 1000;
+        settings.fft.max_frequency = 30000.0;
+        settings.fft.min_frequency = 40000.0;
+        settings.fft.hop_size = Some(5000);
+        settings.visualizer.interpolation_factor = 5.0;
+        settings.visualizer.release = Some(-1.0);
+        settings.grid.lines = 0;
+        settings.grid.alpha = 2.0;
+
+        settings.clamp_to_valid();
+        assert!(settings.validate().is_ok());
+        assert_eq!(settings.fft.size, 1024);
+        assert_eq!(settings.visualizer.interpolation_factor, 1.0);
+        assert_eq!(settings.grid.lines, 1);
     }
 }
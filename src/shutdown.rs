@@ -0,0 +1,168 @@
+use crate::audio::SinkWatch;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How often `Shutdown::stop` checks whether the audio and the workers stopped.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The audio sources and worker threads of the application, stopped in order at exit.
+///
+/// Sources and workers are told to stop through one flag. The sources release their input
+/// first, which shows as their sinks being dropped; then the workers, which may still read what
+/// the sources delivered, are joined.
+///
+/// # Fields
+/// - `flag`: Set to tell the sources and workers to stop.
+/// - `sinks`: Watches of the sinks of the running sources.
+/// - `workers`: Worker threads with their names, joined after the sources stopped.
+pub struct Shutdown {
+    flag: Arc<AtomicBool>,
+    sinks: Vec<SinkWatch>,
+    workers: Vec<(String, JoinHandle<()>)>,
+}
+
+impl Shutdown {
+    /// Creates a new `Shutdown` with nothing to stop yet.
+    pub fn new() -> Self {
+        Shutdown {
+            flag: Arc::new(AtomicBool::new(false)),
+            sinks: Vec::new(),
+            workers: Vec::new(),
+        }
+    }
+
+    /// Returns the flag set when stopping, for the sources and workers to poll.
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        self.flag.clone()
+    }
+
+    /// Waits for the sink of a source to be dropped when stopping.
+    ///
+    /// # Arguments
+    /// - `sink`: Watch of the sink the source was started with, from `AudioReader::sink_watch`.
+    pub fn watch_sink(&mut self, sink: SinkWatch) {
+        self.sinks.push(sink);
+    }
+
+    /// Starts a worker thread, joined when stopping.
+    ///
+    /// # Arguments
+    /// - `name`: Name of the thread, reported if it does not stop in time.
+    /// - `work`: The work of the thread, which returns once `flag` is set.
+    ///
+    /// # Returns
+    /// - An error if the thread cannot be created.
+    pub fn spawn(&mut self, name: &str, work: impl FnOnce() + Send + 'static) -> io::Result<()> {
+        let handle = thread::Builder::new().name(name.to_string()).spawn(work)?;
+        self.workers.push((name.to_string(), handle));
+        Ok(())
+    }
+
+    /// Stops the sources, then joins the workers.
+    ///
+    /// Stopping again after everything stopped returns at once.
+    ///
+    /// # Arguments
+    /// - `timeout`: Time the sources and workers get to stop, together.
+    ///
+    /// # Returns
+    /// - `Ok(())` once everything stopped, or the names of what did not stop in time: `"audio"`
+    ///   for sources still holding their sinks, and the names of the workers still running,
+    ///   which are left behind.
+    pub fn stop(&mut self, timeout: Duration) -> Result<(), Vec<String>> {
+        let deadline = Instant::now() + timeout;
+        self.flag.store(true, Ordering::Relaxed);
+
+        while self.sinks.iter().any(|sink| !sink.is_dropped()) && Instant::now() < deadline {
+            thread::sleep(POLL_INTERVAL);
+        }
+        let mut running = Vec::new();
+        if self.sinks.iter().any(|sink| !sink.is_dropped()) {
+            running.push("audio".to_string());
+        }
+        self.sinks.clear();
+
+        while self.workers.iter().any(|(_, handle)| !handle.is_finished())
+            && Instant::now() < deadline
+        {
+            thread::sleep(POLL_INTERVAL);
+        }
+        for (name, handle) in self.workers.drain(..) {
+            if handle.is_finished() {
+                if handle.join().is_err() {
+                    eprintln!("The {} thread panicked.", name);
+                }
+            } else {
+                running.push(name);
+            }
+        }
+
+        if running.is_empty() {
+            Ok(())
+        } else {
+            Err(running)
+        }
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Shutdown::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{audio_channel, AudioSource, SineTestSource};
+    use crate::settings::FFTSettings;
+
+    #[test]
+    fn sources_and_workers_stop_within_the_timeout() {
+        let mut shutdown = Shutdown::new();
+        let (sink, mut reader) = audio_channel(&FFTSettings::default());
+        shutdown.watch_sink(reader.sink_watch());
+        Box::new(SineTestSource::new(440.0, 0.5, 44100.0))
+            .start(sink, shutdown.flag())
+            .unwrap();
+        let flag = shutdown.flag();
+        shutdown
+            .spawn("reader", move || {
+                while !flag.load(Ordering::Relaxed) {
+                    reader.read();
+                    thread::sleep(Duration::from_millis(1));
+                }
+            })
+            .unwrap();
+
+        let started = Instant::now();
+        assert_eq!(shutdown.stop(Duration::from_secs(2)), Ok(()));
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(shutdown.stop(Duration::ZERO), Ok(()));
+    }
+
+    #[test]
+    fn workers_that_do_not_stop_are_reported() {
+        let mut shutdown = Shutdown::new();
+        let (_sink, reader) = audio_channel(&FFTSettings::default());
+        shutdown.watch_sink(reader.sink_watch());
+        let release = Arc::new(AtomicBool::new(false));
+        let release_clone = release.clone();
+        shutdown
+            .spawn("stuck", move || {
+                while !release_clone.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(1));
+                }
+            })
+            .unwrap();
+
+        assert_eq!(
+            shutdown.stop(Duration::from_millis(50)),
+            Err(vec!["audio".to_string(), "stuck".to_string()])
+        );
+        release.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::fft_utils::interpolate;
+use std::ops::{Deref, DerefMut};
+
+/// The previous heights of the bars of one channel, which a visualizer moves towards the heights
+/// of the next frame.
+///
+/// How many bars a visualizer draws changes at runtime, e.g. with the width, the zoom, the
+/// visualizer or reloaded settings, so visualizers call `ensure_len` with the number of bars of
+/// the frame before indexing the heights. The heights dereference to a slice of pixels.
+///
+/// # Fields
+/// - `heights`: The height of each bar in the last frame, in pixels.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SmoothingState {
+    heights: Vec<f32>,
+}
+
+impl SmoothingState {
+    /// Creates a state of `len` bars at zero height.
+    pub fn new(len: usize) -> Self {
+        SmoothingState {
+            heights: vec![0.0; len],
+        }
+    }
+
+    /// Resizes the state to `len` bars, keeping it as it is if it has that many already.
+    ///
+    /// The previous heights are spread over the new bars by their position across the bars, so
+    /// the first and last bars keep their heights and the bars between take the heights
+    /// interpolated at their position. Growing or shrinking then keeps the outline of the bars
+    /// instead of snapping them to zero or cutting them off.
+    ///
+    /// # Arguments
+    /// - `len`: The number of bars of the next frame.
+    pub fn ensure_len(&mut self, len: usize) {
+        let previous = self.heights.len();
+        if previous == len {
+            return;
+        }
+        if previous == 0 || len < 2 {
+            let first = self.heights.first().copied().unwrap_or(0.0);
+            self.heights = vec![first; len];
+            return;
+        }
+
+        let step = (previous - 1) as f32 / (len - 1) as f32;
+        self.heights = (0..len)
+            .map(|i| {
+                let position = i as f32 * step;
+                let below = (position.floor() as usize).min(previous - 1);
+                let above = (below + 1).min(previous - 1);
+                interpolate(
+                    self.heights[below],
+                    self.heights[above],
+                    position - below as f32,
+                )
+            })
+            .collect();
+    }
+
+    /// Resizes the state to `len` bars without moving any height, for visualizers that move
+    /// the heights to their new bars themselves; added bars start at zero height.
+    pub fn set_len(&mut self, len: usize) {
+        self.heights.resize(len, 0.0);
+    }
+}
+
+impl From<Vec<f32>> for SmoothingState {
+    fn from(heights: Vec<f32>) -> Self {
+        SmoothingState { heights }
+    }
+}
+
+impl Deref for SmoothingState {
+    type Target = [f32];
+
+    fn deref(&self) -> &[f32] {
+        &self.heights
+    }
+}
+
+impl DerefMut for SmoothingState {
+    fn deref_mut(&mut self) -> &mut [f32] {
+        &mut self.heights
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn growing_interpolates_between_the_heights() {
+        let mut state = SmoothingState::from(vec![0.0, 10.0, 20.0]);
+        state.ensure_len(5);
+        assert_eq!(&state[..], [0.0, 5.0, 10.0, 15.0, 20.0]);
+
+        let mut empty = SmoothingState::default();
+        empty.ensure_len(3);
+        assert_eq!(&empty[..], [0.0; 3]);
+    }
+
+    #[test]
+    fn shrinking_keeps_the_outline() {
+        let mut state = SmoothingState::from(vec![0.0, 5.0, 10.0, 15.0, 20.0]);
+        state.ensure_len(3);
+        assert_eq!(&state[..], [0.0, 10.0, 20.0]);
+
+        state.ensure_len(1);
+        assert_eq!(&state[..], [0.0]);
+        state.ensure_len(0);
+        assert!(state.is_empty());
+    }
+
+    #[test]
+    fn the_same_length_keeps_the_heights() {
+        let heights = vec![3.0, 1.0, 4.0, 1.0, 5.0];
+        let mut state = SmoothingState::from(heights.clone());
+        state.ensure_len(5);
+        assert_eq!(&state[..], heights);
+
+        state.set_len(7);
+        assert_eq!(&state[..5], heights);
+        assert_eq!(&state[5..], [0.0, 0.0]);
+    }
+}
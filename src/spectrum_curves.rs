@@ -0,0 +1,314 @@
+use crate::fft_utils::{frequency_indices, smoothing_factor};
+use crate::frequency_mapper::{Channel, FrequencyMapper};
+use crate::level_scale::{bar_rect, LevelScale};
+use crate::settings::{BarDirection, Settings};
+use gtk::cairo::Context;
+use gtk4 as gtk;
+use rustfft::num_complex::Complex32;
+use std::sync::Arc;
+
+/// Long-term average and max-hold curves drawn as thin lines over the bars.
+///
+/// Both are kept per bar and channel, from the heights the bars are smoothed toward, with the
+/// bars laid out like those of the `frequency`, `holographic_glow` and `waveform_spectrum`
+/// visualizers.
+///
+/// # Fields
+/// - `settings`: Shared settings containing the `[curves]` configuration.
+/// - `direction`: Edge the bars of the visualizer drawn under the curves grow from.
+/// - `half_width`: Half of the width the bars were last laid out at.
+/// - `average`: The long-term average height of every left and right channel bar, empty before
+///   the first frame.
+/// - `max_hold`: The highest height every left and right channel bar reached since the last
+///   reset, empty before the first frame.
+pub struct SpectrumCurves {
+    settings: Arc<Settings>,
+    direction: BarDirection,
+    half_width: f64,
+    average: [Vec<f32>; 2],
+    max_hold: [Vec<f32>; 2],
+}
+
+impl SpectrumCurves {
+    /// Creates a new `SpectrumCurves` instance.
+    ///
+    /// # Arguments
+    /// - `settings`: Shared settings containing the `[curves]` configuration and the displayed
+    ///   frequency range.
+    /// - `visualizer`: Name of the visualizer drawn under the curves, whose bars they follow.
+    pub fn new(settings: Arc<Settings>, visualizer: &str) -> Self {
+        let mut curves = SpectrumCurves {
+            settings,
+            direction: BarDirection::default(),
+            half_width: 0.0,
+            average: [Vec::new(), Vec::new()],
+            max_hold: [Vec::new(), Vec::new()],
+        };
+        curves.set_visualizer(visualizer);
+        curves
+    }
+
+    /// Orients the curves like the bars of another visualizer, starting with the next frame.
+    ///
+    /// # Arguments
+    /// - `visualizer`: Name of the visualizer now drawn under the curves.
+    pub fn set_visualizer(&mut self, visualizer: &str) {
+        self.direction = self.settings.visualizer_settings(visualizer).direction;
+    }
+
+    /// Returns whether any curve is drawn.
+    pub fn enabled(&self) -> bool {
+        self.settings.curves.average || self.settings.curves.max_hold
+    }
+
+    /// Starts the max-hold curve over from the next frame.
+    pub fn clear_max_hold(&mut self) {
+        self.max_hold = [Vec::new(), Vec::new()];
+    }
+
+    /// Adds a frame to the curves.
+    ///
+    /// # Arguments
+    /// - `fft_left`: FFT data for the left audio channel, as drawn by the bars.
+    /// - `fft_right`: FFT data for the right audio channel, as drawn by the bars.
+    /// - `level_scale`: Height mapping of the bars, including any automatic gain.
+    /// - `width`: The width of the region the bars are drawn in.
+    /// - `elapsed`: Time since the previous frame, in seconds.
+    pub fn update(
+        &mut self,
+        fft_left: &[Complex32],
+        fft_right: &[Complex32],
+        level_scale: &LevelScale,
+        width: f64,
+        elapsed: f32,
+    ) {
+        if !self.enabled() {
+            return;
+        }
+        let fft_size = fft_left.len();
+        let (min_index, max_index) = frequency_indices(&self.settings.fft, fft_size);
+        let mapper = FrequencyMapper::from_settings(&self.settings, fft_size);
+        let half_width = width / 2.0;
+        let slots = mapper.bar_slots(min_index..max_index, half_width);
+        // A resize merges bins differently, so the curves move to the bars of the new width
+        if half_width != self.half_width {
+            for heights in self.average.iter_mut().chain(&mut self.max_hold) {
+                if heights.is_empty() {
+                    continue;
+                }
+                heights.resize(heights.len().max(slots.len()), 0.0);
+                mapper.remap_slot_heights(
+                    min_index..max_index,
+                    self.half_width,
+                    half_width,
+                    heights,
+                );
+                heights.truncate(slots.len());
+            }
+            self.half_width = half_width;
+        }
+
+        // The same heights the bars are smoothed toward: the loudest bin of every bar
+        let targets = [fft_left, fft_right].map(|fft| {
+            slots
+                .iter()
+                .map(|slot| {
+                    let magnitude = fft[slot.bins.clone()]
+                        .iter()
+                        .map(|value| value.norm())
+                        .fold(0.0, f32::max);
+                    level_scale.height(magnitude)
+                })
+                .collect::<Vec<f32>>()
+        });
+        self.accumulate(&targets, elapsed);
+    }
+
+    /// Moves the average toward the heights of a frame and raises the max-hold to them.
+    ///
+    /// Curves without heights yet, or with another number of bars, start at the heights.
+    fn accumulate(&mut self, targets: &[Vec<f32>; 2], elapsed: f32) {
+        let factor = smoothing_factor(elapsed, self.settings.curves.average_secs);
+        for (average, target) in self.average.iter_mut().zip(targets) {
+            if average.len() != target.len() {
+                average.clone_from(target);
+                continue;
+            }
+            for (value, &target) in average.iter_mut().zip(target) {
+                *value += (target - *value) * factor;
+            }
+        }
+        for (max_hold, target) in self.max_hold.iter_mut().zip(targets) {
+            if max_hold.len() != target.len() {
+                max_hold.clone_from(target);
+                continue;
+            }
+            for (value, &target) in max_hold.iter_mut().zip(target) {
+                *value = value.max(target);
+            }
+        }
+    }
+
+    /// Draws the enabled curves.
+    ///
+    /// # Arguments
+    /// - `cr`: The Cairo `Context` of the region the bars are drawn in.
+    /// - `width`: The width of the region.
+    /// - `height`: The height of the region.
+    pub fn draw(&self, cr: &Context, width: f64, height: f64) {
+        let curves = &self.settings.curves;
+        if curves.average {
+            let color = curves.average_color.to_rgba(curves.alpha);
+            self.draw_curve(cr, &self.average, color, width, height);
+        }
+        if curves.max_hold {
+            let color = curves.max_hold_color.to_rgba(curves.alpha);
+            self.draw_curve(cr, &self.max_hold, color, width, height);
+        }
+    }
+
+    /// Draws one curve as a line through the ends of the bars, from the left edge to the right.
+    fn draw_curve(
+        &self,
+        cr: &Context,
+        heights: &[Vec<f32>; 2],
+        (r, g, b, a): (f64, f64, f64, f64),
+        width: f64,
+        height: f64,
+    ) {
+        let fft_size = self.settings.fft.padded_size();
+        let (min_index, max_index) = frequency_indices(&self.settings.fft, fft_size);
+        let mapper = FrequencyMapper::from_settings(&self.settings, fft_size);
+        let half_width = width / 2.0;
+        let slots = mapper.bar_slots(min_index..max_index, half_width);
+        let direction = self.direction;
+
+        // A mirrored left channel runs from the center outward, so it is walked backward
+        let mut left: Vec<_> = slots
+            .iter()
+            .zip(&heights[0])
+            .map(|(slot, &h)| (slot.span(half_width, Channel::Left), h))
+            .collect();
+        if mapper.descending(Channel::Left) {
+            left.reverse();
+        }
+        let points = left.into_iter().chain(
+            slots
+                .iter()
+                .zip(&heights[1])
+                .map(|(slot, &h)| (slot.span(half_width, Channel::Right), h)),
+        );
+
+        let _ = cr.save();
+        cr.new_path();
+        for (span, bar_height) in points {
+            let (x, y, bar_width, bar_height) =
+                bar_rect(direction, f64::from(bar_height), span, height);
+            // Bars hanging down end at their bottom edge
+            let y = match direction {
+                BarDirection::Down => y + bar_height,
+                BarDirection::Up | BarDirection::Center => y,
+            };
+            cr.line_to(x + bar_width / 2.0, y);
+        }
+        cr.set_source_rgba(r, g, b, a);
+        cr.set_line_width(self.settings.curves.line_width);
+        let _ = cr.stroke();
+        let _ = cr.restore();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::CurvesSettings;
+
+    fn curves(average_secs: f32) -> SpectrumCurves {
+        SpectrumCurves::new(
+            Arc::new(Settings {
+                curves: CurvesSettings {
+                    average: true,
+                    average_secs,
+                    max_hold: true,
+                    ..CurvesSettings::default()
+                },
+                ..Settings::default()
+            }),
+            "frequency",
+        )
+    }
+
+    #[test]
+    fn the_average_converges_to_a_constant_input() {
+        let mut curves = curves(10.0);
+        curves.accumulate(&[vec![0.0; 4], vec![0.0; 4]], 1.0 / 60.0);
+
+        let targets = [vec![100.0, 50.0, 10.0, 0.0], vec![80.0; 4]];
+        let mut previous = curves.average[0][0];
+        // One time constant covers about 63% of the change
+        for _ in 0..600 {
+            curves.accumulate(&targets, 1.0 / 60.0);
+            assert!(curves.average[0][0] > previous);
+            previous = curves.average[0][0];
+        }
+        assert!((previous - 63.2).abs() < 0.5, "after 10 s: {}", previous);
+
+        for _ in 0..6000 {
+            curves.accumulate(&targets, 1.0 / 60.0);
+        }
+        for (average, target) in curves.average.iter().zip(&targets) {
+            for (value, target) in average.iter().zip(target) {
+                assert!((value - target).abs() < 0.01, "{} != {}", value, target);
+            }
+        }
+    }
+
+    #[test]
+    fn the_max_hold_only_rises_until_it_is_cleared() {
+        let mut curves = curves(10.0);
+        let mut previous = vec![0.0; 3];
+        for frame in 0..50 {
+            let heights: Vec<f32> = (0..3).map(|i| ((frame * 7 + i * 13) % 17) as f32).collect();
+            curves.accumulate(&[heights.clone(), heights.clone()], 1.0 / 60.0);
+            let max_hold = &curves.max_hold[0];
+            for ((value, previous), height) in max_hold.iter().zip(&previous).zip(&heights) {
+                assert!(value >= previous && value >= height);
+            }
+            previous.clone_from(max_hold);
+        }
+        assert_eq!(previous, [16.0; 3]);
+
+        curves.clear_max_hold();
+        curves.accumulate(&[vec![2.0; 3], vec![1.0; 3]], 1.0 / 60.0);
+        assert_eq!(curves.max_hold, [vec![2.0; 3], vec![1.0; 3]]);
+    }
+
+    #[test]
+    fn curves_follow_the_bar_heights() {
+        let settings = Settings::default();
+        let fft_size = settings.fft.size;
+        let level_scale = LevelScale::new(&settings.visualizer_settings("frequency"), fft_size);
+        let mut curves = curves(0.0);
+        let spectrum: Vec<_> = (0..fft_size)
+            .map(|i| Complex32::new((i % 5) as f32 * 40.0, 0.0))
+            .collect();
+
+        curves.update(&spectrum, &spectrum, &level_scale, 800.0, 1.0 / 60.0);
+        let mapper = FrequencyMapper::from_settings(&settings, fft_size);
+        let (min_index, max_index) = frequency_indices(&settings.fft, fft_size);
+        let slots = mapper.bar_slots(min_index..max_index, 400.0);
+        assert_eq!(curves.average[0].len(), slots.len());
+        let expected: Vec<f32> = slots
+            .iter()
+            .map(|slot| {
+                let magnitude = spectrum[slot.bins.clone()]
+                    .iter()
+                    .map(|value| value.norm())
+                    .fold(0.0, f32::max);
+                level_scale.height(magnitude)
+            })
+            .collect();
+        assert_eq!(curves.average[1], expected);
+        assert_eq!(curves.max_hold[0], expected);
+    }
+}
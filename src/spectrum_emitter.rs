@@ -0,0 +1,357 @@
+use crate::audio::{AudioData, AudioReader};
+use crate::cli::EmitFormat;
+use crate::dsp::SpectrumAnalyzer;
+use crate::fft_utils::{frequency_indices, to_mid_side, PaddedFft};
+use crate::hop_scheduler::HopScheduler;
+use crate::noise_profile::NoiseProfile;
+use crate::osc_output::{bin_heights, rms};
+use crate::settings::{ChannelMode, Settings};
+use rustfft::num_complex::Complex32;
+use std::io::{self, BufWriter, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Writes a line for every analysis window to a writer, as CSV or newline-delimited JSON.
+///
+/// Each line holds the time of the end of the window in milliseconds, the binned bar heights of
+/// both channels like the OSC output, and the RMS levels of the window.
+///
+/// # Fields
+/// - `settings`: Shared application settings with the FFT and `[output]` configuration.
+/// - `format`: Format of the written lines.
+/// - `writer`: Buffered destination of the lines, flushed once per batch of windows.
+/// - `hop_scheduler`: Finds the windows completed since the previous batch.
+/// - `fft`: Forward FFT of `fft.size` samples padded to `fft.padded_size()`.
+/// - `analyzer_left`: Smoothing and noise subtraction of the left channel.
+/// - `analyzer_right`: Smoothing and noise subtraction of the right channel.
+/// - `wrote_header`: Whether the CSV header line was written.
+pub struct SpectrumEmitter<W: Write> {
+    settings: Arc<Settings>,
+    format: EmitFormat,
+    writer: BufWriter<W>,
+    hop_scheduler: HopScheduler,
+    fft: PaddedFft,
+    analyzer_left: SpectrumAnalyzer,
+    analyzer_right: SpectrumAnalyzer,
+    wrote_header: bool,
+}
+
+impl<W: Write> SpectrumEmitter<W> {
+    /// Creates a new `SpectrumEmitter` instance, starting at the first captured sample.
+    ///
+    /// # Arguments
+    /// - `settings`: Shared application settings.
+    /// - `format`: Format of the written lines.
+    /// - `writer`: Destination of the lines, e.g. standard output.
+    pub fn new(settings: Arc<Settings>, format: EmitFormat, writer: W) -> Self {
+        let mut analyzer_left = SpectrumAnalyzer::new(&settings.visualizer);
+        let mut analyzer_right = SpectrumAnalyzer::new(&settings.visualizer);
+        if let Some(profile) = NoiseProfile::load(
+            &settings.calibration.profile_path,
+            settings.fft.padded_size(),
+        ) {
+            analyzer_left.set_noise_profile(Some(profile.left));
+            analyzer_right.set_noise_profile(Some(profile.right));
+        }
+
+        SpectrumEmitter {
+            format,
+            writer: BufWriter::new(writer),
+            hop_scheduler: HopScheduler::new(settings.fft.size, settings.fft.hop_length()),
+            fft: PaddedFft::new(&settings.fft, settings.audio.input_gain_db),
+            analyzer_left,
+            analyzer_right,
+            wrote_header: false,
+            settings,
+        }
+    }
+
+    /// Analyzes the windows completed since the previous call and writes a line for each.
+    ///
+    /// # Arguments
+    /// - `audio`: The latest captured audio.
+    ///
+    /// # Returns
+    /// - The number of lines written, or the error of the writer, e.g. `BrokenPipe` once the
+    ///   reading end of a pipe was closed.
+    pub fn process(&mut self, audio: &AudioData) -> io::Result<usize> {
+        if self.format == EmitFormat::Csv && !self.wrote_header {
+            writeln!(
+                self.writer,
+                "{}",
+                csv_header(self.settings.output.emit_bins)
+            )?;
+            self.wrote_header = true;
+        }
+
+        let mid_side;
+        let (left, right) = match self.settings.fft.channel_mode {
+            ChannelMode::Lr => (&audio.left_buffer[..], &audio.right_buffer[..]),
+            ChannelMode::Ms => {
+                mid_side = to_mid_side(&audio.left_buffer, &audio.right_buffer);
+                (&mid_side.0[..], &mid_side.1[..])
+            }
+        };
+        let size = self.settings.fft.size;
+        let starts = self
+            .hop_scheduler
+            .windows(audio.received_frames, left.len());
+        for &start in &starts {
+            let window = start..start + size;
+            let samples = (&left[window.clone()], &right[window]);
+            // Frames captured up to the end of the window
+            let end = audio
+                .received_frames
+                .wrapping_sub(left.len() - start - size);
+            let timestamp =
+                Duration::from_secs_f64(end as f64 / self.settings.fft.sample_rate as f64);
+
+            let mut fft_left = self.transform(samples.0);
+            let mut fft_right = self.transform(samples.1);
+            self.analyzer_left.process(&mut fft_left);
+            self.analyzer_right.process(&mut fft_right);
+            self.write_line(timestamp, samples, (&fft_left, &fft_right))?;
+        }
+        self.writer.flush()?;
+        Ok(starts.len())
+    }
+
+    /// Writes the lines of the audio read from `reader`, polling twice per hop, until `shutdown`
+    /// is set or the reading end of the output is closed.
+    ///
+    /// # Arguments
+    /// - `reader`: Reader of the analyzed audio.
+    /// - `shutdown`: Stops the emission once set.
+    ///
+    /// # Returns
+    /// - `Ok(())` once stopped by `shutdown` or a closed pipe, or any other error of the writer.
+    pub fn run(mut self, reader: &mut AudioReader, shutdown: &AtomicBool) -> io::Result<()> {
+        let poll_interval = Duration::from_secs_f32(
+            self.settings.fft.hop_length() as f32 / self.settings.fft.sample_rate / 2.0,
+        );
+        while !shutdown.load(Ordering::Relaxed) {
+            match self.process(reader.read()) {
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+                Err(e) => return Err(e),
+            }
+            thread::sleep(poll_interval);
+        }
+        Ok(())
+    }
+
+    /// Writes the line of one window.
+    ///
+    /// # Arguments
+    /// - `timestamp`: Time of the end of the window, relative to the start of the capture.
+    /// - `samples`: The left and right samples of the window.
+    /// - `spectrum`: The analyzed left and right spectra.
+    fn write_line(
+        &mut self,
+        timestamp: Duration,
+        samples: (&[f32], &[f32]),
+        spectrum: (&[Complex32], &[Complex32]),
+    ) -> io::Result<()> {
+        let bins = self.settings.output.emit_bins;
+        let gain = self.settings.visualizer.gain;
+        let range = frequency_indices(&self.settings.fft, spectrum.0.len());
+        let line = format_line(
+            self.format,
+            timestamp.as_millis() as u64,
+            &bin_heights(spectrum.0, range, bins, gain),
+            &bin_heights(spectrum.1, range, bins, gain),
+            (rms(samples.0), rms(samples.1)),
+        );
+        writeln!(self.writer, "{}", line)
+    }
+
+    /// Returns the FFT of `samples`.
+    fn transform(&mut self, samples: &[f32]) -> Vec<Complex32> {
+        self.fft.transform(samples)
+    }
+}
+
+/// Writes the lines of the audio read from `reader` to standard output, reporting write errors.
+///
+/// Returns once `shutdown` is set or standard output is closed, e.g. by the end of a pipeline.
+///
+/// # Arguments
+/// - `settings`: Shared application settings.
+/// - `format`: Format of the written lines.
+/// - `reader`: Reader of the analyzed audio.
+/// - `shutdown`: Stops the emission once set.
+pub fn emit_to_stdout(
+    settings: Arc<Settings>,
+    format: EmitFormat,
+    mut reader: AudioReader,
+    shutdown: &AtomicBool,
+) {
+    let emitter = SpectrumEmitter::new(settings, format, io::stdout().lock());
+    if let Err(e) = emitter.run(&mut reader, shutdown) {
+        eprintln!("Failed to write the spectrum: {}", e);
+    }
+}
+
+/// Returns the CSV header line naming the columns of `bins` bins per channel.
+fn csv_header(bins: usize) -> String {
+    let mut columns = vec!["t".to_string()];
+    for channel in ["left", "right"] {
+        columns.extend((0..bins).map(|bin| format!("{}_{}", channel, bin)));
+    }
+    columns.extend(["rms_left".to_string(), "rms_right".to_string()]);
+    columns.join(",")
+}
+
+/// Formats the line of one window, without the newline.
+///
+/// # Arguments
+/// - `format`: Format of the line.
+/// - `t`: Time of the end of the window, in milliseconds.
+/// - `left`: Bar heights of the left channel.
+/// - `right`: Bar heights of the right channel.
+/// - `rms`: RMS levels of the left and right channel.
+fn format_line(format: EmitFormat, t: u64, left: &[f32], right: &[f32], rms: (f32, f32)) -> String {
+    let join = |values: &[f32]| {
+        values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    match format {
+        EmitFormat::Csv => {
+            let values = left.iter().chain(right).chain([&rms.0, &rms.1]);
+            let mut fields = vec![t.to_string()];
+            fields.extend(values.map(|value| value.to_string()));
+            fields.join(",")
+        }
+        EmitFormat::Json => format!(
+            "{{\"t\":{},\"left\":[{}],\"right\":[{}],\"rms\":[{},{}]}}",
+            t,
+            join(left),
+            join(right),
+            rms.0,
+            rms.1
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::TAU;
+
+    /// Captures a 1 kHz tone of `frames` frames, pushed in chunks of `chunk` frames between
+    /// calls to `process`, and returns the written text and the number of lines reported.
+    fn emit(format: EmitFormat, frames: usize, chunk: usize) -> (String, usize) {
+        let settings = Arc::new(Settings::default());
+        let sample_rate = settings.fft.sample_rate;
+        let mut audio = AudioData::new(crate::audio::capture_len(&settings.fft));
+        let mut emitter = SpectrumEmitter::new(settings, format, Vec::new());
+
+        let tone: Vec<(f32, f32)> = (0..frames)
+            .map(|i| {
+                let sample = 0.5 * (TAU * 1000.0 * i as f32 / sample_rate).sin();
+                (sample, sample)
+            })
+            .collect();
+        let mut lines = 0;
+        for frames in tone.chunks(chunk) {
+            audio.push_frames(frames);
+            lines += emitter.process(&audio).unwrap();
+        }
+        let text = String::from_utf8(emitter.writer.into_inner().unwrap()).unwrap();
+        (text, lines)
+    }
+
+    #[test]
+    fn a_line_is_written_per_hop() {
+        let settings = Settings::default();
+        let hop = settings.fft.hop_length();
+        let (text, lines) = emit(EmitFormat::Csv, 40 * hop, 735);
+        assert_eq!(lines, 40);
+
+        let mut rows = text.lines();
+        let header = rows.next().unwrap();
+        assert!(header.starts_with("t,left_0,"));
+        assert!(header.ends_with(",right_31,rms_left,rms_right"));
+
+        let times: Vec<u64> = rows
+            .map(|row| {
+                let fields: Vec<&str> = row.split(',').collect();
+                assert_eq!(fields.len(), 1 + 2 * 32 + 2);
+                fields[0].parse().unwrap()
+            })
+            .collect();
+        assert_eq!(times.len(), 40);
+        // The windows end one hop apart
+        let hop_ms = hop as f32 * 1000.0 / settings.fft.sample_rate;
+        for pair in times.windows(2) {
+            assert!(
+                ((pair[1] - pair[0]) as f32 - hop_ms).abs() <= 1.0,
+                "{:?}",
+                pair
+            );
+        }
+    }
+
+    #[test]
+    fn json_lines_hold_heights_and_levels() {
+        let hop = Settings::default().fft.hop_length();
+        let (text, lines) = emit(EmitFormat::Json, 8 * hop, hop);
+        assert_eq!(lines, 8);
+        let last = text.lines().last().unwrap();
+        assert!(last.starts_with("{\"t\":"), "{}", last);
+        assert!(last.contains("\"left\":[") && last.contains("\"right\":["));
+        assert!(last.ends_with("]}"));
+    }
+
+    #[test]
+    fn lines_list_the_fields_in_order() {
+        let left = [0.5, 1.0];
+        let right = [0.0, 2.5];
+        assert_eq!(
+            format_line(EmitFormat::Csv, 12, &left, &right, (0.25, 0.125)),
+            "12,0.5,1,0,2.5,0.25,0.125"
+        );
+        assert_eq!(
+            format_line(EmitFormat::Json, 12, &left, &right, (0.25, 0.125)),
+            "{\"t\":12,\"left\":[0.5,1],\"right\":[0,2.5],\"rms\":[0.25,0.125]}"
+        );
+        assert_eq!(csv_header(1), "t,left_0,right_0,rms_left,rms_right");
+    }
+
+    /// Accepts `remaining` bytes, then fails like a pipe whose reader went away.
+    struct ClosingPipe {
+        remaining: usize,
+    }
+
+    impl Write for ClosingPipe {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.remaining == 0 {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            let written = buf.len().min(self.remaining);
+            self.remaining -= written;
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn a_closed_pipe_stops_the_emission() {
+        let settings = Arc::new(Settings::default());
+        let (mut sink, mut reader) = crate::audio::audio_channel(&settings.fft);
+        sink.push_frames(&vec![(0.5, 0.5); 8 * settings.fft.hop_length()]);
+
+        let emitter =
+            SpectrumEmitter::new(settings, EmitFormat::Csv, ClosingPipe { remaining: 100 });
+        let shutdown = AtomicBool::new(false);
+        assert!(emitter.run(&mut reader, &shutdown).is_ok());
+    }
+}
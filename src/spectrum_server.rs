@@ -0,0 +1,364 @@
+use crate::fft_utils::frequency_indices;
+use crate::osc_output::{bin_heights, rms};
+use crate::settings::Settings;
+use futures::{SinkExt, StreamExt};
+use rustfft::num_complex::Complex32;
+use serde::Serialize;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::Handle;
+use tokio::sync::{broadcast, watch};
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::{Message, Role};
+use tokio_tungstenite::WebSocketStream;
+
+/// Frames queued for a WebSocket client before it is dropped for not keeping up.
+const CLIENT_BACKLOG: usize = 8;
+/// Largest request head read from a client, in bytes.
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// One analyzed frame, as sent to clients.
+///
+/// # Fields
+/// - `t`: Time of the frame relative to the start of the analysis, in milliseconds.
+/// - `left`: Binned bar heights of the left channel, like the OSC output.
+/// - `right`: Binned bar heights of the right channel.
+/// - `rms`: RMS levels of the left and right samples.
+#[derive(Serialize)]
+struct SpectrumFrame {
+    t: u64,
+    left: Vec<f32>,
+    right: Vec<f32>,
+    rms: [f32; 2],
+}
+
+/// Publishes analyzed frames to the spectrum server on `127.0.0.1:output.http_port`.
+///
+/// The server pushes every published frame to its WebSocket clients as a JSON text message
+/// `{"t": ms, "left": [..], "right": [..], "rms": [l, r]}`, and answers `GET /spectrum` with the
+/// latest one. Publishing never waits for the clients: frames go into a broadcast channel, and
+/// clients that fall `CLIENT_BACKLOG` frames behind are disconnected.
+///
+/// # Fields
+/// - `settings`: Shared application settings.
+/// - `frames`: Frames for the WebSocket clients.
+/// - `latest`: The latest frame, for `GET /spectrum`.
+/// - `interval`: Minimum time between two frames.
+/// - `last_sent`: Timestamp of the last frame published.
+/// - `local_addr`: Address the server listens on.
+#[derive(Clone)]
+pub struct SpectrumPublisher {
+    settings: Arc<Settings>,
+    frames: broadcast::Sender<Arc<str>>,
+    latest: Arc<watch::Sender<Option<Arc<str>>>>,
+    interval: Duration,
+    last_sent: Option<Duration>,
+    local_addr: SocketAddr,
+}
+
+impl SpectrumPublisher {
+    /// Starts the spectrum server on `runtime` if `output.http_port` is set.
+    ///
+    /// # Arguments
+    /// - `settings`: Shared settings containing the `[output]` configuration.
+    /// - `runtime`: Runtime the server runs on.
+    ///
+    /// # Returns
+    /// - The publisher feeding the server, or `None` if no port is configured or it cannot be
+    ///   bound.
+    pub fn start(settings: Arc<Settings>, runtime: &Handle) -> Option<Self> {
+        let port = settings.output.http_port?;
+        let listener = match bind(port) {
+            Ok(listener) => listener,
+            Err(e) => {
+                eprintln!(
+                    "Failed to start the spectrum server on port {}: {}",
+                    port, e
+                );
+                return None;
+            }
+        };
+        let local_addr = listener.local_addr().ok()?;
+        status!(
+            "Serving the spectrum at ws://{} and http://{}/spectrum",
+            local_addr,
+            local_addr
+        );
+
+        let (frames, _) = broadcast::channel(CLIENT_BACKLOG);
+        let (latest, latest_receiver) = watch::channel(None);
+        runtime.spawn(serve(listener, frames.clone(), latest_receiver));
+        Some(SpectrumPublisher {
+            interval: Duration::from_secs_f32(1.0 / settings.output.http_rate_hz.max(0.1)),
+            settings,
+            frames,
+            latest: Arc::new(latest),
+            last_sent: None,
+            local_addr,
+        })
+    }
+
+    /// Returns the address the server listens on, e.g. to find the port of `http_port = 0`.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Publishes one analyzed frame, unless the previous frame was published less than
+    /// `interval` ago.
+    ///
+    /// # Arguments
+    /// - `timestamp`: Time of the frame, relative to the start of the analysis.
+    /// - `samples`: The left and right samples the frame was computed from.
+    /// - `spectrum`: The analyzed left and right spectra.
+    pub fn publish(
+        &mut self,
+        timestamp: Duration,
+        samples: (&[f32], &[f32]),
+        spectrum: (&[Complex32], &[Complex32]),
+    ) {
+        if let Some(last_sent) = self.last_sent {
+            if timestamp.saturating_sub(last_sent) < self.interval {
+                return;
+            }
+        }
+        self.last_sent = Some(timestamp);
+
+        let bins = self.settings.output.http_bins;
+        let gain = self.settings.visualizer.gain;
+        let range = frequency_indices(&self.settings.fft, spectrum.0.len());
+        let frame = SpectrumFrame {
+            t: timestamp.as_millis() as u64,
+            left: bin_heights(spectrum.0, range, bins, gain),
+            right: bin_heights(spectrum.1, range, bins, gain),
+            rms: [rms(samples.0), rms(samples.1)],
+        };
+        let frame: Arc<str> = match serde_json::to_string(&frame) {
+            Ok(json) => json.into(),
+            Err(e) => {
+                eprintln!("Failed to encode spectrum frame: {}", e);
+                return;
+            }
+        };
+
+        // Sending fails only while no WebSocket client is connected
+        let _ = self.frames.send(frame.clone());
+        self.latest.send_replace(Some(frame));
+    }
+}
+
+/// A request read from a client of the spectrum server.
+#[derive(Debug, PartialEq)]
+enum Request {
+    /// A WebSocket handshake with its `Sec-WebSocket-Key`.
+    WebSocket(String),
+    /// `GET /spectrum`.
+    Spectrum,
+    /// Any other request.
+    NotFound,
+}
+
+impl Request {
+    /// Parses the head of an HTTP request, up to the empty line ending its headers.
+    fn parse(head: &str) -> Self {
+        let mut lines = head.lines();
+        let mut request_line = lines.next().unwrap_or("").split_whitespace();
+        if request_line.next() != Some("GET") {
+            return Request::NotFound;
+        }
+        let path = request_line.next().unwrap_or("");
+
+        let mut upgrade = false;
+        let mut key = None;
+        for line in lines {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match name.trim().to_ascii_lowercase().as_str() {
+                "upgrade" => upgrade = value.eq_ignore_ascii_case("websocket"),
+                "sec-websocket-key" => key = Some(value.to_string()),
+                _ => {}
+            }
+        }
+
+        match (upgrade, key) {
+            (true, Some(key)) => Request::WebSocket(key),
+            _ if path == "/spectrum" => Request::Spectrum,
+            _ => Request::NotFound,
+        }
+    }
+}
+
+/// Binds a non-blocking listener to `port` on the loopback interface.
+fn bind(port: u16) -> io::Result<std::net::TcpListener> {
+    let listener = std::net::TcpListener::bind(("127.0.0.1", port))?;
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+/// Accepts clients until the runtime shuts down, serving each on a task of its own.
+async fn serve(
+    listener: std::net::TcpListener,
+    frames: broadcast::Sender<Arc<str>>,
+    latest: watch::Receiver<Option<Arc<str>>>,
+) {
+    let listener = match TcpListener::from_std(listener) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to start the spectrum server: {}", e);
+            return;
+        }
+    };
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(serve_client(stream, frames.clone(), latest.clone()));
+            }
+            Err(e) => eprintln!("Failed to accept a spectrum client: {}", e),
+        }
+    }
+}
+
+/// Answers one request, streaming frames for as long as a WebSocket client stays connected.
+///
+/// Errors mean the client went away, so they end the connection without being reported.
+async fn serve_client(
+    mut stream: TcpStream,
+    frames: broadcast::Sender<Arc<str>>,
+    latest: watch::Receiver<Option<Arc<str>>>,
+) {
+    let Ok(head) = read_request_head(&mut stream).await else {
+        return;
+    };
+    match Request::parse(&head) {
+        Request::WebSocket(key) => {
+            // Subscribing before the handshake completes, so no frame after it is missed
+            let frames = frames.subscribe();
+            let response = format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                derive_accept_key(key.as_bytes())
+            );
+            if stream.write_all(response.as_bytes()).await.is_ok() {
+                let websocket = WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+                stream_frames(websocket, frames).await;
+            }
+        }
+        Request::Spectrum => {
+            let frame = latest.borrow().clone();
+            let _ = match frame {
+                Some(frame) => respond(&mut stream, "200 OK", &frame).await,
+                None => respond(&mut stream, "503 Service Unavailable", "").await,
+            };
+        }
+        Request::NotFound => {
+            let _ = respond(&mut stream, "404 Not Found", "").await;
+        }
+    }
+}
+
+/// Reads the head of an HTTP request, up to and including the empty line ending its headers.
+async fn read_request_head(stream: &mut TcpStream) -> io::Result<String> {
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_REQUEST_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "request too large",
+            ));
+        }
+        // One byte at a time, so no WebSocket data following the head is consumed
+        head.push(stream.read_u8().await?);
+    }
+    String::from_utf8(head).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Writes a complete HTTP response with a JSON body and closes the connection.
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) -> io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Sends frames to a WebSocket client until it disconnects or falls behind.
+async fn stream_frames(
+    websocket: WebSocketStream<TcpStream>,
+    mut frames: broadcast::Receiver<Arc<str>>,
+) {
+    let (mut outgoing, mut incoming) = websocket.split();
+    loop {
+        tokio::select! {
+            frame = frames.recv() => match frame {
+                Ok(frame) => {
+                    if outgoing.send(Message::Text(frame.to_string())).await.is_err() {
+                        return;
+                    }
+                }
+                // A client that cannot keep up is dropped rather than queueing frames for it
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    let _ = outgoing.send(Message::Close(None)).await;
+                    return;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            // Reading answers pings; anything but a close is ignored
+            message = incoming.next() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn websocket_handshakes_are_told_from_spectrum_requests() {
+        let handshake = "GET / HTTP/1.1\r\nHost: 127.0.0.1:8787\r\nConnection: Upgrade\r\nUpgrade: WebSocket\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n";
+        assert_eq!(
+            Request::parse(handshake),
+            Request::WebSocket("dGhlIHNhbXBsZSBub25jZQ==".to_string())
+        );
+        assert_eq!(
+            Request::parse("GET /spectrum HTTP/1.1\r\nHost: localhost\r\n\r\n"),
+            Request::Spectrum
+        );
+        assert_eq!(
+            Request::parse("GET /other HTTP/1.1\r\n\r\n"),
+            Request::NotFound
+        );
+        assert_eq!(
+            Request::parse("POST /spectrum HTTP/1.1\r\n\r\n"),
+            Request::NotFound
+        );
+        // An upgrade without a key is no handshake
+        assert_eq!(
+            Request::parse("GET /spectrum HTTP/1.1\r\nUpgrade: websocket\r\n\r\n"),
+            Request::Spectrum
+        );
+    }
+
+    #[test]
+    fn frames_are_json_objects() {
+        let frame = SpectrumFrame {
+            t: 1500,
+            left: vec![0.0, 1.5],
+            right: vec![2.0, 0.25],
+            rms: [0.5, 0.125],
+        };
+        assert_eq!(
+            serde_json::to_string(&frame).unwrap(),
+            r#"{"t":1500,"left":[0.0,1.5],"right":[2.0,0.25],"rms":[0.5,0.125]}"#
+        );
+    }
+}
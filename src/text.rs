@@ -0,0 +1,213 @@
+use crate::settings::UiSettings;
+use gtk::cairo::Context;
+use gtk::pango;
+use gtk::pango::prelude::*;
+use gtk4 as gtk;
+
+/// The default `ui.font_size`, at which the sizes given to `TextStyle::ui` are drawn unscaled.
+const REFERENCE_FONT_SIZE: f64 = 12.0;
+
+thread_local! {
+    /// Layout measuring text without a Cairo context, shared by every `measure`.
+    static MEASURE_LAYOUT: pango::Layout =
+        pango::Layout::new(&pangocairo::FontMap::default().create_context());
+}
+
+/// Horizontal placement of text relative to its `x` coordinate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextAlign {
+    /// The text starts at `x`.
+    #[default]
+    Left,
+    /// The text is centered on `x`.
+    Center,
+    /// The text ends at `x`.
+    Right,
+}
+
+/// How `draw_text` and `measure` lay out text.
+///
+/// # Fields
+/// - `font`: Pango font description without a size, such as `"Sans"`, `"Sans Bold"` or
+///   `"Noto Sans, Noto Sans CJK JP"`; characters missing from the font fall back to others.
+/// - `size`: Font size, in pixels.
+/// - `color`: RGBA color of the text.
+/// - `align`: Placement of the text relative to its `x` coordinate.
+/// - `max_width`: Width at which the text is cut off with an ellipsis, or `None` for no limit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextStyle {
+    pub font: String,
+    pub size: f64,
+    pub color: (f64, f64, f64, f64),
+    pub align: TextAlign,
+    pub max_width: Option<f64>,
+}
+
+impl TextStyle {
+    /// Creates a left-aligned style drawing opaque white text without a width limit.
+    ///
+    /// # Arguments
+    /// - `font`: Pango font description without a size.
+    /// - `size`: Font size, in pixels.
+    pub fn new(font: &str, size: f64) -> Self {
+        TextStyle {
+            font: font.to_string(),
+            size,
+            color: (1.0, 1.0, 1.0, 1.0),
+            align: TextAlign::Left,
+            max_width: None,
+        }
+    }
+
+    /// Creates a style in the `ui.font` of the overlays.
+    ///
+    /// # Arguments
+    /// - `ui`: The `[ui]` settings.
+    /// - `size`: Font size at the default `ui.font_size`, in pixels; scaled along with it.
+    pub fn ui(ui: &UiSettings, size: f64) -> Self {
+        TextStyle::new(&ui.font, size * ui.font_size / REFERENCE_FONT_SIZE)
+    }
+
+    /// Creates a style in the `ui.monospace_font`, for text laid out in columns.
+    ///
+    /// # Arguments
+    /// - `ui`: The `[ui]` settings.
+    /// - `size`: Font size at the default `ui.font_size`, in pixels; scaled along with it.
+    pub fn monospace(ui: &UiSettings, size: f64) -> Self {
+        TextStyle::new(
+            &ui.monospace_font,
+            size * ui.font_size / REFERENCE_FONT_SIZE,
+        )
+    }
+
+    /// Returns the style with a bold font.
+    pub fn bold(mut self) -> Self {
+        self.font.push_str(" Bold");
+        self
+    }
+
+    /// Applies the font, width limit and text to a layout.
+    fn configure(&self, layout: &pango::Layout, text: &str) {
+        let mut font = pango::FontDescription::from_string(&self.font);
+        font.set_absolute_size(self.size * f64::from(pango::SCALE));
+        layout.set_font_description(Some(&font));
+        match self.max_width {
+            Some(max_width) => {
+                layout.set_width((max_width.max(0.0) * f64::from(pango::SCALE)) as i32);
+                layout.set_ellipsize(pango::EllipsizeMode::End);
+            }
+            None => {
+                layout.set_width(-1);
+                layout.set_ellipsize(pango::EllipsizeMode::None);
+            }
+        }
+        layout.set_text(text);
+    }
+}
+
+/// Returns the logical width and height of a layout, in pixels.
+fn layout_size(layout: &pango::Layout) -> (f64, f64) {
+    let (width, height) = layout.size();
+    let scale = f64::from(pango::SCALE);
+    (f64::from(width) / scale, f64::from(height) / scale)
+}
+
+/// Draws text with Pango, snapped to whole device pixels so it stays sharp.
+///
+/// # Arguments
+/// - `cr`: The Cairo `Context` used for drawing.
+/// - `text`: The text to draw.
+/// - `x`: Horizontal position of the text, as placed by `style.align`.
+/// - `y`: Top of the text.
+/// - `style`: Font, color, alignment and width limit of the text.
+pub fn draw_text(cr: &Context, text: &str, x: f64, y: f64, style: &TextStyle) {
+    let layout = pangocairo::functions::create_layout(cr);
+    style.configure(&layout, text);
+    let (width, _) = layout_size(&layout);
+    let left = match style.align {
+        TextAlign::Left => x,
+        TextAlign::Center => x - width / 2.0,
+        TextAlign::Right => x - width,
+    };
+
+    let (device_x, device_y) = cr.user_to_device(left, y);
+    let (left, top) = cr.device_to_user(device_x.round(), device_y.round());
+    let (r, g, b, a) = style.color;
+    cr.set_source_rgba(r, g, b, a);
+    cr.move_to(left, top);
+    pangocairo::functions::show_layout(cr, &layout);
+}
+
+/// Returns the width and height `draw_text` takes for text, in pixels, for placing it and the
+/// shapes behind it.
+///
+/// # Arguments
+/// - `text`: The text to measure.
+/// - `style`: Font and width limit of the text.
+pub fn measure(text: &str, style: &TextStyle) -> (f64, f64) {
+    MEASURE_LAYOUT.with(|layout| {
+        style.configure(layout, text);
+        layout_size(layout)
+    })
+}
+
+/// Adds a rectangle with rounded corners to the current path, for backing text.
+///
+/// # Arguments
+/// - `cr`: The Cairo `Context` used for drawing.
+/// - `(x, y, width, height)`: The rectangle.
+/// - `radius`: Radius of the corners, reduced to fit small rectangles.
+pub fn rounded_rectangle(cr: &Context, (x, y, width, height): (f64, f64, f64, f64), radius: f64) {
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    let radius = radius.min(width / 2.0).min(height / 2.0).max(0.0);
+    cr.new_sub_path();
+    cr.arc(x + width - radius, y + radius, radius, -FRAC_PI_2, 0.0);
+    cr.arc(
+        x + width - radius,
+        y + height - radius,
+        radius,
+        0.0,
+        FRAC_PI_2,
+    );
+    cr.arc(x + radius, y + height - radius, radius, FRAC_PI_2, PI);
+    cr.arc(x + radius, y + radius, radius, PI, PI + FRAC_PI_2);
+    cr.close_path();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measured_width_grows_with_ascii_text() {
+        let style = TextStyle::ui(&UiSettings::default(), 12.0);
+        let widths: Vec<f64> = (1..=32)
+            .map(|len| measure(&"x".repeat(len), &style).0)
+            .collect();
+        assert!(widths.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(widths[0] < widths[31]);
+        assert!(measure("x", &style).1 > 0.0);
+    }
+
+    #[test]
+    fn ellipsized_text_stays_within_its_width() {
+        let style = TextStyle {
+            max_width: Some(60.0),
+            ..TextStyle::ui(&UiSettings::default(), 12.0)
+        };
+        let (width, _) = measure(&"long title ".repeat(20), &style);
+        assert!(width <= 60.0);
+    }
+
+    #[test]
+    fn ui_sizes_scale_with_the_configured_font_size() {
+        let ui = UiSettings {
+            font_size: 18.0,
+            ..UiSettings::default()
+        };
+        assert_eq!(TextStyle::ui(&ui, 10.0).size, 15.0);
+        assert_eq!(TextStyle::monospace(&ui, 12.0).font, "Monospace");
+        assert_eq!(TextStyle::ui(&ui, 12.0).bold().font, "Sans Bold");
+    }
+}
@@ -0,0 +1,188 @@
+use gtk::cairo::{self, Context, Format, ImageSurface, Operator};
+use gtk4 as gtk;
+
+/// Phosphor-like trails behind the visualizer.
+///
+/// The visualizer draws into a surface kept between frames. Every frame first fades the surface
+/// toward transparent by the persistence factor, so earlier frames linger as fading trails below
+/// the new one.
+///
+/// # Fields
+/// - `persistence`: Fraction of the previous frames kept each frame; `0.0` disables the trails.
+/// - `scale`: Device pixels per logical pixel of the drawing area.
+/// - `surface`: The faded previous frames at device resolution, reallocated when the drawing area
+///   is resized or moves to a display with another scale.
+pub struct Trails {
+    persistence: f64,
+    scale: f64,
+    surface: Option<ImageSurface>,
+}
+
+impl Trails {
+    /// Creates a new `Trails` instance.
+    ///
+    /// # Arguments
+    /// - `persistence`: Fraction of the previous frames kept each frame, `effects.persistence`.
+    pub fn new(persistence: f64) -> Self {
+        Trails {
+            persistence: persistence.clamp(0.0, 1.0),
+            scale: 1.0,
+            surface: None,
+        }
+    }
+
+    /// Sets the device pixels per logical pixel, starting the trails over when it changes.
+    pub fn set_scale(&mut self, scale: f64) {
+        if scale != self.scale {
+            self.scale = scale;
+            self.surface = None;
+        }
+    }
+
+    /// Returns whether earlier frames linger, so every frame differs from the previous one.
+    pub fn is_enabled(&self) -> bool {
+        self.persistence > 0.0
+    }
+
+    /// Starts the trails over, releasing the trail surface until the next frame.
+    pub fn clear(&mut self) {
+        self.surface = None;
+    }
+
+    /// Draws a frame on top of the faded previous frames.
+    ///
+    /// # Arguments
+    /// - `cr`: The Cairo `Context` the frame and its trails are drawn to.
+    /// - `width`: The width of the drawing area.
+    /// - `height`: The height of the drawing area.
+    /// - `draw`: Function drawing the frame to the context it is given.
+    ///
+    /// Without persistence, or if the trail surface cannot be created, the frame is drawn to `cr`
+    /// directly.
+    pub fn draw<F: FnOnce(&Context)>(&mut self, cr: &Context, width: f64, height: f64, draw: F) {
+        let persistence = self.persistence;
+        if persistence <= 0.0 {
+            draw(cr);
+            return;
+        }
+        let surface = match self.surface_for(width, height) {
+            Ok(surface) => surface,
+            Err(e) => {
+                eprintln!(
+                    "Failed to create the trail surface, drawing without trails: {}",
+                    e
+                );
+                self.persistence = 0.0;
+                draw(cr);
+                return;
+            }
+        };
+
+        match Context::new(surface) {
+            Ok(trail_cr) => {
+                // Scale the color and opacity of every pixel by the persistence
+                trail_cr.set_operator(Operator::DestIn);
+                let _ = trail_cr.paint_with_alpha(persistence);
+                trail_cr.set_operator(Operator::Over);
+                draw(&trail_cr);
+            }
+            Err(_) => {
+                draw(cr);
+                return;
+            }
+        }
+
+        // Restoring the previous source releases the surface, keeping its data accessible
+        let _ = cr.save();
+        let _ = cr.set_source_surface(surface, 0.0, 0.0);
+        let _ = cr.paint();
+        let _ = cr.restore();
+    }
+
+    /// Returns the trail surface, replacing it with an empty one if the size changed.
+    ///
+    /// The surface has one pixel per device pixel, with a device scale so it is drawn to and
+    /// painted in logical pixels.
+    fn surface_for(&mut self, width: f64, height: f64) -> Result<&ImageSurface, cairo::Error> {
+        let scale = self.scale;
+        let (width, height) = (
+            (width * scale).ceil().max(1.0) as i32,
+            (height * scale).ceil().max(1.0) as i32,
+        );
+        let resized = !matches!(
+            &self.surface,
+            Some(surface) if surface.width() == width && surface.height() == height
+        );
+        if resized {
+            // Trails of the old size would be stretched or cut off, so they start over
+            let surface = ImageSurface::create(Format::ARgb32, width, height)?;
+            surface.set_device_scale(scale, scale);
+            self.surface = Some(surface);
+        }
+        Ok(self.surface.as_ref().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the alpha of the top-left pixel of the trail surface.
+    fn trail_alpha(trails: &mut Trails) -> u8 {
+        let surface = trails.surface.as_mut().unwrap();
+        surface.flush();
+        let data = surface.data().unwrap();
+        // ARGB32 pixels are native-endian 32-bit words with alpha in the high byte
+        let pixel = u32::from_ne_bytes([data[0], data[1], data[2], data[3]]);
+        (pixel >> 24) as u8
+    }
+
+    fn fill_white(cr: &Context) {
+        cr.set_source_rgba(1.0, 1.0, 1.0, 1.0);
+        cr.paint().unwrap();
+    }
+
+    #[test]
+    fn trails_fade_by_the_persistence() {
+        let target = ImageSurface::create(Format::ARgb32, 4, 4).unwrap();
+        let cr = Context::new(&target).unwrap();
+        let mut trails = Trails::new(0.5);
+
+        trails.draw(&cr, 4.0, 4.0, fill_white);
+        assert_eq!(trail_alpha(&mut trails), 255);
+        trails.draw(&cr, 4.0, 4.0, |_| {});
+        assert!((127..=128).contains(&trail_alpha(&mut trails)));
+        trails.draw(&cr, 4.0, 4.0, |_| {});
+        assert!((63..=64).contains(&trail_alpha(&mut trails)));
+    }
+
+    #[test]
+    fn resizing_starts_the_trails_over() {
+        let target = ImageSurface::create(Format::ARgb32, 8, 8).unwrap();
+        let cr = Context::new(&target).unwrap();
+        let mut trails = Trails::new(0.9);
+
+        trails.draw(&cr, 4.0, 4.0, fill_white);
+        trails.draw(&cr, 8.0, 2.5, |_| {});
+        let surface = trails.surface.as_ref().unwrap();
+        assert_eq!((surface.width(), surface.height()), (8, 3));
+        assert_eq!(trail_alpha(&mut trails), 0);
+    }
+
+    #[test]
+    fn trails_use_device_pixels() {
+        let target = ImageSurface::create(Format::ARgb32, 8, 8).unwrap();
+        let cr = Context::new(&target).unwrap();
+        let mut trails = Trails::new(0.9);
+
+        trails.set_scale(2.0);
+        trails.draw(&cr, 4.0, 2.5, fill_white);
+        let surface = trails.surface.as_ref().unwrap();
+        assert_eq!((surface.width(), surface.height()), (8, 5));
+        assert_eq!(surface.device_scale(), (2.0, 2.0));
+
+        // Moving to a display with another scale starts the trails over
+        trails.set_scale(1.0);
+        assert!(trails.surface.is_none());
+    }
+}
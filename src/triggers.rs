@@ -0,0 +1,357 @@
+use crate::fft_utils::band_energy;
+use crate::settings::{Settings, TriggerAction, TriggerSettings};
+use rustfft::num_complex::Complex32;
+use std::io::{self, Write};
+use std::process::{Child, Command};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread;
+use std::time::Duration;
+
+/// Number of trigger events that may wait for the worker thread before new ones are dropped.
+const EVENT_QUEUE_LEN: usize = 64;
+/// Level reported for a band without energy, in dBFS.
+const SILENCE_DB: f32 = -120.0;
+
+/// Where a trigger is in its cycle.
+///
+/// - `Below`: The level is below the threshold and the trigger is off.
+/// - `Attack`: The level rose above the threshold at the given time; the trigger switches on
+///   once it stays there for `attack_ms`.
+/// - `Above`: The level is above the threshold and the trigger is on.
+/// - `Hold`: The level fell below the threshold at the given time; the trigger stays on for
+///   `release_ms` unless the level rises again.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TriggerState {
+    Below,
+    Attack(Duration),
+    Above,
+    Hold(Duration),
+}
+
+/// A trigger switching on or off.
+///
+/// # Fields
+/// - `index`: Index of the trigger among the `[[trigger]]` tables.
+/// - `on`: Whether the trigger switched on rather than off.
+/// - `level_db`: Level of the band when the trigger switched, in dBFS.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TriggerEvent {
+    pub index: usize,
+    pub on: bool,
+    pub level_db: f32,
+}
+
+/// One configured trigger and its state.
+///
+/// # Fields
+/// - `settings`: The `[[trigger]]` table of the trigger.
+/// - `state`: Where the trigger is in its cycle.
+pub struct Trigger {
+    settings: TriggerSettings,
+    state: TriggerState,
+}
+
+impl Trigger {
+    /// Creates a new `Trigger`, starting off.
+    pub fn new(settings: TriggerSettings) -> Self {
+        Trigger {
+            settings,
+            state: TriggerState::Below,
+        }
+    }
+
+    /// Returns where the trigger is in its cycle.
+    pub fn state(&self) -> TriggerState {
+        self.state
+    }
+
+    /// Advances the trigger by one analyzed frame.
+    ///
+    /// # Arguments
+    /// - `level_db`: Level of the band in this frame, in dBFS.
+    /// - `timestamp`: Time of the frame, relative to the start of the analysis.
+    ///
+    /// # Returns
+    /// - `Some(true)` when the trigger switched on, `Some(false)` when it switched off, or `None`
+    ///   when it stayed as it was.
+    pub fn update(&mut self, level_db: f32, timestamp: Duration) -> Option<bool> {
+        let above = level_db > self.settings.threshold_db;
+        let attack = Duration::from_secs_f64(f64::from(self.settings.attack_ms.max(0.0)) / 1000.0);
+        let release =
+            Duration::from_secs_f64(f64::from(self.settings.release_ms.max(0.0)) / 1000.0);
+        let held = |since: Duration, hold: Duration| timestamp.saturating_sub(since) >= hold;
+
+        let (state, switched) = match (self.state, above) {
+            (TriggerState::Below, false) => (TriggerState::Below, None),
+            (TriggerState::Below, true) if attack.is_zero() => (TriggerState::Above, Some(true)),
+            (TriggerState::Below, true) => (TriggerState::Attack(timestamp), None),
+            (TriggerState::Attack(_), false) => (TriggerState::Below, None),
+            (TriggerState::Attack(since), true) if held(since, attack) => {
+                (TriggerState::Above, Some(true))
+            }
+            (TriggerState::Attack(since), true) => (TriggerState::Attack(since), None),
+            (TriggerState::Above | TriggerState::Hold(_), true) => (TriggerState::Above, None),
+            (TriggerState::Above, false) if release.is_zero() => (TriggerState::Below, Some(false)),
+            (TriggerState::Above, false) => (TriggerState::Hold(timestamp), None),
+            (TriggerState::Hold(since), false) if held(since, release) => {
+                (TriggerState::Below, Some(false))
+            }
+            (TriggerState::Hold(since), false) => (TriggerState::Hold(since), None),
+        };
+        self.state = state;
+        switched
+    }
+}
+
+/// Runs the `[[trigger]]` tables over the analyzed frames.
+///
+/// The triggers are evaluated in the analysis; their actions run on a worker thread, so neither
+/// a slow command nor a blocked stdout delays the frames. Events arriving while the queue of the
+/// worker is full are dropped and counted.
+///
+/// # Fields
+/// - `triggers`: The configured triggers.
+/// - `sample_rate`: Sample rate of the analyzed audio, in Hz.
+/// - `zero_pad_factor`: The `fft.zero_pad_factor` the spectra are padded with.
+/// - `sender`: Queue of the events run by the worker thread.
+/// - `dropped`: Number of events dropped because the queue was full.
+pub struct TriggerEngine {
+    triggers: Vec<Trigger>,
+    sample_rate: f32,
+    zero_pad_factor: usize,
+    sender: SyncSender<TriggerEvent>,
+    dropped: usize,
+}
+
+impl TriggerEngine {
+    /// Creates a `TriggerEngine` for the `[[trigger]]` tables and starts its worker thread.
+    ///
+    /// # Returns
+    /// - The engine, or `None` if no trigger is configured or the worker cannot be started.
+    pub fn from_settings(settings: &Settings) -> Option<Self> {
+        if settings.triggers.is_empty() {
+            return None;
+        }
+        let (sender, receiver) = mpsc::sync_channel(EVENT_QUEUE_LEN);
+        let triggers = settings.triggers.clone();
+        // The worker ends once the engine, and with it the sender, is dropped
+        let worker = thread::Builder::new()
+            .name("triggers".to_string())
+            .spawn(move || run_actions(&triggers, receiver));
+        if let Err(e) = worker {
+            eprintln!("Failed to start the trigger worker: {}", e);
+            return None;
+        }
+
+        Some(TriggerEngine {
+            triggers: settings
+                .triggers
+                .iter()
+                .cloned()
+                .map(Trigger::new)
+                .collect(),
+            sample_rate: settings.fft.sample_rate,
+            zero_pad_factor: settings.fft.zero_pad_factor,
+            sender,
+            dropped: 0,
+        })
+    }
+
+    /// Returns whether a trigger waits for its attack or release time to pass, which takes
+    /// further frames even while the spectrum stays the same.
+    pub fn is_pending(&self) -> bool {
+        self.triggers.iter().any(|trigger| {
+            matches!(
+                trigger.state(),
+                TriggerState::Attack(_) | TriggerState::Hold(_)
+            )
+        })
+    }
+
+    /// Advances every trigger by one analyzed frame, queueing the actions of those that switched.
+    ///
+    /// # Arguments
+    /// - `timestamp`: Time of the frame, relative to the start of the analysis.
+    /// - `spectrum`: The analyzed left and right spectra, before smoothing.
+    pub fn process(&mut self, timestamp: Duration, spectrum: (&[Complex32], &[Complex32])) {
+        for (index, trigger) in self.triggers.iter_mut().enumerate() {
+            let (low, high) = (trigger.settings.low, trigger.settings.high);
+            let energy =
+                |spectrum| band_energy(spectrum, self.sample_rate, self.zero_pad_factor, low, high);
+            let level_db = to_db((energy(spectrum.0) + energy(spectrum.1)) / 2.0);
+            let Some(on) = trigger.update(level_db, timestamp) else {
+                continue;
+            };
+
+            let event = TriggerEvent {
+                index,
+                on,
+                level_db,
+            };
+            if let Err(TrySendError::Full(_)) = self.sender.try_send(event) {
+                self.dropped += 1;
+                if self.dropped.is_power_of_two() {
+                    eprintln!(
+                        "Trigger actions are too slow, dropped {} events.",
+                        self.dropped
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Converts a band amplitude to dBFS, floored at `SILENCE_DB`.
+fn to_db(amplitude: f32) -> f32 {
+    if amplitude > 0.0 {
+        (20.0 * amplitude.log10()).max(SILENCE_DB)
+    } else {
+        SILENCE_DB
+    }
+}
+
+/// Replaces `%STATE%` and `%LEVEL%` in the command of an `exec` trigger.
+///
+/// # Arguments
+/// - `command`: The configured command.
+/// - `event`: The event the command runs for.
+///
+/// # Returns
+/// - The command with `%STATE%` replaced by `on` or `off` and `%LEVEL%` by the level in dB with
+///   one decimal.
+pub fn substitute(command: &str, event: &TriggerEvent) -> String {
+    command
+        .replace("%STATE%", state_name(event.on))
+        .replace("%LEVEL%", &format!("{:.1}", event.level_db))
+}
+
+/// Returns the name of a trigger state in commands and printed lines.
+fn state_name(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// Runs the actions of the events sent by a `TriggerEngine`, until the engine is dropped.
+///
+/// # Arguments
+/// - `triggers`: The `[[trigger]]` tables the events refer to by index.
+/// - `receiver`: The events, in the order the triggers switched.
+fn run_actions(triggers: &[TriggerSettings], receiver: Receiver<TriggerEvent>) {
+    let mut children: Vec<Child> = Vec::new();
+    for event in receiver {
+        // Commands run concurrently; finished ones are reaped as further events arrive
+        children.retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+
+        let Some(trigger) = triggers.get(event.index) else {
+            continue;
+        };
+        match trigger.action {
+            TriggerAction::Print => {
+                let line = format!(
+                    "trigger {} {:.0}-{:.0} Hz {} {:.1} dB",
+                    event.index,
+                    trigger.low,
+                    trigger.high,
+                    state_name(event.on),
+                    event.level_db
+                );
+                let mut stdout = io::stdout().lock();
+                let _ = writeln!(stdout, "{}", line).and_then(|()| stdout.flush());
+            }
+            TriggerAction::Exec => {
+                let command = substitute(&trigger.command, &event);
+                match Command::new("sh").arg("-c").arg(&command).spawn() {
+                    Ok(child) => children.push(child),
+                    Err(e) => eprintln!("Failed to run trigger command {:?}: {}", command, e),
+                }
+            }
+        }
+    }
+    for mut child in children {
+        let _ = child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn triggers_debounce_with_their_hold_times() {
+        let mut trigger = Trigger::new(TriggerSettings {
+            threshold_db: -30.0,
+            attack_ms: 20.0,
+            release_ms: 100.0,
+            ..TriggerSettings::default()
+        });
+
+        // (time in ms, level in dB, switch, state after the frame)
+        let script = [
+            (0, -60.0, None, TriggerState::Below),
+            // A blip shorter than the attack time is ignored
+            (10, -20.0, None, TriggerState::Attack(millis(10))),
+            (20, -40.0, None, TriggerState::Below),
+            (30, -20.0, None, TriggerState::Attack(millis(30))),
+            (50, -10.0, Some(true), TriggerState::Above),
+            (60, -35.0, None, TriggerState::Hold(millis(60))),
+            // Rising again during the hold keeps the trigger on without a new event
+            (100, -25.0, None, TriggerState::Above),
+            (110, -45.0, None, TriggerState::Hold(millis(110))),
+            (200, -50.0, None, TriggerState::Hold(millis(110))),
+            (210, -50.0, Some(false), TriggerState::Below),
+            (220, -50.0, None, TriggerState::Below),
+        ];
+        for (time, level, switch, state) in script {
+            assert_eq!(
+                trigger.update(level, millis(time)),
+                switch,
+                "at {} ms",
+                time
+            );
+            assert_eq!(trigger.state(), state, "at {} ms", time);
+        }
+    }
+
+    #[test]
+    fn triggers_without_hold_times_switch_at_once() {
+        let mut trigger = Trigger::new(TriggerSettings {
+            threshold_db: -30.0,
+            attack_ms: 0.0,
+            release_ms: 0.0,
+            ..TriggerSettings::default()
+        });
+        assert_eq!(trigger.update(-20.0, millis(0)), Some(true));
+        assert_eq!(trigger.update(-20.0, millis(10)), None);
+        assert_eq!(trigger.update(-40.0, millis(20)), Some(false));
+        assert_eq!(trigger.state(), TriggerState::Below);
+    }
+
+    #[test]
+    fn commands_get_the_state_and_level() {
+        let event = TriggerEvent {
+            index: 0,
+            on: true,
+            level_db: -12.345,
+        };
+        assert_eq!(
+            substitute("lights %STATE% --level %LEVEL%", &event),
+            "lights on --level -12.3"
+        );
+        let off = TriggerEvent { on: false, ..event };
+        assert_eq!(substitute("echo %STATE%", &off), "echo off");
+    }
+
+    #[test]
+    fn band_levels_are_in_dbfs() {
+        assert_eq!(to_db(1.0), 0.0);
+        assert!((to_db(0.1) + 20.0).abs() < 1e-4);
+        assert_eq!(to_db(0.0), SILENCE_DB);
+    }
+}
@@ -0,0 +1,151 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+/// Bits of `Shared::back` holding the index of the back slot.
+const INDEX_MASK: u8 = 0b011;
+/// Bit of `Shared::back` set when the back slot holds a value the reader has not taken yet.
+const FRESH: u8 = 0b100;
+
+/// Three slots shared by one writer and one reader.
+///
+/// At any time the writer owns one slot, the reader owns another, and the third is the back
+/// slot, owned by neither. Publishing and reading swap a slot with the back slot, so neither side
+/// ever waits for the other.
+///
+/// # Fields
+/// - `slots`: The three values.
+/// - `back`: Index of the back slot, with `FRESH` set while it holds an unread value.
+struct Shared<T> {
+    slots: [UnsafeCell<T>; 3],
+    back: AtomicU8,
+}
+
+// Each slot is only accessed by the side owning it, and ownership moves through `back`
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+/// Writing side of a triple buffer; see `triple_buffer`.
+///
+/// # Fields
+/// - `shared`: The slots shared with the reader.
+/// - `index`: The slot owned by the writer.
+pub struct TripleBufferWriter<T> {
+    shared: Arc<Shared<T>>,
+    index: u8,
+}
+
+/// Reading side of a triple buffer; see `triple_buffer`.
+///
+/// # Fields
+/// - `shared`: The slots shared with the writer.
+/// - `index`: The slot owned by the reader.
+pub struct TripleBufferReader<T> {
+    shared: Arc<Shared<T>>,
+    index: u8,
+}
+
+/// Creates a wait-free triple buffer handing values from one thread to another.
+///
+/// The writer always has a free slot to write into and the reader always sees the most recently
+/// published value, so neither side blocks: values published faster than they are read are
+/// skipped, and a value is read again until a newer one is published.
+///
+/// # Arguments
+/// - `initial`: The value all three slots start with.
+///
+/// # Returns
+/// - The writing and reading sides.
+pub fn triple_buffer<T: Clone>(initial: T) -> (TripleBufferWriter<T>, TripleBufferReader<T>) {
+    let shared = Arc::new(Shared {
+        slots: [
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial),
+        ],
+        back: AtomicU8::new(2),
+    });
+    (
+        TripleBufferWriter {
+            shared: shared.clone(),
+            index: 0,
+        },
+        TripleBufferReader { shared, index: 1 },
+    )
+}
+
+impl<T> TripleBufferWriter<T> {
+    /// Writes the next value into the writer's slot and publishes it.
+    ///
+    /// # Arguments
+    /// - `write`: Function updating the slot in place. The slot holds an older value that was
+    ///   published before, so the function must overwrite everything it relies on.
+    pub fn publish<F: FnOnce(&mut T)>(&mut self, write: F) {
+        // The writer owns its slot until the swap below
+        write(unsafe { &mut *self.shared.slots[self.index as usize].get() });
+
+        // Release the written slot to the reader and acquire the slot the reader released
+        let back = self.shared.back.swap(self.index | FRESH, Ordering::AcqRel);
+        self.index = back & INDEX_MASK;
+    }
+}
+
+impl<T> TripleBufferReader<T> {
+    /// Returns the most recently published value.
+    pub fn read(&mut self) -> &T {
+        if self.shared.back.load(Ordering::Relaxed) & FRESH != 0 {
+            // Release the slot read before and acquire the published slot
+            let back = self.shared.back.swap(self.index, Ordering::AcqRel);
+            self.index = back & INDEX_MASK;
+        }
+        // The reader owns its slot until the next swap
+        unsafe { &*self.shared.slots[self.index as usize].get() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn reads_return_the_latest_published_value() {
+        let (mut writer, mut reader) = triple_buffer(0);
+        assert_eq!(*reader.read(), 0);
+
+        writer.publish(|value| *value = 1);
+        writer.publish(|value| *value = 2);
+        assert_eq!(*reader.read(), 2);
+        // Without a new value, the last one is read again
+        assert_eq!(*reader.read(), 2);
+
+        writer.publish(|value| *value = 3);
+        assert_eq!(*reader.read(), 3);
+    }
+
+    #[test]
+    fn concurrent_reads_are_never_torn() {
+        const LEN: usize = 1024;
+        const FRAMES: u64 = 200_000;
+
+        let (mut writer, mut reader) = triple_buffer(vec![0_u64; LEN]);
+        let writer_thread = thread::spawn(move || {
+            for frame in 1..=FRAMES {
+                writer.publish(|buffer| buffer.fill(frame));
+            }
+        });
+
+        let mut last_frame = 0;
+        while last_frame < FRAMES {
+            let buffer = reader.read();
+            let frame = buffer[0];
+            assert!(
+                buffer.iter().all(|&value| value == frame),
+                "torn read of frame {}",
+                frame
+            );
+            assert!(frame >= last_frame, "frame {} after {}", frame, last_frame);
+            last_frame = frame;
+        }
+        writer_thread.join().unwrap();
+    }
+}
@@ -0,0 +1,53 @@
+use crate::power_state::PowerState;
+use futures::StreamExt;
+use tokio::sync::watch;
+use zbus::{proxy, Connection};
+
+#[proxy(
+    interface = "org.freedesktop.UPower",
+    default_service = "org.freedesktop.UPower",
+    default_path = "/org/freedesktop/UPower"
+)]
+trait UPower {
+    #[zbus(property)]
+    fn on_battery(&self) -> zbus::Result<bool>;
+}
+
+/// Watches whether UPower reports the machine running on battery, publishing every change to
+/// `tx` and logging it with `battery_fps`, the frame rate drawn at on battery.
+///
+/// Runs until the system bus connection fails; the power state then stays as last published.
+pub async fn watch_power(tx: watch::Sender<PowerState>, battery_fps: f32) {
+    if let Err(e) = watch_bus(tx, battery_fps).await {
+        eprintln!("Failed to watch the power source: {}", e);
+    }
+}
+
+/// Connects to the system bus and follows the `OnBattery` property.
+async fn watch_bus(tx: watch::Sender<PowerState>, battery_fps: f32) -> zbus::Result<()> {
+    let connection = Connection::system().await?;
+    let upower = UPowerProxy::new(&connection).await?;
+
+    // Subscribe before reading so a change in between is not missed
+    let mut changes = upower.receive_on_battery_changed().await;
+    publish(&tx, upower.on_battery().await?, battery_fps);
+    while let Some(change) = changes.next().await {
+        if let Ok(on_battery) = change.get().await {
+            publish(&tx, on_battery, battery_fps);
+        }
+    }
+    Ok(())
+}
+
+/// Publishes the power state, logging it when it changed.
+fn publish(tx: &watch::Sender<PowerState>, on_battery: bool, battery_fps: f32) {
+    let state = PowerState::from_on_battery(on_battery);
+    tx.send_if_modified(|published| {
+        if *published == state {
+            return false;
+        }
+        *published = state;
+        eprintln!("{}", state.change_message(battery_fps));
+        true
+    });
+}
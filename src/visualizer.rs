@@ -1,6 +1,18 @@
+use crate::chromagram_visualizer::ChromagramVisualizer;
+use crate::color::Palette;
+use crate::difference_visualizer::DifferenceVisualizer;
+use crate::frequency_holographic_glow_visualizer::HolographicGlowVisualizer;
+use crate::frequency_range_visualizer::FrequencyRangeVisualizer;
+use crate::line_spectrum_visualizer::LineSpectrumVisualizer;
+use crate::octave_band_visualizer::OctaveBandVisualizer;
+use crate::radial_visualizer::RadialVisualizer;
+use crate::settings::Settings;
+use crate::smoothing_state::SmoothingState;
+use crate::waveform_spectrum_visualizer::WaveformSpectrumVisualizer;
 use gtk::cairo::Context;
 use gtk4 as gtk;
 use rustfft::num_complex::Complex32;
+use std::sync::Arc;
 
 /// A trait defining a generic interface for visualizers that can draw audio data
 /// on a graphical context using FFT (Fast Fourier Transform) data.
@@ -10,6 +22,17 @@ use rustfft::num_complex::Complex32;
 ///
 /// # Required Method
 /// - `draw`: Renders the visualizer using FFT data for both left and right audio channels.
+///
+/// # Provided Methods
+/// - `as_gl`: Returns the OpenGL port of the visualizer, if it has one.
+/// - `waveform_fraction`: Returns the part of the height given to a waveform above the
+///   visualizer, if it has one.
+/// - `center_line`: Returns whether the bars grow from a center line the grid emphasizes.
+/// - `channel_layout`: Returns where the channels are drawn, for placing the legend.
+/// - `needs_animation`: Returns whether the visualizer changes while the spectrum does not.
+/// - `resized`: Notifies the visualizer that its drawing area changed size.
+/// - `rescale_heights`: Adapts the previous heights to a new size of the drawing area.
+/// - `set_palette`: Replaces the palette the visualizer colors its bars with.
 pub trait Visualizer: Send + Sync {
     /// Draws the visualizer's output onto a given graphical context (`cr`) using FFT data.
     ///
@@ -19,16 +42,20 @@ pub trait Visualizer: Send + Sync {
     /// - `fft_left`: FFT data for the left audio channel, represented as a slice of complex values.
     /// - `fft_right`: FFT data for the right audio channel, represented as a slice of complex values.
     /// - `cr`: The Cairo drawing context used for rendering.
-    /// - `previous_heights_left`: A mutable vector storing the previous heights of bars (or other elements)
-    ///   for the left channel, used for smooth transitions or interpolation.
-    /// - `previous_heights_right`: A mutable vector storing the previous heights of bars for the right channel.
+    /// - `previous_heights_left`: The previous heights of bars (or other elements) for the left
+    ///   channel, used for smooth transitions or interpolation.
+    /// - `previous_heights_right`: The previous heights of bars for the right channel.
+    /// - `elapsed`: Time since the previous frame, in seconds.
     ///
     /// # Description
     /// Implementations of this function should use the FFT data (`fft_left` and `fft_right`)
     /// to create a visual representation of the audio spectrum. The `previous_heights_left`
     /// and `previous_heights_right` vectors allow the visualizer to retain state between
     /// frames, enabling smoother transitions by interpolating between previous and current
-    /// frame values.
+    /// frame values. Interpolating by `fft_utils::smoothing_factor(elapsed, ...)` rather than a
+    /// fixed fraction per frame keeps the transitions equally fast at any frame rate. The number
+    /// of bars changes at runtime, so implementations call `SmoothingState::ensure_len` with the
+    /// number of bars of the frame before indexing the heights.
     fn draw(
         &self,
         width: i32,
@@ -36,7 +63,319 @@ pub trait Visualizer: Send + Sync {
         fft_left: &[Complex32],
         fft_right: &[Complex32],
         cr: &Context,
-        previous_heights_left: &mut Vec<f32>,
-        previous_heights_right: &mut Vec<f32>,
+        previous_heights_left: &mut SmoothingState,
+        previous_heights_right: &mut SmoothingState,
+        elapsed: f32,
     );
+
+    /// Returns the visualizer as a `GlVisualizer` when it can be drawn by the `gl` renderer.
+    ///
+    /// Visualizers without an OpenGL port keep the default, and are drawn with Cairo by every
+    /// renderer.
+    fn as_gl(&self) -> Option<&dyn GlVisualizer> {
+        None
+    }
+
+    /// Returns the fraction of the height drawn as a scrolling waveform of the recent audio
+    /// above the visualizer, for visualizers combining the two.
+    ///
+    /// The renderer draws the waveform, and `draw` receives only the height below it.
+    /// Visualizers without a waveform keep the default.
+    fn waveform_fraction(&self) -> Option<f64> {
+        None
+    }
+
+    /// Returns whether the bars grow up and down from a line through the middle of the drawing
+    /// area, which the grid then draws stronger than its other horizontal lines.
+    ///
+    /// Visualizers growing from an edge keep the default.
+    fn center_line(&self) -> bool {
+        false
+    }
+
+    /// Returns where the left and right channels are drawn, which the legend labels.
+    ///
+    /// Visualizers drawing the left channel on the left half and the right channel on the
+    /// right half keep the default.
+    fn channel_layout(&self) -> ChannelLayout {
+        ChannelLayout::SideBySide
+    }
+
+    /// Returns whether the next frame would differ from the last one even without a new
+    /// spectrum, e.g. because the visualizer rotates or smooths state of its own.
+    ///
+    /// Frames are skipped while the spectrum and the previous heights stay the same, unless a
+    /// visualizer needs them. Visualizers whose only state between frames is the previous
+    /// heights keep the default.
+    fn needs_animation(&self) -> bool {
+        false
+    }
+
+    /// Called before the first frame and whenever the size of the drawing area changes, with
+    /// the width and height `draw` receives from then on.
+    ///
+    /// Visualizers keeping anything of the size between frames, such as cached surfaces,
+    /// replace it here. The default does nothing.
+    fn resized(&mut self, _width: i32, _height: i32) {}
+
+    /// Moves the previous heights of one channel from the layout at size `from` to the layout
+    /// at size `to`, so the bars keep their levels through a resize instead of growing from
+    /// zero.
+    ///
+    /// # Arguments
+    /// - `heights`: The previous heights of one channel, as passed to `draw`.
+    /// - `from`: The width and height the heights were drawn at.
+    /// - `to`: The width and height the next frame is drawn at.
+    ///
+    /// Heights are in pixels, independent of the height of the drawing area, so the default
+    /// keeps them for visualizers with one height per FFT bin. Visualizers whose bars depend on
+    /// the width move the heights to the bars of the new width, resizing the state to them.
+    fn rescale_heights(&self, _heights: &mut SmoothingState, _from: (i32, i32), _to: (i32, i32)) {}
+
+    /// Replaces the palette the visualizer colors its bars with, starting with the next frame;
+    /// called every frame while `visualizer.palette_cycle` fades between palettes.
+    ///
+    /// Visualizers looking their colors up in a `Palette` replace it. Visualizers with fixed
+    /// colors keep the default, which does nothing.
+    fn set_palette(&mut self, _palette: &Palette) {}
+}
+
+/// Where a visualizer draws the left and right channels.
+///
+/// - `SideBySide`: The left channel on the left half, the right channel on the right half.
+/// - `TopBottom`: The left channel on the upper half, the right channel on the lower half.
+/// - `Overlay`: Both channels across the whole drawing area.
+/// - `Combined`: The channels are combined into one display, with nothing to label.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelLayout {
+    SideBySide,
+    TopBottom,
+    Overlay,
+    Combined,
+}
+
+/// One axis-aligned bar, laid out in the pixel coordinates of the drawing area.
+///
+/// The layout matches the per-instance vertex attributes of the `gl` renderer, so a slice of
+/// instances is uploaded as is.
+///
+/// # Fields
+/// - `rect`: Left edge, top edge, width and height of the bar.
+/// - `color`: RGBA color of the bar, not premultiplied, each component in [0.0, 1.0].
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BarInstance {
+    pub rect: [f32; 4],
+    pub color: [f32; 4],
+}
+
+/// A visualizer drawing only axis-aligned bars, which the `gl` renderer draws as instanced quads.
+///
+/// Laying out the bars is separate from drawing them, so the Cairo and OpenGL renderers show the
+/// same bars from the same code.
+pub trait GlVisualizer {
+    /// Lays out the bars of one frame, advancing the smoothed heights like `Visualizer::draw`.
+    ///
+    /// # Arguments
+    /// - `width`: The width of the drawing area in pixels.
+    /// - `height`: The height of the drawing area in pixels.
+    /// - `fft_left`: FFT data for the left audio channel.
+    /// - `fft_right`: FFT data for the right audio channel.
+    /// - `previous_heights_left`: The previous heights of the left channel bars.
+    /// - `previous_heights_right`: The previous heights of the right channel bars.
+    /// - `elapsed`: Time since the previous frame, in seconds.
+    /// - `instances`: Receives the bars, in drawing order.
+    fn bar_instances(
+        &self,
+        width: i32,
+        height: i32,
+        fft_left: &[Complex32],
+        fft_right: &[Complex32],
+        previous_heights_left: &mut SmoothingState,
+        previous_heights_right: &mut SmoothingState,
+        elapsed: f32,
+        instances: &mut Vec<BarInstance>,
+    );
+}
+
+/// Function creating a visualizer from the application settings.
+pub type VisualizerConstructor = Box<dyn Fn(Arc<Settings>) -> Box<dyn Visualizer> + Send + Sync>;
+
+/// A set of visualizers that can be created by name.
+///
+/// Names are kept in registration order, which is also the order the visualizers are cycled
+/// through at runtime.
+///
+/// # Fields
+/// - `entries`: Registered names and their constructors.
+pub struct VisualizerRegistry {
+    entries: Vec<(String, VisualizerConstructor)>,
+}
+
+impl VisualizerRegistry {
+    /// Creates a registry containing only the built-in visualizers.
+    pub fn new() -> Self {
+        let mut registry = VisualizerRegistry {
+            entries: Vec::new(),
+        };
+
+        registry.register("frequency", |settings| {
+            Box::new(FrequencyRangeVisualizer::new(settings))
+        });
+        registry.register("holographic_glow", |settings| {
+            Box::new(HolographicGlowVisualizer::new(settings))
+        });
+        registry.register("radial", |settings| {
+            Box::new(RadialVisualizer::new(settings))
+        });
+        registry.register("line", |settings| {
+            Box::new(LineSpectrumVisualizer::new(settings))
+        });
+        registry.register("octave", |settings| {
+            Box::new(OctaveBandVisualizer::new(settings))
+        });
+        registry.register("chromagram", |settings| {
+            Box::new(ChromagramVisualizer::new(settings))
+        });
+        registry.register("waveform_spectrum", |settings| {
+            Box::new(WaveformSpectrumVisualizer::new(settings))
+        });
+        registry.register("difference", |settings| {
+            Box::new(DifferenceVisualizer::new(settings))
+        });
+
+        registry
+    }
+
+    /// Adds a visualizer under `name`, replacing any visualizer already registered under it.
+    ///
+    /// # Arguments
+    /// - `name`: The name used in `visualizer.kind`.
+    /// - `constructor`: Function creating the visualizer from the application settings.
+    pub fn register<F>(&mut self, name: &str, constructor: F)
+    where
+        F: Fn(Arc<Settings>) -> Box<dyn Visualizer> + Send + Sync + 'static,
+    {
+        self.register_boxed(name, Box::new(constructor));
+    }
+
+    /// Adds an already boxed constructor under `name`, replacing any existing entry.
+    pub fn register_boxed(&mut self, name: &str, constructor: VisualizerConstructor) {
+        match self.entries.iter_mut().find(|(entry, _)| entry == name) {
+            Some(entry) => entry.1 = constructor,
+            None => self.entries.push((name.to_string(), constructor)),
+        }
+    }
+
+    /// Returns the registered names in registration order.
+    pub fn names(&self) -> Vec<&str> {
+        self.entries.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Creates the visualizer registered under `name`.
+    ///
+    /// # Returns
+    /// - The visualizer, or an error listing the available names if `name` is not registered.
+    pub fn create(
+        &self,
+        name: &str,
+        settings: Arc<Settings>,
+    ) -> Result<Box<dyn Visualizer>, String> {
+        self.entries
+            .iter()
+            .find(|(entry, _)| entry == name)
+            .map(|(_, constructor)| constructor(settings))
+            .ok_or_else(|| {
+                format!(
+                    "Unknown visualizer \"{}\", available visualizers: {}",
+                    name,
+                    self.names().join(", ")
+                )
+            })
+    }
+
+    /// Returns the name following `name` in registration order, wrapping around at the end.
+    pub fn next_name(&self, name: &str) -> Option<&str> {
+        let names = self.names();
+        let next = match names.iter().position(|entry| *entry == name) {
+            Some(index) => (index + 1) % names.len(),
+            None => 0,
+        };
+        names.get(next).copied()
+    }
+}
+
+impl Default for VisualizerRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct DummyVisualizer;
+
+    impl Visualizer for DummyVisualizer {
+        fn draw(
+            &self,
+            _width: i32,
+            _height: i32,
+            _fft_left: &[Complex32],
+            _fft_right: &[Complex32],
+            _cr: &Context,
+            _previous_heights_left: &mut SmoothingState,
+            _previous_heights_right: &mut SmoothingState,
+            _elapsed: f32,
+        ) {
+        }
+    }
+
+    #[test]
+    fn registered_visualizers_are_created_by_name() {
+        let mut registry = VisualizerRegistry::new();
+        registry.register("dummy", |_| Box::new(DummyVisualizer));
+
+        assert_eq!(
+            registry.names(),
+            [
+                "frequency",
+                "holographic_glow",
+                "radial",
+                "line",
+                "octave",
+                "chromagram",
+                "waveform_spectrum",
+                "difference",
+                "dummy"
+            ]
+        );
+        assert!(registry
+            .create("dummy", Arc::new(Settings::default()))
+            .is_ok());
+    }
+
+    #[test]
+    fn unknown_names_list_the_available_visualizers() {
+        let registry = VisualizerRegistry::new();
+        let error = registry
+            .create("missing", Arc::new(Settings::default()))
+            .err()
+            .unwrap();
+
+        assert!(error.contains("missing"));
+        assert!(error.contains("frequency, holographic_glow, radial, line, octave, chromagram"));
+    }
+
+    #[test]
+    fn next_name_cycles_in_registration_order() {
+        let registry = VisualizerRegistry::new();
+
+        assert_eq!(registry.next_name("frequency"), Some("holographic_glow"));
+        assert_eq!(registry.next_name("chromagram"), Some("waveform_spectrum"));
+        assert_eq!(registry.next_name("waveform_spectrum"), Some("difference"));
+        assert_eq!(registry.next_name("difference"), Some("frequency"));
+        assert_eq!(registry.next_name("missing"), Some("frequency"));
+    }
 }
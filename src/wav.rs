@@ -0,0 +1,289 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// WAVE format tag for integer PCM samples.
+const WAVE_FORMAT_PCM: u16 = 1;
+/// WAVE format tag for IEEE floating point samples.
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+/// WAVE format tag of files whose actual format is given by a sub-format GUID.
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+/// Size of the RIFF header written before the sample data, in bytes.
+const WAV_HEADER_LEN: u32 = 44;
+
+/// Writer producing a 32-bit float WAV file.
+///
+/// The header is written with empty sizes when the writer is created and patched by `finalize`,
+/// so a recording is only a valid file once it has been finalized.
+///
+/// # Fields
+/// - `writer`: The destination of the file.
+/// - `channels`: Number of interleaved channels.
+/// - `samples_written`: Number of samples written so far, over all channels.
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    channels: u16,
+    samples_written: u64,
+}
+
+impl WavWriter<BufWriter<File>> {
+    /// Creates the file at `path` and writes the WAV header to it.
+    ///
+    /// # Arguments
+    /// - `path`: The file to create; an existing file is overwritten.
+    /// - `channels`: Number of interleaved channels.
+    /// - `sample_rate`: Sample rate of the recording, in Hz.
+    pub fn create(path: &Path, channels: u16, sample_rate: u32) -> io::Result<Self> {
+        WavWriter::new(BufWriter::new(File::create(path)?), channels, sample_rate)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    /// Creates a new `WavWriter` and writes the WAV header to `writer`.
+    ///
+    /// # Arguments
+    /// - `writer`: The destination of the file, positioned at its start.
+    /// - `channels`: Number of interleaved channels.
+    /// - `sample_rate`: Sample rate of the recording, in Hz.
+    pub fn new(mut writer: W, channels: u16, sample_rate: u32) -> io::Result<Self> {
+        let block_align = channels * 4;
+
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(WAV_HEADER_LEN - 8).to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        writer.write_all(&WAVE_FORMAT_IEEE_FLOAT.to_le_bytes())?;
+        writer.write_all(&channels.to_le_bytes())?;
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&32u16.to_le_bytes())?;
+        writer.write_all(b"data")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+
+        Ok(WavWriter {
+            writer,
+            channels,
+            samples_written: 0,
+        })
+    }
+
+    /// Appends interleaved samples to the file.
+    pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        for sample in samples {
+            self.writer.write_all(&sample.to_le_bytes())?;
+        }
+        self.samples_written += samples.len() as u64;
+        Ok(())
+    }
+
+    /// Writes the final chunk sizes into the header and flushes the file.
+    ///
+    /// # Returns
+    /// - The underlying writer.
+    pub fn finalize(mut self) -> io::Result<W> {
+        // Only whole frames count towards the data chunk, which RIFF limits to 4 GiB
+        let frames = self.samples_written / self.channels.max(1) as u64;
+        let data_len = (frames * self.channels as u64 * 4).min((u32::MAX - WAV_HEADER_LEN) as u64);
+        let data_len = data_len as u32;
+
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer
+            .write_all(&(WAV_HEADER_LEN - 8 + data_len).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(40))?;
+        self.writer.write_all(&data_len.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+/// Decoded contents of a WAV file.
+///
+/// # Fields
+/// - `channels`: Number of interleaved channels.
+/// - `sample_rate`: Sample rate, in Hz.
+/// - `samples`: Interleaved samples, scaled to the range [-1.0, 1.0].
+pub struct WavData {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub samples: Vec<f32>,
+}
+
+/// Reads and decodes the WAV file at `path`.
+pub fn read_wav_file(path: &Path) -> Result<WavData, Box<dyn std::error::Error>> {
+    Ok(read_wav(&std::fs::read(path)?)?)
+}
+
+/// Decodes a WAV file.
+///
+/// # Arguments
+/// - `bytes`: The contents of the file.
+///
+/// # Returns
+/// - The decoded file, or an error describing why it is not supported. 8, 16, 24 and 32-bit
+///   integer PCM and 32 and 64-bit float samples are supported.
+pub fn read_wav(bytes: &[u8]) -> Result<WavData, String> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err("not a RIFF WAVE file".to_string());
+    }
+
+    let mut format = None;
+    let mut data = None;
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let len = read_u32(bytes, offset + 4) as usize;
+        let body_start = offset + 8;
+        // Files written without finalizing may claim more data than they contain
+        let body = &bytes[body_start..(body_start + len).min(bytes.len())];
+
+        match id {
+            b"fmt " => format = Some(parse_format(body)?),
+            b"data" => data = Some(body),
+            _ => {}
+        }
+
+        // Chunks are padded to an even length
+        offset = body_start + len + len % 2;
+    }
+
+    let (tag, channels, sample_rate, bits) = format.ok_or("missing fmt chunk")?;
+    let data = data.ok_or("missing data chunk")?;
+    if channels == 0 {
+        return Err("file has no channels".to_string());
+    }
+
+    let samples = match (tag, bits) {
+        (WAVE_FORMAT_PCM, 8) => data.iter().map(|&s| (s as f32 - 128.0) / 128.0).collect(),
+        (WAVE_FORMAT_PCM, 16) => data
+            .chunks_exact(2)
+            .map(|s| i16::from_le_bytes([s[0], s[1]]) as f32 / 32_768.0)
+            .collect(),
+        (WAVE_FORMAT_PCM, 24) => data
+            .chunks_exact(3)
+            .map(|s| (i32::from_le_bytes([0, s[0], s[1], s[2]]) >> 8) as f32 / 8_388_608.0)
+            .collect(),
+        (WAVE_FORMAT_PCM, 32) => data
+            .chunks_exact(4)
+            .map(|s| i32::from_le_bytes([s[0], s[1], s[2], s[3]]) as f32 / 2_147_483_648.0)
+            .collect(),
+        (WAVE_FORMAT_IEEE_FLOAT, 32) => data
+            .chunks_exact(4)
+            .map(|s| f32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+            .collect(),
+        (WAVE_FORMAT_IEEE_FLOAT, 64) => data
+            .chunks_exact(8)
+            .map(|s| f64::from_le_bytes(s.try_into().unwrap()) as f32)
+            .collect(),
+        _ => {
+            return Err(format!(
+                "unsupported sample format {} with {} bits per sample",
+                tag, bits
+            ))
+        }
+    };
+
+    Ok(WavData {
+        channels,
+        sample_rate,
+        samples,
+    })
+}
+
+/// Parses a `fmt ` chunk into its format tag, channel count, sample rate and bits per sample.
+fn parse_format(body: &[u8]) -> Result<(u16, u16, u32, u16), String> {
+    if body.len() < 16 {
+        return Err("fmt chunk is too short".to_string());
+    }
+
+    let mut tag = read_u16(body, 0);
+    // The first two bytes of the sub-format GUID hold the actual format tag
+    if tag == WAVE_FORMAT_EXTENSIBLE && body.len() >= 26 {
+        tag = read_u16(body, 24);
+    }
+
+    Ok((
+        tag,
+        read_u16(body, 2),
+        read_u32(body, 4),
+        read_u16(body, 14),
+    ))
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn wav_writer_round_trips_samples() {
+        let samples: Vec<f32> = (0..1000).map(|n| (n as f32 * 0.01).sin()).collect();
+
+        let mut wav = WavWriter::new(Cursor::new(Vec::new()), 2, 48000).unwrap();
+        wav.write_samples(&samples[..300]).unwrap();
+        wav.write_samples(&samples[300..]).unwrap();
+        let bytes = wav.finalize().unwrap().into_inner();
+
+        assert_eq!(read_u32(&bytes, 4) as usize, bytes.len() - 8);
+        assert_eq!(read_u32(&bytes, 28), 48000 * 8);
+        let decoded = read_wav(&bytes).unwrap();
+        assert_eq!(decoded.channels, 2);
+        assert_eq!(decoded.sample_rate, 48000);
+        assert_eq!(decoded.samples, samples);
+    }
+
+    #[test]
+    fn empty_recordings_are_valid_files() {
+        let wav = WavWriter::new(Cursor::new(Vec::new()), 2, 44100).unwrap();
+        let bytes = wav.finalize().unwrap().into_inner();
+
+        assert!(read_wav(&bytes).unwrap().samples.is_empty());
+    }
+
+    #[test]
+    fn reads_integer_pcm() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(4 + 24 + 8 + 6u32).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        for value in [WAVE_FORMAT_PCM, 1] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&22050u32.to_le_bytes());
+        bytes.extend_from_slice(&44100u32.to_le_bytes());
+        for value in [2u16, 16] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&6u32.to_le_bytes());
+        for value in [0i16, 16_384, -32_768] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let decoded = read_wav(&bytes).unwrap();
+        assert_eq!((decoded.channels, decoded.sample_rate), (1, 22050));
+        assert_eq!(decoded.samples, [0.0, 0.5, -1.0]);
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_wav() {
+        assert!(read_wav(b"not a wav file").is_err());
+    }
+}
@@ -0,0 +1,217 @@
+use gtk::cairo::Context;
+use gtk4 as gtk;
+
+/// Number of samples reduced to one minimum and maximum of the history.
+const BLOCK_SIZE: usize = 64;
+/// Color of the waveform.
+const WAVEFORM_COLOR: (f64, f64, f64, f64) = (0.55, 0.85, 1.0, 0.85);
+/// Opacity of the line marking zero amplitude.
+const ZERO_LINE_ALPHA: f64 = 0.25;
+
+/// The recent audio reduced to the minimum and maximum of every `BLOCK_SIZE` samples, a few
+/// seconds long, for drawing a scrolling waveform.
+///
+/// # Fields
+/// - `blocks`: Ring buffer of the minimum and maximum of the latest complete blocks; block `n`,
+///   counted from the first sample, is stored at `n % blocks.len()`.
+/// - `total_blocks`: Number of complete blocks pushed so far.
+/// - `current`: Minimum and maximum of the block being filled.
+/// - `current_len`: Number of samples in the block being filled.
+pub struct WaveformHistory {
+    blocks: Vec<(f32, f32)>,
+    total_blocks: u64,
+    current: (f32, f32),
+    current_len: usize,
+}
+
+/// The history reduced to one minimum and maximum per pixel column.
+///
+/// # Fields
+/// - `columns`: Minimum and maximum of each column, oldest first; `(0.0, 0.0)` before the
+///   first audio.
+/// - `offset`: Part of a column not filled yet by the newest column, from `0.0` to `1.0`.
+///   Drawing the columns this far to the right scrolls the waveform smoothly instead of by
+///   whole columns.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WaveformColumns {
+    pub columns: Vec<(f32, f32)>,
+    pub offset: f64,
+}
+
+impl WaveformHistory {
+    /// Creates a new `WaveformHistory` instance without any audio.
+    ///
+    /// # Arguments
+    /// - `sample_rate`: The sample rate of the pushed samples, in Hz.
+    /// - `seconds`: Length of the history, in seconds.
+    pub fn new(sample_rate: f32, seconds: f32) -> Self {
+        let len = ((sample_rate * seconds) as usize / BLOCK_SIZE).max(1);
+        WaveformHistory {
+            blocks: vec![(0.0, 0.0); len],
+            total_blocks: 0,
+            current: (f32::INFINITY, f32::NEG_INFINITY),
+            current_len: 0,
+        }
+    }
+
+    /// Appends samples, replacing the oldest ones once the history is full.
+    pub fn push(&mut self, samples: impl IntoIterator<Item = f32>) {
+        for sample in samples {
+            self.current = (self.current.0.min(sample), self.current.1.max(sample));
+            self.current_len += 1;
+            if self.current_len == BLOCK_SIZE {
+                let index = (self.total_blocks % self.blocks.len() as u64) as usize;
+                self.blocks[index] = self.current;
+                self.total_blocks += 1;
+                self.current = (f32::INFINITY, f32::NEG_INFINITY);
+                self.current_len = 0;
+            }
+        }
+    }
+
+    /// Reduces the whole history to `n_columns` columns.
+    ///
+    /// Columns start at whole multiples of their length counted from the first sample, so a
+    /// column keeps its blocks while it scrolls and only the newest one grows.
+    pub fn column_min_max(&self, n_columns: usize) -> WaveformColumns {
+        let n_columns = n_columns.max(1);
+        let len = self.blocks.len() as u64;
+        let blocks_per_column = len as f64 / n_columns as f64;
+        let column_start = |column: u64| (column as f64 * blocks_per_column).floor() as u64;
+        let end_column = (self.total_blocks as f64 / blocks_per_column).ceil() as u64;
+        let oldest = self.total_blocks.saturating_sub(len);
+
+        let columns = (0..n_columns as u64)
+            .map(|i| {
+                let Some(column) = (end_column + i).checked_sub(n_columns as u64) else {
+                    return (0.0, 0.0);
+                };
+                // Columns narrower than a block show the block they start in
+                let first = column_start(column).max(oldest);
+                let last = column_start(column + 1)
+                    .max(first + 1)
+                    .min(self.total_blocks);
+                (first..last)
+                    .map(|block| self.blocks[(block % len) as usize])
+                    .reduce(|(min, max), block| (min.min(block.0), max.max(block.1)))
+                    .unwrap_or((0.0, 0.0))
+            })
+            .collect();
+
+        let offset = match end_column.checked_sub(1) {
+            Some(newest) => {
+                let newest_len = column_start(newest + 1) - column_start(newest);
+                let filled = self.total_blocks - column_start(newest);
+                1.0 - filled as f64 / newest_len.max(1) as f64
+            }
+            None => 0.0,
+        };
+        WaveformColumns { columns, offset }
+    }
+}
+
+/// Draws the history as a band between the minimum and maximum of each pixel column, the newest
+/// audio at the right edge, around a line marking zero amplitude.
+///
+/// # Arguments
+/// - `cr`: The Cairo `Context` used for drawing.
+/// - `width`: The width of the waveform in pixels.
+/// - `height`: The height of the waveform in pixels.
+/// - `history`: The recent audio.
+pub fn draw_waveform(cr: &Context, width: i32, height: i32, history: &WaveformHistory) {
+    let center = f64::from(height) / 2.0;
+    let (r, g, b, a) = WAVEFORM_COLOR;
+    cr.set_source_rgba(r, g, b, ZERO_LINE_ALPHA);
+    cr.rectangle(0.0, center.floor(), f64::from(width), 1.0);
+    let _ = cr.fill();
+
+    // One column more than fits, so the columns scrolling in and out cover both edges
+    let waveform = history.column_min_max(width.max(1) as usize + 1);
+    cr.set_source_rgba(r, g, b, a);
+    for (i, &(min, max)) in waveform.columns.iter().enumerate() {
+        let x = i as f64 - 1.0 + waveform.offset;
+        let top = center - f64::from(max.clamp(-1.0, 1.0)) * center;
+        let bottom = center - f64::from(min.clamp(-1.0, 1.0)) * center;
+        cr.rectangle(x, top, 1.0, (bottom - top).max(1.0));
+    }
+    let _ = cr.fill();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a block alternating between `level` and `-level`.
+    fn block(level: f32) -> Vec<f32> {
+        (0..BLOCK_SIZE)
+            .map(|i| if i % 2 == 0 { level } else { -level })
+            .collect()
+    }
+
+    /// History of 8 blocks.
+    fn history() -> WaveformHistory {
+        WaveformHistory::new(BLOCK_SIZE as f32 * 8.0, 1.0)
+    }
+
+    #[test]
+    fn columns_hold_the_extremes_of_their_blocks() {
+        let mut history = history();
+        assert_eq!(
+            history.column_min_max(4),
+            WaveformColumns {
+                columns: vec![(0.0, 0.0); 4],
+                offset: 0.0
+            }
+        );
+
+        for level in 1..=8 {
+            history.push(block(level as f32 / 10.0));
+        }
+        let waveform = history.column_min_max(4);
+        assert_eq!(
+            waveform.columns,
+            [(-0.2, 0.2), (-0.4, 0.4), (-0.6, 0.6), (-0.8, 0.8)]
+        );
+        assert_eq!(waveform.offset, 0.0);
+
+        // A block not completed yet is left out
+        history.push(block(1.0)[..BLOCK_SIZE - 1].iter().copied());
+        assert_eq!(history.column_min_max(4), waveform);
+    }
+
+    #[test]
+    fn the_newest_column_grows_and_scrolls_in_smoothly() {
+        let mut history = history();
+        for level in 1..=9 {
+            history.push(block(level as f32 / 10.0));
+        }
+
+        // The oldest block is gone, and the newest column holds one of its two blocks
+        let waveform = history.column_min_max(4);
+        assert_eq!(
+            waveform.columns,
+            [(-0.4, 0.4), (-0.6, 0.6), (-0.8, 0.8), (-0.9, 0.9)]
+        );
+        assert_eq!(waveform.offset, 0.5);
+
+        history.push(block(0.05));
+        let waveform = history.column_min_max(4);
+        assert_eq!(waveform.columns[3], (-0.9, 0.9));
+        assert_eq!(waveform.offset, 0.0);
+    }
+
+    #[test]
+    fn columns_narrower_than_a_block_repeat_it() {
+        let mut history = history();
+        for level in 1..=8 {
+            history.push(block(level as f32 / 10.0));
+        }
+
+        let waveform = history.column_min_max(16);
+        assert_eq!(waveform.columns.len(), 16);
+        assert_eq!(waveform.columns[0], (-0.1, 0.1));
+        assert_eq!(waveform.columns[1], (-0.1, 0.1));
+        assert_eq!(waveform.columns[15], (-0.8, 0.8));
+        assert_eq!(waveform.offset, 0.0);
+    }
+}
@@ -0,0 +1,71 @@
+use crate::color::Palette;
+use crate::frequency_range_visualizer::FrequencyRangeVisualizer;
+use crate::settings::Settings;
+use crate::smoothing_state::SmoothingState;
+use crate::visualizer::Visualizer;
+use gtk::cairo::Context;
+use gtk4 as gtk;
+use rustfft::num_complex::Complex32;
+use std::sync::Arc;
+
+/// A visualizer showing the frequency bars below a scrolling waveform of the last few seconds.
+///
+/// The renderer draws the waveform into the top `waveform.split` of the region; the bars fill
+/// the rest.
+pub struct WaveformSpectrumVisualizer {
+    bars: FrequencyRangeVisualizer,
+    split: f64,
+}
+
+impl WaveformSpectrumVisualizer {
+    /// Creates a new `WaveformSpectrumVisualizer` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - Shared application settings to configure visualizer parameters.
+    pub fn new(settings: Arc<Settings>) -> Self {
+        let split = f64::from(settings.waveform.split);
+        WaveformSpectrumVisualizer {
+            bars: FrequencyRangeVisualizer::new(settings),
+            split,
+        }
+    }
+}
+
+impl Visualizer for WaveformSpectrumVisualizer {
+    /// Draws the frequency bars below the waveform, like the `frequency` visualizer.
+    fn draw(
+        &self,
+        width: i32,
+        height: i32,
+        fft_left: &[Complex32],
+        fft_right: &[Complex32],
+        cr: &Context,
+        previous_heights_left: &mut SmoothingState,
+        previous_heights_right: &mut SmoothingState,
+        elapsed: f32,
+    ) {
+        self.bars.draw(
+            width,
+            height,
+            fft_left,
+            fft_right,
+            cr,
+            previous_heights_left,
+            previous_heights_right,
+            elapsed,
+        );
+    }
+
+    fn waveform_fraction(&self) -> Option<f64> {
+        Some(self.split)
+    }
+
+    fn rescale_heights(&self, heights: &mut SmoothingState, from: (i32, i32), to: (i32, i32)) {
+        self.bars.rescale_heights(heights, from, to);
+    }
+
+    fn set_palette(&mut self, palette: &Palette) {
+        self.bars.set_palette(palette);
+    }
+}
@@ -0,0 +1,369 @@
+//! End-to-end tests of the analysis pipeline: generated audio is delivered through an
+//! `AudioSource` to the capture ring buffer, transformed, smoothed and mapped to bar heights.
+//!
+//! Nothing is drawn, so the tests need neither an audio device nor a display.
+
+use sonic_spectra::settings::Settings;
+use sonic_spectra::visualizer::{BarInstance, VisualizerRegistry};
+use sonic_spectra::{
+    audio_channel, AudioSink, AudioSource, Channel, EmitFormat, FrameRenderer, FrequencyMapper,
+    SmoothingState, SpectrumEmitter,
+};
+use std::f32::consts::TAU;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+/// Size of the area the bars are laid out in, in pixels.
+const AREA: (i32, i32) = (800, 600);
+/// Number of frames analyzed before the heights are checked.
+const FRAMES: u32 = 30;
+/// Time between frames.
+const FRAME_INTERVAL: Duration = Duration::from_nanos(16_666_667);
+
+/// Audio source delivering a fixed recording at once, from the calling thread.
+struct SyntheticSource {
+    frames: Vec<(f32, f32)>,
+}
+
+impl AudioSource for SyntheticSource {
+    fn start(
+        self: Box<Self>,
+        mut sink: AudioSink,
+        _shutdown: Arc<AtomicBool>,
+    ) -> Result<(), String> {
+        // Chunks of about a video frame, as delivered by a capture callback
+        for chunk in self.frames.chunks(735) {
+            sink.push_frames(chunk);
+        }
+        Ok(())
+    }
+}
+
+/// Feeds `samples` of both channels through the pipeline and returns the bars of the frequency
+/// visualizer after `FRAMES` frames.
+fn bars_after_frames(settings: &Arc<Settings>, samples: Vec<f32>) -> Vec<BarInstance> {
+    let (sink, mut reader) = audio_channel(&settings.fft);
+    let source = Box::new(SyntheticSource {
+        frames: samples.iter().map(|&sample| (sample, sample)).collect(),
+    });
+    source
+        .start(sink, Arc::new(AtomicBool::new(false)))
+        .unwrap();
+
+    let mut renderer = FrameRenderer::new(
+        settings.clone(),
+        VisualizerRegistry::new(),
+        Vec::new(),
+        FRAME_INTERVAL,
+    );
+    let visualizer = VisualizerRegistry::new()
+        .create("frequency", settings.clone())
+        .unwrap();
+    let bars = visualizer
+        .as_gl()
+        .expect("the frequency visualizer lays out bars");
+
+    let mut heights_left = SmoothingState::default();
+    let mut heights_right = SmoothingState::default();
+    let mut instances = Vec::new();
+    for frame in 0..FRAMES {
+        let audio = reader.read();
+        let spectrum = renderer.analyze_capture(
+            &audio.left_buffer,
+            &audio.right_buffer,
+            audio.received_frames,
+            FRAME_INTERVAL * frame,
+        );
+        instances.clear();
+        bars.bar_instances(
+            AREA.0,
+            AREA.1,
+            &spectrum.left,
+            &spectrum.right,
+            &mut heights_left,
+            &mut heights_right,
+            FRAME_INTERVAL.as_secs_f32(),
+            &mut instances,
+        );
+    }
+    instances
+}
+
+/// Returns the bar of the right channel drawn at `frequency`.
+fn bar_at(settings: &Settings, bars: &[BarInstance], frequency: f32) -> BarInstance {
+    let mapper = FrequencyMapper::from_settings(settings, settings.fft.size);
+    let x = mapper.mirrored_x(
+        mapper.position(frequency).unwrap(),
+        AREA.0 as f64 / 2.0,
+        Channel::Right,
+    ) as f32;
+    *bars[bars.len() / 2..]
+        .iter()
+        .find(|bar| bar.rect[0] <= x && x <= bar.rect[0] + bar.rect[2])
+        .unwrap_or_else(|| panic!("no bar at {} Hz", frequency))
+}
+
+/// Returns `len` samples of pseudo-random white noise between -1.0 and 1.0.
+fn white_noise(len: usize) -> Vec<f32> {
+    let mut state = 0x2545_f491_u32;
+    (0..len)
+        .map(|_| {
+            // xorshift32
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as f32 / u32::MAX as f32 * 2.0 - 1.0
+        })
+        .collect()
+}
+
+/// Returns enough samples for every frame of `bars_after_frames`.
+fn recording_len(settings: &Settings) -> usize {
+    let seconds = FRAME_INTERVAL.as_secs_f32() * FRAMES as f32;
+    (seconds * settings.fft.sample_rate) as usize + settings.fft.size
+}
+
+#[test]
+fn a_sine_peaks_at_its_frequency() {
+    let settings = Arc::new(Settings::default());
+    let sample_rate = settings.fft.sample_rate;
+    let sine = (0..recording_len(&settings))
+        .map(|i| 0.5 * (TAU * 1000.0 * i as f32 / sample_rate).sin())
+        .collect();
+    let bars = bars_after_frames(&settings, sine);
+
+    let peak = bar_at(&settings, &bars, 1000.0);
+    let tallest = bars.iter().map(|bar| bar.rect[3]).fold(0.0, f32::max);
+    assert!(peak.rect[3] > 0.0);
+    assert_eq!(peak.rect[3], tallest);
+
+    // One decade of magnitude, or 20 dB, is `scale_factor` pixels tall
+    let decade = settings.visualizer.scale_factor;
+    for frequency in [500.0, 2000.0] {
+        let octave = bar_at(&settings, &bars, frequency);
+        assert!(
+            peak.rect[3] - octave.rect[3] >= decade,
+            "{} Hz is {} px below the peak",
+            frequency,
+            peak.rect[3] - octave.rect[3]
+        );
+    }
+}
+
+#[test]
+fn noise_stays_within_the_drawing_area() {
+    let settings = Arc::new(Settings::default());
+    let bars = bars_after_frames(&settings, white_noise(recording_len(&settings)));
+
+    assert!(!bars.is_empty());
+    for bar in &bars {
+        assert!(bar.rect.iter().all(|value| value.is_finite()), "{:?}", bar);
+        assert!(bar.color.iter().all(|value| value.is_finite()), "{:?}", bar);
+        let [x, y, width, height] = bar.rect;
+        assert!(0.0 <= height && height <= AREA.1 as f32, "{:?}", bar);
+        assert!(0.0 <= y && y + height <= AREA.1 as f32 + 1e-3, "{:?}", bar);
+        assert!(0.0 <= x && x + width <= AREA.0 as f32 + 1e-3, "{:?}", bar);
+    }
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn websocket_clients_receive_spectrum_frames() {
+    use futures::StreamExt;
+    use serde_json::Value;
+    use sonic_spectra::SpectrumPublisher;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use tokio_tungstenite::tungstenite::Message;
+
+    // Frames further apart than the rate limit, so every one is published
+    const SPACING: Duration = Duration::from_millis(40);
+    const BINS: usize = 16;
+
+    let mut settings = Settings::default();
+    settings.output.http_port = Some(0);
+    settings.output.http_bins = BINS;
+    let settings = Arc::new(settings);
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let publisher = SpectrumPublisher::start(settings.clone(), runtime.handle()).unwrap();
+    let address = publisher.local_addr();
+    let (mut client, _) = runtime
+        .block_on(tokio_tungstenite::connect_async(format!(
+            "ws://{}",
+            address
+        )))
+        .unwrap();
+
+    let sample_rate = settings.fft.sample_rate;
+    let (sink, mut reader) = audio_channel(&settings.fft);
+    let source = Box::new(SyntheticSource {
+        frames: (0..recording_len(&settings))
+            .map(|i| {
+                let sample = 0.5 * (TAU * 1000.0 * i as f32 / sample_rate).sin();
+                (sample, sample)
+            })
+            .collect(),
+    });
+    source
+        .start(sink, Arc::new(AtomicBool::new(false)))
+        .unwrap();
+    let mut renderer = FrameRenderer::new(
+        settings.clone(),
+        VisualizerRegistry::new(),
+        Vec::new(),
+        FRAME_INTERVAL,
+    );
+    renderer.set_spectrum_publisher(publisher);
+
+    let check_frame = |frame: &Value, t: u64| {
+        assert_eq!(frame["t"].as_u64(), Some(t), "{:?}", frame);
+        for channel in ["left", "right"] {
+            let heights = frame[channel].as_array().unwrap();
+            assert_eq!(heights.len(), BINS);
+            assert!(heights.iter().all(|height| height.as_f64().unwrap() >= 0.0));
+            assert!(heights.iter().any(|height| height.as_f64().unwrap() > 0.0));
+        }
+        // The RMS level of a sine is its amplitude over the square root of two
+        let rms = frame["rms"].as_array().unwrap();
+        assert_eq!(rms.len(), 2);
+        for level in rms {
+            assert!((level.as_f64().unwrap() - 0.5 / 2f64.sqrt()).abs() < 0.01);
+        }
+    };
+
+    for frame in 0..5 {
+        let audio = reader.read();
+        let timestamp = SPACING * frame;
+        renderer.analyze_capture(
+            &audio.left_buffer,
+            &audio.right_buffer,
+            audio.received_frames,
+            timestamp,
+        );
+        let message = runtime.block_on(client.next()).unwrap().unwrap();
+        let Message::Text(text) = message else {
+            panic!("expected a text frame, got {:?}", message);
+        };
+        let frame: Value = serde_json::from_str(&text).unwrap();
+        check_frame(&frame, timestamp.as_millis() as u64);
+    }
+
+    // The latest frame is also served on request
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream
+        .write_all(b"GET /spectrum HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.1 200 OK"), "{}", head);
+    let frame: Value = serde_json::from_str(body).unwrap();
+    check_frame(&frame, (SPACING * 4).as_millis() as u64);
+}
+
+/// Writer handing everything written to a channel, failing like a pipe once the receiver is gone.
+struct ChannelPipe(mpsc::Sender<Vec<u8>>);
+
+impl Write for ChannelPipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.0.send(buf.to_vec()) {
+            Ok(()) => Ok(buf.len()),
+            Err(_) => Err(io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn emitted_lines_follow_the_hop_rate_until_the_pipe_closes() {
+    const BINS: usize = 16;
+    const LINES: usize = 300;
+
+    let mut settings = Settings::default();
+    settings.output.emit_bins = BINS;
+    let settings = Arc::new(settings);
+    let sample_rate = settings.fft.sample_rate;
+    let hop = settings.fft.hop_length();
+
+    // A tone delivered in chunks at four times real time, for at most 20 s of audio
+    let (mut sink, mut reader) = audio_channel(&settings.fft);
+    let source_done = Arc::new(AtomicBool::new(false));
+    let source = {
+        let source_done = source_done.clone();
+        thread::spawn(move || {
+            let chunk = 256;
+            let interval = Duration::from_secs_f32(chunk as f32 / sample_rate / 4.0);
+            let mut position = 0;
+            while !source_done.load(Ordering::Relaxed) && position < 20 * sample_rate as usize {
+                let frames: Vec<(f32, f32)> = (position..position + chunk)
+                    .map(|i| {
+                        let sample = 0.5 * (TAU * 1000.0 * i as f32 / sample_rate).sin();
+                        (sample, sample)
+                    })
+                    .collect();
+                sink.push_frames(&frames);
+                position += chunk;
+                thread::sleep(interval);
+            }
+        })
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    let emitter = SpectrumEmitter::new(settings.clone(), EmitFormat::Csv, ChannelPipe(sender));
+    let emitter = thread::spawn(move || emitter.run(&mut reader, &AtomicBool::new(false)));
+
+    let mut text = String::new();
+    while text.lines().count() <= LINES {
+        let chunk = receiver
+            .recv_timeout(Duration::from_secs(10))
+            .expect("the emitter stalled");
+        text.push_str(std::str::from_utf8(&chunk).unwrap());
+    }
+    // Closing the pipe ends the emission without an error
+    drop(receiver);
+    emitter.join().unwrap().unwrap();
+    source_done.store(true, Ordering::Relaxed);
+    source.join().unwrap();
+
+    let mut lines = text.lines();
+    assert_eq!(lines.next().unwrap().split(',').count(), 1 + 2 * BINS + 2);
+    let hop_ms = hop as f32 * 1000.0 / sample_rate;
+    let mut last_t: Option<f32> = None;
+    for line in lines.take(LINES) {
+        let fields: Vec<f32> = line
+            .split(',')
+            .map(|field| field.parse().unwrap())
+            .collect();
+        assert_eq!(fields.len(), 1 + 2 * BINS + 2, "{}", line);
+        let (t, heights, rms) = (fields[0], &fields[1..=2 * BINS], &fields[2 * BINS + 1..]);
+
+        // One line per hop, possibly skipping windows that left the buffer on a slow machine
+        if let Some(last_t) = last_t {
+            let steps = (t - last_t) / hop_ms;
+            assert!(
+                steps > 0.5 && (steps - steps.round()).abs() < 0.2,
+                "{} after {}",
+                t,
+                last_t
+            );
+        }
+        last_t = Some(t);
+        assert!(heights.iter().all(|&height| height >= 0.0));
+        // Windows still holding the silence before the tone start at zero
+        if t >= 2.0 * settings.fft.size as f32 * 1000.0 / sample_rate {
+            assert!(heights.iter().any(|&height| height > 0.0), "{}", line);
+            for level in rms {
+                assert!((level - 0.5 / 2f32.sqrt()).abs() < 0.01, "{}", line);
+            }
+        }
+    }
+}